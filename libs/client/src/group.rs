@@ -79,6 +79,48 @@ impl KanidmClient {
         .await
     }
 
+    pub async fn group_account_policy_password_history_length_set(
+        &self,
+        id: &str,
+        length: u32,
+    ) -> Result<(), ClientError> {
+        self.perform_put_request(
+            &format!("/v1/group/{id}/_attr/auth_password_history_length"),
+            vec![length.to_string()],
+        )
+        .await
+    }
+
+    pub async fn group_account_policy_password_history_length_reset(
+        &self,
+        id: &str,
+    ) -> Result<(), ClientError> {
+        self.perform_delete_request(&format!(
+            "/v1/group/{id}/_attr/auth_password_history_length"
+        ))
+        .await
+    }
+
+    pub async fn group_account_policy_password_maximum_age_set(
+        &self,
+        id: &str,
+        age: u32,
+    ) -> Result<(), ClientError> {
+        self.perform_put_request(
+            &format!("/v1/group/{id}/_attr/auth_password_maximum_age"),
+            vec![age.to_string()],
+        )
+        .await
+    }
+
+    pub async fn group_account_policy_password_maximum_age_reset(
+        &self,
+        id: &str,
+    ) -> Result<(), ClientError> {
+        self.perform_delete_request(&format!("/v1/group/{id}/_attr/auth_password_maximum_age"))
+            .await
+    }
+
     pub async fn group_account_policy_privilege_expiry_set(
         &self,
         id: &str,
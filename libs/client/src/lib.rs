@@ -1571,6 +1571,16 @@ impl KanidmClient {
         Ok(Some(r.youare))
     }
 
+    pub async fn idm_self_oauth2_consent_list(&self) -> Result<Vec<Oauth2Consent>, ClientError> {
+        self.perform_get_request("/v1/self/_oauth2_consent").await
+    }
+
+    /// Revoke your consent to an OAuth2 client, ending all of the sessions it holds for you.
+    pub async fn idm_self_oauth2_consent_revoke(&self, id: &str) -> Result<(), ClientError> {
+        self.perform_delete_request(format!("/v1/self/_oauth2_consent/{id}").as_str())
+            .await
+    }
+
     // Raw DB actions
     pub async fn search(&self, filter: Filter) -> Result<Vec<Entry>, ClientError> {
         let sr = SearchRequest { filter };
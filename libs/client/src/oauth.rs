@@ -3,11 +3,17 @@ use kanidm_proto::attribute::Attribute;
 use kanidm_proto::constants::{
     ATTR_DISPLAYNAME, ATTR_KEY_ACTION_REVOKE, ATTR_KEY_ACTION_ROTATE, ATTR_NAME,
     ATTR_OAUTH2_ALLOW_INSECURE_CLIENT_DISABLE_PKCE, ATTR_OAUTH2_ALLOW_LOCALHOST_REDIRECT,
-    ATTR_OAUTH2_CONSENT_PROMPT_ENABLE, ATTR_OAUTH2_JWT_LEGACY_CRYPTO_ENABLE,
-    ATTR_OAUTH2_PREFER_SHORT_USERNAME, ATTR_OAUTH2_RS_BASIC_SECRET, ATTR_OAUTH2_RS_ORIGIN,
-    ATTR_OAUTH2_RS_ORIGIN_LANDING, ATTR_OAUTH2_STRICT_REDIRECT_URI,
+    ATTR_OAUTH2_CIBA_ENABLE, ATTR_OAUTH2_CONSENT_PROMPT_ENABLE,
+    ATTR_OAUTH2_JWT_LEGACY_CRYPTO_ENABLE, ATTR_OAUTH2_PAIRWISE_SUBJECT_ENABLE,
+    ATTR_OAUTH2_PREFER_SHORT_USERNAME, ATTR_OAUTH2_REQUIRE_DPOP, ATTR_OAUTH2_REQUIRE_PAR,
+    ATTR_OAUTH2_RS_AUTHORISATION_DETAILS_TYPE, ATTR_OAUTH2_RS_BACKCHANNEL_LOGOUT_URI,
+    ATTR_OAUTH2_RS_BASIC_SECRET, ATTR_OAUTH2_RS_ENCRYPTED_RESPONSE_ALG, ATTR_OAUTH2_RS_JWKS,
+    ATTR_OAUTH2_RS_ORIGIN, ATTR_OAUTH2_RS_ORIGIN_LANDING,
+    ATTR_OAUTH2_RS_TLS_CLIENT_AUTH_SUBJECT_DN, ATTR_OAUTH2_RS_TLS_CLIENT_CERTIFICATE,
+    ATTR_OAUTH2_STRICT_REDIRECT_URI,
 };
 use kanidm_proto::internal::{ImageValue, Oauth2ClaimMapJoin};
+use kanidm_proto::oauth2::IdTokenEncryptionAlg;
 use kanidm_proto::v1::Entry;
 use reqwest::multipart;
 use std::collections::BTreeMap;
@@ -261,6 +267,20 @@ impl KanidmClient {
             .map_err(|e| ClientError::JsonDecode(e, opid))
     }
 
+    /// Revoke the registration access token of a dynamically registered client, so that
+    /// it can no longer be read, updated or deleted through the registration endpoint.
+    pub async fn idm_oauth2_rs_revoke_registration_access_token(
+        &self,
+        id: &str,
+    ) -> Result<(), ClientError> {
+        self.perform_delete_request(&format!(
+            "/v1/oauth2/{}/_attr/{}",
+            id,
+            Attribute::OAuth2RsRegistrationTokenId.as_str()
+        ))
+        .await
+    }
+
     pub async fn idm_oauth2_rs_enable_pkce(&self, id: &str) -> Result<(), ClientError> {
         let mut update_oauth2_rs = Entry {
             attrs: BTreeMap::new(),
@@ -512,4 +532,260 @@ impl KanidmClient {
         self.perform_patch_request(format!("/v1/oauth2/{}", id).as_str(), update_oauth2_rs)
             .await
     }
+
+    pub async fn idm_oauth2_rs_set_backchannel_logout_uri(
+        &self,
+        id: &str,
+        uri: &Url,
+    ) -> Result<(), ClientError> {
+        let mut update_oauth2_rs = Entry {
+            attrs: BTreeMap::new(),
+        };
+        update_oauth2_rs.attrs.insert(
+            ATTR_OAUTH2_RS_BACKCHANNEL_LOGOUT_URI.to_string(),
+            vec![uri.to_string()],
+        );
+        self.perform_patch_request(format!("/v1/oauth2/{}", id).as_str(), update_oauth2_rs)
+            .await
+    }
+
+    pub async fn idm_oauth2_rs_remove_backchannel_logout_uri(
+        &self,
+        id: &str,
+    ) -> Result<(), ClientError> {
+        self.perform_delete_request(&format!(
+            "/v1/oauth2/{}/_attr/{}",
+            id,
+            Attribute::OAuth2RsBackchannelLogoutUri.as_str()
+        ))
+        .await
+    }
+
+    /// Set the JSON web key set whose keys this client may sign client assertions with.
+    pub async fn idm_oauth2_rs_set_jwks(&self, id: &str, jwks: &str) -> Result<(), ClientError> {
+        let mut update_oauth2_rs = Entry {
+            attrs: BTreeMap::new(),
+        };
+        update_oauth2_rs
+            .attrs
+            .insert(ATTR_OAUTH2_RS_JWKS.to_string(), vec![jwks.to_string()]);
+        self.perform_patch_request(format!("/v1/oauth2/{}", id).as_str(), update_oauth2_rs)
+            .await
+    }
+
+    pub async fn idm_oauth2_rs_remove_jwks(&self, id: &str) -> Result<(), ClientError> {
+        self.perform_delete_request(&format!(
+            "/v1/oauth2/{}/_attr/{}",
+            id,
+            Attribute::OAuth2RsJwks.as_str()
+        ))
+        .await
+    }
+
+    pub async fn idm_oauth2_rs_enable_require_par(&self, id: &str) -> Result<(), ClientError> {
+        let mut update_oauth2_rs = Entry {
+            attrs: BTreeMap::new(),
+        };
+        update_oauth2_rs.attrs.insert(
+            ATTR_OAUTH2_REQUIRE_PAR.to_string(),
+            vec!["true".to_string()],
+        );
+        self.perform_patch_request(format!("/v1/oauth2/{}", id).as_str(), update_oauth2_rs)
+            .await
+    }
+
+    pub async fn idm_oauth2_rs_disable_require_par(&self, id: &str) -> Result<(), ClientError> {
+        let mut update_oauth2_rs = Entry {
+            attrs: BTreeMap::new(),
+        };
+        update_oauth2_rs.attrs.insert(
+            ATTR_OAUTH2_REQUIRE_PAR.to_string(),
+            vec!["false".to_string()],
+        );
+        self.perform_patch_request(format!("/v1/oauth2/{}", id).as_str(), update_oauth2_rs)
+            .await
+    }
+
+    pub async fn idm_oauth2_rs_enable_require_dpop(&self, id: &str) -> Result<(), ClientError> {
+        let mut update_oauth2_rs = Entry {
+            attrs: BTreeMap::new(),
+        };
+        update_oauth2_rs.attrs.insert(
+            ATTR_OAUTH2_REQUIRE_DPOP.to_string(),
+            vec!["true".to_string()],
+        );
+        self.perform_patch_request(format!("/v1/oauth2/{}", id).as_str(), update_oauth2_rs)
+            .await
+    }
+
+    pub async fn idm_oauth2_rs_disable_require_dpop(&self, id: &str) -> Result<(), ClientError> {
+        let mut update_oauth2_rs = Entry {
+            attrs: BTreeMap::new(),
+        };
+        update_oauth2_rs.attrs.insert(
+            ATTR_OAUTH2_REQUIRE_DPOP.to_string(),
+            vec!["false".to_string()],
+        );
+        self.perform_patch_request(format!("/v1/oauth2/{}", id).as_str(), update_oauth2_rs)
+            .await
+    }
+
+    /// Set the subject DN of the CA issued certificate this client may authenticate with.
+    pub async fn idm_oauth2_rs_set_tls_client_auth_subject_dn(
+        &self,
+        id: &str,
+        subject_dn: &str,
+    ) -> Result<(), ClientError> {
+        let mut update_oauth2_rs = Entry {
+            attrs: BTreeMap::new(),
+        };
+        update_oauth2_rs.attrs.insert(
+            ATTR_OAUTH2_RS_TLS_CLIENT_AUTH_SUBJECT_DN.to_string(),
+            vec![subject_dn.to_string()],
+        );
+        self.perform_patch_request(format!("/v1/oauth2/{}", id).as_str(), update_oauth2_rs)
+            .await
+    }
+
+    pub async fn idm_oauth2_rs_remove_tls_client_auth_subject_dn(
+        &self,
+        id: &str,
+    ) -> Result<(), ClientError> {
+        self.perform_delete_request(&format!(
+            "/v1/oauth2/{}/_attr/{}",
+            id,
+            Attribute::OAuth2RsTlsClientAuthSubjectDn.as_str()
+        ))
+        .await
+    }
+
+    /// Register a PEM encoded self signed certificate that this client may authenticate with.
+    pub async fn idm_oauth2_rs_add_tls_client_certificate(
+        &self,
+        id: &str,
+        pem_data: &str,
+    ) -> Result<(), ClientError> {
+        self.perform_post_request(
+            format!("/v1/oauth2/{id}/_attr/{ATTR_OAUTH2_RS_TLS_CLIENT_CERTIFICATE}").as_str(),
+            vec![pem_data.to_string()],
+        )
+        .await
+    }
+
+    pub async fn idm_oauth2_rs_remove_tls_client_certificates(
+        &self,
+        id: &str,
+    ) -> Result<(), ClientError> {
+        self.perform_delete_request(&format!(
+            "/v1/oauth2/{}/_attr/{}",
+            id,
+            Attribute::OAuth2RsTlsClientCertificate.as_str()
+        ))
+        .await
+    }
+
+    /// Encrypt ID tokens and userinfo to a public key in the JSON web key set of this client.
+    pub async fn idm_oauth2_rs_set_encrypted_response_alg(
+        &self,
+        id: &str,
+        alg: IdTokenEncryptionAlg,
+    ) -> Result<(), ClientError> {
+        let mut update_oauth2_rs = Entry {
+            attrs: BTreeMap::new(),
+        };
+        update_oauth2_rs.attrs.insert(
+            ATTR_OAUTH2_RS_ENCRYPTED_RESPONSE_ALG.to_string(),
+            vec![alg.to_string()],
+        );
+        self.perform_patch_request(format!("/v1/oauth2/{}", id).as_str(), update_oauth2_rs)
+            .await
+    }
+
+    pub async fn idm_oauth2_rs_remove_encrypted_response_alg(
+        &self,
+        id: &str,
+    ) -> Result<(), ClientError> {
+        self.perform_delete_request(&format!(
+            "/v1/oauth2/{}/_attr/{}",
+            id,
+            Attribute::OAuth2RsEncryptedResponseAlg.as_str()
+        ))
+        .await
+    }
+
+    /// Allow this client to request rich authorisation details of this type.
+    pub async fn idm_oauth2_rs_add_authorisation_details_type(
+        &self,
+        id: &str,
+        details_type: &str,
+    ) -> Result<(), ClientError> {
+        self.perform_post_request(
+            format!("/v1/oauth2/{id}/_attr/{ATTR_OAUTH2_RS_AUTHORISATION_DETAILS_TYPE}").as_str(),
+            vec![details_type.to_string()],
+        )
+        .await
+    }
+
+    pub async fn idm_oauth2_rs_remove_authorisation_details_type(
+        &self,
+        id: &str,
+        details_type: &str,
+    ) -> Result<(), ClientError> {
+        self.perform_delete_request_with_body(
+            format!("/v1/oauth2/{id}/_attr/{ATTR_OAUTH2_RS_AUTHORISATION_DETAILS_TYPE}").as_str(),
+            vec![details_type.to_string()],
+        )
+        .await
+    }
+
+    pub async fn idm_oauth2_rs_enable_ciba(&self, id: &str) -> Result<(), ClientError> {
+        let mut update_oauth2_rs = Entry {
+            attrs: BTreeMap::new(),
+        };
+        update_oauth2_rs.attrs.insert(
+            ATTR_OAUTH2_CIBA_ENABLE.to_string(),
+            vec!["true".to_string()],
+        );
+        self.perform_patch_request(format!("/v1/oauth2/{}", id).as_str(), update_oauth2_rs)
+            .await
+    }
+
+    pub async fn idm_oauth2_rs_disable_ciba(&self, id: &str) -> Result<(), ClientError> {
+        let mut update_oauth2_rs = Entry {
+            attrs: BTreeMap::new(),
+        };
+        update_oauth2_rs.attrs.insert(
+            ATTR_OAUTH2_CIBA_ENABLE.to_string(),
+            vec!["false".to_string()],
+        );
+        self.perform_patch_request(format!("/v1/oauth2/{}", id).as_str(), update_oauth2_rs)
+            .await
+    }
+
+    pub async fn idm_oauth2_rs_enable_pairwise_subject(&self, id: &str) -> Result<(), ClientError> {
+        let mut update_oauth2_rs = Entry {
+            attrs: BTreeMap::new(),
+        };
+        update_oauth2_rs.attrs.insert(
+            ATTR_OAUTH2_PAIRWISE_SUBJECT_ENABLE.to_string(),
+            vec!["true".to_string()],
+        );
+        self.perform_patch_request(format!("/v1/oauth2/{}", id).as_str(), update_oauth2_rs)
+            .await
+    }
+
+    pub async fn idm_oauth2_rs_disable_pairwise_subject(
+        &self,
+        id: &str,
+    ) -> Result<(), ClientError> {
+        let mut update_oauth2_rs = Entry {
+            attrs: BTreeMap::new(),
+        };
+        update_oauth2_rs.attrs.insert(
+            ATTR_OAUTH2_PAIRWISE_SUBJECT_ENABLE.to_string(),
+            vec!["false".to_string()],
+        );
+        self.perform_patch_request(format!("/v1/oauth2/{}", id).as_str(), update_oauth2_rs)
+            .await
+    }
 }
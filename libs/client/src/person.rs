@@ -1,7 +1,10 @@
 use std::collections::BTreeMap;
 
 use kanidm_proto::constants::*;
-use kanidm_proto::internal::{CredentialStatus, IdentifyUserRequest, IdentifyUserResponse};
+use kanidm_proto::internal::{
+    CredentialSoftLockAccount, CredentialSoftLockStatus, CredentialStatus, IdentifyUserRequest,
+    IdentifyUserResponse,
+};
 use kanidm_proto::v1::{AccountUnixExtend, Entry, SingleStringRequest, UatStatus};
 use uuid::Uuid;
 
@@ -143,6 +146,26 @@ impl KanidmClient {
         })
     }
 
+    pub async fn idm_person_account_list_credential_locked(
+        &self,
+    ) -> Result<Vec<CredentialSoftLockAccount>, ClientError> {
+        self.perform_get_request("/v1/person/_credential/_lock")
+            .await
+    }
+
+    pub async fn idm_person_account_get_credential_lock_status(
+        &self,
+        id: &str,
+    ) -> Result<Vec<CredentialSoftLockStatus>, ClientError> {
+        self.perform_get_request(format!("/v1/person/{id}/_credential/_lock").as_str())
+            .await
+    }
+
+    pub async fn idm_person_account_credential_unlock(&self, id: &str) -> Result<(), ClientError> {
+        self.perform_delete_request(format!("/v1/person/{id}/_credential/_lock").as_str())
+            .await
+    }
+
     // This helper calls through the credential update session wrappers to
     pub async fn idm_person_account_primary_credential_set_password(
         &self,
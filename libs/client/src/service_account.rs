@@ -259,4 +259,31 @@ impl KanidmClient {
         )
         .await
     }
+
+    /// Set the scopes that OAuth2 clients registered with this service account's api
+    /// tokens grant to members of a group.
+    pub async fn idm_service_account_update_oauth2_registration_scope_map(
+        &self,
+        id: &str,
+        group: &str,
+        scopes: Vec<&str>,
+    ) -> Result<(), ClientError> {
+        let scopes: Vec<String> = scopes.into_iter().map(str::to_string).collect();
+        self.perform_post_request(
+            format!("/v1/service_account/{id}/_oauth2_registration_scopemap/{group}").as_str(),
+            scopes,
+        )
+        .await
+    }
+
+    pub async fn idm_service_account_delete_oauth2_registration_scope_map(
+        &self,
+        id: &str,
+        group: &str,
+    ) -> Result<(), ClientError> {
+        self.perform_delete_request(
+            format!("/v1/service_account/{id}/_oauth2_registration_scopemap/{group}").as_str(),
+        )
+        .await
+    }
 }
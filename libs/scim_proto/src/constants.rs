@@ -9,6 +9,23 @@ pub const SCIM_SCHEMA_PREIX: &str = "urn:ietf:params:scim:api:";
 pub const SCIM_SCHEMA_USER: &str = "urn:ietf:params:scim:schemas:core:2.0:User";
 pub const SCIM_SCHEMA_GROUP: &str = "urn:ietf:params:scim:schemas:core:2.0:Group";
 
+// https://datatracker.ietf.org/doc/html/rfc7644#section-3.5.2
+pub const SCIM_SCHEMA_PATCH_OP: &str = "urn:ietf:params:scim:api:messages:2.0:PatchOp";
+pub const SCIM_SCHEMA_LIST_RESPONSE: &str = "urn:ietf:params:scim:api:messages:2.0:ListResponse";
+
+// https://datatracker.ietf.org/doc/html/rfc7644#section-3.7
+pub const SCIM_SCHEMA_BULK_REQUEST: &str = "urn:ietf:params:scim:api:messages:2.0:BulkRequest";
+pub const SCIM_SCHEMA_BULK_RESPONSE: &str = "urn:ietf:params:scim:api:messages:2.0:BulkResponse";
+
+// https://datatracker.ietf.org/doc/html/rfc7644#section-3.12
+pub const SCIM_SCHEMA_ERROR: &str = "urn:ietf:params:scim:api:messages:2.0:Error";
+
+// https://datatracker.ietf.org/doc/html/rfc7643#section-5
+pub const SCIM_SCHEMA_SERVICE_PROVIDER_CONFIG: &str =
+    "urn:ietf:params:scim:schemas:core:2.0:ServiceProviderConfig";
+pub const SCIM_SCHEMA_RESOURCE_TYPE: &str = "urn:ietf:params:scim:schemas:core:2.0:ResourceType";
+pub const SCIM_SCHEMA_SCHEMA: &str = "urn:ietf:params:scim:schemas:core:2.0:Schema";
+
 #[cfg(test)]
 pub(crate) const RFC7643_USER: &str = r#"
 {
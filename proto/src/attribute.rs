@@ -40,6 +40,8 @@ pub enum Attribute {
     AttributeType,
     AuthSessionExpiry,
     AuthPasswordMinimumLength,
+    AuthPasswordHistoryLength,
+    AuthPasswordMaximumAge,
     BadlistPassword,
     Certificate,
     CascadeDeleted,
@@ -51,6 +53,7 @@ pub enum Attribute {
     CreatedAtCid,
     CredentialUpdateIntentToken,
     CredentialTypeMinimum,
+    CredentialSoftLock,
     DeniedName,
     DeleteAfter,
     Description,
@@ -151,6 +154,18 @@ pub enum Attribute {
     OAuth2AccountProvider,
     OAuth2AccountUniqueUserId,
     OAuth2ConsentPromptEnable,
+    OAuth2RsBackchannelLogoutUri,
+    OAuth2RsJwks,
+    OAuth2RequirePar,
+    OAuth2RequireDpop,
+    OAuth2RsTlsClientAuthSubjectDn,
+    OAuth2RsTlsClientCertificate,
+    OAuth2RsRegistrationTokenId,
+    OAuth2RegistrationScopeMap,
+    OAuth2RsEncryptedResponseAlg,
+    OAuth2RsAuthorisationDetailsType,
+    OAuth2CibaEnable,
+    OAuth2PairwiseSubjectEnable,
     ObjectClass,
     OtherNoIndex,
     PassKeys,
@@ -314,6 +329,8 @@ impl Attribute {
             Attribute::AttributeType => ATTR_ATTRIBUTETYPE,
             Attribute::AuthSessionExpiry => ATTR_AUTH_SESSION_EXPIRY,
             Attribute::AuthPasswordMinimumLength => ATTR_AUTH_PASSWORD_MINIMUM_LENGTH,
+            Attribute::AuthPasswordHistoryLength => ATTR_AUTH_PASSWORD_HISTORY_LENGTH,
+            Attribute::AuthPasswordMaximumAge => ATTR_AUTH_PASSWORD_MAXIMUM_AGE,
             Attribute::BadlistPassword => ATTR_BADLIST_PASSWORD,
             Attribute::Certificate => ATTR_CERTIFICATE,
             Attribute::CascadeDeleted => ATTR_CASCADE_DELETED,
@@ -325,6 +342,7 @@ impl Attribute {
             Attribute::CreatedAtCid => ATTR_CREATED_AT_CID,
             Attribute::CredentialUpdateIntentToken => ATTR_CREDENTIAL_UPDATE_INTENT_TOKEN,
             Attribute::CredentialTypeMinimum => ATTR_CREDENTIAL_TYPE_MINIMUM,
+            Attribute::CredentialSoftLock => ATTR_CREDENTIAL_SOFTLOCK,
             Attribute::DeniedName => ATTR_DENIED_NAME,
             Attribute::DeleteAfter => ATTR_DELETE_AFTER,
             Attribute::Description => ATTR_DESCRIPTION,
@@ -426,6 +444,20 @@ impl Attribute {
             Attribute::OAuth2AccountProvider => ATTR_OAUTH2_ACCOUNT_PROVIDER,
             Attribute::OAuth2AccountUniqueUserId => ATTR_OAUTH2_ACCOUNT_UNIQUE_USER_ID,
             Attribute::OAuth2ConsentPromptEnable => ATTR_OAUTH2_CONSENT_PROMPT_ENABLE,
+            Attribute::OAuth2RsBackchannelLogoutUri => ATTR_OAUTH2_RS_BACKCHANNEL_LOGOUT_URI,
+            Attribute::OAuth2RsJwks => ATTR_OAUTH2_RS_JWKS,
+            Attribute::OAuth2RequirePar => ATTR_OAUTH2_REQUIRE_PAR,
+            Attribute::OAuth2RequireDpop => ATTR_OAUTH2_REQUIRE_DPOP,
+            Attribute::OAuth2RsTlsClientAuthSubjectDn => ATTR_OAUTH2_RS_TLS_CLIENT_AUTH_SUBJECT_DN,
+            Attribute::OAuth2RsTlsClientCertificate => ATTR_OAUTH2_RS_TLS_CLIENT_CERTIFICATE,
+            Attribute::OAuth2RsRegistrationTokenId => ATTR_OAUTH2_RS_REGISTRATION_TOKEN_ID,
+            Attribute::OAuth2RegistrationScopeMap => ATTR_OAUTH2_REGISTRATION_SCOPE_MAP,
+            Attribute::OAuth2RsEncryptedResponseAlg => ATTR_OAUTH2_RS_ENCRYPTED_RESPONSE_ALG,
+            Attribute::OAuth2RsAuthorisationDetailsType => {
+                ATTR_OAUTH2_RS_AUTHORISATION_DETAILS_TYPE
+            }
+            Attribute::OAuth2CibaEnable => ATTR_OAUTH2_CIBA_ENABLE,
+            Attribute::OAuth2PairwiseSubjectEnable => ATTR_OAUTH2_PAIRWISE_SUBJECT_ENABLE,
             Attribute::ObjectClass => ATTR_OBJECTCLASS,
             Attribute::OtherNoIndex => ATTR_OTHER_NO_INDEX,
             Attribute::PassKeys => ATTR_PASSKEYS,
@@ -536,6 +568,8 @@ impl Attribute {
             ATTR_ATTRIBUTETYPE => Attribute::AttributeType,
             ATTR_AUTH_SESSION_EXPIRY => Attribute::AuthSessionExpiry,
             ATTR_AUTH_PASSWORD_MINIMUM_LENGTH => Attribute::AuthPasswordMinimumLength,
+            ATTR_AUTH_PASSWORD_HISTORY_LENGTH => Attribute::AuthPasswordHistoryLength,
+            ATTR_AUTH_PASSWORD_MAXIMUM_AGE => Attribute::AuthPasswordMaximumAge,
             ATTR_BADLIST_PASSWORD => Attribute::BadlistPassword,
             ATTR_CERTIFICATE => Attribute::Certificate,
             ATTR_CASCADE_DELETED => Attribute::CascadeDeleted,
@@ -547,6 +581,7 @@ impl Attribute {
             ATTR_CREATED_AT_CID => Attribute::CreatedAtCid,
             ATTR_CREDENTIAL_UPDATE_INTENT_TOKEN => Attribute::CredentialUpdateIntentToken,
             ATTR_CREDENTIAL_TYPE_MINIMUM => Attribute::CredentialTypeMinimum,
+            ATTR_CREDENTIAL_SOFTLOCK => Attribute::CredentialSoftLock,
             ATTR_DENIED_NAME => Attribute::DeniedName,
             ATTR_DELETE_AFTER => Attribute::DeleteAfter,
             ATTR_DESCRIPTION => Attribute::Description,
@@ -648,6 +683,20 @@ impl Attribute {
             ATTR_OAUTH2_ACCOUNT_PROVIDER => Attribute::OAuth2AccountProvider,
             ATTR_OAUTH2_ACCOUNT_UNIQUE_USER_ID => Attribute::OAuth2AccountUniqueUserId,
             ATTR_OAUTH2_CONSENT_PROMPT_ENABLE => Attribute::OAuth2ConsentPromptEnable,
+            ATTR_OAUTH2_RS_BACKCHANNEL_LOGOUT_URI => Attribute::OAuth2RsBackchannelLogoutUri,
+            ATTR_OAUTH2_RS_JWKS => Attribute::OAuth2RsJwks,
+            ATTR_OAUTH2_REQUIRE_PAR => Attribute::OAuth2RequirePar,
+            ATTR_OAUTH2_REQUIRE_DPOP => Attribute::OAuth2RequireDpop,
+            ATTR_OAUTH2_RS_TLS_CLIENT_AUTH_SUBJECT_DN => Attribute::OAuth2RsTlsClientAuthSubjectDn,
+            ATTR_OAUTH2_RS_TLS_CLIENT_CERTIFICATE => Attribute::OAuth2RsTlsClientCertificate,
+            ATTR_OAUTH2_RS_REGISTRATION_TOKEN_ID => Attribute::OAuth2RsRegistrationTokenId,
+            ATTR_OAUTH2_REGISTRATION_SCOPE_MAP => Attribute::OAuth2RegistrationScopeMap,
+            ATTR_OAUTH2_RS_ENCRYPTED_RESPONSE_ALG => Attribute::OAuth2RsEncryptedResponseAlg,
+            ATTR_OAUTH2_RS_AUTHORISATION_DETAILS_TYPE => {
+                Attribute::OAuth2RsAuthorisationDetailsType
+            }
+            ATTR_OAUTH2_CIBA_ENABLE => Attribute::OAuth2CibaEnable,
+            ATTR_OAUTH2_PAIRWISE_SUBJECT_ENABLE => Attribute::OAuth2PairwiseSubjectEnable,
             ATTR_OBJECTCLASS => Attribute::ObjectClass,
             ATTR_OTHER_NO_INDEX => Attribute::OtherNoIndex,
             ATTR_PASSKEYS => Attribute::PassKeys,
@@ -28,6 +28,7 @@ pub const VALID_IMAGE_UPLOAD_CONTENT_TYPES: [&str; 5] = [
 ];
 
 pub const APPLICATION_JSON: &str = "application/json";
+pub const APPLICATION_JWT: &str = "application/jwt";
 
 /// The "system" path for Kanidm client config
 pub const DEFAULT_CLIENT_CONFIG_PATH: &str = env!("KANIDM_CLIENT_CONFIG_PATH");
@@ -81,6 +82,8 @@ pub const ATTR_ATTRIBUTENAME: &str = "attributename";
 pub const ATTR_ATTRIBUTETYPE: &str = "attributetype";
 pub const ATTR_AUTH_SESSION_EXPIRY: &str = "authsession_expiry";
 pub const ATTR_AUTH_PASSWORD_MINIMUM_LENGTH: &str = "auth_password_minimum_length";
+pub const ATTR_AUTH_PASSWORD_HISTORY_LENGTH: &str = "auth_password_history_length";
+pub const ATTR_AUTH_PASSWORD_MAXIMUM_AGE: &str = "auth_password_maximum_age";
 pub const ATTR_BADLIST_PASSWORD: &str = "badlist_password";
 pub const ATTR_CASCADE_DELETED: &str = "cascade_deleted";
 pub const ATTR_CERTIFICATE: &str = "certificate";
@@ -92,6 +95,7 @@ pub const ATTR_COOKIE_PRIVATE_KEY: &str = "cookie_private_key";
 pub const ATTR_CREATED_AT_CID: &str = "created_at_cid";
 pub const ATTR_CREDENTIAL_UPDATE_INTENT_TOKEN: &str = "credential_update_intent_token";
 pub const ATTR_CREDENTIAL_TYPE_MINIMUM: &str = "credential_type_minimum";
+pub const ATTR_CREDENTIAL_SOFTLOCK: &str = "credential_softlock";
 pub const ATTR_DENIED_NAME: &str = "denied_name";
 pub const ATTR_DESCRIPTION: &str = "description";
 pub const ATTR_DIRECTMEMBEROF: &str = "directmemberof";
@@ -192,6 +196,18 @@ pub const ATTR_OAUTH2_ACCOUNT_CREDENTIAL_UUID: &str = "oauth2_account_credential
 pub const ATTR_OAUTH2_ACCOUNT_PROVIDER: &str = "oauth2_account_provider";
 pub const ATTR_OAUTH2_ACCOUNT_UNIQUE_USER_ID: &str = "oauth2_account_unique_user_id";
 pub const ATTR_OAUTH2_CONSENT_PROMPT_ENABLE: &str = "oauth2_consent_prompt_enable";
+pub const ATTR_OAUTH2_RS_BACKCHANNEL_LOGOUT_URI: &str = "oauth2_rs_backchannel_logout_uri";
+pub const ATTR_OAUTH2_RS_JWKS: &str = "oauth2_rs_jwks";
+pub const ATTR_OAUTH2_REQUIRE_PAR: &str = "oauth2_require_par";
+pub const ATTR_OAUTH2_REQUIRE_DPOP: &str = "oauth2_require_dpop";
+pub const ATTR_OAUTH2_RS_TLS_CLIENT_AUTH_SUBJECT_DN: &str = "oauth2_rs_tls_client_auth_subject_dn";
+pub const ATTR_OAUTH2_RS_TLS_CLIENT_CERTIFICATE: &str = "oauth2_rs_tls_client_certificate";
+pub const ATTR_OAUTH2_RS_REGISTRATION_TOKEN_ID: &str = "oauth2_rs_registration_token_id";
+pub const ATTR_OAUTH2_REGISTRATION_SCOPE_MAP: &str = "oauth2_registration_scope_map";
+pub const ATTR_OAUTH2_RS_ENCRYPTED_RESPONSE_ALG: &str = "oauth2_rs_encrypted_response_alg";
+pub const ATTR_OAUTH2_RS_AUTHORISATION_DETAILS_TYPE: &str = "oauth2_rs_authorisation_details_type";
+pub const ATTR_OAUTH2_CIBA_ENABLE: &str = "oauth2_ciba_enable";
+pub const ATTR_OAUTH2_PAIRWISE_SUBJECT_ENABLE: &str = "oauth2_pairwise_subject_enable";
 pub const ATTR_OBJECTCLASS: &str = "objectclass";
 pub const ATTR_OTHER_NO_INDEX: &str = "other-no-index";
 pub const ATTR_PASSKEYS: &str = "passkeys";
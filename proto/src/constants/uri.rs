@@ -20,6 +20,12 @@ pub const OAUTH2_TOKEN_ENDPOINT: &str = "/oauth2/token";
 pub const OAUTH2_TOKEN_INTROSPECT_ENDPOINT: &str = "/oauth2/token/introspect";
 /// ⚠️  ⚠️   WARNING DO NOT CHANGE THIS  ⚠️  ⚠️
 pub const OAUTH2_TOKEN_REVOKE_ENDPOINT: &str = "/oauth2/token/revoke";
+/// ⚠️  ⚠️   WARNING DO NOT CHANGE THIS  ⚠️  ⚠️
+pub const OAUTH2_PUSHED_AUTHORISATION_ENDPOINT: &str = "/oauth2/par";
+/// ⚠️  ⚠️   WARNING DO NOT CHANGE THIS  ⚠️  ⚠️
+pub const OAUTH2_REGISTRATION_ENDPOINT: &str = "/oauth2/register";
+/// ⚠️  ⚠️   WARNING DO NOT CHANGE THIS  ⚠️  ⚠️
+pub const OAUTH2_BACKCHANNEL_AUTHENTICATION_ENDPOINT: &str = "/oauth2/bc-authorize";
 
 /// ⚠️  ⚠️   WARNING DO NOT CHANGE THIS  ⚠️  ⚠️
 pub const OAUTH2_DEVICE_LOGIN: &str = "/oauth2/device"; // starts with /ui
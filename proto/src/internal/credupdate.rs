@@ -338,6 +338,7 @@ pub enum PasswordFeedback {
     TooShort(u32),
     BadListed,
     DontReusePasswords,
+    RecentlyUsed(u32),
 }
 
 /// Human-readable PasswordFeedback result.
@@ -420,6 +421,10 @@ impl fmt::Display for PasswordFeedback {
             PasswordFeedback::ThisIsSimilarToACommonlyUsedPassword => {
                 write!(f, "This is similar to a commonly used password.")
             }
+            PasswordFeedback::RecentlyUsed(history_length) => write!(
+                f,
+                "This password was used recently, it must be different to your last {history_length} passwords."
+            ),
             PasswordFeedback::TooShort(minlength) => write!(
                 f,
                 "Password was too short, needs to be at least {minlength} characters long."
@@ -67,6 +67,7 @@ pub enum OperationError {
     // Logic errors, or "soft" errors. These are to guide the user or user-interface
     // in some way.
     SessionExpired,
+    PasswordExpired,
     DuplicateKey,
     DuplicateLabel,
     EmptyRequest,
@@ -154,6 +155,12 @@ pub enum OperationError {
     AU0007UserAuthTokenInvalid,
     AU0008ClientAuthInfoPrevalidation,
 
+    // Breached Password Index Errors
+    BP0001IndexIoError,
+    BP0002IndexInvalidHeader,
+    BP0003SourceInvalidHash,
+    BP0004SourceNotOrdered,
+
     // Kanidm Generic Errors
     KG001TaskTimeout,
     KG002TaskCommFailure,
@@ -229,6 +236,12 @@ pub enum OperationError {
     SC0031Int64SyntaxInvalid,
     SC0032Uint64SyntaxInvalid,
     SC0033AssertionContainsDuplicateUuids,
+    SC0034PatchSchemaInvalid,
+    SC0035PatchOperationInvalid,
+    SC0036PatchPathUnsupported,
+    SC0037CoreAttributeUnsupported,
+    SC0038BulkRequestInvalid,
+    SC0039BulkOperationInvalid,
     // Migration
     MG0001InvalidReMigrationLevel,
     MG0002RaiseDomainLevelExceedsMaximum,
@@ -329,6 +342,8 @@ pub enum OperationError {
 
     // Plugins
     PL0001GidOverlapsSystemRange,
+    PL0002Oauth2JwksInvalid,
+    PL0003Oauth2EncryptedResponseAlgInvalid,
 
     // Web UI
     UI0001ChallengeSerialisation,
@@ -374,6 +389,7 @@ impl OperationError {
     pub fn message(&self) -> Option<String> {
         match self {
             Self::SessionExpired => None,
+            Self::PasswordExpired => Some("The password has expired and must be changed.".into()),
             Self::EmptyRequest => None,
             Self::Backend => None,
             Self::NoMatchingEntries => None,
@@ -450,6 +466,11 @@ impl OperationError {
     Self::AU0007UserAuthTokenInvalid => Some("User auth token was unable to be generated".into()),
     Self::AU0008ClientAuthInfoPrevalidation => Some("Client Authentication Info prevalidation did not occur when expected".into()),
 
+            Self::BP0001IndexIoError => Some("Unable to read or write the breached password index".into()),
+            Self::BP0002IndexInvalidHeader => Some("The breached password index is not valid, it must be rebuilt".into()),
+            Self::BP0003SourceInvalidHash => Some("The breached password source contains a line that is not a SHA-1 or NTLM hash".into()),
+            Self::BP0004SourceNotOrdered => Some("The breached password source must be ordered by hash".into()),
+
             Self::CU0001WebauthnAttestationNotTrusted => None,
             Self::CU0002WebauthnRegistrationError => None,
             Self::CU0003WebauthnUserNotVerified => Some("User Verification bit not set while registering credential, you may need to configure a PIN on this device.".into()),
@@ -571,6 +592,8 @@ impl OperationError {
             Self::MG0009InvalidTargetLevelForBootstrap => Some("The request target domain level was not valid for bootstrapping a new server instance".into()),
             Self::MG0010DowngradeNotAllowed => Some("Downgrade Attempted".into()),
             Self::PL0001GidOverlapsSystemRange => None,
+            Self::PL0002Oauth2JwksInvalid => Some("The OAuth2 client JSON web key set is not valid.".into()),
+            Self::PL0003Oauth2EncryptedResponseAlgInvalid => Some("The OAuth2 client encrypted response algorithm is not supported.".into()),
             Self::SC0001IncomingSshPublicKey => None,
             Self::SC0002ReferenceSyntaxInvalid => Some("A SCIM Reference Set contained invalid syntax and can not be processed.".into()),
             Self::SC0003MailSyntaxInvalid => Some("A SCIM Mail Address contained invalid syntax".into()),
@@ -605,6 +628,12 @@ impl OperationError {
             Self::SC0031Int64SyntaxInvalid => Some("A SCIM Int64 contained invalid syntax".into()),
             Self::SC0032Uint64SyntaxInvalid => Some("A SCIM Uint64 contained invalid syntax".into()),
             Self::SC0033AssertionContainsDuplicateUuids => Some("SCIM assertion contains duplicate entry ids, unable to proceed.".into()),
+            Self::SC0034PatchSchemaInvalid => Some("A SCIM PatchOp request did not contain the PatchOp schema.".into()),
+            Self::SC0035PatchOperationInvalid => Some("A SCIM PatchOp operation was missing a required path or value.".into()),
+            Self::SC0036PatchPathUnsupported => Some("A SCIM PatchOp operation path or value filter is not supported.".into()),
+            Self::SC0037CoreAttributeUnsupported => Some("A SCIM core schema attribute is not supported by Kanidm.".into()),
+            Self::SC0038BulkRequestInvalid => Some("A SCIM BulkRequest did not contain the BulkRequest schema, had too many operations, or reused a bulkId.".into()),
+            Self::SC0039BulkOperationInvalid => Some("A SCIM bulk operation had an unsupported path, an unknown bulkId, or was missing its data.".into()),
             Self::UI0001ChallengeSerialisation => Some("The WebAuthn challenge was unable to be serialised.".into()),
             Self::UI0002InvalidState => Some("The credential update process returned an invalid state transition.".into()),
             Self::UI0003InvalidOauth2Resume => Some("The server attempted to resume OAuth2, but no OAuth2 session is in progress.".into()),
@@ -7,6 +7,7 @@ use crate::constants::{
 };
 use clap::ValueEnum;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
 use std::fmt;
 use std::str::FromStr;
 use url::Url;
@@ -44,6 +45,88 @@ pub enum AppLink {
     },
 }
 
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
+/// An OAuth2 client that a user has consented to, or that holds sessions on behalf of
+/// the user. This is used in the UI so that users can review and revoke their consent.
+pub struct Oauth2Consent {
+    pub name: String,
+    pub display_name: String,
+    /// The scopes the user consented to share with this client.
+    pub scopes: BTreeSet<String>,
+    /// The number of sessions this client currently holds for the user.
+    pub sessions: usize,
+    /// When the user last authorised this client. This is not updated when the client
+    /// refreshes its tokens.
+    #[serde(with = "time::serde::timestamp::option")]
+    pub last_authorised: Option<time::OffsetDateTime>,
+}
+
+impl fmt::Display for Oauth2Consent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "name: {}", self.name)?;
+        writeln!(f, "display name: {}", self.display_name)?;
+        for scope in &self.scopes {
+            writeln!(f, "scope: {scope}")?;
+        }
+        writeln!(f, "sessions: {}", self.sessions)?;
+        if let Some(last_authorised) = self.last_authorised {
+            writeln!(f, "last authorised: {last_authorised}")
+        } else {
+            writeln!(f, "last authorised: never")
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, ToSchema)]
+/// The soft lock state of one of an account's credentials. Soft locks are applied after
+/// failed authentication attempts to rate limit guessing of the credential.
+pub struct CredentialSoftLockStatus {
+    pub cred_id: Uuid,
+    /// The number of failed authentication attempts in the current window.
+    pub failures: u64,
+    /// If authentication with this credential is currently denied.
+    pub locked: bool,
+    /// When authentication with this credential is next allowed.
+    #[serde(with = "time::serde::timestamp")]
+    pub unlock_at: time::OffsetDateTime,
+    /// When the failure count is reset.
+    #[serde(with = "time::serde::timestamp")]
+    pub reset_at: time::OffsetDateTime,
+}
+
+impl fmt::Display for CredentialSoftLockStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "credential: {}", self.cred_id)?;
+        writeln!(f, "failures: {}", self.failures)?;
+        if self.locked {
+            writeln!(f, "locked until: {}", self.unlock_at)?;
+        } else {
+            writeln!(f, "locked: false")?;
+        }
+        writeln!(f, "failures reset at: {}", self.reset_at)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, ToSchema)]
+/// An account that has at least one credential that is currently soft locked.
+pub struct CredentialSoftLockAccount {
+    pub uuid: Uuid,
+    pub spn: String,
+    /// The credentials of the account that are currently soft locked.
+    pub credentials: Vec<CredentialSoftLockStatus>,
+}
+
+impl fmt::Display for CredentialSoftLockAccount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "spn: {}", self.spn)?;
+        writeln!(f, "uuid: {}", self.uuid)?;
+        for credential in &self.credentials {
+            write!(f, "{credential}")?;
+        }
+        Ok(())
+    }
+}
+
 #[derive(
     Debug, Serialize, Deserialize, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, ToSchema,
 )]
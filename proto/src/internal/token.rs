@@ -48,6 +48,12 @@ pub struct UserAuthToken {
 
     pub limit_search_max_results: Option<u64>,
     pub limit_search_max_filter_test: Option<u64>,
+
+    /// If present, the password of this account expires soon and should be changed
+    /// before this time, otherwise future authentications will require a credential
+    /// update.
+    #[serde(default, with = "time::serde::timestamp::option")]
+    pub password_expiry: Option<time::OffsetDateTime>,
 }
 
 impl fmt::Display for UserAuthToken {
@@ -69,6 +75,9 @@ impl fmt::Display for UserAuthToken {
                 writeln!(f, "purpose: read write (expiry: none)")?
             }
         }
+        if let Some(exp) = self.password_expiry {
+            writeln!(f, "password expiry: {exp}")?;
+        }
         Ok(())
     }
 }
@@ -1,13 +1,16 @@
 //! Oauth2 RFC protocol definitions.
 
 use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::str::FromStr;
 
 use base64::{engine::general_purpose::STANDARD, Engine as _};
 use serde::{Deserialize, Serialize};
 use serde_with::base64::{Base64, UrlSafe};
 use serde_with::formats::SpaceSeparator;
 use serde_with::{
-    formats, rust::deserialize_ignore_any, serde_as, skip_serializing_none, StringWithSeparator,
+    formats, json::JsonString, rust::deserialize_ignore_any, serde_as, skip_serializing_none,
+    PickFirst, StringWithSeparator,
 };
 use url::Url;
 use uuid::Uuid;
@@ -18,6 +21,22 @@ pub const OAUTH2_DEVICE_CODE_EXPIRY_SECONDS: u64 = 300;
 pub const OAUTH2_DEVICE_CODE_INTERVAL_SECONDS: u64 = 5;
 /// Token type URI for OAuth2 access tokens as per RFC8693.
 pub const OAUTH2_TOKEN_TYPE_ACCESS_TOKEN: &str = "urn:ietf:params:oauth:token-type:access_token";
+/// Client assertion type for JWT client authentication as per RFC7523.
+pub const OAUTH2_CLIENT_ASSERTION_TYPE_JWT_BEARER: &str =
+    "urn:ietf:params:oauth:client-assertion-type:jwt-bearer";
+/// How many seconds a pushed authorisation request is valid for.
+pub const OAUTH2_PUSHED_AUTHORISATION_EXPIRY_SECONDS: u64 = 90;
+/// Prefix of the request_uri returned for a pushed authorisation request as per RFC9126.
+pub const OAUTH2_PUSHED_AUTHORISATION_REQUEST_URI_PREFIX: &str =
+    "urn:ietf:params:oauth:request_uri:";
+/// The longest binding message that a client may ask to be shown to the user in CIBA.
+pub const OAUTH2_BACKCHANNEL_BINDING_MESSAGE_MAX_LEN: usize = 64;
+/// HTTP header carrying a DPoP proof as per RFC9449.
+pub const OAUTH2_DPOP_HEADER: &str = "DPoP";
+/// The JWT type of a DPoP proof as per RFC9449.
+pub const OAUTH2_DPOP_PROOF_TYP: &str = "dpop+jwt";
+/// How many seconds either side of the current time a DPoP proof may have been issued at.
+pub const OAUTH2_DPOP_PROOF_LEEWAY_SECONDS: u64 = 60;
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy)]
 pub enum CodeChallengeMethod {
@@ -66,10 +85,37 @@ pub struct AuthorisationRequest {
     pub oidc_ext: AuthorisationRequestOidc,
     // Needs to be hoisted here due to serde flatten bug #3185
     pub max_age: Option<i64>,
+    /// Fine-grained authorisations requested by the client. These are a JSON string
+    /// in query parameters, but a JSON array in request objects.
+    #[serde_as(as = "Option<PickFirst<(JsonString, _)>>")]
+    pub authorization_details: Option<Vec<AuthorisationDetail>>,
     #[serde(flatten)]
     pub unknown_keys: BTreeMap<String, serde_json::value::Value>,
 }
 
+/// A fine-grained authorisation requested by a client. Only the `type` is interpreted
+/// by the authorisation server, and the detail is passed on to the resource server
+/// in access tokens and introspection.
+/// <https://datatracker.ietf.org/doc/html/rfc9396#section-2>
+#[skip_serializing_none]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct AuthorisationDetail {
+    #[serde(rename = "type")]
+    pub type_: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub locations: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub actions: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub datatypes: Vec<String>,
+    pub identifier: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub privileges: Vec<String>,
+    /// Fields that are defined by the type of this authorisation.
+    #[serde(flatten)]
+    pub extensions: BTreeMap<String, serde_json::Value>,
+}
+
 impl AuthorisationRequest {
     /// Get the `response_mode` appropriate for this request, taking into
     /// account defaults from the `response_type` parameter.
@@ -103,6 +149,126 @@ impl AuthorisationRequest {
     }
 }
 
+/// An Authorisation Request that carries its parameters by reference, either as a
+/// `request_uri` from a pushed authorisation request (RFC9126) or as a signed
+/// `request` object (RFC9101).
+#[skip_serializing_none]
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AuthorisationRequestRef {
+    pub client_id: String,
+    pub request_uri: Option<String>,
+    pub request: Option<String>,
+}
+
+/// The parameters of an Authorisation Request, as either the plain request or
+/// a reference to it.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub enum AuthorisationRequestParams {
+    ByValue(Box<AuthorisationRequest>),
+    ByReference(AuthorisationRequestRef),
+}
+
+impl AuthorisationRequestParams {
+    /// Parse the parameters from a urlencoded query or form body. If a `request_uri`
+    /// or `request` is present then all other parameters except the `client_id` are
+    /// ignored, as they must be taken from the referenced request.
+    pub fn from_query(query: &str) -> Result<Self, serde_urlencoded::de::Error> {
+        let is_by_reference = url::form_urlencoded::parse(query.as_bytes())
+            .any(|(key, _)| key == "request_uri" || key == "request");
+
+        if is_by_reference {
+            serde_urlencoded::from_str(query).map(AuthorisationRequestParams::ByReference)
+        } else {
+            serde_urlencoded::from_str(query)
+                .map(Box::new)
+                .map(AuthorisationRequestParams::ByValue)
+        }
+    }
+}
+
+impl From<AuthorisationRequest> for AuthorisationRequestParams {
+    fn from(auth_req: AuthorisationRequest) -> Self {
+        AuthorisationRequestParams::ByValue(Box::new(auth_req))
+    }
+}
+
+/// The response to a pushed authorisation request.
+/// <https://datatracker.ietf.org/doc/html/rfc9126#section-2.2>
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PushedAuthorisationResponse {
+    pub request_uri: String,
+    pub expires_in: u64,
+}
+
+/// A client initiated backchannel authentication request, asking the user identified by
+/// the hint to approve the client on another device.
+/// <https://openid.net/specs/openid-client-initiated-backchannel-authentication-core-1_0.html#auth_request>
+#[serde_as]
+#[skip_serializing_none]
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct BackchannelAuthenticationRequest {
+    #[serde_as(as = "StringWithSeparator::<SpaceSeparator, String>")]
+    pub scope: BTreeSet<String>,
+    pub login_hint: Option<String>,
+    pub login_hint_token: Option<String>,
+    pub id_token_hint: Option<String>,
+    /// A short message shown to the user, so they can match the request to the client device.
+    pub binding_message: Option<String>,
+    /// How many seconds the client would like the request to remain valid for.
+    pub requested_expiry: Option<u64>,
+    /// Only sent in ping and push modes, which are not supported.
+    pub client_notification_token: Option<String>,
+    pub acr_values: Option<String>,
+    pub user_code: Option<String>,
+}
+
+/// The response to a backchannel authentication request. The client polls the token
+/// endpoint with the auth_req_id until the user has approved or denied it.
+/// <https://openid.net/specs/openid-client-initiated-backchannel-authentication-core-1_0.html#successful_authentication_request_acknowdlegment>
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BackchannelAuthenticationResponse {
+    pub auth_req_id: String,
+    pub expires_in: u64,
+    pub interval: u64,
+}
+
+/// The metadata of a client that is registered dynamically.
+/// <https://datatracker.ietf.org/doc/html/rfc7591#section-2>
+#[skip_serializing_none]
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct ClientMetadata {
+    #[serde(default)]
+    pub redirect_uris: Vec<Url>,
+    pub token_endpoint_auth_method: Option<TokenEndpointAuthMethod>,
+    #[serde(default)]
+    pub grant_types: Vec<String>,
+    #[serde(default)]
+    pub response_types: Vec<String>,
+    pub client_name: Option<String>,
+    /// A space separated list of the scopes this client may request.
+    pub scope: Option<String>,
+    /// The public keys this client signs client assertions with.
+    pub jwks: Option<serde_json::Value>,
+}
+
+/// The response to a client registration, or a read of a client's configuration.
+/// <https://datatracker.ietf.org/doc/html/rfc7591#section-3.2.1>
+#[skip_serializing_none]
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ClientRegistrationResponse {
+    pub client_id: String,
+    pub client_secret: Option<String>,
+    pub client_id_issued_at: Option<i64>,
+    /// Always 0 when a secret is issued, as client secrets do not expire.
+    pub client_secret_expires_at: Option<i64>,
+    /// The token used to read, update or delete this client's registration.
+    /// <https://datatracker.ietf.org/doc/html/rfc7592#section-3>
+    pub registration_access_token: Option<String>,
+    pub registration_client_uri: Option<Url>,
+    #[serde(flatten)]
+    pub metadata: ClientMetadata,
+}
+
 /// An OIDC client redirects to the authorisation server with Authorisation Request
 /// parameters.
 #[skip_serializing_none]
@@ -133,6 +299,9 @@ pub enum AuthorisationResponse {
         // pub display_name: String,
         // The token we need to be given back to allow this to proceed
         consent_token: String,
+        // Fine-grained authorisations requested by the client.
+        #[serde(default)]
+        authorization_details: Vec<AuthorisationDetail>,
     },
     Permitted,
 }
@@ -170,6 +339,9 @@ pub enum GrantTypeReq {
         #[serde_as(as = "Option<StringWithSeparator::<SpaceSeparator, String>>")]
         scope: Option<BTreeSet<String>>,
     },
+    /// ref <https://openid.net/specs/openid-client-initiated-backchannel-authentication-core-1_0.html#token_request>
+    #[serde(rename = "urn:openid:params:grant-type:ciba")]
+    Ciba { auth_req_id: String },
     /// ref <https://www.rfc-editor.org/rfc/rfc8628#section-3.4>
     #[serde(rename = "urn:ietf:params:oauth:grant-type:device_code")]
     DeviceCode {
@@ -241,6 +413,56 @@ pub struct OAuth2RFC9068TokenExtensions {
 
     pub session_id: Uuid,
     pub parent_session_id: Option<Uuid>,
+
+    /// Set when the token is bound to a DPoP key or a client certificate.
+    pub cnf: Option<ConfirmationClaim>,
+
+    /// The fine-grained authorisations granted to this token.
+    /// <https://datatracker.ietf.org/doc/html/rfc9396#section-9.1>
+    pub authorization_details: Option<Vec<AuthorisationDetail>>,
+}
+
+/// The confirmation claim, binding a token to a proof of possession key.
+///
+/// Ref <https://datatracker.ietf.org/doc/html/rfc9449#section-6>
+/// Ref <https://datatracker.ietf.org/doc/html/rfc8705#section-3.1>
+#[skip_serializing_none]
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConfirmationClaim {
+    /// The base64url encoded RFC7638 JWK SHA-256 thumbprint of the DPoP key.
+    pub jkt: Option<String>,
+    /// The base64url encoded SHA-256 thumbprint of the DER encoded client certificate.
+    #[serde(rename = "x5t#S256")]
+    pub x5t_s256: Option<String>,
+}
+
+/// The event name that identifies a logout token.
+pub const OIDC_BACKCHANNEL_LOGOUT_EVENT: &str =
+    "http://schemas.openid.net/event/backchannel-logout";
+
+/// A logout token, sent to a client's back-channel logout uri when one of its
+/// sessions is terminated.
+///
+/// Ref <https://openid.net/specs/openid-connect-backchannel-1_0.html#LogoutToken>
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct OidcLogoutToken {
+    /// The issuer of this token
+    pub iss: Url,
+    /// Unique id of the subject
+    pub sub: Uuid,
+    /// client_id of the oauth2 rp
+    pub aud: String,
+    /// Issued at time.
+    pub iat: i64,
+    /// Expiry in UTC epoch seconds
+    pub exp: i64,
+    /// JWT ID, unique to this logout token.
+    pub jti: Uuid,
+    /// The events this token describes, always containing [OIDC_BACKCHANNEL_LOGOUT_EVENT].
+    pub events: BTreeMap<String, serde_json::Value>,
+    /// The id of the session that was terminated. This matches the `sid` claim
+    /// of the id tokens issued in this session.
+    pub sid: Uuid,
 }
 
 #[derive(Serialize, Deserialize, Debug, PartialEq)]
@@ -270,6 +492,9 @@ pub struct AccessTokenResponse {
     pub scope: BTreeSet<String>,
     /// If the `openid` scope was requested, an `id_token` may be present in the response.
     pub id_token: Option<String>,
+    /// The fine-grained authorisations that were granted, if any were requested.
+    /// <https://datatracker.ietf.org/doc/html/rfc9396#section-7>
+    pub authorization_details: Option<Vec<AuthorisationDetail>>,
 }
 
 /// Access token types, per [IANA Registry - OAuth Access Token Types](https://www.iana.org/assignments/oauth-parameters/oauth-parameters.xhtml#token-types)
@@ -311,12 +536,29 @@ pub struct TokenRevokeRequest {
     pub client_post_auth: ClientPostAuth,
 }
 
+/// Request that the user's session with Kanidm, and their sessions with the client, are
+/// ended. On success the user agent is redirected to the `post_logout_redirect_uri`.
+/// <https://openid.net/specs/openid-connect-rpinitiated-1_0.html#RPLogout>
+#[skip_serializing_none]
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct EndSessionRequest {
+    /// An id token previously issued to the client for the user. Required by Kanidm.
+    pub id_token_hint: Option<String>,
+    pub client_id: Option<String>,
+    /// Must be a registered redirect uri of the client.
+    pub post_logout_redirect_uri: Option<Url>,
+    pub state: Option<String>,
+}
+
 #[skip_serializing_none]
 #[derive(Serialize, Deserialize, Debug, Default)]
 /// <https://datatracker.ietf.org/doc/html/rfc6749#section-2.3.1>
 pub struct ClientPostAuth {
     pub client_id: Option<String>,
     pub client_secret: Option<String>,
+    /// A signed JWT authenticating the client. <https://datatracker.ietf.org/doc/html/rfc7523#section-2.2>
+    pub client_assertion: Option<String>,
+    pub client_assertion_type: Option<String>,
 }
 
 impl From<(String, Option<String>)> for ClientPostAuth {
@@ -324,6 +566,7 @@ impl From<(String, Option<String>)> for ClientPostAuth {
         ClientPostAuth {
             client_id: Some(client_id),
             client_secret,
+            ..Default::default()
         }
     }
 }
@@ -333,6 +576,7 @@ impl From<(&str, Option<&str>)> for ClientPostAuth {
         ClientPostAuth {
             client_id: Some(client_id.to_string()),
             client_secret: client_secret.map(|s| s.to_string()),
+            ..Default::default()
         }
     }
 }
@@ -389,6 +633,11 @@ pub struct AccessTokenIntrospectResponse {
     pub iss: Option<String>,
     // JWT ID <https://www.rfc-editor.org/rfc/rfc7519#section-4.1.7> set to session ID
     pub jti: Uuid,
+    /// Present when the token is DPoP bound <https://datatracker.ietf.org/doc/html/rfc9449#section-6.2>
+    /// or certificate bound <https://datatracker.ietf.org/doc/html/rfc8705#section-3.2>.
+    pub cnf: Option<ConfirmationClaim>,
+    /// <https://datatracker.ietf.org/doc/html/rfc9396#section-9.2>
+    pub authorization_details: Option<Vec<AuthorisationDetail>>,
 }
 
 impl AccessTokenIntrospectResponse {
@@ -406,6 +655,8 @@ impl AccessTokenIntrospectResponse {
             aud: None,
             iss: None,
             jti: session_id,
+            cnf: None,
+            authorization_details: None,
         }
     }
 }
@@ -445,6 +696,8 @@ pub enum GrantType {
     Implicit,
     #[serde(rename = "urn:ietf:params:oauth:grant-type:token-exchange")]
     TokenExchange,
+    #[serde(rename = "urn:openid:params:grant-type:ciba")]
+    Ciba,
 }
 
 fn grant_types_supported_default() -> Vec<GrantType> {
@@ -455,6 +708,15 @@ fn grant_types_supported_default() -> Vec<GrantType> {
     ]
 }
 
+/// How the tokens of an approved backchannel authentication are delivered to the client.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum BackchannelTokenDeliveryMode {
+    Poll,
+    Ping,
+    Push,
+}
+
 #[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
 #[serde(rename_all = "snake_case")]
 pub enum SubjectType {
@@ -474,15 +736,65 @@ pub enum IdTokenSignAlg {
     // WE REFUSE TO SUPPORT NONE. DON'T EVEN ASK. IT WON'T HAPPEN.
     ES256,
     RS256,
+    /// Only valid for client assertions signed with the client secret.
+    HS256,
 }
 
-#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+/// Key management algorithms that ID tokens and userinfo responses can be encrypted
+/// to a client's public key with.
+pub enum IdTokenEncryptionAlg {
+    #[serde(rename = "RSA-OAEP")]
+    RsaOaep,
+    #[serde(rename = "ECDH-ES+A256KW")]
+    EcdhEsA256Kw,
+}
+
+impl IdTokenEncryptionAlg {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            IdTokenEncryptionAlg::RsaOaep => "RSA-OAEP",
+            IdTokenEncryptionAlg::EcdhEsA256Kw => "ECDH-ES+A256KW",
+        }
+    }
+}
+
+impl fmt::Display for IdTokenEncryptionAlg {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for IdTokenEncryptionAlg {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "RSA-OAEP" => Ok(IdTokenEncryptionAlg::RsaOaep),
+            "ECDH-ES+A256KW" => Ok(IdTokenEncryptionAlg::EcdhEsA256Kw),
+            _ => Err("Invalid encryption algorithm, must be either 'RSA-OAEP' or 'ECDH-ES+A256KW'"),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "UPPERCASE")]
+/// Content encryption algorithms of encrypted ID tokens and userinfo responses.
+pub enum IdTokenEncryptionEnc {
+    A256GCM,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
 #[serde(rename_all = "snake_case")]
 pub enum TokenEndpointAuthMethod {
+    /// A public client that does not authenticate to the token endpoint.
+    None,
     ClientSecretPost,
     ClientSecretBasic,
     ClientSecretJwt,
     PrivateKeyJwt,
+    TlsClientAuth,
+    SelfSignedTlsClientAuth,
 }
 
 fn token_endpoint_auth_methods_supported_default() -> Vec<TokenEndpointAuthMethod> {
@@ -566,11 +878,11 @@ pub struct OidcDiscoveryResponse {
     // https://openid.net/specs/openid-connect-core-1_0.html#PairwiseAlg
     pub subject_types_supported: Vec<SubjectType>,
     pub id_token_signing_alg_values_supported: Vec<IdTokenSignAlg>,
-    pub id_token_encryption_alg_values_supported: Option<Vec<String>>,
-    pub id_token_encryption_enc_values_supported: Option<Vec<String>>,
+    pub id_token_encryption_alg_values_supported: Option<Vec<IdTokenEncryptionAlg>>,
+    pub id_token_encryption_enc_values_supported: Option<Vec<IdTokenEncryptionEnc>>,
     pub userinfo_signing_alg_values_supported: Option<Vec<String>>,
-    pub userinfo_encryption_alg_values_supported: Option<Vec<String>>,
-    pub userinfo_encryption_enc_values_supported: Option<Vec<String>>,
+    pub userinfo_encryption_alg_values_supported: Option<Vec<IdTokenEncryptionAlg>>,
+    pub userinfo_encryption_enc_values_supported: Option<Vec<IdTokenEncryptionEnc>>,
     pub request_object_signing_alg_values_supported: Option<Vec<String>>,
     pub request_object_encryption_alg_values_supported: Option<Vec<String>>,
     pub request_object_encryption_enc_values_supported: Option<Vec<String>>,
@@ -621,6 +933,36 @@ pub struct OidcDiscoveryResponse {
 
     /// Ref <https://www.rfc-editor.org/rfc/rfc8628#section-4>
     pub device_authorization_endpoint: Option<Url>,
+
+    /// Ref <https://openid.net/specs/openid-connect-rpinitiated-1_0.html#OPMetadata>
+    pub end_session_endpoint: Option<Url>,
+
+    /// Ref <https://openid.net/specs/openid-connect-backchannel-1_0.html#BCSupport>
+    #[serde(default)]
+    pub backchannel_logout_supported: bool,
+    #[serde(default)]
+    pub backchannel_logout_session_supported: bool,
+
+    /// Ref <https://datatracker.ietf.org/doc/html/rfc9126#section-5>
+    pub pushed_authorization_request_endpoint: Option<Url>,
+    #[serde(default)]
+    pub require_pushed_authorization_requests: bool,
+
+    /// Ref <https://datatracker.ietf.org/doc/html/rfc9449#section-5.1>
+    pub dpop_signing_alg_values_supported: Option<Vec<IdTokenSignAlg>>,
+
+    /// Ref <https://datatracker.ietf.org/doc/html/rfc8705#section-3.3>
+    #[serde(default)]
+    pub tls_client_certificate_bound_access_tokens: bool,
+
+    /// Ref <https://datatracker.ietf.org/doc/html/rfc9396#section-10>
+    pub authorization_details_types_supported: Option<Vec<String>>,
+
+    /// Ref <https://openid.net/specs/openid-client-initiated-backchannel-authentication-core-1_0.html#rfc.section.4>
+    pub backchannel_authentication_endpoint: Option<Url>,
+    pub backchannel_token_delivery_modes_supported: Option<Vec<BackchannelTokenDeliveryMode>>,
+    #[serde(default)]
+    pub backchannel_user_code_parameter_supported: bool,
 }
 
 /// The response to an OAuth2 rfc8414 metadata request
@@ -667,6 +1009,34 @@ pub struct Oauth2Rfc8414MetadataResponse {
 
     // RFC7636
     pub code_challenge_methods_supported: Vec<PkceAlg>,
+
+    // RFC9101
+    #[serde(default = "request_parameter_supported_default")]
+    pub request_parameter_supported: bool,
+    #[serde(default = "request_uri_parameter_supported_default")]
+    pub request_uri_parameter_supported: bool,
+    pub request_object_signing_alg_values_supported: Option<Vec<IdTokenSignAlg>>,
+
+    // RFC9126
+    pub pushed_authorization_request_endpoint: Option<Url>,
+    #[serde(default)]
+    pub require_pushed_authorization_requests: bool,
+
+    // RFC9449
+    pub dpop_signing_alg_values_supported: Option<Vec<IdTokenSignAlg>>,
+
+    // RFC8705
+    #[serde(default)]
+    pub tls_client_certificate_bound_access_tokens: bool,
+
+    // RFC9396
+    pub authorization_details_types_supported: Option<Vec<String>>,
+
+    // OpenID CIBA
+    pub backchannel_authentication_endpoint: Option<Url>,
+    pub backchannel_token_delivery_modes_supported: Option<Vec<BackchannelTokenDeliveryMode>>,
+    #[serde(default)]
+    pub backchannel_user_code_parameter_supported: bool,
 }
 
 #[skip_serializing_none]
@@ -712,7 +1082,10 @@ impl DeviceAuthorizationResponse {
 
 #[cfg(test)]
 mod tests {
-    use super::{AccessTokenRequest, GrantTypeReq, OAUTH2_TOKEN_TYPE_ACCESS_TOKEN};
+    use super::{
+        AccessTokenRequest, AuthorisationRequestParams, GrantTypeReq,
+        OAUTH2_TOKEN_TYPE_ACCESS_TOKEN,
+    };
     use std::collections::BTreeSet;
     use url::Url;
 
@@ -795,4 +1168,36 @@ mod tests {
             _ => panic!("Wrong grant type"),
         }
     }
+
+    #[test]
+    fn test_authorisation_request_params_from_query() {
+        let params = AuthorisationRequestParams::from_query(
+            "response_type=code&client_id=test_resource_server&redirect_uri=https%3A%2F%2Fdemo.example.com%2Foauth2%2Fresult&scope=openid&max_age=60",
+        )
+        .expect("Failed to parse");
+
+        let AuthorisationRequestParams::ByValue(auth_req) = params else {
+            panic!("Expected a by value request");
+        };
+        assert_eq!(auth_req.client_id, "test_resource_server");
+        assert_eq!(auth_req.max_age, Some(60));
+
+        let params = AuthorisationRequestParams::from_query(
+            "client_id=test_resource_server&request_uri=urn%3Aietf%3Aparams%3Aoauth%3Arequest_uri%3Aabcd&scope=openid",
+        )
+        .expect("Failed to parse");
+
+        let AuthorisationRequestParams::ByReference(auth_req_ref) = params else {
+            panic!("Expected a by reference request");
+        };
+        assert_eq!(auth_req_ref.client_id, "test_resource_server");
+        assert_eq!(
+            auth_req_ref.request_uri.as_deref(),
+            Some("urn:ietf:params:oauth:request_uri:abcd")
+        );
+        assert!(auth_req_ref.request.is_none());
+
+        // A by reference request still requires the client_id.
+        assert!(AuthorisationRequestParams::from_query("request=abcd").is_err());
+    }
 }
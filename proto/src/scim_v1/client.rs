@@ -1,5 +1,7 @@
 //! These are types that a client will send to the server.
-use super::{ScimEntryGeneric, ScimEntryGetQuery, ScimMail, ScimOauth2ClaimMapJoinChar};
+use super::{
+    ScimEntryGeneric, ScimEntryGetQuery, ScimMail, ScimOauth2ClaimMapJoinChar, ScimPatchPath,
+};
 use crate::attribute::Attribute;
 use crate::v1::OutboundMessage;
 use scim_proto::ScimEntryHeader;
@@ -7,7 +9,7 @@ use serde::{Deserialize, Serialize};
 use serde_json::Value as JsonValue;
 use serde_with::formats::PreferMany;
 use serde_with::OneOrMany;
-use serde_with::{base64, formats, serde_as, skip_serializing_none};
+use serde_with::{base64, formats, serde_as, skip_serializing_none, DisplayFromStr};
 use sshkey_attest::proto::PublicKey as SshPublicKey;
 use std::collections::{BTreeMap, BTreeSet};
 use std::num::NonZeroU64;
@@ -352,3 +354,77 @@ impl TryFrom<ScimEntryPutKanidm> for ScimEntryPutGeneric {
         })
     }
 }
+
+/// The type of change a SCIM PATCH operation makes to an attribute.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ScimPatchOpType {
+    // Some providers send these capitalised, and RFC7644 operation names are case insensitive.
+    #[serde(alias = "Add")]
+    Add,
+    #[serde(alias = "Remove")]
+    Remove,
+    #[serde(alias = "Replace")]
+    Replace,
+}
+
+#[serde_as]
+#[skip_serializing_none]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ScimPatchOperation {
+    pub op: ScimPatchOpType,
+    /// The attribute this operation applies to. If unset, the value must be an object
+    /// of the attributes to add or replace.
+    #[serde_as(as = "Option<DisplayFromStr>")]
+    #[schema(value_type = Option<String>)]
+    #[serde(default)]
+    pub path: Option<ScimPatchPath>,
+    pub value: Option<JsonValue>,
+}
+
+/// A SCIM PatchOp request, which makes a partial update to an entry.
+/// https://datatracker.ietf.org/doc/html/rfc7644#section-3.5.2
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ScimEntryPatchGeneric {
+    pub schemas: Vec<String>,
+    #[serde(rename = "Operations")]
+    pub operations: Vec<ScimPatchOperation>,
+}
+
+/// The HTTP method of an operation in a SCIM bulk request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum ScimBulkMethod {
+    Post,
+    Put,
+    Patch,
+    Delete,
+}
+
+#[skip_serializing_none]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ScimBulkOperation {
+    pub method: ScimBulkMethod,
+    /// An identifier for a created entry, which later operations can refer to
+    /// as `bulkId:<bulk_id>` in their path or data.
+    pub bulk_id: Option<String>,
+    /// The resource this operation applies to, such as `/Entry` or `/Entry/{id}`.
+    pub path: String,
+    pub data: Option<JsonValue>,
+}
+
+/// A SCIM bulk request, which applies a list of operations in a single transaction.
+/// https://datatracker.ietf.org/doc/html/rfc7644#section-3.7
+#[skip_serializing_none]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ScimBulkRequest {
+    pub schemas: Vec<String>,
+    /// The number of failed operations after which the remaining operations are
+    /// not applied. If unset, all operations are attempted.
+    #[schema(value_type = Option<u64>)]
+    pub fail_on_errors: Option<NonZeroU64>,
+    #[serde(rename = "Operations")]
+    pub operations: Vec<ScimBulkOperation>,
+}
@@ -0,0 +1,1248 @@
+//! The SCIM core User and Group resources from RFC7643, and the discovery resources
+//! from RFC7644 section 4. Generic SCIM provisioning clients only understand these
+//! shapes, so this module maps them onto Kanidm's persons and groups and their
+//! attributes.
+//!
+//! | Core attribute        | Kanidm attribute   |
+//! |-----------------------|--------------------|
+//! | `id`                  | `uuid`             |
+//! | User `userName`       | `name`             |
+//! | User `displayName`    | `displayname`      |
+//! | User `emails`         | `mail`             |
+//! | User `active`         | `account_expire`   |
+//! | User `groups`         | `directmemberof`   |
+//! | Group `displayName`   | `name`             |
+//! | Group `members`       | `member`           |
+
+use super::client::{
+    ScimDateTime, ScimEntryPatchGeneric, ScimEntryPostGeneric, ScimEntryPutGeneric,
+    ScimPatchOpType, ScimPatchOperation,
+};
+use super::server::{ScimEntryKanidm, ScimListResponse, ScimValueKanidm};
+use super::{
+    AttrPath, JsonValue, ScimEntryGetQuery, ScimFilter, ScimMail, ScimPatchPath, SCIM_SCHEMA_GROUP,
+    SCIM_SCHEMA_LIST_RESPONSE, SCIM_SCHEMA_RESOURCE_TYPE, SCIM_SCHEMA_SCHEMA,
+    SCIM_SCHEMA_SERVICE_PROVIDER_CONFIG, SCIM_SCHEMA_USER,
+};
+use crate::attribute::{Attribute, SubAttribute};
+use crate::internal::OperationError;
+use serde::{Deserialize, Serialize};
+use serde_with::skip_serializing_none;
+use std::collections::BTreeMap;
+use std::num::NonZeroU64;
+use std::ops::Not;
+use time::OffsetDateTime;
+use url::Url;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+/// The prefix that the core resource endpoints are served from.
+pub const SCIM_CORE_PATH_PREFIX: &str = "/scim/v2";
+
+/// A value of a multivalued attribute such as `emails`.
+#[skip_serializing_none]
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ScimCoreMultiValue {
+    pub value: String,
+    #[serde(rename = "type")]
+    pub type_: Option<String>,
+    #[serde(default, skip_serializing_if = "<&bool>::not")]
+    pub primary: bool,
+    pub display: Option<String>,
+}
+
+/// A reference to another resource, such as the members of a group.
+#[skip_serializing_none]
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ScimCoreReference {
+    /// The id of the referenced resource. Kanidm also accepts a name or spn here.
+    pub value: String,
+    pub display: Option<String>,
+    #[serde(rename = "type")]
+    pub type_: Option<String>,
+    #[serde(rename = "$ref")]
+    pub reference: Option<Url>,
+}
+
+#[skip_serializing_none]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ScimCoreMeta {
+    pub resource_type: String,
+    pub location: Option<Url>,
+}
+
+/// A resource of the `urn:ietf:params:scim:schemas:core:2.0:User` schema. Attributes
+/// that Kanidm can not represent are ignored.
+#[skip_serializing_none]
+#[derive(Serialize, Deserialize, Debug, Clone, Default, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ScimCoreUser {
+    #[serde(default)]
+    pub schemas: Vec<String>,
+    pub id: Option<Uuid>,
+    pub external_id: Option<String>,
+    pub user_name: String,
+    pub display_name: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub emails: Vec<ScimCoreMultiValue>,
+    pub active: Option<bool>,
+    /// The groups this user is a direct member of. This is read only.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub groups: Vec<ScimCoreReference>,
+    pub meta: Option<ScimCoreMeta>,
+}
+
+impl ScimCoreUser {
+    /// Build the core view of a Kanidm person. Returns `None` if the entry has no
+    /// name that is visible to the caller.
+    pub fn from_kanidm(entry: &ScimEntryKanidm, origin: &Url, now: OffsetDateTime) -> Option<Self> {
+        let id = entry.header.id;
+        let user_name = entry.get_string_attr(&Attribute::Name)?.clone();
+        let display_name = entry.get_string_attr(&Attribute::DisplayName).cloned();
+
+        let emails = match entry.attrs.get(&Attribute::Mail) {
+            Some(ScimValueKanidm::Mail(mails)) => mails
+                .iter()
+                .map(|mail| ScimCoreMultiValue {
+                    value: mail.value.clone(),
+                    primary: mail.primary,
+                    ..Default::default()
+                })
+                .collect(),
+            _ => Vec::new(),
+        };
+
+        let (expired, not_yet_valid) = core_account_validity(entry, now);
+
+        let groups = entry
+            .get_scim_refs_attr(&Attribute::DirectMemberOf)
+            .map(|refs| {
+                refs.iter()
+                    .map(|scim_ref| ScimCoreReference {
+                        value: scim_ref.uuid.to_string(),
+                        display: Some(scim_ref.value.clone()),
+                        type_: Some("direct".to_string()),
+                        reference: ScimCoreResourceType::Group.location(origin, scim_ref.uuid),
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        Some(ScimCoreUser {
+            schemas: vec![SCIM_SCHEMA_USER.to_string()],
+            id: Some(id),
+            external_id: entry.header.external_id.clone(),
+            user_name,
+            display_name,
+            emails,
+            active: Some(!(expired || not_yet_valid)),
+            groups,
+            meta: Some(ScimCoreResourceType::User.meta(origin, id)),
+        })
+    }
+
+    fn to_kanidm_attrs(&self) -> Result<BTreeMap<Attribute, Option<JsonValue>>, OperationError> {
+        let display_name = self.display_name.as_ref().unwrap_or(&self.user_name);
+
+        let mail = if self.emails.is_empty() {
+            None
+        } else {
+            Some(core_mails_to_kanidm(&self.emails)?)
+        };
+
+        Ok(BTreeMap::from([
+            (
+                Attribute::Name,
+                Some(JsonValue::String(self.user_name.clone())),
+            ),
+            (
+                Attribute::DisplayName,
+                Some(JsonValue::String(display_name.clone())),
+            ),
+            (Attribute::Mail, mail),
+        ]))
+    }
+
+    /// The attributes to create a Kanidm person with.
+    pub fn to_kanidm_post(
+        &self,
+        now: OffsetDateTime,
+    ) -> Result<ScimEntryPostGeneric, OperationError> {
+        let mut attrs: BTreeMap<_, _> = self
+            .to_kanidm_attrs()?
+            .into_iter()
+            .filter_map(|(attr, value)| value.map(|value| (attr, value)))
+            .collect();
+
+        // An inactive user is one whose account has expired.
+        if self.active == Some(false) {
+            attrs.insert(Attribute::AccountExpire, core_datetime_to_kanidm(now)?);
+        }
+
+        Ok(ScimEntryPostGeneric { attrs })
+    }
+
+    /// Replace the attributes of the Kanidm person `current`. Core attributes that are
+    /// absent from this resource are removed from the person. The validity of the account
+    /// is only changed if `active` differs from its current state, so that an expiry set
+    /// by an administrator is retained.
+    pub fn to_kanidm_put(
+        &self,
+        current: &ScimEntryKanidm,
+        now: OffsetDateTime,
+    ) -> Result<ScimEntryPutGeneric, OperationError> {
+        let mut attrs = self.to_kanidm_attrs()?;
+
+        let (expired, not_yet_valid) = core_account_validity(current, now);
+
+        match self.active {
+            Some(false) if !(expired || not_yet_valid) => {
+                attrs.insert(
+                    Attribute::AccountExpire,
+                    Some(core_datetime_to_kanidm(now)?),
+                );
+            }
+            Some(true) => {
+                if expired {
+                    attrs.insert(Attribute::AccountExpire, None);
+                }
+                if not_yet_valid {
+                    attrs.insert(Attribute::AccountValidFrom, None);
+                }
+            }
+            _ => {}
+        }
+
+        Ok(ScimEntryPutGeneric {
+            id: current.header.id,
+            query: ScimEntryGetQuery::default(),
+            attrs,
+        })
+    }
+}
+
+/// A resource of the `urn:ietf:params:scim:schemas:core:2.0:Group` schema.
+#[skip_serializing_none]
+#[derive(Serialize, Deserialize, Debug, Clone, Default, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ScimCoreGroup {
+    #[serde(default)]
+    pub schemas: Vec<String>,
+    pub id: Option<Uuid>,
+    pub external_id: Option<String>,
+    /// Kanidm groups have no separate display name, so this must be a valid
+    /// Kanidm group name.
+    pub display_name: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub members: Vec<ScimCoreReference>,
+    pub meta: Option<ScimCoreMeta>,
+}
+
+impl ScimCoreGroup {
+    /// Build the core view of a Kanidm group. Returns `None` if the entry has no
+    /// name that is visible to the caller.
+    pub fn from_kanidm(entry: &ScimEntryKanidm, origin: &Url) -> Option<Self> {
+        let id = entry.header.id;
+        let display_name = entry.get_string_attr(&Attribute::Name)?.clone();
+
+        let members = entry
+            .get_scim_refs_attr(&Attribute::Member)
+            .map(|refs| {
+                refs.iter()
+                    .map(|scim_ref| ScimCoreReference {
+                        value: scim_ref.uuid.to_string(),
+                        display: Some(scim_ref.value.clone()),
+                        ..Default::default()
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        Some(ScimCoreGroup {
+            schemas: vec![SCIM_SCHEMA_GROUP.to_string()],
+            id: Some(id),
+            external_id: entry.header.external_id.clone(),
+            display_name,
+            members,
+            meta: Some(ScimCoreResourceType::Group.meta(origin, id)),
+        })
+    }
+
+    fn to_kanidm_attrs(&self) -> BTreeMap<Attribute, Option<JsonValue>> {
+        let member = if self.members.is_empty() {
+            None
+        } else {
+            Some(core_references_to_kanidm(
+                self.members.iter().map(|member| member.value.clone()),
+            ))
+        };
+
+        BTreeMap::from([
+            (
+                Attribute::Name,
+                Some(JsonValue::String(self.display_name.clone())),
+            ),
+            (Attribute::Member, member),
+        ])
+    }
+
+    /// The attributes to create a Kanidm group with.
+    pub fn to_kanidm_post(&self) -> ScimEntryPostGeneric {
+        let attrs = self
+            .to_kanidm_attrs()
+            .into_iter()
+            .filter_map(|(attr, value)| value.map(|value| (attr, value)))
+            .collect();
+
+        ScimEntryPostGeneric { attrs }
+    }
+
+    /// Replace the attributes of the Kanidm group `id`. If no members are listed,
+    /// all members are removed from the group.
+    pub fn to_kanidm_put(&self, id: Uuid) -> ScimEntryPutGeneric {
+        ScimEntryPutGeneric {
+            id,
+            query: ScimEntryGetQuery::default(),
+            attrs: self.to_kanidm_attrs(),
+        }
+    }
+}
+
+/// Whether the account of a Kanidm person has expired, and whether it is not yet valid.
+fn core_account_validity(entry: &ScimEntryKanidm, now: OffsetDateTime) -> (bool, bool) {
+    let expired = match entry.attrs.get(&Attribute::AccountExpire) {
+        Some(ScimValueKanidm::DateTime(expire)) => *expire <= now,
+        _ => false,
+    };
+    let not_yet_valid = match entry.attrs.get(&Attribute::AccountValidFrom) {
+        Some(ScimValueKanidm::DateTime(valid_from)) => *valid_from > now,
+        _ => false,
+    };
+    (expired, not_yet_valid)
+}
+
+fn core_datetime_to_kanidm(date_time: OffsetDateTime) -> Result<JsonValue, OperationError> {
+    serde_json::to_value(ScimDateTime { date_time })
+        .map_err(|_| OperationError::SC0010DateTimeSyntaxInvalid)
+}
+
+fn core_mails_to_kanidm(emails: &[ScimCoreMultiValue]) -> Result<JsonValue, OperationError> {
+    let mails: Vec<_> = emails
+        .iter()
+        .map(|email| ScimMail {
+            primary: email.primary,
+            value: email.value.clone(),
+        })
+        .collect();
+
+    serde_json::to_value(mails).map_err(|_| OperationError::SC0003MailSyntaxInvalid)
+}
+
+fn core_references_to_kanidm(values: impl Iterator<Item = String>) -> JsonValue {
+    JsonValue::Array(values.map(JsonValue::String).collect())
+}
+
+/// Decode a patch value for a multivalued attribute. Clients may send a single value
+/// or an array, and each value may be a bare string or an object.
+fn core_multi_values(
+    value: JsonValue,
+    err: impl Fn() -> OperationError,
+) -> Result<Vec<ScimCoreMultiValue>, OperationError> {
+    let values = match value {
+        JsonValue::Array(values) => values,
+        value => vec![value],
+    };
+
+    values
+        .into_iter()
+        .map(|value| match value {
+            JsonValue::String(value) => Ok(ScimCoreMultiValue {
+                value,
+                ..Default::default()
+            }),
+            value => serde_json::from_value(value).map_err(|_| err()),
+        })
+        .collect()
+}
+
+fn core_active(value: &JsonValue) -> Result<bool, OperationError> {
+    match value {
+        JsonValue::Bool(active) => Ok(*active),
+        // Some providers send booleans as strings in patch operations.
+        JsonValue::String(active) if active.eq_ignore_ascii_case("true") => Ok(true),
+        JsonValue::String(active) if active.eq_ignore_ascii_case("false") => Ok(false),
+        _ => Err(OperationError::SC0005BoolSyntaxInvalid),
+    }
+}
+
+/// The core resource types that Kanidm serves.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScimCoreResourceType {
+    User,
+    Group,
+}
+
+impl ScimCoreResourceType {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::User => "User",
+            Self::Group => "Group",
+        }
+    }
+
+    pub fn endpoint(&self) -> &'static str {
+        match self {
+            Self::User => "/Users",
+            Self::Group => "/Groups",
+        }
+    }
+
+    pub fn schema(&self) -> &'static str {
+        match self {
+            Self::User => SCIM_SCHEMA_USER,
+            Self::Group => SCIM_SCHEMA_GROUP,
+        }
+    }
+
+    pub fn location(&self, origin: &Url, id: Uuid) -> Option<Url> {
+        origin
+            .join(&format!(
+                "{}{}/{}",
+                SCIM_CORE_PATH_PREFIX,
+                self.endpoint(),
+                id
+            ))
+            .ok()
+    }
+
+    fn meta(self, origin: &Url, id: Uuid) -> ScimCoreMeta {
+        ScimCoreMeta {
+            resource_type: self.name().to_string(),
+            location: self.location(origin, id),
+        }
+    }
+
+    /// Map a core attribute name to the Kanidm attribute it is stored in. Core
+    /// attribute names are case insensitive.
+    fn attribute_to_kanidm(self, attr: &Attribute) -> Result<Attribute, OperationError> {
+        match (self, attr.as_str().to_lowercase().as_str()) {
+            (_, "id") => Ok(Attribute::Uuid),
+            (Self::User, "username") => Ok(Attribute::Name),
+            (Self::User, "displayname") => Ok(Attribute::DisplayName),
+            (Self::User, "emails") => Ok(Attribute::Mail),
+            (Self::Group, "displayname") => Ok(Attribute::Name),
+            (Self::Group, "members") => Ok(Attribute::Member),
+            _ => Err(OperationError::SC0037CoreAttributeUnsupported),
+        }
+    }
+
+    fn attrpath_to_kanidm(self, path: AttrPath) -> Result<AttrPath, OperationError> {
+        let AttrPath { a, s } = path;
+        match s {
+            // The value of a multivalued attribute is what Kanidm stores.
+            None | Some(SubAttribute::Value) => Ok(AttrPath {
+                a: self.attribute_to_kanidm(&a)?,
+                s: None,
+            }),
+            Some(_) => Err(OperationError::SC0037CoreAttributeUnsupported),
+        }
+    }
+
+    fn filter_to_kanidm(self, filter: ScimFilter) -> Result<ScimFilter, OperationError> {
+        let filter = match filter {
+            ScimFilter::Or(left, right) => ScimFilter::Or(
+                Box::new(self.filter_to_kanidm(*left)?),
+                Box::new(self.filter_to_kanidm(*right)?),
+            ),
+            ScimFilter::And(left, right) => ScimFilter::And(
+                Box::new(self.filter_to_kanidm(*left)?),
+                Box::new(self.filter_to_kanidm(*right)?),
+            ),
+            ScimFilter::Not(inner) => ScimFilter::Not(Box::new(self.filter_to_kanidm(*inner)?)),
+            ScimFilter::Present(path) => ScimFilter::Present(self.attrpath_to_kanidm(path)?),
+            ScimFilter::Equal(path, value) => {
+                ScimFilter::Equal(self.attrpath_to_kanidm(path)?, value)
+            }
+            ScimFilter::NotEqual(path, value) => {
+                ScimFilter::NotEqual(self.attrpath_to_kanidm(path)?, value)
+            }
+            ScimFilter::Contains(path, value) => {
+                ScimFilter::Contains(self.attrpath_to_kanidm(path)?, value)
+            }
+            ScimFilter::StartsWith(path, value) => {
+                ScimFilter::StartsWith(self.attrpath_to_kanidm(path)?, value)
+            }
+            ScimFilter::EndsWith(path, value) => {
+                ScimFilter::EndsWith(self.attrpath_to_kanidm(path)?, value)
+            }
+            ScimFilter::Greater(path, value) => {
+                ScimFilter::Greater(self.attrpath_to_kanidm(path)?, value)
+            }
+            ScimFilter::Less(path, value) => {
+                ScimFilter::Less(self.attrpath_to_kanidm(path)?, value)
+            }
+            ScimFilter::GreaterOrEqual(path, value) => {
+                ScimFilter::GreaterOrEqual(self.attrpath_to_kanidm(path)?, value)
+            }
+            ScimFilter::LessOrEqual(path, value) => {
+                ScimFilter::LessOrEqual(self.attrpath_to_kanidm(path)?, value)
+            }
+            ScimFilter::Complex(..) => return Err(OperationError::SC0037CoreAttributeUnsupported),
+        };
+        Ok(filter)
+    }
+
+    /// Map the filter and sort attributes of a list request onto Kanidm attributes.
+    /// Core resources are always returned whole, so `attributes` is ignored.
+    pub fn query_to_kanidm(
+        &self,
+        query: ScimEntryGetQuery,
+    ) -> Result<ScimEntryGetQuery, OperationError> {
+        let ScimEntryGetQuery {
+            sort_by,
+            sort_order,
+            start_index,
+            count,
+            filter,
+            ..
+        } = query;
+
+        Ok(ScimEntryGetQuery {
+            attributes: None,
+            ext_access_check: false,
+            sort_by: sort_by
+                .map(|attr| self.attribute_to_kanidm(&attr))
+                .transpose()?,
+            sort_order,
+            start_index,
+            count,
+            filter: filter
+                .map(|filter| self.filter_to_kanidm(filter))
+                .transpose()?,
+        })
+    }
+
+    /// Map a patch of a core resource to a patch of the Kanidm entry.
+    pub fn patch_to_kanidm(
+        &self,
+        patch: ScimEntryPatchGeneric,
+        now: OffsetDateTime,
+    ) -> Result<ScimEntryPatchGeneric, OperationError> {
+        let ScimEntryPatchGeneric {
+            schemas,
+            operations,
+        } = patch;
+
+        let mut mapped = Vec::with_capacity(operations.len());
+        for operation in operations {
+            self.patch_operation_to_kanidm(operation, now, &mut mapped)?;
+        }
+
+        Ok(ScimEntryPatchGeneric {
+            schemas,
+            operations: mapped,
+        })
+    }
+
+    fn patch_operation_to_kanidm(
+        self,
+        operation: ScimPatchOperation,
+        now: OffsetDateTime,
+        mapped: &mut Vec<ScimPatchOperation>,
+    ) -> Result<(), OperationError> {
+        let ScimPatchOperation { op, path, value } = operation;
+
+        let Some(path) = path else {
+            // Expand the object of attributes so that each one is mapped individually.
+            return match value {
+                Some(JsonValue::Object(attrs)) => {
+                    attrs.into_iter().try_for_each(|(attr, value)| {
+                        let path = ScimPatchPath {
+                            a: Attribute::from(attr.as_str()),
+                            s: None,
+                            filter: None,
+                        };
+                        self.patch_operation_to_kanidm(
+                            ScimPatchOperation {
+                                op,
+                                path: Some(path),
+                                value: Some(value),
+                            },
+                            now,
+                            mapped,
+                        )
+                    })
+                }
+                _ => Err(OperationError::SC0035PatchOperationInvalid),
+            };
+        };
+
+        let ScimPatchPath { a, s, filter } = path;
+
+        if self == Self::User && a.as_str().eq_ignore_ascii_case("active") {
+            if s.is_some() || filter.is_some() {
+                return Err(OperationError::SC0036PatchPathUnsupported);
+            }
+
+            let active = match (op, value) {
+                (ScimPatchOpType::Remove, _) => true,
+                (_, Some(value)) => core_active(&value)?,
+                (_, None) => return Err(OperationError::SC0035PatchOperationInvalid),
+            };
+
+            // An inactive user is one whose account has expired.
+            let path = Some(ScimPatchPath {
+                a: Attribute::AccountExpire,
+                s: None,
+                filter: None,
+            });
+
+            mapped.push(if active {
+                ScimPatchOperation {
+                    op: ScimPatchOpType::Remove,
+                    path,
+                    value: None,
+                }
+            } else {
+                ScimPatchOperation {
+                    op: ScimPatchOpType::Replace,
+                    path,
+                    value: Some(core_datetime_to_kanidm(now)?),
+                }
+            });
+            return Ok(());
+        }
+
+        let s = match s {
+            None | Some(SubAttribute::Value) => None,
+            Some(_) => return Err(OperationError::SC0036PatchPathUnsupported),
+        };
+
+        let a = self.attribute_to_kanidm(&a)?;
+
+        let value = match (&a, value) {
+            (Attribute::Mail, Some(value)) => {
+                Some(core_mails_to_kanidm(&core_multi_values(value, || {
+                    OperationError::SC0003MailSyntaxInvalid
+                })?)?)
+            }
+            (Attribute::Member, Some(value)) => Some(core_references_to_kanidm(
+                core_multi_values(value, || OperationError::SC0002ReferenceSyntaxInvalid)?
+                    .into_iter()
+                    .map(|member| member.value),
+            )),
+            (_, value) => value,
+        };
+
+        mapped.push(ScimPatchOperation {
+            op,
+            path: Some(ScimPatchPath { a, s, filter }),
+            value,
+        });
+
+        Ok(())
+    }
+}
+
+/// A list of core resources. This differs from [ScimListResponse] in that
+/// `Resources` is capitalised as RFC7644 requires.
+#[skip_serializing_none]
+#[derive(Serialize, Debug, Clone, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ScimCoreListResponse<T> {
+    pub schemas: Vec<String>,
+    pub total_results: u64,
+    #[schema(value_type = u64)]
+    pub items_per_page: Option<NonZeroU64>,
+    #[schema(value_type = u64)]
+    pub start_index: Option<NonZeroU64>,
+    #[serde(rename = "Resources")]
+    pub resources: Vec<T>,
+}
+
+impl<T> ScimCoreListResponse<T> {
+    pub fn new(resources: Vec<T>) -> Self {
+        ScimCoreListResponse {
+            schemas: vec![SCIM_SCHEMA_LIST_RESPONSE.to_string()],
+            total_results: resources.len() as u64,
+            items_per_page: None,
+            start_index: None,
+            resources,
+        }
+    }
+
+    /// Convert a list of Kanidm entries to core resources. Entries that can not be
+    /// represented are omitted from the results.
+    pub fn from_kanidm<F>(list: ScimListResponse, f: F) -> Self
+    where
+        F: Fn(&ScimEntryKanidm) -> Option<T>,
+    {
+        let ScimListResponse {
+            total_results,
+            items_per_page,
+            start_index,
+            resources,
+            ..
+        } = list;
+
+        let entries = resources.len() as u64;
+        let resources: Vec<_> = resources.iter().filter_map(f).collect();
+        let omitted = entries - resources.len() as u64;
+
+        ScimCoreListResponse {
+            schemas: vec![SCIM_SCHEMA_LIST_RESPONSE.to_string()],
+            total_results: total_results.saturating_sub(omitted),
+            items_per_page,
+            start_index,
+            resources,
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone, ToSchema)]
+pub struct ScimSupported {
+    pub supported: bool,
+}
+
+#[derive(Serialize, Debug, Clone, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ScimBulkSupported {
+    pub supported: bool,
+    pub max_operations: u64,
+    pub max_payload_size: u64,
+}
+
+#[derive(Serialize, Debug, Clone, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ScimFilterSupported {
+    pub supported: bool,
+    pub max_results: u64,
+}
+
+#[skip_serializing_none]
+#[derive(Serialize, Debug, Clone, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ScimAuthenticationScheme {
+    #[serde(rename = "type")]
+    pub type_: String,
+    pub name: String,
+    pub description: String,
+    pub spec_uri: Option<Url>,
+    pub primary: bool,
+}
+
+/// The features of the SCIM protocol that Kanidm supports.
+/// https://datatracker.ietf.org/doc/html/rfc7643#section-5
+#[skip_serializing_none]
+#[derive(Serialize, Debug, Clone, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ScimServiceProviderConfig {
+    pub schemas: Vec<String>,
+    pub documentation_uri: Option<Url>,
+    pub patch: ScimSupported,
+    pub bulk: ScimBulkSupported,
+    pub filter: ScimFilterSupported,
+    pub change_password: ScimSupported,
+    pub sort: ScimSupported,
+    pub etag: ScimSupported,
+    pub authentication_schemes: Vec<ScimAuthenticationScheme>,
+    pub meta: ScimCoreMeta,
+}
+
+impl ScimServiceProviderConfig {
+    pub fn new(origin: &Url, max_results: u64) -> Self {
+        ScimServiceProviderConfig {
+            schemas: vec![SCIM_SCHEMA_SERVICE_PROVIDER_CONFIG.to_string()],
+            documentation_uri: Url::parse("https://kanidm.github.io/kanidm/stable/").ok(),
+            patch: ScimSupported { supported: true },
+            bulk: ScimBulkSupported {
+                supported: false,
+                max_operations: 0,
+                max_payload_size: 0,
+            },
+            filter: ScimFilterSupported {
+                supported: true,
+                max_results,
+            },
+            change_password: ScimSupported { supported: false },
+            sort: ScimSupported { supported: true },
+            etag: ScimSupported { supported: false },
+            authentication_schemes: vec![ScimAuthenticationScheme {
+                type_: "oauthbearertoken".to_string(),
+                name: "OAuth Bearer Token".to_string(),
+                description: "Authentication with a Kanidm API token or session token".to_string(),
+                spec_uri: Url::parse("https://www.rfc-editor.org/info/rfc6750").ok(),
+                primary: true,
+            }],
+            meta: ScimCoreMeta {
+                resource_type: "ServiceProviderConfig".to_string(),
+                location: origin
+                    .join(&format!("{SCIM_CORE_PATH_PREFIX}/ServiceProviderConfig"))
+                    .ok(),
+            },
+        }
+    }
+}
+
+/// https://datatracker.ietf.org/doc/html/rfc7643#section-6
+#[derive(Serialize, Debug, Clone, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ScimResourceType {
+    pub schemas: Vec<String>,
+    pub id: String,
+    pub name: String,
+    pub endpoint: String,
+    pub description: String,
+    pub schema: String,
+    pub meta: ScimCoreMeta,
+}
+
+impl ScimResourceType {
+    pub fn new(resource_type: ScimCoreResourceType, origin: &Url) -> Self {
+        let description = match resource_type {
+            ScimCoreResourceType::User => "A Kanidm person account",
+            ScimCoreResourceType::Group => "A Kanidm group",
+        };
+
+        ScimResourceType {
+            schemas: vec![SCIM_SCHEMA_RESOURCE_TYPE.to_string()],
+            id: resource_type.name().to_string(),
+            name: resource_type.name().to_string(),
+            endpoint: resource_type.endpoint().to_string(),
+            description: description.to_string(),
+            schema: resource_type.schema().to_string(),
+            meta: ScimCoreMeta {
+                resource_type: "ResourceType".to_string(),
+                location: origin
+                    .join(&format!(
+                        "{}/ResourceTypes/{}",
+                        SCIM_CORE_PATH_PREFIX,
+                        resource_type.name()
+                    ))
+                    .ok(),
+            },
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone, Copy, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub enum ScimSchemaAttributeType {
+    String,
+    Boolean,
+    Complex,
+    Reference,
+}
+
+#[derive(Serialize, Debug, Clone, Copy, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub enum ScimSchemaMutability {
+    ReadOnly,
+    ReadWrite,
+    Immutable,
+}
+
+#[derive(Serialize, Debug, Clone, Copy, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub enum ScimSchemaUniqueness {
+    None,
+    Server,
+}
+
+#[derive(Serialize, Debug, Clone, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ScimSchemaAttributeDefinition {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub type_: ScimSchemaAttributeType,
+    pub multi_valued: bool,
+    pub description: String,
+    pub required: bool,
+    pub case_exact: bool,
+    pub mutability: ScimSchemaMutability,
+    pub returned: String,
+    pub uniqueness: ScimSchemaUniqueness,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    #[schema(no_recursion)]
+    pub sub_attributes: Vec<ScimSchemaAttributeDefinition>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub reference_types: Vec<String>,
+}
+
+impl ScimSchemaAttributeDefinition {
+    fn new(name: &str, type_: ScimSchemaAttributeType, description: &str) -> Self {
+        ScimSchemaAttributeDefinition {
+            name: name.to_string(),
+            type_,
+            multi_valued: false,
+            description: description.to_string(),
+            required: false,
+            case_exact: false,
+            mutability: ScimSchemaMutability::ReadWrite,
+            returned: "default".to_string(),
+            uniqueness: ScimSchemaUniqueness::None,
+            sub_attributes: Vec::new(),
+            reference_types: Vec::new(),
+        }
+    }
+
+    fn required(mut self) -> Self {
+        self.required = true;
+        self
+    }
+
+    fn unique(mut self) -> Self {
+        self.uniqueness = ScimSchemaUniqueness::Server;
+        self
+    }
+
+    fn read_only(mut self) -> Self {
+        self.mutability = ScimSchemaMutability::ReadOnly;
+        self
+    }
+
+    fn multi_valued(mut self, sub_attributes: Vec<ScimSchemaAttributeDefinition>) -> Self {
+        self.multi_valued = true;
+        self.sub_attributes = sub_attributes;
+        self
+    }
+
+    fn references(mut self, reference_types: &[&str]) -> Self {
+        self.reference_types = reference_types.iter().map(|s| s.to_string()).collect();
+        self
+    }
+}
+
+/// The subset of a core schema that Kanidm supports.
+/// https://datatracker.ietf.org/doc/html/rfc7643#section-7
+#[derive(Serialize, Debug, Clone, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ScimSchemaDefinition {
+    pub schemas: Vec<String>,
+    pub id: String,
+    pub name: String,
+    pub description: String,
+    pub attributes: Vec<ScimSchemaAttributeDefinition>,
+    pub meta: ScimCoreMeta,
+}
+
+impl ScimSchemaDefinition {
+    pub fn new(resource_type: ScimCoreResourceType, origin: &Url) -> Self {
+        use ScimSchemaAttributeDefinition as Def;
+        use ScimSchemaAttributeType as Type;
+
+        let attributes = match resource_type {
+            ScimCoreResourceType::User => vec![
+                Def::new("userName", Type::String, "The name of the person.")
+                    .required()
+                    .unique(),
+                Def::new(
+                    "displayName",
+                    Type::String,
+                    "The display name of the person.",
+                ),
+                Def::new("emails", Type::Complex, "The mail addresses of the person.")
+                    .multi_valued(vec![
+                        Def::new("value", Type::String, "A mail address."),
+                        Def::new("primary", Type::Boolean, "The primary mail address."),
+                    ]),
+                Def::new(
+                    "active",
+                    Type::Boolean,
+                    "If the account is able to authenticate.",
+                ),
+                Def::new(
+                    "groups",
+                    Type::Complex,
+                    "The groups the person is a direct member of.",
+                )
+                .read_only()
+                .multi_valued(vec![
+                    Def::new("value", Type::String, "The id of the group.").read_only(),
+                    Def::new("$ref", Type::Reference, "The URI of the group.")
+                        .read_only()
+                        .references(&["Group"]),
+                    Def::new("display", Type::String, "The name of the group.").read_only(),
+                ]),
+            ],
+            ScimCoreResourceType::Group => vec![
+                Def::new("displayName", Type::String, "The name of the group.")
+                    .required()
+                    .unique(),
+                Def::new("members", Type::Complex, "The members of the group.").multi_valued(vec![
+                    Def::new("value", Type::String, "The id of the member.")
+                        .references(&["User", "Group"]),
+                    Def::new("display", Type::String, "The name of the member.").read_only(),
+                ]),
+            ],
+        };
+
+        ScimSchemaDefinition {
+            schemas: vec![SCIM_SCHEMA_SCHEMA.to_string()],
+            id: resource_type.schema().to_string(),
+            name: resource_type.name().to_string(),
+            description: format!("Kanidm {} resources", resource_type.name()),
+            attributes,
+            meta: ScimCoreMeta {
+                resource_type: "Schema".to_string(),
+                location: origin
+                    .join(&format!(
+                        "{}/Schemas/{}",
+                        SCIM_CORE_PATH_PREFIX,
+                        resource_type.schema()
+                    ))
+                    .ok(),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scim_v1::server::ScimReference;
+    use scim_proto::ScimEntryHeader;
+    use time::Duration;
+
+    #[test]
+    fn scim_core_user_to_kanidm() {
+        let now = OffsetDateTime::UNIX_EPOCH + Duration::days(20089);
+
+        let user: ScimCoreUser = serde_json::from_str(
+            r#"{
+                "schemas": ["urn:ietf:params:scim:schemas:core:2.0:User"],
+                "externalId": "701984",
+                "userName": "bjensen",
+                "name": { "givenName": "Barbara" },
+                "emails": [
+                    { "value": "bjensen@example.com", "type": "work", "primary": true },
+                    { "value": "babs@jensen.org", "type": "home" }
+                ],
+                "active": false,
+                "password": "t1meMa$heen"
+            }"#,
+        )
+        .expect("Failed to parse core user");
+
+        let post = user.to_kanidm_post(now).expect("Failed to map core user");
+
+        assert_eq!(
+            post.attrs.get(&Attribute::Name),
+            Some(&JsonValue::String("bjensen".to_string()))
+        );
+        // The display name defaults to the user name.
+        assert_eq!(
+            post.attrs.get(&Attribute::DisplayName),
+            Some(&JsonValue::String("bjensen".to_string()))
+        );
+        assert_eq!(
+            post.attrs.get(&Attribute::Mail),
+            Some(&serde_json::json!([
+                { "primary": true, "value": "bjensen@example.com" },
+                { "primary": false, "value": "babs@jensen.org" }
+            ]))
+        );
+        assert_eq!(
+            post.attrs.get(&Attribute::AccountExpire),
+            Some(&JsonValue::String("2025-01-01T00:00:00Z".to_string()))
+        );
+
+        // Put removes attributes that are absent so the resource is replaced.
+        let current = core_user_entry(Vec::new());
+        let user = ScimCoreUser {
+            user_name: "bjensen".to_string(),
+            ..Default::default()
+        };
+        let put = user
+            .to_kanidm_put(&current, now)
+            .expect("Failed to map core user");
+        assert_eq!(put.attrs.get(&Attribute::Mail), Some(&None));
+        assert_eq!(put.attrs.get(&Attribute::AccountExpire), None);
+    }
+
+    fn core_user_entry(attrs: Vec<(Attribute, ScimValueKanidm)>) -> ScimEntryKanidm {
+        ScimEntryKanidm {
+            header: ScimEntryHeader {
+                schemas: Vec::new(),
+                id: Uuid::new_v4(),
+                external_id: None,
+                meta: None,
+            },
+            ext_access_check: None,
+            attrs: attrs.into_iter().collect(),
+        }
+    }
+
+    #[test]
+    fn scim_core_user_put_active() {
+        let now = OffsetDateTime::UNIX_EPOCH + Duration::days(20089);
+        let now_json = JsonValue::String("2025-01-01T00:00:00Z".to_string());
+
+        let active_user = ScimCoreUser {
+            user_name: "bjensen".to_string(),
+            active: Some(true),
+            ..Default::default()
+        };
+        let inactive_user = ScimCoreUser {
+            active: Some(false),
+            ..active_user.clone()
+        };
+
+        // A future expiry set by an administrator is retained.
+        let current = core_user_entry(vec![(
+            Attribute::AccountExpire,
+            ScimValueKanidm::DateTime(now + Duration::days(31)),
+        )]);
+        let put = active_user
+            .to_kanidm_put(&current, now)
+            .expect("Failed to map core user");
+        assert_eq!(put.id, current.header.id);
+        assert_eq!(put.attrs.get(&Attribute::AccountExpire), None);
+
+        // Deactivating an active user expires it now.
+        let put = inactive_user
+            .to_kanidm_put(&current, now)
+            .expect("Failed to map core user");
+        assert_eq!(
+            put.attrs.get(&Attribute::AccountExpire),
+            Some(&Some(now_json))
+        );
+
+        // An inactive user keeps its original expiry.
+        let current = core_user_entry(vec![(
+            Attribute::AccountExpire,
+            ScimValueKanidm::DateTime(now - Duration::days(31)),
+        )]);
+        let put = inactive_user
+            .to_kanidm_put(&current, now)
+            .expect("Failed to map core user");
+        assert_eq!(put.attrs.get(&Attribute::AccountExpire), None);
+
+        // Activating an expired or not yet valid user removes the restriction.
+        let put = active_user
+            .to_kanidm_put(&current, now)
+            .expect("Failed to map core user");
+        assert_eq!(put.attrs.get(&Attribute::AccountExpire), Some(&None));
+        assert_eq!(put.attrs.get(&Attribute::AccountValidFrom), None);
+
+        let current = core_user_entry(vec![(
+            Attribute::AccountValidFrom,
+            ScimValueKanidm::DateTime(now + Duration::days(31)),
+        )]);
+        let put = active_user
+            .to_kanidm_put(&current, now)
+            .expect("Failed to map core user");
+        assert_eq!(put.attrs.get(&Attribute::AccountExpire), None);
+        assert_eq!(put.attrs.get(&Attribute::AccountValidFrom), Some(&None));
+    }
+
+    #[test]
+    fn scim_core_user_from_kanidm() {
+        let now = OffsetDateTime::UNIX_EPOCH + Duration::days(20089);
+        let origin = Url::parse("https://idm.example.com").expect("Invalid url");
+        let id = Uuid::new_v4();
+        let group_id = Uuid::new_v4();
+
+        let entry = ScimEntryKanidm {
+            header: ScimEntryHeader {
+                schemas: Vec::new(),
+                id,
+                external_id: None,
+                meta: None,
+            },
+            ext_access_check: None,
+            attrs: BTreeMap::from([
+                (
+                    Attribute::Name,
+                    ScimValueKanidm::String("bjensen".to_string()),
+                ),
+                (
+                    Attribute::Mail,
+                    ScimValueKanidm::Mail(vec![ScimMail {
+                        primary: true,
+                        value: "bjensen@example.com".to_string(),
+                    }]),
+                ),
+                (
+                    Attribute::AccountExpire,
+                    ScimValueKanidm::DateTime(now - Duration::days(31)),
+                ),
+                (
+                    Attribute::DirectMemberOf,
+                    ScimValueKanidm::EntryReferences(vec![ScimReference {
+                        uuid: group_id,
+                        value: "tour_guides@idm.example.com".to_string(),
+                    }]),
+                ),
+            ]),
+        };
+
+        let user = ScimCoreUser::from_kanidm(&entry, &origin, now).expect("Failed to map user");
+
+        assert_eq!(user.id, Some(id));
+        assert_eq!(user.user_name, "bjensen");
+        assert_eq!(user.active, Some(false));
+        assert_eq!(user.emails.len(), 1);
+        assert!(user.emails[0].primary);
+        assert_eq!(user.groups.len(), 1);
+        assert_eq!(user.groups[0].value, group_id.to_string());
+        assert_eq!(
+            user.groups[0]
+                .reference
+                .as_ref()
+                .map(|url| url.as_str().to_string()),
+            Some(format!("https://idm.example.com/scim/v2/Groups/{group_id}"))
+        );
+        assert_eq!(
+            user.meta.and_then(|meta| meta.location).map(String::from),
+            Some(format!("https://idm.example.com/scim/v2/Users/{id}"))
+        );
+    }
+
+    #[test]
+    fn scim_core_group_patch_to_kanidm() {
+        let now = OffsetDateTime::UNIX_EPOCH + Duration::days(20089);
+
+        let patch: ScimEntryPatchGeneric = serde_json::from_str(
+            r#"{
+                "schemas": ["urn:ietf:params:scim:api:messages:2.0:PatchOp"],
+                "Operations": [
+                    { "op": "Replace", "value": { "displayName": "tour_guides" } }
+                ]
+            }"#,
+        )
+        .expect("Failed to parse patch");
+
+        let patch = ScimCoreResourceType::Group
+            .patch_to_kanidm(patch, now)
+            .expect("Failed to map patch");
+
+        assert_eq!(patch.operations.len(), 1);
+        assert_eq!(patch.operations[0].op, ScimPatchOpType::Replace);
+        assert_eq!(
+            patch.operations[0].path.as_ref().map(|path| &path.a),
+            Some(&Attribute::Name)
+        );
+
+        // Users map displayName to their own attribute, and the sub attributes
+        // that Kanidm can't store are rejected.
+        let patch: ScimEntryPatchGeneric = serde_json::from_str(
+            r#"{
+                "schemas": ["urn:ietf:params:scim:api:messages:2.0:PatchOp"],
+                "Operations": [
+                    { "op": "replace", "path": "displayName", "value": "Babs Jensen" },
+                    { "op": "replace", "path": "displayName.primary", "value": true }
+                ]
+            }"#,
+        )
+        .expect("Failed to parse patch");
+
+        let err = ScimCoreResourceType::User
+            .patch_to_kanidm(patch, now)
+            .expect_err("Patch should have been rejected");
+        assert_eq!(err, OperationError::SC0036PatchPathUnsupported);
+    }
+}
@@ -15,6 +15,9 @@
 //!
 //! The server module, which describes how a server should transmit entries and
 //! how it should receive them.
+//!
+//! The core_schema module, which describes the RFC7643 core User and Group resources
+//! for clients that only understand the standard SCIM schemas.
 
 use crate::attribute::{Attribute, SubAttribute};
 use serde::{Deserialize, Serialize};
@@ -33,7 +36,13 @@ pub use self::synch::*;
 pub use scim_proto::prelude::*;
 pub use serde_json::Value as JsonValue;
 
+/// The maximum number of operations in a SCIM bulk request.
+pub const SCIM_BULK_MAX_OPERATIONS: usize = 1000;
+/// The maximum size in bytes of a SCIM bulk request.
+pub const SCIM_BULK_MAX_PAYLOAD_BYTES: usize = 1024 * 1024 * 16;
+
 pub mod client;
+pub mod core_schema;
 pub mod server;
 mod synch;
 
@@ -220,6 +229,29 @@ impl fmt::Display for ScimFilter {
     }
 }
 
+/// The target of a SCIM PATCH operation. This is an attribute, optionally with a
+/// filter to select which values of a multivalued attribute are affected, such as
+/// `members[value eq "2d0a9e7c-cc08-4ca2-8d7f-114f9abcfc8a"]`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScimPatchPath {
+    pub a: Attribute,
+    pub s: Option<SubAttribute>,
+    pub filter: Option<ScimComplexFilter>,
+}
+
+impl fmt::Display for ScimPatchPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.a)?;
+        if let Some(filter) = self.filter.as_ref() {
+            write!(f, "[{filter}]")?;
+        }
+        if let Some(subattr) = self.s.as_ref() {
+            write!(f, ".{subattr}")?;
+        }
+        Ok(())
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
 pub enum ScimComplexFilter {
     Or(Box<ScimComplexFilter>, Box<ScimComplexFilter>),
@@ -415,6 +447,12 @@ peg::parser! {
         rule unquotedvalue() -> JsonValue =
             s:$((!operator()[_])*) {? serde_json::from_str(s).map_err(|_| "invalid json value" ) }
 
+        pub(crate) rule patchpath() -> ScimPatchPath =
+            a:attrname() "[" e:parse_complex() "]" s:dot_subattr()? {
+                ScimPatchPath { a, s, filter: Some(e) }
+            }
+            / a:attrname() s:dot_subattr()? { ScimPatchPath { a, s, filter: None } }
+
         pub(crate) rule attrpath() -> AttrPath =
             a:attrname() s:dot_subattr()? { AttrPath { a, s } }
 
@@ -439,6 +477,13 @@ impl FromStr for AttrPath {
     }
 }
 
+impl FromStr for ScimPatchPath {
+    type Err = peg::error::ParseError<peg::str::LineCol>;
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        scimfilter::patchpath(input)
+    }
+}
+
 impl FromStr for ScimFilter {
     type Err = peg::error::ParseError<peg::str::LineCol>;
     fn from_str(input: &str) -> Result<Self, Self::Err> {
@@ -1020,4 +1065,39 @@ mod tests {
             ))
         );
     }
+
+    #[test]
+    fn test_scimfilter_patch_path() {
+        assert_eq!(
+            ScimPatchPath::from_str("displayname"),
+            Ok(ScimPatchPath {
+                a: Attribute::from("displayname"),
+                s: None,
+                filter: None,
+            })
+        );
+
+        assert_eq!(
+            ScimPatchPath::from_str("mail.primary"),
+            Ok(ScimPatchPath {
+                a: Attribute::from("mail"),
+                s: Some(SubAttribute::Primary),
+                filter: None,
+            })
+        );
+
+        assert_eq!(
+            ScimPatchPath::from_str(r#"member[value eq "extra_1"]"#),
+            Ok(ScimPatchPath {
+                a: Attribute::from("member"),
+                s: None,
+                filter: Some(ScimComplexFilter::Equal(
+                    SubAttribute::Value,
+                    JsonValue::String("extra_1".to_string())
+                )),
+            })
+        );
+
+        assert!(ScimPatchPath::from_str("member[value eq").is_err());
+    }
 }
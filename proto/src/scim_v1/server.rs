@@ -1,3 +1,4 @@
+use super::client::ScimBulkMethod;
 use super::ScimMail;
 use super::ScimOauth2ClaimMapJoinChar;
 use super::ScimSshPublicKey;
@@ -33,14 +34,14 @@ pub struct ScimEntryKanidm {
 }
 
 impl ScimEntryKanidm {
-    fn get_string_attr(&self, attr: &Attribute) -> Option<&String> {
+    pub(crate) fn get_string_attr(&self, attr: &Attribute) -> Option<&String> {
         self.attrs.get(attr).and_then(|v| match v {
             ScimValueKanidm::String(s) => Some(s),
             _ => None,
         })
     }
 
-    fn get_scim_refs_attr(&self, attr: &Attribute) -> Option<&Vec<ScimReference>> {
+    pub(crate) fn get_scim_refs_attr(&self, attr: &Attribute) -> Option<&Vec<ScimReference>> {
         let option = self.attrs.get(attr);
         option.and_then(|v| match v {
             ScimValueKanidm::EntryReferences(s) => Some(s),
@@ -64,6 +65,37 @@ pub struct ScimListResponse {
     pub resources: Vec<ScimEntryKanidm>,
 }
 
+/// https://datatracker.ietf.org/doc/html/rfc7644#section-3.12
+#[skip_serializing_none]
+#[derive(Serialize, Debug, Clone, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ScimErrorResponse {
+    pub schemas: Vec<String>,
+    /// The HTTP status code of the error, as a string.
+    pub status: String,
+    pub detail: Option<String>,
+}
+
+#[skip_serializing_none]
+#[derive(Serialize, Debug, Clone, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ScimBulkOperationResponse {
+    pub method: ScimBulkMethod,
+    pub bulk_id: Option<String>,
+    pub location: Option<Url>,
+    /// The HTTP status code of the operation, as a string.
+    pub status: String,
+    pub response: Option<ScimErrorResponse>,
+}
+
+/// The results of the operations of a SCIM bulk request that were applied.
+#[derive(Serialize, Debug, Clone, ToSchema)]
+pub struct ScimBulkResponse {
+    pub schemas: Vec<String>,
+    #[serde(rename = "Operations")]
+    pub operations: Vec<ScimBulkOperationResponse>,
+}
+
 #[derive(Serialize, Debug, Clone, ToSchema)]
 pub enum ScimAttributeEffectiveAccess {
     /// All attributes on the entry have this permission granted
@@ -4,6 +4,7 @@ use std::fmt;
 use utoipa::ToSchema;
 use uuid::Uuid;
 
+use crate::internal::CUIntentToken;
 use webauthn_rs_proto::PublicKeyCredential;
 use webauthn_rs_proto::RequestChallengeResponse;
 
@@ -51,6 +52,9 @@ pub enum AuthState {
     Denied(String),
     /// Everything is good, your bearer token has been issued and is within.
     Success(String),
+    /// Your password has expired and must be changed before a bearer token can be
+    /// issued. The enclosed credential update intent token allows you to change it.
+    CredentialUpdateRequired(CUIntentToken),
 }
 
 /// The credential challenge provided by a user.
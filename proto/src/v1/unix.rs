@@ -82,6 +82,10 @@ pub struct UnixUserToken {
     // The default value of bool is false.
     #[serde(default)]
     pub valid: bool,
+    /// If present, the password used to authenticate expires soon and should be
+    /// changed before this time.
+    #[serde(default, with = "time::serde::timestamp::option")]
+    pub password_expiry: Option<time::OffsetDateTime>,
 }
 
 impl Display for UnixUserToken {
@@ -5,17 +5,21 @@
 use crate::{QueryServerReadV1, QueryServerWriteV1};
 use crypto_glue::s256::Sha256Output;
 use kanidm_proto::internal::{
-    DomainInfo as ProtoDomainInfo, DomainUpgradeCheckReport as ProtoDomainUpgradeCheckReport,
+    CredentialSoftLockStatus as ProtoCredentialSoftLockStatus, DomainInfo as ProtoDomainInfo,
+    DomainUpgradeCheckReport as ProtoDomainUpgradeCheckReport,
 };
 use kanidm_proto::scim_v1::client::ScimAssertGeneric;
 use kanidmd_lib::prelude::*;
 use kanidmd_lib::{
     event::{PurgeDeleteAfterEvent, PurgeRecycledEvent, PurgeTombstoneEvent},
-    idm::delayed::DelayedAction,
+    idm::delayed::{DelayedAction, Oauth2BackchannelLogout},
     server::scim::ScimAssertEvent,
 };
 use tracing::{Instrument, Level};
 
+/// How long we wait for a client to accept a back-channel logout token.
+const OAUTH2_BACKCHANNEL_LOGOUT_TIMEOUT: Duration = Duration::from_secs(10);
+
 impl QueryServerReadV1 {
     #[instrument(
         level = "info",
@@ -44,6 +48,22 @@ impl QueryServerReadV1 {
 
         idms_prox_read.qs_read.domain_upgrade_check()
     }
+
+    #[instrument(
+        level = "info",
+        skip(self, eventid),
+        fields(uuid = ?eventid)
+    )]
+    pub(crate) async fn handle_admin_credential_lock_status(
+        &self,
+        name: String,
+        eventid: Uuid,
+    ) -> Result<Vec<ProtoCredentialSoftLockStatus>, OperationError> {
+        let ct = duration_from_epoch_now();
+        let mut idms_prox_read = self.idms.proxy_read().await?;
+
+        idms_prox_read.account_credential_softlock_status(name.as_str(), ct)
+    }
 }
 
 impl QueryServerWriteV1 {
@@ -124,6 +144,20 @@ impl QueryServerWriteV1 {
     }
 
     pub(crate) async fn handle_delayedaction(&self, da_batch: &mut Vec<DelayedAction>) {
+        // Back-channel logouts have nothing to write, they only need to be sent to the
+        // client. Do that in the background so a slow client can't stall this queue.
+        for da in da_batch.extract_if(.., |da| {
+            matches!(da, DelayedAction::Oauth2BackchannelLogout(_))
+        }) {
+            if let DelayedAction::Oauth2BackchannelLogout(backchannel_logout) = da {
+                tokio::spawn(send_oauth2_backchannel_logout(backchannel_logout));
+            }
+        }
+
+        if da_batch.is_empty() {
+            return;
+        }
+
         let eventid = Uuid::new_v4();
         let span = span!(Level::INFO, "process_delayed_action", uuid = ?eventid);
 
@@ -221,6 +255,23 @@ impl QueryServerWriteV1 {
         idms_prox_write.commit()
     }
 
+    #[instrument(
+        level = "info",
+        skip(self, eventid),
+        fields(uuid = ?eventid)
+    )]
+    pub(crate) async fn handle_admin_credential_unlock(
+        &self,
+        name: String,
+        eventid: Uuid,
+    ) -> Result<(), OperationError> {
+        let ct = duration_from_epoch_now();
+        let mut idms_prox_write = self.idms.proxy_write(ct).await?;
+        idms_prox_write.unlock_account_credentials(name.as_str())?;
+
+        idms_prox_write.commit()
+    }
+
     #[instrument(
         level = "info",
         skip_all,
@@ -275,3 +326,52 @@ impl QueryServerWriteV1 {
         idms_prox_write.commit()
     }
 }
+
+#[instrument(
+    level = "info",
+    skip_all,
+    fields(rs_uuid = ?backchannel_logout.rs_uuid, session_id = ?backchannel_logout.session_id)
+)]
+async fn send_oauth2_backchannel_logout(backchannel_logout: Oauth2BackchannelLogout) {
+    let Oauth2BackchannelLogout {
+        backchannel_logout_uri,
+        logout_token,
+        ..
+    } = backchannel_logout;
+
+    let client = match reqwest::ClientBuilder::new()
+        .timeout(OAUTH2_BACKCHANNEL_LOGOUT_TIMEOUT)
+        .build()
+    {
+        Ok(client) => client,
+        Err(err) => {
+            error!(?err, "Invalid oauth2 http client builder parameters");
+            return;
+        }
+    };
+
+    match client
+        .post(backchannel_logout_uri.as_str())
+        .form(&[("logout_token", logout_token.as_str())])
+        .send()
+        .await
+    {
+        Ok(res) if res.status().is_success() => {
+            debug!(%backchannel_logout_uri, "Delivered oauth2 back-channel logout");
+        }
+        Ok(res) => {
+            warn!(
+                status = ?res.status(),
+                %backchannel_logout_uri,
+                "Client rejected oauth2 back-channel logout"
+            );
+        }
+        Err(err) => {
+            warn!(
+                ?err,
+                %backchannel_logout_uri,
+                "Unable to deliver oauth2 back-channel logout"
+            );
+        }
+    }
+}
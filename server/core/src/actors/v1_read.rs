@@ -1,9 +1,10 @@
 use compact_jwt::{JweCompact, Jwk, JwsCompact};
 use kanidm_proto::backup::BackupCompression;
 use kanidm_proto::internal::{
-    ApiToken, AppLink, CURequest, CUSessionToken, CUStatus, CredentialStatus, IdentifyUserRequest,
-    IdentifyUserResponse, ImageValue, OperationError, RadiusAuthToken, SearchRequest,
-    SearchResponse, UserAuthToken,
+    ApiToken, AppLink, CURequest, CUSessionToken, CUStatus, CredentialSoftLockAccount,
+    CredentialSoftLockStatus, CredentialStatus, IdentifyUserRequest, IdentifyUserResponse,
+    ImageValue, Oauth2Consent, OperationError, RadiusAuthToken, SearchRequest, SearchResponse,
+    UserAuthToken,
 };
 use kanidm_proto::oauth2::OidcWebfingerResponse;
 use kanidm_proto::v1::{
@@ -21,14 +22,15 @@ use kanidmd_lib::{
     idm::authentication::AuthStep,
     idm::credupdatesession::CredentialUpdateSessionToken,
     idm::event::{
-        AuthEvent, AuthResult, CredentialStatusEvent, RadiusAuthTokenEvent, UnixGroupTokenEvent,
-        UnixUserAuthEvent, UnixUserTokenEvent,
+        AuthEvent, AuthResult, CredentialSoftLockEvent, CredentialStatusEvent,
+        RadiusAuthTokenEvent, UnixGroupTokenEvent, UnixUserAuthEvent, UnixUserTokenEvent,
     },
-    idm::ldap::{LdapBoundToken, LdapResponseState},
+    idm::ldap::{LdapBoundToken, LdapResponseState, LdapSaslBindRequest, LdapWriteOps},
     idm::oauth2::{
-        AccessTokenIntrospectRequest, AccessTokenIntrospectResponse, AuthorisationRequest,
-        AuthoriseReject, AuthoriseResponse, JwkKeySet, Oauth2Error, Oauth2Rfc8414MetadataResponse,
-        OidcDiscoveryResponse, OidcToken,
+        AccessTokenIntrospectRequest, AccessTokenIntrospectResponse, AuthorisationRequestParams,
+        AuthoriseReject, AuthoriseResponse, ClientRegistrationResponse, JwkKeySet, Oauth2Error,
+        Oauth2Rfc8414MetadataResponse, OidcDiscoveryResponse, PendingBackchannelAuthorisation,
+        UserinfoResponse,
     },
     idm::server::{DomainInfoRead, IdmServerTransaction},
     idm::serviceaccount::ListApiTokenEvent,
@@ -1119,6 +1121,63 @@ impl QueryServerReadV1 {
         idms_prox_read.get_credentialstatus(&cse)
     }
 
+    #[instrument(
+        level = "info",
+        skip_all,
+        fields(uuid = ?eventid)
+    )]
+    pub async fn handle_idmcredentialsoftlockstatus(
+        &self,
+        client_auth_info: ClientAuthInfo,
+        uuid_or_name: String,
+        eventid: Uuid,
+    ) -> Result<Vec<CredentialSoftLockStatus>, OperationError> {
+        let ct = duration_from_epoch_now();
+        let mut idms_prox_read = self.idms.proxy_read().await?;
+
+        let ident = idms_prox_read
+            .validate_client_auth_info_to_ident(client_auth_info, ct)
+            .inspect_err(|err| {
+                error!(?err, "Invalid identity");
+            })?;
+        let target_uuid = idms_prox_read
+            .qs_read
+            .name_to_uuid(uuid_or_name.as_str())
+            .inspect_err(|err| {
+                error!(?err, "Error resolving id to target");
+            })?;
+
+        let csle = CredentialSoftLockEvent::from_parts(ident, target_uuid).inspect_err(|err| {
+            error!(?err, "Failed to begin credential soft lock status read");
+        })?;
+
+        trace!(?csle, "Begin event");
+
+        idms_prox_read.get_credential_softlock_status(&csle, ct)
+    }
+
+    #[instrument(
+        level = "info",
+        skip_all,
+        fields(uuid = ?eventid)
+    )]
+    pub async fn handle_list_credential_softlocked(
+        &self,
+        client_auth_info: ClientAuthInfo,
+        eventid: Uuid,
+    ) -> Result<Vec<CredentialSoftLockAccount>, OperationError> {
+        let ct = duration_from_epoch_now();
+        let mut idms_prox_read = self.idms.proxy_read().await?;
+
+        let ident = idms_prox_read
+            .validate_client_auth_info_to_ident(client_auth_info, ct)
+            .inspect_err(|err| {
+                error!(?err, "Invalid identity");
+            })?;
+
+        idms_prox_read.list_credential_softlocked(&ident, ct)
+    }
+
     #[instrument(
         level = "info",
         skip_all,
@@ -1344,7 +1403,7 @@ impl QueryServerReadV1 {
     pub async fn handle_oauth2_authorise(
         &self,
         client_auth_info: ClientAuthInfo,
-        auth_req: AuthorisationRequest,
+        auth_req_params: AuthorisationRequestParams,
         eventid: Uuid,
     ) -> Result<AuthoriseResponse, Oauth2Error> {
         let ct = duration_from_epoch_now();
@@ -1361,7 +1420,7 @@ impl QueryServerReadV1 {
             .ok();
 
         // Now we can send to the idm server for authorisation checking.
-        idms_prox_read.check_oauth2_authorisation(ident.as_ref(), &auth_req, ct)
+        idms_prox_read.check_oauth2_authorisation_params(ident.as_ref(), &auth_req_params, ct)
     }
 
     #[instrument(
@@ -1417,15 +1476,24 @@ impl QueryServerReadV1 {
         &self,
         client_id: String,
         token: &JwsCompact,
+        dpop_proof: Option<&DpopProof>,
+        client_cert: Option<&ClientCertInfo>,
         eventid: Uuid,
-    ) -> Result<OidcToken, Oauth2Error> {
+    ) -> Result<UserinfoResponse, Oauth2Error> {
         let ct = duration_from_epoch_now();
         let mut idms_prox_read = self
             .idms
             .proxy_read()
             .await
             .map_err(Oauth2Error::ServerError)?;
-        idms_prox_read.oauth2_openid_userinfo(&client_id, token, ct)
+        let userinfo = idms_prox_read.oauth2_openid_userinfo(
+            &client_id,
+            token,
+            dpop_proof,
+            client_cert,
+            ct,
+        )?;
+        idms_prox_read.oauth2_openid_userinfo_response(&client_id, userinfo)
     }
 
     #[instrument(
@@ -1485,6 +1553,25 @@ impl QueryServerReadV1 {
         idms_prox_read.oauth2_openid_publickey(&client_id)
     }
 
+    #[instrument(
+        level = "info",
+        skip_all,
+        fields(uuid = ?eventid)
+    )]
+    pub async fn handle_oauth2_client_registration_read(
+        &self,
+        client_auth_info: ClientAuthInfo,
+        client_id: String,
+        eventid: Uuid,
+    ) -> Result<ClientRegistrationResponse, Oauth2Error> {
+        let mut idms_prox_read = self
+            .idms
+            .proxy_read()
+            .await
+            .map_err(Oauth2Error::ServerError)?;
+        idms_prox_read.oauth2_client_registration_read(&client_id, &client_auth_info)
+    }
+
     #[instrument(
         level = "info",
         skip_all,
@@ -1508,6 +1595,50 @@ impl QueryServerReadV1 {
         idms_prox_read.list_applinks(&ident)
     }
 
+    #[instrument(
+        level = "info",
+        skip_all,
+        fields(uuid = ?eventid)
+    )]
+    pub async fn handle_list_oauth2_backchannel_authorisations(
+        &self,
+        client_auth_info: ClientAuthInfo,
+        eventid: Uuid,
+    ) -> Result<Vec<PendingBackchannelAuthorisation>, OperationError> {
+        let ct = duration_from_epoch_now();
+        let mut idms_prox_read = self.idms.proxy_read().await?;
+        let ident = idms_prox_read
+            .validate_client_auth_info_to_ident(client_auth_info, ct)
+            .map_err(|e| {
+                error!("Invalid identity: {:?}", e);
+                e
+            })?;
+
+        Ok(idms_prox_read.list_oauth2_backchannel_authorisations(&ident, ct))
+    }
+
+    #[instrument(
+        level = "info",
+        skip_all,
+        fields(uuid = ?eventid)
+    )]
+    pub async fn handle_list_oauth2_consents(
+        &self,
+        client_auth_info: ClientAuthInfo,
+        eventid: Uuid,
+    ) -> Result<Vec<Oauth2Consent>, OperationError> {
+        let ct = duration_from_epoch_now();
+        let mut idms_prox_read = self.idms.proxy_read().await?;
+        let ident = idms_prox_read
+            .validate_client_auth_info_to_ident(client_auth_info, ct)
+            .map_err(|e| {
+                error!("Invalid identity: {:?}", e);
+                e
+            })?;
+
+        idms_prox_read.list_oauth2_consents(&ident, ct)
+    }
+
     #[instrument(
         level = "info",
         skip_all,
@@ -1556,24 +1687,60 @@ impl QueryServerReadV1 {
         eventid: Uuid,
         protomsg: LdapMsg,
         uat: Option<LdapBoundToken>,
+        client_cert: Option<&ClientCertInfo>,
         ip_addr: IpAddr,
     ) -> Option<LdapResponseState> {
-        let res = match ServerOps::try_from(protomsg) {
-            Ok(server_op) => self
+        let protomsg = match LdapSaslBindRequest::try_from(protomsg) {
+            Ok(sasl_bind) => {
+                let res = self
+                    .ldap
+                    .do_sasl_bind(&self.idms, sasl_bind, client_cert)
+                    .await
+                    .unwrap_or_else(|e| {
+                        error!("do_sasl_bind failed -> {:?}", e);
+                        LdapResponseState::Disconnect(DisconnectionNotice::gen(
+                            LdapResultCode::Other,
+                            format!("Internal Server Error {:?}", &eventid).as_str(),
+                        ))
+                    });
+                return Some(res);
+            }
+            Err(protomsg) => protomsg,
+        };
+
+        let res = match LdapWriteOps::try_from(protomsg) {
+            Ok(write_op) => self
                 .ldap
-                .do_op(&self.idms, server_op, uat, ip_addr, eventid)
+                .do_write_op(&self.idms, write_op, uat, ip_addr)
                 .await
                 .unwrap_or_else(|e| {
-                    error!("do_op failed -> {:?}", e);
+                    error!("do_write_op failed -> {:?}", e);
                     LdapResponseState::Disconnect(DisconnectionNotice::gen(
                         LdapResultCode::Other,
                         format!("Internal Server Error {:?}", &eventid).as_str(),
                     ))
                 }),
-            Err(_) => LdapResponseState::Disconnect(DisconnectionNotice::gen(
-                LdapResultCode::ProtocolError,
-                format!("Invalid Request {:?}", &eventid).as_str(),
-            )),
+            Err(protomsg) => {
+                // Controls are not retained by the server op, so we take them first.
+                let controls = protomsg.ctrl.clone();
+                match ServerOps::try_from(protomsg) {
+                    Ok(server_op) => self
+                        .ldap
+                        .do_op(&self.idms, server_op, &controls, uat, ip_addr, eventid)
+                        .await
+                        .unwrap_or_else(|e| {
+                            error!("do_op failed -> {:?}", e);
+                            LdapResponseState::Disconnect(DisconnectionNotice::gen(
+                                LdapResultCode::Other,
+                                format!("Internal Server Error {:?}", &eventid).as_str(),
+                            ))
+                        }),
+                    Err(_) => LdapResponseState::Disconnect(DisconnectionNotice::gen(
+                        LdapResultCode::ProtocolError,
+                        format!("Invalid Request {:?}", &eventid).as_str(),
+                    )),
+                }
+            }
         };
         Some(res)
     }
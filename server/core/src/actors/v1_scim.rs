@@ -1,6 +1,6 @@
 use super::{QueryServerReadV1, QueryServerWriteV1};
 use kanidm_proto::scim_v1::{
-    client::{ScimEntryPostGeneric, ScimEntryPutGeneric},
+    client::{ScimBulkRequest, ScimEntryPatchGeneric, ScimEntryPostGeneric, ScimEntryPutGeneric},
     server::{ScimEntryKanidm, ScimListResponse},
     ScimApplicationPassword, ScimApplicationPasswordCreate, ScimEntryGetQuery, ScimFilter,
     ScimSyncRequest, ScimSyncState,
@@ -11,7 +11,10 @@ use kanidmd_lib::idm::scim::{
 };
 use kanidmd_lib::idm::server::IdmServerTransaction;
 use kanidmd_lib::prelude::*;
-use kanidmd_lib::server::scim::{ScimCreateEvent, ScimDeleteEvent, ScimEntryPutEvent};
+use kanidmd_lib::server::scim::{
+    ScimBulkEvent, ScimBulkOperationOutcome, ScimCreateEvent, ScimDeleteEvent, ScimEntryPatchEvent,
+    ScimEntryPutEvent,
+};
 
 impl QueryServerWriteV1 {
     #[instrument(
@@ -398,6 +401,79 @@ impl QueryServerWriteV1 {
             .scim_put(scim_entry_put_event)
             .and_then(|r| idms_prox_write.commit().map(|_| r))
     }
+
+    #[instrument(
+        level = "info",
+        skip_all,
+        fields(uuid = ?eventid)
+    )]
+    pub async fn handle_scim_entry_patch(
+        &self,
+        client_auth_info: ClientAuthInfo,
+        eventid: Uuid,
+        uuid_or_name: String,
+        query: ScimEntryGetQuery,
+        generic: ScimEntryPatchGeneric,
+    ) -> Result<ScimEntryKanidm, OperationError> {
+        let ct = duration_from_epoch_now();
+        let mut idms_prox_write = self.idms.proxy_write(ct).await?;
+        let ident = idms_prox_write
+            .validate_client_auth_info_to_ident(client_auth_info, ct)
+            .map_err(|op_err| {
+                admin_error!(err = ?op_err, "Invalid identity");
+                op_err
+            })?;
+
+        let target = idms_prox_write
+            .qs_write
+            .name_to_uuid(uuid_or_name.as_str())
+            .map_err(|e| {
+                admin_error!(err = ?e, "Error resolving id to target");
+                e
+            })?;
+
+        let scim_entry_patch_event = ScimEntryPatchEvent::try_from(
+            ident,
+            target,
+            generic,
+            &query,
+            &mut idms_prox_write.qs_write,
+        )?;
+
+        idms_prox_write
+            .qs_write
+            .scim_patch(scim_entry_patch_event)
+            .and_then(|r| idms_prox_write.commit().map(|_| r))
+    }
+
+    #[instrument(
+        level = "info",
+        skip_all,
+        fields(uuid = ?eventid)
+    )]
+    pub async fn handle_scim_bulk(
+        &self,
+        client_auth_info: ClientAuthInfo,
+        eventid: Uuid,
+        request: ScimBulkRequest,
+    ) -> Result<Vec<ScimBulkOperationOutcome>, OperationError> {
+        let scim_bulk_event = ScimBulkEvent::try_from(request)?;
+
+        let ct = duration_from_epoch_now();
+        let mut idms_prox_write = self.idms.proxy_write(ct).await?;
+        let ident = idms_prox_write
+            .validate_client_auth_info_to_ident(client_auth_info, ct)
+            .map_err(|op_err| {
+                admin_error!(err = ?op_err, "Invalid identity");
+                op_err
+            })?;
+
+        let outcomes = idms_prox_write
+            .qs_write
+            .scim_bulk(&ident, &scim_bulk_event)?;
+
+        idms_prox_write.commit().map(|_| outcomes)
+    }
 }
 
 impl QueryServerReadV1 {
@@ -21,9 +21,15 @@ use kanidmd_lib::{
         InitCredentialUpdateEvent, InitCredentialUpdateIntentEvent,
         InitCredentialUpdateIntentSendEvent,
     },
-    idm::event::{GeneratePasswordEvent, RegenerateRadiusSecretEvent, UnixPasswordChangeEvent},
+    idm::event::{
+        CredentialSoftLockEvent, GeneratePasswordEvent, RegenerateRadiusSecretEvent,
+        UnixPasswordChangeEvent,
+    },
     idm::oauth2::{
-        AccessTokenRequest, AccessTokenResponse, AuthorisePermitSuccess, Oauth2Error,
+        AccessTokenRequest, AccessTokenResponse, AuthorisationRequestParams,
+        AuthorisePermitSuccess, BackchannelAuthenticationRequest,
+        BackchannelAuthenticationResponse, ClientMetadata, ClientPostAuth,
+        ClientRegistrationResponse, EndSessionRequest, Oauth2Error, PushedAuthorisationResponse,
         TokenRevokeRequest,
     },
     idm::server::IdmServerTransaction,
@@ -1249,6 +1255,41 @@ impl QueryServerWriteV1 {
             .map(|_| ())
     }
 
+    #[instrument(
+        level = "info",
+        skip_all,
+        fields(uuid = ?eventid)
+    )]
+    pub async fn handle_idmcredentialsoftlockunlock(
+        &self,
+        client_auth_info: ClientAuthInfo,
+        uuid_or_name: String,
+        eventid: Uuid,
+    ) -> Result<(), OperationError> {
+        let ct = duration_from_epoch_now();
+        let mut idms_prox_write = self.idms.proxy_write(ct).await?;
+        let ident = idms_prox_write
+            .validate_client_auth_info_to_ident(client_auth_info, ct)
+            .inspect_err(|err| {
+                error!(?err, "Invalid identity");
+            })?;
+
+        let target_uuid = idms_prox_write
+            .qs_write
+            .name_to_uuid(uuid_or_name.as_str())
+            .inspect_err(|err| {
+                error!(?err, "Error resolving id to target");
+            })?;
+
+        let csle = CredentialSoftLockEvent::from_parts(ident, target_uuid).inspect_err(|err| {
+            error!(?err, "Failed to begin credential soft lock unlock");
+        })?;
+
+        idms_prox_write
+            .credential_softlock_unlock(&csle)
+            .and_then(|_| idms_prox_write.commit())
+    }
+
     #[instrument(level = "debug", skip_all)]
     pub async fn handle_image_update(
         &self,
@@ -1402,6 +1443,121 @@ impl QueryServerWriteV1 {
             .and_then(|_| idms_prox_write.commit().map(|_| ()))
     }
 
+    #[instrument(
+        level = "info",
+        skip_all,
+        fields(uuid = ?eventid)
+    )]
+    pub async fn handle_service_account_oauth2_registration_scopemap_update(
+        &self,
+        client_auth_info: ClientAuthInfo,
+        group: String,
+        scopes: Vec<String>,
+        filter: Filter<FilterInvalid>,
+        eventid: Uuid,
+    ) -> Result<(), OperationError> {
+        let ct = duration_from_epoch_now();
+        let mut idms_prox_write = self.idms.proxy_write(ct).await?;
+
+        let ident = idms_prox_write
+            .validate_client_auth_info_to_ident(client_auth_info, ct)
+            .map_err(|e| {
+                error!(err = ?e, "Invalid identity");
+                e
+            })?;
+
+        let group_uuid = idms_prox_write
+            .qs_write
+            .name_to_uuid(group.as_str())
+            .map_err(|e| {
+                error!(err = ?e, "Error resolving group name to target");
+                e
+            })?;
+
+        let ml = ModifyList::new_append(
+            Attribute::OAuth2RegistrationScopeMap,
+            Value::new_oauthscopemap(group_uuid, scopes.into_iter().collect()).ok_or_else(
+                || OperationError::InvalidAttribute("Invalid Oauth Scope Map syntax".to_string()),
+            )?,
+        );
+
+        let mdf = match ModifyEvent::from_internal_parts(
+            ident,
+            &ml,
+            &filter,
+            &idms_prox_write.qs_write,
+        ) {
+            Ok(m) => m,
+            Err(e) => {
+                error!(err = ?e, "Failed to begin modify");
+                return Err(e);
+            }
+        };
+
+        trace!(?mdf, "Begin modify event");
+
+        idms_prox_write
+            .qs_write
+            .modify(&mdf)
+            .and_then(|_| idms_prox_write.commit().map(|_| ()))
+    }
+
+    #[instrument(
+        level = "info",
+        skip_all,
+        fields(uuid = ?eventid)
+    )]
+    pub async fn handle_service_account_oauth2_registration_scopemap_delete(
+        &self,
+        client_auth_info: ClientAuthInfo,
+        group: String,
+        filter: Filter<FilterInvalid>,
+        eventid: Uuid,
+    ) -> Result<(), OperationError> {
+        let ct = duration_from_epoch_now();
+        let mut idms_prox_write = self.idms.proxy_write(ct).await?;
+
+        let ident = idms_prox_write
+            .validate_client_auth_info_to_ident(client_auth_info, ct)
+            .map_err(|e| {
+                error!(err = ?e, "Invalid identity");
+                e
+            })?;
+
+        let group_uuid = idms_prox_write
+            .qs_write
+            .name_to_uuid(group.as_str())
+            .map_err(|e| {
+                error!(err = ?e, "Error resolving group name to target");
+                e
+            })?;
+
+        let ml = ModifyList::new_remove(
+            Attribute::OAuth2RegistrationScopeMap,
+            PartialValue::Refer(group_uuid),
+        );
+
+        let mdf = match ModifyEvent::from_internal_parts(
+            ident,
+            &ml,
+            &filter,
+            &idms_prox_write.qs_write,
+        ) {
+            Ok(m) => m,
+            Err(e) => {
+                error!(err = ?e, "Failed to begin modify");
+                return Err(e);
+            }
+        };
+
+        trace!(?mdf, "Begin modify event");
+
+        idms_prox_write
+            .qs_write
+            .modify(&mdf)
+            .and_then(|_| idms_prox_write.commit().map(|_| ()))
+    }
+
     #[instrument(
         level = "info",
         skip_all,
@@ -1751,6 +1907,197 @@ impl QueryServerWriteV1 {
         resp
     }
 
+    #[instrument(
+        level = "info",
+        skip_all,
+        fields(uuid = ?eventid)
+    )]
+    pub async fn handle_oauth2_pushed_authorisation(
+        &self,
+        client_auth_info: ClientAuthInfo,
+        client_post_auth: ClientPostAuth,
+        auth_req_params: AuthorisationRequestParams,
+        eventid: Uuid,
+    ) -> Result<PushedAuthorisationResponse, Oauth2Error> {
+        let ct = duration_from_epoch_now();
+        let mut idms_prox_write = self
+            .idms
+            .proxy_write(ct)
+            .await
+            .map_err(Oauth2Error::ServerError)?;
+        let resp = idms_prox_write.oauth2_pushed_authorisation(
+            &client_auth_info,
+            &client_post_auth,
+            &auth_req_params,
+            ct,
+        )?;
+
+        idms_prox_write.commit().map_err(Oauth2Error::ServerError)?;
+        Ok(resp)
+    }
+
+    #[instrument(
+        level = "info",
+        skip_all,
+        fields(uuid = ?eventid)
+    )]
+    pub async fn handle_oauth2_backchannel_authentication(
+        &self,
+        client_auth_info: ClientAuthInfo,
+        client_post_auth: ClientPostAuth,
+        bc_req: BackchannelAuthenticationRequest,
+        eventid: Uuid,
+    ) -> Result<BackchannelAuthenticationResponse, Oauth2Error> {
+        let ct = duration_from_epoch_now();
+        let mut idms_prox_write = self
+            .idms
+            .proxy_write(ct)
+            .await
+            .map_err(Oauth2Error::ServerError)?;
+        let resp = idms_prox_write.oauth2_backchannel_authentication(
+            &client_auth_info,
+            &client_post_auth,
+            &bc_req,
+            ct,
+        )?;
+
+        idms_prox_write.commit().map_err(Oauth2Error::ServerError)?;
+        Ok(resp)
+    }
+
+    #[instrument(
+        level = "info",
+        skip_all,
+        fields(uuid = ?eventid)
+    )]
+    pub async fn handle_oauth2_backchannel_authorisation_decide(
+        &self,
+        client_auth_info: ClientAuthInfo,
+        auth_req_id: Uuid,
+        approve: bool,
+        eventid: Uuid,
+    ) -> Result<(), OperationError> {
+        let ct = duration_from_epoch_now();
+        let mut idms_prox_write = self.idms.proxy_write(ct).await?;
+
+        let ident = idms_prox_write
+            .validate_client_auth_info_to_ident(client_auth_info, ct)
+            .inspect_err(|err| {
+                error!(?err, "Invalid identity");
+            })?;
+
+        idms_prox_write
+            .oauth2_backchannel_authorisation_decide(&ident, auth_req_id, approve, ct)
+            .and_then(|()| idms_prox_write.commit())
+    }
+
+    #[instrument(
+        level = "info",
+        skip_all,
+        fields(uuid = ?eventid)
+    )]
+    pub async fn handle_oauth2_consent_revoke(
+        &self,
+        client_auth_info: ClientAuthInfo,
+        client_id: String,
+        eventid: Uuid,
+    ) -> Result<(), OperationError> {
+        let ct = duration_from_epoch_now();
+        let mut idms_prox_write = self.idms.proxy_write(ct).await?;
+
+        let ident = idms_prox_write
+            .validate_client_auth_info_to_ident(client_auth_info, ct)
+            .inspect_err(|err| {
+                error!(?err, "Invalid identity");
+            })?;
+
+        idms_prox_write
+            .oauth2_consent_revoke(&ident, &client_id)
+            .and_then(|()| idms_prox_write.commit())
+    }
+
+    #[instrument(
+        level = "info",
+        skip_all,
+        fields(uuid = ?eventid)
+    )]
+    pub async fn handle_oauth2_client_register(
+        &self,
+        client_auth_info: ClientAuthInfo,
+        metadata: ClientMetadata,
+        eventid: Uuid,
+    ) -> Result<ClientRegistrationResponse, Oauth2Error> {
+        let ct = duration_from_epoch_now();
+        let mut idms_prox_write = self
+            .idms
+            .proxy_write(ct)
+            .await
+            .map_err(Oauth2Error::ServerError)?;
+
+        // The initial access token is an api token of the registering service account.
+        let ident = idms_prox_write
+            .validate_client_auth_info_to_ident(client_auth_info, ct)
+            .map_err(|e| {
+                error!(err = ?e, "Invalid identity");
+                Oauth2Error::AuthenticationRequired
+            })?;
+
+        let resp = idms_prox_write.oauth2_client_register(&ident, &metadata, ct)?;
+
+        idms_prox_write.commit().map_err(Oauth2Error::ServerError)?;
+        Ok(resp)
+    }
+
+    #[instrument(
+        level = "info",
+        skip_all,
+        fields(uuid = ?eventid)
+    )]
+    pub async fn handle_oauth2_client_registration_update(
+        &self,
+        client_auth_info: ClientAuthInfo,
+        client_id: String,
+        metadata: ClientMetadata,
+        eventid: Uuid,
+    ) -> Result<ClientRegistrationResponse, Oauth2Error> {
+        let ct = duration_from_epoch_now();
+        let mut idms_prox_write = self
+            .idms
+            .proxy_write(ct)
+            .await
+            .map_err(Oauth2Error::ServerError)?;
+        let resp = idms_prox_write.oauth2_client_registration_update(
+            &client_id,
+            &client_auth_info,
+            &metadata,
+        )?;
+
+        idms_prox_write.commit().map_err(Oauth2Error::ServerError)?;
+        Ok(resp)
+    }
+
+    #[instrument(
+        level = "info",
+        skip_all,
+        fields(uuid = ?eventid)
+    )]
+    pub async fn handle_oauth2_client_registration_delete(
+        &self,
+        client_auth_info: ClientAuthInfo,
+        client_id: String,
+        eventid: Uuid,
+    ) -> Result<(), Oauth2Error> {
+        let ct = duration_from_epoch_now();
+        let mut idms_prox_write = self
+            .idms
+            .proxy_write(ct)
+            .await
+            .map_err(Oauth2Error::ServerError)?;
+        idms_prox_write
+            .oauth2_client_registration_delete(&client_id, &client_auth_info)
+            .and_then(|()| idms_prox_write.commit().map_err(Oauth2Error::ServerError))
+    }
+
     #[instrument(
         level = "info",
         skip_all,
@@ -1773,6 +2120,34 @@ impl QueryServerWriteV1 {
             .and_then(|()| idms_prox_write.commit().map_err(Oauth2Error::ServerError))
     }
 
+    #[instrument(
+        level = "info",
+        skip_all,
+        fields(uuid = ?eventid)
+    )]
+    pub async fn handle_oauth2_openid_end_session(
+        &self,
+        client_auth_info: ClientAuthInfo,
+        client_id: String,
+        end_session_req: EndSessionRequest,
+        eventid: Uuid,
+    ) -> Result<Option<Url>, Oauth2Error> {
+        let ct = duration_from_epoch_now();
+        let mut idms_prox_write = self
+            .idms
+            .proxy_write(ct)
+            .await
+            .map_err(Oauth2Error::ServerError)?;
+        idms_prox_write
+            .oauth2_openid_end_session(&client_id, client_auth_info, &end_session_req, ct)
+            .and_then(|redirect_uri| {
+                idms_prox_write
+                    .commit()
+                    .map(|()| redirect_uri)
+                    .map_err(Oauth2Error::ServerError)
+            })
+    }
+
     #[cfg(feature = "dev-oauth2-device-flow")]
     pub async fn handle_oauth2_device_flow_start(
         &self,
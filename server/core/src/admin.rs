@@ -17,7 +17,8 @@ use tracing::{span, Instrument, Level};
 use uuid::Uuid;
 
 pub use kanidm_proto::internal::{
-    DomainInfo as ProtoDomainInfo, DomainUpgradeCheckReport as ProtoDomainUpgradeCheckReport,
+    CredentialSoftLockStatus as ProtoCredentialSoftLockStatus, DomainInfo as ProtoDomainInfo,
+    DomainUpgradeCheckReport as ProtoDomainUpgradeCheckReport,
     DomainUpgradeCheckStatus as ProtoDomainUpgradeCheckStatus,
 };
 
@@ -25,6 +26,8 @@ pub use kanidm_proto::internal::{
 pub enum AdminTaskRequest {
     RecoverAccount { name: String },
     DisableAccount { name: String },
+    CredentialLockStatus { name: String },
+    CredentialUnlock { name: String },
     ShowReplicationCertificate,
     RenewReplicationCertificate,
     RefreshReplicationConsumer,
@@ -40,6 +43,9 @@ pub enum AdminTaskResponse {
     RecoverAccount {
         password: String,
     },
+    CredentialLockStatus {
+        status: Vec<ProtoCredentialSoftLockStatus>,
+    },
     ShowReplicationCertificate {
         cert: String,
     },
@@ -61,6 +67,9 @@ impl std::fmt::Debug for AdminTaskResponse {
         match self {
             // the intent here is that we aren't sharing secret material in logs
             AdminTaskResponse::RecoverAccount { .. } => write!(f, "RecoverAccount {{ .. }}"),
+            AdminTaskResponse::CredentialLockStatus { status } => {
+                write!(f, "CredentialLockStatus {{ status: {:?} }}", status)
+            }
             // the intent here is that we aren't sharing secret material in logs
             AdminTaskResponse::ShowReplicationCertificate { .. } => {
                 write!(f, "ShowReplicationCertificate {{ .. }}",)
@@ -359,6 +368,30 @@ async fn handle_client(
                         }
                     }
                 }
+                AdminTaskRequest::CredentialLockStatus { name } => {
+                    match server_ro
+                        .handle_admin_credential_lock_status(name, eventid)
+                        .await
+                    {
+                        Ok(status) => AdminTaskResponse::CredentialLockStatus { status },
+                        Err(e) => {
+                            error!(err = ?e, "error during credential-lock-status");
+                            AdminTaskResponse::Error
+                        }
+                    }
+                }
+                AdminTaskRequest::CredentialUnlock { name } => {
+                    match server_rw
+                        .handle_admin_credential_unlock(name, eventid)
+                        .await
+                    {
+                        Ok(()) => AdminTaskResponse::Success,
+                        Err(e) => {
+                            error!(err = ?e, "error during credential-unlock");
+                            AdminTaskResponse::Error
+                        }
+                    }
+                }
                 AdminTaskRequest::ShowReplicationCertificate => match repl_ctrl_tx.as_mut() {
                     Some(ctrl_tx) => show_replication_certificate(ctrl_tx).await,
                     None => {
@@ -112,6 +112,10 @@ pub struct TlsConfiguration {
     pub chain: PathBuf,
     pub key: PathBuf,
     pub client_ca: Option<PathBuf>,
+    pub oauth2_client_ca: Option<PathBuf>,
+    /// Accept client certificates that are not trusted by the client CA, so that they can
+    /// authenticate OAuth2 clients at the token endpoint.
+    pub oauth2_client_auth: bool,
 }
 
 #[derive(Debug, Default)]
@@ -276,6 +280,13 @@ pub struct ServerConfig {
 
     /// The directory path of the client ca and crl dir.
     tls_client_ca: Option<PathBuf>,
+    /// The file path of the CA certificates that issue OAuth2 client certificates. Only
+    /// certificates from these CAs may be used for OAuth2 `tls_client_auth`.
+    tls_oauth2_client_ca: Option<PathBuf>,
+    /// Request client certificates from OAuth2 clients during the TLS handshake, so that they
+    /// can authenticate with `self_signed_tls_client_auth`. Enabled when `tls_oauth2_client_ca`
+    /// is set.
+    tls_oauth2_client_auth: Option<bool>,
 
     /// The listener address for the HTTPS server.
     ///
@@ -380,13 +391,18 @@ pub struct ServerConfigV2 {
     tls_chain: Option<PathBuf>,
     tls_key: Option<PathBuf>,
     tls_client_ca: Option<PathBuf>,
+    tls_oauth2_client_ca: Option<PathBuf>,
+    tls_oauth2_client_auth: Option<bool>,
 
     migration_path: Option<PathBuf>,
+    breached_password_index: Option<PathBuf>,
 
     #[serde_as(as = "Option<OneOrMany<_, PreferOne>>")]
     bindaddress: Option<Vec<String>>,
     #[serde_as(as = "Option<OneOrMany<_, PreferOne>>")]
     ldapbindaddress: Option<Vec<String>>,
+    #[serde_as(as = "Option<OneOrMany<_, PreferOne>>")]
+    ldapstarttlsbindaddress: Option<Vec<String>>,
 
     role: Option<ServerRole>,
     log_level: Option<LogLevel>,
@@ -428,6 +444,7 @@ pub struct IntegrationReplConfig {
 pub struct Configuration {
     pub address: Vec<String>,
     pub ldapbindaddress: Option<Vec<String>>,
+    pub ldapstarttlsbindaddress: Option<Vec<String>>,
     pub adminbindpath: String,
     pub threads: usize,
     // db type later
@@ -437,6 +454,8 @@ pub struct Configuration {
     pub maximum_request: usize,
 
     pub migration_path: Option<PathBuf>,
+    /// An offline index of breached passwords that new passwords are checked against.
+    pub breached_password_index: Option<PathBuf>,
 
     pub http_client_address_info: HttpAddressInfo,
     pub ldap_client_address_info: LdapAddressInfo,
@@ -460,6 +479,7 @@ impl Configuration {
         ConfigurationBuilder {
             bindaddress: None,
             ldapbindaddress: None,
+            ldapstarttlsbindaddress: None,
             // set by build profiles
             adminbindpath: env!("KANIDM_SERVER_ADMIN_BIND_PATH").to_string(),
             threads: std::thread::available_parallelism()
@@ -472,12 +492,15 @@ impl Configuration {
             db_fs_type: None,
             db_arc_size: None,
             migration_path: None,
+            breached_password_index: None,
             maximum_request: 256 * 1024, // 256k
             http_client_address_info: HttpAddressInfo::default(),
             ldap_client_address_info: LdapAddressInfo::default(),
             tls_key: None,
             tls_chain: None,
             tls_client_ca: None,
+            tls_oauth2_client_ca: None,
+            tls_oauth2_client_auth: None,
             online_backup: None,
             domain: None,
             origin: None,
@@ -493,12 +516,14 @@ impl Configuration {
         Configuration {
             address: vec![DEFAULT_SERVER_ADDRESS.to_string()],
             ldapbindaddress: None,
+            ldapstarttlsbindaddress: None,
             adminbindpath: env!("KANIDM_SERVER_ADMIN_BIND_PATH").to_string(),
             threads: 1,
             db_path: None,
             db_fs_type: None,
             db_arc_size: None,
             migration_path: None,
+            breached_password_index: None,
             maximum_request: 256 * 1024, // 256k
             http_client_address_info: HttpAddressInfo::default(),
             ldap_client_address_info: LdapAddressInfo::default(),
@@ -531,6 +556,11 @@ impl fmt::Display for Configuration {
             }
             None => write!(f, "ldap address: disabled, ")?,
         };
+        if let Some(las) = &self.ldapstarttlsbindaddress {
+            for la in las {
+                write!(f, "ldap starttls address: {la}, ")?;
+            }
+        }
         write!(f, "origin: {} ", self.origin)?;
         write!(f, "admin bind path: {}, ", self.adminbindpath)?;
         write!(f, "thread count: {}, ", self.threads)?;
@@ -605,18 +635,22 @@ impl fmt::Display for Configuration {
 pub struct ConfigurationBuilder {
     bindaddress: Option<Vec<String>>,
     ldapbindaddress: Option<Vec<String>>,
+    ldapstarttlsbindaddress: Option<Vec<String>>,
     adminbindpath: String,
     threads: usize,
     db_path: Option<PathBuf>,
     db_fs_type: Option<FsType>,
     db_arc_size: Option<usize>,
     migration_path: Option<PathBuf>,
+    breached_password_index: Option<PathBuf>,
     maximum_request: usize,
     http_client_address_info: HttpAddressInfo,
     ldap_client_address_info: LdapAddressInfo,
     tls_key: Option<PathBuf>,
     tls_chain: Option<PathBuf>,
     tls_client_ca: Option<PathBuf>,
+    tls_oauth2_client_ca: Option<PathBuf>,
+    tls_oauth2_client_auth: Option<bool>,
     online_backup: Option<OnlineBackup>,
     domain: Option<String>,
     origin: Option<Url>,
@@ -818,6 +852,14 @@ impl ConfigurationBuilder {
             self.tls_client_ca = config.tls_client_ca;
         }
 
+        if config.tls_oauth2_client_ca.is_some() {
+            self.tls_oauth2_client_ca = config.tls_oauth2_client_ca;
+        }
+
+        if config.tls_oauth2_client_auth.is_some() {
+            self.tls_oauth2_client_auth = config.tls_oauth2_client_auth;
+        }
+
         if config.bindaddress.is_some() {
             self.bindaddress = config.bindaddress.map(|a| vec![a]);
         }
@@ -886,6 +928,10 @@ impl ConfigurationBuilder {
             self.migration_path = config.migration_path;
         }
 
+        if config.breached_password_index.is_some() {
+            self.breached_password_index = config.breached_password_index;
+        }
+
         if config.db_fs_type.is_some() {
             self.db_fs_type = config.db_fs_type;
         }
@@ -902,6 +948,14 @@ impl ConfigurationBuilder {
             self.tls_client_ca = config.tls_client_ca;
         }
 
+        if config.tls_oauth2_client_ca.is_some() {
+            self.tls_oauth2_client_ca = config.tls_oauth2_client_ca;
+        }
+
+        if config.tls_oauth2_client_auth.is_some() {
+            self.tls_oauth2_client_auth = config.tls_oauth2_client_auth;
+        }
+
         if config.bindaddress.is_some() {
             self.bindaddress = config.bindaddress;
         }
@@ -910,6 +964,10 @@ impl ConfigurationBuilder {
             self.ldapbindaddress = config.ldapbindaddress;
         }
 
+        if config.ldapstarttlsbindaddress.is_some() {
+            self.ldapstarttlsbindaddress = config.ldapstarttlsbindaddress;
+        }
+
         if let Some(adminbindpath) = config.adminbindpath {
             self.adminbindpath = adminbindpath;
         }
@@ -969,18 +1027,22 @@ impl ConfigurationBuilder {
         let ConfigurationBuilder {
             bindaddress,
             ldapbindaddress,
+            ldapstarttlsbindaddress,
             adminbindpath,
             threads,
             db_path,
             db_fs_type,
             db_arc_size,
             migration_path,
+            breached_password_index,
             maximum_request,
             http_client_address_info,
             ldap_client_address_info,
             tls_key,
             tls_chain,
             tls_client_ca,
+            tls_oauth2_client_ca,
+            tls_oauth2_client_auth,
             mut online_backup,
             domain,
             origin,
@@ -995,6 +1057,9 @@ impl ConfigurationBuilder {
                 chain,
                 key,
                 client_ca,
+                oauth2_client_auth: tls_oauth2_client_auth.unwrap_or_default()
+                    || tls_oauth2_client_ca.is_some(),
+                oauth2_client_ca: tls_oauth2_client_ca,
             }),
             _ => {
                 eprintln!("ERROR: Tls Private Key and Certificate Chain are required.");
@@ -1036,12 +1101,14 @@ impl ConfigurationBuilder {
         Some(Configuration {
             address,
             ldapbindaddress,
+            ldapstarttlsbindaddress,
             adminbindpath,
             threads,
             db_path,
             db_fs_type,
             db_arc_size,
             migration_path,
+            breached_password_index,
             maximum_request,
             http_client_address_info,
             ldap_client_address_info,
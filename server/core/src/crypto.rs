@@ -15,14 +15,23 @@ use crypto_glue::{
     },
     x509::{
         oiddb::{rfc5280, rfc5912},
-        uuid_to_serial, Builder, Certificate, CertificateBuilder, ExtendedKeyUsage, GeneralName,
-        Ia5String, Name, Profile, SubjectAltName, SubjectPublicKeyInfoOwned, Time, Validity,
+        uuid_to_serial, x509_digest_public_key_sha256, Builder, Certificate, CertificateBuilder,
+        ExtendedKeyUsage, GeneralName, Ia5String, Name, Profile, SubjectAltName,
+        SubjectPublicKeyInfoOwned, Time, Validity, X509Store,
     },
 };
+use kanidmd_lib::idm::authentication::ClientCertInfo;
 use rustls::{
-    pki_types::{pem::PemObject, CertificateDer, CertificateRevocationListDer, PrivateKeyDer},
-    server::{ServerConfig, WebPkiClientVerifier},
-    RootCertStore,
+    client::danger::HandshakeSignatureValid,
+    crypto::{verify_tls12_signature, verify_tls13_signature, WebPkiSupportedAlgorithms},
+    pki_types::{
+        pem::PemObject, CertificateDer, CertificateRevocationListDer, PrivateKeyDer, UnixTime,
+    },
+    server::{
+        danger::{ClientCertVerified, ClientCertVerifier},
+        ServerConfig, ServerConnection, WebPkiClientVerifier,
+    },
+    DigitallySignedStruct, DistinguishedName, RootCertStore, SignatureScheme,
 };
 use std::fs;
 use std::io::{Read, Write};
@@ -30,7 +39,8 @@ use std::path::Path;
 use std::str::FromStr;
 use std::sync::Arc;
 use std::time::{Duration, SystemTime};
-use tokio_rustls::TlsAcceptor;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio_rustls::{Accept, TlsAcceptor};
 use uuid::Uuid;
 
 const CA_VALID_DAYS: u64 = 30;
@@ -140,7 +150,7 @@ pub fn check_privkey_minimums(privkey: &PrivateKeyDer<'_>) -> Result<(), String>
 /// to build our sockets for HTTPS/LDAPS.
 pub fn setup_tls(
     tls_config: &Option<TlsConfiguration>,
-) -> Result<Option<TlsAcceptor>, std::io::Error> {
+) -> Result<Option<ServerTlsAcceptor>, std::io::Error> {
     let Some(tls_param) = tls_config.as_ref() else {
         return Ok(None);
     };
@@ -171,7 +181,10 @@ pub fn setup_tls(
     // default. We may swap to rustcrypto in future.
     let provider: Arc<_> = rustls::crypto::aws_lc_rs::default_provider().into();
 
-    let client_cert_verifier = if let Some(client_ca) = tls_param.client_ca.as_ref() {
+    let mut client_cert_roots = RootCertStore::empty();
+    let mut client_cert_crls = Vec::new();
+
+    if let Some(client_ca) = tls_param.client_ca.as_ref() {
         info!(
             "Loading client CA certificates from {}",
             client_ca.display()
@@ -187,8 +200,6 @@ pub fn setup_tls(
 
         let dirents: Vec<_> = read_dir.filter_map(|item| item.ok()).collect();
 
-        let mut client_cert_roots = RootCertStore::empty();
-
         for cert_dir_ent in dirents.iter().filter(|item| {
             item.file_name()
                 .to_str()
@@ -206,8 +217,6 @@ pub fn setup_tls(
             })?;
         }
 
-        let mut client_cert_crls = Vec::new();
-
         for cert_dir_ent in dirents.iter().filter(|item| {
             item.file_name()
                 .to_str()
@@ -223,7 +232,9 @@ pub fn setup_tls(
 
             client_cert_crls.push(cert_pem);
         }
+    }
 
+    let client_ca_verifier = if tls_param.client_ca.is_some() {
         WebPkiClientVerifier::builder_with_provider(client_cert_roots.into(), provider.clone())
             .with_crls(client_cert_crls)
             .allow_unauthenticated()
@@ -235,18 +246,203 @@ pub fn setup_tls(
         WebPkiClientVerifier::no_client_auth()
     };
 
+    // The OAuth2 client CA is deliberately not a root of the client CA, as that would allow it
+    // to issue certificates for any other purpose. OAuth2 client certificates are accepted
+    // without being trusted, and are only checked against a client at the token endpoint.
+    let client_cert_verifier = Arc::new(OAuth2ClientCertVerifier {
+        client_ca: client_ca_verifier,
+        oauth2_client_auth: tls_param.oauth2_client_auth,
+        supported_algs: provider.signature_verification_algorithms,
+    });
+
     let tls_server_config = ServerConfig::builder_with_provider(provider)
         .with_safe_default_protocol_versions()
         .and_then(|builder| {
             builder
-                .with_client_cert_verifier(client_cert_verifier)
+                .with_client_cert_verifier(client_cert_verifier.clone())
                 .with_single_cert(cert_chain_der, private_key_der)
         })
         .map_err(|err| std::io::Error::other(format!("Failed to create TLS listener. The TLS Server Configuration could not be built: {err:?}")))?;
 
     let tls_acceptor = TlsAcceptor::from(Arc::new(tls_server_config));
 
-    Ok(Some(tls_acceptor))
+    Ok(Some(ServerTlsAcceptor {
+        acceptor: tls_acceptor,
+        client_cert_verifier,
+    }))
+}
+
+/// The TLS acceptor of the HTTPS and LDAPS listeners. This keeps the client certificate
+/// verifier of the acceptor, so that the certificate of an established connection can be
+/// checked for whether the client CA trusts it.
+#[derive(Clone)]
+pub struct ServerTlsAcceptor {
+    acceptor: TlsAcceptor,
+    client_cert_verifier: Arc<OAuth2ClientCertVerifier>,
+}
+
+/// The certificate a client presented during the TLS handshake.
+pub(crate) enum PeerCertificate {
+    None,
+    /// The certificate is trusted by the client CA, and may be used to authenticate.
+    Trusted(ClientCertInfo),
+    /// The certificate is not trusted, and may only authenticate an OAuth2 client that it
+    /// is configured for.
+    OAuth2(ClientCertInfo),
+}
+
+impl ServerTlsAcceptor {
+    pub fn accept<IO>(&self, stream: IO) -> Accept<IO>
+    where
+        IO: AsyncRead + AsyncWrite + Unpin,
+    {
+        self.acceptor.accept(stream)
+    }
+
+    /// Process the client cert (if any) that was presented during the TLS handshake. The CRL
+    /// has already been checked as part of the TLS connection establishment.
+    pub(crate) fn peer_certificate(
+        &self,
+        connection: &ServerConnection,
+    ) -> Result<PeerCertificate, ()> {
+        let Some((peer_cert, intermediates)) = connection
+            .peer_certificates()
+            // The first certificate relates to the peer.
+            .and_then(|peer_certs| peer_certs.split_first())
+        else {
+            return Ok(PeerCertificate::None);
+        };
+
+        // Extract the cert from rustls DER to x509-cert which is a better
+        // parser to handle the various extensions.
+        let certificate = Certificate::from_der(peer_cert).map_err(|err| {
+            error!(?err, "unable to process DER certificate to x509");
+        })?;
+
+        let Some(public_key_s256) = x509_digest_public_key_sha256(&certificate) else {
+            error!("subject public key bitstring is not octet aligned");
+            return Err(());
+        };
+
+        let client_cert = ClientCertInfo {
+            public_key_s256,
+            certificate,
+        };
+
+        if self
+            .client_cert_verifier
+            .is_trusted(peer_cert, intermediates)
+        {
+            Ok(PeerCertificate::Trusted(client_cert))
+        } else {
+            Ok(PeerCertificate::OAuth2(client_cert))
+        }
+    }
+}
+
+/// Verifies client certificates with the client CA. When OAuth2 clients may authenticate with
+/// certificates, a certificate that the client CA does not trust is still accepted as long as
+/// the client proves it holds the key, since the token endpoint checks it against the client.
+#[derive(Debug)]
+struct OAuth2ClientCertVerifier {
+    client_ca: Arc<dyn ClientCertVerifier>,
+    oauth2_client_auth: bool,
+    supported_algs: WebPkiSupportedAlgorithms,
+}
+
+impl OAuth2ClientCertVerifier {
+    fn is_trusted(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+    ) -> bool {
+        self.client_ca.offer_client_auth()
+            && self
+                .client_ca
+                .verify_client_cert(end_entity, intermediates, UnixTime::now())
+                .is_ok()
+    }
+}
+
+impl ClientCertVerifier for OAuth2ClientCertVerifier {
+    fn offer_client_auth(&self) -> bool {
+        self.oauth2_client_auth || self.client_ca.offer_client_auth()
+    }
+
+    fn client_auth_mandatory(&self) -> bool {
+        false
+    }
+
+    fn root_hint_subjects(&self) -> &[DistinguishedName] {
+        // OAuth2 clients may present certificates from any issuer, so no hints are sent.
+        if self.oauth2_client_auth {
+            &[]
+        } else {
+            self.client_ca.root_hint_subjects()
+        }
+    }
+
+    fn verify_client_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        now: UnixTime,
+    ) -> Result<ClientCertVerified, rustls::Error> {
+        match self
+            .client_ca
+            .verify_client_cert(end_entity, intermediates, now)
+        {
+            Ok(verified) => Ok(verified),
+            Err(_) if self.oauth2_client_auth => Ok(ClientCertVerified::assertion()),
+            Err(err) => Err(err),
+        }
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls12_signature(message, cert, dss, &self.supported_algs)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls13_signature(message, cert, dss, &self.supported_algs)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.supported_algs.supported_schemes()
+    }
+}
+
+/// Load the CA certificates that issue OAuth2 client certificates, so that the token endpoint
+/// can check that a client certificate was issued by them.
+pub(crate) fn load_oauth2_client_ca(
+    tls_config: &Option<TlsConfiguration>,
+) -> Result<Option<X509Store>, std::io::Error> {
+    let Some(oauth2_client_ca) = tls_config
+        .as_ref()
+        .and_then(|tls_param| tls_param.oauth2_client_ca.as_ref())
+    else {
+        return Ok(None);
+    };
+
+    let ca_pem = fs::read(oauth2_client_ca)?;
+
+    let ca_certs = Certificate::load_pem_chain(&ca_pem).map_err(|err| {
+        std::io::Error::other(format!(
+            "The OAuth2 Client CA Certificates could not be parsed: {} {err:?}",
+            oauth2_client_ca.display()
+        ))
+    })?;
+
+    Ok(Some(X509Store::new(&ca_certs)))
 }
 
 #[derive(Debug)]
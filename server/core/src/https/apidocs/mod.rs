@@ -80,6 +80,8 @@ impl Modify for SecurityAddon {
         super::v1_scim::scim_entry_put,
         super::v1_scim::scim_entry_id_get,
         super::v1_scim::scim_entry_id_delete,
+        super::v1_scim::scim_entry_id_patch,
+        super::v1_scim::scim_bulk_post,
         super::v1_scim::scim_person_id_get,
         super::v1_scim::scim_person_id_application_create_password,
         super::v1_scim::scim_person_id_application_delete_password,
@@ -94,11 +96,28 @@ impl Modify for SecurityAddon {
         super::v1_scim::scim_message_id_get,
         super::v1_scim::scim_message_ready_get,
         super::v1_scim::scim_message_id_sent_post,
+        super::v2_scim::scim_core_users_get,
+        super::v2_scim::scim_core_users_post,
+        super::v2_scim::scim_core_users_id_get,
+        super::v2_scim::scim_core_users_id_put,
+        super::v2_scim::scim_core_users_id_patch,
+        super::v2_scim::scim_core_users_id_delete,
+        super::v2_scim::scim_core_groups_get,
+        super::v2_scim::scim_core_groups_post,
+        super::v2_scim::scim_core_groups_id_get,
+        super::v2_scim::scim_core_groups_id_put,
+        super::v2_scim::scim_core_groups_id_patch,
+        super::v2_scim::scim_core_groups_id_delete,
+        super::v2_scim::scim_core_service_provider_config_get,
+        super::v2_scim::scim_core_resource_types_get,
+        super::v2_scim::scim_core_schemas_get,
 
         super::v1::schema_get,
         super::v1::whoami,
         super::v1::whoami_uat,
         super::v1::applinks_get,
+        super::v1::self_oauth2_consent_get,
+        super::v1::self_oauth2_consent_delete,
         super::v1::schema_attributetype_get,
         super::v1::schema_attributetype_get_id,
         super::v1::schema_classtype_get,
@@ -107,6 +126,8 @@ impl Modify for SecurityAddon {
         super::v1::person_post,
         super::v1::service_account_credential_generate,
         super::v1::service_account_api_token_delete,
+        super::v1::service_account_oauth2_registration_scopemap_post,
+        super::v1::service_account_oauth2_registration_scopemap_delete,
         super::v1::service_account_api_token_get,
         super::v1::service_account_api_token_post,
         super::v1::person_search_id,
@@ -120,6 +141,9 @@ impl Modify for SecurityAddon {
         super::v1::person_get_id_certificate,
         super::v1::person_post_id_certificate,
         super::v1::person_get_id_credential_status,
+        super::v1::person_get_credential_lock,
+        super::v1::person_get_id_credential_lock,
+        super::v1::person_id_credential_lock_delete,
         super::v1::person_id_credential_update_get,
         super::v1::person_id_credential_update_intent_get,
         super::v1::person_id_credential_update_intent_send_post,
@@ -235,6 +259,24 @@ impl Modify for SecurityAddon {
             scim_v1::ScimApplicationPasswordCreate,
             scim_v1::ScimApplicationPassword,
             scim_v1::client::ScimEntryPostGeneric,
+            scim_v1::client::ScimEntryPatchGeneric,
+            scim_v1::client::ScimPatchOperation,
+            scim_v1::client::ScimPatchOpType,
+            scim_v1::client::ScimBulkRequest,
+            scim_v1::client::ScimBulkOperation,
+            scim_v1::client::ScimBulkMethod,
+            scim_v1::server::ScimBulkResponse,
+            scim_v1::server::ScimBulkOperationResponse,
+            scim_v1::server::ScimErrorResponse,
+            scim_v1::core_schema::ScimCoreUser,
+            scim_v1::core_schema::ScimCoreGroup,
+            scim_v1::core_schema::ScimCoreMultiValue,
+            scim_v1::core_schema::ScimCoreReference,
+            scim_v1::core_schema::ScimCoreMeta,
+            scim_v1::core_schema::ScimServiceProviderConfig,
+            scim_v1::core_schema::ScimResourceType,
+            scim_v1::core_schema::ScimSchemaDefinition,
+            scim_v1::core_schema::ScimSchemaAttributeDefinition,
 
             internal::ApiToken,
             internal::ApiTokenPurpose,
@@ -243,6 +285,8 @@ impl Modify for SecurityAddon {
             internal::CreateRequest,
             internal::CredentialDetail,
             internal::CredentialDetailType,
+            internal::CredentialSoftLockAccount,
+            internal::CredentialSoftLockStatus,
             internal::CredentialStatus,
             internal::CUExtPortal,
             internal::CUIntentToken,
@@ -296,6 +340,7 @@ impl Modify for SecurityAddon {
             internal::CURegWarning,
             internal::IdentifyUserResponse,
             internal::AppLink,
+            internal::Oauth2Consent,
 
             internal::IdentifyUserRequest,
             // terrible workaround for other things
@@ -47,6 +47,35 @@ impl WebError {
     }
 }
 
+/// The HTTP status code that an `OperationError` is reported with.
+pub(crate) fn operation_error_status_code(inner: &OperationError) -> StatusCode {
+    match inner {
+        OperationError::NotAuthenticated | OperationError::SessionExpired => {
+            StatusCode::UNAUTHORIZED
+        }
+        OperationError::SystemProtectedObject
+        | OperationError::AccessDenied
+        | OperationError::PasswordExpired => StatusCode::FORBIDDEN,
+        OperationError::NoMatchingEntries => StatusCode::NOT_FOUND,
+        OperationError::PasswordQuality(_)
+        | OperationError::EmptyRequest
+        | OperationError::InvalidAttribute(_)
+        | OperationError::InvalidAttributeName(_)
+        | OperationError::SchemaViolation(_)
+        | OperationError::SC0034PatchSchemaInvalid
+        | OperationError::SC0035PatchOperationInvalid
+        | OperationError::SC0036PatchPathUnsupported
+        | OperationError::SC0037CoreAttributeUnsupported
+        | OperationError::SC0038BulkRequestInvalid
+        | OperationError::SC0039BulkOperationInvalid
+        | OperationError::CU0003WebauthnUserNotVerified
+        | OperationError::PL0002Oauth2JwksInvalid
+        | OperationError::PL0003Oauth2EncryptedResponseAlgInvalid
+        | OperationError::VL0001ValueSshPublicKeyString => StatusCode::BAD_REQUEST,
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+
 impl IntoResponse for WebError {
     fn into_response(self) -> Response {
         match self {
@@ -94,29 +123,13 @@ impl IntoResponse for WebError {
             )
                 .into_response(),
             WebError::OperationError(inner) => {
-                let (code, headers) = match &inner {
+                let code = operation_error_status_code(&inner);
+                let headers = match &inner {
                     OperationError::NotAuthenticated | OperationError::SessionExpired => {
                         // https://datatracker.ietf.org/doc/html/rfc7235#section-4.1
-                        (
-                            StatusCode::UNAUTHORIZED,
-                            Some([("WWW-Authenticate", "Bearer"); 1]),
-                        )
-                    }
-                    OperationError::SystemProtectedObject | OperationError::AccessDenied => {
-                        (StatusCode::FORBIDDEN, None)
-                    }
-
-                    OperationError::NoMatchingEntries => (StatusCode::NOT_FOUND, None),
-                    OperationError::PasswordQuality(_)
-                    | OperationError::EmptyRequest
-                    | OperationError::InvalidAttribute(_)
-                    | OperationError::InvalidAttributeName(_)
-                    | OperationError::SchemaViolation(_)
-                    | OperationError::CU0003WebauthnUserNotVerified
-                    | OperationError::VL0001ValueSshPublicKeyString => {
-                        (StatusCode::BAD_REQUEST, None)
+                        Some([("WWW-Authenticate", "Bearer"); 1])
                     }
-                    _ => (StatusCode::INTERNAL_SERVER_ERROR, None),
+                    _ => None,
                 };
                 let body = serde_json::to_string(&inner).unwrap_or(inner.to_string());
                 debug!(?body);
@@ -6,7 +6,8 @@ use axum::{
 use axum_extra::extract::cookie::CookieJar;
 use compact_jwt::JwsCompact;
 use kanidm_proto::internal::COOKIE_BEARER_TOKEN;
-use kanidmd_lib::prelude::{ClientAuthInfo, ClientCertInfo, Source};
+use kanidm_proto::oauth2::OAUTH2_DPOP_HEADER;
+use kanidmd_lib::prelude::{ClientAuthInfo, ClientCertInfo, DpopProof, Source};
 use std::net::{IpAddr, SocketAddr};
 use std::str::FromStr;
 
@@ -28,6 +29,7 @@ impl FromRequestParts<ServerState> for VerifiedClientInformation {
             connection_addr: _,
             client_ip_addr,
             client_cert,
+            oauth2_client_cert,
         } = parts.extensions.remove::<ClientConnInfo>().ok_or((
             StatusCode::INTERNAL_SERVER_ERROR,
             "request info contains invalid data",
@@ -46,7 +48,7 @@ impl FromRequestParts<ServerState> for VerifiedClientInformation {
 
                 if authz_type == "basic" {
                     (Some(authz_data.to_string()), None)
-                } else if authz_type == "bearer" {
+                } else if authz_type == "bearer" || authz_type == "dpop" {
                     if let Ok(jwsc) = JwsCompact::from_str(authz_data) {
                         (None, Some(jwsc))
                     } else {
@@ -78,6 +80,14 @@ impl FromRequestParts<ServerState> for VerifiedClientInformation {
             basic_authz,
         );
 
+        if let Some(oauth2_client_cert) = oauth2_client_cert {
+            client_auth_info.set_oauth2_client_cert(oauth2_client_cert);
+        }
+
+        if let Some(dpop_proof) = dpop_proof_from_parts(parts) {
+            client_auth_info.set_dpop_proof(dpop_proof);
+        }
+
         // now, we want to update the client auth info with the sessions user-auth-token
         // if any. We ignore errors here as the auth info MAY NOT be a valid token
         // and so in that case no prevalidation will occur.
@@ -105,6 +115,7 @@ impl FromRequestParts<ServerState> for AuthorisationHeaders {
             connection_addr: _,
             client_ip_addr,
             client_cert,
+            oauth2_client_cert,
         } = parts.extensions.remove::<ClientConnInfo>().ok_or((
             StatusCode::INTERNAL_SERVER_ERROR,
             "request info contains invalid data",
@@ -123,7 +134,7 @@ impl FromRequestParts<ServerState> for AuthorisationHeaders {
 
                 if authz_type == "basic" {
                     (Some(authz_data.to_string()), None)
-                } else if authz_type == "bearer" {
+                } else if authz_type == "bearer" || authz_type == "dpop" {
                     if let Ok(jwsc) = JwsCompact::from_str(authz_data) {
                         (None, Some(jwsc))
                     } else {
@@ -141,17 +152,44 @@ impl FromRequestParts<ServerState> for AuthorisationHeaders {
             (None, None)
         };
 
-        let client_auth_info = ClientAuthInfo::new(
+        let mut client_auth_info = ClientAuthInfo::new(
             Source::Https(client_ip_addr),
             client_cert,
             bearer_token,
             basic_authz,
         );
 
+        if let Some(oauth2_client_cert) = oauth2_client_cert {
+            client_auth_info.set_oauth2_client_cert(oauth2_client_cert);
+        }
+
+        if let Some(dpop_proof) = dpop_proof_from_parts(parts) {
+            client_auth_info.set_dpop_proof(dpop_proof);
+        }
+
         Ok(AuthorisationHeaders(client_auth_info))
     }
 }
 
+/// A DPoP proof is bound to the method of the request it was sent with, so we
+/// record that alongside the proof.
+fn dpop_proof_from_parts(parts: &Parts) -> Option<DpopProof> {
+    let proof = parts
+        .headers
+        .get(OAUTH2_DPOP_HEADER)?
+        .to_str()
+        .map_err(|err| {
+            warn!(?err, "Invalid DPoP header, ignoring");
+        })
+        .ok()?
+        .to_string();
+
+    Some(DpopProof {
+        proof,
+        htm: parts.method.to_string(),
+    })
+}
+
 pub struct DomainInfo(pub DomainInfoRead);
 
 impl FromRequestParts<ServerState> for DomainInfo {
@@ -178,6 +216,9 @@ pub struct ClientConnInfo {
     pub client_ip_addr: IpAddr,
     // Only set if the certificate is VALID
     pub client_cert: Option<ClientCertInfo>,
+    // A certificate the client CA did not validate. This may only be used to
+    // authenticate OAuth2 clients at the token endpoint.
+    pub oauth2_client_cert: Option<ClientCertInfo>,
 }
 
 // This is the normal way that our extractors get the ip info
@@ -194,6 +235,7 @@ impl Connected<SocketAddr> for ClientConnInfo {
             client_ip_addr: connection_addr.ip().to_canonical(),
             connection_addr,
             client_cert: None,
+            oauth2_client_cert: None,
         }
     }
 }
@@ -122,6 +122,7 @@ async fn ip_address_middleware_inner(
         connection_addr,
         client_ip_addr,
         client_cert,
+        oauth2_client_cert,
     }) = request
         .extract_parts::<ConnectInfo<ClientConnInfo>>()
         .await
@@ -190,5 +191,6 @@ async fn ip_address_middleware_inner(
         connection_addr,
         client_ip_addr,
         client_cert,
+        oauth2_client_cert,
     })
 }
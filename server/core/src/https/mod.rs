@@ -2,6 +2,7 @@ use self::extractors::ClientConnInfo;
 use self::javascript::*;
 use crate::actors::{QueryServerReadV1, QueryServerWriteV1};
 use crate::config::{AddressSet, Configuration, TcpAddressInfo};
+use crate::crypto::{PeerCertificate, ServerTlsAcceptor};
 use crate::tcp::process_client_addr;
 use crate::CoreAction;
 use axum::{
@@ -15,15 +16,11 @@ use axum::{
 };
 use axum_extra::extract::cookie::CookieJar;
 use compact_jwt::{error::JwtError, JwsCompact, JwsHs256Signer, JwsVerifier};
-use crypto_glue::{
-    traits::DecodeDer,
-    x509::{x509_digest_public_key_sha256, Certificate},
-};
 use futures::pin_mut;
 use hyper::body::Incoming;
 use hyper_util::rt::{TokioExecutor, TokioIo, TokioTimer};
 use kanidm_proto::{config::ServerRole, constants::KSESSIONID, internal::COOKIE_AUTH_SESSION_ID};
-use kanidmd_lib::{idm::authentication::ClientCertInfo, status::StatusActor};
+use kanidmd_lib::status::StatusActor;
 use serde::de::DeserializeOwned;
 use sketching::*;
 use std::fmt::Write;
@@ -39,7 +36,6 @@ use tokio::{
     task,
     time::timeout,
 };
-use tokio_rustls::TlsAcceptor;
 use tower::Service;
 use tower_http::{services::ServeDir, timeout::TimeoutLayer, trace::TraceLayer};
 use url::Url;
@@ -63,6 +59,7 @@ mod v1;
 mod v1_domain;
 mod v1_oauth2;
 mod v1_scim;
+mod v2_scim;
 mod views;
 
 #[derive(Clone, Copy, Eq, PartialEq, Debug)]
@@ -198,8 +195,8 @@ pub async fn create_https_server(
     qe_w_ref: &'static QueryServerWriteV1,
     qe_r_ref: &'static QueryServerReadV1,
     server_message_tx: broadcast::Sender<CoreAction>,
-    maybe_tls_acceptor: Option<TlsAcceptor>,
-    tls_acceptor_reload_tx: &broadcast::Sender<TlsAcceptor>,
+    maybe_tls_acceptor: Option<ServerTlsAcceptor>,
+    tls_acceptor_reload_tx: &broadcast::Sender<ServerTlsAcceptor>,
 ) -> Result<Vec<task::JoinHandle<()>>, ()> {
     let all_js_files = get_js_files(config.role)?;
     // set up the CSP headers
@@ -309,6 +306,7 @@ pub async fn create_https_server(
     let app = Router::new()
         .merge(oauth2::route_setup(state.clone()))
         .merge(v1_scim::route_setup())
+        .merge(v2_scim::route_setup())
         .merge(v1::route_setup(state.clone()))
         .route("/robots.txt", get(generic::robots_txt))
         .route(
@@ -450,12 +448,12 @@ pub async fn create_https_server(
 }
 
 async fn server_tls_loop(
-    mut tls_acceptor: TlsAcceptor,
+    mut tls_acceptor: ServerTlsAcceptor,
     listener: TcpListener,
     app: IntoMakeServiceWithConnectInfo<Router, ClientConnInfo>,
     mut rx: broadcast::Receiver<CoreAction>,
     server_message_tx: broadcast::Sender<CoreAction>,
-    mut tls_acceptor_reload_rx: broadcast::Receiver<TlsAcceptor>,
+    mut tls_acceptor_reload_rx: broadcast::Receiver<ServerTlsAcceptor>,
     trusted_tcp_info_ips: Arc<TcpAddressInfo>,
 ) {
     pin_mut!(listener);
@@ -549,6 +547,7 @@ pub(crate) async fn handle_conn(
         connection_addr,
         client_ip_addr,
         client_cert: None,
+        oauth2_client_cert: None,
     };
 
     // Hyper has its own `AsyncRead` and `AsyncWrite` traits and doesn't use tokio.
@@ -560,7 +559,7 @@ pub(crate) async fn handle_conn(
 
 /// This handles an individual connection.
 pub(crate) async fn handle_tls_conn(
-    acceptor: TlsAcceptor,
+    acceptor: ServerTlsAcceptor,
     stream: TcpStream,
     app: IntoMakeServiceWithConnectInfo<Router, ClientConnInfo>,
     connection_addr: SocketAddr,
@@ -603,42 +602,21 @@ pub(crate) async fn handle_tls_conn(
         }
     };
 
-    let maybe_peer_cert = tls_stream
-        .get_ref()
-        .1
-        .peer_certificates()
-        // The first certificate relates to the peer.
-        .and_then(|peer_certs| peer_certs.first());
-
-    // Process the client cert (if any)
-    let client_cert = if let Some(peer_cert) = maybe_peer_cert {
-        // We don't need to check the CRL here - it's already completed as part of the
-        // TLS connection establishment process.
-
-        // Extract the cert from rustls DER to x509-cert which is a better
-        // parser to handle the various extensions.
-        let certificate = Certificate::from_der(peer_cert).map_err(|ossl_err| {
-            error!(?ossl_err, "unable to process DER certificate to x509");
-            std::io::Error::from(ErrorKind::ConnectionAborted)
-        })?;
-
-        let public_key_s256 = x509_digest_public_key_sha256(&certificate).ok_or_else(|| {
-            error!("subject public key bitstring is not octet aligned");
-            std::io::Error::from(ErrorKind::ConnectionAborted)
-        })?;
-
-        Some(ClientCertInfo {
-            public_key_s256,
-            certificate,
-        })
-    } else {
-        None
+    // Process the client cert (if any). A certificate that the client CA does not trust is
+    // only made available to OAuth2, which checks it against the client's configuration.
+    let (client_cert, oauth2_client_cert) = match acceptor.peer_certificate(tls_stream.get_ref().1)
+    {
+        Ok(PeerCertificate::Trusted(client_cert)) => (Some(client_cert), None),
+        Ok(PeerCertificate::OAuth2(client_cert)) => (None, Some(client_cert)),
+        Ok(PeerCertificate::None) => (None, None),
+        Err(()) => return Err(std::io::Error::from(ErrorKind::ConnectionAborted)),
     };
 
     let client_conn_info = ClientConnInfo {
         connection_addr,
         client_ip_addr,
         client_cert,
+        oauth2_client_cert,
     };
 
     // Hyper has its own `AsyncRead` and `AsyncWrite` traits and doesn't use tokio.
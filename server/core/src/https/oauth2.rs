@@ -4,9 +4,10 @@ use super::errors::WebError;
 use super::middleware::KOpId;
 use super::ServerState;
 use crate::https::extractors::{AuthorisationHeaders, VerifiedClientInformation};
+use crate::https::views::{constants::Urls, cookies};
 use axum::{
     body::Body,
-    extract::{Path, Query, State},
+    extract::{Path, Query, RawForm, RawQuery, State},
     http::{
         header::{
             ACCESS_CONTROL_ALLOW_HEADERS, ACCESS_CONTROL_ALLOW_ORIGIN, CONTENT_TYPE, LOCATION,
@@ -15,22 +16,25 @@ use axum::{
         HeaderValue, StatusCode,
     },
     middleware::from_fn,
-    response::{IntoResponse, Response},
+    response::{IntoResponse, Redirect, Response},
     routing::{get, post},
     Extension, Form, Json, Router,
 };
+use axum_extra::extract::cookie::CookieJar;
 use axum_macros::debug_handler;
 use kanidm_proto::constants::uri::{
     OAUTH2_AUTHORISE, OAUTH2_AUTHORISE_PERMIT, OAUTH2_AUTHORISE_REJECT,
 };
-use kanidm_proto::constants::APPLICATION_JSON;
+use kanidm_proto::constants::{APPLICATION_JSON, APPLICATION_JWT};
 use kanidm_proto::oauth2::AuthorisationResponse;
 
 #[cfg(feature = "dev-oauth2-device-flow")]
 use kanidm_proto::oauth2::DeviceAuthorizationResponse;
 use kanidmd_lib::idm::oauth2::{
-    AccessTokenIntrospectRequest, AccessTokenRequest, AuthorisationRequest, AuthoriseResponse,
-    ErrorResponse, Oauth2Error, TokenRevokeRequest,
+    AccessTokenIntrospectRequest, AccessTokenRequest, AuthorisationRequest,
+    AuthorisationRequestParams, AuthoriseResponse, BackchannelAuthenticationRequest,
+    ClientMetadata, ClientPostAuth, EndSessionRequest, ErrorResponse, Oauth2Error,
+    TokenRevokeRequest, UserinfoResponse,
 };
 use kanidmd_lib::prelude::f_eq;
 use kanidmd_lib::prelude::*;
@@ -41,7 +45,11 @@ use serde_with::{serde_as, StringWithSeparator};
 
 #[cfg(feature = "dev-oauth2-device-flow")]
 use uri::OAUTH2_AUTHORISE_DEVICE;
-use uri::{OAUTH2_TOKEN_ENDPOINT, OAUTH2_TOKEN_INTROSPECT_ENDPOINT, OAUTH2_TOKEN_REVOKE_ENDPOINT};
+use uri::{
+    OAUTH2_BACKCHANNEL_AUTHENTICATION_ENDPOINT, OAUTH2_PUSHED_AUTHORISATION_ENDPOINT,
+    OAUTH2_REGISTRATION_ENDPOINT, OAUTH2_TOKEN_ENDPOINT, OAUTH2_TOKEN_INTROSPECT_ENDPOINT,
+    OAUTH2_TOKEN_REVOKE_ENDPOINT,
+};
 
 // == Oauth2 Configuration Endpoints ==
 
@@ -161,7 +169,7 @@ pub async fn oauth2_authorise_post(
     AuthorisationHeaders(client_auth_info): AuthorisationHeaders,
     Json(auth_req): Json<AuthorisationRequest>,
 ) -> impl IntoResponse {
-    let mut res = oauth2_authorise(state, auth_req, kopid, client_auth_info)
+    let mut res = oauth2_authorise(state, auth_req.into(), kopid, client_auth_info)
         .await
         .into_response();
     if res.status() == StatusCode::FOUND {
@@ -176,21 +184,33 @@ pub async fn oauth2_authorise_get(
     State(state): State<ServerState>,
     Extension(kopid): Extension<KOpId>,
     AuthorisationHeaders(client_auth_info): AuthorisationHeaders,
-    Query(auth_req): Query<AuthorisationRequest>,
-) -> impl IntoResponse {
+    RawQuery(query): RawQuery,
+) -> Response {
+    // The request may be given by value, or by reference to a pushed request or request object.
+    let auth_req_params =
+        match AuthorisationRequestParams::from_query(query.as_deref().unwrap_or_default()) {
+            Ok(auth_req_params) => auth_req_params,
+            Err(err) => {
+                error!(?err, "Unable to parse OAuth2 authorisation request");
+                return WebError::OAuth2(Oauth2Error::InvalidRequest).into_response();
+            }
+        };
+
     // Start the oauth2 authorisation flow to present to the user.
-    oauth2_authorise(state, auth_req, kopid, client_auth_info).await
+    oauth2_authorise(state, auth_req_params, kopid, client_auth_info)
+        .await
+        .into_response()
 }
 
 async fn oauth2_authorise(
     state: ServerState,
-    auth_req: AuthorisationRequest,
+    auth_req_params: AuthorisationRequestParams,
     kopid: KOpId,
     client_auth_info: ClientAuthInfo,
 ) -> impl IntoResponse {
     let res: Result<AuthoriseResponse, Oauth2Error> = state
         .qe_r_ref
-        .handle_oauth2_authorise(client_auth_info, auth_req, kopid.eventid)
+        .handle_oauth2_authorise(client_auth_info, auth_req_params, kopid.eventid)
         .await;
 
     match res {
@@ -199,6 +219,7 @@ async fn oauth2_authorise(
             scopes,
             pii_scopes,
             consent_token,
+            authorization_details,
         }) => {
             // Render a redirect to the consent page for the user to interact with
             // to authorise this session-id
@@ -209,6 +230,7 @@ async fn oauth2_authorise(
                 scopes,
                 pii_scopes,
                 consent_token,
+                authorization_details,
             })
             .unwrap();
             #[allow(clippy::unwrap_used)]
@@ -473,6 +495,200 @@ pub async fn oauth2_token_post(
     }
 }
 
+/// Accept an authorisation request pushed by the client, returning the request_uri the
+/// client then redirects the user agent with. <https://datatracker.ietf.org/doc/html/rfc9126>
+#[instrument(level = "debug", skip_all)]
+pub async fn oauth2_pushed_authorisation_post(
+    State(state): State<ServerState>,
+    Extension(kopid): Extension<KOpId>,
+    AuthorisationHeaders(client_auth_info): AuthorisationHeaders,
+    RawForm(raw_form): RawForm,
+) -> Response {
+    // The client authentication and the authorisation request share the same form.
+    let parsed = std::str::from_utf8(&raw_form)
+        .map_err(|err| err.to_string())
+        .and_then(|form| {
+            let client_post_auth = serde_urlencoded::from_str::<ClientPostAuth>(form)
+                .map_err(|err| err.to_string())?;
+            let auth_req_params =
+                AuthorisationRequestParams::from_query(form).map_err(|err| err.to_string())?;
+            Ok((client_post_auth, auth_req_params))
+        });
+
+    let (client_post_auth, auth_req_params) = match parsed {
+        Ok(parsed) => parsed,
+        Err(err) => {
+            error!(?err, "Unable to parse pushed authorisation request");
+            return WebError::OAuth2(Oauth2Error::InvalidRequest).into_response();
+        }
+    };
+
+    match state
+        .qe_w_ref
+        .handle_oauth2_pushed_authorisation(
+            client_auth_info,
+            client_post_auth,
+            auth_req_params,
+            kopid.eventid,
+        )
+        .await
+    {
+        Ok(par_res) => (
+            StatusCode::CREATED,
+            [(ACCESS_CONTROL_ALLOW_ORIGIN, "*")],
+            Json(par_res),
+        )
+            .into_response(),
+        Err(e) => WebError::OAuth2(e).into_response(),
+    }
+}
+
+/// Start a client initiated backchannel authentication, returning the auth_req_id that
+/// the client then polls the token endpoint with.
+/// <https://openid.net/specs/openid-client-initiated-backchannel-authentication-core-1_0.html#auth_backchannel_endpoint>
+#[instrument(level = "debug", skip_all)]
+pub async fn oauth2_backchannel_authentication_post(
+    State(state): State<ServerState>,
+    Extension(kopid): Extension<KOpId>,
+    AuthorisationHeaders(client_auth_info): AuthorisationHeaders,
+    RawForm(raw_form): RawForm,
+) -> Response {
+    // The client authentication and the authentication request share the same form.
+    let parsed = std::str::from_utf8(&raw_form)
+        .map_err(|err| err.to_string())
+        .and_then(|form| {
+            let client_post_auth = serde_urlencoded::from_str::<ClientPostAuth>(form)
+                .map_err(|err| err.to_string())?;
+            let bc_req = serde_urlencoded::from_str::<BackchannelAuthenticationRequest>(form)
+                .map_err(|err| err.to_string())?;
+            Ok((client_post_auth, bc_req))
+        });
+
+    let (client_post_auth, bc_req) = match parsed {
+        Ok(parsed) => parsed,
+        Err(err) => {
+            error!(?err, "Unable to parse backchannel authentication request");
+            return WebError::OAuth2(Oauth2Error::InvalidRequest).into_response();
+        }
+    };
+
+    match state
+        .qe_w_ref
+        .handle_oauth2_backchannel_authentication(
+            client_auth_info,
+            client_post_auth,
+            bc_req,
+            kopid.eventid,
+        )
+        .await
+    {
+        Ok(bc_res) => (
+            StatusCode::OK,
+            [(ACCESS_CONTROL_ALLOW_ORIGIN, "*")],
+            Json(bc_res),
+        )
+            .into_response(),
+        Err(e) => WebError::OAuth2(e).into_response(),
+    }
+}
+
+/// Register a client, authorised by an initial access token issued to a service account.
+/// <https://datatracker.ietf.org/doc/html/rfc7591#section-3>
+#[instrument(level = "debug", skip_all)]
+pub async fn oauth2_client_register_post(
+    State(state): State<ServerState>,
+    Extension(kopid): Extension<KOpId>,
+    AuthorisationHeaders(client_auth_info): AuthorisationHeaders,
+    Json(metadata): Json<ClientMetadata>,
+) -> Response {
+    match state
+        .qe_w_ref
+        .handle_oauth2_client_register(client_auth_info, metadata, kopid.eventid)
+        .await
+    {
+        Ok(registration) => (
+            StatusCode::CREATED,
+            [(ACCESS_CONTROL_ALLOW_ORIGIN, "*")],
+            Json(registration),
+        )
+            .into_response(),
+        Err(e) => WebError::OAuth2(e).into_response(),
+    }
+}
+
+/// Read the configuration of a registered client, authorised by its registration access
+/// token. <https://datatracker.ietf.org/doc/html/rfc7592#section-2.1>
+#[instrument(level = "debug", skip_all)]
+pub async fn oauth2_client_registration_get(
+    State(state): State<ServerState>,
+    Extension(kopid): Extension<KOpId>,
+    AuthorisationHeaders(client_auth_info): AuthorisationHeaders,
+    Path(client_id): Path<String>,
+) -> Response {
+    match state
+        .qe_r_ref
+        .handle_oauth2_client_registration_read(client_auth_info, client_id, kopid.eventid)
+        .await
+    {
+        Ok(registration) => (
+            StatusCode::OK,
+            [(ACCESS_CONTROL_ALLOW_ORIGIN, "*")],
+            Json(registration),
+        )
+            .into_response(),
+        Err(e) => WebError::OAuth2(e).into_response(),
+    }
+}
+
+/// Replace the configuration of a registered client, authorised by its registration access
+/// token. <https://datatracker.ietf.org/doc/html/rfc7592#section-2.2>
+#[instrument(level = "debug", skip_all)]
+pub async fn oauth2_client_registration_put(
+    State(state): State<ServerState>,
+    Extension(kopid): Extension<KOpId>,
+    AuthorisationHeaders(client_auth_info): AuthorisationHeaders,
+    Path(client_id): Path<String>,
+    Json(metadata): Json<ClientMetadata>,
+) -> Response {
+    match state
+        .qe_w_ref
+        .handle_oauth2_client_registration_update(
+            client_auth_info,
+            client_id,
+            metadata,
+            kopid.eventid,
+        )
+        .await
+    {
+        Ok(registration) => (
+            StatusCode::OK,
+            [(ACCESS_CONTROL_ALLOW_ORIGIN, "*")],
+            Json(registration),
+        )
+            .into_response(),
+        Err(e) => WebError::OAuth2(e).into_response(),
+    }
+}
+
+/// Delete a registered client, authorised by its registration access token.
+/// <https://datatracker.ietf.org/doc/html/rfc7592#section-2.3>
+#[instrument(level = "debug", skip_all)]
+pub async fn oauth2_client_registration_delete(
+    State(state): State<ServerState>,
+    Extension(kopid): Extension<KOpId>,
+    AuthorisationHeaders(client_auth_info): AuthorisationHeaders,
+    Path(client_id): Path<String>,
+) -> Response {
+    match state
+        .qe_w_ref
+        .handle_oauth2_client_registration_delete(client_auth_info, client_id, kopid.eventid)
+        .await
+    {
+        Ok(()) => (StatusCode::NO_CONTENT, [(ACCESS_CONTROL_ALLOW_ORIGIN, "*")]).into_response(),
+        Err(e) => WebError::OAuth2(e).into_response(),
+    }
+}
+
 // For future openid integration
 pub async fn oauth2_openid_discovery_get(
     State(state): State<ServerState>,
@@ -577,16 +793,102 @@ pub async fn oauth2_openid_userinfo_get(
 
     let res = state
         .qe_r_ref
-        .handle_oauth2_openid_userinfo(client_id, client_token, kopid.eventid)
+        .handle_oauth2_openid_userinfo(
+            client_id,
+            client_token,
+            client_auth_info.dpop_proof(),
+            client_auth_info.tls_client_cert(),
+            kopid.eventid,
+        )
         .await;
 
     match res {
-        Ok(uir) => (
+        Ok(UserinfoResponse::Claims(uir)) => (
             StatusCode::OK,
             [(ACCESS_CONTROL_ALLOW_ORIGIN, "*")],
             Json(uir),
         )
             .into_response(),
+        // Encrypted userinfo is returned as a JWT.
+        Ok(UserinfoResponse::Encrypted(uir)) => (
+            StatusCode::OK,
+            [
+                (ACCESS_CONTROL_ALLOW_ORIGIN, "*"),
+                (CONTENT_TYPE, APPLICATION_JWT),
+            ],
+            uir.to_string(),
+        )
+            .into_response(),
+        Err(e) => WebError::OAuth2(e).into_response(),
+    }
+}
+
+/// OpenID Connect RP-Initiated Logout. The client sends the user agent here to end the user's
+/// sessions, and it is then redirected back to the client, or to the login page.
+pub async fn oauth2_openid_end_session_get(
+    State(state): State<ServerState>,
+    Path(client_id): Path<String>,
+    Extension(kopid): Extension<KOpId>,
+    VerifiedClientInformation(client_auth_info): VerifiedClientInformation,
+    jar: CookieJar,
+    Query(end_session_req): Query<EndSessionRequest>,
+) -> Response {
+    oauth2_openid_end_session(
+        state,
+        client_id,
+        kopid,
+        client_auth_info,
+        jar,
+        end_session_req,
+    )
+    .await
+}
+
+pub async fn oauth2_openid_end_session_post(
+    State(state): State<ServerState>,
+    Path(client_id): Path<String>,
+    Extension(kopid): Extension<KOpId>,
+    VerifiedClientInformation(client_auth_info): VerifiedClientInformation,
+    jar: CookieJar,
+    Form(end_session_req): Form<EndSessionRequest>,
+) -> Response {
+    oauth2_openid_end_session(
+        state,
+        client_id,
+        kopid,
+        client_auth_info,
+        jar,
+        end_session_req,
+    )
+    .await
+}
+
+async fn oauth2_openid_end_session(
+    state: ServerState,
+    client_id: String,
+    kopid: KOpId,
+    client_auth_info: ClientAuthInfo,
+    jar: CookieJar,
+    end_session_req: EndSessionRequest,
+) -> Response {
+    let res = state
+        .qe_w_ref
+        .handle_oauth2_openid_end_session(
+            client_auth_info,
+            client_id,
+            end_session_req,
+            kopid.eventid,
+        )
+        .await;
+
+    match res {
+        Ok(redirect_uri) => {
+            let jar = cookies::destroy_session(jar, &state);
+            let redirect_uri = redirect_uri
+                .map(String::from)
+                .unwrap_or_else(|| Urls::Login.as_ref().to_string());
+            (jar, Redirect::to(&redirect_uri)).into_response()
+        }
         Err(e) => WebError::OAuth2(e).into_response(),
     }
 }
@@ -719,7 +1021,7 @@ pub async fn oauth2_preflight_options() -> Response {
         StatusCode::OK,
         [
             (ACCESS_CONTROL_ALLOW_ORIGIN, "*"),
-            (ACCESS_CONTROL_ALLOW_HEADERS, "Authorization"),
+            (ACCESS_CONTROL_ALLOW_HEADERS, "Authorization, DPoP"),
         ],
         String::new(),
     )
@@ -783,6 +1085,12 @@ pub fn route_setup(state: ServerState) -> Router<ServerState> {
         )
         // // ⚠️  ⚠️   WARNING  ⚠️  ⚠️
         // // IF YOU CHANGE THESE VALUES YOU MUST UPDATE OIDC DISCOVERY URLS
+        .route(
+            "/oauth2/openid/{client_id}/end_session",
+            get(oauth2_openid_end_session_get).post(oauth2_openid_end_session_post),
+        )
+        // // ⚠️  ⚠️   WARNING  ⚠️  ⚠️
+        // // IF YOU CHANGE THESE VALUES YOU MUST UPDATE OIDC DISCOVERY URLS
         .route(
             "/oauth2/openid/{client_id}/public_key.jwk",
             get(oauth2_openid_publickey_get).options(oauth2_preflight_options),
@@ -835,6 +1143,30 @@ pub fn route_setup(state: ServerState) -> Router<ServerState> {
             post(oauth2_token_introspect_post),
         )
         .route(OAUTH2_TOKEN_REVOKE_ENDPOINT, post(oauth2_token_revoke_post))
+        // ⚠️  ⚠️   WARNING  ⚠️  ⚠️
+        // IF YOU CHANGE THESE VALUES YOU MUST UPDATE OIDC DISCOVERY URLS
+        .route(
+            OAUTH2_PUSHED_AUTHORISATION_ENDPOINT,
+            post(oauth2_pushed_authorisation_post).options(oauth2_preflight_options),
+        )
+        // ⚠️  ⚠️   WARNING  ⚠️  ⚠️
+        // IF YOU CHANGE THESE VALUES YOU MUST UPDATE OIDC DISCOVERY URLS
+        .route(
+            OAUTH2_REGISTRATION_ENDPOINT,
+            post(oauth2_client_register_post).options(oauth2_preflight_options),
+        )
+        .route(
+            &format!("{OAUTH2_REGISTRATION_ENDPOINT}/{{client_id}}"),
+            get(oauth2_client_registration_get)
+                .put(oauth2_client_registration_put)
+                .delete(oauth2_client_registration_delete),
+        )
+        // ⚠️  ⚠️   WARNING  ⚠️  ⚠️
+        // IF YOU CHANGE THESE VALUES YOU MUST UPDATE OIDC DISCOVERY URLS
+        .route(
+            OAUTH2_BACKCHANNEL_AUTHENTICATION_ENDPOINT,
+            post(oauth2_backchannel_authentication_post),
+        )
         .merge(openid_router)
         .with_state(state)
         .layer(from_fn(super::middleware::caching::dont_cache_me));
@@ -17,9 +17,10 @@ use compact_jwt::{Jwk, Jws, JwsSigner};
 use kanidm_proto::constants::uri::V1_AUTH_VALID;
 use kanidm_proto::internal::{
     ApiToken, AppLink, CUIntentSend, CUIntentToken, CURequest, CUSessionToken, CUStatus,
-    CreateRequest, CredentialStatus, DeleteRequest, IdentifyUserRequest, IdentifyUserResponse,
-    ModifyRequest, RadiusAuthToken, SearchRequest, SearchResponse, UserAuthToken,
-    COOKIE_AUTH_SESSION_ID, COOKIE_BEARER_TOKEN,
+    CreateRequest, CredentialSoftLockAccount, CredentialSoftLockStatus, CredentialStatus,
+    DeleteRequest, IdentifyUserRequest, IdentifyUserResponse, ModifyRequest, Oauth2Consent,
+    RadiusAuthToken, SearchRequest, SearchResponse, UserAuthToken, COOKIE_AUTH_SESSION_ID,
+    COOKIE_BEARER_TOKEN,
 };
 use kanidm_proto::v1::{
     AccountUnixExtend, ApiTokenGenerate, AuthIssueSession, AuthRequest, AuthResponse,
@@ -995,6 +996,73 @@ pub async fn service_account_api_token_delete(
         .map_err(WebError::from)
 }
 
+#[utoipa::path(
+    post,
+    path = "/v1/service_account/{id}/_oauth2_registration_scopemap/{group}",
+    request_body=Vec<String>,
+    responses(
+        DefaultApiResponse,
+    ),
+    security(("token_jwt" = [])),
+    tag = "service_account",
+    operation_id = "service_account_oauth2_registration_scopemap_post",
+)]
+/// Set the scopes that OAuth2 clients registered by this service account grant to a group
+pub async fn service_account_oauth2_registration_scopemap_post(
+    State(state): State<ServerState>,
+    Path((id, group)): Path<(String, String)>,
+    Extension(kopid): Extension<KOpId>,
+    VerifiedClientInformation(client_auth_info): VerifiedClientInformation,
+    Json(scopes): Json<Vec<String>>,
+) -> Result<Json<()>, WebError> {
+    let filter = filter_all!(f_eq(Attribute::Class, EntryClass::ServiceAccount.into()));
+    let filter = Filter::join_parts_and(filter, filter_all!(f_id(id.as_str())));
+    state
+        .qe_w_ref
+        .handle_service_account_oauth2_registration_scopemap_update(
+            client_auth_info,
+            group,
+            scopes,
+            filter,
+            kopid.eventid,
+        )
+        .await
+        .map(Json::from)
+        .map_err(WebError::from)
+}
+
+#[utoipa::path(
+    delete,
+    path = "/v1/service_account/{id}/_oauth2_registration_scopemap/{group}",
+    responses(
+        DefaultApiResponse,
+    ),
+    security(("token_jwt" = [])),
+    tag = "service_account",
+    operation_id = "service_account_oauth2_registration_scopemap_delete",
+)]
+/// Remove the scopes that OAuth2 clients registered by this service account grant to a group
+pub async fn service_account_oauth2_registration_scopemap_delete(
+    State(state): State<ServerState>,
+    Path((id, group)): Path<(String, String)>,
+    Extension(kopid): Extension<KOpId>,
+    VerifiedClientInformation(client_auth_info): VerifiedClientInformation,
+) -> Result<Json<()>, WebError> {
+    let filter = filter_all!(f_eq(Attribute::Class, EntryClass::ServiceAccount.into()));
+    let filter = Filter::join_parts_and(filter, filter_all!(f_id(id.as_str())));
+    state
+        .qe_w_ref
+        .handle_service_account_oauth2_registration_scopemap_delete(
+            client_auth_info,
+            group,
+            filter,
+            kopid.eventid,
+        )
+        .await
+        .map(Json::from)
+        .map_err(WebError::from)
+}
+
 #[utoipa::path(
     get,
     path = "/v1/person/{id}/_attr/{attr}",
@@ -1570,6 +1638,81 @@ pub async fn person_get_id_credential_status(
     }
 }
 
+#[utoipa::path(
+    get,
+    path = "/v1/person/_credential/_lock",
+    responses(
+        (status=200, body=Vec<CredentialSoftLockAccount>, content_type=APPLICATION_JSON),
+        ApiResponseWithout200,
+    ),
+    security(("token_jwt" = [])),
+    tag = "person/credential",
+)]
+/// List the persons that have at least one credential that is currently soft locked.
+pub async fn person_get_credential_lock(
+    State(state): State<ServerState>,
+    Extension(kopid): Extension<KOpId>,
+    VerifiedClientInformation(client_auth_info): VerifiedClientInformation,
+) -> Result<Json<Vec<CredentialSoftLockAccount>>, WebError> {
+    state
+        .qe_r_ref
+        .handle_list_credential_softlocked(client_auth_info, kopid.eventid)
+        .await
+        .map(Json::from)
+        .map_err(WebError::from)
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/person/{id}/_credential/_lock",
+    responses(
+        (status=200, body=Vec<CredentialSoftLockStatus>, content_type=APPLICATION_JSON),
+        ApiResponseWithout200,
+    ),
+    security(("token_jwt" = [])),
+    tag = "person/credential",
+)]
+/// Show the soft lock state of the credentials of a person. Credentials are soft locked
+/// after failed authentication attempts.
+pub async fn person_get_id_credential_lock(
+    State(state): State<ServerState>,
+    Extension(kopid): Extension<KOpId>,
+    VerifiedClientInformation(client_auth_info): VerifiedClientInformation,
+    Path(id): Path<String>,
+) -> Result<Json<Vec<CredentialSoftLockStatus>>, WebError> {
+    state
+        .qe_r_ref
+        .handle_idmcredentialsoftlockstatus(client_auth_info, id, kopid.eventid)
+        .await
+        .map(Json::from)
+        .map_err(WebError::from)
+}
+
+#[utoipa::path(
+    delete,
+    path = "/v1/person/{id}/_credential/_lock",
+    responses(
+        DefaultApiResponse,
+    ),
+    security(("token_jwt" = [])),
+    tag = "person/credential",
+    operation_id = "person_id_credential_lock_delete"
+)]
+/// Release the soft locks held on the credentials of a person.
+pub async fn person_id_credential_lock_delete(
+    State(state): State<ServerState>,
+    Extension(kopid): Extension<KOpId>,
+    VerifiedClientInformation(client_auth_info): VerifiedClientInformation,
+    Path(id): Path<String>,
+) -> Result<Json<()>, WebError> {
+    state
+        .qe_w_ref
+        .handle_idmcredentialsoftlockunlock(client_auth_info, id, kopid.eventid)
+        .await
+        .map(Json::from)
+        .map_err(WebError::from)
+}
+
 #[utoipa::path(
     get,
     path = "/v1/person/{id}/_ssh_pubkeys",
@@ -2836,6 +2979,56 @@ pub async fn applinks_get(
         .map_err(WebError::from)
 }
 
+#[utoipa::path(
+    get,
+    path = "/v1/self/_oauth2_consent",
+    responses(
+        (status=200, body=Vec<Oauth2Consent>, content_type=APPLICATION_JSON),
+        ApiResponseWithout200,
+    ),
+    security(("token_jwt" = [])),
+    tag = "self",
+    operation_id = "self_oauth2_consent_get",
+)]
+/// Returns the OAuth2 clients you have consented to, and the sessions they hold
+pub async fn self_oauth2_consent_get(
+    State(state): State<ServerState>,
+    Extension(kopid): Extension<KOpId>,
+    VerifiedClientInformation(client_auth_info): VerifiedClientInformation,
+) -> Result<Json<Vec<Oauth2Consent>>, WebError> {
+    state
+        .qe_r_ref
+        .handle_list_oauth2_consents(client_auth_info, kopid.eventid)
+        .await
+        .map(Json::from)
+        .map_err(WebError::from)
+}
+
+#[utoipa::path(
+    delete,
+    path = "/v1/self/_oauth2_consent/{id}",
+    responses(
+        DefaultApiResponse,
+    ),
+    security(("token_jwt" = [])),
+    tag = "self",
+    operation_id = "self_oauth2_consent_delete",
+)]
+/// Revoke your consent to an OAuth2 client, and end all of the sessions it holds for you
+pub async fn self_oauth2_consent_delete(
+    State(state): State<ServerState>,
+    Path(id): Path<String>,
+    Extension(kopid): Extension<KOpId>,
+    VerifiedClientInformation(client_auth_info): VerifiedClientInformation,
+) -> Result<Json<()>, WebError> {
+    state
+        .qe_w_ref
+        .handle_oauth2_consent_revoke(client_auth_info, id, kopid.eventid)
+        .await
+        .map(Json::from)
+        .map_err(WebError::from)
+}
+
 #[utoipa::path(
     post,
     path = "/v1/reauth",
@@ -2991,6 +3184,14 @@ fn auth_session_state_management(
                     debug!("🧩 -> AuthState::Denied");
                     Ok(ProtoAuthState::Denied(reason))
                 }
+                AuthState::CredentialUpdateRequired(intent) => {
+                    debug!("🧩 -> AuthState::CredentialUpdateRequired");
+                    jar = jar.remove(Cookie::from(COOKIE_AUTH_SESSION_ID));
+                    Ok(ProtoAuthState::CredentialUpdateRequired(CUIntentToken {
+                        token: intent.intent_id,
+                        expiry_time: intent.expiry_time,
+                    }))
+                }
             }
             .map(|state| AuthResponse { sessionid, state })
         }
@@ -3217,6 +3418,11 @@ pub(crate) fn route_setup(state: ServerState) -> Router<ServerState> {
         // )
         // Applinks are the list of apps this account can access.
         .route("/v1/self/_applinks", get(applinks_get))
+        .route("/v1/self/_oauth2_consent", get(self_oauth2_consent_get))
+        .route(
+            "/v1/self/_oauth2_consent/{id}",
+            delete(self_oauth2_consent_delete),
+        )
         // Person routes
         .route("/v1/person", get(person_get).post(person_post))
         .route("/v1/person/_search/{id}", get(person_search_id))
@@ -3241,6 +3447,14 @@ pub(crate) fn route_setup(state: ServerState) -> Router<ServerState> {
             "/v1/person/{id}/_credential/_status",
             get(person_get_id_credential_status),
         )
+        .route(
+            "/v1/person/_credential/_lock",
+            get(person_get_credential_lock),
+        )
+        .route(
+            "/v1/person/{id}/_credential/_lock",
+            get(person_get_id_credential_lock).delete(person_id_credential_lock_delete),
+        )
         .route(
             "/v1/person/{id}/_credential/_update",
             get(person_id_credential_update_get),
@@ -3316,6 +3530,11 @@ pub(crate) fn route_setup(state: ServerState) -> Router<ServerState> {
             "/v1/service_account/{id}/_api_token/{token_id}",
             delete(service_account_api_token_delete),
         )
+        .route(
+            "/v1/service_account/{id}/_oauth2_registration_scopemap/{group}",
+            post(service_account_oauth2_registration_scopemap_post)
+                .delete(service_account_oauth2_registration_scopemap_delete),
+        )
         // .route(
         //     "/v1/service_account/{id}/_credential",
         //     get(|| async { "TODO" }),
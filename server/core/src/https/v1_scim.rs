@@ -1,5 +1,5 @@
 use super::apidocs::response_schema::{ApiResponseWithout200, DefaultApiResponse};
-use super::errors::WebError;
+use super::errors::{operation_error_status_code, WebError};
 use super::middleware::KOpId;
 use super::v1::{
     json_rest_event_get, json_rest_event_get_id, json_rest_event_get_id_attr, json_rest_event_post,
@@ -8,18 +8,23 @@ use super::v1::{
 use super::ServerState;
 use crate::https::extractors::VerifiedClientInformation;
 use axum::extract::{rejection::JsonRejection, DefaultBodyLimit, Path, Query, State};
+use axum::http::StatusCode;
 use axum::response::{Html, IntoResponse, Response};
 use axum::routing::{delete, get, post};
 use axum::{Extension, Json, Router};
 use kanidm_proto::scim_v1::ScimEntry;
 use kanidm_proto::scim_v1::{
-    client::{ScimEntryPostGeneric, ScimEntryPutGeneric},
-    server::{ScimEntryKanidm, ScimListResponse},
+    client::{ScimBulkRequest, ScimEntryPatchGeneric, ScimEntryPostGeneric, ScimEntryPutGeneric},
+    server::{
+        ScimBulkOperationResponse, ScimBulkResponse, ScimEntryKanidm, ScimErrorResponse,
+        ScimListResponse,
+    },
     ScimApplicationPassword, ScimApplicationPasswordCreate, ScimEntryGetQuery, ScimSyncRequest,
-    ScimSyncState,
+    ScimSyncState, SCIM_BULK_MAX_PAYLOAD_BYTES, SCIM_SCHEMA_BULK_RESPONSE, SCIM_SCHEMA_ERROR,
 };
 use kanidm_proto::v1::Entry as ProtoEntry;
 use kanidmd_lib::prelude::*;
+use kanidmd_lib::server::scim::ScimBulkOperationResult;
 
 const DEFAULT_SCIM_SYNC_BYTES: usize = 1024 * 1024 * 32;
 
@@ -428,6 +433,108 @@ async fn scim_entry_id_delete(
         .map_err(WebError::from)
 }
 
+#[utoipa::path(
+    patch,
+    path = "/scim/v1/Entry/{id}",
+    request_body = ScimEntryPatchGeneric,
+    responses(
+        (status = 200, content_type=APPLICATION_JSON, body=ScimEntry),
+        ApiResponseWithout200,
+    ),
+    security(("token_jwt" = [])),
+    tag = "scim",
+    operation_id = "scim_entry_id_patch"
+)]
+/// Apply a SCIM PatchOp to an entry, returning the entry after the changes.
+async fn scim_entry_id_patch(
+    State(state): State<ServerState>,
+    Path(id): Path<String>,
+    Extension(kopid): Extension<KOpId>,
+    VerifiedClientInformation(client_auth_info): VerifiedClientInformation,
+    Query(scim_entry_get_query): Query<ScimEntryGetQuery>,
+    Json(patch_generic): Json<ScimEntryPatchGeneric>,
+) -> Result<Json<ScimEntryKanidm>, WebError> {
+    state
+        .qe_w_ref
+        .handle_scim_entry_patch(
+            client_auth_info,
+            kopid.eventid,
+            id,
+            scim_entry_get_query,
+            patch_generic,
+        )
+        .await
+        .map(Json::from)
+        .map_err(WebError::from)
+}
+
+#[utoipa::path(
+    post,
+    path = "/scim/v1/Bulk",
+    request_body = ScimBulkRequest,
+    responses(
+        (status = 200, content_type=APPLICATION_JSON, body=ScimBulkResponse),
+        ApiResponseWithout200,
+    ),
+    security(("token_jwt" = [])),
+    tag = "scim",
+    operation_id = "scim_bulk_post"
+)]
+/// Apply a SCIM bulk request of create, modify and delete operations on entries. Operations
+/// refer to the entries created by earlier operations with `bulkId:<bulkId>`.
+async fn scim_bulk_post(
+    State(state): State<ServerState>,
+    Extension(kopid): Extension<KOpId>,
+    VerifiedClientInformation(client_auth_info): VerifiedClientInformation,
+    Json(bulk_request): Json<ScimBulkRequest>,
+) -> Result<Json<ScimBulkResponse>, WebError> {
+    let outcomes = state
+        .qe_w_ref
+        .handle_scim_bulk(client_auth_info, kopid.eventid, bulk_request)
+        .await?;
+
+    let location = |uuid: Uuid| state.origin.join(&format!("/scim/v1/Entry/{uuid}")).ok();
+
+    let operations = outcomes
+        .into_iter()
+        .map(|outcome| {
+            let (location, status, response) = match outcome.result {
+                ScimBulkOperationResult::Created(uuid) => {
+                    (location(uuid), StatusCode::CREATED, None)
+                }
+                ScimBulkOperationResult::Modified(uuid) => (location(uuid), StatusCode::OK, None),
+                ScimBulkOperationResult::Deleted => (None, StatusCode::NO_CONTENT, None),
+                ScimBulkOperationResult::Failed(err) => {
+                    // RFC 7644 reports a uniqueness conflict of a bulk operation as 409.
+                    let status = match &err {
+                        OperationError::AttributeUniqueness(_) => StatusCode::CONFLICT,
+                        err => operation_error_status_code(err),
+                    };
+                    let response = ScimErrorResponse {
+                        schemas: vec![SCIM_SCHEMA_ERROR.to_string()],
+                        status: status.as_u16().to_string(),
+                        detail: Some(err.to_string()),
+                    };
+                    (None, status, Some(response))
+                }
+            };
+
+            ScimBulkOperationResponse {
+                method: outcome.method,
+                bulk_id: outcome.bulk_id,
+                location,
+                status: status.as_u16().to_string(),
+                response,
+            }
+        })
+        .collect();
+
+    Ok(Json(ScimBulkResponse {
+        schemas: vec![SCIM_SCHEMA_BULK_RESPONSE.to_string()],
+        operations,
+    }))
+}
+
 #[utoipa::path(
     get,
     path = "/scim/v1/Entry/{id}",
@@ -952,6 +1059,8 @@ pub fn route_setup() -> Router<ServerState> {
         //  Entry    /Entry/{id}      GET                    Retrieve a generic entry
         //                                                   of any kind from the database.
         //                                                   {id} is any unique id.
+        //                            PATCH                  Partially update an entry
+        //                                                   with a SCIM PatchOp.
         .route(
             "/scim/v1/Entry",
             get(scim_entry_get)
@@ -960,7 +1069,15 @@ pub fn route_setup() -> Router<ServerState> {
         )
         .route(
             "/scim/v1/Entry/{id}",
-            get(scim_entry_id_get).delete(scim_entry_id_delete),
+            get(scim_entry_id_get)
+                .patch(scim_entry_id_patch)
+                .delete(scim_entry_id_delete),
+        )
+        //  Bulk     /Bulk            POST                   Apply create, modify and delete
+        //                                                   operations to generic entries.
+        .route(
+            "/scim/v1/Bulk",
+            post(scim_bulk_post).layer(DefaultBodyLimit::max(SCIM_BULK_MAX_PAYLOAD_BYTES)),
         )
         //  Person   /Person/{id}     GET                    Retrieve a a person from the
         //                                                   database.
@@ -0,0 +1,610 @@
+//! SCIM core schema endpoints. These present Kanidm persons and groups as the RFC7643
+//! User and Group resources so that generic SCIM provisioning clients are able to
+//! manage them. The Kanidm specific SCIM endpoints are in [super::v1_scim].
+
+use super::apidocs::response_schema::ApiResponseWithout200;
+use super::errors::WebError;
+use super::middleware::KOpId;
+use super::ServerState;
+use crate::https::extractors::VerifiedClientInformation;
+use axum::extract::{Path, Query, State};
+use axum::http::StatusCode;
+use axum::routing::get;
+use axum::{Extension, Json, Router};
+use kanidm_proto::scim_v1::{
+    client::ScimEntryPatchGeneric,
+    core_schema::{
+        ScimCoreGroup, ScimCoreListResponse, ScimCoreResourceType, ScimCoreUser, ScimResourceType,
+        ScimSchemaDefinition, ScimServiceProviderConfig,
+    },
+    server::ScimEntryKanidm,
+    ScimEntryGetQuery,
+};
+use kanidmd_lib::prelude::*;
+use time::OffsetDateTime;
+
+/// Check that the entry `id` exists and is of the class that the core resource
+/// type maps to, so that a User can't be modified through the Groups endpoint.
+async fn scim_core_entry_check(
+    state: &ServerState,
+    kopid: &KOpId,
+    client_auth_info: ClientAuthInfo,
+    id: Uuid,
+    class: EntryClass,
+) -> Result<ScimEntryKanidm, WebError> {
+    state
+        .qe_r_ref
+        .scim_entry_id_get(
+            client_auth_info,
+            kopid.eventid,
+            id.to_string(),
+            class,
+            ScimEntryGetQuery::default(),
+        )
+        .await
+        .map_err(WebError::from)
+}
+
+fn scim_core_user(state: &ServerState, entry: &ScimEntryKanidm) -> Result<ScimCoreUser, WebError> {
+    ScimCoreUser::from_kanidm(
+        entry,
+        &state.origin,
+        OffsetDateTime::UNIX_EPOCH + duration_from_epoch_now(),
+    )
+    .ok_or(WebError::from(OperationError::NoMatchingEntries))
+}
+
+fn scim_core_group(
+    state: &ServerState,
+    entry: &ScimEntryKanidm,
+) -> Result<ScimCoreGroup, WebError> {
+    ScimCoreGroup::from_kanidm(entry, &state.origin)
+        .ok_or(WebError::from(OperationError::NoMatchingEntries))
+}
+
+#[utoipa::path(
+    get,
+    path = "/scim/v2/Users",
+    responses(
+        (status = 200, content_type=APPLICATION_JSON, body=ScimCoreListResponse<ScimCoreUser>),
+        ApiResponseWithout200,
+    ),
+    security(("token_jwt" = [])),
+    tag = "scim",
+    operation_id = "scim_core_users_get"
+)]
+/// List or filter persons as SCIM core Users.
+async fn scim_core_users_get(
+    State(state): State<ServerState>,
+    Extension(kopid): Extension<KOpId>,
+    VerifiedClientInformation(client_auth_info): VerifiedClientInformation,
+    Query(scim_entry_get_query): Query<ScimEntryGetQuery>,
+) -> Result<Json<ScimCoreListResponse<ScimCoreUser>>, WebError> {
+    let query = ScimCoreResourceType::User.query_to_kanidm(scim_entry_get_query)?;
+    let now = OffsetDateTime::UNIX_EPOCH + duration_from_epoch_now();
+
+    state
+        .qe_r_ref
+        .scim_entry_search(
+            client_auth_info,
+            kopid.eventid,
+            EntryClass::Person.into(),
+            query,
+        )
+        .await
+        .map(|list| {
+            ScimCoreListResponse::from_kanidm(list, |entry| {
+                ScimCoreUser::from_kanidm(entry, &state.origin, now)
+            })
+        })
+        .map(Json::from)
+        .map_err(WebError::from)
+}
+
+#[utoipa::path(
+    post,
+    path = "/scim/v2/Users",
+    request_body = ScimCoreUser,
+    responses(
+        (status = 201, content_type=APPLICATION_JSON, body=ScimCoreUser),
+        ApiResponseWithout200,
+    ),
+    security(("token_jwt" = [])),
+    tag = "scim",
+    operation_id = "scim_core_users_post"
+)]
+/// Create a person from a SCIM core User.
+async fn scim_core_users_post(
+    State(state): State<ServerState>,
+    Extension(kopid): Extension<KOpId>,
+    VerifiedClientInformation(client_auth_info): VerifiedClientInformation,
+    Json(user): Json<ScimCoreUser>,
+) -> Result<(StatusCode, Json<ScimCoreUser>), WebError> {
+    let post_generic =
+        user.to_kanidm_post(OffsetDateTime::UNIX_EPOCH + duration_from_epoch_now())?;
+
+    let entry = state
+        .qe_w_ref
+        .scim_entry_create(
+            client_auth_info,
+            kopid.eventid,
+            &[EntryClass::Account, EntryClass::Person],
+            post_generic,
+        )
+        .await?;
+
+    scim_core_user(&state, &entry).map(|user| (StatusCode::CREATED, Json(user)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/scim/v2/Users/{id}",
+    responses(
+        (status = 200, content_type=APPLICATION_JSON, body=ScimCoreUser),
+        ApiResponseWithout200,
+    ),
+    security(("token_jwt" = [])),
+    tag = "scim",
+    operation_id = "scim_core_users_id_get"
+)]
+async fn scim_core_users_id_get(
+    State(state): State<ServerState>,
+    Path(id): Path<Uuid>,
+    Extension(kopid): Extension<KOpId>,
+    VerifiedClientInformation(client_auth_info): VerifiedClientInformation,
+) -> Result<Json<ScimCoreUser>, WebError> {
+    let entry =
+        scim_core_entry_check(&state, &kopid, client_auth_info, id, EntryClass::Person).await?;
+
+    scim_core_user(&state, &entry).map(Json::from)
+}
+
+#[utoipa::path(
+    put,
+    path = "/scim/v2/Users/{id}",
+    request_body = ScimCoreUser,
+    responses(
+        (status = 200, content_type=APPLICATION_JSON, body=ScimCoreUser),
+        ApiResponseWithout200,
+    ),
+    security(("token_jwt" = [])),
+    tag = "scim",
+    operation_id = "scim_core_users_id_put"
+)]
+/// Replace the core attributes of a person.
+async fn scim_core_users_id_put(
+    State(state): State<ServerState>,
+    Path(id): Path<Uuid>,
+    Extension(kopid): Extension<KOpId>,
+    VerifiedClientInformation(client_auth_info): VerifiedClientInformation,
+    Json(user): Json<ScimCoreUser>,
+) -> Result<Json<ScimCoreUser>, WebError> {
+    let current = scim_core_entry_check(
+        &state,
+        &kopid,
+        client_auth_info.clone(),
+        id,
+        EntryClass::Person,
+    )
+    .await?;
+
+    let put_generic = user.to_kanidm_put(
+        &current,
+        OffsetDateTime::UNIX_EPOCH + duration_from_epoch_now(),
+    )?;
+
+    let entry = state
+        .qe_w_ref
+        .handle_scim_entry_put(client_auth_info, kopid.eventid, put_generic)
+        .await?;
+
+    scim_core_user(&state, &entry).map(Json::from)
+}
+
+#[utoipa::path(
+    patch,
+    path = "/scim/v2/Users/{id}",
+    request_body = ScimEntryPatchGeneric,
+    responses(
+        (status = 200, content_type=APPLICATION_JSON, body=ScimCoreUser),
+        ApiResponseWithout200,
+    ),
+    security(("token_jwt" = [])),
+    tag = "scim",
+    operation_id = "scim_core_users_id_patch"
+)]
+/// Apply a SCIM PatchOp to the core attributes of a person.
+async fn scim_core_users_id_patch(
+    State(state): State<ServerState>,
+    Path(id): Path<Uuid>,
+    Extension(kopid): Extension<KOpId>,
+    VerifiedClientInformation(client_auth_info): VerifiedClientInformation,
+    Json(patch_generic): Json<ScimEntryPatchGeneric>,
+) -> Result<Json<ScimCoreUser>, WebError> {
+    let patch_generic = ScimCoreResourceType::User.patch_to_kanidm(
+        patch_generic,
+        OffsetDateTime::UNIX_EPOCH + duration_from_epoch_now(),
+    )?;
+
+    scim_core_entry_check(
+        &state,
+        &kopid,
+        client_auth_info.clone(),
+        id,
+        EntryClass::Person,
+    )
+    .await?;
+
+    let entry = state
+        .qe_w_ref
+        .handle_scim_entry_patch(
+            client_auth_info,
+            kopid.eventid,
+            id.to_string(),
+            ScimEntryGetQuery::default(),
+            patch_generic,
+        )
+        .await?;
+
+    scim_core_user(&state, &entry).map(Json::from)
+}
+
+#[utoipa::path(
+    delete,
+    path = "/scim/v2/Users/{id}",
+    responses(
+        (status = 204),
+        ApiResponseWithout200,
+    ),
+    security(("token_jwt" = [])),
+    tag = "scim",
+    operation_id = "scim_core_users_id_delete"
+)]
+async fn scim_core_users_id_delete(
+    State(state): State<ServerState>,
+    Path(id): Path<Uuid>,
+    Extension(kopid): Extension<KOpId>,
+    VerifiedClientInformation(client_auth_info): VerifiedClientInformation,
+) -> Result<StatusCode, WebError> {
+    state
+        .qe_w_ref
+        .scim_entry_id_delete(
+            client_auth_info,
+            kopid.eventid,
+            id.to_string(),
+            EntryClass::Person,
+        )
+        .await
+        .map(|()| StatusCode::NO_CONTENT)
+        .map_err(WebError::from)
+}
+
+#[utoipa::path(
+    get,
+    path = "/scim/v2/Groups",
+    responses(
+        (status = 200, content_type=APPLICATION_JSON, body=ScimCoreListResponse<ScimCoreGroup>),
+        ApiResponseWithout200,
+    ),
+    security(("token_jwt" = [])),
+    tag = "scim",
+    operation_id = "scim_core_groups_get"
+)]
+/// List or filter groups as SCIM core Groups.
+async fn scim_core_groups_get(
+    State(state): State<ServerState>,
+    Extension(kopid): Extension<KOpId>,
+    VerifiedClientInformation(client_auth_info): VerifiedClientInformation,
+    Query(scim_entry_get_query): Query<ScimEntryGetQuery>,
+) -> Result<Json<ScimCoreListResponse<ScimCoreGroup>>, WebError> {
+    let query = ScimCoreResourceType::Group.query_to_kanidm(scim_entry_get_query)?;
+
+    state
+        .qe_r_ref
+        .scim_entry_search(
+            client_auth_info,
+            kopid.eventid,
+            EntryClass::Group.into(),
+            query,
+        )
+        .await
+        .map(|list| {
+            ScimCoreListResponse::from_kanidm(list, |entry| {
+                ScimCoreGroup::from_kanidm(entry, &state.origin)
+            })
+        })
+        .map(Json::from)
+        .map_err(WebError::from)
+}
+
+#[utoipa::path(
+    post,
+    path = "/scim/v2/Groups",
+    request_body = ScimCoreGroup,
+    responses(
+        (status = 201, content_type=APPLICATION_JSON, body=ScimCoreGroup),
+        ApiResponseWithout200,
+    ),
+    security(("token_jwt" = [])),
+    tag = "scim",
+    operation_id = "scim_core_groups_post"
+)]
+/// Create a group from a SCIM core Group.
+async fn scim_core_groups_post(
+    State(state): State<ServerState>,
+    Extension(kopid): Extension<KOpId>,
+    VerifiedClientInformation(client_auth_info): VerifiedClientInformation,
+    Json(group): Json<ScimCoreGroup>,
+) -> Result<(StatusCode, Json<ScimCoreGroup>), WebError> {
+    let entry = state
+        .qe_w_ref
+        .scim_entry_create(
+            client_auth_info,
+            kopid.eventid,
+            &[EntryClass::Group],
+            group.to_kanidm_post(),
+        )
+        .await?;
+
+    scim_core_group(&state, &entry).map(|group| (StatusCode::CREATED, Json(group)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/scim/v2/Groups/{id}",
+    responses(
+        (status = 200, content_type=APPLICATION_JSON, body=ScimCoreGroup),
+        ApiResponseWithout200,
+    ),
+    security(("token_jwt" = [])),
+    tag = "scim",
+    operation_id = "scim_core_groups_id_get"
+)]
+async fn scim_core_groups_id_get(
+    State(state): State<ServerState>,
+    Path(id): Path<Uuid>,
+    Extension(kopid): Extension<KOpId>,
+    VerifiedClientInformation(client_auth_info): VerifiedClientInformation,
+) -> Result<Json<ScimCoreGroup>, WebError> {
+    let entry =
+        scim_core_entry_check(&state, &kopid, client_auth_info, id, EntryClass::Group).await?;
+
+    scim_core_group(&state, &entry).map(Json::from)
+}
+
+#[utoipa::path(
+    put,
+    path = "/scim/v2/Groups/{id}",
+    request_body = ScimCoreGroup,
+    responses(
+        (status = 200, content_type=APPLICATION_JSON, body=ScimCoreGroup),
+        ApiResponseWithout200,
+    ),
+    security(("token_jwt" = [])),
+    tag = "scim",
+    operation_id = "scim_core_groups_id_put"
+)]
+/// Replace the name and members of a group.
+async fn scim_core_groups_id_put(
+    State(state): State<ServerState>,
+    Path(id): Path<Uuid>,
+    Extension(kopid): Extension<KOpId>,
+    VerifiedClientInformation(client_auth_info): VerifiedClientInformation,
+    Json(group): Json<ScimCoreGroup>,
+) -> Result<Json<ScimCoreGroup>, WebError> {
+    scim_core_entry_check(
+        &state,
+        &kopid,
+        client_auth_info.clone(),
+        id,
+        EntryClass::Group,
+    )
+    .await?;
+
+    // This is not a route, but it looks like one to the apidocs route check.
+    let put_generic = group.to_kanidm_put(id); // skip_route_check
+
+    let entry = state
+        .qe_w_ref
+        .handle_scim_entry_put(client_auth_info, kopid.eventid, put_generic)
+        .await?;
+
+    scim_core_group(&state, &entry).map(Json::from)
+}
+
+#[utoipa::path(
+    patch,
+    path = "/scim/v2/Groups/{id}",
+    request_body = ScimEntryPatchGeneric,
+    responses(
+        (status = 200, content_type=APPLICATION_JSON, body=ScimCoreGroup),
+        ApiResponseWithout200,
+    ),
+    security(("token_jwt" = [])),
+    tag = "scim",
+    operation_id = "scim_core_groups_id_patch"
+)]
+/// Apply a SCIM PatchOp to the name and members of a group.
+async fn scim_core_groups_id_patch(
+    State(state): State<ServerState>,
+    Path(id): Path<Uuid>,
+    Extension(kopid): Extension<KOpId>,
+    VerifiedClientInformation(client_auth_info): VerifiedClientInformation,
+    Json(patch_generic): Json<ScimEntryPatchGeneric>,
+) -> Result<Json<ScimCoreGroup>, WebError> {
+    let patch_generic = ScimCoreResourceType::Group.patch_to_kanidm(
+        patch_generic,
+        OffsetDateTime::UNIX_EPOCH + duration_from_epoch_now(),
+    )?;
+
+    scim_core_entry_check(
+        &state,
+        &kopid,
+        client_auth_info.clone(),
+        id,
+        EntryClass::Group,
+    )
+    .await?;
+
+    let entry = state
+        .qe_w_ref
+        .handle_scim_entry_patch(
+            client_auth_info,
+            kopid.eventid,
+            id.to_string(),
+            ScimEntryGetQuery::default(),
+            patch_generic,
+        )
+        .await?;
+
+    scim_core_group(&state, &entry).map(Json::from)
+}
+
+#[utoipa::path(
+    delete,
+    path = "/scim/v2/Groups/{id}",
+    responses(
+        (status = 204),
+        ApiResponseWithout200,
+    ),
+    security(("token_jwt" = [])),
+    tag = "scim",
+    operation_id = "scim_core_groups_id_delete"
+)]
+async fn scim_core_groups_id_delete(
+    State(state): State<ServerState>,
+    Path(id): Path<Uuid>,
+    Extension(kopid): Extension<KOpId>,
+    VerifiedClientInformation(client_auth_info): VerifiedClientInformation,
+) -> Result<StatusCode, WebError> {
+    state
+        .qe_w_ref
+        .scim_entry_id_delete(
+            client_auth_info,
+            kopid.eventid,
+            id.to_string(),
+            EntryClass::Group,
+        )
+        .await
+        .map(|()| StatusCode::NO_CONTENT)
+        .map_err(WebError::from)
+}
+
+#[utoipa::path(
+    get,
+    path = "/scim/v2/ServiceProviderConfig",
+    responses(
+        (status = 200, content_type=APPLICATION_JSON, body=ScimServiceProviderConfig),
+        ApiResponseWithout200,
+    ),
+    security(("token_jwt" = [])),
+    tag = "scim",
+    operation_id = "scim_core_service_provider_config_get"
+)]
+/// The SCIM features that this server supports.
+async fn scim_core_service_provider_config_get(
+    State(state): State<ServerState>,
+    VerifiedClientInformation(_client_auth_info): VerifiedClientInformation,
+) -> Json<ScimServiceProviderConfig> {
+    Json(ScimServiceProviderConfig::new(
+        &state.origin,
+        DEFAULT_LIMIT_SEARCH_MAX_RESULTS,
+    ))
+}
+
+#[utoipa::path(
+    get,
+    path = "/scim/v2/ResourceTypes",
+    responses(
+        (status = 200, content_type=APPLICATION_JSON, body=ScimCoreListResponse<ScimResourceType>),
+        ApiResponseWithout200,
+    ),
+    security(("token_jwt" = [])),
+    tag = "scim",
+    operation_id = "scim_core_resource_types_get"
+)]
+/// The SCIM core resource types that this server supports.
+async fn scim_core_resource_types_get(
+    State(state): State<ServerState>,
+    VerifiedClientInformation(_client_auth_info): VerifiedClientInformation,
+) -> Json<ScimCoreListResponse<ScimResourceType>> {
+    Json(ScimCoreListResponse::new(vec![
+        ScimResourceType::new(ScimCoreResourceType::User, &state.origin),
+        ScimResourceType::new(ScimCoreResourceType::Group, &state.origin),
+    ]))
+}
+
+#[utoipa::path(
+    get,
+    path = "/scim/v2/Schemas",
+    responses(
+        (status = 200, content_type=APPLICATION_JSON, body=ScimCoreListResponse<ScimSchemaDefinition>),
+        ApiResponseWithout200,
+    ),
+    security(("token_jwt" = [])),
+    tag = "scim",
+    operation_id = "scim_core_schemas_get"
+)]
+/// The attributes of the SCIM core schemas that this server supports.
+async fn scim_core_schemas_get(
+    State(state): State<ServerState>,
+    VerifiedClientInformation(_client_auth_info): VerifiedClientInformation,
+) -> Json<ScimCoreListResponse<ScimSchemaDefinition>> {
+    Json(ScimCoreListResponse::new(vec![
+        ScimSchemaDefinition::new(ScimCoreResourceType::User, &state.origin),
+        ScimSchemaDefinition::new(ScimCoreResourceType::Group, &state.origin),
+    ]))
+}
+
+#[instrument(level = "debug", skip_all, name = "https_v2_scim_route_setup")]
+pub fn route_setup() -> Router<ServerState> {
+    Router::new()
+        // https://datatracker.ietf.org/doc/html/rfc7644#section-3.2
+        //
+        //  Resource Endpoint         Operations             Description
+        //  -------- ---------------- ---------------------- --------------------
+        //  User     /Users           GET, POST              List, filter or create
+        //                                                   persons.
+        //           /Users/{id}      GET, PUT, PATCH,       Retrieve, modify or
+        //                            DELETE                 delete a person.
+        //
+        //  Group    /Groups          GET, POST              List, filter or create
+        //                                                   groups.
+        //           /Groups/{id}     GET, PUT, PATCH,       Retrieve, modify or
+        //                            DELETE                 delete a group.
+        .route(
+            "/scim/v2/Users",
+            get(scim_core_users_get).post(scim_core_users_post),
+        )
+        .route(
+            "/scim/v2/Users/{id}",
+            get(scim_core_users_id_get)
+                .put(scim_core_users_id_put)
+                .patch(scim_core_users_id_patch)
+                .delete(scim_core_users_id_delete),
+        )
+        .route(
+            "/scim/v2/Groups",
+            get(scim_core_groups_get).post(scim_core_groups_post),
+        )
+        .route(
+            "/scim/v2/Groups/{id}",
+            get(scim_core_groups_id_get)
+                .put(scim_core_groups_id_put)
+                .patch(scim_core_groups_id_patch)
+                .delete(scim_core_groups_id_delete),
+        )
+        //  Service  /ServiceProvider GET (Section 4)        Retrieve service
+        //  provider Config                                  provider's
+        //  config.                                          configuration.
+        .route(
+            "/scim/v2/ServiceProviderConfig",
+            get(scim_core_service_provider_config_get),
+        )
+        //  Resource /ResourceTypes   GET (Section 4)        Retrieve supported
+        //  type                                             resource types.
+        .route("/scim/v2/ResourceTypes", get(scim_core_resource_types_get))
+        //  Schema   /Schemas         GET (Section 4)        Retrieve one or more
+        //                                                   supported schemas.
+        .route("/scim/v2/Schemas", get(scim_core_schemas_get))
+}
@@ -2,12 +2,15 @@ use askama::Template;
 use askama_web::WebTemplate;
 use axum::{
     extract::State,
-    response::{IntoResponse, Response},
-    Extension,
+    response::{IntoResponse, Redirect, Response},
+    Extension, Form,
 };
 use axum_htmx::HxPushUrl;
+use serde::Deserialize;
+use uuid::Uuid;
 
 use kanidm_proto::internal::{AppLink, UserAuthToken};
+use kanidmd_lib::idm::oauth2::PendingBackchannelAuthorisation;
 
 use super::constants::Urls;
 use super::navbar::NavbarCtx;
@@ -27,6 +30,13 @@ struct AppsView {
 #[template(path = "apps_partial.html")]
 struct AppsPartialView {
     apps: Vec<AppLink>,
+    backchannel_authorisations: Vec<PendingBackchannelAuthorisation>,
+}
+
+#[derive(Deserialize, Debug)]
+pub(crate) struct BackchannelDecisionForm {
+    auth_req_id: Uuid,
+    approve: bool,
 }
 
 pub(crate) async fn view_apps_get(
@@ -43,11 +53,19 @@ pub(crate) async fn view_apps_get(
         .handle_list_applinks(client_auth_info.clone(), kopid.eventid)
         .await
         .map_err(|old| HtmxError::new(&kopid, old, domain_info.clone()))?;
+    let backchannel_authorisations = state
+        .qe_r_ref
+        .handle_list_oauth2_backchannel_authorisations(client_auth_info.clone(), kopid.eventid)
+        .await
+        .map_err(|old| HtmxError::new(&kopid, old, domain_info.clone()))?;
     let uat: &UserAuthToken = client_auth_info
         .pre_validated_uat()
         .map_err(|op_err| HtmxError::new(&kopid, op_err, domain_info.clone()))?;
 
-    let apps_partial = AppsPartialView { apps: app_links };
+    let apps_partial = AppsPartialView {
+        apps: app_links,
+        backchannel_authorisations,
+    };
 
     println!("{:?}", &uat.ui_hints);
     Ok({
@@ -59,3 +77,25 @@ pub(crate) async fn view_apps_get(
         (HxPushUrl(Urls::Apps.to_string()), apps_view).into_response()
     })
 }
+
+/// Approve or deny a pending client initiated backchannel authentication request.
+pub(crate) async fn view_apps_backchannel_post(
+    State(state): State<ServerState>,
+    Extension(kopid): Extension<KOpId>,
+    VerifiedClientInformation(client_auth_info): VerifiedClientInformation,
+    DomainInfo(domain_info): DomainInfo,
+    Form(decision): Form<BackchannelDecisionForm>,
+) -> axum::response::Result<Response> {
+    state
+        .qe_w_ref
+        .handle_oauth2_backchannel_authorisation_decide(
+            client_auth_info,
+            decision.auth_req_id,
+            decision.approve,
+            kopid.eventid,
+        )
+        .await
+        .map_err(|old| HtmxError::new(&kopid, old, domain_info))?;
+
+    Ok(Redirect::to(Urls::Apps.as_ref()).into_response())
+}
@@ -0,0 +1,85 @@
+use super::constants::{ProfileMenuItems, Urls};
+use super::navbar::NavbarCtx;
+use crate::https::extractors::{DomainInfo, VerifiedClientInformation};
+use crate::https::middleware::KOpId;
+use crate::https::views::errors::HtmxError;
+use crate::https::ServerState;
+use askama::Template;
+use askama_web::WebTemplate;
+use axum::extract::State;
+use axum::response::{IntoResponse, Response};
+use axum::Extension;
+use axum_extra::extract::Form;
+use kanidm_proto::internal::{Oauth2Consent, UserAuthToken};
+use serde::Deserialize;
+
+#[derive(Template, WebTemplate)]
+#[template(path = "user_settings.html")]
+pub(crate) struct ProfileView {
+    navbar_ctx: NavbarCtx,
+    profile_partial: ConsentPartialView,
+}
+
+#[derive(Template, Clone, WebTemplate)]
+#[template(path = "user_settings_consent_partial.html")]
+pub(crate) struct ConsentPartialView {
+    menu_active_item: ProfileMenuItems,
+    consents: Vec<Oauth2Consent>,
+}
+
+#[derive(Deserialize, Debug)]
+pub(crate) struct RevokeConsentForm {
+    name: String,
+}
+
+pub(crate) async fn view_consent_get(
+    State(state): State<ServerState>,
+    Extension(kopid): Extension<KOpId>,
+    VerifiedClientInformation(client_auth_info): VerifiedClientInformation,
+    DomainInfo(domain_info): DomainInfo,
+) -> axum::response::Result<Response> {
+    let uat: &UserAuthToken = client_auth_info
+        .pre_validated_uat()
+        .map_err(|op_err| HtmxError::new(&kopid, op_err, domain_info.clone()))?;
+
+    let consents = state
+        .qe_r_ref
+        .handle_list_oauth2_consents(client_auth_info.clone(), kopid.eventid)
+        .await
+        .map_err(|op_err| HtmxError::new(&kopid, op_err, domain_info.clone()))?;
+
+    Ok(ProfileView {
+        navbar_ctx: NavbarCtx::new(domain_info, &uat.ui_hints),
+        profile_partial: ConsentPartialView {
+            menu_active_item: ProfileMenuItems::Consent,
+            consents,
+        },
+    }
+    .into_response())
+}
+
+pub(crate) async fn view_consent_revoke_post(
+    State(state): State<ServerState>,
+    Extension(kopid): Extension<KOpId>,
+    VerifiedClientInformation(client_auth_info): VerifiedClientInformation,
+    DomainInfo(domain_info): DomainInfo,
+    Form(revoke_form): Form<RevokeConsentForm>,
+) -> axum::response::Result<Response> {
+    state
+        .qe_w_ref
+        .handle_oauth2_consent_revoke(client_auth_info.clone(), revoke_form.name, kopid.eventid)
+        .await
+        .map_err(|op_err| HtmxError::new(&kopid, op_err, domain_info.clone()))?;
+
+    let consents = state
+        .qe_r_ref
+        .handle_list_oauth2_consents(client_auth_info, kopid.eventid)
+        .await
+        .map_err(|op_err| HtmxError::new(&kopid, op_err, domain_info))?;
+
+    Ok(ConsentPartialView {
+        menu_active_item: ProfileMenuItems::Consent,
+        consents,
+    }
+    .into_response())
+}
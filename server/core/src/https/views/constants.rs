@@ -8,6 +8,7 @@ pub(crate) enum ProfileMenuItems {
     EnrolDevice,
     UnixPassword,
     Radius,
+    Consent,
 }
 
 // pub(crate) enum UiMessage {
@@ -33,6 +34,7 @@ pub(crate) enum Urls {
     Ui,
     WellKnownChangePassword,
     Radius,
+    Consent,
     Admin,
 }
 
@@ -49,6 +51,7 @@ impl AsRef<str> for Urls {
             Self::Ui => "/ui",
             Self::WellKnownChangePassword => "/.well-known/change-password",
             Self::Radius => "/ui/radius",
+            Self::Consent => "/ui/consent",
             Self::Admin => "/ui/admin/persons",
         }
     }
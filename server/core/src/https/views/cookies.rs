@@ -3,6 +3,9 @@
 use crate::https::ServerState;
 use axum_extra::extract::cookie::{Cookie, CookieJar, SameSite};
 use compact_jwt::{Jws, JwsSigner};
+use kanidm_proto::internal::{
+    COOKIE_AUTH_SESSION_ID, COOKIE_BEARER_TOKEN, COOKIE_CU_SESSION_TOKEN, COOKIE_OAUTH2_REQ,
+};
 use serde::de::DeserializeOwned;
 use serde::Serialize;
 
@@ -39,6 +42,14 @@ pub fn destroy(jar: CookieJar, ck_id: &str, state: &ServerState) -> CookieJar {
     }
 }
 
+/// Remove the cookies of the user's session, such as when they logout.
+pub fn destroy_session(mut jar: CookieJar, state: &ServerState) -> CookieJar {
+    jar = destroy(jar, COOKIE_BEARER_TOKEN, state);
+    jar = destroy(jar, COOKIE_OAUTH2_REQ, state);
+    jar = destroy(jar, COOKIE_AUTH_SESSION_ID, state);
+    destroy(jar, COOKIE_CU_SESSION_TOKEN, state)
+}
+
 pub fn make_unsigned<'a>(state: &'_ ServerState, ck_id: &'a str, value: String) -> Cookie<'a> {
     new_cookie(state, ck_id, value)
 }
@@ -18,8 +18,7 @@ use axum::{
 use axum_extra::extract::cookie::{CookieJar, SameSite};
 use hyper::Uri;
 use kanidm_proto::internal::{
-    UserAuthToken, COOKIE_AUTH_SESSION_ID, COOKIE_BEARER_TOKEN, COOKIE_CU_SESSION_TOKEN,
-    COOKIE_OAUTH2_REQ, COOKIE_USERNAME,
+    UserAuthToken, COOKIE_AUTH_SESSION_ID, COOKIE_BEARER_TOKEN, COOKIE_OAUTH2_REQ, COOKIE_USERNAME,
 };
 use kanidm_proto::{
     oauth2::{AccessTokenRequest, AccessTokenResponse},
@@ -168,7 +167,7 @@ pub async fn view_logout_get(
     VerifiedClientInformation(client_auth_info): VerifiedClientInformation,
     Extension(kopid): Extension<KOpId>,
     DomainInfo(domain_info): DomainInfo,
-    mut jar: CookieJar,
+    jar: CookieJar,
 ) -> Response {
     let response = if let Err(err_code) = state
         .qe_w_ref
@@ -186,10 +185,7 @@ pub async fn view_logout_get(
     };
 
     // Always clear cookies even on an error.
-    jar = cookies::destroy(jar, COOKIE_BEARER_TOKEN, &state);
-    jar = cookies::destroy(jar, COOKIE_OAUTH2_REQ, &state);
-    jar = cookies::destroy(jar, COOKIE_AUTH_SESSION_ID, &state);
-    jar = cookies::destroy(jar, COOKIE_CU_SESSION_TOKEN, &state);
+    let jar = cookies::destroy_session(jar, &state);
 
     (jar, response).into_response()
 }
@@ -1079,6 +1075,14 @@ async fn view_login_step(
                     }
                 }
             }
+            AuthState::CredentialUpdateRequired(intent) => {
+                debug!("🧩 -> AuthState::CredentialUpdateRequired");
+                jar = cookies::destroy(jar, COOKIE_AUTH_SESSION_ID, &state);
+
+                // The password has expired, send the user to change it.
+                let token_uri_string = format!("{}?token={}", Urls::CredReset, intent.intent_id);
+                break Redirect::to(&token_uri_string).into_response();
+            }
             AuthState::Denied(reason) => {
                 debug!("🧩 -> AuthState::Denied");
                 jar = cookies::destroy(jar, COOKIE_AUTH_SESSION_ID, &state);
@@ -18,8 +18,9 @@ use kanidmd_lib::{
 
 mod admin;
 mod apps;
+mod consent;
 pub(crate) mod constants;
-mod cookies;
+pub(crate) mod cookies;
 mod enrol;
 mod errors;
 mod login;
@@ -101,6 +102,7 @@ pub fn view_router(state: ServerState) -> Router<ServerState> {
             get(|| async { Redirect::permanent(Urls::Login.as_ref()) }),
         )
         .route("/apps", get(apps::view_apps_get))
+        .route("/apps/backchannel", post(apps::view_apps_backchannel_post))
         .route("/enrol", get(enrol::view_enrol_get))
         .route("/reset", get(reset::view_reset_get))
         .route(
@@ -111,6 +113,7 @@ pub fn view_router(state: ServerState) -> Router<ServerState> {
         .route("/profile", get(profile::view_profile_get))
         .route("/profile/diff", get(profile::view_profile_get))
         .route("/radius", get(radius::view_radius_get))
+        .route("/consent", get(consent::view_consent_get))
         .route("/unlock", get(login::view_reauth_to_referer_get))
         .route("/logout", get(login::view_logout_get));
 
@@ -134,6 +137,10 @@ pub fn view_router(state: ServerState) -> Router<ServerState> {
             post(reset::view_add_ssh_publickey),
         )
         .route("/radius/generate", post(radius::view_radius_post))
+        .route(
+            "/api/user_settings/revoke_consent",
+            post(consent::view_consent_revoke_post),
+        )
         .route("/api/delete_alt_creds", post(reset::remove_alt_creds))
         .route("/api/delete_unixcred", post(reset::remove_unixcred))
         .route("/api/add_totp", post(reset::add_totp))
@@ -3,7 +3,9 @@ use crate::https::{
     middleware::KOpId,
     ServerState,
 };
-use kanidmd_lib::idm::oauth2::{AuthorisationRequest, AuthoriseResponse, Oauth2Error};
+use kanidmd_lib::idm::oauth2::{
+    AuthorisationDetail, AuthorisationRequestParams, AuthoriseResponse, Oauth2Error,
+};
 use kanidmd_lib::prelude::*;
 
 use kanidm_proto::internal::COOKIE_OAUTH2_REQ;
@@ -13,10 +15,12 @@ use std::collections::BTreeSet;
 use askama::Template;
 use askama_web::WebTemplate;
 
+#[cfg(feature = "dev-oauth2-device-flow")]
+use axum::extract::Query;
 #[cfg(feature = "dev-oauth2-device-flow")]
 use axum::http::StatusCode;
 use axum::{
-    extract::{Query, State},
+    extract::{RawQuery, State},
     http::header::ACCESS_CONTROL_ALLOW_ORIGIN,
     response::{IntoResponse, Redirect, Response},
     Extension, Form,
@@ -34,6 +38,7 @@ struct ConsentRequestView {
     client_name: String,
     // scopes: BTreeSet<String>,
     pii_scopes: BTreeSet<String>,
+    authorization_details: Vec<AuthorisationDetail>,
     consent_token: String,
     redirect: Option<String>,
 }
@@ -50,15 +55,29 @@ pub async fn view_index_get(
     VerifiedClientInformation(client_auth_info): VerifiedClientInformation,
     DomainInfo(domain_info): DomainInfo,
     jar: CookieJar,
-    Query(auth_req): Query<AuthorisationRequest>,
+    RawQuery(query): RawQuery,
 ) -> Response {
+    let auth_req_params =
+        match AuthorisationRequestParams::from_query(query.as_deref().unwrap_or_default()) {
+            Ok(auth_req_params) => auth_req_params,
+            Err(err) => {
+                error!(?err, "Unable to parse OAuth2 authorisation request");
+                return UnrecoverableErrorView {
+                    err_code: OperationError::InvalidRequestState,
+                    operation_id: kopid.eventid,
+                    domain_info,
+                }
+                .into_response();
+            }
+        };
+
     oauth2_auth_req(
         state,
         kopid,
         client_auth_info,
         domain_info,
         jar,
-        Some(auth_req),
+        Some(auth_req_params),
     )
     .await
 }
@@ -71,7 +90,7 @@ pub async fn view_resume_get(
     jar: CookieJar,
 ) -> Response {
     let maybe_auth_req =
-        cookies::get_signed::<AuthorisationRequest>(&state, &jar, COOKIE_OAUTH2_REQ);
+        cookies::get_signed::<AuthorisationRequestParams>(&state, &jar, COOKIE_OAUTH2_REQ);
 
     oauth2_auth_req(
         state,
@@ -90,7 +109,7 @@ async fn oauth2_auth_req(
     client_auth_info: ClientAuthInfo,
     domain_info: DomainInfoRead,
     jar: CookieJar,
-    maybe_auth_req: Option<AuthorisationRequest>,
+    maybe_auth_req: Option<AuthorisationRequestParams>,
 ) -> Response {
     // No matter what, we always clear the stored oauth2 cookie to prevent
     // ui loops
@@ -138,6 +157,7 @@ async fn oauth2_auth_req(
             scopes: _,
             pii_scopes,
             consent_token,
+            authorization_details,
         }) => {
             // We can just render the form now, the consent token has everything we need.
             (
@@ -146,6 +166,7 @@ async fn oauth2_auth_req(
                     client_name,
                     // scopes,
                     pii_scopes,
+                    authorization_details,
                     consent_token,
                     redirect: None,
                 },
@@ -1,12 +1,15 @@
 use crate::actors::QueryServerReadV1;
 use crate::config::TcpAddressInfo;
+use crate::crypto::{PeerCertificate, ServerTlsAcceptor};
 use crate::tcp::process_client_addr;
 use crate::CoreAction;
 use futures_util::sink::SinkExt;
 use futures_util::stream::StreamExt;
-use kanidmd_lib::idm::ldap::{LdapBoundToken, LdapResponseState};
+use kanidmd_lib::idm::ldap::{
+    LdapBoundToken, LdapResponseState, LdapStartTlsOp, LDAP_EXOP_STARTTLS,
+};
 use kanidmd_lib::prelude::*;
-use ldap3_proto::proto::LdapMsg;
+use ldap3_proto::proto::{LdapExtendedResponse, LdapMsg, LdapOp, LdapResult, LdapResultCode};
 use ldap3_proto::LdapCodec;
 use std::net::SocketAddr;
 use std::str::FromStr;
@@ -15,28 +18,31 @@ use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt};
 use tokio::net::{TcpListener, TcpStream};
 use tokio::sync::broadcast;
 use tokio::time::timeout;
-use tokio_rustls::TlsAcceptor;
-use tokio_util::codec::{FramedRead, FramedWrite};
+use tokio_util::codec::{Framed, FramedRead, FramedWrite};
 
 const LDAP_CLIENT_IO_TIMEOUT: Duration = Duration::from_secs(300);
 const LDAP_CLIENT_CONN_TIMEOUT: Duration = Duration::from_secs(30);
 
 struct LdapSession {
     uat: Option<LdapBoundToken>,
+    // The certificate presented during TLS setup, used for SASL EXTERNAL binds.
+    client_cert: Option<ClientCertInfo>,
 }
 
 impl LdapSession {
-    fn new() -> Self {
+    fn new(client_cert: Option<ClientCertInfo>) -> Self {
         LdapSession {
             // We start un-authenticated
             uat: None,
+            client_cert,
         }
     }
 }
 
-#[instrument(name = "ldap-request", skip(client_address, qe_r_ref))]
+#[instrument(name = "ldap-request", skip(client_address, client_cert, qe_r_ref))]
 async fn client_process_msg(
     uat: Option<LdapBoundToken>,
+    client_cert: Option<&ClientCertInfo>,
     client_address: SocketAddr,
     protomsg: LdapMsg,
     qe_r_ref: &'static QueryServerReadV1,
@@ -48,7 +54,7 @@ async fn client_process_msg(
         "LDAP client"
     );
     qe_r_ref
-        .handle_ldaprequest(eventid, protomsg, uat, client_address.ip())
+        .handle_ldaprequest(eventid, protomsg, uat, client_cert, client_address.ip())
         .await
 }
 
@@ -56,6 +62,7 @@ async fn client_process<STREAM>(
     stream: STREAM,
     client_address: SocketAddr,
     connection_address: SocketAddr,
+    client_cert: Option<ClientCertInfo>,
     qe_r_ref: &'static QueryServerReadV1,
 ) where
     STREAM: AsyncRead + AsyncWrite + AsyncWriteExt + Unpin,
@@ -65,7 +72,7 @@ async fn client_process<STREAM>(
     let mut w = FramedWrite::new(w, LdapCodec::default());
 
     // This is a connected client session. we need to associate some state to the session
-    let mut session = LdapSession::new();
+    let mut session = LdapSession::new(client_cert);
     // Now that we have the session we begin an event loop to process input OR we return.
     loop {
         let protomsg = match timeout(LDAP_CLIENT_IO_TIMEOUT, r.next()).await {
@@ -90,7 +97,34 @@ async fn client_process<STREAM>(
 
         debug!(?client_address, ?connection_address);
 
-        match client_process_msg(uat, caddr, protomsg, qe_r_ref).await {
+        // StartTLS is only negotiated by client_starttls_process. This loop also serves
+        // plaintext connections, so the request is refused without claiming TLS is present.
+        if let LdapOp::ExtendedRequest(ler) = &protomsg.op {
+            if ler.name == LDAP_EXOP_STARTTLS {
+                let rmsg = LdapMsg {
+                    msgid: protomsg.msgid,
+                    op: LdapOp::ExtendedResponse(LdapExtendedResponse {
+                        res: LdapResult {
+                            code: LdapResultCode::UnwillingToPerform,
+                            matcheddn: "".to_string(),
+                            message: "StartTLS is not available on this connection".to_string(),
+                            referral: vec![],
+                        },
+                        name: Some(LDAP_EXOP_STARTTLS.to_string()),
+                        value: None,
+                    }),
+                    ctrl: vec![],
+                };
+                if w.send(rmsg).await.is_err() {
+                    break;
+                }
+                continue;
+            }
+        }
+
+        let client_cert = session.client_cert.as_ref();
+
+        match client_process_msg(uat, client_cert, caddr, protomsg, qe_r_ref).await {
             // I'd really have liked to have put this near the [LdapResponseState::Bind] but due
             // to the handing of `audit` it isn't possible due to borrows, etc.
             Some(LdapResponseState::Unbind) => break,
@@ -147,9 +181,30 @@ async fn client_process<STREAM>(
     }
 }
 
+/// The client cert (if any) that was presented during the TLS handshake, as long as the client
+/// CA trusts it. Certificates that are only accepted for OAuth2 clients can not be used here.
+fn client_cert_info(
+    tls_acceptor: &ServerTlsAcceptor,
+    tlsstream: &tokio_rustls::server::TlsStream<TcpStream>,
+    client_addr: SocketAddr,
+) -> Result<Option<ClientCertInfo>, ()> {
+    match tls_acceptor.peer_certificate(tlsstream.get_ref().1) {
+        Ok(PeerCertificate::Trusted(client_cert)) => Ok(Some(client_cert)),
+        Ok(PeerCertificate::OAuth2(_)) => {
+            debug!(%client_addr, "Ignoring client certificate that is not trusted by the client CA");
+            Ok(None)
+        }
+        Ok(PeerCertificate::None) => Ok(None),
+        Err(()) => {
+            error!(%client_addr, "Unable to process client certificate");
+            Err(())
+        }
+    }
+}
+
 async fn client_tls_accept(
     stream: TcpStream,
-    tls_acceptor: TlsAcceptor,
+    tls_acceptor: ServerTlsAcceptor,
     connection_addr: SocketAddr,
     qe_r_ref: &'static QueryServerReadV1,
     trusted_tcp_info_ips: Arc<TcpAddressInfo>,
@@ -190,22 +245,168 @@ async fn client_tls_accept(
         }
     };
 
+    let Ok(client_cert) = client_cert_info(&tls_acceptor, &tlsstream, client_addr) else {
+        return;
+    };
+
     // Start the event handler now that the connection is setup
     tokio::spawn(client_process(
         tlsstream,
         client_addr,
         connection_addr,
+        client_cert,
         qe_r_ref,
     ));
 }
 
-/// TLS LDAP Listener, hands off to [client_tls_accept]
+/// Process a plaintext connection until the client requests StartTLS. Before this only the
+/// rootDSE can be read, and once TLS is established the connection is handed to [client_process].
+async fn client_starttls_process(
+    stream: TcpStream,
+    tls_acceptor: ServerTlsAcceptor,
+    client_address: SocketAddr,
+    connection_address: SocketAddr,
+    qe_r_ref: &'static QueryServerReadV1,
+) {
+    let mut framed = Framed::new(stream, LdapCodec::default());
+
+    loop {
+        let protomsg = match timeout(LDAP_CLIENT_IO_TIMEOUT, framed.next()).await {
+            Ok(Some(Ok(protomsg))) => protomsg,
+            Ok(Some(Err(req_err))) => {
+                error!(?req_err, "Invalid LDAP request");
+                return;
+            }
+            Ok(None) => {
+                debug!("connection closed");
+                return;
+            }
+            Err(_) => {
+                debug!("client IO timeout, closing connection");
+                return;
+            }
+        };
+
+        debug!(?client_address, ?connection_address);
+
+        match LdapStartTlsOp::from(protomsg) {
+            LdapStartTlsOp::StartTls(rmsg) => {
+                if framed.send(rmsg).await.is_err() {
+                    return;
+                }
+                break;
+            }
+            LdapStartTlsOp::Process(protomsg) => {
+                // No bind is possible yet, so there is never a token to retain.
+                let rmsgs = match client_process_msg(None, None, client_address, protomsg, qe_r_ref)
+                    .await
+                {
+                    Some(LdapResponseState::Respond(rmsg))
+                    | Some(LdapResponseState::Bind(_, rmsg)) => vec![rmsg],
+                    Some(LdapResponseState::MultiPartResponse(v))
+                    | Some(LdapResponseState::BindMultiPartResponse(_, v)) => v,
+                    Some(LdapResponseState::Disconnect(rmsg)) => {
+                        let _ = framed.send(rmsg).await;
+                        return;
+                    }
+                    Some(LdapResponseState::Unbind) => return,
+                    None => {
+                        error!("Internal server error");
+                        return;
+                    }
+                };
+                for rmsg in rmsgs {
+                    if framed.send(rmsg).await.is_err() {
+                        return;
+                    }
+                }
+            }
+            LdapStartTlsOp::Refuse(Some(rmsg)) => {
+                if framed.send(rmsg).await.is_err() {
+                    return;
+                }
+            }
+            LdapStartTlsOp::Refuse(None) => {}
+            LdapStartTlsOp::Disconnect(rmsg) => {
+                let _ = framed.send(rmsg).await;
+                return;
+            }
+            LdapStartTlsOp::Unbind => return,
+        }
+    }
+
+    // Any data the client sent after the StartTLS request was sent in plaintext and may
+    // have been injected by an attacker, so it must never be processed inside the TLS session.
+    if !framed.read_buffer().is_empty() {
+        warn!(%client_address, "Data received after StartTLS request, closing connection");
+        return;
+    }
+
+    let stream = framed.into_inner();
+
+    let tlsstream = match timeout(LDAP_CLIENT_CONN_TIMEOUT, tls_acceptor.accept(stream)).await {
+        Ok(Ok(ta)) => ta,
+        Ok(Err(err)) => {
+            error!(?err, %client_address, %connection_address, "LDAP StartTLS setup error");
+            return;
+        }
+        Err(_) => {
+            error!(%client_address, %connection_address, "LDAP StartTLS timeout error");
+            return;
+        }
+    };
+
+    let Ok(client_cert) = client_cert_info(&tls_acceptor, &tlsstream, client_address) else {
+        return;
+    };
+
+    client_process(
+        tlsstream,
+        client_address,
+        connection_address,
+        client_cert,
+        qe_r_ref,
+    )
+    .await
+}
+
+async fn client_starttls_accept(
+    stream: TcpStream,
+    tls_acceptor: ServerTlsAcceptor,
+    connection_addr: SocketAddr,
+    qe_r_ref: &'static QueryServerReadV1,
+    trusted_tcp_info_ips: Arc<TcpAddressInfo>,
+) {
+    let Ok((stream, client_addr)) = process_client_addr(
+        stream,
+        connection_addr,
+        LDAP_CLIENT_CONN_TIMEOUT,
+        trusted_tcp_info_ips,
+    )
+    .await
+    else {
+        debug!(%connection_addr, "Unable to process client address");
+        return;
+    };
+
+    tokio::spawn(client_starttls_process(
+        stream,
+        tls_acceptor,
+        client_addr,
+        connection_addr,
+        qe_r_ref,
+    ));
+}
+
+/// TLS LDAP Listener, hands off to [client_tls_accept], or to [client_starttls_accept]
+/// when the listener is plaintext until the client requests StartTLS.
 async fn ldap_tls_acceptor(
     listener: TcpListener,
-    mut tls_acceptor: TlsAcceptor,
+    starttls: bool,
+    mut tls_acceptor: ServerTlsAcceptor,
     qe_r_ref: &'static QueryServerReadV1,
     mut rx: broadcast::Receiver<CoreAction>,
-    mut tls_acceptor_reload_rx: broadcast::Receiver<TlsAcceptor>,
+    mut tls_acceptor_reload_rx: broadcast::Receiver<ServerTlsAcceptor>,
     trusted_tcp_info_ips: Arc<TcpAddressInfo>,
 ) {
     loop {
@@ -220,7 +421,11 @@ async fn ldap_tls_acceptor(
                 match accept_result {
                     Ok((tcpstream, client_socket_addr)) => {
                         let clone_tls_acceptor = tls_acceptor.clone();
-                        tokio::spawn(client_tls_accept(tcpstream, clone_tls_acceptor, client_socket_addr, qe_r_ref, trusted_tcp_info_ips.clone()));
+                        if starttls {
+                            tokio::spawn(client_starttls_accept(tcpstream, clone_tls_acceptor, client_socket_addr, qe_r_ref, trusted_tcp_info_ips.clone()));
+                        } else {
+                            tokio::spawn(client_tls_accept(tcpstream, clone_tls_acceptor, client_socket_addr, qe_r_ref, trusted_tcp_info_ips.clone()));
+                        }
                     }
                     Err(err) => {
                         warn!(?err, "LDAP acceptor error, continuing");
@@ -253,7 +458,7 @@ async fn ldap_plaintext_acceptor(
             accept_result = listener.accept() => {
                 match accept_result {
                     Ok((tcpstream, client_socket_addr)) => {
-                        tokio::spawn(client_process(tcpstream, client_socket_addr, client_socket_addr, qe_r_ref));
+                        tokio::spawn(client_process(tcpstream, client_socket_addr, client_socket_addr, None, qe_r_ref));
                     }
                     Err(e) => {
                         error!("LDAP acceptor error, continuing -> {:?}", e);
@@ -267,10 +472,10 @@ async fn ldap_plaintext_acceptor(
 
 pub(crate) async fn create_ldap_server(
     addresses: &[String],
-    opt_ssl_acceptor: Option<TlsAcceptor>,
+    opt_ssl_acceptor: Option<ServerTlsAcceptor>,
     qe_r_ref: &'static QueryServerReadV1,
     server_message_tx: &broadcast::Sender<CoreAction>,
-    tls_acceptor_reload_tx: &broadcast::Sender<TlsAcceptor>,
+    tls_acceptor_reload_tx: &broadcast::Sender<ServerTlsAcceptor>,
     trusted_tcp_info_ips: Arc<TcpAddressInfo>,
 ) -> Result<Vec<tokio::task::JoinHandle<()>>, ()> {
     let mut ldap_acceptor_handles = Vec::with_capacity(addresses.len());
@@ -305,6 +510,7 @@ pub(crate) async fn create_ldap_server(
 
                 tokio::spawn(ldap_tls_acceptor(
                     listener,
+                    false,
                     ssl_acceptor,
                     qe_r_ref,
                     rx,
@@ -321,3 +527,49 @@ pub(crate) async fn create_ldap_server(
 
     Ok(ldap_acceptor_handles)
 }
+
+/// Create plaintext LDAP listeners that require clients to upgrade with StartTLS. Unlike
+/// [create_ldap_server] these always require the TLS acceptor.
+pub(crate) async fn create_ldap_starttls_server(
+    addresses: &[String],
+    tls_acceptor: ServerTlsAcceptor,
+    qe_r_ref: &'static QueryServerReadV1,
+    server_message_tx: &broadcast::Sender<CoreAction>,
+    tls_acceptor_reload_tx: &broadcast::Sender<ServerTlsAcceptor>,
+    trusted_tcp_info_ips: Arc<TcpAddressInfo>,
+) -> Result<Vec<tokio::task::JoinHandle<()>>, ()> {
+    let mut ldap_acceptor_handles = Vec::with_capacity(addresses.len());
+
+    for address in addresses {
+        let addr = SocketAddr::from_str(address).map_err(|e| {
+            error!(
+                "Could not parse LDAP StartTLS server address {} -> {:?}",
+                address, e
+            );
+        })?;
+
+        let listener = TcpListener::bind(&addr).await.map_err(|e| {
+            error!(
+                "Could not bind to LDAP StartTLS server address {} -> {:?}",
+                address, e
+            );
+        })?;
+
+        info!("Starting LDAP StartTLS interface ldap://{} ...", address);
+
+        let ldap_acceptor_handle = tokio::spawn(ldap_tls_acceptor(
+            listener,
+            true,
+            tls_acceptor.clone(),
+            qe_r_ref,
+            server_message_tx.subscribe(),
+            tls_acceptor_reload_tx.subscribe(),
+            trusted_tcp_info_ips.clone(),
+        ));
+
+        info!("Created LDAP StartTLS interface");
+        ldap_acceptor_handles.push(ldap_acceptor_handle);
+    }
+
+    Ok(ldap_acceptor_handles)
+}
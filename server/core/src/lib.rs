@@ -53,6 +53,7 @@ use kanidm_proto::config::ServerRole;
 use kanidm_proto::internal::OperationError;
 use kanidm_proto::scim_v1::client::ScimAssertGeneric;
 use kanidmd_lib::be::{Backend, BackendConfig, BackendTransaction};
+use kanidmd_lib::idm::breachedpassword::BreachedPasswordIndex;
 use kanidmd_lib::idm::ldap::LdapServer;
 use kanidmd_lib::prelude::*;
 use kanidmd_lib::schema::Schema;
@@ -61,6 +62,8 @@ use kanidmd_lib::value::CredentialType;
 use regex::Regex;
 use std::collections::BTreeSet;
 use std::fmt::{Display, Formatter};
+use std::fs::File;
+use std::io::BufReader;
 use std::path::Path;
 use std::path::PathBuf;
 use std::sync::Arc;
@@ -124,12 +127,28 @@ async fn setup_qs_idms(
         .initialise_helper(curtime, DOMAIN_TGT_LEVEL)
         .await?;
 
+    let breached_passwords = config
+        .breached_password_index
+        .as_deref()
+        .map(BreachedPasswordIndex::open)
+        .transpose()
+        .inspect_err(|err| {
+            error!(?err, path = ?config.breached_password_index, "Unable to open the breached password index");
+        })?;
+
+    let oauth2_client_ca = crypto::load_oauth2_client_ca(&config.tls_config).map_err(|err| {
+        error!(?err, "Unable to load the OAuth2 client CA");
+        OperationError::InvalidState
+    })?;
+
     // We generate a SINGLE idms only!
     let is_integration_test = config.integration_test_config.is_some();
     let (idms, idms_delayed, idms_audit) = IdmServer::new(
         query_server.clone(),
         &config.origin,
         is_integration_test,
+        breached_passwords,
+        oauth2_client_ca,
         curtime,
     )
     .await?;
@@ -683,6 +702,54 @@ pub async fn verify_server_core(config: &Configuration) {
     // Now add IDM server verifications?
 }
 
+pub fn breached_password_index_build_core(config: &Configuration, source: &Path) {
+    let Some(index_path) = config.breached_password_index.as_deref() else {
+        error!("breached_password_index is not set in the server configuration");
+        std::process::exit(1);
+    };
+
+    let source_file = match File::open(source) {
+        Ok(f) => f,
+        Err(err) => {
+            error!(?err, "Unable to open breached password source.");
+            let diag = kanidm_lib_file_permissions::diagnose_path(source);
+            info!(%diag);
+            std::process::exit(1);
+        }
+    };
+
+    // Build into a temporary file and then move it into place, so that a server that starts
+    // during the build never reads a partially written index.
+    let tmp_path = index_path.with_extension("tmp");
+    let tmp_file = match File::create(&tmp_path) {
+        Ok(f) => f,
+        Err(err) => {
+            error!(?err, "Unable to create breached password index.");
+            let diag = kanidm_lib_file_permissions::diagnose_path(&tmp_path);
+            info!(%diag);
+            std::process::exit(1);
+        }
+    };
+
+    match BreachedPasswordIndex::build(BufReader::new(source_file), tmp_file) {
+        Ok((hash, entries)) => {
+            info!(%hash, %entries, "Built breached password index");
+        }
+        Err(err) => {
+            error!(?err, "Failed to build breached password index");
+            let _ = std::fs::remove_file(&tmp_path);
+            std::process::exit(1);
+        }
+    }
+
+    if let Err(err) = std::fs::rename(&tmp_path, index_path) {
+        error!(?err, "Unable to replace breached password index.");
+        std::process::exit(1);
+    }
+
+    info!(path = %index_path.display(), "Breached password index updated");
+}
+
 pub fn cert_generate_core(config: &Configuration) {
     // Get the cert root
 
@@ -1331,6 +1398,30 @@ pub async fn create_server_core(
         }
     };
 
+    let maybe_ldap_starttls_acceptor_handles = match &config.ldapstarttlsbindaddress {
+        Some(la) => {
+            let Some(tls_acceptor) = maybe_tls_acceptor.clone() else {
+                error!("LDAP StartTLS requires TLS to be configured");
+                return Err(());
+            };
+
+            let h = ldaps::create_ldap_starttls_server(
+                la,
+                tls_acceptor,
+                server_read_ref,
+                &broadcast_tx,
+                &tls_acceptor_reload_tx,
+                config.ldap_client_address_info.trusted_tcp_info(),
+            )
+            .await?;
+            Some(h)
+        }
+        None => {
+            debug!("LDAP StartTLS not requested, skipping");
+            None
+        }
+    };
+
     // If we have replication configured, setup the listener with its initial replication
     // map (if any).
     let (maybe_repl_handle, maybe_repl_ctrl_tx) = match &config.repl_config {
@@ -1418,6 +1509,12 @@ pub async fn create_server_core(
         }
     }
 
+    if let Some(ldap_handles) = maybe_ldap_starttls_acceptor_handles {
+        for ldap_handle in ldap_handles {
+            handles.push((TaskName::LdapActor, ldap_handle))
+        }
+    }
+
     if let Some(http_handles) = maybe_http_acceptor_handles {
         for http_handle in http_handles {
             handles.push((TaskName::HttpsServer, http_handle))
@@ -38,11 +38,11 @@ use kanidmd_core::admin::{
 };
 use kanidmd_core::config::{Configuration, ServerConfigUntagged};
 use kanidmd_core::{
-    backup_server_core, cert_generate_core, create_server_core, dbscan_get_id2entry_core,
-    dbscan_list_id2entry_core, dbscan_list_index_analysis_core, dbscan_list_index_core,
-    dbscan_list_indexes_core, dbscan_list_quarantined_core, dbscan_quarantine_id2entry_core,
-    dbscan_restore_quarantined_core, domain_rename_core, reindex_server_core, restore_server_core,
-    vacuum_server_core, verify_server_core, CoreAction,
+    backup_server_core, breached_password_index_build_core, cert_generate_core, create_server_core,
+    dbscan_get_id2entry_core, dbscan_list_id2entry_core, dbscan_list_index_analysis_core,
+    dbscan_list_index_core, dbscan_list_indexes_core, dbscan_list_quarantined_core,
+    dbscan_quarantine_id2entry_core, dbscan_restore_quarantined_core, domain_rename_core,
+    reindex_server_core, restore_server_core, vacuum_server_core, verify_server_core, CoreAction,
 };
 use serde::Serialize;
 use sketching::pipeline::TracingPipelineGuard;
@@ -190,6 +190,19 @@ async fn submit_admin_req_human(path: &str, req: AdminTaskRequest) -> ExitCode {
 
     match reqs.next().await {
         Some(Ok(AdminTaskResponse::RecoverAccount { password })) => info!(new_password = ?password),
+        Some(Ok(AdminTaskResponse::CredentialLockStatus { status })) => {
+            if status.is_empty() {
+                info!("No credentials are soft locked");
+            }
+            for cred_status in status {
+                info!("------------------------");
+                info!("credential_id          : {}", cred_status.cred_id);
+                info!("failures               : {}", cred_status.failures);
+                info!("locked                 : {}", cred_status.locked);
+                info!("unlock_at              : {}", cred_status.unlock_at);
+                info!("reset_at               : {}", cred_status.reset_at);
+            }
+        }
         Some(Ok(AdminTaskResponse::ShowReplicationCertificate { cert })) => {
             info!(certificate = ?cert)
         }
@@ -579,7 +592,10 @@ async fn start_daemon(opt: KanidmdParser, config: Configuration) -> ExitCode {
         | KanidmdOpt::RenewReplicationCertificate
         | KanidmdOpt::RefreshReplicationConsumer { .. }
         | KanidmdOpt::RecoverAccount { .. }
-        | KanidmdOpt::DisableAccount { .. } => None,
+        | KanidmdOpt::DisableAccount { .. }
+        | KanidmdOpt::CredentialLockStatus { .. }
+        | KanidmdOpt::CredentialUnlock { .. }
+        | KanidmdOpt::BreachedPasswordIndex { .. } => None,
         _ => {
             // Okay - Lets now create our lock and go.
             #[allow(clippy::expect_used)]
@@ -943,6 +959,10 @@ async fn kanidm_main(config: Configuration, opt: KanidmdParser) -> ExitCode {
             info!("Running in certificate generate mode ...");
             cert_generate_core(&config);
         }
+        KanidmdOpt::BreachedPasswordIndex { source } => {
+            info!("Running in breached password index build mode ...");
+            breached_password_index_build_core(&config, source);
+        }
         KanidmdOpt::Database {
             commands: DbCommands::Backup(bopt),
         } => {
@@ -1012,6 +1032,28 @@ async fn kanidm_main(config: Configuration, opt: KanidmdParser) -> ExitCode {
             )
             .await;
         }
+        KanidmdOpt::CredentialLockStatus { name } => {
+            info!("Running credential lock status ...");
+
+            submit_admin_req_human(
+                config.adminbindpath.as_str(),
+                AdminTaskRequest::CredentialLockStatus {
+                    name: name.to_owned(),
+                },
+            )
+            .await;
+        }
+        KanidmdOpt::CredentialUnlock { name } => {
+            info!("Running credential unlock ...");
+
+            submit_admin_req_human(
+                config.adminbindpath.as_str(),
+                AdminTaskRequest::CredentialUnlock {
+                    name: name.to_owned(),
+                },
+            )
+            .await;
+        }
         KanidmdOpt::Database {
             commands: DbCommands::Reindex,
         } => {
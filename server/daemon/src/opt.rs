@@ -176,6 +176,20 @@ enum KanidmdOpt {
         /// The account name to disable.
         name: String,
     },
+    #[clap(name = "credential-lock-status")]
+    /// Display the soft lock state of an account's credentials.
+    CredentialLockStatus {
+        #[clap(value_parser)]
+        /// The account name to display the credential lock state of.
+        name: String,
+    },
+    #[clap(name = "credential-unlock")]
+    /// Release any soft lock held on an account's credentials after failed authentication attempts.
+    CredentialUnlock {
+        #[clap(value_parser)]
+        /// The account name to unlock the credentials of.
+        name: String,
+    },
     /// Display this server's replication certificate
     ShowReplicationCertificate,
     /// Renew this server's replication certificate
@@ -201,6 +215,14 @@ enum KanidmdOpt {
         #[clap(subcommand)]
         commands: DbCommands,
     },
+    #[clap(name = "breached-password-index")]
+    /// Build the breached password index listed in the configuration from a local corpus of
+    /// SHA-1 or NTLM hashes ordered by hash, such as the Have I Been Pwned downloads.
+    BreachedPasswordIndex {
+        #[clap(value_parser)]
+        /// The path to the hash ordered corpus to import.
+        source: PathBuf,
+    },
     /// Change domain settings
     #[clap(name = "domain")]
     DomainSettings {
@@ -221,6 +221,8 @@ pub enum DbCred {
         uuid: Uuid,
         #[serde(default = "unix_epoch")]
         timestamp: OffsetDateTime,
+        #[serde(default)]
+        password_history: Vec<DbPasswordV1>,
     },
     #[serde(rename = "V2GPw")]
     V2GenPassword {
@@ -228,6 +230,8 @@ pub enum DbCred {
         uuid: Uuid,
         #[serde(default = "unix_epoch")]
         timestamp: OffsetDateTime,
+        #[serde(default)]
+        password_history: Vec<DbPasswordV1>,
     },
     #[serde(rename = "V3PwMfa")]
     V3PasswordMfa {
@@ -238,6 +242,8 @@ pub enum DbCred {
         uuid: Uuid,
         #[serde(default = "unix_epoch")]
         timestamp: OffsetDateTime,
+        #[serde(default)]
+        password_history: Vec<DbPasswordV1>,
     },
 }
 
@@ -266,6 +272,22 @@ impl DbCred {
                 .unwrap_or(OffsetDateTime::UNIX_EPOCH),
         }
     }
+
+    pub fn password_history(&self) -> &[DbPasswordV1] {
+        match self {
+            DbCred::V2Password {
+                password_history, ..
+            }
+            | DbCred::V2GenPassword {
+                password_history, ..
+            }
+            | DbCred::V3PasswordMfa {
+                password_history, ..
+            } => password_history.as_slice(),
+            // Older credentials predate password history.
+            _ => &[],
+        }
+    }
 }
 
 impl Eq for DbCred {}
@@ -354,12 +376,22 @@ impl fmt::Display for DbCred {
                 password: _,
                 uuid,
                 timestamp,
-            } => write!(f, "V2Pw ( u {uuid}, t {timestamp} )"),
+                password_history,
+            } => write!(
+                f,
+                "V2Pw ( u {uuid}, t {timestamp}, h {} )",
+                password_history.len()
+            ),
             DbCred::V2GenPassword {
                 password: _,
                 uuid,
                 timestamp,
-            } => write!(f, "V2GPw ( u {uuid}, t {timestamp} )"),
+                password_history,
+            } => write!(
+                f,
+                "V2GPw ( u {uuid}, t {timestamp}, h {} )",
+                password_history.len()
+            ),
             DbCred::V2PasswordMfa {
                 password: _,
                 totp,
@@ -381,14 +413,16 @@ impl fmt::Display for DbCred {
                 webauthn,
                 uuid,
                 timestamp,
+                password_history,
             } => write!(
                 f,
-                "V3PwMfa (p true, w {}, t {}, b {}, u {}, t {})",
+                "V3PwMfa (p true, w {}, t {}, b {}, u {}, t {}, h {})",
                 webauthn.len(),
                 totp.len(),
                 backup_code.is_some(),
                 uuid,
-                timestamp
+                timestamp,
+                password_history.len()
             ),
         }
     }
@@ -780,6 +814,22 @@ pub enum DbValueApplicationPassword {
     },
 }
 
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub enum DbValueCredentialSoftLock {
+    V1 {
+        #[serde(rename = "u")]
+        cred_id: Uuid,
+        #[serde(rename = "c")]
+        count: u64,
+        #[serde(rename = "r")]
+        reset_at: Duration,
+        #[serde(rename = "l")]
+        unlock_at: Duration,
+        #[serde(rename = "t")]
+        updated_at: Duration,
+    },
+}
+
 #[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
 pub enum DbValueSetV2 {
     #[serde(rename = "U8")]
@@ -886,6 +936,8 @@ pub enum DbValueSetV2 {
     Message(OutboundMessage),
     #[serde(rename = "S256")]
     Sha256(BTreeSet<Sha256Output>),
+    #[serde(rename = "SL")]
+    CredentialSoftLock(Vec<DbValueCredentialSoftLock>),
 }
 
 impl DbValueSetV2 {
@@ -942,6 +994,7 @@ impl DbValueSetV2 {
             DbValueSetV2::Certificate(set) => set.len(),
             DbValueSetV2::ApplicationPassword(set) => set.len(),
             DbValueSetV2::Sha256(set) => set.len(),
+            DbValueSetV2::CredentialSoftLock(set) => set.len(),
             DbValueSetV2::Json(_) | DbValueSetV2::Message(_) => 1,
         }
     }
@@ -201,6 +201,8 @@ pub struct BackendWriteTransaction<'a> {
     idlayer: IdlArcSqliteWriteTransaction<'a>,
     idxmeta_wr: CowCellWriteTxn<'a, IdxMeta>,
     ruv: ReplicationUpdateVectorWriteTransaction<'a>,
+    // The number of writes made to entries in this transaction.
+    writes: usize,
 }
 
 impl IdRawEntry {
@@ -1121,6 +1123,12 @@ impl<'a> BackendWriteTransaction<'a> {
         &mut self.ruv
     }
 
+    /// The number of writes made to entries in this transaction. This allows a caller to
+    /// determine if a failed operation had already changed the content of the transaction.
+    pub(crate) fn writes(&self) -> usize {
+        self.writes
+    }
+
     #[instrument(level = "debug", name = "be::create", skip_all)]
     pub fn create(
         &mut self,
@@ -1145,6 +1153,8 @@ impl<'a> BackendWriteTransaction<'a> {
             }
         })?;
 
+        self.writes += 1;
+
         // Now, assign id's to all the new entries.
 
         let mut id_max = self.idlayer.get_id2entry_max_id()?;
@@ -1189,6 +1199,8 @@ impl<'a> BackendWriteTransaction<'a> {
             return Err(OperationError::EmptyRequest);
         }
 
+        self.writes += 1;
+
         // Assign id's to all the new entries.
         let mut id_max = self.idlayer.get_id2entry_max_id()?;
         let c_entries: Vec<_> = entries
@@ -1230,6 +1242,8 @@ impl<'a> BackendWriteTransaction<'a> {
 
         assert_eq!(post_entries.len(), pre_entries.len());
 
+        self.writes += 1;
+
         let post_entries_iter = post_entries.iter().filter(|e| {
             trace!(?cid);
             trace!(changestate = ?e.get_changestate());
@@ -1340,6 +1354,8 @@ impl<'a> BackendWriteTransaction<'a> {
         update_entries: &[(EntrySealedCommitted, Arc<EntrySealedCommitted>)],
         create_entries: Vec<EntrySealedNew>,
     ) -> Result<(), OperationError> {
+        self.writes += 1;
+
         // For the values in create_cands, create these with similar code to the refresh
         // path.
         if !create_entries.is_empty() {
@@ -2075,6 +2091,7 @@ impl<'a> BackendWriteTransaction<'a> {
             mut idlayer,
             idxmeta_wr,
             ruv,
+            writes: _,
         } = self;
 
         // write the ruv content back to the db.
@@ -2268,6 +2285,7 @@ impl Backend {
             idlayer: self.idlayer.write()?,
             idxmeta_wr: self.idxmeta.write(),
             ruv: self.ruv.write(),
+            writes: 0,
         })
     }
 }
@@ -169,6 +169,10 @@ pub const AUTH_SESSION_TIMEOUT: u64 = 300;
 // 5 minute mfa reg window
 pub const MFAREG_SESSION_TIMEOUT: u64 = 300;
 pub const PW_MIN_LENGTH: u32 = 10;
+// Each remembered password is verified on change, so the history must stay short.
+pub const PW_HISTORY_LENGTH_MAX: u32 = 24;
+// Warn that a password will expire for up to 14 days before it does.
+pub const PW_EXPIRY_WARNING_PERIOD: Duration = Duration::from_secs(86400 * 14);
 
 // Maximum - Sessions have no upper bound.
 pub const MAXIMUM_AUTH_SESSION_EXPIRY: u32 = u32::MAX;
@@ -187,6 +191,17 @@ pub const OAUTH_REFRESH_TOKEN_EXPIRY: u64 = 3600 * 16;
 /// of the refresh token, which is bound to the issuing session.
 pub const OAUTH2_ACCESS_TOKEN_EXPIRY: u32 = 15 * 60;
 
+/// How long a back-channel logout token is valid for after it is issued.
+pub const OAUTH2_LOGOUT_TOKEN_EXPIRY: u32 = 2 * 60;
+
+/// The maximum remaining validity of a client assertion. This bounds how long
+/// the identifiers of used assertions must be retained to prevent replay.
+pub const OAUTH2_CLIENT_ASSERTION_MAX_EXPIRY: u64 = 60 * 60;
+
+/// The maximum lifetime of a request object. This bounds how long the identifiers
+/// of used request objects must be retained to prevent replay.
+pub const OAUTH2_REQUEST_OBJECT_MAX_EXPIRY: u64 = 10 * 60;
+
 /// The amount of time a suppliers clock can be "ahead" before
 /// we warn about possible clock synchronisation issues.
 pub const REPL_SUPPLIER_ADVANCE_WINDOW: Duration = Duration::from_secs(600);
@@ -390,6 +390,33 @@ pub const UUID_SCHEMA_ATTR_ACCOUNT_SOFTLOCK_EXPIRE: Uuid =
     uuid!("00000000-0000-0000-0000-ffff00000222");
 pub const UUID_SCHEMA_ATTR_PASSWORD_CHANGED_TIME: Uuid =
     uuid!("00000000-0000-0000-0000-ffff00000223");
+pub const UUID_SCHEMA_ATTR_OAUTH2_RS_BACKCHANNEL_LOGOUT_URI: Uuid =
+    uuid!("00000000-0000-0000-0000-ffff00000224");
+pub const UUID_SCHEMA_ATTR_OAUTH2_RS_JWKS: Uuid = uuid!("00000000-0000-0000-0000-ffff00000225");
+pub const UUID_SCHEMA_ATTR_OAUTH2_REQUIRE_PAR: Uuid = uuid!("00000000-0000-0000-0000-ffff00000226");
+pub const UUID_SCHEMA_ATTR_OAUTH2_REQUIRE_DPOP: Uuid =
+    uuid!("00000000-0000-0000-0000-ffff00000227");
+pub const UUID_SCHEMA_ATTR_OAUTH2_RS_TLS_CLIENT_AUTH_SUBJECT_DN: Uuid =
+    uuid!("00000000-0000-0000-0000-ffff00000228");
+pub const UUID_SCHEMA_ATTR_OAUTH2_RS_TLS_CLIENT_CERTIFICATE: Uuid =
+    uuid!("00000000-0000-0000-0000-ffff00000229");
+pub const UUID_SCHEMA_ATTR_OAUTH2_RS_REGISTRATION_TOKEN_ID: Uuid =
+    uuid!("00000000-0000-0000-0000-ffff00000230");
+pub const UUID_SCHEMA_ATTR_OAUTH2_REGISTRATION_SCOPE_MAP: Uuid =
+    uuid!("00000000-0000-0000-0000-ffff00000231");
+pub const UUID_SCHEMA_ATTR_OAUTH2_RS_ENCRYPTED_RESPONSE_ALG: Uuid =
+    uuid!("00000000-0000-0000-0000-ffff00000232");
+pub const UUID_SCHEMA_ATTR_OAUTH2_RS_AUTHORISATION_DETAILS_TYPE: Uuid =
+    uuid!("00000000-0000-0000-0000-ffff00000233");
+pub const UUID_SCHEMA_ATTR_OAUTH2_CIBA_ENABLE: Uuid = uuid!("00000000-0000-0000-0000-ffff00000234");
+pub const UUID_SCHEMA_ATTR_OAUTH2_PAIRWISE_SUBJECT_ENABLE: Uuid =
+    uuid!("00000000-0000-0000-0000-ffff00000235");
+pub const UUID_SCHEMA_ATTR_AUTH_PASSWORD_HISTORY_LENGTH: Uuid =
+    uuid!("00000000-0000-0000-0000-ffff00000236");
+pub const UUID_SCHEMA_ATTR_AUTH_PASSWORD_MAXIMUM_AGE: Uuid =
+    uuid!("00000000-0000-0000-0000-ffff00000237");
+pub const UUID_SCHEMA_ATTR_CREDENTIAL_SOFTLOCK: Uuid =
+    uuid!("00000000-0000-0000-0000-ffff00000238");
 
 // =====
 // Incorrectly name spaced.
@@ -526,6 +553,10 @@ pub const UUID_IDM_ACP_OAUTH2_MANAGE_BASIC: Uuid = uuid!("00000000-0000-0000-000
 pub const UUID_INTERNAL_MIGRATION: Uuid = uuid!("00000000-0000-0000-0000-ffffff000082");
 pub const UUID_INTERNAL_SESSION_ID: Uuid = uuid!("00000000-0000-0000-0000-ffffff000083");
 
+pub const UUID_IDM_ACP_OAUTH2_CLIENT_REGISTRATION_MANAGE: Uuid =
+    uuid!("00000000-0000-0000-0000-ffffff000084");
+pub const UUID_DOMAIN_OAUTH2_PAIRWISE_KEY: Uuid = uuid!("00000000-0000-0000-0000-ffffff000085");
+
 // End of system ranges
 pub const UUID_DOES_NOT_EXIST: Uuid = uuid!("00000000-0000-0000-0000-fffffffffffe");
 pub const UUID_ANONYMOUS: Uuid = uuid!("00000000-0000-0000-0000-ffffffffffff");
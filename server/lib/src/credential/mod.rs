@@ -1,4 +1,5 @@
 use std::convert::TryFrom;
+use std::time::Duration;
 
 use hashbrown::{HashMap as Map, HashSet};
 use kanidm_proto::internal::{CredentialDetail, CredentialDetailType, OperationError};
@@ -89,6 +90,8 @@ pub struct Credential {
     // TODO #59: Add auth policy IE validUntil, lock state ...
     // locked: bool
     timestamp: OffsetDateTime,
+    // Prior passwords of this credential, newest first, so that they can't be reused.
+    password_history: Vec<Password>,
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -144,6 +147,12 @@ impl TryFrom<DbCred> for Credential {
         // We need to retrieve the timestamp here since not all DbCreds have one.
         // All V1 creds will fall back to a default
         let timestamp = value.last_changed_timestamp();
+        let password_history = value
+            .password_history()
+            .iter()
+            .cloned()
+            .map(Password::try_from)
+            .collect::<Result<Vec<_>, _>>()?;
 
         // Work out what the policy is?
         match value {
@@ -167,6 +176,7 @@ impl TryFrom<DbCred> for Credential {
                         type_,
                         uuid,
                         timestamp,
+                        password_history,
                     })
                 } else {
                     Err(())
@@ -192,6 +202,7 @@ impl TryFrom<DbCred> for Credential {
                         type_,
                         uuid,
                         timestamp,
+                        password_history,
                     })
                 } else {
                     Err(())
@@ -248,6 +259,7 @@ impl TryFrom<DbCred> for Credential {
                         type_,
                         uuid,
                         timestamp,
+                        password_history,
                     })
                 } else {
                     Err(())
@@ -284,6 +296,7 @@ impl TryFrom<DbCred> for Credential {
                         type_,
                         uuid,
                         timestamp,
+                        password_history,
                     })
                 } else {
                     Err(())
@@ -301,6 +314,7 @@ impl TryFrom<DbCred> for Credential {
                         type_,
                         uuid,
                         timestamp,
+                        password_history,
                     })
                 } else {
                     Err(())
@@ -339,6 +353,7 @@ impl TryFrom<DbCred> for Credential {
                         type_,
                         uuid,
                         timestamp,
+                        password_history,
                     })
                 } else {
                     Err(())
@@ -374,6 +389,7 @@ impl TryFrom<DbCred> for Credential {
                         type_,
                         uuid,
                         timestamp,
+                        password_history,
                     })
                 } else {
                     Err(())
@@ -394,6 +410,14 @@ impl Credential {
         self.timestamp
     }
 
+    /// The time at which the password of this credential expires, given the maximum age
+    /// allowed by account policy. Credentials without a password never expire.
+    pub(crate) fn password_expiry(&self, maximum_age: Duration) -> Option<OffsetDateTime> {
+        self.password_ref()
+            .ok()
+            .map(|_| self.timestamp + maximum_age)
+    }
+
     /// Create a new credential that contains a CredentialType::Password
     pub fn new_password_only(
         policy: &CryptoPolicy,
@@ -504,6 +528,7 @@ impl Credential {
             uuid: Uuid::new_v4(),
             // Update the timestamp to signify a changed credential
             timestamp: self.timestamp,
+            password_history: self.password_history.clone(),
         })
     }
 
@@ -553,6 +578,7 @@ impl Credential {
             uuid: Uuid::new_v4(),
             // Update the timestamp to signify a changed credential
             timestamp,
+            password_history: self.password_history.clone(),
         })
     }
 
@@ -592,6 +618,7 @@ impl Credential {
             // Rotate the credential id on any change to invalidate sessions.
             uuid: Uuid::new_v4(),
             timestamp: self.timestamp,
+            password_history: self.password_history.clone(),
         }))
     }
 
@@ -657,16 +684,23 @@ impl Credential {
     /// Extract this credential into it's Serialisable Database form, ready for persistence.
     pub fn to_db_valuev1(&self) -> DbCred {
         let uuid = self.uuid;
+        let password_history = self
+            .password_history
+            .iter()
+            .map(|pw| pw.to_dbpasswordv1())
+            .collect();
         match &self.type_ {
             CredentialType::Password(pw) => DbCred::V2Password {
                 password: pw.to_dbpasswordv1(),
                 uuid,
                 timestamp: self.timestamp,
+                password_history,
             },
             CredentialType::GeneratedPassword(pw) => DbCred::V2GenPassword {
                 password: pw.to_dbpasswordv1(),
                 uuid,
                 timestamp: self.timestamp,
+                password_history,
             },
             CredentialType::PasswordMfa(pw, totp, map, backup_code) => DbCred::V3PasswordMfa {
                 password: pw.to_dbpasswordv1(),
@@ -678,6 +712,7 @@ impl Credential {
                 webauthn: map.iter().map(|(k, v)| (k.clone(), v.clone())).collect(),
                 uuid,
                 timestamp: self.timestamp,
+                password_history,
             },
             CredentialType::Webauthn(map) => DbCred::TmpWn {
                 webauthn: map.iter().map(|(k, v)| (k.clone(), v.clone())).collect(),
@@ -686,6 +721,46 @@ impl Credential {
         }
     }
 
+    /// Does this cleartext match the current password of this credential, or one of the
+    /// passwords before it? In total `history_length` passwords are checked.
+    pub(crate) fn password_recently_used(
+        &self,
+        cleartext: &str,
+        history_length: u32,
+    ) -> Result<bool, OperationError> {
+        for pw in self.recent_passwords().take(history_length as usize) {
+            if pw.verify(cleartext).map_err(|e| {
+                error!(crypto_err = ?e);
+                OperationError::CryptographyError
+            })? {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Remember the passwords of the credential that this one replaces, trimmed so that
+    /// together with our current password `history_length` passwords are retained.
+    pub(crate) fn rotate_password_history(
+        &mut self,
+        replaced: Option<&Credential>,
+        history_length: u32,
+    ) {
+        self.password_history = replaced
+            .into_iter()
+            .flat_map(|cred| cred.recent_passwords())
+            .take((history_length as usize).saturating_sub(1))
+            .cloned()
+            .collect();
+    }
+
+    fn recent_passwords(&self) -> impl Iterator<Item = &Password> {
+        self.password_ref()
+            .ok()
+            .into_iter()
+            .chain(self.password_history.iter())
+    }
+
     pub(crate) fn update_password(&self, pw: Password, timestamp: OffsetDateTime) -> Self {
         let type_ = match &self.type_ {
             CredentialType::Password(_) | CredentialType::GeneratedPassword(_) => {
@@ -703,6 +778,7 @@ impl Credential {
             uuid: Uuid::new_v4(),
             // Update the timestamp to signify a changed credential
             timestamp,
+            password_history: self.password_history.clone(),
         }
     }
 
@@ -735,6 +811,7 @@ impl Credential {
             uuid: Uuid::new_v4(),
             // Update the timestamp to signify a changed credential
             timestamp,
+            password_history: self.password_history.clone(),
         }
     }
 
@@ -760,6 +837,7 @@ impl Credential {
             uuid: Uuid::new_v4(),
             // Update the timestamp to signify a changed credential
             timestamp,
+            password_history: self.password_history.clone(),
         }
     }
 
@@ -775,6 +853,7 @@ impl Credential {
             type_: CredentialType::GeneratedPassword(pw),
             uuid: Uuid::new_v4(),
             timestamp,
+            password_history: Vec::new(),
         }
     }
 
@@ -783,6 +862,7 @@ impl Credential {
             type_: CredentialType::Password(pw),
             uuid: Uuid::new_v4(),
             timestamp,
+            password_history: Vec::new(),
         }
     }
 
@@ -828,6 +908,7 @@ impl Credential {
                 uuid: Uuid::new_v4(),
                 // Update the timestamp to signify a changed credential
                 timestamp,
+                password_history: self.password_history.clone(),
             }),
             _ => Err(OperationError::InvalidAccountState(
                 "Non-MFA credential type".to_string(),
@@ -850,6 +931,7 @@ impl Credential {
                             // code.
                             uuid: self.uuid,
                             timestamp: self.timestamp,
+                            password_history: self.password_history.clone(),
                         })
                     }
                     _ => Err(OperationError::InvalidAccountState(
@@ -874,6 +956,7 @@ impl Credential {
                 uuid: Uuid::new_v4(),
                 // Update the timestamp to signify a changed credential
                 timestamp,
+                password_history: self.password_history.clone(),
             }),
             _ => Err(OperationError::InvalidAccountState(
                 "Non-MFA credential type".to_string(),
@@ -58,6 +58,10 @@
 //!
 //! ```
 //!
+//! As each server only holds these states in memory, the result of a failure is also
+//! persisted to the account as a [CredSoftLockRecord]. These records are replicated, and
+//! are applied to the in memory state before any authentication decision is made so that
+//! a lock can not be bypassed by restarting a server or moving between replicas.
 
 use std::time::Duration;
 
@@ -72,6 +76,28 @@ pub enum CredSoftLockPolicy {
 }
 
 impl CredSoftLockPolicy {
+    /// The delay tier that a failure count falls in. These follow the thresholds
+    /// of [Self::failure_next_state], and only increase as the failure count does.
+    fn failure_tier(&self, count: usize) -> usize {
+        match self {
+            CredSoftLockPolicy::Password => [3, 9, 25, 100]
+                .into_iter()
+                .take_while(|threshold| count >= *threshold)
+                .count(),
+            CredSoftLockPolicy::Totp(_) => usize::from(count >= 3),
+            CredSoftLockPolicy::Webauthn | CredSoftLockPolicy::Unrestricted => 0,
+        }
+    }
+
+    /// Webauthn locks only slow the next attempt, and an unrestricted credential is
+    /// never locked, so neither is worth persisting.
+    fn is_persisted(&self) -> bool {
+        matches!(
+            self,
+            CredSoftLockPolicy::Password | CredSoftLockPolicy::Totp(_)
+        )
+    }
+
     /// Determine the next lock state after a failure based on this credentials
     /// policy.
     fn failure_next_state(&self, count: usize, ct: Duration) -> LockState {
@@ -151,6 +177,62 @@ impl CredSoftLockPolicy {
     }
 }
 
+/// The persisted state of a credential soft lock. A record with a count of 0 is a
+/// cleared record, which is written when an administrator unlocks the credential.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CredSoftLockRecord {
+    pub count: u64,
+    pub reset_at: Duration,
+    pub unlock_at: Duration,
+    pub updated_at: Duration,
+}
+
+impl CredSoftLockRecord {
+    /// A record that releases any lock held on the credential at this time.
+    pub fn cleared(ct: Duration) -> Self {
+        CredSoftLockRecord {
+            count: 0,
+            reset_at: ct,
+            unlock_at: ct,
+            updated_at: ct,
+        }
+    }
+
+    pub fn is_cleared(&self) -> bool {
+        self.count == 0
+    }
+
+    /// Is the credential denied from authenticating at this time.
+    pub fn is_locked(&self, ct: Duration) -> bool {
+        !self.is_cleared() && ct <= self.unlock_at && ct <= self.reset_at
+    }
+
+    /// Is this record still able to affect the lock state after this time.
+    pub fn is_active(&self, ct: Duration) -> bool {
+        !self.is_cleared() && ct <= self.reset_at
+    }
+
+    /// Combine two records of the same credential, preferring the most restrictive
+    /// state. A cleared record only takes precedence over failures that were recorded
+    /// before it, so that failures that occur after an unlock are never lost.
+    pub fn merge(&self, other: &Self) -> Self {
+        match (self.is_cleared(), other.is_cleared()) {
+            (false, false) => CredSoftLockRecord {
+                count: self.count.max(other.count),
+                reset_at: self.reset_at.max(other.reset_at),
+                unlock_at: self.unlock_at.max(other.unlock_at),
+                updated_at: self.updated_at.max(other.updated_at),
+            },
+            (true, false) if self.updated_at > other.updated_at => *self,
+            (true, false) => *other,
+            (false, true) if other.updated_at > self.updated_at => *other,
+            (false, true) => *self,
+            (true, true) if other.updated_at > self.updated_at => *other,
+            (true, true) => *self,
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 enum LockState {
     Init,
@@ -175,6 +257,8 @@ pub(crate) struct CredSoftLock {
     // Policy (for determining delay times based on num failures, and when to reset?)
     policy: CredSoftLockPolicy,
     last_expire_at: Duration,
+    // The time of the most recent record that was applied or produced by this lock.
+    updated_at: Duration,
 }
 
 impl CredSoftLock {
@@ -183,6 +267,65 @@ impl CredSoftLock {
             state: LockState::Init,
             policy,
             last_expire_at: Duration::from_secs(0),
+            updated_at: Duration::ZERO,
+        }
+    }
+
+    /// Apply a persisted record to this lock. Records that are not newer than the
+    /// last record seen by this lock are ignored, since their content is already
+    /// reflected in the current state.
+    pub fn apply_record(&mut self, record: &CredSoftLockRecord) {
+        if record.updated_at <= self.updated_at {
+            return;
+        }
+        self.updated_at = record.updated_at;
+
+        if record.is_cleared() {
+            self.state = LockState::Init;
+            return;
+        }
+
+        let count = usize::try_from(record.count).unwrap_or(usize::MAX);
+
+        let mut next_state = match self.state {
+            LockState::Init => LockState::Locked {
+                count,
+                reset_at: record.reset_at,
+                unlock_at: record.unlock_at,
+            },
+            LockState::Locked {
+                count: l_count,
+                reset_at,
+                unlock_at,
+            } => LockState::Locked {
+                count: l_count.max(count),
+                reset_at: reset_at.max(record.reset_at),
+                unlock_at: unlock_at.max(record.unlock_at),
+            },
+            LockState::Unlocked(l_count, reset_at) => LockState::Locked {
+                count: l_count.max(count),
+                reset_at: reset_at.max(record.reset_at),
+                unlock_at: record.unlock_at,
+            },
+        };
+        std::mem::swap(&mut self.state, &mut next_state);
+    }
+
+    /// The record to persist for the current state of this lock. This is only
+    /// present while the lock is holding a failure.
+    pub fn to_record(&self) -> Option<CredSoftLockRecord> {
+        match self.state {
+            LockState::Locked {
+                count,
+                reset_at,
+                unlock_at,
+            } => Some(CredSoftLockRecord {
+                count: count as u64,
+                reset_at,
+                unlock_at,
+                updated_at: self.updated_at,
+            }),
+            LockState::Init | LockState::Unlocked(..) => None,
         }
     }
 
@@ -242,8 +385,18 @@ impl CredSoftLock {
         !matches!(self.state, LockState::Locked { .. })
     }
 
-    /// Document a failure of authentication at this time.
-    pub fn record_failure(&mut self, ct: Duration) {
+    /// Document a failure of authentication at this time. Returns true if this failure
+    /// changed the lock in a way that must be persisted, which is when it starts a new
+    /// lock window or moves the lock into a longer delay tier. Other failures only
+    /// affect the local state, so that not every failure causes a replicated write.
+    pub fn record_failure(&mut self, ct: Duration) -> bool {
+        self.updated_at = self.updated_at.max(ct);
+        let prior_tier = match self.state {
+            LockState::Init => None,
+            LockState::Locked { count, .. } | LockState::Unlocked(count, _) => {
+                Some(self.policy.failure_tier(count))
+            }
+        };
         let mut next_state = match self.state {
             LockState::Init => {
                 self.policy.failure_next_state(1, ct)
@@ -264,6 +417,18 @@ impl CredSoftLock {
             }
         };
         std::mem::swap(&mut self.state, &mut next_state);
+
+        let next_tier = match self.state {
+            LockState::Locked { count, .. } => Some(self.policy.failure_tier(count)),
+            LockState::Init | LockState::Unlocked(..) => None,
+        };
+
+        self.policy.is_persisted()
+            && match (prior_tier, next_tier) {
+                (None, Some(_)) => true,
+                (Some(prior), Some(next)) => next > prior,
+                (_, None) => false,
+            }
     }
 
     #[cfg(test)]
@@ -541,4 +706,132 @@ mod tests {
             }
         );
     }
+
+    #[test]
+    fn test_credential_softlock_record_apply() {
+        let ct = Duration::from_secs(10);
+
+        // A failure on one server produces a record ...
+        let mut slock = CredSoftLock::new(CredSoftLockPolicy::Password);
+        slock.record_failure(ct);
+        let record = slock.to_record().expect("No record produced");
+        assert_eq!(
+            record,
+            CredSoftLockRecord {
+                count: 1,
+                reset_at: Duration::from_secs(ONEDAY),
+                unlock_at: Duration::from_secs(10 + 1),
+                updated_at: ct,
+            }
+        );
+        assert!(record.is_locked(ct));
+
+        // ... that locks the credential on a server that has not seen the failure.
+        let mut slock2 = CredSoftLock::new(CredSoftLockPolicy::Password);
+        slock2.apply_record(&record);
+        slock2.apply_time_step(ct, None);
+        assert!(!slock2.is_valid());
+
+        // Re-applying the same record changes nothing.
+        slock2.apply_record(&record);
+        assert_eq!(slock2.peek_state(), slock.peek_state());
+
+        // The next failure continues the count from the record.
+        let ct2 = ct + Duration::from_secs(2);
+        slock2.apply_time_step(ct2, None);
+        assert!(slock2.is_valid());
+        slock2.record_failure(ct2);
+        assert_eq!(
+            slock2.peek_state(),
+            &LockState::Locked {
+                count: 2,
+                reset_at: Duration::from_secs(ONEDAY),
+                unlock_at: Duration::from_secs(10 + 3)
+            }
+        );
+
+        // A cleared record releases the lock.
+        let ct3 = ct2 + Duration::from_secs(1);
+        slock2.apply_record(&CredSoftLockRecord::cleared(ct3));
+        slock2.apply_time_step(ct3, None);
+        assert!(slock2.is_state_init());
+        assert!(slock2.to_record().is_none());
+
+        // But a record older than the unlock is ignored.
+        slock2.apply_record(&slock.to_record().expect("No record produced"));
+        assert!(slock2.is_state_init());
+    }
+
+    #[test]
+    fn test_credential_softlock_record_failure_persisted() {
+        let mut ct = Duration::from_secs(10);
+        let mut slock = CredSoftLock::new(CredSoftLockPolicy::Password);
+
+        // The first failure starts a lock window, and is persisted.
+        assert!(slock.record_failure(ct));
+
+        // Failures within the same delay tier are not.
+        ct += Duration::from_secs(2);
+        slock.apply_time_step(ct, None);
+        assert!(!slock.record_failure(ct));
+
+        // Moving into a longer delay is.
+        ct += Duration::from_secs(2);
+        slock.apply_time_step(ct, None);
+        assert!(slock.record_failure(ct));
+
+        ct += Duration::from_secs(4);
+        slock.apply_time_step(ct, None);
+        assert!(!slock.record_failure(ct));
+
+        // Once the window resets, the next failure is persisted again.
+        ct = Duration::from_secs(ONEDAY + 10);
+        slock.apply_time_step(ct, None);
+        assert!(slock.is_state_init());
+        assert!(slock.record_failure(ct));
+
+        // Webauthn locks are never persisted.
+        let mut slock = CredSoftLock::new(CredSoftLockPolicy::Webauthn);
+        assert!(!slock.record_failure(ct));
+    }
+
+    #[test]
+    fn test_credential_softlock_record_merge() {
+        let a = CredSoftLockRecord {
+            count: 4,
+            reset_at: Duration::from_secs(ONEDAY),
+            unlock_at: Duration::from_secs(20),
+            updated_at: Duration::from_secs(17),
+        };
+        let b = CredSoftLockRecord {
+            count: 2,
+            reset_at: Duration::from_secs(ONEDAY),
+            unlock_at: Duration::from_secs(25),
+            updated_at: Duration::from_secs(22),
+        };
+
+        // The most restrictive state of both records is taken.
+        let expect = CredSoftLockRecord {
+            count: 4,
+            reset_at: Duration::from_secs(ONEDAY),
+            unlock_at: Duration::from_secs(25),
+            updated_at: Duration::from_secs(22),
+        };
+        assert_eq!(a.merge(&b), expect);
+        assert_eq!(b.merge(&a), expect);
+
+        // An unlock only supersedes failures that occurred before it.
+        let cleared = CredSoftLockRecord::cleared(Duration::from_secs(30));
+        assert_eq!(a.merge(&cleared), cleared);
+        assert_eq!(cleared.merge(&a), cleared);
+
+        let c = CredSoftLockRecord {
+            count: 1,
+            reset_at: Duration::from_secs(ONEDAY),
+            unlock_at: Duration::from_secs(32),
+            updated_at: Duration::from_secs(31),
+        };
+        assert_eq!(c.merge(&cleared), c);
+        assert_eq!(cleared.merge(&c), c);
+    }
 }
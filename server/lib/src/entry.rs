@@ -28,6 +28,7 @@ use crate::be::dbentry::{DbEntry, DbEntryVers};
 use crate::be::dbvalue::DbValueSetV2;
 use crate::be::{IdxKey, IdxSlope};
 use crate::credential::apppwd::ApplicationPassword;
+use crate::credential::softlock::CredSoftLockRecord;
 use crate::credential::Credential;
 use crate::filter::{Filter, FilterInvalid, FilterResolved, FilterValidResolved};
 use crate::idm::ldap::ldap_vattr_map;
@@ -2936,6 +2937,14 @@ impl<VALID, STATE> Entry<VALID, STATE> {
             .and_then(|vs| vs.as_application_password_map())
     }
 
+    pub fn get_ava_credential_softlock<A: AsRef<Attribute>>(
+        &self,
+        attr: A,
+    ) -> Option<&BTreeMap<Uuid, CredSoftLockRecord>> {
+        self.get_ava_set(attr)
+            .and_then(|vs| vs.as_credential_softlock_map())
+    }
+
     /// Return a single security principle name, if valid to transform this value.
     pub(crate) fn generate_spn(&self, domain_name: &str) -> Option<ValueSet> {
         if let Some(name) = self.get_ava_single_iname(Attribute::Name) {
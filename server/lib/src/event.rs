@@ -311,6 +311,35 @@ impl SearchEvent {
         })
     }
 
+    /// As [Self::new_ext_impersonate_uuid], but only matching the entries with the given uuids.
+    /// This is used to resolve a single page of an earlier search.
+    pub(crate) fn new_ext_impersonate_uuid_restricted(
+        qs: &mut QueryServerReadTransaction,
+        ident: Identity,
+        lf: &LdapFilter,
+        attrs: Option<BTreeSet<Attribute>>,
+        uuids: &[Uuid],
+    ) -> Result<Self, OperationError> {
+        let f = Filter::from_ldap_ro(&ident, lf, qs)?;
+        let restrict = Filter::new(f_or(
+            uuids
+                .iter()
+                .map(|u| f_eq(Attribute::Uuid, PartialValue::Uuid(*u)))
+                .collect(),
+        ));
+        let filter_orig = Filter::join_parts_and(f, restrict)
+            .validate(qs.get_schema())
+            .map_err(OperationError::SchemaViolation)?;
+        let filter = filter_orig.clone().into_ignore_hidden();
+        Ok(SearchEvent {
+            ident,
+            filter,
+            filter_orig,
+            attrs,
+            effective_access_check: false,
+        })
+    }
+
     /// ⚠️  - Bypass the schema state machine and force the filter to be considered valid.
     /// This is a TEST ONLY method and will never be exposed in production.
     #[cfg(test)]
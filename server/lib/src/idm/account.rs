@@ -1,7 +1,7 @@
 use super::accountpolicy::ResolvedAccountPolicy;
 use super::group::{load_account_policy, load_all_groups_from_account, Group, Unix};
 use crate::constants::UUID_ANONYMOUS;
-use crate::credential::softlock::CredSoftLockPolicy;
+use crate::credential::softlock::{CredSoftLockPolicy, CredSoftLockRecord};
 use crate::credential::{apppwd::ApplicationPassword, Credential};
 use crate::entry::{Entry, EntryCommitted, EntryReduced, EntrySealed};
 use crate::event::SearchEvent;
@@ -13,7 +13,9 @@ use crate::prelude::*;
 use crate::schema::SchemaTransaction;
 use crate::value::{IntentTokenState, PartialValue, SessionState, Value};
 use kanidm_lib_crypto::CryptoPolicy;
-use kanidm_proto::internal::{CredentialStatus, UatPurpose, UiHint, UserAuthToken};
+use kanidm_proto::internal::{
+    CredentialSoftLockStatus, CredentialStatus, UatPurpose, UiHint, UserAuthToken,
+};
 use kanidm_proto::v1::{UatStatus, UatStatusState, UnixGroupToken, UnixUserToken};
 use sshkey_attest::proto::PublicKey as SshPublicKey;
 use std::collections::{BTreeMap, BTreeSet};
@@ -61,6 +63,7 @@ pub struct Account {
     pub valid_from: Option<OffsetDateTime>,
     pub expire: Option<OffsetDateTime>,
     softlock_expire: Option<OffsetDateTime>,
+    softlock_records: BTreeMap<Uuid, CredSoftLockRecord>,
     pub radius_secret: Option<String>,
     pub ui_hints: BTreeSet<UiHint>,
     pub mail_primary: Option<String>,
@@ -140,6 +143,11 @@ macro_rules! try_from_entry {
 
         let softlock_expire = $value.get_ava_single_datetime(Attribute::AccountSoftlockExpire);
 
+        let softlock_records = $value
+            .get_ava_credential_softlock(Attribute::CredentialSoftLock)
+            .cloned()
+            .unwrap_or_default();
+
         let radius_secret = $value
             .get_ava_single_secret(Attribute::RadiusSecret)
             .map(str::to_string);
@@ -250,6 +258,7 @@ macro_rules! try_from_entry {
             valid_from,
             expire,
             softlock_expire,
+            softlock_records,
             radius_secret,
             spn,
             ui_hints,
@@ -274,6 +283,18 @@ impl Account {
         self.primary.as_ref()
     }
 
+    /// The time at which the password of the primary credential expires, if account
+    /// policy limits the age of passwords.
+    pub(crate) fn primary_password_expiry(
+        &self,
+        account_policy: &ResolvedAccountPolicy,
+    ) -> Option<OffsetDateTime> {
+        let maximum_age = account_policy.pw_maximum_age()?;
+        self.primary
+            .as_ref()
+            .and_then(|cred| cred.password_expiry(maximum_age))
+    }
+
     pub(crate) fn sshkeys(&self) -> &BTreeMap<String, SshPublicKey> {
         &self.sshkeys
     }
@@ -302,6 +323,11 @@ impl Account {
         self.softlock_expire
     }
 
+    /// The persisted soft lock state of this credential, if any failures have been recorded.
+    pub(crate) fn softlock_record(&self, cred_uuid: &Uuid) -> Option<&CredSoftLockRecord> {
+        self.softlock_records.get(cred_uuid)
+    }
+
     #[instrument(level = "trace", skip_all)]
     pub(crate) fn try_from_entry_ro(
         value: &Entry<EntrySealed, EntryCommitted>,
@@ -412,6 +438,10 @@ impl Account {
             // groups: self.groups.iter().map(|g| g.to_proto()).collect(),
             limit_search_max_results,
             limit_search_max_filter_test,
+            password_expiry: Self::password_expiry_warning(
+                self.primary_password_expiry(account_policy),
+                ct,
+            ),
         })
     }
 
@@ -471,6 +501,10 @@ impl Account {
             // groups: self.groups.iter().map(|g| g.to_proto()).collect(),
             limit_search_max_results,
             limit_search_max_filter_test,
+            password_expiry: Self::password_expiry_warning(
+                self.primary_password_expiry(account_policy),
+                ct,
+            ),
         })
     }
 
@@ -508,9 +542,21 @@ impl Account {
             // groups: self.groups.iter().map(|g| g.to_proto()).collect(),
             limit_search_max_results,
             limit_search_max_filter_test,
+            // Certificate sessions don't use a password.
+            password_expiry: None,
         })
     }
 
+    /// Clients are only told of a password expiry once it is within the warning period,
+    /// so that they can prompt the user to change their password before it lapses.
+    pub(crate) fn password_expiry_warning(
+        password_expiry: Option<OffsetDateTime>,
+        ct: Duration,
+    ) -> Option<OffsetDateTime> {
+        let warn_after = OffsetDateTime::UNIX_EPOCH + ct + PW_EXPIRY_WARNING_PERIOD;
+        password_expiry.filter(|expiry| *expiry <= warn_after)
+    }
+
     /// Determine if an entry is within it's validity period using it's `valid_from` and
     /// `expire` attributes. `true` indicates the account is within the valid period.
     pub fn check_within_valid_time(
@@ -728,6 +774,55 @@ impl Account {
         ))
     }
 
+    /// Release any soft lock held on the credentials of this account. A cleared record is
+    /// written for each credential that could be locked, so that failures which are yet to
+    /// be persisted at the time of the unlock are released too.
+    pub(crate) fn gen_softlock_unlock_mod(&self, ct: Duration) -> ModifyList<ModifyInvalid> {
+        let record = CredSoftLockRecord::cleared(ct);
+
+        let cred_ids: BTreeSet<Uuid> = self
+            .softlock_records
+            .keys()
+            .copied()
+            .chain(self.primary.as_ref().map(|cred| cred.uuid))
+            .chain(
+                self.unix_extn
+                    .as_ref()
+                    .and_then(|extn| extn.ucred())
+                    .map(|cred| cred.uuid),
+            )
+            .collect();
+
+        ModifyList::new_list(
+            cred_ids
+                .into_iter()
+                .map(|cred_id| {
+                    Modify::Present(
+                        Attribute::CredentialSoftLock,
+                        Value::CredentialSoftLock(cred_id, record),
+                    )
+                })
+                .collect(),
+        )
+    }
+
+    pub(crate) fn to_credential_softlock_status(
+        &self,
+        ct: Duration,
+    ) -> Vec<CredentialSoftLockStatus> {
+        self.softlock_records
+            .iter()
+            .filter(|(_, record)| record.is_active(ct))
+            .map(|(cred_id, record)| CredentialSoftLockStatus {
+                cred_id: *cred_id,
+                failures: record.count,
+                locked: record.is_locked(ct),
+                unlock_at: OffsetDateTime::UNIX_EPOCH + record.unlock_at,
+                reset_at: OffsetDateTime::UNIX_EPOCH + record.reset_at,
+            })
+            .collect()
+    }
+
     pub(crate) fn to_credentialstatus(&self) -> Result<CredentialStatus, OperationError> {
         // In the future this will need to handle multiple credentials, not just single.
 
@@ -884,6 +979,7 @@ impl Account {
             groups,
             sshkeys,
             valid: self.is_within_valid_time(ct),
+            password_expiry: None,
         })
     }
 
@@ -8,6 +8,8 @@ pub(crate) struct AccountPolicy {
     privilege_expiry: u32,
     authsession_expiry: u32,
     pw_min_length: u32,
+    pw_history_length: u32,
+    pw_maximum_age: Option<u32>,
     credential_policy: CredentialType,
     webauthn_att_ca_list: Option<AttestationCaList>,
     limit_search_max_filter_test: Option<u64>,
@@ -36,6 +38,13 @@ impl From<&EntrySealedCommitted> for Option<AccountPolicy> {
             .get_ava_single_uint32(Attribute::AuthPasswordMinimumLength)
             .unwrap_or(PW_MIN_LENGTH);
 
+        let pw_history_length = val
+            .get_ava_single_uint32(Attribute::AuthPasswordHistoryLength)
+            .map(|len| len.min(PW_HISTORY_LENGTH_MAX))
+            .unwrap_or_default();
+
+        let pw_maximum_age = val.get_ava_single_uint32(Attribute::AuthPasswordMaximumAge);
+
         let credential_policy = val
             .get_ava_single_credential_type(Attribute::CredentialTypeMinimum)
             .unwrap_or(CredentialType::Any);
@@ -59,6 +68,8 @@ impl From<&EntrySealedCommitted> for Option<AccountPolicy> {
             privilege_expiry,
             authsession_expiry,
             pw_min_length,
+            pw_history_length,
+            pw_maximum_age,
             credential_policy,
             webauthn_att_ca_list,
             limit_search_max_filter_test,
@@ -74,6 +85,8 @@ pub(crate) struct ResolvedAccountPolicy {
     privilege_expiry: u32,
     authsession_expiry: u32,
     pw_min_length: u32,
+    pw_history_length: u32,
+    pw_maximum_age: Option<u32>,
     credential_policy: CredentialType,
     webauthn_att_ca_list: Option<AttestationCaList>,
     limit_search_max_filter_test: Option<u64>,
@@ -88,6 +101,8 @@ impl ResolvedAccountPolicy {
             privilege_expiry: DEFAULT_AUTH_PRIVILEGE_EXPIRY,
             authsession_expiry: DEFAULT_AUTH_SESSION_EXPIRY,
             pw_min_length: PW_MIN_LENGTH,
+            pw_history_length: 0,
+            pw_maximum_age: None,
             credential_policy: CredentialType::Any,
             webauthn_att_ca_list: None,
             limit_search_max_filter_test: Some(DEFAULT_LIMIT_SEARCH_MAX_FILTER_TEST),
@@ -105,6 +120,8 @@ impl ResolvedAccountPolicy {
             privilege_expiry: MAXIMUM_AUTH_PRIVILEGE_EXPIRY,
             authsession_expiry: MAXIMUM_AUTH_SESSION_EXPIRY,
             pw_min_length: PW_MIN_LENGTH,
+            pw_history_length: 0,
+            pw_maximum_age: None,
             credential_policy: CredentialType::Any,
             webauthn_att_ca_list: None,
             limit_search_max_filter_test: None,
@@ -128,6 +145,22 @@ impl ResolvedAccountPolicy {
                 accumulate.pw_min_length = acc_pol.pw_min_length
             }
 
+            // Take the longer pw history
+            if acc_pol.pw_history_length > accumulate.pw_history_length {
+                accumulate.pw_history_length = acc_pol.pw_history_length
+            }
+
+            // Take the shorter pw maximum age
+            if let Some(pol_age) = acc_pol.pw_maximum_age {
+                if let Some(acc_age) = accumulate.pw_maximum_age {
+                    if pol_age < acc_age {
+                        accumulate.pw_maximum_age = Some(pol_age);
+                    }
+                } else {
+                    accumulate.pw_maximum_age = Some(pol_age);
+                }
+            }
+
             // Take the greater credential type policy
             if acc_pol.credential_policy > accumulate.credential_policy {
                 accumulate.credential_policy = acc_pol.credential_policy
@@ -185,6 +218,15 @@ impl ResolvedAccountPolicy {
         self.pw_min_length
     }
 
+    pub(crate) fn pw_history_length(&self) -> u32 {
+        self.pw_history_length
+    }
+
+    pub(crate) fn pw_maximum_age(&self) -> Option<Duration> {
+        self.pw_maximum_age
+            .map(|age| Duration::from_secs(age as u64))
+    }
+
     pub(crate) fn credential_policy(&self) -> CredentialType {
         self.credential_policy
     }
@@ -280,6 +322,8 @@ jAGGiQIwHFj+dJZYUJR786osByBelJYsVZd2GbHQu209b5RCmGQ21gpSAk9QZW4B
             privilege_expiry: 100,
             authsession_expiry: 100,
             pw_min_length: 11,
+            pw_history_length: 5,
+            pw_maximum_age: None,
             credential_policy: CredentialType::Mfa,
             webauthn_att_ca_list: Some(att_ca_list_a),
             limit_search_max_filter_test: Some(10),
@@ -302,6 +346,8 @@ jAGGiQIwHFj+dJZYUJR786osByBelJYsVZd2GbHQu209b5RCmGQ21gpSAk9QZW4B
             privilege_expiry: 150,
             authsession_expiry: 50,
             pw_min_length: 15,
+            pw_history_length: 3,
+            pw_maximum_age: Some(86400),
             credential_policy: CredentialType::Passkey,
             webauthn_att_ca_list: Some(att_ca_list_b),
             limit_search_max_filter_test: Some(5),
@@ -314,6 +360,8 @@ jAGGiQIwHFj+dJZYUJR786osByBelJYsVZd2GbHQu209b5RCmGQ21gpSAk9QZW4B
         assert_eq!(rap.privilege_expiry(), 100);
         assert_eq!(rap.authsession_expiry(), 50);
         assert_eq!(rap.pw_min_length(), 15);
+        assert_eq!(rap.pw_history_length(), 5);
+        assert_eq!(rap.pw_maximum_age(), Some(Duration::from_secs(86400)));
         assert_eq!(rap.credential_policy, CredentialType::Passkey);
         assert_eq!(rap.limit_search_max_results(), Some(15));
         assert_eq!(rap.limit_search_max_filter_test(), Some(10));
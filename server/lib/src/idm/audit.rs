@@ -29,4 +29,13 @@ pub enum AuditEvent {
         #[serde(with = "time::serde::timestamp")]
         time: OffsetDateTime,
     },
+    CredentialSoftLockUnlock {
+        source: AuditSource,
+        /// The identity that released the soft locks.
+        actor: Option<Uuid>,
+        uuid: Uuid,
+        spn: String,
+        #[serde(with = "time::serde::timestamp")]
+        time: OffsetDateTime,
+    },
 }
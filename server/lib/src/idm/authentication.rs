@@ -1,3 +1,4 @@
+use crate::idm::credupdatesession::CredentialUpdateIntentToken;
 use crate::prelude::{OperationError, Url};
 use crate::server::identity::Source;
 use compact_jwt::JwsCompact;
@@ -80,6 +81,10 @@ pub enum AuthState {
 
     Denied(String),
     Success(Box<JwsCompact>, AuthIssueSession),
+
+    /// The account authenticated with a password that has exceeded its maximum age. Rather
+    /// than a session, a credential update intent is issued so the password can be changed.
+    CredentialUpdateRequired(CredentialUpdateIntentToken),
 }
 
 impl fmt::Debug for AuthState {
@@ -90,6 +95,9 @@ impl fmt::Debug for AuthState {
             AuthState::External(allow) => write!(f, "AuthState::External({allow:?})"),
             AuthState::Denied(reason) => write!(f, "AuthState::Denied({reason:?})"),
             AuthState::Success(_token, issue) => write!(f, "AuthState::Success({issue:?})"),
+            AuthState::CredentialUpdateRequired(_intent) => {
+                write!(f, "AuthState::CredentialUpdateRequired")
+            }
         }
     }
 }
@@ -152,8 +160,13 @@ pub(crate) enum PreValidatedTokenStatus {
 pub struct ClientAuthInfo {
     pub(crate) source: Source,
     pub(crate) client_cert: Option<ClientCertInfo>,
+    /// A certificate that was presented during the TLS handshake, but is not trusted by the
+    /// client CA. It may only authenticate OAuth2 clients, which are configured with the
+    /// certificates they accept.
+    pub(crate) oauth2_client_cert: Option<ClientCertInfo>,
     pub(crate) bearer_token: Option<JwsCompact>,
     pub(crate) basic_authz: Option<String>,
+    pub(crate) dpop_proof: Option<DpopProof>,
     pub(crate) pre_validated_token: PreValidatedTokenStatus,
 }
 
@@ -167,16 +180,40 @@ impl ClientAuthInfo {
         Self {
             source,
             client_cert,
+            oauth2_client_cert: None,
             bearer_token,
             basic_authz,
+            dpop_proof: None,
             pre_validated_token: Default::default(),
         }
     }
 
+    /// Attach the DPoP proof that was sent with this request.
+    pub fn set_dpop_proof(&mut self, dpop_proof: DpopProof) {
+        self.dpop_proof = Some(dpop_proof)
+    }
+
+    /// Attach a certificate from the TLS handshake that the client CA did not trust.
+    pub fn set_oauth2_client_cert(&mut self, oauth2_client_cert: ClientCertInfo) {
+        self.oauth2_client_cert = Some(oauth2_client_cert)
+    }
+
     pub fn bearer_token(&self) -> Option<&JwsCompact> {
         self.bearer_token.as_ref()
     }
 
+    pub fn dpop_proof(&self) -> Option<&DpopProof> {
+        self.dpop_proof.as_ref()
+    }
+
+    /// The certificate presented during the TLS handshake, whether or not the client CA
+    /// trusts it. Only OAuth2 may use this, as it checks the certificate itself.
+    pub fn tls_client_cert(&self) -> Option<&ClientCertInfo> {
+        self.client_cert
+            .as_ref()
+            .or(self.oauth2_client_cert.as_ref())
+    }
+
     pub fn pre_validated_uat(&self) -> Result<&UserAuthToken, OperationError> {
         match &self.pre_validated_token {
             PreValidatedTokenStatus::Valid(uat) => Ok(uat),
@@ -197,14 +234,24 @@ pub struct ClientCertInfo {
     pub certificate: Certificate,
 }
 
+/// A DPoP proof from the `DPoP` header of a request, along with the http method of
+/// that request which the proof must be bound to.
+#[derive(Debug, Clone)]
+pub struct DpopProof {
+    pub proof: String,
+    pub htm: String,
+}
+
 #[cfg(test)]
 impl ClientAuthInfo {
     pub(crate) fn none() -> Self {
         ClientAuthInfo {
             source: Source::Internal,
             client_cert: None,
+            oauth2_client_cert: None,
             bearer_token: None,
             basic_authz: None,
+            dpop_proof: None,
             pre_validated_token: Default::default(),
         }
     }
@@ -216,8 +263,10 @@ impl From<Source> for ClientAuthInfo {
         ClientAuthInfo {
             source: value,
             client_cert: None,
+            oauth2_client_cert: None,
             bearer_token: None,
             basic_authz: None,
+            dpop_proof: None,
             pre_validated_token: Default::default(),
         }
     }
@@ -229,8 +278,10 @@ impl From<JwsCompact> for ClientAuthInfo {
         ClientAuthInfo {
             source: Source::Internal,
             client_cert: None,
+            oauth2_client_cert: None,
             bearer_token: Some(value),
             basic_authz: None,
+            dpop_proof: None,
             pre_validated_token: Default::default(),
         }
     }
@@ -242,8 +293,10 @@ impl From<ClientCertInfo> for ClientAuthInfo {
         ClientAuthInfo {
             source: Source::Internal,
             client_cert: Some(value),
+            oauth2_client_cert: None,
             bearer_token: None,
             basic_authz: None,
+            dpop_proof: None,
             pre_validated_token: Default::default(),
         }
     }
@@ -255,8 +308,10 @@ impl From<&str> for ClientAuthInfo {
         ClientAuthInfo {
             source: Source::Internal,
             client_cert: None,
+            oauth2_client_cert: None,
             bearer_token: None,
             basic_authz: Some(value.to_string()),
+            dpop_proof: None,
             pre_validated_token: Default::default(),
         }
     }
@@ -271,8 +326,10 @@ impl ClientAuthInfo {
         ClientAuthInfo {
             source: Source::Internal,
             client_cert: None,
+            oauth2_client_cert: None,
             bearer_token: None,
             basic_authz: Some(value),
+            dpop_proof: None,
             pre_validated_token: Default::default(),
         }
     }
@@ -9,13 +9,18 @@ use crate::idm::account::Account;
 use crate::idm::accountpolicy::ResolvedAccountPolicy;
 use crate::idm::audit::AuditEvent;
 use crate::idm::authentication::{AuthCredential, AuthExternal, AuthState};
+use crate::idm::credupdatesession::{CredentialUpdateIntentToken, DEFAULT_INTENT_TTL};
 use crate::idm::delayed::{
-    AuthSessionRecord, BackupCodeRemoval, DelayedAction, PasswordUpgrade, WebauthnCounterIncrement,
+    AuthSessionRecord, BackupCodeRemoval, CredentialUpdateIntentRecord, DelayedAction,
+    PasswordUpgrade, WebauthnCounterIncrement,
 };
 use crate::idm::oauth2_client::OAuth2ClientProvider;
 use crate::prelude::*;
 use crate::server::keys::KeyObject;
-use crate::value::{AuthType, Session, SessionExtMetadata, SessionState};
+use crate::utils::readable_password_from_random;
+use crate::value::{
+    AuthType, CredUpdateSessionPerms, IntentTokenState, Session, SessionExtMetadata, SessionState,
+};
 use compact_jwt::Jws;
 use hashbrown::HashSet;
 use kanidm_proto::internal::UserAuthToken;
@@ -49,6 +54,7 @@ const BAD_AUTH_TYPE_MSG: &str = "invalid authentication method in this context";
 const BAD_CREDENTIALS: &str = "invalid credential message";
 const ACCOUNT_EXPIRED: &str = "account expired";
 const PW_BADLIST_MSG: &str = "password is in badlist";
+const PW_EXPIRED_MSG: &str = "password has expired";
 const BAD_OAUTH2_CSRF_STATE_MSG: &str = "invalid oauth2 csrf state";
 
 #[derive(Debug, Clone)]
@@ -1093,6 +1099,7 @@ pub(crate) struct AuthSessionData<'a> {
     pub(crate) client_auth_info: ClientAuthInfo,
 
     pub(crate) oauth2_client_provider: Option<&'a OAuth2ClientProvider>,
+    pub(crate) credential_update_intents: Option<&'a BTreeMap<String, IntentTokenState>>,
 }
 
 #[derive(Clone)]
@@ -1123,6 +1130,50 @@ pub(crate) struct AuthSession {
 
     // The cryptographic provider to encrypt or sign anything in this operation.
     key_object: Arc<KeyObject>,
+
+    // An existing credential update intent that can be returned if the password has
+    // expired, rather than issuing a new one on every authentication.
+    password_expiry_intent: Option<CredentialUpdateIntentToken>,
+}
+
+/// The permissions granted to the credential update intent that is issued when an
+/// account's password has expired.
+fn password_expiry_intent_perms(account: &Account) -> CredUpdateSessionPerms {
+    CredUpdateSessionPerms {
+        ext_cred_portal_can_view: false,
+        primary_can_edit: true,
+        passkeys_can_edit: false,
+        attested_passkeys_can_edit: false,
+        unixcred_can_edit: account.unix_extn().is_some(),
+        sshpubkey_can_edit: false,
+    }
+}
+
+/// Find a previously issued password expiry intent that is still valid for at least half
+/// of its lifetime, so that it can be returned again.
+fn find_password_expiry_intent(
+    account: &Account,
+    intents: Option<&BTreeMap<String, IntentTokenState>>,
+    ct: Duration,
+) -> Option<CredentialUpdateIntentToken> {
+    let expected_perms = password_expiry_intent_perms(account);
+    let min_ttl = ct + DEFAULT_INTENT_TTL / 2;
+
+    intents?
+        .iter()
+        .filter_map(|(intent_id, state)| match state {
+            IntentTokenState::Valid { max_ttl, perms }
+                if *perms == expected_perms && *max_ttl >= min_ttl =>
+            {
+                Some((intent_id, *max_ttl))
+            }
+            _ => None,
+        })
+        .max_by_key(|(_, max_ttl)| *max_ttl)
+        .map(|(intent_id, max_ttl)| CredentialUpdateIntentToken {
+            intent_id: intent_id.clone(),
+            expiry_time: OffsetDateTime::UNIX_EPOCH + max_ttl,
+        })
 }
 
 impl AuthSession {
@@ -1244,6 +1295,9 @@ impl AuthSession {
             (None, AuthState::Denied(reason.to_string()))
         } else {
             // We can proceed
+            let password_expiry_intent =
+                find_password_expiry_intent(&asd.account, asd.credential_update_intents, asd.ct);
+
             let auth_session = AuthSession {
                 account: asd.account,
                 account_policy: asd.account_policy,
@@ -1252,6 +1306,7 @@ impl AuthSession {
                 intent: AuthIntent::InitialAuth { privileged },
                 source: asd.client_auth_info.source,
                 key_object,
+                password_expiry_intent,
             };
             // Get the set of mechanisms that can proceed. This is tied
             // to the session so that it can mutate state and have progression
@@ -1406,6 +1461,7 @@ impl AuthSession {
                     },
                     source: asd.client_auth_info.source,
                     key_object,
+                    password_expiry_intent: None,
                 };
 
                 (Some(auth_session), next_auth_state)
@@ -1530,6 +1586,28 @@ impl AuthSession {
                     webauthn,
                     pw_badlist,
                 ) {
+                    CredState::Success { auth_type, .. }
+                        if self.password_expired(auth_type, time) =>
+                    {
+                        match auth_type {
+                            // Generated passwords are managed by the account's administrators,
+                            // so they must rotate it.
+                            AuthType::GeneratedPassword => {
+                                security_info!("Generated password has expired");
+                                (
+                                    Some(AuthSessionState::Denied(PW_EXPIRED_MSG)),
+                                    Ok(AuthState::Denied(PW_EXPIRED_MSG.to_string())),
+                                )
+                            }
+                            _ => {
+                                let intent = self.issue_credential_update_intent(time, async_tx)?;
+                                (
+                                    Some(AuthSessionState::Success),
+                                    Ok(AuthState::CredentialUpdateRequired(intent)),
+                                )
+                            }
+                        }
+                    }
                     CredState::Success {
                         auth_type,
                         cred_id,
@@ -1609,6 +1687,84 @@ impl AuthSession {
         response
     }
 
+    /// Determine if the password used in this authentication has exceeded the maximum age
+    /// allowed by account policy. Reauthentication is not affected, since it occurs within
+    /// a session that was already issued.
+    fn password_expired(&self, auth_type: AuthType, time: Duration) -> bool {
+        if !matches!(self.intent, AuthIntent::InitialAuth { .. }) {
+            return false;
+        }
+
+        match auth_type {
+            AuthType::Password
+            | AuthType::GeneratedPassword
+            | AuthType::PasswordTotp
+            | AuthType::PasswordBackupCode
+            | AuthType::PasswordSecurityKey => self
+                .account
+                .primary_password_expiry(&self.account_policy)
+                .is_some_and(|expiry| expiry <= OffsetDateTime::UNIX_EPOCH + time),
+            AuthType::Anonymous
+            | AuthType::Passkey
+            | AuthType::AttestedPasskey
+            | AuthType::OAuth2Trust => false,
+        }
+    }
+
+    /// Issue a credential update intent that only permits the account to change its
+    /// passwords. If the account already holds a valid intent for this purpose it is
+    /// returned, otherwise a new one is persisted by a delayed action, in the same way
+    /// as sessions.
+    fn issue_credential_update_intent(
+        &self,
+        time: Duration,
+        async_tx: &Sender<DelayedAction>,
+    ) -> Result<CredentialUpdateIntentToken, OperationError> {
+        if let Some(intent) = self
+            .password_expiry_intent
+            .as_ref()
+            .filter(|intent| OffsetDateTime::UNIX_EPOCH + time < intent.expiry_time)
+        {
+            security_info!(
+                "Password has expired, returning existing credential update intent for {} {}",
+                self.account.spn(),
+                self.account.uuid
+            );
+            return Ok(intent.clone());
+        }
+
+        let intent_id = readable_password_from_random();
+        let max_ttl = time + DEFAULT_INTENT_TTL;
+
+        let perms = password_expiry_intent_perms(&self.account);
+
+        security_info!(
+            "Password has expired, issuing credential update intent for {} {}",
+            self.account.spn(),
+            self.account.uuid
+        );
+
+        async_tx
+            .send(DelayedAction::CredentialUpdateIntentRecord(
+                CredentialUpdateIntentRecord {
+                    target_uuid: self.account.uuid,
+                    intent_id: intent_id.clone(),
+                    max_ttl,
+                    perms,
+                },
+            ))
+            .map_err(|e| {
+                debug!(?e, "queue failure");
+                admin_error!("unable to queue credential update intent");
+                OperationError::AU0005DelayedProcessFailure
+            })?;
+
+        Ok(CredentialUpdateIntentToken {
+            intent_id,
+            expiry_time: OffsetDateTime::UNIX_EPOCH + max_ttl,
+        })
+    }
+
     fn issue_uat(
         &mut self,
         auth_type: AuthType,
@@ -1812,6 +1968,7 @@ mod tests {
             ct: duration_from_epoch_now(),
             client_auth_info: Source::Internal.into(),
             oauth2_client_provider: None,
+            credential_update_intents: None,
         };
 
         let key_object = KeyObjectInternal::new_test();
@@ -1851,6 +2008,7 @@ mod tests {
                 ct: duration_from_epoch_now(),
                 client_auth_info: Source::Internal.into(),
                 oauth2_client_provider: None,
+                credential_update_intents: None,
             };
             let key_object = KeyObjectInternal::new_test();
             let (session, state) = AuthSession::new(asd, $privileged, key_object);
@@ -2034,6 +2192,7 @@ mod tests {
             ct: duration_from_epoch_now(),
             client_auth_info: Source::Internal.into(),
             oauth2_client_provider: None,
+            credential_update_intents: None,
         };
         let key_object = KeyObjectInternal::new_test();
         let (session, state) = AuthSession::new(asd, false, key_object);
@@ -2075,6 +2234,7 @@ mod tests {
             ct: duration_from_epoch_now(),
             client_auth_info: Source::Internal.into(),
             oauth2_client_provider: None,
+            credential_update_intents: None,
         };
         let key_object = KeyObjectInternal::new_test();
         let (session, state) = AuthSession::new(asd, false, key_object);
@@ -2121,6 +2281,7 @@ mod tests {
             ct: duration_from_epoch_now(),
             client_auth_info: Source::Internal.into(),
             oauth2_client_provider: None,
+            credential_update_intents: None,
         };
         let key_object = KeyObjectInternal::new_test();
         let (session, state) = AuthSession::new(asd, false, key_object);
@@ -2414,6 +2575,7 @@ mod tests {
                 ct: duration_from_epoch_now(),
                 client_auth_info: Source::Internal.into(),
                 oauth2_client_provider: None,
+                credential_update_intents: None,
             };
             let key_object = KeyObjectInternal::new_test();
             let (session, state) = AuthSession::new(asd, false, key_object);
@@ -3463,6 +3625,7 @@ mod tests {
             ct: current_time,
             client_auth_info: Source::Internal.into(),
             oauth2_client_provider: Some(&oauth2_client_provider),
+            credential_update_intents: None,
         };
         let key_object = KeyObjectInternal::new_test();
 
@@ -3537,6 +3700,7 @@ mod tests {
             refresh_token: Some("super_secret_refresh_token".to_string()),
             scope: oauth2_client_provider.request_scopes.clone(),
             id_token: None,
+            authorization_details: None,
         };
 
         let state = session
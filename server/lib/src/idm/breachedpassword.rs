@@ -0,0 +1,483 @@
+//! An offline index of breached passwords. This is built from a corpus of password hashes that
+//! is ordered by hash, such as the downloadable SHA-1 or NTLM lists from Have I Been Pwned, and
+//! is consulted when a password is set without any network access.
+//!
+//! The index is bucketed by the first 20 bits (five hex characters) of each hash, the same
+//! k-anonymity prefix used by the online range api. Only the table of bucket offsets is loaded
+//! into memory when the server starts. A lookup reads the single bucket that the candidate's
+//! prefix selects from disk, which is a few kilobytes even for the full public corpus.
+//!
+//! Each hash is truncated to its first 8 bytes to keep the index compact. At the size of the
+//! public corpus the chance of a false positive from this is negligible, and a false positive
+//! only asks the user to choose a different password.
+
+use crate::prelude::*;
+use crypto_glue::{sha1::Sha1, traits::Digest};
+use md4::Md4;
+use std::fmt;
+use std::fs::File;
+use std::io::{BufRead, BufWriter, Seek, SeekFrom, Write};
+use std::path::Path;
+
+const INDEX_MAGIC: &[u8; 4] = b"KBPI";
+const INDEX_VERSION: u8 = 1;
+const INDEX_HEADER_LEN: u64 = 8;
+
+const PREFIX_BITS: u32 = 20;
+const BUCKET_COUNT: usize = 1 << PREFIX_BITS;
+// One offset for the start of each bucket, and a final offset for the end of the last bucket.
+const BUCKET_TABLE_LEN: u64 = (BUCKET_COUNT as u64 + 1) * 8;
+const ENTRY_LEN: u64 = 8;
+
+/// The hash algorithm of the corpus an index was built from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BreachedPasswordHash {
+    Sha1,
+    Ntlm,
+}
+
+impl BreachedPasswordHash {
+    fn from_digest_len(len: usize) -> Option<Self> {
+        match len {
+            20 => Some(BreachedPasswordHash::Sha1),
+            16 => Some(BreachedPasswordHash::Ntlm),
+            _ => None,
+        }
+    }
+
+    fn from_byte(value: u8) -> Option<Self> {
+        match value {
+            1 => Some(BreachedPasswordHash::Sha1),
+            2 => Some(BreachedPasswordHash::Ntlm),
+            _ => None,
+        }
+    }
+
+    fn to_byte(self) -> u8 {
+        match self {
+            BreachedPasswordHash::Sha1 => 1,
+            BreachedPasswordHash::Ntlm => 2,
+        }
+    }
+
+    fn truncated_digest(self, cleartext: &str) -> Result<u64, OperationError> {
+        let digest = match self {
+            BreachedPasswordHash::Sha1 => Sha1::digest(cleartext.as_bytes()).to_vec(),
+            BreachedPasswordHash::Ntlm => {
+                // NTLM is md4 of the password encoded as utf16le.
+                let clear_utf16le: Vec<u8> = cleartext
+                    .encode_utf16()
+                    .flat_map(|c| c.to_le_bytes())
+                    .collect();
+                Md4::digest(&clear_utf16le).to_vec()
+            }
+        };
+
+        truncate_digest(&digest).ok_or(OperationError::InvalidState)
+    }
+}
+
+impl fmt::Display for BreachedPasswordHash {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BreachedPasswordHash::Sha1 => write!(f, "sha1"),
+            BreachedPasswordHash::Ntlm => write!(f, "ntlm"),
+        }
+    }
+}
+
+fn truncate_digest(digest: &[u8]) -> Option<u64> {
+    digest
+        .get(..ENTRY_LEN as usize)
+        .and_then(|bytes| bytes.try_into().ok())
+        .map(u64::from_be_bytes)
+}
+
+fn bucket_of(value: u64) -> u64 {
+    value >> (u64::BITS - PREFIX_BITS)
+}
+
+fn index_io_error(err: &std::io::Error) -> OperationError {
+    error!(?err, "breached password index io failure");
+    OperationError::BP0001IndexIoError
+}
+
+fn read_u64s(bytes: &[u8]) -> Vec<u64> {
+    bytes
+        .chunks_exact(ENTRY_LEN as usize)
+        .filter_map(|chunk| chunk.try_into().ok())
+        .map(u64::from_be_bytes)
+        .collect()
+}
+
+/// Positional reads of the index, so that concurrent lookups don't need to share a cursor.
+trait IndexSource: Send + Sync {
+    fn read_exact_at(&self, buf: &mut [u8], offset: u64) -> std::io::Result<()>;
+}
+
+impl IndexSource for File {
+    #[cfg(unix)]
+    fn read_exact_at(&self, buf: &mut [u8], offset: u64) -> std::io::Result<()> {
+        std::os::unix::fs::FileExt::read_exact_at(self, buf, offset)
+    }
+
+    #[cfg(windows)]
+    fn read_exact_at(&self, mut buf: &mut [u8], mut offset: u64) -> std::io::Result<()> {
+        while !buf.is_empty() {
+            match std::os::windows::fs::FileExt::seek_read(self, buf, offset)? {
+                0 => return Err(std::io::ErrorKind::UnexpectedEof.into()),
+                read => {
+                    buf = std::mem::take(&mut buf).get_mut(read..).unwrap_or_default();
+                    offset += read as u64;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+impl IndexSource for Vec<u8> {
+    fn read_exact_at(&self, buf: &mut [u8], offset: u64) -> std::io::Result<()> {
+        let src = usize::try_from(offset)
+            .ok()
+            .and_then(|start| self.get(start..start.checked_add(buf.len())?))
+            .ok_or(std::io::ErrorKind::UnexpectedEof)?;
+        buf.copy_from_slice(src);
+        Ok(())
+    }
+}
+
+fn read_header(index: &dyn IndexSource) -> Result<BreachedPasswordHash, OperationError> {
+    let mut header = [0u8; INDEX_HEADER_LEN as usize];
+    index
+        .read_exact_at(&mut header, 0)
+        .map_err(|err| index_io_error(&err))?;
+
+    let [m0, m1, m2, m3, version, hash, _, _] = header;
+
+    if [m0, m1, m2, m3] != *INDEX_MAGIC || version != INDEX_VERSION {
+        error!("breached password index has an invalid header or unsupported version");
+        return Err(OperationError::BP0002IndexInvalidHeader);
+    }
+
+    BreachedPasswordHash::from_byte(hash).ok_or_else(|| {
+        error!(?hash, "breached password index has an unknown hash type");
+        OperationError::BP0002IndexInvalidHeader
+    })
+}
+
+/// A breached password index. Only the bucket table is held in memory.
+pub struct BreachedPasswordIndex {
+    hash: BreachedPasswordHash,
+    // The offset of the first entry of each bucket, and a final offset for the end of
+    // the last bucket.
+    buckets: Vec<u64>,
+    index: Box<dyn IndexSource>,
+}
+
+impl fmt::Debug for BreachedPasswordIndex {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BreachedPasswordIndex")
+            .field("hash", &self.hash)
+            .field("entries", &self.entries())
+            .finish()
+    }
+}
+
+impl BreachedPasswordIndex {
+    /// Open and validate the index at this path, and load its bucket table into memory. The
+    /// server must be restarted to use an index that has been rebuilt.
+    pub fn open(path: &Path) -> Result<Self, OperationError> {
+        let index = File::open(path).map_err(|err| index_io_error(&err))?;
+        let len = index.metadata().map_err(|err| index_io_error(&err))?.len();
+
+        let entries_len = len.checked_sub(INDEX_HEADER_LEN + BUCKET_TABLE_LEN);
+
+        if entries_len.is_none_or(|l| l % ENTRY_LEN != 0) {
+            error!(?path, "breached password index is truncated");
+            return Err(OperationError::BP0002IndexInvalidHeader);
+        }
+
+        let entries_count = entries_len.unwrap_or_default() / ENTRY_LEN;
+        let index = Self::load(Box::new(index), entries_count)?;
+
+        info!(
+            ?path,
+            hash = %index.hash,
+            entries = %index.entries(),
+            "loaded breached password index"
+        );
+
+        Ok(index)
+    }
+
+    fn load(index: Box<dyn IndexSource>, entries_count: u64) -> Result<Self, OperationError> {
+        let hash = read_header(index.as_ref())?;
+
+        let mut bucket_table = vec![0u8; BUCKET_TABLE_LEN as usize];
+        index
+            .read_exact_at(&mut bucket_table, INDEX_HEADER_LEN)
+            .map_err(|err| index_io_error(&err))?;
+        let buckets = read_u64s(&bucket_table);
+
+        // The offsets must be ordered, and the final offset must be the end of the entries.
+        if !buckets.is_sorted() || buckets.last() != Some(&entries_count) {
+            error!("breached password index bucket table is corrupt");
+            return Err(OperationError::BP0002IndexInvalidHeader);
+        }
+
+        Ok(BreachedPasswordIndex {
+            hash,
+            buckets,
+            index,
+        })
+    }
+
+    fn entries(&self) -> u64 {
+        self.buckets.last().copied().unwrap_or_default()
+    }
+
+    /// Check if this password is present in the index. This reads a single bucket, which is
+    /// comparable to the database reads of the transaction that the password is set in.
+    pub(crate) fn contains(&self, cleartext: &str) -> Result<bool, OperationError> {
+        let candidate = self.hash.truncated_digest(cleartext)?;
+        let bucket = bucket_of(candidate) as usize;
+
+        let Some([start, end]) = self.buckets.get(bucket..=bucket + 1) else {
+            error!(?bucket, "breached password index bucket is out of range");
+            return Err(OperationError::BP0002IndexInvalidHeader);
+        };
+
+        // The bucket table was validated as ordered when it was loaded.
+        let mut bucket_entries = vec![0u8; ((end - start) * ENTRY_LEN) as usize];
+        self.index
+            .read_exact_at(
+                &mut bucket_entries,
+                INDEX_HEADER_LEN + BUCKET_TABLE_LEN + start * ENTRY_LEN,
+            )
+            .map_err(|err| index_io_error(&err))?;
+
+        Ok(read_u64s(&bucket_entries).binary_search(&candidate).is_ok())
+    }
+
+    /// Build an index from a source of hex encoded SHA-1 or NTLM hashes, one per line and
+    /// ordered by hash. Lines may be suffixed with `:count` as in the Have I Been Pwned
+    /// downloads. Returns the hash type of the source and the number of entries indexed.
+    pub fn build<R: BufRead, W: Write + Seek>(
+        source: R,
+        index: W,
+    ) -> Result<(BreachedPasswordHash, u64), OperationError> {
+        let mut index = BufWriter::new(index);
+
+        // Skip the header and bucket table, which are written once all entries are known.
+        index
+            .seek(SeekFrom::Start(INDEX_HEADER_LEN + BUCKET_TABLE_LEN))
+            .map_err(|err| index_io_error(&err))?;
+
+        let mut bucket_counts = vec![0u64; BUCKET_COUNT];
+        let mut source_hash = None;
+        let mut previous = None;
+        let mut entries = 0;
+
+        for (line_number, line) in source.lines().enumerate() {
+            let line = line.map_err(|err| index_io_error(&err))?;
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let hex_digest = line.split(':').next().unwrap_or_default();
+            let Some(line_hash) = hex::decode(hex_digest)
+                .ok()
+                .and_then(|digest| {
+                    BreachedPasswordHash::from_digest_len(digest.len())
+                        .zip(truncate_digest(&digest))
+                })
+                .filter(|(line_hash, _)| source_hash.unwrap_or(*line_hash) == *line_hash)
+            else {
+                error!(
+                    line = line_number + 1,
+                    "breached password source line is not a hash of the expected type"
+                );
+                return Err(OperationError::BP0003SourceInvalidHash);
+            };
+
+            let (line_hash, value) = line_hash;
+            source_hash = Some(line_hash);
+
+            match previous {
+                Some(previous) if value < previous => {
+                    error!(
+                        line = line_number + 1,
+                        "breached password source is not ordered by hash"
+                    );
+                    return Err(OperationError::BP0004SourceNotOrdered);
+                }
+                // Distinct hashes can share the same truncated value.
+                Some(previous) if value == previous => continue,
+                _ => {}
+            }
+            previous = Some(value);
+
+            if let Some(count) = bucket_counts.get_mut(bucket_of(value) as usize) {
+                *count += 1;
+            }
+            index
+                .write_all(&value.to_be_bytes())
+                .map_err(|err| index_io_error(&err))?;
+            entries += 1;
+        }
+
+        let Some(source_hash) = source_hash else {
+            error!("breached password source contains no hashes");
+            return Err(OperationError::BP0003SourceInvalidHash);
+        };
+
+        index
+            .seek(SeekFrom::Start(0))
+            .map_err(|err| index_io_error(&err))?;
+        index
+            .write_all(INDEX_MAGIC)
+            .map_err(|err| index_io_error(&err))?;
+        index
+            .write_all(&[INDEX_VERSION, source_hash.to_byte(), 0, 0])
+            .map_err(|err| index_io_error(&err))?;
+
+        let mut offset: u64 = 0;
+        index
+            .write_all(&offset.to_be_bytes())
+            .map_err(|err| index_io_error(&err))?;
+        for count in bucket_counts {
+            offset += count;
+            index
+                .write_all(&offset.to_be_bytes())
+                .map_err(|err| index_io_error(&err))?;
+        }
+
+        index.flush().map_err(|err| index_io_error(&err))?;
+
+        Ok((source_hash, entries))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BreachedPasswordHash, BreachedPasswordIndex, ENTRY_LEN};
+    use crate::prelude::*;
+    use crypto_glue::{sha1::Sha1, traits::Digest};
+    use md4::Md4;
+    use std::io::Cursor;
+
+    fn build_source(hash: BreachedPasswordHash, passwords: &[&str]) -> String {
+        let mut hashes: Vec<String> = passwords
+            .iter()
+            .map(|pw| match hash {
+                BreachedPasswordHash::Sha1 => hex::encode_upper(Sha1::digest(pw.as_bytes())),
+                BreachedPasswordHash::Ntlm => {
+                    let clear_utf16le: Vec<u8> =
+                        pw.encode_utf16().flat_map(|c| c.to_le_bytes()).collect();
+                    hex::encode_upper(Md4::digest(&clear_utf16le))
+                }
+            })
+            .collect();
+        hashes.sort_unstable();
+
+        hashes.into_iter().map(|h| format!("{h}:10\r\n")).collect()
+    }
+
+    fn check_index(hash: BreachedPasswordHash) {
+        let breached = [
+            "password",
+            "hunter2",
+            "correct horse battery staple",
+            "🦀🦀🦀",
+        ];
+        let source = build_source(hash, &breached);
+
+        let mut index = Cursor::new(Vec::new());
+        let (index_hash, entries) = BreachedPasswordIndex::build(source.as_bytes(), &mut index)
+            .expect("Failed to build index");
+        assert_eq!(index_hash, hash);
+        assert_eq!(entries, breached.len() as u64);
+
+        let index = BreachedPasswordIndex::load(Box::new(index.into_inner()), entries)
+            .expect("Failed to load index");
+        assert_eq!(index.hash, hash);
+
+        for pw in breached {
+            assert_eq!(index.contains(pw), Ok(true));
+        }
+
+        assert_eq!(
+            index.contains("vieSh8eet3sheiquoh9rahzoh2oolohr"),
+            Ok(false)
+        );
+
+        // An index that is missing entries is rejected.
+        let mut truncated = Cursor::new(Vec::new());
+        BreachedPasswordIndex::build(source.as_bytes(), &mut truncated)
+            .expect("Failed to build index");
+        let mut truncated = truncated.into_inner();
+        truncated.truncate(truncated.len() - ENTRY_LEN as usize);
+        assert_eq!(
+            BreachedPasswordIndex::load(Box::new(truncated), entries - 1).map(|_| ()),
+            Err(OperationError::BP0002IndexInvalidHeader)
+        );
+    }
+
+    #[test]
+    fn test_breached_password_digest() {
+        // Known digests of "password".
+        assert_eq!(
+            BreachedPasswordHash::Sha1.truncated_digest("password"),
+            Ok(0x5BAA61E4C9B93F3F)
+        );
+        assert_eq!(
+            BreachedPasswordHash::Ntlm.truncated_digest("password"),
+            Ok(0x8846F7EAEE8FB117)
+        );
+    }
+
+    #[test]
+    fn test_breached_password_index_sha1() {
+        check_index(BreachedPasswordHash::Sha1);
+    }
+
+    #[test]
+    fn test_breached_password_index_ntlm() {
+        check_index(BreachedPasswordHash::Ntlm);
+    }
+
+    #[test]
+    fn test_breached_password_index_invalid_source() {
+        // Unordered
+        let source = "5BAA61E4C9B93F3F0682250B6CF8331B7EE68FD8:10\n\
+                      0000000CAEF405439D57847A8657218C618160B2:1\n";
+        assert_eq!(
+            BreachedPasswordIndex::build(source.as_bytes(), Cursor::new(Vec::new())),
+            Err(OperationError::BP0004SourceNotOrdered)
+        );
+
+        // Mixed hash types
+        let source = "0000000CAEF405439D57847A8657218C618160B2:1\n\
+                      8846F7EAEE8FB117AD06BDD830B7586C:10\n";
+        assert_eq!(
+            BreachedPasswordIndex::build(source.as_bytes(), Cursor::new(Vec::new())),
+            Err(OperationError::BP0003SourceInvalidHash)
+        );
+
+        // Not a hash
+        let source = "password:10\n";
+        assert_eq!(
+            BreachedPasswordIndex::build(source.as_bytes(), Cursor::new(Vec::new())),
+            Err(OperationError::BP0003SourceInvalidHash)
+        );
+
+        // Empty
+        assert_eq!(
+            BreachedPasswordIndex::build("".as_bytes(), Cursor::new(Vec::new())),
+            Err(OperationError::BP0003SourceInvalidHash)
+        );
+    }
+}
@@ -35,7 +35,7 @@ const MAXIMUM_CRED_UPDATE_TTL: Duration = Duration::from_secs(900);
 // Minimum 5 minutes.
 const MINIMUM_INTENT_TTL: Duration = Duration::from_secs(300);
 // Default 1 hour.
-const DEFAULT_INTENT_TTL: Duration = Duration::from_secs(3600);
+pub(crate) const DEFAULT_INTENT_TTL: Duration = Duration::from_secs(3600);
 // Default 1 day.
 const MAXIMUM_INTENT_TTL: Duration = Duration::from_secs(86400);
 
@@ -44,7 +44,10 @@ pub enum PasswordQuality {
     TooShort(u32),
     BadListed,
     DontReusePasswords,
+    RecentlyUsed(u32),
     Feedback(Vec<PasswordFeedback>),
+    /// The password could not be checked, so it can't be accepted.
+    Unverifiable(OperationError),
 }
 
 #[derive(Clone, Debug)]
@@ -1807,6 +1810,7 @@ impl IdmServerCredUpdateTransaction<'_> {
         resolved_account_policy: &ResolvedAccountPolicy,
         related_inputs: &[&str],
         radius_secret: Option<&str>,
+        password_history: Option<&Credential>,
     ) -> Result<(), PasswordQuality> {
         // password strength and badlisting is always global, rather than per-pw-policy.
         // pw-policy as check on the account is about requirements for mfa for example.
@@ -1962,10 +1966,44 @@ impl IdmServerCredUpdateTransaction<'_> {
             .contains(&cleartext.to_lowercase())
         {
             security_info!("Password found in badlist, rejecting");
-            Err(PasswordQuality::BadListed)
-        } else {
-            Ok(())
+            return Err(PasswordQuality::BadListed);
+        }
+
+        // Check the offline breached password index if one is configured. If the index
+        // can't be checked the change is rejected, rather than accepting a password that
+        // may be breached.
+        if let Some(breached_passwords) = self.breached_passwords {
+            match breached_passwords.contains(cleartext) {
+                Ok(true) => {
+                    security_info!("Password found in breached password index, rejecting");
+                    return Err(PasswordQuality::BadListed);
+                }
+                Ok(false) => {}
+                Err(err) => {
+                    error!(
+                        ?err,
+                        "Unable to check the breached password index, rejecting"
+                    );
+                    return Err(PasswordQuality::Unverifiable(err));
+                }
+            }
         }
+
+        // Finally, has this password been used recently? This is last since verifying each
+        // historic password is expensive.
+        let pw_history_length = resolved_account_policy.pw_history_length();
+        if let Some(password_history) = password_history {
+            // A credential that can't be verified is logged, and doesn't prevent the change.
+            if password_history
+                .password_recently_used(cleartext, pw_history_length)
+                .unwrap_or_default()
+            {
+                security_info!("Password was recently used, rejecting");
+                return Err(PasswordQuality::RecentlyUsed(pw_history_length));
+            }
+        }
+
+        Ok(())
     }
 
     #[instrument(level = "trace", skip(cust, self))]
@@ -1987,6 +2025,7 @@ impl IdmServerCredUpdateTransaction<'_> {
             &session.resolved_account_policy,
             session.account.related_inputs().as_slice(),
             session.account.radius_secret.as_deref(),
+            session.account.primary.as_ref(),
         )
         .map_err(|e| match e {
             PasswordQuality::TooShort(sz) => {
@@ -1998,7 +2037,13 @@ impl IdmServerCredUpdateTransaction<'_> {
             PasswordQuality::DontReusePasswords => {
                 OperationError::PasswordQuality(vec![PasswordFeedback::DontReusePasswords])
             }
+            PasswordQuality::RecentlyUsed(history_length) => {
+                OperationError::PasswordQuality(vec![PasswordFeedback::RecentlyUsed(
+                    history_length,
+                )])
+            }
             PasswordQuality::Feedback(feedback) => OperationError::PasswordQuality(feedback),
+            PasswordQuality::Unverifiable(err) => err,
         })?;
 
         Ok(session.deref().into())
@@ -2030,6 +2075,7 @@ impl IdmServerCredUpdateTransaction<'_> {
             &session.resolved_account_policy,
             session.account.related_inputs().as_slice(),
             session.account.radius_secret.as_deref(),
+            session.account.primary.as_ref(),
         )
         .map_err(|e| match e {
             PasswordQuality::TooShort(sz) => {
@@ -2041,10 +2087,16 @@ impl IdmServerCredUpdateTransaction<'_> {
             PasswordQuality::DontReusePasswords => {
                 OperationError::PasswordQuality(vec![PasswordFeedback::DontReusePasswords])
             }
+            PasswordQuality::RecentlyUsed(history_length) => {
+                OperationError::PasswordQuality(vec![PasswordFeedback::RecentlyUsed(
+                    history_length,
+                )])
+            }
             PasswordQuality::Feedback(feedback) => OperationError::PasswordQuality(feedback),
+            PasswordQuality::Unverifiable(err) => err,
         })?;
 
-        let ncred = match &session.primary {
+        let mut ncred = match &session.primary {
             Some(primary) => {
                 // Is there a need to update the uuid of the cred re softlocks?
                 primary.set_password(self.crypto_policy, pw, timestamp)?
@@ -2052,6 +2104,13 @@ impl IdmServerCredUpdateTransaction<'_> {
             None => Credential::new_password_only(self.crypto_policy, pw, timestamp)?,
         };
 
+        // History is taken from the committed credential, so that passwords only set during
+        // this session are not remembered.
+        ncred.rotate_password_history(
+            session.account.primary.as_ref(),
+            session.resolved_account_policy.pw_history_length(),
+        );
+
         session.primary = Some(ncred);
         Ok(session.deref().into())
     }
@@ -2645,6 +2704,7 @@ impl IdmServerCredUpdateTransaction<'_> {
             &session.resolved_account_policy,
             session.account.related_inputs().as_slice(),
             session.account.radius_secret.as_deref(),
+            session.account.unix_extn().and_then(|extn| extn.ucred()),
         )
         .map_err(|e| match e {
             PasswordQuality::TooShort(sz) => {
@@ -2656,10 +2716,16 @@ impl IdmServerCredUpdateTransaction<'_> {
             PasswordQuality::DontReusePasswords => {
                 OperationError::PasswordQuality(vec![PasswordFeedback::DontReusePasswords])
             }
+            PasswordQuality::RecentlyUsed(history_length) => {
+                OperationError::PasswordQuality(vec![PasswordFeedback::RecentlyUsed(
+                    history_length,
+                )])
+            }
             PasswordQuality::Feedback(feedback) => OperationError::PasswordQuality(feedback),
+            PasswordQuality::Unverifiable(err) => err,
         })?;
 
-        let ncred = match &session.unixcred {
+        let mut ncred = match &session.unixcred {
             Some(unixcred) => {
                 // Is there a need to update the uuid of the cred re softlocks?
                 unixcred.set_password(self.crypto_policy, pw, timestamp)?
@@ -2667,6 +2733,11 @@ impl IdmServerCredUpdateTransaction<'_> {
             None => Credential::new_password_only(self.crypto_policy, pw, timestamp)?,
         };
 
+        ncred.rotate_password_history(
+            session.account.unix_extn().and_then(|extn| extn.ucred()),
+            session.resolved_account_policy.pw_history_length(),
+        );
+
         session.unixcred = Some(ncred);
         Ok(session.deref().into())
     }
@@ -2801,7 +2872,7 @@ mod tests {
     };
     use crate::idm::server::{IdmServer, IdmServerCredUpdateTransaction, IdmServerDelayed};
     use crate::prelude::*;
-    use crate::utils::password_from_random_len;
+    use crate::utils::{password_from_random, password_from_random_len};
     use crate::value::CredentialType;
     use crate::valueset::ValueSetEmailAddress;
     use compact_jwt::JwsCompact;
@@ -3745,6 +3816,96 @@ mod tests {
         commit_session(idms, ct, cust).await;
     }
 
+    #[idm_test]
+    async fn credential_update_password_history_account_policy(
+        idms: &IdmServer,
+        _idms_delayed: &mut IdmServerDelayed,
+    ) {
+        let ct = Duration::from_secs(TEST_CURRENT_TIME);
+
+        // Remember the current and one prior password.
+        let test_pw_history_length = 2;
+
+        let mut idms_prox_write = idms.proxy_write(ct).await.unwrap();
+
+        let modlist = ModifyList::new_purge_and_set(
+            Attribute::AuthPasswordHistoryLength,
+            Value::Uint32(test_pw_history_length),
+        );
+        idms_prox_write
+            .qs_write
+            .internal_modify_uuid(UUID_IDM_ALL_ACCOUNTS, &modlist)
+            .expect("Unable to change password history length");
+
+        assert!(idms_prox_write.commit().is_ok());
+
+        let pw_a = password_from_random();
+        let pw_b = password_from_random();
+        let pw_c = password_from_random();
+
+        let (cust, _) = setup_test_session(idms, ct).await;
+        let cutxn = idms.cred_update_transaction().await.unwrap();
+        cutxn
+            .credential_primary_set_password(&cust, ct, &pw_a)
+            .expect("Failed to update the primary cred password");
+        // Passwords set during the session are not remembered.
+        cutxn
+            .credential_primary_set_password(&cust, ct, &pw_b)
+            .expect("Failed to update the primary cred password");
+        cutxn
+            .credential_primary_set_password(&cust, ct, &pw_a)
+            .expect("Failed to update the primary cred password");
+        drop(cutxn);
+        commit_session(idms, ct, cust).await;
+
+        // The current password can't be set again.
+        let (cust, _) = renew_test_session(idms, ct).await;
+        let cutxn = idms.cred_update_transaction().await.unwrap();
+        let err = cutxn
+            .credential_check_password_quality(&cust, ct, &pw_a)
+            .unwrap_err();
+        assert!(
+            matches!(err, OperationError::PasswordQuality(details) if details == vec!(PasswordFeedback::RecentlyUsed(test_pw_history_length)))
+        );
+        let err = cutxn
+            .credential_primary_set_password(&cust, ct, &pw_a)
+            .unwrap_err();
+        assert!(
+            matches!(err, OperationError::PasswordQuality(details) if details == vec!(PasswordFeedback::RecentlyUsed(test_pw_history_length)))
+        );
+        cutxn
+            .credential_primary_set_password(&cust, ct, &pw_b)
+            .expect("Failed to update the primary cred password");
+        drop(cutxn);
+        commit_session(idms, ct, cust).await;
+
+        // Neither the current or prior password can be used.
+        let (cust, _) = renew_test_session(idms, ct).await;
+        let cutxn = idms.cred_update_transaction().await.unwrap();
+        for pw in [&pw_a, &pw_b] {
+            let err = cutxn
+                .credential_primary_set_password(&cust, ct, pw)
+                .unwrap_err();
+            assert!(
+                matches!(err, OperationError::PasswordQuality(details) if details == vec!(PasswordFeedback::RecentlyUsed(test_pw_history_length)))
+            );
+        }
+        cutxn
+            .credential_primary_set_password(&cust, ct, &pw_c)
+            .expect("Failed to update the primary cred password");
+        drop(cutxn);
+        commit_session(idms, ct, cust).await;
+
+        // The oldest password has been trimmed from the history, so it can be used again.
+        let (cust, _) = renew_test_session(idms, ct).await;
+        let cutxn = idms.cred_update_transaction().await.unwrap();
+        cutxn
+            .credential_primary_set_password(&cust, ct, &pw_a)
+            .expect("Failed to update the primary cred password");
+        drop(cutxn);
+        commit_session(idms, ct, cust).await;
+    }
+
     // Test set of primary account password
     //    - fail pw quality checks etc
     //    - set correctly.
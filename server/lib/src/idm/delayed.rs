@@ -1,8 +1,11 @@
+use crate::credential::softlock::CredSoftLockRecord;
 use crate::prelude::*;
 use crate::value::AuthType;
+use crate::value::CredUpdateSessionPerms;
 use crate::value::SessionExtMetadata;
 use std::fmt;
 use time::OffsetDateTime;
+use url::Url;
 use uuid::Uuid;
 use webauthn_rs::prelude::AuthenticationResult;
 
@@ -13,6 +16,9 @@ pub enum DelayedAction {
     WebauthnCounterIncrement(WebauthnCounterIncrement),
     BackupCodeRemoval(BackupCodeRemoval),
     AuthSessionRecord(AuthSessionRecord),
+    CredentialUpdateIntentRecord(CredentialUpdateIntentRecord),
+    CredentialSoftLockRecord(CredentialSoftLockRecord),
+    Oauth2BackchannelLogout(Oauth2BackchannelLogout),
 }
 
 pub struct PasswordUpgrade {
@@ -66,3 +72,51 @@ pub struct AuthSessionRecord {
     pub type_: AuthType,
     pub ext_metadata: SessionExtMetadata,
 }
+
+/// A credential update intent issued in place of a session, when an account authenticates
+/// with a password that has exceeded its maximum age.
+pub struct CredentialUpdateIntentRecord {
+    pub target_uuid: Uuid,
+    pub intent_id: String,
+    pub max_ttl: Duration,
+    pub perms: CredUpdateSessionPerms,
+}
+
+impl fmt::Debug for CredentialUpdateIntentRecord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CredentialUpdateIntentRecord")
+            .field("target_uuid", &self.target_uuid)
+            .field("max_ttl", &self.max_ttl)
+            .field("perms", &self.perms)
+            .finish()
+    }
+}
+
+/// The soft lock state of a credential after an authentication failure, persisted so
+/// that the lock applies across restarts and on every replica.
+#[derive(Debug)]
+pub struct CredentialSoftLockRecord {
+    pub target_uuid: Uuid,
+    pub cred_uuid: Uuid,
+    pub record: CredSoftLockRecord,
+}
+
+/// A signed logout token that must be delivered to an oauth2 client. Unlike other
+/// delayed actions this does not write to the database, and is sent by the server
+/// core once the transaction that terminated the session has committed.
+pub struct Oauth2BackchannelLogout {
+    pub rs_uuid: Uuid,
+    pub session_id: Uuid,
+    pub backchannel_logout_uri: Url,
+    pub logout_token: String,
+}
+
+impl fmt::Debug for Oauth2BackchannelLogout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Oauth2BackchannelLogout")
+            .field("rs_uuid", &self.rs_uuid)
+            .field("session_id", &self.session_id)
+            .field("backchannel_logout_uri", &self.backchannel_logout_uri)
+            .finish()
+    }
+}
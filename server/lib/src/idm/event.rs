@@ -225,6 +225,24 @@ impl CredentialStatusEvent {
     }
 }
 
+#[derive(Debug)]
+pub struct CredentialSoftLockEvent {
+    pub ident: Identity,
+    pub target: Uuid,
+}
+
+impl CredentialSoftLockEvent {
+    pub fn from_parts(ident: Identity, target: Uuid) -> Result<Self, OperationError> {
+        Ok(CredentialSoftLockEvent { ident, target })
+    }
+
+    pub(crate) fn new_internal(target: Uuid) -> Self {
+        let ident = Identity::from_internal();
+
+        CredentialSoftLockEvent { ident, target }
+    }
+}
+
 pub struct LdapAuthEvent {
     // pub ident: Identity,
     pub target: Uuid,
@@ -1,23 +1,61 @@
 //! LDAP specific operations handling components. This is where LDAP operations
 //! are sent to for processing.
 
-use std::collections::BTreeSet;
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
 use std::iter;
 use std::str::FromStr;
 
 use compact_jwt::JwsCompact;
 use kanidm_proto::constants::*;
 use kanidm_proto::internal::{ApiToken, UserAuthToken};
+use kanidm_proto::v1::Entry as ProtoEntry;
+use ldap3_proto::control::LdapControl;
+use ldap3_proto::proto::{
+    LdapAddRequest, LdapBindCred, LdapBindRequest, LdapBindResponse, LdapExtendedResponse,
+    LdapIntermediateResponse, LdapModify, LdapModifyRequest, LdapModifyType, LdapMsg, LdapOp,
+    LdapPasswordModifyRequest, LdapResult, SaslCredentials, SyncRequestMode, SyncStateValue,
+};
 use ldap3_proto::simple::*;
 use regex::{Regex, RegexBuilder};
 use std::net::IpAddr;
+use tokio::sync::Mutex;
 use tracing::trace;
 use uuid::Uuid;
 
+use crate::be::BackendTransaction;
 use crate::event::SearchEvent;
-use crate::idm::event::{LdapApplicationAuthEvent, LdapAuthEvent, LdapTokenAuthEvent};
-use crate::idm::server::{IdmServer, IdmServerAuthTransaction, IdmServerTransaction};
+use crate::idm::event::{
+    LdapApplicationAuthEvent, LdapAuthEvent, LdapTokenAuthEvent, UnixPasswordChangeEvent,
+};
+use crate::idm::server::{
+    IdmServer, IdmServerAuthTransaction, IdmServerProxyReadTransaction, IdmServerTransaction,
+};
 use crate::prelude::*;
+use crate::repl::proto::ReplRuvRange;
+use crate::repl::ruv::{
+    RangeDiffStatus, ReplicationUpdateVector, ReplicationUpdateVectorTransaction,
+};
+
+/// RFC 4532 - Who am I?
+const LDAP_EXOP_WHOAMI: &str = "1.3.6.1.4.1.4203.1.11.3";
+/// RFC 3062 - LDAP Password Modify Extended Operation
+const LDAP_EXOP_PASSWORD_MODIFY: &str = "1.3.6.1.4.1.4203.1.11.1";
+/// RFC 4511 - StartTLS Extended Operation
+pub const LDAP_EXOP_STARTTLS: &str = "1.3.6.1.4.1.1466.20037";
+/// RFC 2696 - LDAP Control Extension for Simple Paged Results Manipulation
+const LDAP_CONTROL_PAGED_RESULTS: &str = "1.2.840.113556.1.4.319";
+/// RFC 4533 - The Lightweight Directory Access Protocol (LDAP) Content Synchronization Operation
+const LDAP_CONTROL_SYNC_REQUEST: &str = "1.3.6.1.4.1.4203.1.9.1.1";
+
+/// RFC 4422 - Simple Authentication and Security Layer (SASL), Appendix A
+const LDAP_SASL_EXTERNAL: &str = "EXTERNAL";
+
+/// How long a paged search is retained between pages before it is discarded.
+const LDAP_PAGED_SEARCH_TIMEOUT: Duration = Duration::from_secs(300);
+/// The maximum number of paged searches a single session may have in progress.
+const LDAP_PAGED_SEARCH_MAX_PER_SESSION: usize = 8;
+/// The maximum number of paged searches that may be in progress across all sessions.
+const LDAP_PAGED_SEARCH_MAX_TOTAL: usize = 128;
 
 // Clippy doesn't like Bind here. But proto needs unboxed ldapmsg,
 // and ldapboundtoken is moved. Really, it's not too bad, every message here is pretty sucky.
@@ -39,6 +77,8 @@ pub enum LdapSession {
     UserAuthToken(UserAuthToken),
     ApiToken(ApiToken),
     ApplicationPasswordBind(Uuid, Uuid),
+    // The uuid of the client certificate, and the account that owns it.
+    ClientCertificate(Uuid, Uuid),
 }
 
 #[derive(Debug, Clone)]
@@ -61,6 +101,246 @@ pub struct LdapServer {
     dnre: Regex,
     binddnre: Regex,
     max_queryable_attrs: usize,
+    paged_searches: Mutex<BTreeMap<Vec<u8>, LdapPagedSearch>>,
+}
+
+/// The resolved form of a search request, ready to be executed.
+struct LdapSearchPlan {
+    lfilter: LdapFilter,
+    k_attrs: Option<BTreeSet<Attribute>>,
+    l_attrs: Vec<String>,
+    all_attrs: bool,
+}
+
+/// An in progress paged search, keyed by the cookie given to the client. Only the uuids
+/// of the remaining entries are kept, and each page is resolved as it is requested.
+struct LdapPagedSearch {
+    // Only the session that started the search may continue it.
+    session_id: Uuid,
+    expiry: Duration,
+    plan: LdapSearchPlan,
+    uuids: VecDeque<Uuid>,
+}
+
+/// Write operations that the ldap3_proto simple server operations don't model, so
+/// we decode them from the protocol message ourselves. These are mapped onto the
+/// same create, modify and delete events that the HTTPS api uses so that access
+/// controls are applied identically.
+#[derive(Debug, Clone)]
+pub enum LdapWriteOps {
+    Modify(i32, LdapModifyRequest),
+    Add(i32, LdapAddRequest),
+    Delete(i32, String),
+    PasswordModify(i32, LdapPasswordModifyRequest),
+}
+
+impl TryFrom<LdapMsg> for LdapWriteOps {
+    // Give the message back so that it can be processed as a read operation.
+    type Error = LdapMsg;
+
+    fn try_from(msg: LdapMsg) -> Result<Self, Self::Error> {
+        let msgid = msg.msgid;
+        match msg.op {
+            LdapOp::ModifyRequest(mr) => Ok(LdapWriteOps::Modify(msgid, mr)),
+            LdapOp::AddRequest(ar) => Ok(LdapWriteOps::Add(msgid, ar)),
+            LdapOp::DelRequest(dn) => Ok(LdapWriteOps::Delete(msgid, dn)),
+            LdapOp::ExtendedRequest(ref er) if er.name == LDAP_EXOP_PASSWORD_MODIFY => {
+                match LdapPasswordModifyRequest::try_from(er) {
+                    Ok(pmr) => Ok(LdapWriteOps::PasswordModify(msgid, pmr)),
+                    Err(_) => Err(msg),
+                }
+            }
+            _ => Err(msg),
+        }
+    }
+}
+
+impl LdapWriteOps {
+    fn gen_result(&self, code: LdapResultCode, message: String) -> LdapMsg {
+        let res = LdapResult {
+            code,
+            matcheddn: "".to_string(),
+            message,
+            referral: Vec::with_capacity(0),
+        };
+
+        let (msgid, op) = match self {
+            LdapWriteOps::Modify(msgid, _) => (*msgid, LdapOp::ModifyResponse(res)),
+            LdapWriteOps::Add(msgid, _) => (*msgid, LdapOp::AddResponse(res)),
+            LdapWriteOps::Delete(msgid, _) => (*msgid, LdapOp::DelResponse(res)),
+            LdapWriteOps::PasswordModify(msgid, _) => (
+                *msgid,
+                LdapOp::ExtendedResponse(LdapExtendedResponse {
+                    res,
+                    name: None,
+                    value: None,
+                }),
+            ),
+        };
+
+        LdapMsg {
+            msgid,
+            op,
+            ctrl: Vec::with_capacity(0),
+        }
+    }
+
+    pub fn gen_success(&self) -> LdapMsg {
+        self.gen_result(LdapResultCode::Success, "".to_string())
+    }
+
+    pub fn gen_error(&self, code: LdapResultCode, message: String) -> LdapMsg {
+        self.gen_result(code, message)
+    }
+}
+
+/// How a message received on a plaintext connection is handled before StartTLS has
+/// completed. Until then only the rootDSE can be read, so that clients can discover
+/// that StartTLS is supported, and all other operations are refused.
+#[derive(Debug)]
+pub enum LdapStartTlsOp {
+    /// Send this response, and then begin the TLS handshake.
+    StartTls(LdapMsg),
+    /// The message does not require confidentiality and can be processed.
+    Process(LdapMsg),
+    /// The operation is refused. Some operations (such as abandon) have no response.
+    Refuse(Option<LdapMsg>),
+    /// The message is not valid, send this notice and close the connection.
+    Disconnect(LdapMsg),
+    /// The client has closed the connection.
+    Unbind,
+}
+
+impl From<LdapMsg> for LdapStartTlsOp {
+    fn from(msg: LdapMsg) -> Self {
+        let gen_result = |code: LdapResultCode| {
+            let message = if code == LdapResultCode::Success {
+                "".to_string()
+            } else {
+                "StartTLS is required before this operation".to_string()
+            };
+            LdapResult {
+                code,
+                matcheddn: "".to_string(),
+                message,
+                referral: vec![],
+            }
+        };
+
+        let op = match &msg.op {
+            LdapOp::ExtendedRequest(ler) if ler.name == LDAP_EXOP_STARTTLS => {
+                return LdapStartTlsOp::StartTls(LdapMsg {
+                    msgid: msg.msgid,
+                    op: LdapOp::ExtendedResponse(LdapExtendedResponse {
+                        res: gen_result(LdapResultCode::Success),
+                        name: Some(LDAP_EXOP_STARTTLS.to_string()),
+                        value: None,
+                    }),
+                    ctrl: vec![],
+                })
+            }
+            LdapOp::SearchRequest(lsr)
+                if lsr.base.is_empty() && lsr.scope == LdapSearchScope::Base =>
+            {
+                return LdapStartTlsOp::Process(msg)
+            }
+            LdapOp::UnbindRequest => return LdapStartTlsOp::Unbind,
+            LdapOp::AbandonRequest(_) => return LdapStartTlsOp::Refuse(None),
+            LdapOp::BindRequest(_) => LdapOp::BindResponse(LdapBindResponse {
+                res: gen_result(LdapResultCode::ConfidentialityRequired),
+                saslcreds: None,
+            }),
+            LdapOp::SearchRequest(_) => {
+                LdapOp::SearchResultDone(gen_result(LdapResultCode::ConfidentialityRequired))
+            }
+            LdapOp::ModifyRequest(_) => {
+                LdapOp::ModifyResponse(gen_result(LdapResultCode::ConfidentialityRequired))
+            }
+            LdapOp::AddRequest(_) => {
+                LdapOp::AddResponse(gen_result(LdapResultCode::ConfidentialityRequired))
+            }
+            LdapOp::DelRequest(_) => {
+                LdapOp::DelResponse(gen_result(LdapResultCode::ConfidentialityRequired))
+            }
+            LdapOp::ModifyDNRequest(_) => {
+                LdapOp::ModifyDNResponse(gen_result(LdapResultCode::ConfidentialityRequired))
+            }
+            LdapOp::CompareRequest(_) => {
+                LdapOp::CompareResult(gen_result(LdapResultCode::ConfidentialityRequired))
+            }
+            LdapOp::ExtendedRequest(_) => LdapOp::ExtendedResponse(LdapExtendedResponse {
+                res: gen_result(LdapResultCode::ConfidentialityRequired),
+                name: None,
+                value: None,
+            }),
+            _ => {
+                return LdapStartTlsOp::Disconnect(DisconnectionNotice::gen(
+                    LdapResultCode::ProtocolError,
+                    "Invalid Request",
+                ))
+            }
+        };
+
+        LdapStartTlsOp::Refuse(Some(LdapMsg {
+            msgid: msg.msgid,
+            op,
+            ctrl: vec![],
+        }))
+    }
+}
+
+/// A SASL bind request. The ldap3_proto simple server operations only model simple binds,
+/// so we decode these from the protocol message ourselves.
+#[derive(Debug, Clone)]
+pub struct LdapSaslBindRequest {
+    pub msgid: i32,
+    pub mechanism: String,
+    pub credentials: Vec<u8>,
+}
+
+impl TryFrom<LdapMsg> for LdapSaslBindRequest {
+    // Give the message back so that it can be processed as a simple operation.
+    type Error = LdapMsg;
+
+    fn try_from(msg: LdapMsg) -> Result<Self, Self::Error> {
+        match msg.op {
+            LdapOp::BindRequest(LdapBindRequest {
+                cred:
+                    LdapBindCred::SASL(SaslCredentials {
+                        mechanism,
+                        credentials,
+                    }),
+                ..
+            }) => Ok(LdapSaslBindRequest {
+                msgid: msg.msgid,
+                mechanism,
+                credentials,
+            }),
+            _ => Err(msg),
+        }
+    }
+}
+
+impl LdapSaslBindRequest {
+    pub fn gen_success(&self) -> LdapMsg {
+        self.gen_error(LdapResultCode::Success, "".to_string())
+    }
+
+    pub fn gen_error(&self, code: LdapResultCode, message: String) -> LdapMsg {
+        LdapMsg {
+            msgid: self.msgid,
+            op: LdapOp::BindResponse(LdapBindResponse {
+                res: LdapResult {
+                    code,
+                    matcheddn: "".to_string(),
+                    message,
+                    referral: vec![],
+                },
+                saslcreds: None,
+            }),
+            ctrl: vec![],
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -143,7 +423,22 @@ impl LdapServer {
                 },
                 LdapPartialAttribute {
                     atype: "supportedextension".to_string(),
-                    vals: vec!["1.3.6.1.4.1.4203.1.11.3".as_bytes().to_vec()],
+                    vals: vec![
+                        LDAP_EXOP_WHOAMI.as_bytes().to_vec(),
+                        LDAP_EXOP_PASSWORD_MODIFY.as_bytes().to_vec(),
+                        LDAP_EXOP_STARTTLS.as_bytes().to_vec(),
+                    ],
+                },
+                LdapPartialAttribute {
+                    atype: "supportedcontrol".to_string(),
+                    vals: vec![
+                        LDAP_CONTROL_PAGED_RESULTS.as_bytes().to_vec(),
+                        LDAP_CONTROL_SYNC_REQUEST.as_bytes().to_vec(),
+                    ],
+                },
+                LdapPartialAttribute {
+                    atype: "supportedsaslmechanisms".to_string(),
+                    vals: vec![LDAP_SASL_EXTERNAL.as_bytes().to_vec()],
                 },
                 LdapPartialAttribute {
                     atype: "supportedfeatures".to_string(),
@@ -162,9 +457,274 @@ impl LdapServer {
             dnre,
             binddnre,
             max_queryable_attrs,
+            paged_searches: Mutex::new(BTreeMap::new()),
+        })
+    }
+
+    /// Parse a search request into the filter and attributes to execute. If this returns
+    /// `None` the search can not match any entries.
+    fn plan_search(&self, sr: &SearchRequest) -> Result<Option<LdapSearchPlan>, OperationError> {
+        // Parse the operation and make sure it's sane before we start the txn.
+
+        // This scoping returns an extra filter component.
+
+        let (opt_attr, opt_value) = match self.dnre.captures(sr.base.as_str()) {
+            Some(caps) => (
+                caps.name("attr").map(|v| v.as_str().to_string()),
+                caps.name("val").map(|v| v.as_str().to_string()),
+            ),
+            None => {
+                request_error!("LDAP Search failure - invalid basedn");
+                return Err(OperationError::InvalidRequestState);
+            }
+        };
+
+        let req_dn = match (opt_attr, opt_value) {
+            (Some(a), Some(v)) => Some((a, v)),
+            (None, None) => None,
+            _ => {
+                request_error!("LDAP Search failure - invalid rdn");
+                return Err(OperationError::InvalidRequestState);
+            }
+        };
+
+        trace!(rdn = ?req_dn);
+
+        // Map the Some(a,v) to ...?
+
+        let ext_filter = match (&sr.scope, req_dn) {
+            // OneLevel and Child searches are **very** similar for us because child
+            // is a "subtree search excluding base". Because we don't have a tree structure at
+            // all, this is the same as a one level (all children of base excluding base).
+            (LdapSearchScope::Children, Some(_r)) | (LdapSearchScope::OneLevel, Some(_r)) => {
+                return Ok(None)
+            }
+            (LdapSearchScope::Children, None) | (LdapSearchScope::OneLevel, None) => {
+                // exclude domain_info
+                Some(LdapFilter::Not(Box::new(LdapFilter::Equality(
+                    Attribute::Uuid.to_string(),
+                    STR_UUID_DOMAIN_INFO.to_string(),
+                ))))
+            }
+            // because we request a specific DN, these are the same since we want the same
+            // entry.
+            (LdapSearchScope::Base, Some((a, v))) | (LdapSearchScope::Subtree, Some((a, v))) => {
+                Some(LdapFilter::Equality(a, v))
+            }
+            (LdapSearchScope::Base, None) => {
+                // domain_info
+                Some(LdapFilter::Equality(
+                    Attribute::Uuid.to_string(),
+                    STR_UUID_DOMAIN_INFO.to_string(),
+                ))
+            }
+            (LdapSearchScope::Subtree, None) => {
+                // No filter changes needed.
+                None
+            }
+        };
+
+        let mut no_attrs = false;
+        let mut all_attrs = false;
+        let mut all_op_attrs = false;
+
+        let attrs_len = sr.attrs.len();
+        if sr.attrs.is_empty() {
+            // If [], then "all" attrs
+            all_attrs = true;
+        } else if attrs_len < self.max_queryable_attrs {
+            sr.attrs.iter().for_each(|a| {
+                if a == "*" {
+                    all_attrs = true;
+                } else if a == "+" {
+                    // This forces the BE to get all the attrs so we can
+                    // map all vattrs.
+                    all_attrs = true;
+                    all_op_attrs = true;
+                } else if a == "1.1" {
+                    /*
+                     *  ref https://www.rfc-editor.org/rfc/rfc4511#section-4.5.1.8
+                     *
+                     *  A list containing only the OID "1.1" indicates that no
+                     *  attributes are to be returned. If "1.1" is provided with other
+                     *  attributeSelector values, the "1.1" attributeSelector is
+                     *  ignored. This OID was chosen because it does not (and can not)
+                     *  correspond to any attribute in use.
+                     */
+                    if sr.attrs.len() == 1 {
+                        no_attrs = true;
+                    }
+                }
+            })
+        } else {
+            admin_error!(
+                "Too many LDAP attributes requested. Maximum allowed is {}, while your search query had {}",
+                self.max_queryable_attrs, attrs_len
+            );
+            return Err(OperationError::ResourceLimit);
+        }
+
+        // We need to retain this to know what the client requested.
+        let (k_attrs, l_attrs) = if no_attrs {
+            // Request no attributes and no mapped attributes.
+            (None, Vec::with_capacity(0))
+        } else if all_op_attrs {
+            // We need all attrs, and we do a full v_attr map.
+            (None, ldap_all_vattrs())
+        } else if all_attrs {
+            // We are already getting all attrs, but if there are any virtual attrs
+            // we need them in our request as well.
+            let req_attrs: Vec<String> = sr
+                .attrs
+                .iter()
+                .filter_map(|a| {
+                    let a_lower = a.to_lowercase();
+
+                    if ldap_vattr_map(&a_lower).is_some() {
+                        Some(a_lower)
+                    } else {
+                        None
+                    }
+                })
+                .collect();
+
+            (None, req_attrs)
+        } else {
+            // What the client requested, in LDAP forms.
+            let req_attrs: Vec<String> = sr
+                .attrs
+                .iter()
+                .filter_map(|a| {
+                    if a == "*" || a == "+" || a == "1.1" {
+                        None
+                    } else {
+                        Some(a.to_lowercase())
+                    }
+                })
+                .collect();
+            // This is what the client requested, but mapped to kanidm forms.
+            // NOTE: All req_attrs are lowercase at this point.
+            let mapped_attrs: BTreeSet<_> = req_attrs
+                .iter()
+                .map(|a| Attribute::from(ldap_vattr_map(a).unwrap_or(a)))
+                .collect();
+
+            (Some(mapped_attrs), req_attrs)
+        };
+
+        admin_info!(attr = ?l_attrs, "LDAP Search Request LDAP Attrs");
+        admin_info!(attr = ?k_attrs, "LDAP Search Request Mapped Attrs");
+
+        // join the filter, with ext_filter
+        let lfilter = match ext_filter {
+            Some(ext) => LdapFilter::And(vec![
+                sr.filter.clone(),
+                ext,
+                LdapFilter::Not(Box::new(LdapFilter::Or(vec![
+                    LdapFilter::Equality(Attribute::Class.to_string(), "classtype".to_string()),
+                    LdapFilter::Equality(Attribute::Class.to_string(), "attributetype".to_string()),
+                    LdapFilter::Equality(
+                        Attribute::Class.to_string(),
+                        "access_control_profile".to_string(),
+                    ),
+                ]))),
+            ]),
+            None => LdapFilter::And(vec![
+                sr.filter.clone(),
+                LdapFilter::Not(Box::new(LdapFilter::Or(vec![
+                    LdapFilter::Equality(Attribute::Class.to_string(), "classtype".to_string()),
+                    LdapFilter::Equality(Attribute::Class.to_string(), "attributetype".to_string()),
+                    LdapFilter::Equality(
+                        Attribute::Class.to_string(),
+                        "access_control_profile".to_string(),
+                    ),
+                ]))),
+            ]),
+        };
+
+        admin_info!(filter = ?lfilter, "LDAP Search Filter");
+
+        Ok(Some(LdapSearchPlan {
+            lfilter,
+            k_attrs,
+            l_attrs,
+            all_attrs,
+        }))
+    }
+
+    /// Execute a planned search as this identity, returning the access control reduced entries.
+    /// If `restrict` is given, only the entries with those uuids are returned.
+    fn search_reduced(
+        &self,
+        idm_read: &mut IdmServerProxyReadTransaction<'_>,
+        ident: Identity,
+        plan: &LdapSearchPlan,
+        restrict: Option<&[Uuid]>,
+    ) -> Result<Vec<EntryReducedCommitted>, OperationError> {
+        // ! Remember, searchEvent wraps to ignore hidden for us.
+        let se = match restrict {
+            Some(uuids) => SearchEvent::new_ext_impersonate_uuid_restricted(
+                &mut idm_read.qs_read,
+                ident,
+                &plan.lfilter,
+                plan.k_attrs.clone(),
+                uuids,
+            ),
+            None => SearchEvent::new_ext_impersonate_uuid(
+                &mut idm_read.qs_read,
+                ident,
+                &plan.lfilter,
+                plan.k_attrs.clone(),
+            ),
+        }
+        .map_err(|e| {
+            admin_error!("failed to create search event -> {:?}", e);
+            e
+        })?;
+
+        idm_read.qs_read.search_ext(&se).map_err(|e| {
+            admin_error!("search failure {:?}", e);
+            e
         })
     }
 
+    /// Execute a planned search as this identity, returning the entries in their LDAP form.
+    fn search_entries(
+        &self,
+        idm_read: &mut IdmServerProxyReadTransaction<'_>,
+        ident: Identity,
+        plan: &LdapSearchPlan,
+    ) -> Result<Vec<LdapSearchResultEntry>, OperationError> {
+        let res = self.search_reduced(idm_read, ident, plan, None)?;
+        self.reduced_to_ldap(idm_read, plan, res)
+    }
+
+    /// Transform access control reduced entries into their LDAP form.
+    fn reduced_to_ldap(
+        &self,
+        idm_read: &mut IdmServerProxyReadTransaction<'_>,
+        plan: &LdapSearchPlan,
+        res: Vec<EntryReducedCommitted>,
+    ) -> Result<Vec<LdapSearchResultEntry>, OperationError> {
+        // These have already been fully reduced (access controls applied),
+        // so we can just transform the values and open palm slam them into
+        // the result structure.
+        res.into_iter()
+            .map(|e| {
+                e.to_ldap(
+                    &mut idm_read.qs_read,
+                    self.basedn.as_str(),
+                    plan.all_attrs,
+                    &plan.l_attrs,
+                )
+            })
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| {
+                admin_error!("entry resolve failure {:?}", e);
+                e
+            })
+    }
+
     #[instrument(level = "debug", skip_all)]
     async fn do_search(
         &self,
@@ -178,255 +738,442 @@ impl LdapServer {
         // If the request is "", Base, Present(Attribute::ObjectClass.into()), [], then we want the rootdse.
         if sr.base.is_empty() && sr.scope == LdapSearchScope::Base {
             admin_info!("LDAP Search success - RootDSE");
-            Ok(vec![
+            return Ok(vec![
                 sr.gen_result_entry(self.rootdse.clone()),
                 sr.gen_success(),
-            ])
-        } else {
-            // We want something else apparently. Need to do some more work ...
-            // Parse the operation and make sure it's sane before we start the txn.
+            ]);
+        }
 
-            // This scoping returns an extra filter component.
+        // We want something else apparently. Need to do some more work ...
+        let Some(plan) = self.plan_search(sr)? else {
+            return Ok(vec![sr.gen_success()]);
+        };
 
-            let (opt_attr, opt_value) = match self.dnre.captures(sr.base.as_str()) {
-                Some(caps) => (
-                    caps.name("attr").map(|v| v.as_str().to_string()),
-                    caps.name("val").map(|v| v.as_str().to_string()),
-                ),
-                None => {
-                    request_error!("LDAP Search failure - invalid basedn");
-                    return Err(OperationError::InvalidRequestState);
-                }
-            };
+        let ct = duration_from_epoch_now();
+        let mut idm_read = idms.proxy_read().await?;
+        // Now start the txn - we need it for resolving filter components.
 
-            let req_dn = match (opt_attr, opt_value) {
-                (Some(a), Some(v)) => Some((a, v)),
-                (None, None) => None,
-                _ => {
-                    request_error!("LDAP Search failure - invalid rdn");
-                    return Err(OperationError::InvalidRequestState);
-                }
-            };
+        // Build the event, with the permissions from effective_session
+        let ident = idm_read
+            .validate_ldap_session(&uat.effective_session, source, ct)
+            .map_err(|e| {
+                admin_error!("Invalid identity: {:?}", e);
+                e
+            })?;
 
-            trace!(rdn = ?req_dn);
+        let lres: Vec<_> = self
+            .search_entries(&mut idm_read, ident, &plan)?
+            .into_iter()
+            .map(|r| sr.gen_result_entry(r))
+            .chain(iter::once(sr.gen_success()))
+            .collect();
 
-            // Map the Some(a,v) to ...?
+        admin_info!(
+            nentries = %lres.len(),
+            "LDAP Search Success -> number of entries"
+        );
 
-            let ext_filter = match (&sr.scope, req_dn) {
-                // OneLevel and Child searches are **very** similar for us because child
-                // is a "subtree search excluding base". Because we don't have a tree structure at
-                // all, this is the same as a one level (all children of base excluding base).
-                (LdapSearchScope::Children, Some(_r)) | (LdapSearchScope::OneLevel, Some(_r)) => {
-                    return Ok(vec![sr.gen_success()])
-                }
-                (LdapSearchScope::Children, None) | (LdapSearchScope::OneLevel, None) => {
-                    // exclude domain_info
-                    Some(LdapFilter::Not(Box::new(LdapFilter::Equality(
-                        Attribute::Uuid.to_string(),
-                        STR_UUID_DOMAIN_INFO.to_string(),
-                    ))))
-                }
-                // because we request a specific DN, these are the same since we want the same
-                // entry.
-                (LdapSearchScope::Base, Some((a, v)))
-                | (LdapSearchScope::Subtree, Some((a, v))) => Some(LdapFilter::Equality(a, v)),
-                (LdapSearchScope::Base, None) => {
-                    // domain_info
-                    Some(LdapFilter::Equality(
-                        Attribute::Uuid.to_string(),
-                        STR_UUID_DOMAIN_INFO.to_string(),
-                    ))
-                }
-                (LdapSearchScope::Subtree, None) => {
-                    // No filter changes needed.
-                    None
-                }
-            };
+        Ok(lres)
+    }
 
-            let mut no_attrs = false;
-            let mut all_attrs = false;
-            let mut all_op_attrs = false;
-
-            let attrs_len = sr.attrs.len();
-            if sr.attrs.is_empty() {
-                // If [], then "all" attrs
-                all_attrs = true;
-            } else if attrs_len < self.max_queryable_attrs {
-                sr.attrs.iter().for_each(|a| {
-                    if a == "*" {
-                        all_attrs = true;
-                    } else if a == "+" {
-                        // This forces the BE to get all the attrs so we can
-                        // map all vattrs.
-                        all_attrs = true;
-                        all_op_attrs = true;
-                    } else if a == "1.1" {
-                        /*
-                         *  ref https://www.rfc-editor.org/rfc/rfc4511#section-4.5.1.8
-                         *
-                         *  A list containing only the OID "1.1" indicates that no
-                         *  attributes are to be returned. If "1.1" is provided with other
-                         *  attributeSelector values, the "1.1" attributeSelector is
-                         *  ignored. This OID was chosen because it does not (and can not)
-                         *  correspond to any attribute in use.
-                         */
-                        if sr.attrs.len() == 1 {
-                            no_attrs = true;
-                        }
-                    }
-                })
-            } else {
-                admin_error!(
-                    "Too many LDAP attributes requested. Maximum allowed is {}, while your search query had {}",
-                    self.max_queryable_attrs, attrs_len
-                );
-                return Err(OperationError::ResourceLimit);
-            }
+    /// Process a search with the simple paged results control (RFC 2696). The search is
+    /// executed on the first request, but only the uuids of the matched entries are retained.
+    /// Each following page is then resolved from those uuids as the client presents the cookie
+    /// from the previous page, so entries that are deleted or become hidden from the session
+    /// in between are skipped. The session's search limits apply to the full result set, so
+    /// paging can not be used to read more entries than an unpaged search could.
+    #[instrument(level = "debug", skip_all)]
+    async fn do_paged_search(
+        &self,
+        idms: &IdmServer,
+        sr: &SearchRequest,
+        uat: &LdapBoundToken,
+        source: Source,
+        size: i64,
+        cookie: &[u8],
+    ) -> Result<Vec<LdapMsg>, OperationError> {
+        admin_info!("Attempt LDAP Paged Search for {}", uat.spn);
+        let ct = duration_from_epoch_now();
 
-            // We need to retain this to know what the client requested.
-            let (k_attrs, l_attrs) = if no_attrs {
-                // Request no attributes and no mapped attributes.
-                (None, Vec::with_capacity(0))
-            } else if all_op_attrs {
-                // We need all attrs, and we do a full v_attr map.
-                (None, ldap_all_vattrs())
-            } else if all_attrs {
-                // We are already getting all attrs, but if there are any virtual attrs
-                // we need them in our request as well.
-                let req_attrs: Vec<String> = sr
-                    .attrs
-                    .iter()
-                    .filter_map(|a| {
-                        let a_lower = a.to_lowercase();
+        let existing = {
+            let mut paged_searches = self.paged_searches.lock().await;
+            // Remove any searches that the client abandoned without completing.
+            paged_searches.retain(|_, ps| ps.expiry > ct);
 
-                        if ldap_vattr_map(&a_lower).is_some() {
-                            Some(a_lower)
-                        } else {
-                            None
-                        }
-                    })
-                    .collect();
+            if cookie.is_empty() {
+                let outstanding = paged_searches
+                    .values()
+                    .filter(|ps| ps.session_id == uat.session_id)
+                    .count();
+
+                if outstanding >= LDAP_PAGED_SEARCH_MAX_PER_SESSION {
+                    admin_error!("Too many outstanding LDAP paged searches for this session");
+                    return Err(OperationError::ResourceLimit);
+                }
 
-                (None, req_attrs)
+                if paged_searches.len() >= LDAP_PAGED_SEARCH_MAX_TOTAL {
+                    admin_error!("Too many outstanding LDAP paged searches");
+                    return Err(OperationError::ResourceLimit);
+                }
+                None
             } else {
-                // What the client requested, in LDAP forms.
-                let req_attrs: Vec<String> = sr
-                    .attrs
-                    .iter()
-                    .filter_map(|a| {
-                        if a == "*" || a == "+" || a == "1.1" {
-                            None
-                        } else {
-                            Some(a.to_lowercase())
-                        }
-                    })
-                    .collect();
-                // This is what the client requested, but mapped to kanidm forms.
-                // NOTE: All req_attrs are lowercase at this point.
-                let mapped_attrs: BTreeSet<_> = req_attrs
-                    .iter()
-                    .map(|a| Attribute::from(ldap_vattr_map(a).unwrap_or(a)))
-                    .collect();
+                // Check the owner before removing the search, so that another session
+                // can't abandon it on behalf of the client.
+                if paged_searches
+                    .get(cookie)
+                    .is_some_and(|ps| ps.session_id != uat.session_id)
+                {
+                    security_error!("LDAP Paged Search cookie presented by a different session");
+                    return Err(OperationError::InvalidRequestState);
+                }
+                paged_searches.remove(cookie)
+            }
+        };
 
-                (Some(mapped_attrs), req_attrs)
-            };
+        // A size of zero indicates the client is abandoning the search.
+        let size = usize::try_from(size).unwrap_or_default();
+
+        let (mut paged_search, cookie, page) = match existing {
+            Some(mut paged_search) => {
+                let page_len = size.min(paged_search.uuids.len());
+                let page_uuids: Vec<_> = paged_search.uuids.drain(..page_len).collect();
+
+                let page = if page_uuids.is_empty() {
+                    Vec::with_capacity(0)
+                } else {
+                    let mut idm_read = idms.proxy_read().await?;
+
+                    // The session may have been revoked since the search began.
+                    let ident = idm_read
+                        .validate_ldap_session(&uat.effective_session, source, ct)
+                        .map_err(|e| {
+                            admin_error!("Invalid identity: {:?}", e);
+                            e
+                        })?;
+
+                    let mut res: BTreeMap<_, _> = self
+                        .search_reduced(
+                            &mut idm_read,
+                            ident,
+                            &paged_search.plan,
+                            Some(&page_uuids),
+                        )?
+                        .into_iter()
+                        .map(|e| (e.get_uuid(), e))
+                        .collect();
+
+                    // Return the page in the order of the original search.
+                    let res = page_uuids.iter().filter_map(|u| res.remove(u)).collect();
+
+                    self.reduced_to_ldap(&mut idm_read, &paged_search.plan, res)?
+                };
+
+                (paged_search, cookie.to_vec(), page)
+            }
+            None if !cookie.is_empty() => {
+                request_error!("LDAP Paged Search failure - invalid or expired cookie");
+                return Err(OperationError::InvalidRequestState);
+            }
+            None => {
+                if sr.base.is_empty() && sr.scope == LdapSearchScope::Base {
+                    admin_info!("LDAP Paged Search success - RootDSE");
+                    return Ok(vec![
+                        sr.gen_result_entry(self.rootdse.clone()),
+                        gen_paged_success(sr, 0, Vec::with_capacity(0)),
+                    ]);
+                }
 
-            admin_info!(attr = ?l_attrs, "LDAP Search Request LDAP Attrs");
-            admin_info!(attr = ?k_attrs, "LDAP Search Request Mapped Attrs");
+                let Some(plan) = self.plan_search(sr)? else {
+                    return Ok(vec![gen_paged_success(sr, 0, Vec::with_capacity(0))]);
+                };
 
-            let ct = duration_from_epoch_now();
-            let mut idm_read = idms.proxy_read().await?;
-            // Now start the txn - we need it for resolving filter components.
-
-            // join the filter, with ext_filter
-            let lfilter = match ext_filter {
-                Some(ext) => LdapFilter::And(vec![
-                    sr.filter.clone(),
-                    ext,
-                    LdapFilter::Not(Box::new(LdapFilter::Or(vec![
-                        LdapFilter::Equality(Attribute::Class.to_string(), "classtype".to_string()),
-                        LdapFilter::Equality(
-                            Attribute::Class.to_string(),
-                            "attributetype".to_string(),
-                        ),
-                        LdapFilter::Equality(
-                            Attribute::Class.to_string(),
-                            "access_control_profile".to_string(),
-                        ),
-                    ]))),
-                ]),
-                None => LdapFilter::And(vec![
-                    sr.filter.clone(),
-                    LdapFilter::Not(Box::new(LdapFilter::Or(vec![
-                        LdapFilter::Equality(Attribute::Class.to_string(), "classtype".to_string()),
-                        LdapFilter::Equality(
-                            Attribute::Class.to_string(),
-                            "attributetype".to_string(),
-                        ),
-                        LdapFilter::Equality(
-                            Attribute::Class.to_string(),
-                            "access_control_profile".to_string(),
-                        ),
-                    ]))),
-                ]),
-            };
+                let mut idm_read = idms.proxy_read().await?;
 
-            admin_info!(filter = ?lfilter, "LDAP Search Filter");
+                let ident = idm_read
+                    .validate_ldap_session(&uat.effective_session, source, ct)
+                    .map_err(|e| {
+                        admin_error!("Invalid identity: {:?}", e);
+                        e
+                    })?;
 
-            // Build the event, with the permissions from effective_session
-            //
-            // ! Remember, searchEvent wraps to ignore hidden for us.
-            let ident = idm_read
-                .validate_ldap_session(&uat.effective_session, source, ct)
-                .map_err(|e| {
-                    admin_error!("Invalid identity: {:?}", e);
-                    e
-                })?;
-            let se = SearchEvent::new_ext_impersonate_uuid(
-                &mut idm_read.qs_read,
-                ident,
-                &lfilter,
-                k_attrs,
-            )
-            .map_err(|e| {
-                admin_error!("failed to create search event -> {:?}", e);
-                e
-            })?;
+                let mut res = self.search_reduced(&mut idm_read, ident, &plan, None)?;
 
-            let res = idm_read.qs_read.search_ext(&se).map_err(|e| {
-                admin_error!("search failure {:?}", e);
-                e
-            })?;
+                // The first page is returned from this search, and only the uuids of the
+                // remaining entries are kept for the pages that follow.
+                let page_len = size.min(res.len());
+                let uuids = res
+                    .split_off(page_len)
+                    .iter()
+                    .map(|e| e.get_uuid())
+                    .collect();
+                let page = self.reduced_to_ldap(&mut idm_read, &plan, res)?;
 
-            // These have already been fully reduced (access controls applied),
-            // so we can just transform the values and open palm slam them into
-            // the result structure.
-            let lres: Result<Vec<_>, _> = res
-                .into_iter()
-                .map(|e| {
-                    e.to_ldap(
-                        &mut idm_read.qs_read,
-                        self.basedn.as_str(),
-                        all_attrs,
-                        &l_attrs,
-                    )
-                    // if okay, wrap in a ldap msg.
-                    .map(|r| sr.gen_result_entry(r))
-                })
-                .chain(iter::once(Ok(sr.gen_success())))
-                .collect();
+                (
+                    LdapPagedSearch {
+                        session_id: uat.session_id,
+                        expiry: ct,
+                        plan,
+                        uuids,
+                    },
+                    Uuid::new_v4().as_bytes().to_vec(),
+                    page,
+                )
+            }
+        };
 
-            let lres = lres.map_err(|e| {
-                admin_error!("entry resolve failure {:?}", e);
-                e
-            })?;
+        let mut lres: Vec<_> = page.into_iter().map(|r| sr.gen_result_entry(r)).collect();
 
-            admin_info!(
-                nentries = %lres.len(),
-                "LDAP Search Success -> number of entries"
-            );
+        admin_info!(
+            nentries = %lres.len(),
+            remaining = %paged_search.uuids.len(),
+            "LDAP Paged Search Success -> number of entries"
+        );
 
-            Ok(lres)
+        if size == 0 || paged_search.uuids.is_empty() {
+            // The search is complete.
+            lres.push(gen_paged_success(sr, 0, Vec::with_capacity(0)));
+        } else {
+            let remaining = i64::try_from(paged_search.uuids.len()).unwrap_or(i64::MAX);
+            lres.push(gen_paged_success(sr, remaining, cookie.clone()));
+
+            paged_search.expiry = ct + LDAP_PAGED_SEARCH_TIMEOUT;
+            self.paged_searches
+                .lock()
+                .await
+                .insert(cookie, paged_search);
+        }
+
+        Ok(lres)
+    }
+
+    /// Process a search with the content synchronisation control (RFC 4533). The cookie
+    /// given to the client is the replication update vector of this server, which allows
+    /// the entries changed since the previous search to be found on the next request.
+    ///
+    /// Only the refresh stage is provided, using the present phase so that entries the
+    /// client should remove are implied by their absence. Requests for refreshAndPersist
+    /// are refused so that clients don't silently lose the persist stage.
+    #[instrument(level = "debug", skip_all)]
+    async fn do_sync_search(
+        &self,
+        idms: &IdmServer,
+        sr: &SearchRequest,
+        uat: &LdapBoundToken,
+        source: Source,
+        mode: &SyncRequestMode,
+        cookie: Option<&[u8]>,
+    ) -> Result<Vec<LdapMsg>, OperationError> {
+        admin_info!(?mode, "Attempt LDAP Sync Search for {}", uat.spn);
+
+        if *mode == SyncRequestMode::RefreshAndPersist {
+            request_error!("LDAP Sync Search failure - refreshAndPersist is not supported");
+            return Ok(vec![sr.gen_error(
+                LdapResultCode::UnwillingToPerform,
+                "refreshAndPersist is not supported, use refreshOnly".to_string(),
+            )]);
+        }
+
+        let client_ruv = cookie
+            .map(|cookie| {
+                serde_json::from_slice::<ReplRuvRange>(cookie).map_err(|e| {
+                    request_error!(?e, "LDAP Sync Search failure - invalid cookie");
+                    OperationError::InvalidRequestState
+                })
+            })
+            .transpose()?;
+
+        let Some(plan) = self.plan_search(sr)? else {
+            return Ok(vec![gen_sync_done(sr, None)]);
+        };
+
+        let ct = duration_from_epoch_now();
+        let mut idm_read = idms.proxy_read().await?;
+
+        let ident = idm_read
+            .validate_ldap_session(&uat.effective_session, source, ct)
+            .map_err(|e| {
+                admin_error!("Invalid identity: {:?}", e);
+                e
+            })?;
+
+        // The cookie is taken from the same transaction as the search, so that no change
+        // can be missed between this search and the next.
+        let server_ruv = idm_read.qs_read.consumer_get_state()?;
+        let next_cookie = serde_json::to_vec(&server_ruv).map_err(|e| {
+            admin_error!(?e, "Unable to serialise LDAP Sync cookie");
+            OperationError::SerdeJsonError
+        })?;
+
+        // If there is no cookie this is the initial content, and every entry is sent.
+        let changed = if let Some(client_ruv) = client_ruv {
+            let Some(changed) = Self::sync_changed_uuids(&mut idm_read, &ident, client_ruv)? else {
+                return Ok(vec![sr.gen_error(
+                    LdapResultCode::EsyncRefreshRequired,
+                    "Unable to resume from this cookie, a refresh is required".to_string(),
+                )]);
+            };
+            Some(changed)
+        } else {
+            None
+        };
+
+        let res = self.search_reduced(&mut idm_read, ident, &plan, None)?;
+
+        let mut present_uuids = Vec::with_capacity(0);
+        let mut lres = Vec::with_capacity(res.len() + 2);
+
+        for e in res {
+            let entry_uuid = e.get_uuid();
+
+            if changed
+                .as_ref()
+                .is_some_and(|changed| !changed.contains(&entry_uuid))
+            {
+                // The client already holds the current content of this entry.
+                present_uuids.push(entry_uuid);
+                continue;
+            }
+
+            let entry = e
+                .to_ldap(
+                    &mut idm_read.qs_read,
+                    self.basedn.as_str(),
+                    plan.all_attrs,
+                    &plan.l_attrs,
+                )
+                .map_err(|e| {
+                    admin_error!("entry resolve failure {:?}", e);
+                    e
+                })?;
+
+            let mut msg = sr.gen_result_entry(entry);
+            msg.ctrl.push(LdapControl::SyncState {
+                state: SyncStateValue::Add,
+                entry_uuid,
+                cookie: None,
+            });
+            lres.push(msg);
+        }
+
+        admin_info!(
+            nentries = %lres.len(),
+            npresent = %present_uuids.len(),
+            "LDAP Sync Search Success -> number of entries"
+        );
+
+        if !present_uuids.is_empty() {
+            lres.push(LdapMsg {
+                msgid: sr.msgid,
+                op: LdapOp::IntermediateResponse(LdapIntermediateResponse::SyncInfoIdSet {
+                    cookie: None,
+                    refresh_deletes: false,
+                    syncuuids: present_uuids,
+                }),
+                ctrl: vec![],
+            });
+        }
+
+        lres.push(gen_sync_done(sr, Some(next_cookie)));
+
+        Ok(lres)
+    }
+
+    /// Determine the set of entries that have changed since the RUV that was given to the
+    /// client in its sync cookie. If the changes can not be determined then `None` is
+    /// returned, and the client must refresh its content.
+    fn sync_changed_uuids(
+        idm_read: &mut IdmServerProxyReadTransaction<'_>,
+        ident: &Identity,
+        client_ruv: ReplRuvRange,
+    ) -> Result<Option<BTreeSet<Uuid>>, OperationError> {
+        let ReplRuvRange::V1 {
+            domain_uuid,
+            ranges: client_ranges,
+        } = client_ruv;
+
+        if domain_uuid != idm_read.qs_read.get_domain_uuid() {
+            request_warn!("LDAP Sync cookie was issued by a different domain");
+            return Ok(None);
+        }
+
+        let trim_cid = idm_read.qs_read.trim_cid().clone();
+        let server_ranges = idm_read
+            .qs_read
+            .get_be_txn()
+            .get_ruv()
+            .filter_ruv_range(&trim_cid)?;
+
+        let ranges = match ReplicationUpdateVector::range_diff(&client_ranges, &server_ranges) {
+            RangeDiffStatus::Ok(ranges) => ranges,
+            status => {
+                request_warn!(?status, "LDAP Sync cookie does not overlap the current RUV");
+                return Ok(None);
+            }
+        };
+
+        let entries = idm_read
+            .qs_read
+            .get_be_txn()
+            .retrieve_range(&ranges)
+            .map_err(|e| {
+                admin_error!(?e, "backend failure");
+                OperationError::Backend
+            })?;
+
+        // Access controls, schema, the domain (which defines the basedn) and the groups of
+        // the bound identity all affect how entries are presented. If these changed then
+        // entries that were not themselves modified may now differ to the client's copy.
+        let ident_uuid = ident.get_uuid();
+        let requires_refresh = entries.iter().any(|e| {
+            Some(e.get_uuid()) == ident_uuid
+                || [
+                    EntryClass::AccessControlProfile,
+                    EntryClass::AttributeType,
+                    EntryClass::ClassType,
+                    EntryClass::DomainInfo,
+                ]
+                .into_iter()
+                .any(|class| e.attribute_equality(Attribute::Class, &class.into()))
+        });
+
+        if requires_refresh {
+            request_info!("LDAP Sync changes affect content presentation, refresh required");
+            return Ok(None);
+        }
+
+        Ok(Some(entries.iter().map(|e| e.get_uuid()).collect()))
+    }
+
+    async fn do_search_with_controls(
+        &self,
+        idms: &IdmServer,
+        sr: &SearchRequest,
+        uat: &LdapBoundToken,
+        source: Source,
+        controls: &[LdapControl],
+    ) -> Result<Vec<LdapMsg>, OperationError> {
+        let sync = controls.iter().find_map(|ctrl| match ctrl {
+            LdapControl::SyncRequest { mode, cookie, .. } => Some((mode, cookie.as_deref())),
+            _ => None,
+        });
+
+        if let Some((mode, cookie)) = sync {
+            return self
+                .do_sync_search(idms, sr, uat, source, mode, cookie)
+                .await;
+        }
+
+        let paged = controls.iter().find_map(|ctrl| match ctrl {
+            LdapControl::SimplePagedResults { size, cookie } => Some((*size, cookie.as_slice())),
+            _ => None,
+        });
+
+        match paged {
+            Some((size, cookie)) => {
+                self.do_paged_search(idms, sr, uat, source, size, cookie)
+                    .await
+            }
+            None => self.do_search(idms, sr, uat, source).await,
         }
     }
 
@@ -485,6 +1232,59 @@ impl LdapServer {
         Ok(result)
     }
 
+    /// Bind with the SASL EXTERNAL mechanism, where the account is identified by the client
+    /// certificate that was presented during TLS setup. The certificate must be enrolled
+    /// to the account, as with client certificate authentication to the https interface.
+    pub async fn do_sasl_bind(
+        &self,
+        idms: &IdmServer,
+        sasl_bind: LdapSaslBindRequest,
+        client_cert: Option<&ClientCertInfo>,
+    ) -> Result<LdapResponseState, OperationError> {
+        security_info!("Attempt LDAP SASL Bind with {}", sasl_bind.mechanism);
+
+        if sasl_bind.mechanism != LDAP_SASL_EXTERNAL {
+            return Ok(LdapResponseState::Respond(sasl_bind.gen_error(
+                LdapResultCode::AuthMethodNotSupported,
+                format!("SASL mechanism {} is not supported", sasl_bind.mechanism),
+            )));
+        }
+
+        // The identity is always the owner of the certificate, we don't allow the client
+        // to request authorisation as some other identity.
+        if !sasl_bind.credentials.is_empty() {
+            return Ok(LdapResponseState::Respond(sasl_bind.gen_error(
+                LdapResultCode::InvalidCredentials,
+                "SASL EXTERNAL authorisation identities are not supported".to_string(),
+            )));
+        }
+
+        let Some(client_cert) = client_cert else {
+            return Ok(LdapResponseState::Respond(sasl_bind.gen_error(
+                LdapResultCode::InappropriateAuthentication,
+                "No client certificate was presented".to_string(),
+            )));
+        };
+
+        let ct = duration_from_epoch_now();
+        let mut idm_auth = idms.auth().await?;
+        let result = idm_auth.certificate_auth_ldap(client_cert, ct);
+        idm_auth.commit()?;
+
+        match result {
+            Ok(lbt) => {
+                security_info!("✅ LDAP SASL EXTERNAL Bind success for {}", lbt.spn);
+                let rmsg = sasl_bind.gen_success();
+                Ok(LdapResponseState::Bind(lbt, rmsg))
+            }
+            Err(e) => {
+                security_info!(?e, "❌ LDAP SASL EXTERNAL Bind failure");
+                let (rc, msg) = operationerr_to_ldapresultcode(e);
+                Ok(LdapResponseState::Respond(sasl_bind.gen_error(rc, msg)))
+            }
+        }
+    }
+
     #[instrument(level = "debug", skip_all)]
     async fn do_compare(
         &self,
@@ -606,6 +1406,7 @@ impl LdapServer {
         &self,
         idms: &IdmServer,
         server_op: ServerOps,
+        controls: &[LdapControl],
         uat: Option<LdapBoundToken>,
         ip_addr: IpAddr,
         eventid: Uuid,
@@ -626,7 +1427,7 @@ impl LdapServer {
                 }),
             ServerOps::Search(sr) => match uat {
                 Some(u) => self
-                    .do_search(idms, &sr, &u, source)
+                    .do_search_with_controls(idms, &sr, &u, source, controls)
                     .await
                     .map(LdapResponseState::MultiPartResponse)
                     .or_else(|e| {
@@ -649,7 +1450,7 @@ impl LdapServer {
                         }
                     };
                     // If okay, do the search.
-                    self.do_search(idms, &sr, &lbt, Source::Internal)
+                    self.do_search_with_controls(idms, &sr, &lbt, Source::Internal, controls)
                         .await
                         .map(|r| LdapResponseState::BindMultiPartResponse(lbt, r))
                         .or_else(|e| {
@@ -707,6 +1508,295 @@ impl LdapServer {
         } // end match server op
     }
 
+    pub async fn do_write_op(
+        &self,
+        idms: &IdmServer,
+        write_op: LdapWriteOps,
+        uat: Option<LdapBoundToken>,
+        ip_addr: IpAddr,
+    ) -> Result<LdapResponseState, OperationError> {
+        let source = Source::Ldaps(ip_addr);
+
+        // Unlike search and compare, writes never imply an anonymous bind.
+        let Some(uat) = uat else {
+            return Ok(LdapResponseState::Respond(write_op.gen_error(
+                LdapResultCode::InsufficentAccessRights,
+                "Unbound Connection".to_string(),
+            )));
+        };
+
+        let res = match &write_op {
+            LdapWriteOps::Modify(_, mr) => self.do_modify(idms, mr, &uat, source).await,
+            LdapWriteOps::Add(_, ar) => self.do_add(idms, ar, &uat, source).await,
+            LdapWriteOps::Delete(_, dn) => self.do_delete(idms, dn.as_str(), &uat, source).await,
+            LdapWriteOps::PasswordModify(_, pmr) => {
+                self.do_password_modify(idms, pmr, &uat, source).await
+            }
+        };
+
+        Ok(LdapResponseState::Respond(match res {
+            Ok(()) => write_op.gen_success(),
+            Err(e) => {
+                let (rc, msg) = operationerr_to_ldapresultcode(e);
+                write_op.gen_error(rc, msg)
+            }
+        }))
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn do_modify(
+        &self,
+        idms: &IdmServer,
+        mr: &LdapModifyRequest,
+        uat: &LdapBoundToken,
+        source: Source,
+    ) -> Result<(), OperationError> {
+        admin_info!("Attempt LDAP ModifyRequest for {}", uat.spn);
+
+        let (_, rdn_val) = self.write_target_rdn(mr.dn.as_str())?;
+
+        let ct = duration_from_epoch_now();
+        let mut idms_prox_write = idms.proxy_write(ct).await?;
+
+        let ident = idms_prox_write
+            .validate_ldap_session(&uat.effective_session, source, ct)
+            .map_err(|e| {
+                admin_error!("Invalid identity: {:?}", e);
+                e
+            })?;
+
+        let target_uuid = idms_prox_write.qs_write.name_to_uuid(rdn_val.as_str())?;
+
+        let mods = mr
+            .changes
+            .iter()
+            .map(|change| ldap_modify_to_mods(&mut idms_prox_write.qs_write, change))
+            .collect::<Result<Vec<_>, _>>()?
+            .into_iter()
+            .flatten()
+            .collect();
+
+        let modlist = ModifyList::new_list(mods);
+        let filter = filter_all!(f_eq(Attribute::Uuid, PartialValue::Uuid(target_uuid)));
+
+        let me =
+            ModifyEvent::from_internal_parts(ident, &modlist, &filter, &idms_prox_write.qs_write)?;
+
+        idms_prox_write
+            .qs_write
+            .modify(&me)
+            .and_then(|_| idms_prox_write.commit())
+            .inspect(|_| admin_info!("LDAP Modify Success"))
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn do_add(
+        &self,
+        idms: &IdmServer,
+        ar: &LdapAddRequest,
+        uat: &LdapBoundToken,
+        source: Source,
+    ) -> Result<(), OperationError> {
+        admin_info!("Attempt LDAP AddRequest for {}", uat.spn);
+
+        let (rdn_attr, rdn_val) = self.write_target_rdn(ar.dn.as_str())?;
+
+        let mut pe = ProtoEntry {
+            attrs: BTreeMap::new(),
+        };
+
+        for attr in ar.attributes.iter() {
+            let kani_attr = ldap_attr_write_map(attr.atype.as_str())?;
+            let vals = ldap_vals_to_strings(attr.atype.as_str(), &attr.vals)?;
+            let is_class = kani_attr == Attribute::Class;
+
+            pe.attrs.entry(kani_attr.to_string()).or_default().extend(
+                vals.into_iter()
+                    // There is no "top" class in kanidm, every entry is an object.
+                    .filter(|v| !(is_class && v.eq_ignore_ascii_case("top"))),
+            );
+        }
+
+        // The rdn must always be present in the entry. Since spn is generated from the
+        // name of the entry, we set the name instead.
+        let (rdn_attr, rdn_val) = match ldap_attr_write_map(rdn_attr.as_str())? {
+            Attribute::Spn => (
+                Attribute::Name,
+                rdn_val
+                    .split_once('@')
+                    .map(|(name, _)| name.to_string())
+                    .unwrap_or(rdn_val),
+            ),
+            a => (a, rdn_val),
+        };
+
+        let rdn_vals = pe.attrs.entry(rdn_attr.to_string()).or_default();
+        if !rdn_vals.contains(&rdn_val) {
+            rdn_vals.push(rdn_val);
+        }
+
+        let ct = duration_from_epoch_now();
+        let mut idms_prox_write = idms.proxy_write(ct).await?;
+
+        let ident = idms_prox_write
+            .validate_ldap_session(&uat.effective_session, source, ct)
+            .map_err(|e| {
+                admin_error!("Invalid identity: {:?}", e);
+                e
+            })?;
+
+        let entry = Entry::from_proto_entry(&pe, &mut idms_prox_write.qs_write)?;
+        let ce = CreateEvent::new_impersonate_identity(ident, vec![entry]);
+
+        idms_prox_write
+            .qs_write
+            .create(&ce)
+            .and_then(|_| idms_prox_write.commit())
+            .inspect(|_| admin_info!("LDAP Add Success"))
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn do_delete(
+        &self,
+        idms: &IdmServer,
+        dn: &str,
+        uat: &LdapBoundToken,
+        source: Source,
+    ) -> Result<(), OperationError> {
+        admin_info!("Attempt LDAP DelRequest for {}", uat.spn);
+
+        let (_, rdn_val) = self.write_target_rdn(dn)?;
+
+        let ct = duration_from_epoch_now();
+        let mut idms_prox_write = idms.proxy_write(ct).await?;
+
+        let ident = idms_prox_write
+            .validate_ldap_session(&uat.effective_session, source, ct)
+            .map_err(|e| {
+                admin_error!("Invalid identity: {:?}", e);
+                e
+            })?;
+
+        let target_uuid = idms_prox_write.qs_write.name_to_uuid(rdn_val.as_str())?;
+        let filter = filter_all!(f_eq(Attribute::Uuid, PartialValue::Uuid(target_uuid)));
+
+        let de = DeleteEvent::from_parts(ident, &filter, &mut idms_prox_write.qs_write)?;
+
+        idms_prox_write
+            .qs_write
+            .delete(&de)
+            .and_then(|_| idms_prox_write.commit())
+            .inspect(|_| admin_info!("LDAP Delete Success"))
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn do_password_modify(
+        &self,
+        idms: &IdmServer,
+        pmr: &LdapPasswordModifyRequest,
+        uat: &LdapBoundToken,
+        source: Source,
+    ) -> Result<(), OperationError> {
+        admin_info!("Attempt LDAP PasswordModify for {}", uat.spn);
+
+        // We never generate passwords on behalf of the client.
+        let Some(new_password) = pmr.new_password.as_ref() else {
+            request_error!("LDAP PasswordModify failure - no new password was provided");
+            return Err(OperationError::InvalidRequestState);
+        };
+
+        let ct = duration_from_epoch_now();
+
+        // A unix password bind only grants anonymous rights. If the client provides their
+        // current password we re-verify it, which allows the account to change its own
+        // password the same way that passwd(1) would.
+        let reauth_uuid = match (&uat.effective_session, pmr.old_password.as_ref()) {
+            (LdapSession::UnixBind(uuid), Some(old_password)) if *uuid != UUID_ANONYMOUS => {
+                let lae = LdapAuthEvent::from_parts(*uuid, old_password.clone())?;
+                let mut idm_auth = idms.auth().await?;
+                let result = idm_auth.auth_ldap(&lae, ct).await?;
+                idm_auth.commit()?;
+
+                if result.is_none() {
+                    security_info!("LDAP PasswordModify failure - old password is incorrect");
+                    return Err(OperationError::NotAuthenticated);
+                }
+                Some(*uuid)
+            }
+            _ => None,
+        };
+
+        // The user identity may be a dn or a plain name, as RFC 3062 does not require a dn.
+        let user_identity = match pmr.user_identity.as_ref() {
+            Some(user_identity) if user_identity.contains('=') => {
+                let (_, rdn_val) = self.write_target_rdn(user_identity.as_str())?;
+                Some(rdn_val)
+            }
+            Some(user_identity) => Some(user_identity.clone()),
+            None => None,
+        };
+
+        let mut idms_prox_write = idms.proxy_write(ct).await?;
+
+        let target_uuid = match user_identity {
+            Some(user_identity) => idms_prox_write
+                .qs_write
+                .name_to_uuid(user_identity.as_str())?,
+            None => match &uat.effective_session {
+                LdapSession::UnixBind(uuid)
+                | LdapSession::ApplicationPasswordBind(_, uuid)
+                | LdapSession::ClientCertificate(_, uuid) => *uuid,
+                LdapSession::UserAuthToken(uat) => uat.uuid,
+                LdapSession::ApiToken(apit) => apit.account_id,
+            },
+        };
+
+        let ident = match reauth_uuid {
+            // Re-verifying the old password only allows changing our own password.
+            Some(uuid) if uuid == target_uuid => idms_prox_write
+                .validate_ldap_reauth(&uuid, source, ct)
+                .map_err(|e| {
+                    admin_error!("Invalid identity: {:?}", e);
+                    e
+                })?,
+            _ => idms_prox_write
+                .validate_ldap_session(&uat.effective_session, source, ct)
+                .map_err(|e| {
+                    admin_error!("Invalid identity: {:?}", e);
+                    e
+                })?,
+        };
+
+        let pce = UnixPasswordChangeEvent::from_parts(ident, target_uuid, new_password.clone())?;
+
+        idms_prox_write
+            .set_unix_account_password(&pce)
+            .and_then(|_| idms_prox_write.commit())
+            .inspect(|_| security_info!("LDAP PasswordModify Success"))
+    }
+
+    /// Parse the rdn of an entry that is the target of a write. Unlike searches, writes
+    /// must always name a single entry beneath the basedn.
+    fn write_target_rdn(&self, dn: &str) -> Result<(String, String), OperationError> {
+        let Some(caps) = self.dnre.captures(dn) else {
+            request_error!("LDAP Write failure - invalid basedn");
+            return Err(OperationError::InvalidRequestState);
+        };
+
+        if caps.name("app").is_some() {
+            request_error!("LDAP Write failure - application entries can not be written");
+            return Err(OperationError::InvalidRequestState);
+        }
+
+        match (caps.name("attr"), caps.name("val")) {
+            (Some(a), Some(v)) => Ok((a.as_str().to_string(), v.as_str().to_string())),
+            _ => {
+                request_error!("LDAP Write failure - invalid rdn");
+                Err(OperationError::InvalidRequestState)
+            }
+        }
+    }
+
     async fn bind_target_from_bind_dn(
         &self,
         idm_auth: &mut IdmServerAuthTransaction<'_>,
@@ -766,6 +1856,22 @@ impl LdapServer {
     }
 }
 
+fn gen_paged_success(sr: &SearchRequest, size: i64, cookie: Vec<u8>) -> LdapMsg {
+    let mut msg = sr.gen_success();
+    msg.ctrl
+        .push(LdapControl::SimplePagedResults { size, cookie });
+    msg
+}
+
+fn gen_sync_done(sr: &SearchRequest, cookie: Option<Vec<u8>>) -> LdapMsg {
+    let mut msg = sr.gen_success();
+    msg.ctrl.push(LdapControl::SyncDone {
+        cookie,
+        refresh_deletes: false,
+    });
+    msg
+}
+
 fn ldap_domain_to_dc(input: &str) -> String {
     let mut output: String = String::new();
     input.split('.').for_each(|dc| {
@@ -779,8 +1885,56 @@ fn ldap_domain_to_dc(input: &str) -> String {
     output
 }
 
+fn ldap_vals_to_strings(atype: &str, vals: &[Vec<u8>]) -> Result<Vec<String>, OperationError> {
+    vals.iter()
+        .map(|v| {
+            String::from_utf8(v.clone()).map_err(|_| {
+                request_error!(?atype, "LDAP Write failure - value is not valid utf8");
+                OperationError::InvalidAttribute(atype.to_string())
+            })
+        })
+        .collect()
+}
+
+fn ldap_modify_to_mods(
+    qs: &mut QueryServerWriteTransaction,
+    change: &LdapModify,
+) -> Result<Vec<Modify>, OperationError> {
+    let atype = change.modification.atype.as_str();
+    let attr = ldap_attr_write_map(atype)?;
+    let vals = ldap_vals_to_strings(atype, &change.modification.vals)?;
+
+    match change.operation {
+        LdapModifyType::Add => vals
+            .iter()
+            .map(|v| {
+                qs.clone_value(&attr, v)
+                    .map(|v| Modify::Present(attr.clone(), v))
+            })
+            .collect(),
+        // Per rfc4511 a delete with no values removes the attribute.
+        LdapModifyType::Delete if vals.is_empty() => Ok(vec![Modify::Purged(attr)]),
+        LdapModifyType::Delete => vals
+            .iter()
+            .map(|v| {
+                qs.clone_partialvalue(&attr, v)
+                    .map(|pv| Modify::Removed(attr.clone(), pv))
+            })
+            .collect(),
+        LdapModifyType::Replace => iter::once(Ok(Modify::Purged(attr.clone())))
+            .chain(vals.iter().map(|v| {
+                qs.clone_value(&attr, v)
+                    .map(|v| Modify::Present(attr.clone(), v))
+            }))
+            .collect(),
+    }
+}
+
 fn operationerr_to_ldapresultcode(e: OperationError) -> (LdapResultCode, String) {
     match e {
+        OperationError::AccessDenied => (LdapResultCode::InsufficentAccessRights, "".to_string()),
+        OperationError::NoMatchingEntries => (LdapResultCode::NoSuchObject, "".to_string()),
+        OperationError::NotAuthenticated => (LdapResultCode::InvalidCredentials, "".to_string()),
         OperationError::InvalidRequestState => {
             (LdapResultCode::ConstraintViolation, "".to_string())
         }
@@ -857,27 +2011,62 @@ pub(crate) fn ldap_attr_filter_map(input: &str) -> Attribute {
     Attribute::from(ldap_vattr_map(&a_lower).unwrap_or(a_lower.as_str()))
 }
 
-#[cfg(test)]
-mod tests {
-    use crate::prelude::*;
-
+/// Map an ldap attribute name that is the target of a write to the kanidm attribute. Virtual
+/// attributes that are derived from other values can not be written.
+#[inline]
+pub(crate) fn ldap_attr_write_map(input: &str) -> Result<Attribute, OperationError> {
+    let a_lower = input.to_lowercase();
+    match a_lower.as_str() {
+        LDAP_ATTR_DN
+        | LDAP_ATTR_ENTRYDN
+        | LDAP_ATTR_ENTRYUUID
+        | ATTR_HOME_DIRECTORY
+        | LDAP_ATTR_MAIL_PRIMARY
+        | LDAP_ATTR_MAIL_ALTERNATIVE
+        | LDAP_ATTR_EMAIL_PRIMARY
+        | LDAP_ATTR_EMAIL_ALTERNATIVE => {
+            request_error!(attr = ?a_lower, "LDAP Write failure - attribute is not writeable");
+            Err(OperationError::InvalidAttribute(a_lower))
+        }
+        _ => Ok(ldap_attr_filter_map(a_lower.as_str())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::prelude::*;
+
     use compact_jwt::{dangernoverify::JwsDangerReleaseWithoutVerify, JwsVerifier};
+    use crypto_glue::{
+        traits::DecodePem,
+        x509::{x509_digest_public_key_sha256, Certificate},
+    };
     use hashbrown::HashSet;
     use kanidm_proto::internal::ApiToken;
+    use ldap3_proto::control::LdapControl;
     use ldap3_proto::proto::{
-        LdapFilter, LdapMsg, LdapOp, LdapResultCode, LdapSearchScope, LdapSubstringFilter,
+        LdapBindCred, LdapBindRequest, LdapDerefAliases, LdapExtendedRequest, LdapExtendedResponse,
+        LdapFilter, LdapIntermediateResponse, LdapModify, LdapModifyRequest, LdapModifyType,
+        LdapMsg, LdapOp, LdapPasswordModifyRequest, LdapResultCode, LdapSearchRequest,
+        LdapSearchScope, LdapSubstringFilter, SyncRequestMode, SyncStateValue,
     };
     use ldap3_proto::simple::*;
+    use std::collections::{BTreeMap, BTreeSet};
 
-    use super::{LdapServer, LdapSession};
+    use super::{
+        LdapResponseState, LdapSaslBindRequest, LdapServer, LdapSession, LdapStartTlsOp,
+        LdapWriteOps, LDAP_EXOP_STARTTLS,
+    };
     use crate::idm::application::GenerateApplicationPasswordEvent;
+    use crate::idm::delayed::DelayedAction;
     use crate::idm::event::{LdapApplicationAuthEvent, UnixPasswordChangeEvent};
     use crate::idm::serviceaccount::GenerateApiTokenEvent;
+    use crate::repl::proto::ReplRuvRange;
 
     const TEST_PASSWORD: &str = "ntaoeuntnaoeuhraohuercahu😍";
 
     #[idm_test]
-    async fn test_ldap_simple_bind(idms: &IdmServer, _idms_delayed: &IdmServerDelayed) {
+    async fn test_ldap_simple_bind(idms: &IdmServer, idms_delayed: &mut IdmServerDelayed) {
         let ldaps = LdapServer::new(idms).await.expect("failed to start ldap");
 
         let mut idms_prox_write = idms.proxy_write(duration_from_epoch_now()).await.unwrap();
@@ -1042,6 +2231,10 @@ mod tests {
             .unwrap()
             .is_none());
 
+        // The failure is queued to be persisted.
+        let da = idms_delayed.try_recv().expect("invalid");
+        assert!(matches!(da, DelayedAction::CredentialSoftLockRecord(_)));
+
         // Non-existent and invalid DNs
         assert!(ldaps
             .do_bind(
@@ -2835,4 +4028,954 @@ mod tests {
         assert_eq!(invalid_res, Err(OperationError::ResourceLimit));
         assert!(valid_res.is_ok());
     }
+
+    #[idm_test]
+    async fn test_ldap_write_operations(idms: &IdmServer, idms_delayed: &mut IdmServerDelayed) {
+        let ldaps = LdapServer::new(idms).await.expect("failed to start ldap");
+
+        let sa_uuid = uuid!("cc8e95b4-c24f-4d68-ba54-8bed76f63930");
+        let acct_uuid = uuid!("dd8e95b4-c24f-4d68-ba54-8bed76f63930");
+
+        let apitoken = {
+            let e1 = entry_init!(
+                (Attribute::Class, EntryClass::Object.to_value()),
+                (Attribute::Class, EntryClass::ServiceAccount.to_value()),
+                (Attribute::Class, EntryClass::Account.to_value()),
+                (Attribute::Uuid, Value::Uuid(sa_uuid)),
+                (Attribute::Name, Value::new_iname("service_write_test")),
+                (
+                    Attribute::DisplayName,
+                    Value::new_utf8s("service_write_test")
+                )
+            );
+
+            let e2 = entry_init!(
+                (Attribute::Class, EntryClass::Object.to_value()),
+                (Attribute::Class, EntryClass::Person.to_value()),
+                (Attribute::Class, EntryClass::Account.to_value()),
+                (Attribute::Class, EntryClass::PosixAccount.to_value()),
+                (Attribute::Name, Value::new_iname("testperson1")),
+                (Attribute::Uuid, Value::Uuid(acct_uuid)),
+                (Attribute::DisplayName, Value::new_utf8s("testperson1")),
+                (Attribute::GidNumber, Value::new_uint32(12345))
+            );
+
+            let ct = duration_from_epoch_now();
+            let mut server_txn = idms.proxy_write(ct).await.unwrap();
+
+            assert!(server_txn.qs_write.internal_create(vec![e1, e2]).is_ok());
+
+            server_txn
+                .qs_write
+                .internal_modify_uuid(
+                    UUID_IDM_PEOPLE_ADMINS,
+                    &ModifyList::new_append(Attribute::Member, Value::Refer(sa_uuid)),
+                )
+                .expect("Unable to modify PEOPLE_ADMINS group");
+
+            let pce = UnixPasswordChangeEvent::new_internal(acct_uuid, TEST_PASSWORD);
+            assert!(server_txn.set_unix_account_password(&pce).is_ok());
+
+            let gte = GenerateApiTokenEvent {
+                read_write: true,
+                ..GenerateApiTokenEvent::new_internal(sa_uuid, "TestToken", None)
+            };
+
+            let apitoken = server_txn
+                .service_account_generate_api_token(&gte, ct)
+                .expect("Failed to create new apitoken");
+
+            assert!(server_txn.commit().is_ok());
+
+            apitoken
+        };
+
+        #[track_caller]
+        fn assert_write_result(r: &LdapResponseState, code: &LdapResultCode) {
+            match r {
+                LdapResponseState::Respond(LdapMsg {
+                    op: LdapOp::ModifyResponse(res),
+                    ..
+                })
+                | LdapResponseState::Respond(LdapMsg {
+                    op: LdapOp::DelResponse(res),
+                    ..
+                })
+                | LdapResponseState::Respond(LdapMsg {
+                    op: LdapOp::ExtendedResponse(LdapExtendedResponse { res, .. }),
+                    ..
+                }) => {
+                    assert_eq!(&res.code, code);
+                }
+                _ => panic!("Oh no"),
+            };
+        }
+
+        let modify_mail = LdapWriteOps::Modify(
+            1,
+            LdapModifyRequest {
+                dn: "spn=testperson1@example.com,dc=example,dc=com".to_string(),
+                changes: vec![LdapModify {
+                    operation: LdapModifyType::Replace,
+                    modification: LdapPartialAttribute {
+                        atype: LDAP_ATTR_MAIL.to_string(),
+                        vals: vec!["testperson1@example.com".as_bytes().to_vec()],
+                    },
+                }],
+            },
+        );
+
+        // Writes are never implied to be anonymous.
+        assert_write_result(
+            &ldaps
+                .do_write_op(
+                    idms,
+                    modify_mail.clone(),
+                    None,
+                    "127.0.0.1".parse().unwrap(),
+                )
+                .await
+                .unwrap(),
+            &LdapResultCode::InsufficentAccessRights,
+        );
+
+        // Anonymous is read only, so can't write.
+        let anon_t = ldaps.do_bind(idms, "", "").await.unwrap().unwrap();
+        assert_write_result(
+            &ldaps
+                .do_write_op(
+                    idms,
+                    modify_mail.clone(),
+                    Some(anon_t),
+                    "127.0.0.1".parse().unwrap(),
+                )
+                .await
+                .unwrap(),
+            &LdapResultCode::InsufficentAccessRights,
+        );
+
+        // A read-write api token is subject to the same access controls as https.
+        let sa_lbt = ldaps
+            .do_bind(idms, "dn=token", &apitoken.to_string())
+            .await
+            .unwrap()
+            .unwrap();
+
+        assert_write_result(
+            &ldaps
+                .do_write_op(
+                    idms,
+                    modify_mail,
+                    Some(sa_lbt.clone()),
+                    "127.0.0.1".parse().unwrap(),
+                )
+                .await
+                .unwrap(),
+            &LdapResultCode::Success,
+        );
+
+        {
+            let mut idms_prox_read = idms.proxy_read().await.unwrap();
+            let entry = idms_prox_read
+                .qs_read
+                .internal_search_uuid(acct_uuid)
+                .expect("Unable to find account");
+            assert_eq!(
+                entry.get_ava_mail_primary(Attribute::Mail),
+                Some("testperson1@example.com")
+            );
+        }
+
+        // Virtual attributes can't be written.
+        let modify_entrydn = LdapWriteOps::Modify(
+            2,
+            LdapModifyRequest {
+                dn: "name=testperson1,dc=example,dc=com".to_string(),
+                changes: vec![LdapModify {
+                    operation: LdapModifyType::Replace,
+                    modification: LdapPartialAttribute {
+                        atype: LDAP_ATTR_ENTRYDN.to_string(),
+                        vals: vec!["name=other,dc=example,dc=com".as_bytes().to_vec()],
+                    },
+                }],
+            },
+        );
+        assert_write_result(
+            &ldaps
+                .do_write_op(
+                    idms,
+                    modify_entrydn,
+                    Some(sa_lbt.clone()),
+                    "127.0.0.1".parse().unwrap(),
+                )
+                .await
+                .unwrap(),
+            &LdapResultCode::InvalidAttributeSyntax,
+        );
+
+        // The account can change its own password by supplying the old one.
+        let person_lbt = ldaps
+            .do_bind(idms, "testperson1", TEST_PASSWORD)
+            .await
+            .unwrap()
+            .unwrap();
+
+        let new_password = "ieghaiqu9ooRahghoh1phe2ie😍";
+
+        let pw_modify = LdapWriteOps::PasswordModify(
+            3,
+            LdapPasswordModifyRequest {
+                user_identity: None,
+                old_password: Some(TEST_PASSWORD.to_string()),
+                new_password: Some(new_password.to_string()),
+            },
+        );
+        assert_write_result(
+            &ldaps
+                .do_write_op(
+                    idms,
+                    pw_modify,
+                    Some(person_lbt.clone()),
+                    "127.0.0.1".parse().unwrap(),
+                )
+                .await
+                .unwrap(),
+            &LdapResultCode::Success,
+        );
+
+        assert!(ldaps
+            .do_bind(idms, "testperson1", new_password)
+            .await
+            .unwrap()
+            .is_some());
+
+        // The user identity may also be given as a dn.
+        let dn_password = "Oov3ahshoh9eiM8aiquoo0ee😍";
+
+        let dn_pw_modify = LdapWriteOps::PasswordModify(
+            3,
+            LdapPasswordModifyRequest {
+                user_identity: Some("name=testperson1,dc=example,dc=com".to_string()),
+                old_password: Some(new_password.to_string()),
+                new_password: Some(dn_password.to_string()),
+            },
+        );
+        assert_write_result(
+            &ldaps
+                .do_write_op(
+                    idms,
+                    dn_pw_modify,
+                    Some(person_lbt.clone()),
+                    "127.0.0.1".parse().unwrap(),
+                )
+                .await
+                .unwrap(),
+            &LdapResultCode::Success,
+        );
+
+        assert!(ldaps
+            .do_bind(idms, "testperson1", dn_password)
+            .await
+            .unwrap()
+            .is_some());
+
+        // An incorrect old password is rejected.
+        let bad_pw_modify = LdapWriteOps::PasswordModify(
+            4,
+            LdapPasswordModifyRequest {
+                user_identity: None,
+                old_password: Some(TEST_PASSWORD.to_string()),
+                new_password: Some("ahng0Eiz6iesh3ohs7aiV😍".to_string()),
+            },
+        );
+        assert_write_result(
+            &ldaps
+                .do_write_op(
+                    idms,
+                    bad_pw_modify,
+                    Some(person_lbt),
+                    "127.0.0.1".parse().unwrap(),
+                )
+                .await
+                .unwrap(),
+            &LdapResultCode::InvalidCredentials,
+        );
+
+        let da = idms_delayed.try_recv().expect("invalid");
+        assert!(matches!(da, DelayedAction::CredentialSoftLockRecord(_)));
+
+        // Finally, delete the entry.
+        let delete = LdapWriteOps::Delete(5, "name=testperson1,dc=example,dc=com".to_string());
+        assert_write_result(
+            &ldaps
+                .do_write_op(idms, delete, Some(sa_lbt), "127.0.0.1".parse().unwrap())
+                .await
+                .unwrap(),
+            &LdapResultCode::Success,
+        );
+
+        let mut idms_prox_read = idms.proxy_read().await.unwrap();
+        assert!(idms_prox_read
+            .qs_read
+            .internal_search_uuid(acct_uuid)
+            .is_err());
+    }
+
+    #[idm_test]
+    async fn test_ldap_paged_search(idms: &IdmServer, _idms_delayed: &IdmServerDelayed) {
+        let ldaps = LdapServer::new(idms).await.expect("failed to start ldap");
+
+        {
+            let entries: Vec<_> = (0..5)
+                .map(|i| {
+                    let name = format!("testperson{i}");
+                    entry_init!(
+                        (Attribute::Class, EntryClass::Object.to_value()),
+                        (Attribute::Class, EntryClass::Account.to_value()),
+                        (Attribute::Class, EntryClass::PosixAccount.to_value()),
+                        (Attribute::Class, EntryClass::Person.to_value()),
+                        (Attribute::Name, Value::new_iname(&name)),
+                        (Attribute::DisplayName, Value::new_utf8s(&name))
+                    )
+                })
+                .collect();
+
+            let ct = duration_from_epoch_now();
+            let mut server_txn = idms.proxy_write(ct).await.unwrap();
+
+            // Add anonymous to the needed permission groups.
+            server_txn
+                .qs_write
+                .internal_modify_uuid(
+                    UUID_IDM_UNIX_AUTHENTICATION_READ,
+                    &ModifyList::new_append(Attribute::Member, Value::Refer(UUID_ANONYMOUS)),
+                )
+                .expect("Unable to modify UNIX_AUTHENTICATION_READ group");
+
+            assert!(server_txn
+                .qs_write
+                .internal_create(entries)
+                .and_then(|_| server_txn.commit())
+                .is_ok());
+        }
+
+        let anon_t = ldaps.do_bind(idms, "", "").await.unwrap().unwrap();
+
+        let sr = SearchRequest {
+            msgid: 1,
+            base: "dc=example,dc=com".to_string(),
+            scope: LdapSearchScope::Subtree,
+            filter: LdapFilter::Equality(
+                Attribute::Class.to_string(),
+                EntryClass::PosixAccount.to_string(),
+            ),
+            attrs: vec![LDAP_ATTR_NAME.to_string()],
+        };
+
+        fn paged_cookie(r: &[LdapMsg]) -> Vec<u8> {
+            match r.last().and_then(|msg| msg.ctrl.first()) {
+                Some(LdapControl::SimplePagedResults { cookie, .. }) => cookie.clone(),
+                _ => panic!("Oh no"),
+            }
+        }
+
+        let mut cookie = Vec::new();
+        let mut dns = BTreeSet::new();
+        let mut pages = 0;
+        let mut deleted = None;
+
+        loop {
+            let r = ldaps
+                .do_search_with_controls(
+                    idms,
+                    &sr,
+                    &anon_t,
+                    Source::Internal,
+                    &[LdapControl::SimplePagedResults {
+                        size: 2,
+                        cookie: cookie.clone(),
+                    }],
+                )
+                .await
+                .unwrap();
+
+            pages += 1;
+            let page_entries: Vec<_> = r
+                .iter()
+                .filter_map(|msg| match &msg.op {
+                    LdapOp::SearchResultEntry(lsre) => Some(lsre.dn.clone()),
+                    _ => None,
+                })
+                .collect();
+            assert!(page_entries.len() <= 2);
+            dns.extend(page_entries);
+
+            let next_cookie = paged_cookie(&r);
+            if next_cookie.is_empty() {
+                break;
+            }
+
+            // An entry deleted between pages is skipped, rather than returned stale.
+            if pages == 1 {
+                let name = (0..5)
+                    .map(|i| format!("testperson{i}"))
+                    .find(|name| !dns.iter().any(|dn| dn.contains(&format!("{name}@"))))
+                    .expect("No remaining entry");
+
+                let ct = duration_from_epoch_now();
+                let mut server_txn = idms.proxy_write(ct).await.unwrap();
+                assert!(server_txn
+                    .qs_write
+                    .internal_delete(&filter!(f_eq(
+                        Attribute::Name,
+                        PartialValue::new_iname(&name)
+                    )))
+                    .and_then(|_| server_txn.commit())
+                    .is_ok());
+                deleted = Some(name);
+            }
+
+            // A cookie can not be continued by a different session.
+            let other_t = ldaps.do_bind(idms, "", "").await.unwrap().unwrap();
+            assert_eq!(
+                ldaps
+                    .do_search_with_controls(
+                        idms,
+                        &sr,
+                        &other_t,
+                        Source::Internal,
+                        &[LdapControl::SimplePagedResults {
+                            size: 2,
+                            cookie: next_cookie.clone(),
+                        }],
+                    )
+                    .await,
+                Err(OperationError::InvalidRequestState)
+            );
+
+            cookie = next_cookie;
+        }
+
+        assert_eq!(pages, 3);
+        assert_eq!(dns.len(), 4);
+        let deleted = deleted.expect("No entry was deleted");
+        assert!(!dns.iter().any(|dn| dn.contains(&format!("{deleted}@"))));
+
+        // Once complete, the cookie is no longer valid.
+        assert_eq!(
+            ldaps
+                .do_search_with_controls(
+                    idms,
+                    &sr,
+                    &anon_t,
+                    Source::Internal,
+                    &[LdapControl::SimplePagedResults { size: 2, cookie }],
+                )
+                .await,
+            Err(OperationError::InvalidRequestState)
+        );
+
+        // The control is advertised in the rootdse
+        let rootdse_sr = SearchRequest {
+            msgid: 1,
+            base: "".to_string(),
+            scope: LdapSearchScope::Base,
+            filter: LdapFilter::Present(ATTR_OBJECTCLASS.to_string()),
+            attrs: vec!["*".to_string()],
+        };
+        let r = ldaps
+            .do_search(idms, &rootdse_sr, &anon_t, Source::Internal)
+            .await
+            .unwrap();
+        match &r[0].op {
+            LdapOp::SearchResultEntry(lsre) => {
+                assert!(lsre.attributes.iter().any(|attr| {
+                    attr.atype == "supportedcontrol"
+                        && attr
+                            .vals
+                            .contains(&"1.2.840.113556.1.4.319".as_bytes().to_vec())
+                }));
+            }
+            _ => panic!("Oh no"),
+        };
+    }
+
+    #[idm_test]
+    async fn test_ldap_sync_search(idms: &IdmServer, _idms_delayed: &IdmServerDelayed) {
+        let ldaps = LdapServer::new(idms).await.expect("failed to start ldap");
+
+        let uuids: Vec<_> = (0..3).map(|_| Uuid::new_v4()).collect();
+
+        {
+            let entries: Vec<_> = uuids
+                .iter()
+                .enumerate()
+                .map(|(i, uuid)| {
+                    let name = format!("testperson{i}");
+                    entry_init!(
+                        (Attribute::Class, EntryClass::Object.to_value()),
+                        (Attribute::Class, EntryClass::Account.to_value()),
+                        (Attribute::Class, EntryClass::PosixAccount.to_value()),
+                        (Attribute::Class, EntryClass::Person.to_value()),
+                        (Attribute::Name, Value::new_iname(&name)),
+                        (Attribute::Uuid, Value::Uuid(*uuid)),
+                        (Attribute::DisplayName, Value::new_utf8s(&name))
+                    )
+                })
+                .collect();
+
+            let ct = duration_from_epoch_now();
+            let mut server_txn = idms.proxy_write(ct).await.unwrap();
+
+            // Add anonymous to the needed permission groups.
+            server_txn
+                .qs_write
+                .internal_modify_uuid(
+                    UUID_IDM_UNIX_AUTHENTICATION_READ,
+                    &ModifyList::new_append(Attribute::Member, Value::Refer(UUID_ANONYMOUS)),
+                )
+                .expect("Unable to modify UNIX_AUTHENTICATION_READ group");
+
+            assert!(server_txn
+                .qs_write
+                .internal_create(entries)
+                .and_then(|_| server_txn.commit())
+                .is_ok());
+        }
+
+        let anon_t = ldaps.do_bind(idms, "", "").await.unwrap().unwrap();
+
+        let sr = SearchRequest {
+            msgid: 1,
+            base: "dc=example,dc=com".to_string(),
+            scope: LdapSearchScope::Subtree,
+            filter: LdapFilter::Equality(
+                Attribute::Class.to_string(),
+                EntryClass::PosixAccount.to_string(),
+            ),
+            attrs: vec![LDAP_ATTR_NAME.to_string()],
+        };
+
+        let sync_request = |cookie: Option<Vec<u8>>| {
+            vec![LdapControl::SyncRequest {
+                criticality: true,
+                mode: SyncRequestMode::RefreshOnly,
+                cookie,
+                reload_hint: false,
+            }]
+        };
+
+        fn sync_results(r: &[LdapMsg]) -> (BTreeSet<Uuid>, BTreeSet<Uuid>, Vec<u8>) {
+            let mut added = BTreeSet::new();
+            let mut present = BTreeSet::new();
+            let mut cookie = None;
+
+            for msg in r {
+                match (&msg.op, msg.ctrl.first()) {
+                    (
+                        LdapOp::SearchResultEntry(_),
+                        Some(LdapControl::SyncState {
+                            state: SyncStateValue::Add,
+                            entry_uuid,
+                            ..
+                        }),
+                    ) => {
+                        added.insert(*entry_uuid);
+                    }
+                    (
+                        LdapOp::IntermediateResponse(LdapIntermediateResponse::SyncInfoIdSet {
+                            refresh_deletes: false,
+                            syncuuids,
+                            ..
+                        }),
+                        _,
+                    ) => {
+                        present.extend(syncuuids.iter().copied());
+                    }
+                    (
+                        LdapOp::SearchResultDone(_),
+                        Some(LdapControl::SyncDone {
+                            cookie: Some(c),
+                            refresh_deletes: false,
+                        }),
+                    ) => {
+                        cookie = Some(c.clone());
+                    }
+                    _ => panic!("Oh no"),
+                }
+            }
+
+            (added, present, cookie.expect("No sync done cookie"))
+        }
+
+        // The initial content has every entry added.
+        let r = ldaps
+            .do_search_with_controls(idms, &sr, &anon_t, Source::Internal, &sync_request(None))
+            .await
+            .unwrap();
+        let (added, present, cookie) = sync_results(&r);
+        assert!(uuids.iter().all(|u| added.contains(u)));
+        assert!(present.is_empty());
+
+        // Repeating the search with no changes has every entry present.
+        let r = ldaps
+            .do_search_with_controls(
+                idms,
+                &sr,
+                &anon_t,
+                Source::Internal,
+                &sync_request(Some(cookie.clone())),
+            )
+            .await
+            .unwrap();
+        let (added, present, _) = sync_results(&r);
+        assert!(added.is_empty());
+        assert!(uuids.iter().all(|u| present.contains(u)));
+
+        // Modify one entry and delete another.
+        {
+            let ct = duration_from_epoch_now();
+            let mut server_txn = idms.proxy_write(ct).await.unwrap();
+            server_txn
+                .qs_write
+                .internal_modify_uuid(
+                    uuids[1],
+                    &ModifyList::new_purge_and_set(
+                        Attribute::DisplayName,
+                        Value::new_utf8s("changed"),
+                    ),
+                )
+                .expect("Unable to modify entry");
+            assert!(server_txn
+                .qs_write
+                .internal_delete_uuid(uuids[2])
+                .and_then(|_| server_txn.commit())
+                .is_ok());
+        }
+
+        let r = ldaps
+            .do_search_with_controls(
+                idms,
+                &sr,
+                &anon_t,
+                Source::Internal,
+                &sync_request(Some(cookie)),
+            )
+            .await
+            .unwrap();
+        let (added, present, next_cookie) = sync_results(&r);
+        // Only the modified entry is sent.
+        assert!(added.contains(&uuids[1]));
+        assert!(!added.contains(&uuids[0]));
+        assert!(present.contains(&uuids[0]));
+        assert!(!present.contains(&uuids[1]));
+        // The deleted entry is omitted so the client removes it.
+        assert!(!added.contains(&uuids[2]));
+        assert!(!present.contains(&uuids[2]));
+
+        // A cookie from another domain requires a refresh.
+        let foreign_cookie = serde_json::to_vec(&ReplRuvRange::V1 {
+            domain_uuid: Uuid::new_v4(),
+            ranges: BTreeMap::default(),
+        })
+        .unwrap();
+        let r = ldaps
+            .do_search_with_controls(
+                idms,
+                &sr,
+                &anon_t,
+                Source::Internal,
+                &sync_request(Some(foreign_cookie)),
+            )
+            .await
+            .unwrap();
+        assert_eq!(r.len(), 1);
+        match &r[0].op {
+            LdapOp::SearchResultDone(res) => {
+                assert_eq!(res.code, LdapResultCode::EsyncRefreshRequired)
+            }
+            _ => panic!("Oh no"),
+        };
+
+        // An invalid cookie is rejected.
+        assert_eq!(
+            ldaps
+                .do_search_with_controls(
+                    idms,
+                    &sr,
+                    &anon_t,
+                    Source::Internal,
+                    &sync_request(Some(next_cookie[1..].to_vec())),
+                )
+                .await,
+            Err(OperationError::InvalidRequestState)
+        );
+
+        // The persist stage is not provided, so it is refused rather than ending after
+        // the refresh.
+        let r = ldaps
+            .do_search_with_controls(
+                idms,
+                &sr,
+                &anon_t,
+                Source::Internal,
+                &[LdapControl::SyncRequest {
+                    criticality: true,
+                    mode: SyncRequestMode::RefreshAndPersist,
+                    cookie: None,
+                    reload_hint: false,
+                }],
+            )
+            .await
+            .unwrap();
+        assert_eq!(r.len(), 1);
+        match &r[0].op {
+            LdapOp::SearchResultDone(res) => {
+                assert_eq!(res.code, LdapResultCode::UnwillingToPerform)
+            }
+            _ => panic!("Oh no"),
+        };
+    }
+
+    #[idm_test]
+    async fn test_ldap_sasl_external_bind(idms: &IdmServer, _idms_delayed: &IdmServerDelayed) {
+        let ldaps = LdapServer::new(idms).await.expect("failed to start ldap");
+
+        let certificate = Certificate::from_pem(TEST_X509_CERT_DATA)
+            .expect("Unable to parse test X509 cert data");
+        let client_cert = ClientCertInfo {
+            public_key_s256: x509_digest_public_key_sha256(&certificate)
+                .expect("Unable to digest public key"),
+            certificate: certificate.clone(),
+        };
+
+        let acct_uuid = Uuid::new_v4();
+        let cert_uuid = Uuid::new_v4();
+
+        {
+            let e1 = entry_init!(
+                (Attribute::Class, EntryClass::Object.to_value()),
+                (Attribute::Class, EntryClass::Account.to_value()),
+                (Attribute::Class, EntryClass::Person.to_value()),
+                (Attribute::Name, Value::new_iname("testperson1")),
+                (Attribute::Uuid, Value::Uuid(acct_uuid)),
+                (Attribute::DisplayName, Value::new_utf8s("testperson1"))
+            );
+
+            let e2 = entry_init!(
+                (Attribute::Class, EntryClass::Object.to_value()),
+                (Attribute::Class, EntryClass::ClientCertificate.to_value()),
+                (Attribute::Uuid, Value::Uuid(cert_uuid)),
+                (Attribute::Refers, Value::Refer(acct_uuid)),
+                (
+                    Attribute::Certificate,
+                    Value::Certificate(Box::new(certificate.clone()))
+                )
+            );
+
+            let ct = duration_from_epoch_now();
+            let mut server_txn = idms.proxy_write(ct).await.unwrap();
+            assert!(server_txn
+                .qs_write
+                .internal_create(vec![e1, e2])
+                .and_then(|_| server_txn.commit())
+                .is_ok());
+        }
+
+        fn assert_bind_error(r: &LdapResponseState, code: &LdapResultCode) {
+            match r {
+                LdapResponseState::Respond(LdapMsg {
+                    op: LdapOp::BindResponse(res),
+                    ..
+                }) => assert_eq!(&res.res.code, code),
+                _ => panic!("Oh no"),
+            }
+        }
+
+        let sasl_bind = |mechanism: &str| LdapSaslBindRequest {
+            msgid: 1,
+            mechanism: mechanism.to_string(),
+            credentials: Vec::new(),
+        };
+
+        // Only the EXTERNAL mechanism is supported.
+        let r = ldaps
+            .do_sasl_bind(idms, sasl_bind("PLAIN"), Some(&client_cert))
+            .await
+            .unwrap();
+        assert_bind_error(&r, &LdapResultCode::AuthMethodNotSupported);
+
+        // A certificate must have been presented.
+        let r = ldaps
+            .do_sasl_bind(idms, sasl_bind("EXTERNAL"), None)
+            .await
+            .unwrap();
+        assert_bind_error(&r, &LdapResultCode::InappropriateAuthentication);
+
+        // Authorisation identities are not supported.
+        let r = ldaps
+            .do_sasl_bind(
+                idms,
+                LdapSaslBindRequest {
+                    credentials: b"dn:name=admin,dc=example,dc=com".to_vec(),
+                    ..sasl_bind("EXTERNAL")
+                },
+                Some(&client_cert),
+            )
+            .await
+            .unwrap();
+        assert_bind_error(&r, &LdapResultCode::InvalidCredentials);
+
+        let r = ldaps
+            .do_sasl_bind(idms, sasl_bind("EXTERNAL"), Some(&client_cert))
+            .await
+            .unwrap();
+        let cert_t = match r {
+            LdapResponseState::Bind(
+                lbt,
+                LdapMsg {
+                    op: LdapOp::BindResponse(res),
+                    ..
+                },
+            ) => {
+                assert_eq!(res.res.code, LdapResultCode::Success);
+                lbt
+            }
+            _ => panic!("Oh no"),
+        };
+        assert_eq!(
+            cert_t.effective_session,
+            LdapSession::ClientCertificate(cert_uuid, acct_uuid)
+        );
+        assert_eq!(cert_t.spn, "testperson1@example.com");
+
+        let sr = SearchRequest {
+            msgid: 2,
+            base: "dc=example,dc=com".to_string(),
+            scope: LdapSearchScope::Subtree,
+            filter: LdapFilter::Equality(LDAP_ATTR_NAME.to_string(), "testperson1".to_string()),
+            attrs: vec![LDAP_ATTR_NAME.to_string()],
+        };
+
+        let r = ldaps
+            .do_search(idms, &sr, &cert_t, Source::Internal)
+            .await
+            .unwrap();
+        assert_eq!(r.len(), 2);
+
+        // Once the certificate is removed the session is no longer valid.
+        {
+            let ct = duration_from_epoch_now();
+            let mut server_txn = idms.proxy_write(ct).await.unwrap();
+            assert!(server_txn
+                .qs_write
+                .internal_delete_uuid(cert_uuid)
+                .and_then(|_| server_txn.commit())
+                .is_ok());
+        }
+
+        assert!(ldaps
+            .do_search(idms, &sr, &cert_t, Source::Internal)
+            .await
+            .is_err());
+
+        let r = ldaps
+            .do_sasl_bind(idms, sasl_bind("EXTERNAL"), Some(&client_cert))
+            .await
+            .unwrap();
+        assert_bind_error(&r, &LdapResultCode::InvalidCredentials);
+    }
+
+    #[test]
+    fn test_ldap_starttls_plaintext_ops() {
+        let search = |base: &str, scope: LdapSearchScope| LdapMsg {
+            msgid: 1,
+            op: LdapOp::SearchRequest(LdapSearchRequest {
+                base: base.to_string(),
+                scope,
+                aliases: LdapDerefAliases::Never,
+                sizelimit: 0,
+                timelimit: 0,
+                typesonly: false,
+                filter: LdapFilter::Present(ATTR_OBJECTCLASS.to_string()),
+                attrs: vec![],
+            }),
+            ctrl: vec![],
+        };
+
+        fn assert_refused(op: LdapStartTlsOp) {
+            let code = match op {
+                LdapStartTlsOp::Refuse(Some(LdapMsg { op, .. })) => match op {
+                    LdapOp::BindResponse(res) => res.res.code,
+                    LdapOp::SearchResultDone(res) | LdapOp::DelResponse(res) => res.code,
+                    LdapOp::ExtendedResponse(res) => res.res.code,
+                    _ => panic!("Oh no"),
+                },
+                _ => panic!("Oh no"),
+            };
+            assert_eq!(code, LdapResultCode::ConfidentialityRequired);
+        }
+
+        // StartTLS is accepted.
+        let op = LdapStartTlsOp::from(LdapMsg {
+            msgid: 1,
+            op: LdapOp::ExtendedRequest(LdapExtendedRequest {
+                name: LDAP_EXOP_STARTTLS.to_string(),
+                value: None,
+            }),
+            ctrl: vec![],
+        });
+        match op {
+            LdapStartTlsOp::StartTls(LdapMsg {
+                op: LdapOp::ExtendedResponse(res),
+                ..
+            }) => {
+                assert_eq!(res.res.code, LdapResultCode::Success);
+                assert_eq!(res.name.as_deref(), Some(LDAP_EXOP_STARTTLS));
+            }
+            _ => panic!("Oh no"),
+        }
+
+        // The rootDSE can be read so that StartTLS can be discovered.
+        assert!(matches!(
+            LdapStartTlsOp::from(search("", LdapSearchScope::Base)),
+            LdapStartTlsOp::Process(_)
+        ));
+
+        // Everything else is refused.
+        assert_refused(LdapStartTlsOp::from(search(
+            "dc=example,dc=com",
+            LdapSearchScope::Subtree,
+        )));
+        assert_refused(LdapStartTlsOp::from(LdapMsg {
+            msgid: 1,
+            op: LdapOp::BindRequest(LdapBindRequest {
+                dn: "name=admin,dc=example,dc=com".to_string(),
+                cred: LdapBindCred::Simple(TEST_PASSWORD.to_string()),
+            }),
+            ctrl: vec![],
+        }));
+        assert_refused(LdapStartTlsOp::from(LdapMsg {
+            msgid: 1,
+            op: LdapOp::DelRequest("name=admin,dc=example,dc=com".to_string()),
+            ctrl: vec![],
+        }));
+        assert_refused(LdapStartTlsOp::from(LdapMsg {
+            msgid: 1,
+            op: LdapOp::ExtendedRequest(LdapExtendedRequest {
+                name: "1.3.6.1.4.1.4203.1.11.3".to_string(),
+                value: None,
+            }),
+            ctrl: vec![],
+        }));
+
+        assert!(matches!(
+            LdapStartTlsOp::from(LdapMsg {
+                msgid: 1,
+                op: LdapOp::UnbindRequest,
+                ctrl: vec![],
+            }),
+            LdapStartTlsOp::Unbind
+        ));
+    }
 }
@@ -10,6 +10,7 @@ pub(crate) mod applinks;
 pub mod audit;
 pub mod authentication;
 pub(crate) mod authsession;
+pub mod breachedpassword;
 pub mod credupdatesession;
 pub mod delayed;
 pub mod event;
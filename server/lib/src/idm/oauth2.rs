@@ -5,35 +5,61 @@
 //! for operations involving OAuth2 authentication processing.
 
 use crate::idm::account::Account;
+use crate::idm::delayed::Oauth2BackchannelLogout;
 use crate::idm::server::{
     IdmServerProxyReadTransaction, IdmServerProxyWriteTransaction, IdmServerTransaction, Token,
 };
 use crate::prelude::*;
 use crate::server::keys::{KeyObject, KeyProvidersTransaction, KeyProvidersWriteTransaction};
+use crate::server::RevokedOauth2Session;
 use crate::utils;
 use crate::value::{Oauth2Session, OauthClaimMapJoin, SessionState, OAUTHSCOPE_RE};
 use base64::{engine::general_purpose, Engine as _};
 pub use compact_jwt::{compact::JwkKeySet, OidcToken};
 use compact_jwt::{
-    crypto::{JweA128GCMEncipher, JweA128KWEncipher},
-    jwe::Jwe,
+    compact::{EcCurve, JwkUse},
+    crypto::{
+        JweA128GCMEncipher, JweA128KWEncipher, JweA256GCMEncipher, JweEcdhEsA256KWEncipher,
+        JweRSAOAEPEncipher, JwsRs256Verifier,
+    },
+    jwe::{Jwe, JweBuilder},
     jws::JwsBuilder,
-    JweCompact, JwsCompact, OidcClaims, OidcSubject,
+    traits::JwsVerifiable,
+    JwaAlg, JweCompact, Jwk, Jws, JwsCompact, JwsEs256Verifier, JwsHs256Signer, JwsVerifier,
+    OidcClaims, OidcSubject,
 };
 use concread::cowcell::*;
-use crypto_glue::{s256::Sha256, traits::Digest};
+use crypto_glue::{
+    ecdh_p256::{EcdhP256FieldBytes, EcdhP256PublicEncodedPoint, EcdhP256PublicKey},
+    hmac_s256::{HmacSha256, HmacSha256Key},
+    rsa::{BigUint, RS256PublicKey},
+    s256::{Sha256, Sha256Output},
+    traits::{Digest, EncodeDer, FromEncodedPoint, Mac},
+    x509::X509Store,
+};
 use hashbrown::HashMap;
 use hashbrown::HashSet;
 use kanidm_proto::constants::*;
-use kanidm_proto::oauth2::IssuedTokenType;
+use kanidm_proto::internal::Oauth2Consent;
 pub use kanidm_proto::oauth2::{
     AccessTokenIntrospectRequest, AccessTokenIntrospectResponse, AccessTokenRequest,
-    AccessTokenResponse, AccessTokenType, AuthorisationRequest, ClaimType, ClientAuth,
-    ClientPostAuth, CodeChallengeMethod, DeviceAuthorizationResponse, DisplayValue, ErrorResponse,
-    GrantType, GrantTypeReq, IdTokenSignAlg, OAuth2RFC9068Token, OAuth2RFC9068TokenExtensions,
-    Oauth2Rfc8414MetadataResponse, OidcDiscoveryResponse, OidcWebfingerRel, OidcWebfingerResponse,
-    PkceAlg, PkceRequest, ResponseMode, ResponseType, SubjectType, TokenEndpointAuthMethod,
-    TokenRevokeRequest, OAUTH2_TOKEN_TYPE_ACCESS_TOKEN,
+    AccessTokenResponse, AccessTokenType, AuthorisationDetail, AuthorisationRequest,
+    AuthorisationRequestParams, AuthorisationRequestRef, BackchannelAuthenticationRequest,
+    BackchannelAuthenticationResponse, BackchannelTokenDeliveryMode, ClaimType, ClientAuth,
+    ClientMetadata, ClientPostAuth, ClientRegistrationResponse, CodeChallengeMethod,
+    DeviceAuthorizationResponse, DisplayValue, EndSessionRequest, ErrorResponse, GrantType,
+    GrantTypeReq, IdTokenEncryptionAlg, IdTokenEncryptionEnc, IdTokenSignAlg, OAuth2RFC9068Token,
+    OAuth2RFC9068TokenExtensions, Oauth2Rfc8414MetadataResponse, OidcDiscoveryResponse,
+    OidcLogoutToken, OidcWebfingerRel, OidcWebfingerResponse, PkceAlg, PkceRequest,
+    PushedAuthorisationResponse, ResponseMode, ResponseType, SubjectType, TokenEndpointAuthMethod,
+    TokenRevokeRequest, OAUTH2_CLIENT_ASSERTION_TYPE_JWT_BEARER,
+    OAUTH2_PUSHED_AUTHORISATION_EXPIRY_SECONDS, OAUTH2_PUSHED_AUTHORISATION_REQUEST_URI_PREFIX,
+    OAUTH2_TOKEN_TYPE_ACCESS_TOKEN, OIDC_BACKCHANNEL_LOGOUT_EVENT,
+};
+use kanidm_proto::oauth2::{
+    ConfirmationClaim, IssuedTokenType, OAUTH2_BACKCHANNEL_BINDING_MESSAGE_MAX_LEN,
+    OAUTH2_DEVICE_CODE_EXPIRY_SECONDS, OAUTH2_DEVICE_CODE_INTERVAL_SECONDS,
+    OAUTH2_DPOP_PROOF_LEEWAY_SECONDS, OAUTH2_DPOP_PROOF_TYP,
 };
 use serde::{Deserialize, Serialize};
 use serde_with::{formats, serde_as};
@@ -42,7 +68,7 @@ use std::collections::{BTreeMap, BTreeSet};
 use std::fmt;
 use std::str::FromStr;
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, SystemTime};
 use time::OffsetDateTime;
 use tracing::trace;
 use uri::{OAUTH2_TOKEN_INTROSPECT_ENDPOINT, OAUTH2_TOKEN_REVOKE_ENDPOINT};
@@ -92,6 +118,19 @@ pub enum Oauth2Error {
     ///   authorization request but SHOULD wait for user interaction before
     ///   restarting to avoid unnecessary polling.
     ExpiredToken,
+    // from https://datatracker.ietf.org/doc/html/rfc9101#section-6.3
+    InvalidRequestUri,
+    InvalidRequestObject,
+    // from https://datatracker.ietf.org/doc/html/rfc9449#section-5
+    InvalidDpopProof,
+    // from https://datatracker.ietf.org/doc/html/rfc7591#section-3.2.2
+    InvalidRedirectUri,
+    InvalidClientMetadata,
+    // from https://datatracker.ietf.org/doc/html/rfc9396#section-5
+    InvalidAuthorizationDetails,
+    // from https://openid.net/specs/openid-client-initiated-backchannel-authentication-core-1_0.html#auth_error_response
+    UnknownUserId,
+    InvalidBindingMessage,
 }
 
 impl std::fmt::Display for Oauth2Error {
@@ -115,6 +154,14 @@ impl std::fmt::Display for Oauth2Error {
             Oauth2Error::SlowDown => "slow_down",
             Oauth2Error::AuthorizationPending => "authorization_pending",
             Oauth2Error::ExpiredToken => "expired_token",
+            Oauth2Error::InvalidRequestUri => "invalid_request_uri",
+            Oauth2Error::InvalidRequestObject => "invalid_request_object",
+            Oauth2Error::InvalidDpopProof => "invalid_dpop_proof",
+            Oauth2Error::InvalidRedirectUri => "invalid_redirect_uri",
+            Oauth2Error::InvalidClientMetadata => "invalid_client_metadata",
+            Oauth2Error::InvalidAuthorizationDetails => "invalid_authorization_details",
+            Oauth2Error::UnknownUserId => "unknown_user_id",
+            Oauth2Error::InvalidBindingMessage => "invalid_binding_message",
         })
     }
 }
@@ -198,6 +245,9 @@ struct ConsentToken {
     pub nonce: Option<String>,
     /// The format the response should be returned to the application in.
     pub response_mode: SupportedResponseMode,
+    /// The rich authorisation details being granted.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub authorization_details: Vec<AuthorisationDetail>,
 }
 
 #[serde_as]
@@ -221,6 +271,94 @@ struct TokenExchangeCode {
     pub scopes: BTreeSet<String>,
     // We stash some details here for oidc.
     pub nonce: Option<String>,
+    // The rich authorisation details being granted.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub authorization_details: Vec<AuthorisationDetail>,
+}
+
+/// The claims a client asserts about itself when authenticating with a JWT.
+/// <https://datatracker.ietf.org/doc/html/rfc7523#section-3>
+#[serde_as]
+#[derive(Deserialize, Debug)]
+struct ClientAssertionClaims {
+    iss: String,
+    sub: String,
+    #[serde_as(as = "serde_with::OneOrMany<_, formats::PreferOne>")]
+    aud: Vec<String>,
+    exp: i64,
+    nbf: Option<i64>,
+    jti: String,
+}
+
+/// A used client assertion. This is ordered by expiry first so that assertions
+/// can be forgotten once they would be rejected as expired anyway.
+pub(crate) type ClientAssertionJti = (Duration, Uuid, String);
+
+/// A used request object, ordered by expiry first in the same way as client assertions.
+pub(crate) type RequestObjectJti = (Duration, Uuid, String);
+
+/// A used DPoP proof, keyed by the time after which it is no longer accepted, the
+/// thumbprint of the key that signed it, and its jti.
+pub(crate) type DpopProofJti = (Duration, String, String);
+
+/// The claims of a signed request object, which carries the authorisation request
+/// parameters as its claims. <https://datatracker.ietf.org/doc/html/rfc9101#section-4>
+#[serde_as]
+#[derive(Deserialize, Debug)]
+struct RequestObjectClaims {
+    iss: Option<String>,
+    #[serde_as(as = "serde_with::OneOrMany<_, formats::PreferOne>")]
+    #[serde(default)]
+    aud: Vec<String>,
+    exp: Option<i64>,
+    nbf: Option<i64>,
+    iat: Option<i64>,
+    jti: Option<String>,
+    #[serde(flatten)]
+    auth_req: AuthorisationRequest,
+}
+
+/// An authorisation request that a client has pushed, held until the user agent
+/// arrives with the matching request_uri. <https://datatracker.ietf.org/doc/html/rfc9126>
+#[derive(Clone, Debug)]
+pub(crate) struct PushedAuthorisation {
+    expiry: Duration,
+    rs_uuid: Uuid,
+    auth_req: AuthorisationRequest,
+}
+
+/// A backchannel authentication request that is waiting for the user to approve it from
+/// another device, and then for the client to poll for its tokens.
+/// <https://openid.net/specs/openid-client-initiated-backchannel-authentication-core-1_0.html>
+#[derive(Clone, Debug)]
+pub(crate) struct BackchannelAuthorisation {
+    expiry: Duration,
+    rs_uuid: Uuid,
+    client_id: String,
+    account_uuid: Uuid,
+    scopes: BTreeSet<String>,
+    binding_message: Option<String>,
+    state: BackchannelAuthorisationState,
+}
+
+#[derive(Clone, Debug)]
+enum BackchannelAuthorisationState {
+    Pending,
+    Approved {
+        parent_session_id: Uuid,
+        scopes: BTreeSet<String>,
+    },
+    Denied,
+}
+
+/// A backchannel authentication request that is shown to the user so they can decide
+/// whether to approve it.
+#[derive(Debug, Clone)]
+pub struct PendingBackchannelAuthorisation {
+    pub auth_req_id: Uuid,
+    pub client_name: String,
+    pub scopes: BTreeSet<String>,
+    pub binding_message: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -236,6 +374,15 @@ pub(crate) enum Oauth2TokenType {
         nbf: i64,
         // We stash some details here for oidc.
         nonce: Option<String>,
+        // The DPoP key thumbprint this token is bound to, if any.
+        #[serde(default)]
+        dpop_jkt: Option<String>,
+        // The client certificate thumbprint this token is bound to, if any.
+        #[serde(default)]
+        x5t_s256: Option<String>,
+        // The rich authorisation details granted to the session.
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        authorization_details: Vec<AuthorisationDetail>,
     },
     ClientAccess {
         scopes: BTreeSet<String>,
@@ -244,6 +391,10 @@ pub(crate) enum Oauth2TokenType {
         exp: i64,
         iat: i64,
         nbf: i64,
+        #[serde(default)]
+        dpop_jkt: Option<String>,
+        #[serde(default)]
+        x5t_s256: Option<String>,
     },
 }
 
@@ -279,6 +430,8 @@ pub enum AuthoriseResponse {
         // pub display_name: String,
         // The token we need to be given back to allow this to proceed
         consent_token: String,
+        // Rich authorisation details requested by the client.
+        authorization_details: Vec<AuthorisationDetail>,
     },
     Permitted(AuthorisePermitSuccess),
 }
@@ -366,6 +519,14 @@ impl AuthoriseReject {
     }
 }
 
+/// The claims of a userinfo request, which are encrypted when the client requires
+/// encrypted userinfo responses.
+#[derive(Debug)]
+pub enum UserinfoResponse {
+    Claims(Box<OidcToken>),
+    Encrypted(Box<JweCompact>),
+}
+
 #[derive(Clone)]
 enum OauthRSType {
     Basic {
@@ -490,8 +651,37 @@ pub struct Oauth2RS {
     revocation_endpoint: Url,
     introspection_endpoint: Url,
     userinfo_endpoint: Url,
+    end_session_endpoint: Url,
+    par_endpoint: Url,
+    registration_endpoint: Url,
+    backchannel_authentication_endpoint: Url,
     jwks_uri: Url,
     scopes_supported: BTreeSet<String>,
+    /// Where to send logout tokens when a session of this client is terminated.
+    backchannel_logout_uri: Option<Url>,
+    /// The public keys this client may sign client assertions with, and that ID tokens
+    /// and userinfo are encrypted to.
+    client_jwks: Vec<Jwk>,
+    /// How ID tokens and userinfo are encrypted to this client, if at all.
+    encrypted_response_alg: Option<IdTokenEncryptionAlg>,
+    /// The types of rich authorisation details this client may request.
+    authorisation_details_types: BTreeSet<String>,
+    /// Must this client push its authorisation requests before redirecting the user?
+    require_par: bool,
+    /// May this client ask users to approve it from another device?
+    enable_ciba: bool,
+    /// The key that pairwise subject identifiers of this client are derived with, if the
+    /// client has opted in to them.
+    pairwise_subject_key: Option<HmacSha256Key>,
+    /// Must tokens issued to this client be bound to a DPoP key?
+    require_dpop: bool,
+    /// The subject DN of the CA issued certificate this client may authenticate with.
+    tls_client_auth_subject_dn: Option<String>,
+    /// The public key digests of self signed certificates this client may authenticate with.
+    tls_client_certificates: BTreeSet<Sha256Output>,
+    /// The id of the registration access token that manages this client, if it was
+    /// registered dynamically.
+    registration_token_id: Option<Uuid>,
     prefer_short_username: bool,
     type_: OauthRSType,
     /// Does the RS have a custom image set? If not, we use the default.
@@ -515,6 +705,70 @@ impl Oauth2RS {
         }
     }
 
+    /// The subject identifier of an account as seen by this client. With pairwise subjects
+    /// enabled this is derived from the account uuid and the client's sector, so that clients
+    /// in different sectors can not correlate their users.
+    fn subject(&self, account_uuid: Uuid) -> Uuid {
+        let Some(pairwise_subject_key) = &self.pairwise_subject_key else {
+            return account_uuid;
+        };
+
+        let mut hmac = HmacSha256::new(pairwise_subject_key);
+        hmac.update(account_uuid.as_bytes());
+        let subject_hmac = hmac.finalize().into_bytes();
+
+        let mut subject_bytes = [0u8; 16];
+        subject_bytes
+            .iter_mut()
+            .zip(subject_hmac.iter())
+            .for_each(|(s, h)| *s = *h);
+
+        uuid::Builder::from_random_bytes(subject_bytes).into_uuid()
+    }
+
+    /// Does this certificate authenticate the client as per RFC8705? A self signed certificate
+    /// must have been registered to the client, and is matched on its public key
+    /// (`self_signed_tls_client_auth`). Otherwise the certificate must be issued by the CA
+    /// dedicated to OAuth2 clients, and is matched on its subject DN (`tls_client_auth`).
+    /// Certificates that are only trusted by the general client CA are not enough, since those
+    /// may be issued for other purposes.
+    fn client_certificate_valid(
+        &self,
+        client_cert: &ClientCertInfo,
+        oauth2_client_ca: Option<&X509Store>,
+        ct: Duration,
+    ) -> bool {
+        if self
+            .tls_client_certificates
+            .contains(&client_cert.public_key_s256)
+        {
+            return true;
+        }
+
+        let Some(subject_dn) = self.tls_client_auth_subject_dn.as_ref() else {
+            return false;
+        };
+
+        if subject_dn != &client_cert.certificate.tbs_certificate.subject.to_string() {
+            return false;
+        }
+
+        let Some(oauth2_client_ca) = oauth2_client_ca else {
+            warn!("A client certificate was presented, but no OAuth2 client CA is configured");
+            return false;
+        };
+
+        oauth2_client_ca
+            .verify(&client_cert.certificate, &[], SystemTime::UNIX_EPOCH + ct)
+            .inspect_err(|err| {
+                info!(
+                    ?err,
+                    "The client certificate was not issued by the OAuth2 client CA"
+                )
+            })
+            .is_ok()
+    }
+
     /// Does this client require PKCE?
     pub fn require_pkce(&self) -> bool {
         match &self.type_ {
@@ -528,6 +782,12 @@ impl Oauth2RS {
         self.device_authorization_endpoint.is_some()
     }
 
+    /// The rich authorisation detail types to advertise in discovery, if any are allowed.
+    fn authorization_details_types_supported(&self) -> Option<Vec<String>> {
+        (!self.authorisation_details_types.is_empty())
+            .then(|| self.authorisation_details_types.iter().cloned().collect())
+    }
+
     /// Does this client have the consent prompt enabled?
     /// As per RFC-6819 5.2.3.2 it can't be disabled on Public clients
     pub fn enable_consent_prompt(&self) -> bool {
@@ -539,6 +799,211 @@ impl Oauth2RS {
             OauthRSType::Public { .. } => true,
         }
     }
+
+    /// Verify a JWS signed by this client. HS256 signatures are keyed by the client
+    /// secret, while ES256 and RS256 signatures must be made by a key registered to
+    /// the client. Returns `None` if the signature can not be verified.
+    fn verify_client_jws(&self, jwsc: &JwsCompact) -> Result<Option<Jws>, Oauth2Error> {
+        let jws = match jwsc.alg() {
+            JwaAlg::HS256 => {
+                let OauthRSType::Basic { authz_secret, .. } = &self.type_ else {
+                    security_info!("Public clients do not have a secret to sign with");
+                    return Ok(None);
+                };
+
+                JwsHs256Signer::try_from(authz_secret.as_bytes())
+                    .map_err(|err| {
+                        error!(?err, "Unable to load client secret to verify signature");
+                        Oauth2Error::ServerError(OperationError::CryptographyError)
+                    })?
+                    .verify(jwsc)
+                    .ok()
+            }
+            JwaAlg::ES256 => self
+                .client_jwks
+                .iter()
+                .filter_map(|jwk| JwsEs256Verifier::try_from(jwk).ok())
+                .find_map(|verifier| verifier.verify(jwsc).ok()),
+            JwaAlg::RS256 => self
+                .client_jwks
+                .iter()
+                .filter_map(|jwk| JwsRs256Verifier::try_from(jwk).ok())
+                .find_map(|verifier| verifier.verify(jwsc).ok()),
+        };
+
+        Ok(jws)
+    }
+
+    /// Encrypt a payload to a public key of this client if it requires encrypted ID tokens
+    /// and userinfo. Returns `None` if the client does not.
+    /// <https://openid.net/specs/openid-connect-core-1_0.html#Encryption>
+    fn encrypt_response(&self, jwe: &Jwe) -> Result<Option<JweCompact>, Oauth2Error> {
+        let Some(alg) = self.encrypted_response_alg else {
+            return Ok(None);
+        };
+
+        // Keys that the client only intends to sign with can't be used.
+        let mut encryption_keys = self.client_jwks.iter().filter(|jwk| {
+            !matches!(
+                jwk,
+                Jwk::EC {
+                    use_: Some(JwkUse::Sig),
+                    ..
+                } | Jwk::RSA {
+                    use_: Some(JwkUse::Sig),
+                    ..
+                }
+            )
+        });
+
+        let jwe_compact = match alg {
+            IdTokenEncryptionAlg::RsaOaep => encryption_keys
+                .find_map(jwk_rsa_oaep_encipher)
+                .map(|encipher| encipher.encipher::<JweA256GCMEncipher>(jwe)),
+            IdTokenEncryptionAlg::EcdhEsA256Kw => encryption_keys
+                .find_map(jwk_ecdh_es_encipher)
+                .map(|encipher| encipher.encipher::<JweA256GCMEncipher>(jwe)),
+        };
+
+        match jwe_compact {
+            Some(Ok(jwe_compact)) => Ok(Some(jwe_compact)),
+            Some(Err(err)) => {
+                error!(?err, %alg, "Unable to encrypt response to client");
+                Err(Oauth2Error::ServerError(OperationError::CryptographyError))
+            }
+            None => {
+                // We must never fall back to sending the response in the clear.
+                error!(%alg, "OAuth2 client has no public key to encrypt responses with");
+                Err(Oauth2Error::ServerError(OperationError::InvalidState))
+            }
+        }
+    }
+
+    /// Verify a signed request object from this client, returning the authorisation
+    /// request that it carries. When `used_request_objects` is provided the request object
+    /// is consumed, and can not be presented again until it expires.
+    /// <https://datatracker.ietf.org/doc/html/rfc9101#section-6>
+    fn verify_request_object(
+        &self,
+        client_id: &str,
+        request: &str,
+        used_request_objects: Option<&std::sync::Mutex<BTreeSet<RequestObjectJti>>>,
+        ct: Duration,
+    ) -> Result<AuthorisationRequest, Oauth2Error> {
+        let jwsc = JwsCompact::from_str(request).map_err(|err| {
+            security_info!(?err, "Unable to parse request object");
+            Oauth2Error::InvalidRequestObject
+        })?;
+
+        let Some(jws) = self.verify_client_jws(&jwsc)? else {
+            security_info!(alg = ?jwsc.alg(), "Unable to verify request object signature");
+            return Err(Oauth2Error::InvalidRequestObject);
+        };
+
+        let claims: RequestObjectClaims = jws.from_json().map_err(|err| {
+            security_info!(?err, "Request object claims are invalid");
+            Oauth2Error::InvalidRequestObject
+        })?;
+
+        // The client_id outside of the request object must match the one inside of it.
+        if claims.auth_req.client_id != client_id {
+            security_info!(?client_id, request_client_id = %claims.auth_req.client_id, "Request object client_id does not match the request");
+            return Err(Oauth2Error::InvalidRequestObject);
+        }
+
+        if claims.iss.as_ref().is_some_and(|iss| iss != &self.name) {
+            security_info!(iss = ?claims.iss, "Request object issuer must be the client_id");
+            return Err(Oauth2Error::InvalidRequestObject);
+        }
+
+        if !claims.aud.iter().any(|aud| aud == self.iss.as_str()) {
+            security_info!(aud = ?claims.aud, "Request object audience does not contain this issuer");
+            return Err(Oauth2Error::InvalidRequestObject);
+        }
+
+        let Some(exp) = claims.exp else {
+            security_info!("Request object has no expiry");
+            return Err(Oauth2Error::InvalidRequestObject);
+        };
+
+        let now = ct.as_secs() as i64;
+        if exp <= now {
+            security_info!("Request object has expired");
+            return Err(Oauth2Error::InvalidRequestObject);
+        }
+
+        let max_expiry = OAUTH2_REQUEST_OBJECT_MAX_EXPIRY as i64;
+        if exp > now + max_expiry || claims.iat.is_some_and(|iat| exp > iat + max_expiry) {
+            security_info!("Request object expiry exceeds the maximum allowed validity");
+            return Err(Oauth2Error::InvalidRequestObject);
+        }
+
+        if claims.nbf.is_some_and(|nbf| nbf > now) {
+            security_info!("Request object is not yet valid");
+            return Err(Oauth2Error::InvalidRequestObject);
+        }
+
+        let Some(jti) = claims.jti else {
+            security_info!("Request object has no jti");
+            return Err(Oauth2Error::InvalidRequestObject);
+        };
+
+        if let Some(used_request_objects) = used_request_objects {
+            let mut used_request_objects = used_request_objects.lock().map_err(|_| {
+                admin_error!("Request object cache lock is poisoned");
+                Oauth2Error::ServerError(OperationError::InvalidState)
+            })?;
+
+            // Forget any request objects that have expired, they can no longer be replayed.
+            *used_request_objects =
+                used_request_objects.split_off(&(ct, Uuid::nil(), String::new()));
+
+            if !used_request_objects.insert((Duration::from_secs(exp as u64), self.uuid, jti)) {
+                security_info!("Request object has already been used");
+                return Err(Oauth2Error::InvalidRequestObject);
+            }
+        }
+
+        Ok(claims.auth_req)
+    }
+
+    /// Verify the registration access token that manages this client, as issued when the
+    /// client was registered. <https://datatracker.ietf.org/doc/html/rfc7592#section-3>
+    fn verify_registration_access_token(
+        &self,
+        client_auth_info: &ClientAuthInfo,
+    ) -> Result<(), Oauth2Error> {
+        let Some(registration_token_id) = self.registration_token_id else {
+            security_info!(client_id = %self.name, "Client was not registered dynamically");
+            return Err(Oauth2Error::AuthenticationRequired);
+        };
+
+        let Some(jwsc) = client_auth_info.bearer_token() else {
+            security_info!("Missing registration access token");
+            return Err(Oauth2Error::AuthenticationRequired);
+        };
+
+        let claims: RegistrationAccessTokenClaims = self
+            .key_object
+            .jws_verify(jwsc)
+            .map_err(|err| {
+                security_info!(?err, "Unable to verify registration access token");
+                Oauth2Error::AuthenticationRequired
+            })?
+            .from_json()
+            .map_err(|err| {
+                security_info!(?err, "Registration access token claims are invalid");
+                Oauth2Error::AuthenticationRequired
+            })?;
+
+        // A superseded token, or any other token signed by this client's key, is not valid.
+        if claims.client_id != self.name || claims.jti != registration_token_id {
+            security_info!("Registration access token does not manage this client");
+            return Err(Oauth2Error::AuthenticationRequired);
+        }
+
+        Ok(())
+    }
 }
 
 impl std::fmt::Debug for Oauth2RS {
@@ -610,115 +1075,721 @@ impl Oauth2ResourceServers {
     }
 }
 
-/// For when you've got the bearer auth and the post auth and you just want the resulting auth attempt
-fn get_client_auth(
-    client_auth_info: &ClientAuthInfo,
-    client_post_auth: &ClientPostAuth,
-) -> Result<ClientAuth, Oauth2Error> {
-    if let Some(client_authz) = client_auth_info.basic_authz.as_ref() {
-        parse_basic_authz(client_authz.as_str())
-    } else if let Some(client_id) = &client_post_auth.client_id {
-        Ok(ClientAuth {
-            client_id: client_id.clone(),
-            client_secret: client_post_auth.client_secret.clone(),
-        })
-    } else {
-        admin_warn!("OAuth2 client authentication not provided");
-        Err(Oauth2Error::AuthenticationRequired)
+/// Read the subject of a client assertion *without* verifying it, so that the client
+/// and the keys to verify the assertion with can be found.
+fn client_assertion_unverified_subject(client_assertion: &str) -> Result<String, Oauth2Error> {
+    #[derive(Deserialize)]
+    struct UnverifiedSubject {
+        sub: String,
     }
+
+    client_assertion
+        .split('.')
+        .nth(1)
+        .and_then(|payload| general_purpose::URL_SAFE_NO_PAD.decode(payload).ok())
+        .and_then(|payload| serde_json::from_slice::<UnverifiedSubject>(&payload).ok())
+        .map(|unverified| unverified.sub)
+        .ok_or_else(|| {
+            security_info!("Unable to determine the client from the client assertion");
+            Oauth2Error::InvalidRequest
+        })
 }
 
-impl Oauth2ResourceServersWriteTransaction<'_> {
-    #[instrument(level = "debug", name = "oauth2::reload", skip_all)]
-    pub fn reload(
-        &mut self,
-        value: Vec<Arc<EntrySealedCommitted>>,
-        key_providers: &KeyProvidersWriteTransaction,
-        domain_level: DomainVersion,
-    ) -> Result<(), OperationError> {
-        let rs_set: Result<HashMap<_, _>, _> = value
-            .into_iter()
-            .map(|ent| {
-                let uuid = ent.get_uuid();
-                trace!(?uuid, "Checking OAuth2 configuration");
-                // From each entry, attempt to make an OAuth2 configuration.
-                if !ent
-                    .attribute_equality(Attribute::Class, &EntryClass::OAuth2ResourceServer.into())
-                {
-                    error!("Missing class oauth2_resource_server");
-                    // Check we have oauth2_resource_server class
-                    return Err(OperationError::InvalidEntryState);
-                }
+/// Find the account that the subject of a token issued to this client refers to. Pairwise
+/// subjects can not be reversed, so the account is found by the session the token belongs to.
+/// As a result, with pairwise subjects a token is only recognised once its session has
+/// replicated to this server.
+fn oauth2_subject_account_uuid<'a, T: QueryServerTransaction<'a>>(
+    qs: &mut T,
+    o2rs: &Oauth2RS,
+    subject: Uuid,
+    session_id: Uuid,
+) -> Result<Option<Uuid>, OperationError> {
+    if o2rs.pairwise_subject_key.is_none() {
+        return Ok(Some(subject));
+    }
 
-                let Some(key_object) = key_providers.get_key_object_handle(uuid) else {
-                    error!("OAuth2 RS is missing its key object!");
-                    return Err(OperationError::InvalidEntryState);
-                };
+    let entries = qs.internal_search(filter!(f_eq(
+        Attribute::OAuth2Session,
+        PartialValue::Refer(session_id)
+    )))?;
 
-                let type_ = if ent.attribute_equality(
-                    Attribute::Class,
-                    &EntryClass::OAuth2ResourceServerBasic.into(),
-                ) {
-                    let authz_secret = ent
-                        .get_ava_single_secret(Attribute::OAuth2RsBasicSecret)
-                        .map(str::to_string)
-                        .ok_or(OperationError::InvalidValueState)?;
+    Ok(entries
+        .iter()
+        .map(|entry| entry.get_uuid())
+        .find(|account_uuid| o2rs.subject(*account_uuid) == subject))
+}
 
-                    let enable_pkce = ent
-                        .get_ava_single_bool(Attribute::OAuth2AllowInsecureClientDisablePkce)
-                        .map(|e| !e)
-                        .unwrap_or(true);
+/// The claims of a registration access token, signed by the key object of the client that
+/// the token manages.
+#[derive(Serialize, Deserialize, Debug)]
+struct RegistrationAccessTokenClaims {
+    client_id: String,
+    jti: Uuid,
+}
 
-                    let enable_consent_prompt = ent
-                        .get_ava_single_bool(Attribute::OAuth2ConsentPromptEnable)
-                        .unwrap_or(true);
+/// The metadata of a client that has been validated for registration.
+struct ValidClientMetadata {
+    is_public: bool,
+    displayname: Option<String>,
+    redirect_uris: Vec<Url>,
+    scope_maps: BTreeMap<Uuid, BTreeSet<String>>,
+    jwks: Option<String>,
+}
 
-                    OauthRSType::Basic {
-                        authz_secret,
-                        enable_pkce,
-                        enable_consent_prompt,
-                    }
-                } else if ent.attribute_equality(
-                    Attribute::Class,
-                    &EntryClass::OAuth2ResourceServerPublic.into(),
-                ) {
-                    let allow_localhost_redirect = ent
-                        .get_ava_single_bool(Attribute::OAuth2AllowLocalhostRedirect)
-                        .unwrap_or(false);
+impl ValidClientMetadata {
+    /// The modifications that apply this metadata to a client entry. The client's type
+    /// is not included, as it can not be changed.
+    fn modifications(&self, client_id: &str) -> Result<Vec<Modify>, Oauth2Error> {
+        let mut mods = vec![
+            Modify::Purged(Attribute::DisplayName),
+            Modify::Present(
+                Attribute::DisplayName,
+                Value::new_utf8s(self.displayname.as_deref().unwrap_or(client_id)),
+            ),
+            Modify::Purged(Attribute::OAuth2RsOriginLanding),
+            Modify::Purged(Attribute::OAuth2RsOrigin),
+            Modify::Purged(Attribute::OAuth2RsScopeMap),
+            Modify::Purged(Attribute::OAuth2RsJwks),
+        ];
 
-                    OauthRSType::Public {
-                        allow_localhost_redirect,
-                    }
-                } else {
-                    error!("Missing class determining OAuth2 rs type");
-                    return Err(OperationError::InvalidEntryState);
-                };
+        // The first redirect uri is where users land when they open the application.
+        if let Some(landing_uri) = self.redirect_uris.first() {
+            mods.push(Modify::Present(
+                Attribute::OAuth2RsOriginLanding,
+                Value::Url(landing_uri.clone()),
+            ));
+        }
 
-                // Now we know we can load the shared attrs.
-                let name = ent
-                    .get_ava_single_iname(Attribute::Name)
-                    .map(str::to_string)
-                    .ok_or(OperationError::InvalidValueState)?;
+        for redirect_uri in self.redirect_uris.iter() {
+            mods.push(Modify::Present(
+                Attribute::OAuth2RsOrigin,
+                Value::Url(redirect_uri.clone()),
+            ));
+        }
 
-                let displayname = ent
-                    .get_ava_single_utf8(Attribute::DisplayName)
-                    .map(str::to_string)
-                    .ok_or(OperationError::InvalidValueState)?;
+        for (group_uuid, scopes) in self.scope_maps.iter() {
+            let scope_map = Value::new_oauthscopemap(*group_uuid, scopes.clone())
+                .ok_or(Oauth2Error::InvalidClientMetadata)?;
+            mods.push(Modify::Present(Attribute::OAuth2RsScopeMap, scope_map));
+        }
 
-                // Setup the landing uri and its implied origin, as well as
-                // the supplemental origins.
-                let landing_url = ent
-                    .get_ava_single_url(Attribute::OAuth2RsOriginLanding)
-                    .cloned()
-                    .ok_or(OperationError::InvalidValueState)?;
+        if let Some(jwks) = &self.jwks {
+            mods.push(Modify::Present(
+                Attribute::OAuth2RsJwks,
+                Value::new_utf8s(jwks),
+            ));
+        }
 
-                let maybe_extra_urls = ent
-                    .get_ava_set(Attribute::OAuth2RsOrigin)
-                    .and_then(|s| s.as_url_set());
+        Ok(mods)
+    }
+}
 
-                let len_uris = maybe_extra_urls.map(|s| s.len() + 1).unwrap_or(1);
+/// Validate the metadata of a client that is being registered or updated. The client is
+/// granted `permitted_scope_maps`, narrowed to the scopes that it requests.
+/// <https://datatracker.ietf.org/doc/html/rfc7591#section-2>
+fn validate_client_metadata(
+    metadata: &ClientMetadata,
+    permitted_scope_maps: &BTreeMap<Uuid, BTreeSet<String>>,
+) -> Result<ValidClientMetadata, Oauth2Error> {
+    let is_public = match metadata
+        .token_endpoint_auth_method
+        .unwrap_or(TokenEndpointAuthMethod::ClientSecretBasic)
+    {
+        TokenEndpointAuthMethod::None => true,
+        TokenEndpointAuthMethod::ClientSecretBasic
+        | TokenEndpointAuthMethod::ClientSecretPost
+        | TokenEndpointAuthMethod::ClientSecretJwt => false,
+        TokenEndpointAuthMethod::PrivateKeyJwt => {
+            if metadata.jwks.is_none() {
+                warn!("A client that authenticates with private_key_jwt must register its jwks");
+                return Err(Oauth2Error::InvalidClientMetadata);
+            }
+            false
+        }
+        method @ (TokenEndpointAuthMethod::TlsClientAuth
+        | TokenEndpointAuthMethod::SelfSignedTlsClientAuth) => {
+            warn!(
+                ?method,
+                "This client authentication method can not be registered"
+            );
+            return Err(Oauth2Error::InvalidClientMetadata);
+        }
+    };
 
-                // If we are DL8, then strict enforcement is always required.
+    if is_public && metadata.jwks.is_some() {
+        warn!("A public client can not register a jwks");
+        return Err(Oauth2Error::InvalidClientMetadata);
+    }
+
+    if metadata.redirect_uris.is_empty() {
+        warn!("A client must register at least one redirect_uri");
+        return Err(Oauth2Error::InvalidRedirectUri);
+    }
+
+    // https://www.rfc-editor.org/rfc/rfc6749#section-3.1.2
+    if let Some(redirect_uri) = metadata
+        .redirect_uris
+        .iter()
+        .find(|redirect_uri| redirect_uri.scheme() != "https" || redirect_uri.fragment().is_some())
+    {
+        warn!(%redirect_uri, "A redirect_uri must be https and must not contain a fragment");
+        return Err(Oauth2Error::InvalidRedirectUri);
+    }
+
+    if metadata
+        .response_types
+        .iter()
+        .any(|response_type| response_type != "code")
+    {
+        warn!(response_types = ?metadata.response_types, "Only the code response type is supported");
+        return Err(Oauth2Error::InvalidClientMetadata);
+    }
+
+    if !metadata
+        .grant_types
+        .iter()
+        .all(|grant_type| match grant_type.as_str() {
+            "authorization_code" | "refresh_token" => true,
+            // Public clients have no credentials to request tokens for themselves with.
+            "client_credentials" => !is_public,
+            _ => false,
+        })
+    {
+        warn!(grant_types = ?metadata.grant_types, "Unsupported grant types requested");
+        return Err(Oauth2Error::InvalidClientMetadata);
+    }
+
+    let jwks = metadata
+        .jwks
+        .as_ref()
+        .map(|jwks| {
+            serde_json::from_value::<JwkKeySet>(jwks.clone())
+                .and_then(|_| serde_json::to_string(jwks))
+                .map_err(|err| {
+                    warn!(?err, "Invalid client JSON web key set");
+                    Oauth2Error::InvalidClientMetadata
+                })
+        })
+        .transpose()?;
+
+    let scope_maps = match metadata.scope.as_deref() {
+        None => permitted_scope_maps.clone(),
+        Some(scope) => {
+            let requested_scopes: BTreeSet<&str> = scope.split_ascii_whitespace().collect();
+
+            if let Some(denied_scope) = requested_scopes.iter().find(|requested_scope| {
+                !permitted_scope_maps
+                    .values()
+                    .any(|scopes| scopes.contains(**requested_scope))
+            }) {
+                warn!(%denied_scope, "Requested scope is not permitted for this client");
+                return Err(Oauth2Error::InvalidClientMetadata);
+            }
+
+            permitted_scope_maps
+                .iter()
+                .filter_map(|(group_uuid, scopes)| {
+                    let scopes: BTreeSet<String> = scopes
+                        .iter()
+                        .filter(|scope| requested_scopes.contains(scope.as_str()))
+                        .cloned()
+                        .collect();
+                    (!scopes.is_empty()).then_some((*group_uuid, scopes))
+                })
+                .collect()
+        }
+    };
+
+    let displayname = metadata
+        .client_name
+        .as_deref()
+        .map(str::trim)
+        .filter(|client_name| !client_name.is_empty())
+        .map(str::to_string);
+
+    Ok(ValidClientMetadata {
+        is_public,
+        displayname,
+        redirect_uris: metadata.redirect_uris.clone(),
+        scope_maps,
+        jwks,
+    })
+}
+
+/// Describe the registration of a client from its entry.
+/// <https://datatracker.ietf.org/doc/html/rfc7591#section-3.2.1>
+fn client_registration_response(
+    origin: &Url,
+    ent: &EntrySealedCommitted,
+) -> Result<ClientRegistrationResponse, Oauth2Error> {
+    let client_id = ent
+        .get_ava_single_iname(Attribute::Name)
+        .map(str::to_string)
+        .ok_or(Oauth2Error::ServerError(OperationError::InvalidValueState))?;
+
+    let client_secret = ent
+        .get_ava_single_secret(Attribute::OAuth2RsBasicSecret)
+        .map(str::to_string);
+
+    let jwks = ent
+        .get_ava_single_utf8(Attribute::OAuth2RsJwks)
+        .and_then(|jwks| serde_json::from_str::<serde_json::Value>(jwks).ok());
+
+    let (token_endpoint_auth_method, grant_types) = if client_secret.is_none() {
+        (
+            TokenEndpointAuthMethod::None,
+            vec![
+                "authorization_code".to_string(),
+                "refresh_token".to_string(),
+            ],
+        )
+    } else {
+        let token_endpoint_auth_method = if jwks.is_some() {
+            TokenEndpointAuthMethod::PrivateKeyJwt
+        } else {
+            TokenEndpointAuthMethod::ClientSecretBasic
+        };
+        (
+            token_endpoint_auth_method,
+            vec![
+                "authorization_code".to_string(),
+                "refresh_token".to_string(),
+                "client_credentials".to_string(),
+            ],
+        )
+    };
+
+    let redirect_uris = ent
+        .get_ava_set(Attribute::OAuth2RsOrigin)
+        .and_then(|vs| vs.as_url_set())
+        .map(|urls| urls.iter().cloned().collect())
+        .unwrap_or_default();
+
+    let scopes: BTreeSet<&String> = ent
+        .get_ava_as_oauthscopemaps(Attribute::OAuth2RsScopeMap)
+        .map(|scope_maps| scope_maps.values().flatten().collect())
+        .unwrap_or_default();
+
+    let mut registration_client_uri = origin.clone();
+    registration_client_uri.set_path(&format!(
+        "{}/{client_id}",
+        uri::OAUTH2_REGISTRATION_ENDPOINT
+    ));
+
+    Ok(ClientRegistrationResponse {
+        client_secret_expires_at: client_secret.as_ref().map(|_| 0),
+        client_secret,
+        client_id_issued_at: None,
+        registration_access_token: None,
+        registration_client_uri: Some(registration_client_uri),
+        metadata: ClientMetadata {
+            redirect_uris,
+            token_endpoint_auth_method: Some(token_endpoint_auth_method),
+            grant_types,
+            response_types: vec!["code".to_string()],
+            client_name: ent
+                .get_ava_single_utf8(Attribute::DisplayName)
+                .map(str::to_string),
+            scope: (!scopes.is_empty()).then(|| str_concat!(&scopes, " ")),
+            jwks,
+        },
+        client_id,
+    })
+}
+
+#[derive(Deserialize)]
+struct DpopProofHeader {
+    typ: String,
+    jwk: serde_json::Value,
+}
+
+#[derive(Deserialize)]
+struct DpopProofClaims {
+    jti: String,
+    htm: String,
+    htu: Url,
+    iat: i64,
+    ath: Option<String>,
+}
+
+struct VerifiedDpopProof {
+    jkt: String,
+    jti: String,
+    iat: i64,
+}
+
+/// Verify a DPoP proof was made for a request to `htu`, and when an access token is
+/// presented, that it was made for that token. On success the RFC7638 thumbprint of
+/// the key the proof was signed with is returned, along with the proof's jti for
+/// replay detection.
+///
+/// <https://datatracker.ietf.org/doc/html/rfc9449#section-4.3>
+fn verify_dpop_proof(
+    dpop_proof: &DpopProof,
+    htu: &Url,
+    access_token: Option<&JwsCompact>,
+    ct: Duration,
+) -> Result<VerifiedDpopProof, Oauth2Error> {
+    let jwsc = JwsCompact::from_str(&dpop_proof.proof).map_err(|err| {
+        security_info!(?err, "Unable to parse DPoP proof");
+        Oauth2Error::InvalidDpopProof
+    })?;
+
+    // The key to verify the proof with is embedded in the proof itself.
+    let header = dpop_proof
+        .proof
+        .split('.')
+        .next()
+        .and_then(|header| general_purpose::URL_SAFE_NO_PAD.decode(header).ok())
+        .and_then(|header| serde_json::from_slice::<DpopProofHeader>(&header).ok())
+        .ok_or_else(|| {
+            security_info!("DPoP proof header is invalid");
+            Oauth2Error::InvalidDpopProof
+        })?;
+
+    if header.typ != OAUTH2_DPOP_PROOF_TYP {
+        security_info!(typ = %header.typ, "DPoP proof has an invalid type");
+        return Err(Oauth2Error::InvalidDpopProof);
+    }
+
+    if header.jwk.get("d").is_some() {
+        security_info!("DPoP proof must not contain a private key");
+        return Err(Oauth2Error::InvalidDpopProof);
+    }
+
+    let jwk: Jwk = serde_json::from_value(header.jwk.clone()).map_err(|err| {
+        security_info!(?err, "DPoP proof does not contain a valid public key");
+        Oauth2Error::InvalidDpopProof
+    })?;
+
+    let jws = match jwsc.alg() {
+        JwaAlg::ES256 => JwsEs256Verifier::try_from(&jwk)
+            .ok()
+            .and_then(|verifier| verifier.verify(&jwsc).ok()),
+        JwaAlg::RS256 => JwsRs256Verifier::try_from(&jwk)
+            .ok()
+            .and_then(|verifier| verifier.verify(&jwsc).ok()),
+        // A proof must be signed by an asymmetric key.
+        JwaAlg::HS256 => None,
+    }
+    .ok_or_else(|| {
+        security_info!(alg = ?jwsc.alg(), "Unable to verify DPoP proof signature");
+        Oauth2Error::InvalidDpopProof
+    })?;
+
+    let claims: DpopProofClaims = jws.from_json().map_err(|err| {
+        security_info!(?err, "DPoP proof claims are invalid");
+        Oauth2Error::InvalidDpopProof
+    })?;
+
+    if claims.jti.is_empty() {
+        security_info!("DPoP proof does not have a jti");
+        return Err(Oauth2Error::InvalidDpopProof);
+    }
+
+    if claims.htm != dpop_proof.htm {
+        security_info!(htm = %claims.htm, "DPoP proof was not made for this request method");
+        return Err(Oauth2Error::InvalidDpopProof);
+    }
+
+    // The query and fragment are not part of the comparison.
+    let mut proof_htu = claims.htu;
+    proof_htu.set_query(None);
+    proof_htu.set_fragment(None);
+    if &proof_htu != htu {
+        security_info!(htu = %proof_htu, "DPoP proof was not made for this request uri");
+        return Err(Oauth2Error::InvalidDpopProof);
+    }
+
+    if claims.iat.abs_diff(ct.as_secs() as i64) > OAUTH2_DPOP_PROOF_LEEWAY_SECONDS {
+        security_info!(iat = %claims.iat, "DPoP proof was not issued recently");
+        return Err(Oauth2Error::InvalidDpopProof);
+    }
+
+    if let Some(access_token) = access_token {
+        let mut hasher = Sha256::new();
+        hasher.update(access_token.to_string().as_bytes());
+        let ath = general_purpose::URL_SAFE_NO_PAD.encode(hasher.finalize());
+
+        if claims.ath.as_deref() != Some(ath.as_str()) {
+            security_info!("DPoP proof was not made for this access token");
+            return Err(Oauth2Error::InvalidDpopProof);
+        }
+    }
+
+    let jkt = jwk_thumbprint(&header.jwk).ok_or_else(|| {
+        security_info!("Unable to determine the thumbprint of the DPoP proof key");
+        Oauth2Error::InvalidDpopProof
+    })?;
+
+    Ok(VerifiedDpopProof {
+        jkt,
+        jti: claims.jti,
+        iat: claims.iat,
+    })
+}
+
+/// Record that a DPoP proof has been used, rejecting it if it has been seen before. Used
+/// proofs are shared outside of the transaction, as the userinfo endpoint only reads.
+fn consume_dpop_proof(
+    used_proofs: &std::sync::Mutex<BTreeSet<DpopProofJti>>,
+    verified: &VerifiedDpopProof,
+    ct: Duration,
+) -> Result<(), Oauth2Error> {
+    let mut used_proofs = used_proofs.lock().map_err(|_| {
+        admin_error!("DPoP proof cache lock is poisoned");
+        Oauth2Error::ServerError(OperationError::InvalidState)
+    })?;
+
+    // Forget any proofs that would now be rejected for their age anyway.
+    *used_proofs = used_proofs.split_off(&(ct, String::new(), String::new()));
+
+    let proof_jti = (
+        Duration::from_secs(verified.iat.max(0) as u64 + OAUTH2_DPOP_PROOF_LEEWAY_SECONDS),
+        verified.jkt.clone(),
+        verified.jti.clone(),
+    );
+    if !used_proofs.insert(proof_jti) {
+        security_info!("DPoP proof has already been used");
+        return Err(Oauth2Error::InvalidDpopProof);
+    }
+
+    Ok(())
+}
+
+/// The base64url encoded SHA-256 thumbprint of a public key as per RFC7638.
+fn jwk_thumbprint(jwk: &serde_json::Value) -> Option<String> {
+    // Display of a json string member yields it quoted and escaped.
+    let member = |name: &str| {
+        jwk.get(name)
+            .filter(|v| v.is_string())
+            .map(|v| v.to_string())
+    };
+
+    let canonical = match jwk.get("kty").and_then(|kty| kty.as_str())? {
+        "EC" => format!(
+            r#"{{"crv":{},"kty":"EC","x":{},"y":{}}}"#,
+            member("crv")?,
+            member("x")?,
+            member("y")?
+        ),
+        "RSA" => format!(
+            r#"{{"e":{},"kty":"RSA","n":{}}}"#,
+            member("e")?,
+            member("n")?
+        ),
+        _ => return None,
+    };
+
+    let mut hasher = Sha256::new();
+    hasher.update(canonical.as_bytes());
+    Some(general_purpose::URL_SAFE_NO_PAD.encode(hasher.finalize()))
+}
+
+/// An RSA-OAEP encipher for a client's RSA public key.
+fn jwk_rsa_oaep_encipher(jwk: &Jwk) -> Option<JweRSAOAEPEncipher> {
+    let Jwk::RSA { n, e, .. } = jwk else {
+        return None;
+    };
+
+    RS256PublicKey::new(BigUint::from_bytes_be(n), BigUint::from_bytes_be(e))
+        .inspect_err(|err| debug!(?err, "Invalid client RSA public key"))
+        .ok()
+        .map(JweRSAOAEPEncipher::from)
+}
+
+/// An ECDH-ES encipher for a client's P-256 public key. This holds an ephemeral key, so
+/// it must only be used once.
+fn jwk_ecdh_es_encipher(jwk: &Jwk) -> Option<JweEcdhEsA256KWEncipher> {
+    let Jwk::EC {
+        crv: EcCurve::P256,
+        x,
+        y,
+        ..
+    } = jwk
+    else {
+        return None;
+    };
+
+    let mut field_x = EcdhP256FieldBytes::default();
+    let mut field_y = EcdhP256FieldBytes::default();
+    if x.len() != field_x.len() || y.len() != field_y.len() {
+        debug!("Invalid client EC public key coordinates");
+        return None;
+    }
+    field_x.copy_from_slice(x);
+    field_y.copy_from_slice(y);
+
+    let encoded_point =
+        EcdhP256PublicEncodedPoint::from_affine_coordinates(&field_x, &field_y, false);
+    let public_key = EcdhP256PublicKey::from_encoded_point(&encoded_point).into_option()?;
+
+    JweEcdhEsA256KWEncipher::generate_ephemeral(public_key)
+        .inspect_err(|err| error!(?err, "Unable to create ephemeral key"))
+        .ok()
+}
+
+/// The base64url encoded SHA-256 thumbprint of a DER encoded certificate as per RFC8705.
+fn certificate_thumbprint(client_cert: &ClientCertInfo) -> Result<String, Oauth2Error> {
+    let der = client_cert.certificate.to_der().map_err(|err| {
+        error!(?err, "Unable to encode client certificate");
+        Oauth2Error::ServerError(OperationError::CryptographyError)
+    })?;
+
+    let mut hasher = Sha256::new();
+    hasher.update(&der);
+    Ok(general_purpose::URL_SAFE_NO_PAD.encode(hasher.finalize()))
+}
+
+/// The proof of possession keys that an issued token is bound to.
+#[derive(Debug, Clone, Default)]
+struct TokenBinding {
+    /// The thumbprint of the DPoP key, as per RFC9449.
+    dpop_jkt: Option<String>,
+    /// The thumbprint of the mutual TLS client certificate, as per RFC8705.
+    x5t_s256: Option<String>,
+}
+
+impl TokenBinding {
+    fn token_type(&self) -> AccessTokenType {
+        // Certificate bound tokens are still bearer tokens to the client.
+        if self.dpop_jkt.is_some() {
+            AccessTokenType::DPoP
+        } else {
+            AccessTokenType::Bearer
+        }
+    }
+
+    fn cnf(&self) -> Option<ConfirmationClaim> {
+        if self.dpop_jkt.is_none() && self.x5t_s256.is_none() {
+            None
+        } else {
+            Some(ConfirmationClaim {
+                jkt: self.dpop_jkt.clone(),
+                x5t_s256: self.x5t_s256.clone(),
+            })
+        }
+    }
+}
+
+/// For when you've got the bearer auth and the post auth and you just want the resulting auth attempt
+fn get_client_auth(
+    client_auth_info: &ClientAuthInfo,
+    client_post_auth: &ClientPostAuth,
+) -> Result<ClientAuth, Oauth2Error> {
+    if let Some(client_authz) = client_auth_info.basic_authz.as_ref() {
+        parse_basic_authz(client_authz.as_str())
+    } else if let Some(client_id) = &client_post_auth.client_id {
+        Ok(ClientAuth {
+            client_id: client_id.clone(),
+            client_secret: client_post_auth.client_secret.clone(),
+        })
+    } else {
+        admin_warn!("OAuth2 client authentication not provided");
+        Err(Oauth2Error::AuthenticationRequired)
+    }
+}
+
+impl Oauth2ResourceServersWriteTransaction<'_> {
+    #[instrument(level = "debug", name = "oauth2::reload", skip_all)]
+    pub fn reload(
+        &mut self,
+        value: Vec<Arc<EntrySealedCommitted>>,
+        key_providers: &KeyProvidersWriteTransaction,
+        domain_level: DomainVersion,
+    ) -> Result<(), OperationError> {
+        let rs_set: Result<HashMap<_, _>, _> = value
+            .into_iter()
+            .map(|ent| {
+                let uuid = ent.get_uuid();
+                trace!(?uuid, "Checking OAuth2 configuration");
+                // From each entry, attempt to make an OAuth2 configuration.
+                if !ent
+                    .attribute_equality(Attribute::Class, &EntryClass::OAuth2ResourceServer.into())
+                {
+                    error!("Missing class oauth2_resource_server");
+                    // Check we have oauth2_resource_server class
+                    return Err(OperationError::InvalidEntryState);
+                }
+
+                let Some(key_object) = key_providers.get_key_object_handle(uuid) else {
+                    error!("OAuth2 RS is missing its key object!");
+                    return Err(OperationError::InvalidEntryState);
+                };
+
+                let type_ = if ent.attribute_equality(
+                    Attribute::Class,
+                    &EntryClass::OAuth2ResourceServerBasic.into(),
+                ) {
+                    let authz_secret = ent
+                        .get_ava_single_secret(Attribute::OAuth2RsBasicSecret)
+                        .map(str::to_string)
+                        .ok_or(OperationError::InvalidValueState)?;
+
+                    let enable_pkce = ent
+                        .get_ava_single_bool(Attribute::OAuth2AllowInsecureClientDisablePkce)
+                        .map(|e| !e)
+                        .unwrap_or(true);
+
+                    let enable_consent_prompt = ent
+                        .get_ava_single_bool(Attribute::OAuth2ConsentPromptEnable)
+                        .unwrap_or(true);
+
+                    OauthRSType::Basic {
+                        authz_secret,
+                        enable_pkce,
+                        enable_consent_prompt,
+                    }
+                } else if ent.attribute_equality(
+                    Attribute::Class,
+                    &EntryClass::OAuth2ResourceServerPublic.into(),
+                ) {
+                    let allow_localhost_redirect = ent
+                        .get_ava_single_bool(Attribute::OAuth2AllowLocalhostRedirect)
+                        .unwrap_or(false);
+
+                    OauthRSType::Public {
+                        allow_localhost_redirect,
+                    }
+                } else {
+                    error!("Missing class determining OAuth2 rs type");
+                    return Err(OperationError::InvalidEntryState);
+                };
+
+                // Now we know we can load the shared attrs.
+                let name = ent
+                    .get_ava_single_iname(Attribute::Name)
+                    .map(str::to_string)
+                    .ok_or(OperationError::InvalidValueState)?;
+
+                let displayname = ent
+                    .get_ava_single_utf8(Attribute::DisplayName)
+                    .map(str::to_string)
+                    .ok_or(OperationError::InvalidValueState)?;
+
+                // Setup the landing uri and its implied origin, as well as
+                // the supplemental origins.
+                let landing_url = ent
+                    .get_ava_single_url(Attribute::OAuth2RsOriginLanding)
+                    .cloned()
+                    .ok_or(OperationError::InvalidValueState)?;
+
+                // OIDC Core 8.1 - without a sector_identifier_uri the sector is the host of
+                // the client's redirect uri. Opaque landing urls have no host, so the client
+                // is its own sector.
+                let sector_identifier = landing_url
+                    .host_str()
+                    .map(str::to_string)
+                    .unwrap_or_else(|| name.clone());
+
+                let maybe_extra_urls = ent
+                    .get_ava_set(Attribute::OAuth2RsOrigin)
+                    .and_then(|s| s.as_url_set());
+
+                let len_uris = maybe_extra_urls.map(|s| s.len() + 1).unwrap_or(1);
+
+                // If we are DL8, then strict enforcement is always required.
                 let strict_redirect_uri = cfg!(test)
                     || domain_level >= DOMAIN_LEVEL_8
                     || ent
@@ -864,23 +1935,121 @@ impl Oauth2ResourceServersWriteTransaction<'_> {
 
                 let has_custom_image = ent.get_ava_single_image(Attribute::Image).is_some();
 
-                let mut authorization_endpoint = self.inner.origin.clone();
-                authorization_endpoint.set_path("/ui/oauth2");
+                let backchannel_logout_uri = ent
+                    .get_ava_single_url(Attribute::OAuth2RsBackchannelLogoutUri)
+                    .cloned();
+
+                let client_jwks = ent
+                    .get_ava_single_utf8(Attribute::OAuth2RsJwks)
+                    .map(|jwks| {
+                        serde_json::from_str::<JwkKeySet>(jwks)
+                            .map(|jwks| jwks.keys)
+                            .unwrap_or_else(|err| {
+                                warn!(?err, "Ignoring invalid OAuth2 client JSON web key set");
+                                Vec::with_capacity(0)
+                            })
+                    })
+                    .unwrap_or_default();
 
-                let mut token_endpoint = self.inner.origin.clone();
-                token_endpoint.set_path(uri::OAUTH2_TOKEN_ENDPOINT);
+                let encrypted_response_alg = ent
+                    .get_ava_single_utf8(Attribute::OAuth2RsEncryptedResponseAlg)
+                    .and_then(|alg| {
+                        IdTokenEncryptionAlg::from_str(alg)
+                            .map_err(|err| {
+                                warn!(
+                                    ?err,
+                                    "Ignoring invalid OAuth2 client encrypted response algorithm"
+                                );
+                            })
+                            .ok()
+                    });
 
-                let mut revocation_endpoint = self.inner.origin.clone();
-                revocation_endpoint.set_path(OAUTH2_TOKEN_REVOKE_ENDPOINT);
+                let authorisation_details_types = ent
+                    .get_ava_set(Attribute::OAuth2RsAuthorisationDetailsType)
+                    .and_then(|vs| vs.as_utf8_set())
+                    .cloned()
+                    .unwrap_or_default();
 
-                let mut introspection_endpoint = self.inner.origin.clone();
-                introspection_endpoint.set_path(OAUTH2_TOKEN_INTROSPECT_ENDPOINT);
+                let require_par = ent
+                    .get_ava_single_bool(Attribute::OAuth2RequirePar)
+                    .unwrap_or(false);
 
-                let mut userinfo_endpoint = self.inner.origin.clone();
-                userinfo_endpoint.set_path(&format!("/oauth2/openid/{name}/userinfo"));
+                let require_dpop = ent
+                    .get_ava_single_bool(Attribute::OAuth2RequireDpop)
+                    .unwrap_or(false);
 
-                let mut jwks_uri = self.inner.origin.clone();
-                jwks_uri.set_path(&format!("/oauth2/openid/{name}/public_key.jwk"));
+                let enable_ciba = ent
+                    .get_ava_single_bool(Attribute::OAuth2CibaEnable)
+                    .unwrap_or(false);
+
+                let pairwise_subject_key = if ent
+                    .get_ava_single_bool(Attribute::OAuth2PairwiseSubjectEnable)
+                    .unwrap_or(false)
+                {
+                    let Some(pairwise_key_object) =
+                        key_providers.get_key_object_handle(UUID_DOMAIN_OAUTH2_PAIRWISE_KEY)
+                    else {
+                        error!("OAuth2 pairwise subject key object is missing!");
+                        return Err(OperationError::KP0078KeyObjectNotFound);
+                    };
+
+                    // This is always derived from the newest key. The key is never rotated
+                    // automatically since doing so changes the subjects of every client that
+                    // uses pairwise subjects.
+                    let mut pairwise_subject_key = HmacSha256Key::default();
+                    pairwise_key_object.hkdf_s256_expand(
+                        sector_identifier.as_bytes(),
+                        pairwise_subject_key.as_mut_slice(),
+                        Duration::MAX,
+                    )?;
+                    Some(pairwise_subject_key)
+                } else {
+                    None
+                };
+
+                let tls_client_auth_subject_dn = ent
+                    .get_ava_single_utf8(Attribute::OAuth2RsTlsClientAuthSubjectDn)
+                    .map(str::to_string);
+
+                let tls_client_certificates = ent
+                    .get_ava_set(Attribute::OAuth2RsTlsClientCertificate)
+                    .and_then(|vs| vs.as_certificate_set())
+                    .map(|certs| certs.keys().cloned().collect())
+                    .unwrap_or_default();
+
+                let registration_token_id =
+                    ent.get_ava_single_uuid(Attribute::OAuth2RsRegistrationTokenId);
+
+                let mut authorization_endpoint = self.inner.origin.clone();
+                authorization_endpoint.set_path("/ui/oauth2");
+
+                let mut token_endpoint = self.inner.origin.clone();
+                token_endpoint.set_path(uri::OAUTH2_TOKEN_ENDPOINT);
+
+                let mut revocation_endpoint = self.inner.origin.clone();
+                revocation_endpoint.set_path(OAUTH2_TOKEN_REVOKE_ENDPOINT);
+
+                let mut introspection_endpoint = self.inner.origin.clone();
+                introspection_endpoint.set_path(OAUTH2_TOKEN_INTROSPECT_ENDPOINT);
+
+                let mut userinfo_endpoint = self.inner.origin.clone();
+                userinfo_endpoint.set_path(&format!("/oauth2/openid/{name}/userinfo"));
+
+                let mut end_session_endpoint = self.inner.origin.clone();
+                end_session_endpoint.set_path(&format!("/oauth2/openid/{name}/end_session"));
+
+                let mut par_endpoint = self.inner.origin.clone();
+                par_endpoint.set_path(uri::OAUTH2_PUSHED_AUTHORISATION_ENDPOINT);
+
+                let mut registration_endpoint = self.inner.origin.clone();
+                registration_endpoint.set_path(uri::OAUTH2_REGISTRATION_ENDPOINT);
+
+                let mut backchannel_authentication_endpoint = self.inner.origin.clone();
+                backchannel_authentication_endpoint
+                    .set_path(uri::OAUTH2_BACKCHANNEL_AUTHENTICATION_ENDPOINT);
+
+                let mut jwks_uri = self.inner.origin.clone();
+                jwks_uri.set_path(&format!("/oauth2/openid/{name}/public_key.jwk"));
 
                 let mut iss = self.inner.origin.clone();
                 iss.set_path(&format!("/oauth2/openid/{name}"));
@@ -935,8 +2104,23 @@ impl Oauth2ResourceServersWriteTransaction<'_> {
                     revocation_endpoint,
                     introspection_endpoint,
                     userinfo_endpoint,
+                    end_session_endpoint,
+                    par_endpoint,
+                    registration_endpoint,
+                    backchannel_authentication_endpoint,
                     jwks_uri,
                     scopes_supported,
+                    backchannel_logout_uri,
+                    client_jwks,
+                    encrypted_response_alg,
+                    authorisation_details_types,
+                    require_par,
+                    enable_ciba,
+                    pairwise_subject_key,
+                    require_dpop,
+                    tls_client_auth_subject_dn,
+                    tls_client_certificates,
+                    registration_token_id,
                     prefer_short_username,
                     type_,
                     has_custom_image,
@@ -955,6 +2139,63 @@ impl Oauth2ResourceServersWriteTransaction<'_> {
         })
     }
 
+    /// Sign a logout token for each terminated session that belongs to a client with
+    /// a back-channel logout uri. Sessions of other clients are ignored.
+    pub(crate) fn backchannel_logout_tokens(
+        &self,
+        revoked_sessions: &BTreeSet<RevokedOauth2Session>,
+        ct: Duration,
+    ) -> Vec<Oauth2BackchannelLogout> {
+        let iat = ct.as_secs() as i64;
+
+        revoked_sessions
+            .iter()
+            .filter_map(|revoked| {
+                let o2rs = self
+                    .inner
+                    .private_rs_set
+                    .values()
+                    .find(|o2rs| o2rs.uuid == revoked.rs_uuid)?;
+
+                let backchannel_logout_uri = o2rs.backchannel_logout_uri.clone()?;
+
+                let logout_token = OidcLogoutToken {
+                    iss: o2rs.iss.clone(),
+                    sub: o2rs.subject(revoked.account_uuid),
+                    aud: o2rs.name.clone(),
+                    iat,
+                    exp: iat + OAUTH2_LOGOUT_TOKEN_EXPIRY as i64,
+                    jti: Uuid::new_v4(),
+                    events: BTreeMap::from([(
+                        OIDC_BACKCHANNEL_LOGOUT_EVENT.to_string(),
+                        serde_json::json!({}),
+                    )]),
+                    sid: revoked.session_id,
+                };
+
+                trace!(?logout_token);
+                let logout_token = JwsBuilder::into_json(&logout_token)
+                    .map(|builder| builder.set_typ(Some("logout+jwt")).build())
+                    .inspect_err(|err| error!(?err, "Unable to encode logout token data"))
+                    .ok()?;
+
+                let logout_token = match o2rs.sign_alg {
+                    SignatureAlgo::Es256 => o2rs.key_object.jws_es256_sign(&logout_token, ct),
+                    SignatureAlgo::Rs256 => o2rs.key_object.jws_rs256_sign(&logout_token, ct),
+                }
+                .inspect_err(|err| error!(?err, "Unable to sign logout token"))
+                .ok()?;
+
+                Some(Oauth2BackchannelLogout {
+                    rs_uuid: o2rs.uuid,
+                    session_id: revoked.session_id,
+                    backchannel_logout_uri,
+                    logout_token: logout_token.to_string(),
+                })
+            })
+            .collect()
+    }
+
     pub fn commit(self) {
         self.inner.commit();
     }
@@ -1013,12 +2254,19 @@ impl IdmServerProxyWriteTransaction<'_> {
                 })?;
 
             let OAuth2RFC9068Token::<_> {
-                sub: uuid,
+                sub,
                 exp,
                 extensions: OAuth2RFC9068TokenExtensions { session_id, .. },
                 ..
             } = access_token;
 
+            let Some(uuid) = oauth2_subject_account_uuid(&mut self.qs_write, o2rs, sub, session_id)
+                .map_err(Oauth2Error::ServerError)?
+            else {
+                security_info!(?sub, "token subject has no matching session, ignoring");
+                return Ok(());
+            };
+
             (session_id, exp, uuid)
         } else {
             // Assume it's encrypted.
@@ -1087,6 +2335,191 @@ impl IdmServerProxyWriteTransaction<'_> {
             })
     }
 
+    /// OpenID Connect RP-Initiated Logout. The client's session is ended. When the client's
+    /// session was issued from the user's session with Kanidm in this user agent, that session
+    /// and the other OAuth2 sessions issued from it are also ended. Returns the uri that the
+    /// user agent should be redirected to, if the client requested one.
+    #[instrument(level = "debug", skip_all)]
+    pub fn oauth2_openid_end_session(
+        &mut self,
+        client_id: &str,
+        client_auth_info: ClientAuthInfo,
+        end_session_req: &EndSessionRequest,
+        ct: Duration,
+    ) -> Result<Option<Url>, Oauth2Error> {
+        let o2rs = self.oauth2rs.inner.rs_set_get(client_id).ok_or_else(|| {
+            warn!("Invalid OAuth2 client_id (have you configured the OAuth2 resource server?)");
+            Oauth2Error::InvalidClientId
+        })?;
+
+        if end_session_req
+            .client_id
+            .as_ref()
+            .is_some_and(|req_client_id| req_client_id != &o2rs.name)
+        {
+            warn!("OAuth2 end session client_id does not match the client of the endpoint");
+            return Err(Oauth2Error::InvalidRequest);
+        }
+
+        // Without the id token we can't tell that the request came from the client, and so
+        // anyone could end the users sessions or use us as an open redirect.
+        let Some(id_token_hint) = end_session_req.id_token_hint.as_ref() else {
+            warn!("OAuth2 end session request is missing an id_token_hint");
+            return Err(Oauth2Error::InvalidRequest);
+        };
+
+        // The id token may have expired, which is expected if the user has been idle. We only
+        // need to know that we issued it to this client.
+        let id_token: OidcToken = JwsCompact::from_str(id_token_hint)
+            .map_err(|err| {
+                warn!(?err, "Unable to deserialise id_token_hint");
+                Oauth2Error::InvalidRequest
+            })
+            .and_then(|jwsc| {
+                o2rs.key_object.jws_verify(&jwsc).map_err(|err| {
+                    warn!(?err, "Unable to verify id_token_hint");
+                    Oauth2Error::InvalidRequest
+                })
+            })
+            .and_then(|jws| {
+                jws.from_json().map_err(|err| {
+                    warn!(?err, "Unable to deserialise id_token_hint");
+                    Oauth2Error::InvalidRequest
+                })
+            })?;
+
+        if id_token.iss != o2rs.iss || id_token.aud != o2rs.name {
+            warn!("id_token_hint was not issued to this client");
+            return Err(Oauth2Error::InvalidRequest);
+        }
+
+        let OidcSubject::U(subject) = id_token.sub else {
+            warn!("id_token_hint has an invalid subject");
+            return Err(Oauth2Error::InvalidRequest);
+        };
+
+        let session_id = id_token
+            .jti
+            .as_deref()
+            .and_then(|jti| Uuid::parse_str(jti).ok())
+            .ok_or_else(|| {
+                warn!("id_token_hint has an invalid session id");
+                Oauth2Error::InvalidRequest
+            })?;
+
+        let account_uuid =
+            oauth2_subject_account_uuid(&mut self.qs_write, o2rs, subject, session_id)
+                .map_err(Oauth2Error::ServerError)?
+                .ok_or_else(|| {
+                    warn!("Unable to find the account of the id_token_hint");
+                    Oauth2Error::InvalidRequest
+                })?;
+
+        let redirect_uri = end_session_req
+            .post_logout_redirect_uri
+            .as_ref()
+            .map(|post_logout_redirect_uri| {
+                check_redirect_uri(o2rs, post_logout_redirect_uri).map(|()| {
+                    let mut redirect_uri = post_logout_redirect_uri.clone();
+                    if let Some(state) = end_session_req.state.as_deref() {
+                        redirect_uri.query_pairs_mut().append_pair("state", state);
+                    }
+                    redirect_uri
+                })
+            })
+            .transpose()?;
+
+        // The session of this user agent, if it has one.
+        let ua_session_id = match self.validate_client_auth_info_to_ident(client_auth_info, ct) {
+            Ok(ident) if ident.can_logout() => {
+                if ident.get_uuid() != Some(account_uuid) {
+                    warn!(
+                        "id_token_hint is for a different account to the session of the user agent"
+                    );
+                    return Err(Oauth2Error::InvalidRequest);
+                }
+                Some(ident.get_session_id())
+            }
+            Ok(_) | Err(OperationError::SessionExpired) | Err(OperationError::NotAuthenticated) => {
+                None
+            }
+            Err(err) => {
+                error!(?err, "Invalid identity");
+                return Err(Oauth2Error::ServerError(err));
+            }
+        };
+
+        let account = self
+            .qs_write
+            .internal_search_uuid(account_uuid)
+            .map_err(|err| {
+                warn!(?err, "Unable to find the account of the id_token_hint");
+                Oauth2Error::InvalidRequest
+            })?;
+
+        let oauth2_sessions = account.get_ava_as_oauth2session_map(Attribute::OAuth2Session);
+
+        // The session the client's session was issued from.
+        let parent_session_id = oauth2_sessions
+            .and_then(|sessions| sessions.get(&session_id))
+            .and_then(|session| session.parent);
+
+        // The id_token_hint may have expired, or been taken from another user agent, so it is
+        // only enough to end the client's own session. The Kanidm session and the other OAuth2
+        // sessions issued from it are only ended when it is the session of this user agent.
+        let (uat_session_ids, oauth2_session_ids) = match (ua_session_id, parent_session_id) {
+            (Some(ua_session_id), Some(parent_session_id))
+                if ua_session_id == parent_session_id =>
+            {
+                let oauth2_session_ids: BTreeSet<Uuid> = oauth2_sessions
+                    .into_iter()
+                    .flat_map(|sessions| sessions.iter())
+                    .filter(|(_, session)| session.parent == Some(parent_session_id))
+                    .map(|(oauth2_session_id, _)| *oauth2_session_id)
+                    .chain(std::iter::once(session_id))
+                    .collect();
+
+                (BTreeSet::from([parent_session_id]), oauth2_session_ids)
+            }
+            _ => {
+                debug!("id_token_hint was not issued from the session of the user agent");
+                (BTreeSet::new(), BTreeSet::from([session_id]))
+            }
+        };
+
+        // As with revocation, we submit the removals even if the sessions have not yet
+        // replicated to this server.
+        let modlist: ModifyList<ModifyInvalid> = ModifyList::new_list(
+            uat_session_ids
+                .into_iter()
+                .map(|uat_session_id| {
+                    Modify::Removed(
+                        Attribute::UserAuthTokenSession,
+                        PartialValue::Refer(uat_session_id),
+                    )
+                })
+                .chain(oauth2_session_ids.into_iter().map(|oauth2_session_id| {
+                    Modify::Removed(
+                        Attribute::OAuth2Session,
+                        PartialValue::Refer(oauth2_session_id),
+                    )
+                }))
+                .collect(),
+        );
+
+        self.qs_write
+            .internal_modify(
+                &filter!(f_eq(Attribute::Uuid, PartialValue::Uuid(account_uuid))),
+                &modlist,
+            )
+            .map_err(|e| {
+                admin_error!("Failed to modify - end OAuth2 session {:?}", e);
+                Oauth2Error::ServerError(e)
+            })?;
+
+        Ok(redirect_uri)
+    }
+
     #[instrument(level = "debug", skip_all)]
     pub fn check_oauth2_token_exchange(
         &mut self,
@@ -1094,46 +2527,111 @@ impl IdmServerProxyWriteTransaction<'_> {
         token_req: &AccessTokenRequest,
         ct: Duration,
     ) -> Result<AccessTokenResponse, Oauth2Error> {
-        // Public clients will send the client_id via the ATR, so we need to handle this case.
-        let client_auth = get_client_auth(client_auth_info, &token_req.client_post_auth)?;
-
-        let o2rs = self.get_client(&client_auth.client_id)?;
         let is_token_exchange = matches!(token_req.grant_type, GrantTypeReq::TokenExchange { .. });
 
-        // check the secret.
-        let client_authentication_valid = match (&o2rs.type_, is_token_exchange) {
-            (OauthRSType::Basic { .. }, true) => {
-                if client_auth.client_secret.is_some() {
-                    security_info!(
-                        "Client secret is not accepted when exchanging a service account token"
-                    );
-                    return Err(Oauth2Error::InvalidRequest);
-                }
-                true
+        let client_post_auth = &token_req.client_post_auth;
+        let (o2rs, client_authentication_valid) = if client_post_auth.client_assertion.is_some()
+            || client_post_auth.client_assertion_type.is_some()
+        {
+            if is_token_exchange {
+                security_info!(
+                    "Client assertion is not accepted when exchanging a service account token"
+                );
+                return Err(Oauth2Error::InvalidRequest);
             }
-            (OauthRSType::Basic { authz_secret, .. }, false) => {
-                match client_auth.client_secret {
-                    Some(secret) => {
-                        if authz_secret == &secret {
-                            true
-                        } else {
-                            info!("Invalid OAuth2 client_id secret");
-                            return Err(Oauth2Error::AuthenticationRequired);
-                        }
-                    }
-                    None => {
-                        // We can only get here if we relied on the atr for the client_id and secret
-                        info!(
-                            "Invalid OAuth2 authentication - no secret in access token request - this can happen if you're expecting a public client and configured a basic one."
+
+            let o2rs =
+                self.check_oauth2_client_assertion(client_auth_info, client_post_auth, ct)?;
+            (o2rs, true)
+        } else {
+            // Public clients will send the client_id via the ATR, so we need to handle this case.
+            let client_auth = get_client_auth(client_auth_info, client_post_auth)?;
+
+            let o2rs = self.get_client(&client_auth.client_id)?;
+
+            // check the secret.
+            let client_authentication_valid = match (&o2rs.type_, is_token_exchange) {
+                (OauthRSType::Basic { .. }, true) => {
+                    if client_auth.client_secret.is_some() {
+                        security_info!(
+                            "Client secret is not accepted when exchanging a service account token"
                         );
-                        return Err(Oauth2Error::AuthenticationRequired);
+                        return Err(Oauth2Error::InvalidRequest);
+                    }
+                    true
+                }
+                (OauthRSType::Basic { authz_secret, .. }, false) => {
+                    match client_auth.client_secret {
+                        Some(secret) => {
+                            if authz_secret == &secret {
+                                true
+                            } else {
+                                info!("Invalid OAuth2 client_id secret");
+                                return Err(Oauth2Error::AuthenticationRequired);
+                            }
+                        }
+                        None => match client_auth_info.tls_client_cert() {
+                            Some(client_cert)
+                                if o2rs.client_certificate_valid(
+                                    client_cert,
+                                    self.oauth2_client_ca,
+                                    ct,
+                                ) =>
+                            {
+                                true
+                            }
+                            Some(_) => {
+                                info!(
+                                    "Invalid OAuth2 authentication - the client certificate does not authenticate this client"
+                                );
+                                return Err(Oauth2Error::AuthenticationRequired);
+                            }
+                            None => {
+                                // We can only get here if we relied on the atr for the client_id and secret
+                                info!(
+                                    "Invalid OAuth2 authentication - no secret in access token request - this can happen if you're expecting a public client and configured a basic one."
+                                );
+                                return Err(Oauth2Error::AuthenticationRequired);
+                            }
+                        },
                     }
                 }
+                // Relies on the token to be valid - no further action needed.
+                (OauthRSType::Public { .. }, _) => false,
+            };
+
+            (o2rs, client_authentication_valid)
+        };
+
+        // If the client presented a DPoP proof, the tokens we issue are bound to its key.
+        let dpop_jkt = match client_auth_info.dpop_proof.as_ref() {
+            Some(dpop_proof) => {
+                let verified = verify_dpop_proof(dpop_proof, &o2rs.token_endpoint, None, ct)?;
+                consume_dpop_proof(&self.oauth2_dpop_proofs, &verified, ct)?;
+
+                Some(verified.jkt)
             }
-            // Relies on the token to be valid - no further action needed.
-            (OauthRSType::Public { .. }, _) => false,
+            None => None,
         };
 
+        if o2rs.require_dpop && dpop_jkt.is_none() {
+            security_info!("Client requires DPoP, but no DPoP proof was provided");
+            return Err(Oauth2Error::InvalidDpopProof);
+        }
+
+        // Likewise if the client connected with a certificate that authenticates it, the tokens
+        // are bound to that certificate. Any other certificate on the connection was never
+        // checked against this client, so it can not be trusted to hold the binding.
+        let x5t_s256 = client_auth_info
+            .tls_client_cert()
+            .filter(|client_cert| {
+                o2rs.client_certificate_valid(client_cert, self.oauth2_client_ca, ct)
+            })
+            .map(certificate_thumbprint)
+            .transpose()?;
+
+        let binding = TokenBinding { dpop_jkt, x5t_s256 };
+
         // We are authenticated! Yay! Now we can actually check things ...
         match &token_req.grant_type {
             GrantTypeReq::AuthorizationCode {
@@ -1145,11 +2643,12 @@ impl IdmServerProxyWriteTransaction<'_> {
                 code,
                 redirect_uri,
                 code_verifier.as_deref(),
+                binding,
                 ct,
             ),
             GrantTypeReq::ClientCredentials { scope } => {
                 if client_authentication_valid {
-                    self.check_oauth2_token_client_credentials(&o2rs, scope.as_ref(), ct)
+                    self.check_oauth2_token_client_credentials(&o2rs, scope.as_ref(), binding, ct)
                 } else {
                     security_info!(
                         "Unable to proceed with client credentials grant unless client authentication is provided and valid"
@@ -1160,7 +2659,7 @@ impl IdmServerProxyWriteTransaction<'_> {
             GrantTypeReq::RefreshToken {
                 refresh_token,
                 scope,
-            } => self.check_oauth2_token_refresh(&o2rs, refresh_token, scope.as_ref(), ct),
+            } => self.check_oauth2_token_refresh(&o2rs, refresh_token, scope.as_ref(), binding, ct),
             GrantTypeReq::TokenExchange {
                 subject_token,
                 subject_token_type,
@@ -1184,2313 +2683,3710 @@ impl IdmServerProxyWriteTransaction<'_> {
                     audience.as_deref(),
                     resource.as_deref(),
                     scope.as_ref(),
+                    binding,
                     ct,
                 )
             }
+            GrantTypeReq::Ciba { auth_req_id } => {
+                if client_authentication_valid {
+                    self.check_oauth2_token_backchannel(&o2rs, auth_req_id, binding, ct)
+                } else {
+                    security_info!(
+                        "Unable to proceed with backchannel authentication grant unless client authentication is provided and valid"
+                    );
+                    Err(Oauth2Error::AuthenticationRequired)
+                }
+            }
             GrantTypeReq::DeviceCode { device_code, scope } => {
                 self.check_oauth2_device_code_status(device_code, scope)
             }
         }
     }
 
-    fn get_client(&self, client_id: &str) -> Result<Oauth2RS, Oauth2Error> {
-        let s = self
-            .oauth2rs
-            .inner
-            .rs_set_get(client_id)
-            .ok_or_else(|| {
-                warn!("Invalid OAuth2 client_id {}", client_id);
-                Oauth2Error::AuthenticationRequired
-            })?
-            .clone();
-        Ok(s)
-    }
-
-    #[instrument(level = "info", skip(self))]
-    pub fn handle_oauth2_start_device_flow(
+    /// Authenticate a client with a signed JWT as per RFC7523. Assertions signed with
+    /// HS256 are keyed by the client secret (client_secret_jwt), while ES256 and RS256
+    /// assertions must be signed by a key registered to the client (private_key_jwt).
+    fn check_oauth2_client_assertion(
         &mut self,
-        _client_auth_info: ClientAuthInfo,
-        _client_id: &str,
-        _scope: &Option<BTreeSet<String>>,
-        _eventid: Uuid,
-    ) -> Result<DeviceAuthorizationResponse, Oauth2Error> {
-        // let o2rs = self.get_client(client_id)?;
+        client_auth_info: &ClientAuthInfo,
+        client_post_auth: &ClientPostAuth,
+        ct: Duration,
+    ) -> Result<Oauth2RS, Oauth2Error> {
+        if client_auth_info.basic_authz.is_some() || client_post_auth.client_secret.is_some() {
+            security_info!(
+                "Client assertion can not be combined with other client authentication methods"
+            );
+            return Err(Oauth2Error::InvalidRequest);
+        }
 
-        // info!("Got Client: {:?}", o2rs);
+        if client_post_auth.client_assertion_type.as_deref()
+            != Some(OAUTH2_CLIENT_ASSERTION_TYPE_JWT_BEARER)
+        {
+            security_info!(client_assertion_type = ?client_post_auth.client_assertion_type, "Unsupported client assertion type");
+            return Err(Oauth2Error::InvalidRequest);
+        }
 
-        // // TODO: change this to checking if it's got device flow enabled
-        // if !o2rs.require_pkce() {
-        //     security_info!("Device flow is only available for PKCE-enabled clients");
-        //     return Err(Oauth2Error::InvalidRequest);
-        // }
+        let Some(client_assertion) = client_post_auth.client_assertion.as_deref() else {
+            security_info!("Client assertion type was provided without a client assertion");
+            return Err(Oauth2Error::InvalidRequest);
+        };
 
-        // info!(
-        //     "Starting device flow for client_id={} scopes={} source={:?}",
-        //     client_id,
-        //     scope
-        //         .as_ref()
-        //         .map(|s| s.iter().cloned().collect::<Vec<_>>().into_iter().join(","))
-        //         .unwrap_or("[]".to_string()),
-        //     client_auth_info.source
-        // );
+        let jwsc = JwsCompact::from_str(client_assertion).map_err(|err| {
+            security_info!(?err, "Unable to parse client assertion");
+            Oauth2Error::InvalidRequest
+        })?;
 
-        // let mut verification_uri = self.oauth2rs.inner.origin.clone();
-        // verification_uri.set_path(uri::OAUTH2_DEVICE_LOGIN);
+        // The client_id may be omitted when an assertion is used, in which case the subject
+        // names the client. This only selects the keys to verify with, the claims are
+        // checked again once the signature is valid.
+        let client_id = match &client_post_auth.client_id {
+            Some(client_id) => client_id.clone(),
+            None => client_assertion_unverified_subject(client_assertion)?,
+        };
 
-        // let (user_code_string, _user_code) = gen_user_code();
-        // let expiry =
-        //     Duration::from_secs(OAUTH2_DEVICE_CODE_EXPIRY_SECONDS) + duration_from_epoch_now();
-        // let device_code = gen_device_code()
-        //     .inspect_err(|err| error!("Failed to generate a device code! {:?}", err))?;
+        let o2rs = self.get_client(&client_id)?;
 
-        Err(Oauth2Error::InvalidGrant)
+        if !o2rs.is_basic() {
+            security_info!("Public clients can not authenticate with a client assertion");
+            return Err(Oauth2Error::AuthenticationRequired);
+        }
 
-        // TODO: store user_code / expiry / client_id / device_code in the backend, needs to be checked on the token exchange.
-        // Ok(DeviceAuthorizationResponse::new(
-        //     verification_uri,
-        //     device_code,
-        //     user_code_string,
-        // ))
-    }
+        let Some(jws) = o2rs.verify_client_jws(&jwsc)? else {
+            security_info!(alg = ?jwsc.alg(), "Unable to verify client assertion signature");
+            return Err(Oauth2Error::AuthenticationRequired);
+        };
 
-    #[instrument(level = "info", skip(self))]
-    fn check_oauth2_device_code_status(
-        &mut self,
-        device_code: &str,
-        scope: &Option<BTreeSet<String>>,
-    ) -> Result<AccessTokenResponse, Oauth2Error> {
-        // TODO: check the device code is valid, do the needful
-
-        error!(
-            "haven't done the device grant yet! Got device_code={} scope={:?}",
-            device_code, scope
-        );
-        Err(Oauth2Error::AuthorizationPending)
-
-        // if it's an expired code, then just delete it from the db and return an error.
-        // Err(Oauth2Error::ExpiredToken)
-    }
-
-    #[instrument(level = "debug", skip_all)]
-    pub fn check_oauth2_authorise_permit(
-        &mut self,
-        ident: &Identity,
-        consent_token: &str,
-        ct: Duration,
-    ) -> Result<AuthorisePermitSuccess, OperationError> {
-        let Some(account_uuid) = ident.get_uuid() else {
-            error!("consent request ident does not have a valid uuid, unable to proceed");
-            return Err(OperationError::InvalidSessionState);
-        };
-
-        let consent_token_jwe = JweCompact::from_str(consent_token).map_err(|err| {
-            error!(?err, "Consent token is not a valid jwe compact");
-            OperationError::InvalidSessionState
+        let claims: ClientAssertionClaims = jws.from_json().map_err(|err| {
+            security_info!(?err, "Client assertion claims are invalid");
+            Oauth2Error::InvalidRequest
         })?;
 
-        let consent_req: ConsentToken = self
-            .oauth2rs
-            .inner
-            .consent_key
-            .decipher(&consent_token_jwe)
-            .map_err(|err| {
-                error!(?err, "Failed to decrypt consent request");
-                OperationError::CryptographyError
-            })
-            .and_then(|jwe| {
-                jwe.from_json().map_err(|err| {
-                    error!(?err, "Failed to deserialise consent request");
-                    OperationError::SerdeJsonError
-                })
-            })?;
-
-        // Validate that the ident_id matches our current ident.
-        if consent_req.ident_id != ident.get_event_origin_id() {
-            security_info!("consent request ident id does not match the identity of our UAT.");
-            return Err(OperationError::InvalidSessionState);
+        if claims.iss != o2rs.name || claims.sub != o2rs.name {
+            security_info!(iss = %claims.iss, sub = %claims.sub, "Client assertion issuer and subject must be the client_id");
+            return Err(Oauth2Error::AuthenticationRequired);
         }
 
-        // Validate that the session id matches our uat.
-        if consent_req.session_id != ident.get_session_id() {
-            security_info!("consent request session id does not match the session id of our UAT.");
-            return Err(OperationError::InvalidSessionState);
+        if !claims.aud.iter().any(|aud| {
+            aud == o2rs.token_endpoint.as_str()
+                || aud == o2rs.par_endpoint.as_str()
+                || aud == o2rs.iss.as_str()
+        }) {
+            security_info!(aud = ?claims.aud, "Client assertion audience does not contain this token endpoint");
+            return Err(Oauth2Error::AuthenticationRequired);
         }
 
-        if consent_req.expiry <= ct.as_secs() {
-            // Token is expired
-            error!("Failed to decrypt consent request");
-            return Err(OperationError::CryptographyError);
+        let now = ct.as_secs() as i64;
+        if claims.exp <= now {
+            security_info!("Client assertion has expired");
+            return Err(Oauth2Error::AuthenticationRequired);
         }
 
-        // The exchange must be performed in the next 60 seconds.
-        let expiry = ct.as_secs() + 60;
-
-        // Get the resource server config based on this client_id.
-        let o2rs = self
-            .oauth2rs
-            .inner
-            .rs_set_get(&consent_req.client_id)
-            .ok_or_else(|| {
-                admin_error!("Invalid consent request OAuth2 client_id");
-                OperationError::InvalidRequestState
-            })?;
+        if claims.exp > now + OAUTH2_CLIENT_ASSERTION_MAX_EXPIRY as i64 {
+            security_info!("Client assertion expiry exceeds the maximum allowed validity");
+            return Err(Oauth2Error::AuthenticationRequired);
+        }
 
-        // Extract the state, code challenge, redirect_uri
-        let xchg_code = TokenExchangeCode {
-            account_uuid,
-            session_id: ident.get_session_id(),
-            expiry,
-            code_challenge: consent_req.code_challenge,
-            redirect_uri: consent_req.redirect_uri.clone(),
-            scopes: consent_req.scopes.clone(),
-            nonce: consent_req.nonce,
-        };
+        if claims.nbf.is_some_and(|nbf| nbf > now) {
+            security_info!("Client assertion is not yet valid");
+            return Err(Oauth2Error::AuthenticationRequired);
+        }
 
-        // Encrypt the exchange token
-        let code_data_jwe = Jwe::into_json(&xchg_code).map_err(|err| {
-            error!(?err, "Unable to encode xchg_code data");
-            OperationError::SerdeJsonError
+        // The assertion is consumed here, regardless of whether the rest of this request
+        // succeeds and the transaction commits.
+        let mut used_assertions = self.oauth2_client_assertions.lock().map_err(|_| {
+            admin_error!("Client assertion cache lock is poisoned");
+            Oauth2Error::ServerError(OperationError::InvalidState)
         })?;
 
-        let code = o2rs
-            .key_object
-            .jwe_a128gcm_encrypt(&code_data_jwe, ct)
-            .map(|code| code.to_string())
-            .map_err(|err| {
-                error!(?err, "Unable to encrypt xchg_code");
-                OperationError::CryptographyError
-            })?;
-
-        // Everything is DONE! Now submit that it's all happy and the user consented correctly.
-        // this will let them bypass consent steps in the future.
-        // Submit that we consented to the delayed action queue
-
-        let modlist = ModifyList::new_list(vec![
-            Modify::Removed(
-                Attribute::OAuth2ConsentScopeMap,
-                PartialValue::Refer(o2rs.uuid),
-            ),
-            Modify::Present(
-                Attribute::OAuth2ConsentScopeMap,
-                Value::OauthScopeMap(o2rs.uuid, consent_req.scopes.iter().cloned().collect()),
-            ),
-        ]);
+        // Forget any assertions that have expired, they can no longer be replayed.
+        *used_assertions = used_assertions.split_off(&(ct, Uuid::nil(), String::new()));
 
-        self.qs_write.internal_modify(
-            &filter_all!(f_eq(Attribute::Uuid, PartialValue::Uuid(account_uuid))),
-            &modlist,
-        )?;
+        let assertion_jti = (
+            Duration::from_secs(claims.exp as u64),
+            o2rs.uuid,
+            claims.jti,
+        );
+        if !used_assertions.insert(assertion_jti) {
+            security_info!("Client assertion has already been used");
+            return Err(Oauth2Error::AuthenticationRequired);
+        }
+        drop(used_assertions);
 
-        Ok(AuthorisePermitSuccess {
-            redirect_uri: consent_req.redirect_uri,
-            state: consent_req.state,
-            code,
-            response_mode: consent_req.response_mode,
-        })
+        Ok(o2rs)
     }
 
+    /// Store an authorisation request pushed by a client, returning the request_uri that
+    /// the client then redirects the user agent to the authorisation endpoint with.
+    /// <https://datatracker.ietf.org/doc/html/rfc9126#section-2>
     #[instrument(level = "debug", skip_all)]
-    fn check_oauth2_token_exchange_authorization_code(
+    pub fn oauth2_pushed_authorisation(
         &mut self,
-        o2rs: &Oauth2RS,
-        token_req_code: &str,
-        token_req_redirect_uri: &Url,
-        token_req_code_verifier: Option<&str>,
+        client_auth_info: &ClientAuthInfo,
+        client_post_auth: &ClientPostAuth,
+        auth_req_params: &AuthorisationRequestParams,
         ct: Duration,
-    ) -> Result<AccessTokenResponse, Oauth2Error> {
-        // Check the token_req is within the valid time, and correctly signed for
-        // this client.
-        let jwe_compact = JweCompact::from_str(token_req_code).map_err(|_| {
-            error!("Failed to deserialise a valid JWE");
-            Oauth2Error::InvalidRequest
-        })?;
-
-        let code_xchg: TokenExchangeCode = o2rs
-            .key_object
-            .jwe_decrypt(&jwe_compact)
-            .map_err(|_| {
-                admin_error!("Failed to decrypt token exchange request");
-                Oauth2Error::InvalidRequest
-            })
-            .and_then(|jwe| {
-                debug!(?jwe);
-                jwe.from_json::<TokenExchangeCode>().map_err(|err| {
-                    error!(?err, "Failed to deserialise token exchange code");
-                    Oauth2Error::InvalidRequest
-                })
-            })?;
+    ) -> Result<PushedAuthorisationResponse, Oauth2Error> {
+        let o2rs = if client_post_auth.client_assertion.is_some()
+            || client_post_auth.client_assertion_type.is_some()
+        {
+            self.check_oauth2_client_assertion(client_auth_info, client_post_auth, ct)?
+        } else {
+            let client_auth = get_client_auth(client_auth_info, client_post_auth)?;
 
-        if code_xchg.expiry <= ct.as_secs() {
-            error!("Expired token exchange request");
-            return Err(Oauth2Error::InvalidRequest);
-        }
+            let o2rs = self.get_client(&client_auth.client_id)?;
 
-        // If we have a verifier present, we MUST assert that a code challenge is present!
-        // It is worth noting here that code_xchg is *server issued* and encrypted, with
-        // a short validity period. The client controlled value is in token_req.code_verifier
-        if let Some(code_challenge) = code_xchg.code_challenge {
-            // Validate the code_verifier
-            let code_verifier = token_req_code_verifier
-                    .ok_or_else(|| {
-                        security_info!("PKCE code verification failed - code challenge is present, but no verifier was provided");
-                        Oauth2Error::InvalidRequest
-                    })?;
+            // Public clients can only identify themselves, as they would at the
+            // authorisation endpoint.
+            if let OauthRSType::Basic { authz_secret, .. } = &o2rs.type_ {
+                if client_auth.client_secret.as_ref() != Some(authz_secret) {
+                    info!("Invalid OAuth2 client_id secret");
+                    return Err(Oauth2Error::AuthenticationRequired);
+                }
+            }
 
-            let verifier_secret = PkceS256Secret::from(code_verifier.to_string());
+            o2rs
+        };
 
-            if !verifier_secret.verify(code_challenge) {
-                security_info!(
-                    "PKCE code verification failed - this may indicate malicious activity"
-                );
+        let mut auth_req = match auth_req_params {
+            AuthorisationRequestParams::ByValue(auth_req) => (**auth_req).clone(),
+            AuthorisationRequestParams::ByReference(AuthorisationRequestRef {
+                client_id,
+                request_uri: None,
+                request: Some(request),
+            }) => o2rs.verify_request_object(
+                client_id,
+                request,
+                Some(&self.oauth2_request_objects),
+                ct,
+            )?,
+            AuthorisationRequestParams::ByReference(_) => {
+                warn!("A pushed authorisation request must not contain a request_uri");
                 return Err(Oauth2Error::InvalidRequest);
             }
-        } else if o2rs.require_pkce() {
-            security_info!(
-                "PKCE code verification failed - no code challenge present in PKCE enforced mode"
-            );
-            return Err(Oauth2Error::InvalidRequest);
-        } else if token_req_code_verifier.is_some() {
-            security_info!(
-                "PKCE code verification failed - a code verifier is present, but no code challenge in exchange"
-            );
+        };
+
+        if auth_req.client_id.to_lowercase() != o2rs.name {
+            security_info!(client_id = %auth_req.client_id, "Pushed authorisation request client_id does not match the authenticated client");
             return Err(Oauth2Error::InvalidRequest);
         }
 
-        // Validate the redirect_uri is the same as the original.
-        if token_req_redirect_uri != &code_xchg.redirect_uri {
-            security_info!("Invalid OAuth2 redirect_uri (differs from original request uri)");
-            return Err(Oauth2Error::InvalidOrigin);
+        validate_authorisation_request(&o2rs, &auth_req)?;
+
+        // The client authenticates in the same form as the request, so these must not
+        // be retained with it.
+        for key in ["client_secret", "client_assertion", "client_assertion_type"] {
+            auth_req.unknown_keys.remove(key);
         }
 
-        /*
-        // Check that the UAT we are issuing for still is valid.
-        //
-        // Not sure this is actually needed. To create the token exchange code you need to have
-        // a valid, non-expired session, so why do we double check this here?
-        let odt_ct = OffsetDateTime::UNIX_EPOCH + ct;
-        if let Some(expiry) = code_xchg.uat.expiry {
-            if expiry <= odt_ct {
-                security_info!(
-                    "User Auth Token has expired before we could publish the OAuth2 response"
-                );
-                return Err(Oauth2Error::AccessDenied);
-            }
+        // Forget any pushed requests that have expired.
+        let expired: Vec<Uuid> = self
+            .oauth2_pushed_authorisations
+            .iter()
+            .filter_map(|(request_id, pushed)| (pushed.expiry <= ct).then_some(*request_id))
+            .collect();
+        for request_id in expired {
+            self.oauth2_pushed_authorisations.remove(&request_id);
         }
-        */
 
-        // ==== We are now GOOD TO GO! ====
-        // Grant the access token response.
-        let parent_session_id = code_xchg.session_id;
-        let session_id = Uuid::new_v4();
+        let request_id = Uuid::new_v4();
+        let expires_in = OAUTH2_PUSHED_AUTHORISATION_EXPIRY_SECONDS;
 
-        let scopes = code_xchg.scopes;
-        let account_uuid = code_xchg.account_uuid;
-        let nonce = code_xchg.nonce;
+        self.oauth2_pushed_authorisations.insert(
+            request_id,
+            PushedAuthorisation {
+                expiry: ct + Duration::from_secs(expires_in),
+                rs_uuid: o2rs.uuid,
+                auth_req,
+            },
+        );
 
-        self.generate_access_token_response(
-            o2rs,
-            ct,
-            scopes,
-            account_uuid,
-            parent_session_id,
-            session_id,
-            nonce,
-        )
+        Ok(PushedAuthorisationResponse {
+            request_uri: format!("{OAUTH2_PUSHED_AUTHORISATION_REQUEST_URI_PREFIX}{request_id}"),
+            expires_in,
+        })
     }
 
+    /// Start a client initiated backchannel authentication in poll mode. The user named by
+    /// the login_hint is asked to approve the request from their own device, while the
+    /// client polls the token endpoint with the returned auth_req_id.
+    /// <https://openid.net/specs/openid-client-initiated-backchannel-authentication-core-1_0.html#auth_request>
     #[instrument(level = "debug", skip_all)]
-    fn check_oauth2_token_refresh(
+    pub fn oauth2_backchannel_authentication(
         &mut self,
-        o2rs: &Oauth2RS,
-        refresh_token: &str,
-        req_scopes: Option<&BTreeSet<String>>,
+        client_auth_info: &ClientAuthInfo,
+        client_post_auth: &ClientPostAuth,
+        bc_req: &BackchannelAuthenticationRequest,
         ct: Duration,
-    ) -> Result<AccessTokenResponse, Oauth2Error> {
-        let jwe_compact = JweCompact::from_str(refresh_token).map_err(|_| {
-            error!("Failed to deserialise a valid JWE");
-            Oauth2Error::InvalidRequest
-        })?;
+    ) -> Result<BackchannelAuthenticationResponse, Oauth2Error> {
+        let o2rs = if client_post_auth.client_assertion.is_some()
+            || client_post_auth.client_assertion_type.is_some()
+        {
+            self.check_oauth2_client_assertion(client_auth_info, client_post_auth, ct)?
+        } else {
+            let client_auth = get_client_auth(client_auth_info, client_post_auth)?;
 
-        // Validate the refresh token decrypts and it's expiry is within the valid window.
-        let token: Oauth2TokenType = o2rs
-            .key_object
-            .jwe_decrypt(&jwe_compact)
-            .map_err(|_| {
-                admin_error!("Failed to decrypt refresh token request");
-                Oauth2Error::InvalidRequest
-            })
-            .and_then(|jwe| {
-                jwe.from_json().map_err(|err| {
-                    error!(?err, "Failed to deserialise token");
-                    Oauth2Error::InvalidRequest
-                })
-            })?;
+            let o2rs = self.get_client(&client_auth.client_id)?;
 
-        match token {
-            // Oauth2TokenType::Access { .. } |
-            Oauth2TokenType::ClientAccess { .. } => {
-                admin_error!("attempt to refresh with an access token");
-                Err(Oauth2Error::InvalidRequest)
+            // Only confidential clients may start a backchannel authentication.
+            let OauthRSType::Basic { authz_secret, .. } = &o2rs.type_ else {
+                security_info!(?o2rs.name, "Public clients can not use backchannel authentication");
+                return Err(Oauth2Error::UnauthorizedClient);
+            };
+
+            if client_auth.client_secret.as_ref() != Some(authz_secret) {
+                info!("Invalid OAuth2 client_id secret");
+                return Err(Oauth2Error::AuthenticationRequired);
             }
-            Oauth2TokenType::Refresh {
-                scopes,
-                parent_session_id,
-                session_id,
-                exp,
-                uuid,
-                iat,
-                nbf: _,
-                nonce,
-            } => {
-                if exp <= ct.as_secs() as i64 {
-                    security_info!(?uuid, "refresh token has expired, ");
-                    return Err(Oauth2Error::InvalidGrant);
-                }
 
-                // Check the session is still valid. This call checks the parent session
-                // and the OAuth2 session.
-                let valid = self
-                    .check_oauth2_account_uuid_valid(
-                        uuid,
-                        session_id,
-                        Some(parent_session_id),
-                        iat,
-                        ct,
-                    )
-                    .map_err(|_| admin_error!("Account is not valid"));
+            o2rs
+        };
 
-                let Ok(Some(entry)) = valid else {
-                    security_info!(
-                        ?uuid,
-                        "access token has no account not valid, returning inactive"
-                    );
-                    return Err(Oauth2Error::InvalidGrant);
-                };
+        if !o2rs.enable_ciba {
+            security_info!(?o2rs.name, "OAuth2 client is not allowed to use backchannel authentication");
+            return Err(Oauth2Error::UnauthorizedClient);
+        }
 
-                // Check the not issued before of the session relative to this refresh iat
-                let oauth2_session = entry
-                    .get_ava_as_oauth2session_map(Attribute::OAuth2Session)
-                    .and_then(|map| map.get(&session_id))
-                    .ok_or_else(|| {
-                        security_info!(
-                            ?session_id,
-                            "No OAuth2 session found, unable to proceed with refresh"
-                        );
-                        Oauth2Error::InvalidGrant
-                    })?;
+        if !bc_req.scope.contains(OAUTH2_SCOPE_OPENID) {
+            warn!("Backchannel authentication requires the openid scope");
+            return Err(Oauth2Error::InvalidScope);
+        }
 
-                // If the refresh token was issued previous to the time listed in our oauth2_session
-                // this indicates session desync / replay. We must nuke the session at this point.
-                //
-                // Need to think about how to handle this nicely give transactions.
-                if iat < oauth2_session.issued_at.unix_timestamp() {
-                    security_info!(
-                        ?session_id,
-                        "Attempt to reuse a refresh token detected, destroying session"
-                    );
+        // Only poll mode is supported, where the client does not send a notification token.
+        if bc_req.client_notification_token.is_some() {
+            warn!("Backchannel authentication ping and push modes are not supported");
+            return Err(Oauth2Error::InvalidRequest);
+        }
 
-                    // Revoke it
-                    let modlist = ModifyList::new_list(vec![Modify::Removed(
-                        Attribute::OAuth2Session,
-                        PartialValue::Refer(session_id),
-                    )]);
+        if bc_req.login_hint_token.is_some()
+            || bc_req.id_token_hint.is_some()
+            || bc_req.user_code.is_some()
+        {
+            warn!("Backchannel authentication only supports a login_hint");
+            return Err(Oauth2Error::InvalidRequest);
+        }
 
-                    self.qs_write
-                        .internal_modify(
-                            &filter!(f_eq(Attribute::Uuid, PartialValue::Uuid(uuid))),
-                            &modlist,
-                        )
-                        .map_err(|e| {
-                            admin_error!("Failed to modify - revoke OAuth2 session {:?}", e);
-                            Oauth2Error::ServerError(e)
-                        })?;
+        let Some(login_hint) = bc_req.login_hint.as_deref() else {
+            warn!("Backchannel authentication requires a login_hint");
+            return Err(Oauth2Error::InvalidRequest);
+        };
 
-                    return Err(Oauth2Error::InvalidGrant);
-                }
+        if bc_req
+            .binding_message
+            .as_ref()
+            .is_some_and(|msg| msg.chars().count() > OAUTH2_BACKCHANNEL_BINDING_MESSAGE_MAX_LEN)
+        {
+            warn!("Backchannel authentication binding message is too long");
+            return Err(Oauth2Error::InvalidBindingMessage);
+        }
 
-                // Check the scopes are equal or subset, OR none.
-                let update_scopes = if let Some(req_scopes) = req_scopes {
-                    if req_scopes.is_subset(&scopes) {
-                        debug!("oauth2 scopes requested, checked as valid.");
-                        // We have to return the requested set since it
-                        // may be constrained.
-                        req_scopes.clone()
-                    } else {
-                        warn!("oauth2 scopes requested, invalid.");
-                        return Err(Oauth2Error::InvalidScope);
-                    }
-                } else {
-                    debug!("No OAuth2 scopes requested, this is valid.");
-                    // Return the initial set of scopes.
-                    scopes
-                };
+        // The hint must name a person who is able to approve the request.
+        let account_uuid = self
+            .qs_write
+            .name_to_uuid(login_hint)
+            .and_then(|account_uuid| self.qs_write.internal_search_uuid(account_uuid))
+            .ok()
+            .filter(|entry| {
+                entry.get_uuid() != UUID_ANONYMOUS
+                    && entry.attribute_equality(Attribute::Class, &EntryClass::Person.to_partialvalue())
+            })
+            .map(|entry| entry.get_uuid())
+            .ok_or_else(|| {
+                security_info!(%login_hint, "Backchannel authentication login_hint does not match a person");
+                Oauth2Error::UnknownUserId
+            })?;
 
-                // ----------
-                // good to go
+        // Forget any backchannel authentications that have expired.
+        let expired: Vec<Uuid> = self
+            .oauth2_backchannel_authorisations
+            .iter()
+            .filter_map(|(auth_req_id, bc_auth)| (bc_auth.expiry <= ct).then_some(*auth_req_id))
+            .collect();
+        for auth_req_id in expired {
+            self.oauth2_backchannel_authorisations.remove(&auth_req_id);
+        }
 
-                let account_uuid = uuid;
+        let auth_req_id = Uuid::new_v4();
+        let expires_in = bc_req
+            .requested_expiry
+            .unwrap_or(OAUTH2_DEVICE_CODE_EXPIRY_SECONDS)
+            .clamp(1, OAUTH2_DEVICE_CODE_EXPIRY_SECONDS);
 
-                self.generate_access_token_response(
-                    o2rs,
-                    ct,
-                    update_scopes,
-                    account_uuid,
-                    parent_session_id,
-                    session_id,
-                    nonce,
-                )
-            }
-        }
+        self.oauth2_backchannel_authorisations.insert(
+            auth_req_id,
+            BackchannelAuthorisation {
+                expiry: ct + Duration::from_secs(expires_in),
+                rs_uuid: o2rs.uuid,
+                client_id: o2rs.name.clone(),
+                account_uuid,
+                scopes: bc_req.scope.clone(),
+                binding_message: bc_req.binding_message.clone(),
+                state: BackchannelAuthorisationState::Pending,
+            },
+        );
+
+        Ok(BackchannelAuthenticationResponse {
+            auth_req_id: auth_req_id.to_string(),
+            expires_in,
+            interval: OAUTH2_DEVICE_CODE_INTERVAL_SECONDS,
+        })
     }
 
-    #[allow(clippy::too_many_arguments)]
+    /// Approve or deny a backchannel authentication that is waiting on this user. Once
+    /// approved, the tokens issued to the client are children of the session the user
+    /// approved it with.
     #[instrument(level = "debug", skip_all)]
-    fn check_oauth2_token_exchange_service_account(
+    pub fn oauth2_backchannel_authorisation_decide(
         &mut self,
-        o2rs: &Oauth2RS,
-        subject_token: &str,
-        subject_token_type: &str,
-        requested_token_type: Option<&str>,
-        audience: Option<&str>,
-        resource: Option<&str>,
-        req_scopes: Option<&BTreeSet<String>>,
+        ident: &Identity,
+        auth_req_id: Uuid,
+        approve: bool,
         ct: Duration,
-    ) -> Result<AccessTokenResponse, Oauth2Error> {
-        if let Some(rtt) = requested_token_type {
-            if rtt != OAUTH2_TOKEN_TYPE_ACCESS_TOKEN {
-                warn!(
-                    requested_token_type = rtt,
-                    "Unsupported requested_token_type in token exchange"
-                );
-                return Err(Oauth2Error::InvalidRequest);
-            }
-        }
-
-        if let Some(aud) = audience {
-            if aud != o2rs.name {
-                warn!(expected = %o2rs.name, requested = aud, "Token exchange audience mismatch");
-                return Err(Oauth2Error::InvalidTarget);
-            }
-        }
-
-        if let Some(res) = resource {
-            let parsed_resource = Url::parse(res).map_err(|_| {
-                warn!(
-                    requested = res,
-                    "Invalid resource parameter in token exchange"
-                );
-                Oauth2Error::InvalidRequest
-            })?;
-
-            if parsed_resource.fragment().is_some() {
-                warn!(
-                    requested = res,
-                    "Resource parameter must not contain a fragment"
-                );
-                return Err(Oauth2Error::InvalidRequest);
-            }
-
-            let origin = parsed_resource.origin();
-            let target_allowed =
-                o2rs.origins.contains(&origin) || o2rs.opaque_origins.contains(&parsed_resource);
-            if !target_allowed {
-                admin_warn!(requested = res, "Token exchange resource target mismatch");
-                return Err(Oauth2Error::InvalidTarget);
-            }
-        }
+    ) -> Result<(), OperationError> {
+        let Some(mut bc_auth) = self
+            .oauth2_backchannel_authorisations
+            .get(&auth_req_id)
+            .cloned()
+        else {
+            warn!(?auth_req_id, "Backchannel authentication not found");
+            return Err(OperationError::NoMatchingEntries);
+        };
 
-        if subject_token_type != TOKEN_EXCHANGE_SUBJECT_TOKEN_TYPE_ACCESS {
+        if bc_auth.expiry <= ct
+            || Some(bc_auth.account_uuid) != ident.get_uuid()
+            || !matches!(bc_auth.state, BackchannelAuthorisationState::Pending)
+        {
             security_info!(
-                ?subject_token_type,
-                "Unsupported subject_token_type in token exchange"
+                ?auth_req_id,
+                "Backchannel authentication is not pending for this identity"
             );
-            return Err(Oauth2Error::InvalidRequest);
+            return Err(OperationError::InvalidRequestState);
         }
 
-        let jwsc = JwsCompact::from_str(subject_token).map_err(|_| {
-            error!("Failed to deserialise subject token for token exchange");
-            Oauth2Error::InvalidRequest
+        let o2rs = self.get_client(&bc_auth.client_id).map_err(|_| {
+            admin_error!("Invalid backchannel authentication OAuth2 client_id");
+            OperationError::InvalidRequestState
         })?;
 
-        let token = self
-            .validate_and_parse_token_to_identity_token(&jwsc, ct)
-            .map_err(|err| {
-                security_info!(?err, "Unable to validate subject token for token exchange");
-                Oauth2Error::InvalidRequest
-            })?;
-
-        let (apit, entry) = match token {
-            Token::ApiToken(apit, entry) => (apit, entry),
-            Token::UserAuthToken(_) => {
-                security_info!("Token exchange subject_token must be a service account api token");
-                return Err(Oauth2Error::InvalidRequest);
-            }
-        };
-
-        let ident = self
-            .process_apit_to_identity(&apit, Source::Internal, entry, ct)
-            .map_err(|err| match err {
-                OperationError::SessionExpired | OperationError::NotAuthenticated => {
+        bc_auth.state = if approve {
+            // If the user can't be granted the requested scopes, the client is denied.
+            match process_requested_scopes_for_identity(&o2rs, ident, Some(&bc_auth.scopes)) {
+                Ok((_req_scopes, granted_scopes)) => BackchannelAuthorisationState::Approved {
+                    parent_session_id: ident.get_session_id(),
+                    scopes: granted_scopes,
+                },
+                Err(err) => {
                     security_info!(
+                        ?auth_req_id,
                         ?err,
-                        "Service account api token rejected during token exchange"
+                        "Backchannel authentication scopes can not be granted to this identity"
                     );
-                    Oauth2Error::InvalidRequest
+                    BackchannelAuthorisationState::Denied
                 }
-                err => Oauth2Error::ServerError(err),
-            })?;
-
-        let (_req_scopes, granted_scopes) =
-            process_requested_scopes_for_identity(o2rs, &ident, req_scopes)?;
+            }
+        } else {
+            BackchannelAuthorisationState::Denied
+        };
 
-        let session_id = Uuid::new_v4();
-        let parent_session_id = apit.token_id;
-        let account_uuid = apit.account_id;
+        self.oauth2_backchannel_authorisations
+            .insert(auth_req_id, bc_auth);
 
-        self.generate_access_token_response(
-            o2rs,
-            ct,
-            granted_scopes,
-            account_uuid,
-            parent_session_id,
-            session_id,
-            None,
-        )
+        Ok(())
     }
 
-    fn check_oauth2_token_client_credentials(
+    /// Withdraw a user's consent to a client, and end every session the client holds for
+    /// the user so that its refresh tokens can no longer be used. The next authorisation
+    /// of this client will prompt the user for consent again.
+    #[instrument(level = "debug", skip_all)]
+    pub fn oauth2_consent_revoke(
         &mut self,
-        o2rs: &Oauth2RS,
-        req_scopes: Option<&BTreeSet<String>>,
-        ct: Duration,
-    ) -> Result<AccessTokenResponse, Oauth2Error> {
-        let req_scopes = req_scopes.cloned().unwrap_or_default();
+        ident: &Identity,
+        client_id: &str,
+    ) -> Result<(), OperationError> {
+        let Some(account_uuid) = ident.get_uuid() else {
+            error!("Consent revoke ident does not have a valid uuid, unable to proceed");
+            return Err(OperationError::InvalidSessionState);
+        };
 
-        // Validate all request scopes have valid syntax.
-        validate_scopes(&req_scopes)?;
+        let o2rs = self.get_client(client_id).map_err(|_| {
+            warn!(%client_id, "Invalid OAuth2 client_id");
+            OperationError::NoMatchingEntries
+        })?;
 
-        // Of these scopes, which do we have available?
-        let avail_scopes: Vec<String> = req_scopes
-            .intersection(&o2rs.client_scopes)
-            .map(|s| s.to_string())
+        let account_entry = self.qs_write.internal_search_uuid(account_uuid)?;
+
+        // As with token revocation, we submit the removal of the sessions rather than
+        // revoking them so that the change is respected once replication converges.
+        let modlist = account_entry
+            .get_ava_as_oauth2session_map(Attribute::OAuth2Session)
+            .into_iter()
+            .flatten()
+            .filter(|(_, session)| session.rs_uuid == o2rs.uuid)
+            .map(|(session_id, _)| {
+                Modify::Removed(Attribute::OAuth2Session, PartialValue::Refer(*session_id))
+            })
+            .chain(std::iter::once(Modify::Removed(
+                Attribute::OAuth2ConsentScopeMap,
+                PartialValue::Refer(o2rs.uuid),
+            )))
             .collect();
 
-        if avail_scopes.len() != req_scopes.len() {
-            admin_warn!(
-                ident = %o2rs.name,
-                requested_scopes = ?req_scopes,
-                available_scopes = ?o2rs.client_scopes,
-                "Client does not have access to the requested scopes"
-            );
+        self.qs_write
+            .internal_modify(
+                &filter!(f_eq(Attribute::Uuid, PartialValue::Uuid(account_uuid))),
+                &ModifyList::new_list(modlist),
+            )
+            .map_err(|err| {
+                admin_error!(?err, "Failed to revoke OAuth2 consent");
+                err
+            })
+    }
+
+    /// Register a new client on behalf of the holder of an initial access token. The client
+    /// is granted the registration scope maps of the registrant, narrowed to the scopes
+    /// that it requests. <https://datatracker.ietf.org/doc/html/rfc7591#section-3>
+    #[instrument(level = "debug", skip_all)]
+    pub fn oauth2_client_register(
+        &mut self,
+        ident: &Identity,
+        metadata: &ClientMetadata,
+        ct: Duration,
+    ) -> Result<ClientRegistrationResponse, Oauth2Error> {
+        if ident.access_scope() != AccessScope::ReadWrite {
+            security_info!(%ident, "Registering a client requires a read write initial access token");
             return Err(Oauth2Error::AccessDenied);
         }
 
-        // == ready to build the access token ==
-
-        let granted_scopes = avail_scopes
-            .into_iter()
-            .chain(o2rs.client_sup_scopes.iter().cloned())
-            .collect::<BTreeSet<_>>();
+        let registration_scope_maps = ident
+            .get_user_entry()
+            .and_then(|ent| {
+                ent.get_ava_as_oauthscopemaps(Attribute::OAuth2RegistrationScopeMap)
+                    .cloned()
+            })
+            .unwrap_or_default();
 
-        let odt_ct = OffsetDateTime::UNIX_EPOCH + ct;
-        let iat = ct.as_secs() as i64;
-        let exp = iat + OAUTH2_ACCESS_TOKEN_EXPIRY as i64;
-        let odt_exp = odt_ct + Duration::from_secs(OAUTH2_ACCESS_TOKEN_EXPIRY as u64);
-        let expires_in = OAUTH2_ACCESS_TOKEN_EXPIRY;
+        if registration_scope_maps.is_empty() {
+            security_info!(%ident, "Identity is not permitted to register clients");
+            return Err(Oauth2Error::AccessDenied);
+        }
 
-        let session_id = Uuid::new_v4();
+        let registrant_uuid = ident.get_uuid().ok_or_else(|| {
+            security_info!(%ident, "Identity is not permitted to register clients");
+            Oauth2Error::AccessDenied
+        })?;
 
-        let scope = granted_scopes.clone();
+        let valid_metadata = validate_client_metadata(metadata, &registration_scope_maps)?;
 
-        let uuid = o2rs.uuid;
+        let rs_uuid = Uuid::new_v4();
+        let registration_token_id = Uuid::new_v4();
+        let client_id = format!("client-{}", rs_uuid.as_simple());
 
-        let access_token_raw = Oauth2TokenType::ClientAccess {
-            scopes: granted_scopes,
-            session_id,
-            uuid,
-            exp,
-            iat,
-            nbf: iat,
+        let rs_class = if valid_metadata.is_public {
+            EntryClass::OAuth2ResourceServerPublic
+        } else {
+            EntryClass::OAuth2ResourceServerBasic
         };
 
-        let access_token_data = Jwe::into_json(&access_token_raw).map_err(|err| {
-            error!(?err, "Unable to encode token data");
-            Oauth2Error::ServerError(OperationError::SerdeJsonError)
-        })?;
+        let mut entry: EntryInitNew = entry_init!(
+            (Attribute::Class, EntryClass::Object.to_value()),
+            (Attribute::Class, EntryClass::Account.to_value()),
+            (
+                Attribute::Class,
+                EntryClass::OAuth2ResourceServer.to_value()
+            ),
+            (Attribute::Class, rs_class.to_value()),
+            (Attribute::Uuid, Value::Uuid(rs_uuid)),
+            (Attribute::Name, Value::new_iname(&client_id)),
+            (Attribute::OAuth2StrictRedirectUri, Value::new_bool(true)),
+            (
+                Attribute::OAuth2RsRegistrationTokenId,
+                Value::Uuid(registration_token_id)
+            ),
+            (Attribute::EntryManagedBy, Value::Refer(registrant_uuid))
+        );
 
-        let access_token = o2rs
-            .key_object
-            .jwe_a128gcm_encrypt(&access_token_data, ct)
-            .map(|jwe| jwe.to_string())
-            .map_err(|err| {
-                error!(?err, "Unable to encode token data");
-                Oauth2Error::ServerError(OperationError::CryptographyError)
-            })?;
+        for modify in valid_metadata.modifications(&client_id)? {
+            if let Modify::Present(attr, value) = modify {
+                entry.add_ava(attr, value);
+            }
+        }
 
-        // Write the session to the db
-        let session = Value::Oauth2Session(
-            session_id,
-            Oauth2Session {
-                parent: None,
-                state: SessionState::ExpiresAt(odt_exp),
-                issued_at: odt_ct,
-                rs_uuid: o2rs.uuid,
-            },
-        );
+        self.qs_write.internal_create(vec![entry]).map_err(|err| {
+            error!(?err, "Unable to create registered client");
+            Oauth2Error::ServerError(err)
+        })?;
 
-        // We need to create this session on the o2rs
-        let modlist =
-            ModifyList::new_list(vec![Modify::Present(Attribute::OAuth2Session, session)]);
+        // Load the key object of the new client, which signs its registration access token.
+        self.qs_write.reload().map_err(Oauth2Error::ServerError)?;
 
-        self.qs_write
-            .internal_modify(
-                &filter!(f_eq(Attribute::Uuid, PartialValue::Uuid(uuid))),
-                &modlist,
-            )
-            .map_err(|e| {
-                admin_error!("Failed to persist OAuth2 session record {:?}", e);
-                Oauth2Error::ServerError(e)
+        let key_object = self
+            .qs_write
+            .get_key_providers()
+            .get_key_object_handle(rs_uuid)
+            .ok_or_else(|| {
+                error!("Registered client is missing its key object");
+                Oauth2Error::ServerError(OperationError::InvalidState)
             })?;
 
-        Ok(AccessTokenResponse {
-            access_token,
-            token_type: AccessTokenType::Bearer,
-            issued_token_type: Some(IssuedTokenType::AccessToken),
-            expires_in,
-            refresh_token: None,
-            scope,
-            id_token: None,
+        let registration_access_token = JwsBuilder::into_json(&RegistrationAccessTokenClaims {
+            client_id: client_id.clone(),
+            jti: registration_token_id,
         })
+        .map(|builder| builder.build())
+        .map_err(|err| {
+            error!(?err, "Unable to encode registration access token");
+            Oauth2Error::ServerError(OperationError::InvalidState)
+        })
+        .and_then(|jws| {
+            key_object
+                .jws_es256_sign(&jws, ct)
+                .map_err(Oauth2Error::ServerError)
+        })?;
+
+        let ent = self
+            .qs_write
+            .internal_search_uuid(rs_uuid)
+            .map_err(Oauth2Error::ServerError)?;
+
+        let mut registration = client_registration_response(&self.oauth2rs.inner.origin, &ent)?;
+        registration.client_id_issued_at = Some(ct.as_secs() as i64);
+        registration.registration_access_token = Some(registration_access_token.to_string());
+
+        security_info!(%client_id, registrant = %ident, "Registered OAuth2 client");
+
+        Ok(registration)
     }
 
-    fn generate_access_token_response(
+    /// Replace the metadata of a registered client. As the registrant is not known, the
+    /// scopes of the client can only be narrowed.
+    /// <https://datatracker.ietf.org/doc/html/rfc7592#section-2.2>
+    #[instrument(level = "debug", skip_all)]
+    pub fn oauth2_client_registration_update(
         &mut self,
-        o2rs: &Oauth2RS,
-        ct: Duration,
-        //
-        scopes: BTreeSet<String>,
-        account_uuid: Uuid,
-        parent_session_id: Uuid,
-        session_id: Uuid,
-        nonce: Option<String>,
-    ) -> Result<AccessTokenResponse, Oauth2Error> {
-        let odt_ct = OffsetDateTime::UNIX_EPOCH + ct;
-        let iat = ct.as_secs() as i64;
+        client_id: &str,
+        client_auth_info: &ClientAuthInfo,
+        metadata: &ClientMetadata,
+    ) -> Result<ClientRegistrationResponse, Oauth2Error> {
+        let o2rs = self.get_client(client_id)?;
+        o2rs.verify_registration_access_token(client_auth_info)?;
 
-        // TODO: Configurable from the oauth2rs configuration?
+        let valid_metadata = validate_client_metadata(metadata, &o2rs.scope_maps)?;
 
-        // Disclaimer: It may seem odd here that we are ignoring our session when it comes to expiry
-        // times. However, this actually is valid because when we take the initial code exchange
-        // path we have already validate the expiry of the account/session in that process. For the
-        // refresh path, we validate the session expiry and validity before we call this. So these
-        // expiries are *purely* for the tokens we issue and are *not related* to the expiries of the
-        // the session - these are enforced as above!
+        if valid_metadata.is_public != o2rs.is_pkce() {
+            warn!("A registered client can not change between public and confidential");
+            return Err(Oauth2Error::InvalidClientMetadata);
+        }
 
-        let expiry = odt_ct + Duration::from_secs(OAUTH2_ACCESS_TOKEN_EXPIRY as u64);
-        let expires_in = OAUTH2_ACCESS_TOKEN_EXPIRY;
-        let refresh_expiry = iat + OAUTH_REFRESH_TOKEN_EXPIRY as i64;
-        let odt_refresh_expiry = odt_ct + Duration::from_secs(OAUTH_REFRESH_TOKEN_EXPIRY);
+        let modlist = ModifyList::new_list(valid_metadata.modifications(&o2rs.name)?);
 
-        let scope = scopes.clone();
+        self.qs_write
+            .internal_modify_uuid(o2rs.uuid, &modlist)
+            .map_err(|err| {
+                error!(?err, "Unable to update registered client");
+                Oauth2Error::ServerError(err)
+            })?;
 
-        let iss = o2rs.iss.clone();
+        let ent = self
+            .qs_write
+            .internal_search_uuid(o2rs.uuid)
+            .map_err(Oauth2Error::ServerError)?;
 
-        // Just reflect the access token expiry.
-        let exp = expiry.unix_timestamp();
+        client_registration_response(&self.oauth2rs.inner.origin, &ent)
+    }
 
-        let aud = o2rs.name.clone();
+    /// Delete a registered client. <https://datatracker.ietf.org/doc/html/rfc7592#section-2.3>
+    #[instrument(level = "debug", skip_all)]
+    pub fn oauth2_client_registration_delete(
+        &mut self,
+        client_id: &str,
+        client_auth_info: &ClientAuthInfo,
+    ) -> Result<(), Oauth2Error> {
+        let o2rs = self.get_client(client_id)?;
+        o2rs.verify_registration_access_token(client_auth_info)?;
 
-        let client_id = o2rs.name.clone();
+        self.qs_write
+            .internal_delete_uuid(o2rs.uuid)
+            .map_err(|err| {
+                error!(?err, "Unable to delete registered client");
+                Oauth2Error::ServerError(err)
+            })
+    }
 
-        let id_token = if scopes.contains(OAUTH2_SCOPE_OPENID) {
-            // TODO: Scopes map to claims:
-            //
-            // * profile - (name, family\_name, given\_name, middle\_name, nickname, preferred\_username, profile, picture, website, gender, birthdate, zoneinfo, locale, and updated\_at)
-            // * email - (email, email\_verified)
-            // * address - (address)
-            // * phone - (phone\_number, phone\_number\_verified)
-            //
-            // https://openid.net/specs/openid-connect-basic-1_0.html#StandardClaims
+    fn get_client(&self, client_id: &str) -> Result<Oauth2RS, Oauth2Error> {
+        let s = self
+            .oauth2rs
+            .inner
+            .rs_set_get(client_id)
+            .ok_or_else(|| {
+                warn!("Invalid OAuth2 client_id {}", client_id);
+                Oauth2Error::AuthenticationRequired
+            })?
+            .clone();
+        Ok(s)
+    }
 
-            // TODO: Can the user consent to which claims are released? Today as we don't support most
-            // of them anyway, no, but in the future, we can stash these to the consent req.
+    #[instrument(level = "info", skip(self))]
+    pub fn handle_oauth2_start_device_flow(
+        &mut self,
+        _client_auth_info: ClientAuthInfo,
+        _client_id: &str,
+        _scope: &Option<BTreeSet<String>>,
+        _eventid: Uuid,
+    ) -> Result<DeviceAuthorizationResponse, Oauth2Error> {
+        // let o2rs = self.get_client(client_id)?;
 
-            // TODO: If max_age was requested in the request, we MUST provide auth_time.
+        // info!("Got Client: {:?}", o2rs);
 
-            // amr == auth method
-            // We removed this from uat, and I think that it's okay here. AMR is a bit useless anyway
-            // since there is no standard for what it should look like wrt to cred strength.
-            let amr = None;
+        // // TODO: change this to checking if it's got device flow enabled
+        // if !o2rs.require_pkce() {
+        //     security_info!("Device flow is only available for PKCE-enabled clients");
+        //     return Err(Oauth2Error::InvalidRequest);
+        // }
 
-            let entry = match self.qs_write.internal_search_uuid(account_uuid) {
-                Ok(entry) => entry,
-                Err(err) => return Err(Oauth2Error::ServerError(err)),
-            };
+        // info!(
+        //     "Starting device flow for client_id={} scopes={} source={:?}",
+        //     client_id,
+        //     scope
+        //         .as_ref()
+        //         .map(|s| s.iter().cloned().collect::<Vec<_>>().into_iter().join(","))
+        //         .unwrap_or("[]".to_string()),
+        //     client_auth_info.source
+        // );
 
-            let account = match Account::try_from_entry_rw(&entry, &mut self.qs_write) {
-                Ok(account) => account,
-                Err(err) => return Err(Oauth2Error::ServerError(err)),
-            };
+        // let mut verification_uri = self.oauth2rs.inner.origin.clone();
+        // verification_uri.set_path(uri::OAUTH2_DEVICE_LOGIN);
 
-            let s_claims = s_claims_for_account(o2rs, &account, &scopes);
-            let extra_claims = extra_claims_for_account(&account, &o2rs.claim_map, &scopes);
+        // let (user_code_string, _user_code) = gen_user_code();
+        // let expiry =
+        //     Duration::from_secs(OAUTH2_DEVICE_CODE_EXPIRY_SECONDS) + duration_from_epoch_now();
+        // let device_code = gen_device_code()
+        //     .inspect_err(|err| error!("Failed to generate a device code! {:?}", err))?;
 
-            let oidc = OidcToken {
-                iss: iss.clone(),
-                sub: OidcSubject::U(account_uuid),
-                aud: aud.clone(),
-                iat,
-                nbf: Some(iat),
-                exp,
-                auth_time: None,
-                nonce: nonce.clone(),
-                at_hash: None,
-                acr: None,
-                amr,
-                azp: Some(o2rs.name.clone()),
-                jti: Some(session_id.to_string()),
-                s_claims,
-                claims: extra_claims,
-            };
+        Err(Oauth2Error::InvalidGrant)
 
-            trace!(?oidc);
-            let oidc = JwsBuilder::into_json(&oidc)
-                .map(|builder| builder.build())
-                .map_err(|err| {
-                    admin_error!(?err, "Unable to encode access token data");
-                    Oauth2Error::ServerError(OperationError::InvalidState)
-                })?;
-
-            let jwt_signed = match o2rs.sign_alg {
-                SignatureAlgo::Es256 => o2rs.key_object.jws_es256_sign(&oidc, ct),
-                SignatureAlgo::Rs256 => o2rs.key_object.jws_rs256_sign(&oidc, ct),
-            }
-            .map_err(|err| {
-                error!(?err, "Unable to encode oidc token data");
-                Oauth2Error::ServerError(OperationError::InvalidState)
-            })?;
+        // TODO: store user_code / expiry / client_id / device_code in the backend, needs to be checked on the token exchange.
+        // Ok(DeviceAuthorizationResponse::new(
+        //     verification_uri,
+        //     device_code,
+        //     user_code_string,
+        // ))
+    }
 
-            Some(jwt_signed.to_string())
-        } else {
-            // id_token is not required in non-openid flows.
-            None
-        };
+    #[instrument(level = "info", skip(self))]
+    fn check_oauth2_device_code_status(
+        &mut self,
+        device_code: &str,
+        scope: &Option<BTreeSet<String>>,
+    ) -> Result<AccessTokenResponse, Oauth2Error> {
+        // TODO: check the device code is valid, do the needful
 
-        // We need to record this into the record? Delayed action?
-        let access_token_data = OAuth2RFC9068Token {
-            iss: iss.to_string(),
-            sub: account_uuid,
-            aud,
-            exp,
-            nbf: iat,
-            iat,
-            jti: session_id,
-            client_id,
-            extensions: OAuth2RFC9068TokenExtensions {
-                auth_time: None,
-                acr: None,
-                amr: None,
-                scope: scopes.clone(),
-                nonce: nonce.clone(),
-                session_id,
-                parent_session_id: Some(parent_session_id),
-            },
-        };
+        error!(
+            "haven't done the device grant yet! Got device_code={} scope={:?}",
+            device_code, scope
+        );
+        Err(Oauth2Error::AuthorizationPending)
 
-        let access_token_data = JwsBuilder::into_json(&access_token_data)
-            .map(|builder| builder.set_typ(Some("at+jwt")).build())
-            .map_err(|err| {
-                error!(?err, "Unable to encode access token data");
-                Oauth2Error::ServerError(OperationError::InvalidState)
-            })?;
+        // if it's an expired code, then just delete it from the db and return an error.
+        // Err(Oauth2Error::ExpiredToken)
+    }
 
-        let access_token = match o2rs.sign_alg {
-            SignatureAlgo::Es256 => o2rs.key_object.jws_es256_sign(&access_token_data, ct),
-            SignatureAlgo::Rs256 => o2rs.key_object.jws_rs256_sign(&access_token_data, ct),
-        }
-        .map_err(|e| {
-            admin_error!(err = ?e, "Unable to sign access token data");
-            Oauth2Error::ServerError(OperationError::InvalidState)
+    /// Poll for the tokens of a backchannel authentication, which are issued once the
+    /// user has approved it.
+    /// <https://openid.net/specs/openid-client-initiated-backchannel-authentication-core-1_0.html#token_request>
+    #[instrument(level = "debug", skip_all)]
+    fn check_oauth2_token_backchannel(
+        &mut self,
+        o2rs: &Oauth2RS,
+        auth_req_id: &str,
+        binding: TokenBinding,
+        ct: Duration,
+    ) -> Result<AccessTokenResponse, Oauth2Error> {
+        let auth_req_id = Uuid::parse_str(auth_req_id).map_err(|_| {
+            warn!("Invalid backchannel auth_req_id");
+            Oauth2Error::InvalidGrant
         })?;
 
-        let refresh_token_raw = Oauth2TokenType::Refresh {
-            scopes,
-            parent_session_id,
-            session_id,
-            exp: refresh_expiry,
-            uuid: account_uuid,
-            iat,
-            nbf: iat,
-            nonce,
+        let Some(bc_auth) = self
+            .oauth2_backchannel_authorisations
+            .get(&auth_req_id)
+            .filter(|bc_auth| bc_auth.rs_uuid == o2rs.uuid)
+            .cloned()
+        else {
+            security_info!(
+                ?auth_req_id,
+                "Backchannel authentication not found for this client"
+            );
+            return Err(Oauth2Error::InvalidGrant);
         };
 
-        let refresh_token_data = Jwe::into_json(&refresh_token_raw).map_err(|err| {
-            error!(?err, "Unable to encode token data");
-            Oauth2Error::ServerError(OperationError::SerdeJsonError)
-        })?;
-
-        let refresh_token = o2rs
-            .key_object
-            .jwe_a128gcm_encrypt(&refresh_token_data, ct)
-            .map(|jwe| jwe.to_string())
-            .map_err(|err| {
-                error!(?err, "Unable to encrypt token data");
-                Oauth2Error::ServerError(OperationError::CryptographyError)
-            })?;
-
-        // Write the session to the db even with the refresh path, we need to do
-        // this to update the "not issued before" time.
-        let session = Value::Oauth2Session(
-            session_id,
-            Oauth2Session {
-                parent: Some(parent_session_id),
-                state: SessionState::ExpiresAt(odt_refresh_expiry),
-                issued_at: odt_ct,
-                rs_uuid: o2rs.uuid,
-            },
-        );
+        if bc_auth.expiry <= ct {
+            security_info!(?auth_req_id, "Backchannel authentication has expired");
+            return Err(Oauth2Error::ExpiredToken);
+        }
 
-        // We need to update (replace) this session id if present.
-        let modlist = ModifyList::new_list(vec![
-            // NOTE: Oauth2_session has special handling that allows update in place without
-            // the remove step needing to be carried out.
-            // Modify::Removed("oauth2_session".into(), PartialValue::Refer(session_id)),
-            Modify::Present(Attribute::OAuth2Session, session),
-        ]);
+        let (parent_session_id, scopes) = match bc_auth.state {
+            BackchannelAuthorisationState::Pending => {
+                return Err(Oauth2Error::AuthorizationPending);
+            }
+            BackchannelAuthorisationState::Denied => {
+                security_info!(?auth_req_id, "Backchannel authentication was denied");
+                return Err(Oauth2Error::AccessDenied);
+            }
+            BackchannelAuthorisationState::Approved {
+                parent_session_id,
+                scopes,
+            } => (parent_session_id, scopes),
+        };
 
-        self.qs_write
-            .internal_modify(
-                &filter!(f_eq(Attribute::Uuid, PartialValue::Uuid(account_uuid))),
-                &modlist,
-            )
-            .map_err(|e| {
-                admin_error!("Failed to persist OAuth2 session record {:?}", e);
-                Oauth2Error::ServerError(e)
-            })?;
+        // Tokens are only issued once for an approval.
+        self.oauth2_backchannel_authorisations.remove(&auth_req_id);
 
-        Ok(AccessTokenResponse {
-            access_token: access_token.to_string(),
-            token_type: AccessTokenType::Bearer,
-            issued_token_type: Some(IssuedTokenType::AccessToken),
-            expires_in,
-            refresh_token: Some(refresh_token),
-            scope,
-            id_token,
-        })
+        self.generate_access_token_response(
+            o2rs,
+            ct,
+            scopes,
+            bc_auth.account_uuid,
+            parent_session_id,
+            Uuid::new_v4(),
+            None,
+            Vec::with_capacity(0),
+            binding,
+        )
     }
 
-    #[cfg(test)]
-    fn reflect_oauth2_token(
+    #[instrument(level = "debug", skip_all)]
+    pub fn check_oauth2_authorise_permit(
         &mut self,
-        client_auth_info: &ClientAuthInfo,
-        token: &str,
-    ) -> Result<Oauth2TokenType, OperationError> {
-        let Some(client_authz) = client_auth_info.basic_authz.as_ref() else {
-            warn!("OAuth2 client_id not provided by basic authz");
+        ident: &Identity,
+        consent_token: &str,
+        ct: Duration,
+    ) -> Result<AuthorisePermitSuccess, OperationError> {
+        let Some(account_uuid) = ident.get_uuid() else {
+            error!("consent request ident does not have a valid uuid, unable to proceed");
             return Err(OperationError::InvalidSessionState);
         };
 
-        let client_auth = parse_basic_authz(client_authz.as_str()).map_err(|_| {
-            warn!("Invalid client_authz base64");
+        let consent_token_jwe = JweCompact::from_str(consent_token).map_err(|err| {
+            error!(?err, "Consent token is not a valid jwe compact");
             OperationError::InvalidSessionState
         })?;
 
-        // Get the o2rs for the handle.
-        let o2rs = self
+        let consent_req: ConsentToken = self
             .oauth2rs
             .inner
-            .rs_set_get(&client_auth.client_id)
-            .ok_or_else(|| {
-                warn!("Invalid OAuth2 client_id");
-                OperationError::InvalidSessionState
-            })?;
-
-        // check the secret.
-        if let OauthRSType::Basic { authz_secret, .. } = &o2rs.type_ {
-            if o2rs.is_basic() && Some(authz_secret) != client_auth.client_secret.as_ref() {
-                info!(
-                    "Invalid OAuth2 secret for client_id={}",
-                    client_auth.client_id
-                );
-                return Err(OperationError::InvalidSessionState);
-            }
-        }
-
-        let jwe_compact = JweCompact::from_str(token).map_err(|err| {
-            error!(?err, "Failed to deserialise a valid JWE");
-            OperationError::InvalidSessionState
-        })?;
-
-        o2rs.key_object
-            .jwe_decrypt(&jwe_compact)
+            .consent_key
+            .decipher(&consent_token_jwe)
             .map_err(|err| {
-                error!(?err, "Failed to decrypt token reflection request");
+                error!(?err, "Failed to decrypt consent request");
                 OperationError::CryptographyError
             })
             .and_then(|jwe| {
                 jwe.from_json().map_err(|err| {
-                    error!(?err, "Failed to deserialise token for reflection");
+                    error!(?err, "Failed to deserialise consent request");
                     OperationError::SerdeJsonError
                 })
-            })
-    }
-}
-
-impl IdmServerProxyReadTransaction<'_> {
-    #[instrument(level = "debug", skip_all)]
-    pub fn check_oauth2_authorisation(
-        &self,
-        maybe_ident: Option<&Identity>,
-        auth_req: &AuthorisationRequest,
-        ct: Duration,
-    ) -> Result<AuthoriseResponse, Oauth2Error> {
-        // due to identity processing we already know that:
-        // * the session must be authenticated, and valid
-        // * is within it's valid time window.
-        trace!(?auth_req);
+            })?;
 
-        if auth_req.response_type != ResponseType::Code {
-            admin_warn!("Unsupported OAuth2 response_type (should be 'code')");
-            return Err(Oauth2Error::UnsupportedResponseType);
+        // Validate that the ident_id matches our current ident.
+        if consent_req.ident_id != ident.get_event_origin_id() {
+            security_info!("consent request ident id does not match the identity of our UAT.");
+            return Err(OperationError::InvalidSessionState);
         }
 
-        let Some(response_mode) = auth_req.get_response_mode() else {
-            warn!(
-                "Invalid response_mode {:?} for response_type {:?}",
-                auth_req.response_mode, auth_req.response_type
-            );
-            return Err(Oauth2Error::InvalidRequest);
-        };
+        // Validate that the session id matches our uat.
+        if consent_req.session_id != ident.get_session_id() {
+            security_info!("consent request session id does not match the session id of our UAT.");
+            return Err(OperationError::InvalidSessionState);
+        }
 
-        let response_mode = match response_mode {
-            ResponseMode::Query => SupportedResponseMode::Query,
-            ResponseMode::Fragment => SupportedResponseMode::Fragment,
-            ResponseMode::FormPost => {
-                warn!(
-                    "Invalid response mode form_post requested - many clients request this incorrectly but proceed with response_mode=query. Remapping to query."
-                );
-                warn!("This behaviour WILL BE REMOVED in a future release.");
-                SupportedResponseMode::Query
-            }
-            ResponseMode::Invalid => {
-                warn!("Invalid response mode requested, unable to proceed");
-                return Err(Oauth2Error::InvalidRequest);
-            }
-        };
+        if consent_req.expiry <= ct.as_secs() {
+            // Token is expired
+            error!("Failed to decrypt consent request");
+            return Err(OperationError::CryptographyError);
+        }
 
-        /*
-         * 4.1.2.1.  Error Response
-         *
-         * If the request fails due to a missing, invalid, or mismatching
-         * redirection URI, or if the client identifier is missing or invalid,
-         * the authorization server SHOULD inform the resource owner of the
-         * error and MUST NOT automatically redirect the user-agent to the
-         * invalid redirection URI.
-         */
+        // The exchange must be performed in the next 60 seconds.
+        let expiry = ct.as_secs() + 60;
 
-        //
+        // Get the resource server config based on this client_id.
         let o2rs = self
             .oauth2rs
             .inner
-            .rs_set_get(&auth_req.client_id)
+            .rs_set_get(&consent_req.client_id)
             .ok_or_else(|| {
-                warn!(
-                    "Invalid OAuth2 client_id ({}) Have you configured the OAuth2 resource server?",
-                    &auth_req.client_id
-                );
-                Oauth2Error::InvalidClientId
+                admin_error!("Invalid consent request OAuth2 client_id");
+                OperationError::InvalidRequestState
             })?;
 
-        // redirect_uri must be part of the client_id origins, unless the client is public and then it MAY
-        // be a loopback address exempting it from this check and enforcement and we can carry on safely.
-
-        // == start validate oauth2 redirect conditions.
-
-        let auth_req_uri_is_loopback = check_is_loopback(&auth_req.redirect_uri);
-        let type_allows_localhost_redirect = o2rs.type_.allow_localhost_redirect();
-
-        // This allows loopback uri's that are *not* part of the origin/redirect_uri configurations.
-        let loopback_uri_matched = auth_req_uri_is_loopback && type_allows_localhost_redirect;
+        // Extract the state, code challenge, redirect_uri
+        let xchg_code = TokenExchangeCode {
+            account_uuid,
+            session_id: ident.get_session_id(),
+            expiry,
+            code_challenge: consent_req.code_challenge,
+            redirect_uri: consent_req.redirect_uri.clone(),
+            scopes: consent_req.scopes.clone(),
+            nonce: consent_req.nonce,
+            authorization_details: consent_req.authorization_details,
+        };
 
-        // The legacy origin match is in use.
-        let origin_uri_matched =
-            !o2rs.strict_redirect_uri && o2rs.origins.contains(&auth_req.redirect_uri.origin());
+        // Encrypt the exchange token
+        let code_data_jwe = Jwe::into_json(&xchg_code).map_err(|err| {
+            error!(?err, "Unable to encode xchg_code data");
+            OperationError::SerdeJsonError
+        })?;
 
-        // Strict uri validation is in use, must be an exact match.
-        let strict_redirect_uri_matched =
-            o2rs.strict_redirect_uri && o2rs.redirect_uris.contains(&auth_req.redirect_uri);
+        let code = o2rs
+            .key_object
+            .jwe_a128gcm_encrypt(&code_data_jwe, ct)
+            .map(|code| code.to_string())
+            .map_err(|err| {
+                error!(?err, "Unable to encrypt xchg_code");
+                OperationError::CryptographyError
+            })?;
 
-        // Allow opaque origins such as app uris.
-        let opaque_origin_matched = o2rs.opaque_origins.contains(&auth_req.redirect_uri);
+        // Everything is DONE! Now submit that it's all happy and the user consented correctly.
+        // this will let them bypass consent steps in the future.
+        // Submit that we consented to the delayed action queue
 
-        // Was the redirect origin secure?
-        let redirect_origin_is_secure = opaque_origin_matched
-            || auth_req_uri_is_loopback
-            || auth_req.redirect_uri.scheme() == "https";
+        let modlist = ModifyList::new_list(vec![
+            Modify::Removed(
+                Attribute::OAuth2ConsentScopeMap,
+                PartialValue::Refer(o2rs.uuid),
+            ),
+            Modify::Present(
+                Attribute::OAuth2ConsentScopeMap,
+                Value::OauthScopeMap(o2rs.uuid, consent_req.scopes.iter().cloned().collect()),
+            ),
+        ]);
 
-        // We must assert that *AT LEAST* one of the above match conditions holds true to proceed.
-        let valid_match_condition_asserted = loopback_uri_matched
-            || origin_uri_matched
-            || strict_redirect_uri_matched
-            || opaque_origin_matched;
+        self.qs_write.internal_modify(
+            &filter_all!(f_eq(Attribute::Uuid, PartialValue::Uuid(account_uuid))),
+            &modlist,
+        )?;
 
-        if valid_match_condition_asserted {
-            debug!(
-                ?loopback_uri_matched,
-                ?origin_uri_matched,
-                ?strict_redirect_uri_matched,
-                ?opaque_origin_matched,
-                "valid redirect uri match condition met."
-            );
-        } else {
-            // Display why it failed.
+        Ok(AuthorisePermitSuccess {
+            redirect_uri: consent_req.redirect_uri,
+            state: consent_req.state,
+            code,
+            response_mode: consent_req.response_mode,
+        })
+    }
 
-            // This is to catch the specific case that a public client gets a localhost redirect, but
-            // the admin hasn't enabled the flag for it. This way we direct them to the correct cause
-            // of the issue, rather than telling them to configure localhost as a redirect location.
-            let could_allow_localhost_redirect =
-                o2rs.type_.allow_localhost_redirect_could_be_possible();
+    #[instrument(level = "debug", skip_all)]
+    fn check_oauth2_token_exchange_authorization_code(
+        &mut self,
+        o2rs: &Oauth2RS,
+        token_req_code: &str,
+        token_req_redirect_uri: &Url,
+        token_req_code_verifier: Option<&str>,
+        binding: TokenBinding,
+        ct: Duration,
+    ) -> Result<AccessTokenResponse, Oauth2Error> {
+        // Check the token_req is within the valid time, and correctly signed for
+        // this client.
+        let jwe_compact = JweCompact::from_str(token_req_code).map_err(|_| {
+            error!("Failed to deserialise a valid JWE");
+            Oauth2Error::InvalidRequest
+        })?;
 
-            if auth_req_uri_is_loopback
-                && could_allow_localhost_redirect
-                && !type_allows_localhost_redirect
-            {
-                warn!(redirect_uri = %auth_req.redirect_uri, "OAuth2 redirect_uri returns to localhost, but localhost redirection is not allowed. See 'kanidm system oauth2 enable-localhost-redirects'");
-            } else {
-                // Not localhost - must be missing the redirect uri then, which is why strict/origin/opaque all failed to assert
-                if o2rs.strict_redirect_uri {
-                    warn!(
-                        "Invalid OAuth2 redirect_uri (must be an exact match to a redirect-url) - got {} from client but configured uris do not match (check oauth2_rs_origin entries)",
-                        auth_req.redirect_uri.as_str()
-                    );
-                } else {
-                    warn!(
-                        "Invalid OAuth2 redirect_uri (must be related to origin) - got {:?} from client but configured uris differ (compare oauth2_rs_origin_landing with oauth2_rs_origin entries)",
-                        auth_req.redirect_uri.origin()
-                    );
-                }
-            }
+        let code_xchg: TokenExchangeCode = o2rs
+            .key_object
+            .jwe_decrypt(&jwe_compact)
+            .map_err(|_| {
+                admin_error!("Failed to decrypt token exchange request");
+                Oauth2Error::InvalidRequest
+            })
+            .and_then(|jwe| {
+                debug!(?jwe);
+                jwe.from_json::<TokenExchangeCode>().map_err(|err| {
+                    error!(?err, "Failed to deserialise token exchange code");
+                    Oauth2Error::InvalidRequest
+                })
+            })?;
 
-            // All roads lead to error.
-            return Err(Oauth2Error::InvalidOrigin);
+        if code_xchg.expiry <= ct.as_secs() {
+            error!("Expired token exchange request");
+            return Err(Oauth2Error::InvalidRequest);
         }
 
-        // Assert that if secure origins were required, that we are enforcing that.
-        if o2rs.origin_secure_required && !redirect_origin_is_secure {
-            warn!(
-                "Invalid OAuth2 redirect_uri scheme (must be a secure origin) - got {} instead. Secure origins are required when *at least* one redirect_uri is https, then all uri's must also be secure origins.",
-                auth_req.redirect_uri
-            );
-            return Err(Oauth2Error::InvalidOrigin);
-        }
+        // If we have a verifier present, we MUST assert that a code challenge is present!
+        // It is worth noting here that code_xchg is *server issued* and encrypted, with
+        // a short validity period. The client controlled value is in token_req.code_verifier
+        if let Some(code_challenge) = code_xchg.code_challenge {
+            // Validate the code_verifier
+            let code_verifier = token_req_code_verifier
+                    .ok_or_else(|| {
+                        security_info!("PKCE code verification failed - code challenge is present, but no verifier was provided");
+                        Oauth2Error::InvalidRequest
+                    })?;
 
-        // == end validation of oauth2 redirect conditions.
+            let verifier_secret = PkceS256Secret::from(code_verifier.to_string());
 
-        let code_challenge = if let Some(pkce_request) = &auth_req.pkce_request {
-            if !o2rs.require_pkce() {
-                security_info!(?o2rs.name, "Insecure OAuth2 client configuration - PKCE is not enforced, but client is requesting it!");
-            }
-            // CodeChallengeMethod must be S256
-            if pkce_request.code_challenge_method != CodeChallengeMethod::S256 {
-                admin_warn!("Invalid OAuth2 code_challenge_method (must be 'S256')");
+            if !verifier_secret.verify(code_challenge) {
+                security_info!(
+                    "PKCE code verification failed - this may indicate malicious activity"
+                );
                 return Err(Oauth2Error::InvalidRequest);
             }
-            Some(pkce_request.code_challenge.clone())
         } else if o2rs.require_pkce() {
-            security_error!(?o2rs.name, "No PKCE code challenge was provided with client in enforced PKCE mode");
-            return Err(Oauth2Error::InvalidRequest);
-        } else {
-            security_info!(?o2rs.name, "Insecure client configuration - PKCE is not enforced");
-            None
-        };
-
-        // =============================================================================
-        // By this point, we have validated the majority of the security related
-        // parameters of the request. We can now inspect the identity and decide
-        // if we should ask the user to re-authenticate and proceed.
-
-        // TODO: https://openid.net/specs/openid-connect-basic-1_0.html#RequestParameters
-        // Are we going to provide the functions for these? Most of these can be "later".
-        // IF CHANGED: Update OidcDiscoveryResponse!!!
-
-        // TODO: https://openid.net/specs/openid-connect-basic-1_0.html#RequestParameters
-        // prompt - if set to login, we need to force a re-auth. But we don't want to
-        // if the user "only just" logged in, that's annoying. So we need a time window for
-        // this, to detect when we should force it to the consent req.
-
-        // TODO: display = popup vs touch vs wap etc.
-
-        // TODO: max_age, pass through with consent req. If 0, force login.
-        // Otherwise force a login re the uat timeout.
-
-        // TODO: ui_locales / claims_locales for the ui. Only if we don't have a Uat that
-        // would provide this.
-
-        // TODO: id_token_hint - a past token which can be used as a hint.
-
-        let Some(ident) = maybe_ident else {
-            debug!("No identity available, assume authentication required");
-            return Ok(AuthoriseResponse::AuthenticationRequired {
-                client_name: o2rs.displayname.clone(),
-                login_hint: auth_req.oidc_ext.login_hint.clone(),
-            });
-        };
-
-        let Some(account_uuid) = ident.get_uuid() else {
-            error!("Consent request ident does not have a valid UUID, unable to proceed");
+            security_info!(
+                "PKCE code verification failed - no code challenge present in PKCE enforced mode"
+            );
             return Err(Oauth2Error::InvalidRequest);
-        };
-
-        // Deny anonymous access to oauth2
-        if account_uuid == UUID_ANONYMOUS {
-            admin_error!(
-                "Invalid OAuth2 request - refusing to allow user that authenticated with anonymous"
+        } else if token_req_code_verifier.is_some() {
+            security_info!(
+                "PKCE code verification failed - a code verifier is present, but no code challenge in exchange"
             );
-            return Err(Oauth2Error::AccessDenied);
+            return Err(Oauth2Error::InvalidRequest);
         }
 
-        // scopes - you need to have every requested scope or this auth_req is denied.
-        let (req_scopes, granted_scopes) =
-            process_requested_scopes_for_identity(o2rs, ident, Some(&auth_req.scope))?;
-
-        // MICRO OPTIMISATION = flag if we have openid first, so we can into_iter here rather than
-        // cloning.
-        let openid_requested = req_scopes.contains(OAUTH2_SCOPE_OPENID);
-
-        let consent_previously_granted =
-            if let Some(consent_scopes) = ident.get_oauth2_consent_scopes(o2rs.uuid) {
-                trace!(?granted_scopes);
-                trace!(?consent_scopes);
-                granted_scopes.eq(consent_scopes)
-            } else {
-                false
-            };
-
-        let session_id = ident.get_session_id();
+        // Validate the redirect_uri is the same as the original.
+        if token_req_redirect_uri != &code_xchg.redirect_uri {
+            security_info!("Invalid OAuth2 redirect_uri (differs from original request uri)");
+            return Err(Oauth2Error::InvalidOrigin);
+        }
 
-        if consent_previously_granted || !o2rs.enable_consent_prompt() {
-            if event_enabled!(tracing::Level::DEBUG) {
-                let pretty_scopes: Vec<String> =
-                    granted_scopes.iter().map(|s| s.to_owned()).collect();
-                debug!(
-                    pretty_scopes = pretty_scopes.join(","),
-                    prompt_enabled = o2rs.enable_consent_prompt(),
-                    previously_granted = consent_previously_granted,
-                    "Consent flow passed"
+        /*
+        // Check that the UAT we are issuing for still is valid.
+        //
+        // Not sure this is actually needed. To create the token exchange code you need to have
+        // a valid, non-expired session, so why do we double check this here?
+        let odt_ct = OffsetDateTime::UNIX_EPOCH + ct;
+        if let Some(expiry) = code_xchg.uat.expiry {
+            if expiry <= odt_ct {
+                security_info!(
+                    "User Auth Token has expired before we could publish the OAuth2 response"
                 );
+                return Err(Oauth2Error::AccessDenied);
             }
+        }
+        */
 
-            // Xchg token expires in
-            let expiry = ct.as_secs() + 60;
+        // ==== We are now GOOD TO GO! ====
+        // Grant the access token response.
+        let parent_session_id = code_xchg.session_id;
+        let session_id = Uuid::new_v4();
 
-            // Setup for the permit success
-            let xchg_code = TokenExchangeCode {
-                account_uuid,
-                session_id,
-                expiry,
-                code_challenge,
-                redirect_uri: auth_req.redirect_uri.clone(),
-                scopes: granted_scopes.into_iter().collect(),
-                nonce: auth_req.nonce.clone(),
-            };
+        let scopes = code_xchg.scopes;
+        let account_uuid = code_xchg.account_uuid;
+        let nonce = code_xchg.nonce;
+        let authorization_details = code_xchg.authorization_details;
 
-            // Encrypt the exchange token with the key of the client
-            let code_data_jwe = Jwe::into_json(&xchg_code).map_err(|err| {
-                error!(?err, "Unable to encode xchg_code data");
-                Oauth2Error::ServerError(OperationError::SerdeJsonError)
-            })?;
-
-            let code = o2rs
-                .key_object
-                .jwe_a128gcm_encrypt(&code_data_jwe, ct)
-                .map(|jwe| jwe.to_string())
-                .map_err(|err| {
-                    error!(?err, "Unable to encrypt xchg_code data");
-                    Oauth2Error::ServerError(OperationError::CryptographyError)
-                })?;
-
-            Ok(AuthoriseResponse::Permitted(AuthorisePermitSuccess {
-                redirect_uri: auth_req.redirect_uri.clone(),
-                state: auth_req.state.clone(),
-                code,
-                response_mode,
-            }))
-        } else {
-            //  Check that the scopes are the same as a previous consent (if any)
-            // If oidc, what PII is visible?
-            // TODO: Scopes map to claims:
-            //
-            // * profile - (name, family\_name, given\_name, middle\_name, nickname, preferred\_username, profile, picture, website, gender, birthdate, zoneinfo, locale, and updated\_at)
-            // * email - (email, email\_verified)
-            // * address - (address)
-            // * phone - (phone\_number, phone\_number\_verified)
-            //
-            // https://openid.net/specs/openid-connect-basic-1_0.html#StandardClaims
-
-            // IMPORTANT DISTINCTION - Here req scopes must contain openid, but the PII can be supplemented
-            // be the servers scopes!
-            let mut pii_scopes = BTreeSet::default();
-            if openid_requested {
-                // Only mutate if things were requested under openid
-                if granted_scopes.contains(OAUTH2_SCOPE_EMAIL) {
-                    pii_scopes.insert(OAUTH2_SCOPE_EMAIL.to_string());
-                    pii_scopes.insert("email_verified".to_string());
-                }
-            };
-
-            if granted_scopes.contains(OAUTH2_SCOPE_SSH_PUBLICKEYS) {
-                pii_scopes.insert(OAUTH2_SCOPE_SSH_PUBLICKEYS.to_string());
-            }
-
-            // Consent token expires in
-            let expiry = ct.as_secs() + 300;
-
-            // Subsequent we then return an encrypted session handle which allows
-            // the user to indicate their consent to this authorisation.
-            //
-            // This session handle is what we use in "permit" to generate the redirect.
-
-            let consent_req = ConsentToken {
-                client_id: auth_req.client_id.clone(),
-                ident_id: ident.get_event_origin_id(),
-                expiry,
-                session_id,
-                state: auth_req.state.clone(),
-                code_challenge,
-                redirect_uri: auth_req.redirect_uri.clone(),
-                scopes: granted_scopes.iter().cloned().collect(),
-                nonce: auth_req.nonce.clone(),
-                response_mode,
-            };
-
-            let consent_jwe = Jwe::into_json(&consent_req).map_err(|err| {
-                error!(?err, "Unable to encode consent data");
-                Oauth2Error::ServerError(OperationError::SerdeJsonError)
-            })?;
-
-            let consent_token = self
-                .oauth2rs
-                .inner
-                .consent_key
-                .encipher::<JweA128GCMEncipher>(&consent_jwe)
-                .map(|jwe_compact| jwe_compact.to_string())
-                .map_err(|err| {
-                    error!(?err, "Unable to encrypt jwe");
-                    Oauth2Error::ServerError(OperationError::CryptographyError)
-                })?;
-
-            Ok(AuthoriseResponse::ConsentRequested {
-                client_name: o2rs.displayname.clone(),
-                scopes: granted_scopes.into_iter().collect(),
-                pii_scopes,
-                consent_token,
-            })
-        }
-    }
+        self.generate_access_token_response(
+            o2rs,
+            ct,
+            scopes,
+            account_uuid,
+            parent_session_id,
+            session_id,
+            nonce,
+            authorization_details,
+            binding,
+        )
+    }
 
     #[instrument(level = "debug", skip_all)]
-    pub fn check_oauth2_authorise_reject(
-        &self,
-        ident: &Identity,
-        consent_token: &str,
+    fn check_oauth2_token_refresh(
+        &mut self,
+        o2rs: &Oauth2RS,
+        refresh_token: &str,
+        req_scopes: Option<&BTreeSet<String>>,
+        binding: TokenBinding,
         ct: Duration,
-    ) -> Result<AuthoriseReject, OperationError> {
-        let jwe_compact = JweCompact::from_str(consent_token).map_err(|_| {
+    ) -> Result<AccessTokenResponse, Oauth2Error> {
+        let jwe_compact = JweCompact::from_str(refresh_token).map_err(|_| {
             error!("Failed to deserialise a valid JWE");
-            OperationError::CryptographyError
+            Oauth2Error::InvalidRequest
         })?;
 
-        // Decode the consent req with our system fernet key. Use a ttl of 5 minutes.
-        let consent_req: ConsentToken = self
-            .oauth2rs
-            .inner
-            .consent_key
-            .decipher(&jwe_compact)
+        // Validate the refresh token decrypts and it's expiry is within the valid window.
+        let token: Oauth2TokenType = o2rs
+            .key_object
+            .jwe_decrypt(&jwe_compact)
             .map_err(|_| {
-                admin_error!("Failed to decrypt consent request");
-                OperationError::CryptographyError
+                admin_error!("Failed to decrypt refresh token request");
+                Oauth2Error::InvalidRequest
             })
             .and_then(|jwe| {
                 jwe.from_json().map_err(|err| {
-                    error!(?err, "Failed to deserialise consent request");
-                    OperationError::SerdeJsonError
+                    error!(?err, "Failed to deserialise token");
+                    Oauth2Error::InvalidRequest
                 })
             })?;
 
-        // Validate that the ident_id matches our current ident.
-        if consent_req.ident_id != ident.get_event_origin_id() {
-            security_info!("consent request ident id does not match the identity of our UAT.");
-            return Err(OperationError::InvalidSessionState);
-        }
-
-        // Validate that the session id matches our session
-        if consent_req.session_id != ident.get_session_id() {
-            security_info!("consent request sessien id does not match the session id of our UAT.");
-            return Err(OperationError::InvalidSessionState);
-        }
+        match token {
+            // Oauth2TokenType::Access { .. } |
+            Oauth2TokenType::ClientAccess { .. } => {
+                admin_error!("attempt to refresh with an access token");
+                Err(Oauth2Error::InvalidRequest)
+            }
+            Oauth2TokenType::Refresh {
+                scopes,
+                parent_session_id,
+                session_id,
+                exp,
+                uuid,
+                iat,
+                nbf: _,
+                nonce,
+                dpop_jkt: bound_jkt,
+                x5t_s256: bound_x5t_s256,
+                authorization_details,
+            } => {
+                if exp <= ct.as_secs() as i64 {
+                    security_info!(?uuid, "refresh token has expired, ");
+                    return Err(Oauth2Error::InvalidGrant);
+                }
 
-        if consent_req.expiry <= ct.as_secs() {
-            // Token is expired
-            error!("Failed to decrypt consent request");
-            return Err(OperationError::CryptographyError);
-        }
+                // A bound refresh token may only be used by the holder of the same key.
+                let dpop_jkt = match (bound_jkt, binding.dpop_jkt) {
+                    (Some(bound_jkt), Some(dpop_jkt)) if bound_jkt == dpop_jkt => Some(dpop_jkt),
+                    (Some(_), _) => {
+                        security_info!(
+                            ?uuid,
+                            "refresh token is DPoP bound, but the proof was not made with the bound key"
+                        );
+                        return Err(Oauth2Error::InvalidGrant);
+                    }
+                    (None, dpop_jkt) => dpop_jkt,
+                };
 
-        // Get the resource server config based on this client_id.
-        let _o2rs = self
-            .oauth2rs
-            .inner
-            .rs_set_get(&consent_req.client_id)
-            .ok_or_else(|| {
-                admin_error!("Invalid consent request OAuth2 client_id");
-                OperationError::InvalidRequestState
-            })?;
+                let x5t_s256 = match (bound_x5t_s256, binding.x5t_s256) {
+                    (Some(bound_x5t_s256), Some(x5t_s256)) if bound_x5t_s256 == x5t_s256 => {
+                        Some(x5t_s256)
+                    }
+                    (Some(_), _) => {
+                        security_info!(
+                            ?uuid,
+                            "refresh token is certificate bound, but was not presented with the bound certificate"
+                        );
+                        return Err(Oauth2Error::InvalidGrant);
+                    }
+                    (None, x5t_s256) => x5t_s256,
+                };
 
-        // All good, now confirm the rejection to the client application.
-        Ok(AuthoriseReject {
-            redirect_uri: consent_req.redirect_uri,
-            response_mode: consent_req.response_mode,
-        })
-    }
+                let binding = TokenBinding { dpop_jkt, x5t_s256 };
 
-    #[instrument(level = "debug", skip_all)]
-    pub fn check_oauth2_token_introspect(
-        &mut self,
-        client_auth_info: &ClientAuthInfo,
-        intr_req: &AccessTokenIntrospectRequest,
-        ct: Duration,
-    ) -> Result<AccessTokenIntrospectResponse, Oauth2Error> {
-        let client_auth = get_client_auth(client_auth_info, &intr_req.client_post_auth)?;
+                // Check the session is still valid. This call checks the parent session
+                // and the OAuth2 session.
+                let valid = self
+                    .check_oauth2_account_uuid_valid(
+                        uuid,
+                        session_id,
+                        Some(parent_session_id),
+                        iat,
+                        ct,
+                    )
+                    .map_err(|_| admin_error!("Account is not valid"));
 
-        // Get the o2rs for the handle.
-        let o2rs = self
-            .oauth2rs
-            .inner
-            .rs_set_get(&client_auth.client_id)
-            .ok_or_else(|| {
-                warn!("Invalid OAuth2 client_id");
-                Oauth2Error::AuthenticationRequired
-            })?;
+                let Ok(Some(entry)) = valid else {
+                    security_info!(
+                        ?uuid,
+                        "access token has no account not valid, returning inactive"
+                    );
+                    return Err(Oauth2Error::InvalidGrant);
+                };
 
-        // We don't need to authenticate, since possession of the access token is already enough
-        // to prove identity, and we enforce it is cryptographically valid so it can't be bruteforced
-        // by a scanning attack.
-        let prefer_short_username = o2rs.prefer_short_username;
+                // Check the not issued before of the session relative to this refresh iat
+                let oauth2_session = entry
+                    .get_ava_as_oauth2session_map(Attribute::OAuth2Session)
+                    .and_then(|map| map.get(&session_id))
+                    .ok_or_else(|| {
+                        security_info!(
+                            ?session_id,
+                            "No OAuth2 session found, unable to proceed with refresh"
+                        );
+                        Oauth2Error::InvalidGrant
+                    })?;
 
-        if let Ok(jwsc) = JwsCompact::from_str(&intr_req.token) {
-            let access_token = o2rs
-                .key_object
-                .jws_verify(&jwsc)
-                .map_err(|err| {
-                    error!(?err, "Unable to verify access token");
-                    Oauth2Error::InvalidRequest
-                })
-                .and_then(|jws| {
-                    jws.from_json().map_err(|err| {
-                        error!(?err, "Unable to deserialise access token");
-                        Oauth2Error::InvalidRequest
-                    })
-                })?;
+                // If the refresh token was issued previous to the time listed in our oauth2_session
+                // this indicates session desync / replay. We must nuke the session at this point.
+                //
+                // Need to think about how to handle this nicely give transactions.
+                if iat < oauth2_session.issued_at.unix_timestamp() {
+                    security_info!(
+                        ?session_id,
+                        "Attempt to reuse a refresh token detected, destroying session"
+                    );
 
-            let OAuth2RFC9068Token::<_> {
-                iss: _,
-                sub,
-                aud: _,
-                exp,
-                nbf,
-                iat,
-                jti,
-                client_id: _,
-                extensions:
-                    OAuth2RFC9068TokenExtensions {
-                        auth_time: _,
-                        acr: _,
-                        amr: _,
-                        scope: scopes,
-                        nonce: _,
-                        session_id,
-                        parent_session_id,
-                    },
-            } = access_token;
-
-            // Has this token expired?
-            if exp <= ct.as_secs() as i64 {
-                security_info!(?sub, "access token has expired, returning inactive");
-                return Ok(AccessTokenIntrospectResponse::inactive(jti));
-            }
-
-            // Is the user expired, or the OAuth2 session invalid?
-            let valid = self
-                .check_oauth2_account_uuid_valid(sub, session_id, parent_session_id, iat, ct)
-                .map_err(|_| admin_error!("Account is not valid"));
-
-            let Ok(Some(entry)) = valid else {
-                security_info!(
-                    ?sub,
-                    "access token account is not valid, returning inactive"
-                );
-                return Ok(AccessTokenIntrospectResponse::inactive(jti));
-            };
-
-            let account = match Account::try_from_entry_ro(&entry, &mut self.qs_read) {
-                Ok(account) => account,
-                Err(err) => return Err(Oauth2Error::ServerError(err)),
-            };
-
-            // ==== good to generate response ====
-
-            let scope = scopes.clone();
-
-            let preferred_username = if prefer_short_username {
-                Some(account.name().into())
-            } else {
-                Some(account.spn().into())
-            };
+                    // Revoke it
+                    let modlist = ModifyList::new_list(vec![Modify::Removed(
+                        Attribute::OAuth2Session,
+                        PartialValue::Refer(session_id),
+                    )]);
 
-            let token_type = Some(AccessTokenType::Bearer);
-            Ok(AccessTokenIntrospectResponse {
-                active: true,
-                scope,
-                client_id: Some(client_auth.client_id.clone()),
-                username: preferred_username,
-                token_type,
-                iat: Some(iat),
-                exp: Some(exp),
-                nbf: Some(nbf),
-                sub: Some(sub.to_string()),
-                aud: Some(client_auth.client_id),
-                iss: None,
-                jti,
-            })
-        } else {
-            let jwe_compact = JweCompact::from_str(&intr_req.token).map_err(|_| {
-                error!("Failed to deserialise a valid JWE");
-                Oauth2Error::InvalidRequest
-            })?;
+                    self.qs_write
+                        .internal_modify(
+                            &filter!(f_eq(Attribute::Uuid, PartialValue::Uuid(uuid))),
+                            &modlist,
+                        )
+                        .map_err(|e| {
+                            admin_error!("Failed to modify - revoke OAuth2 session {:?}", e);
+                            Oauth2Error::ServerError(e)
+                        })?;
 
-            let token: Oauth2TokenType = o2rs
-                .key_object
-                .jwe_decrypt(&jwe_compact)
-                .map_err(|_| {
-                    admin_error!("Failed to decrypt token introspection request");
-                    Oauth2Error::InvalidRequest
-                })
-                .and_then(|jwe| {
-                    jwe.from_json().map_err(|err| {
-                        error!(?err, "Failed to deserialise token");
-                        Oauth2Error::InvalidRequest
-                    })
-                })?;
+                    return Err(Oauth2Error::InvalidGrant);
+                }
 
-            match token {
-                Oauth2TokenType::ClientAccess {
-                    scopes,
-                    session_id,
-                    uuid,
-                    exp,
-                    iat,
-                    nbf,
-                } => {
-                    // Has this token expired?
-                    if exp <= ct.as_secs() as i64 {
-                        security_info!(?uuid, "access token has expired, returning inactive");
-                        return Ok(AccessTokenIntrospectResponse::inactive(session_id));
+                // Check the scopes are equal or subset, OR none.
+                let update_scopes = if let Some(req_scopes) = req_scopes {
+                    if req_scopes.is_subset(&scopes) {
+                        debug!("oauth2 scopes requested, checked as valid.");
+                        // We have to return the requested set since it
+                        // may be constrained.
+                        req_scopes.clone()
+                    } else {
+                        warn!("oauth2 scopes requested, invalid.");
+                        return Err(Oauth2Error::InvalidScope);
                     }
+                } else {
+                    debug!("No OAuth2 scopes requested, this is valid.");
+                    // Return the initial set of scopes.
+                    scopes
+                };
 
-                    // We can't do the same validity check for the client as we do with an account
-                    let valid = self
-                        .check_oauth2_account_uuid_valid(uuid, session_id, None, iat, ct)
-                        .map_err(|_| admin_error!("Account is not valid"));
-
-                    let Ok(Some(entry)) = valid else {
-                        security_info!(
-                            ?uuid,
-                            "access token account is not valid, returning inactive"
-                        );
-                        return Ok(AccessTokenIntrospectResponse::inactive(session_id));
-                    };
-
-                    let scope = scopes.clone();
-
-                    let token_type = Some(AccessTokenType::Bearer);
+                // ----------
+                // good to go
 
-                    let username = if prefer_short_username {
-                        entry
-                            .get_ava_single_iname(Attribute::Name)
-                            .map(|s| s.to_string())
-                    } else {
-                        entry.get_ava_single_proto_string(Attribute::Spn)
-                    };
+                let account_uuid = uuid;
 
-                    Ok(AccessTokenIntrospectResponse {
-                        active: true,
-                        scope,
-                        client_id: Some(client_auth.client_id.clone()),
-                        username,
-                        token_type,
-                        iat: Some(iat),
-                        exp: Some(exp),
-                        nbf: Some(nbf),
-                        sub: Some(uuid.to_string()),
-                        aud: Some(client_auth.client_id),
-                        iss: None,
-                        jti: session_id,
-                    })
-                }
-                Oauth2TokenType::Refresh { session_id, .. } => {
-                    Ok(AccessTokenIntrospectResponse::inactive(session_id))
-                }
+                self.generate_access_token_response(
+                    o2rs,
+                    ct,
+                    update_scopes,
+                    account_uuid,
+                    parent_session_id,
+                    session_id,
+                    nonce,
+                    authorization_details,
+                    binding,
+                )
             }
         }
     }
 
+    #[allow(clippy::too_many_arguments)]
     #[instrument(level = "debug", skip_all)]
-    pub fn oauth2_openid_userinfo(
+    fn check_oauth2_token_exchange_service_account(
         &mut self,
-        client_id: &str,
-        token: &JwsCompact,
+        o2rs: &Oauth2RS,
+        subject_token: &str,
+        subject_token_type: &str,
+        requested_token_type: Option<&str>,
+        audience: Option<&str>,
+        resource: Option<&str>,
+        req_scopes: Option<&BTreeSet<String>>,
+        binding: TokenBinding,
         ct: Duration,
-    ) -> Result<OidcToken, Oauth2Error> {
-        // DANGER: Why do we have to do this? During the use of qs for internal search
-        // and other operations we need qs to be mut. But when we borrow oauth2rs here we
-        // cause multiple borrows to occur on struct members that freaks rust out. This *IS*
-        // safe however because no element of the search or write process calls the oauth2rs
-        // excepting for this idm layer within a single thread, meaning that stripping the
-        // lifetime here is safe since we are the sole accessor.
-        let o2rs: &Oauth2RS = unsafe {
-            let s = self.oauth2rs.inner.rs_set_get(client_id).ok_or_else(|| {
-                warn!("Invalid OAuth2 client_id (have you configured the OAuth2 resource server?)");
-                Oauth2Error::InvalidClientId
-            })?;
-            &*(s as *const _)
-        };
+    ) -> Result<AccessTokenResponse, Oauth2Error> {
+        if let Some(rtt) = requested_token_type {
+            if rtt != OAUTH2_TOKEN_TYPE_ACCESS_TOKEN {
+                warn!(
+                    requested_token_type = rtt,
+                    "Unsupported requested_token_type in token exchange"
+                );
+                return Err(Oauth2Error::InvalidRequest);
+            }
+        }
 
-        let access_token = o2rs
-            .key_object
-            .jws_verify(token)
-            .map_err(|err| {
-                error!(?err, "Unable to verify access token");
+        if let Some(aud) = audience {
+            if aud != o2rs.name {
+                warn!(expected = %o2rs.name, requested = aud, "Token exchange audience mismatch");
+                return Err(Oauth2Error::InvalidTarget);
+            }
+        }
+
+        if let Some(res) = resource {
+            let parsed_resource = Url::parse(res).map_err(|_| {
+                warn!(
+                    requested = res,
+                    "Invalid resource parameter in token exchange"
+                );
                 Oauth2Error::InvalidRequest
-            })
-            .and_then(|jws| {
-                jws.from_json().map_err(|err| {
-                    error!(?err, "Unable to deserialise access token");
-                    Oauth2Error::InvalidRequest
-                })
             })?;
 
-        let OAuth2RFC9068Token::<_> {
-            iss: _,
-            sub,
-            aud: _,
-            exp,
-            nbf,
-            iat,
-            jti: _,
-            client_id: _,
-            extensions:
-                OAuth2RFC9068TokenExtensions {
-                    auth_time: _,
-                    acr: _,
-                    amr: _,
-                    scope: scopes,
-                    nonce,
-                    session_id,
-                    parent_session_id,
-                },
-        } = access_token;
-        // Has this token expired?
-        if exp <= ct.as_secs() as i64 {
-            security_info!(?sub, "access token has expired, returning inactive");
-            return Err(Oauth2Error::InvalidToken);
-        }
+            if parsed_resource.fragment().is_some() {
+                warn!(
+                    requested = res,
+                    "Resource parameter must not contain a fragment"
+                );
+                return Err(Oauth2Error::InvalidRequest);
+            }
 
-        // Is the user expired, or the OAuth2 session invalid?
-        let valid = self
-            .check_oauth2_account_uuid_valid(sub, session_id, parent_session_id, iat, ct)
-            .map_err(|_| admin_error!("Account is not valid"));
+            let origin = parsed_resource.origin();
+            let target_allowed =
+                o2rs.origins.contains(&origin) || o2rs.opaque_origins.contains(&parsed_resource);
+            if !target_allowed {
+                admin_warn!(requested = res, "Token exchange resource target mismatch");
+                return Err(Oauth2Error::InvalidTarget);
+            }
+        }
 
-        let Ok(Some(entry)) = valid else {
+        if subject_token_type != TOKEN_EXCHANGE_SUBJECT_TOKEN_TYPE_ACCESS {
             security_info!(
-                ?sub,
-                "access token has account not valid, returning inactive"
+                ?subject_token_type,
+                "Unsupported subject_token_type in token exchange"
             );
-            return Err(Oauth2Error::InvalidToken);
-        };
-
-        let account = match Account::try_from_entry_ro(&entry, &mut self.qs_read) {
-            Ok(account) => account,
-            Err(err) => return Err(Oauth2Error::ServerError(err)),
-        };
-
-        let amr = None;
-
-        let iss = o2rs.iss.clone();
+            return Err(Oauth2Error::InvalidRequest);
+        }
 
-        let s_claims = s_claims_for_account(o2rs, &account, &scopes);
-        let extra_claims = extra_claims_for_account(&account, &o2rs.claim_map, &scopes);
+        let jwsc = JwsCompact::from_str(subject_token).map_err(|_| {
+            error!("Failed to deserialise subject token for token exchange");
+            Oauth2Error::InvalidRequest
+        })?;
 
-        // ==== good to generate response ====
+        let token = self
+            .validate_and_parse_token_to_identity_token(&jwsc, ct)
+            .map_err(|err| {
+                security_info!(?err, "Unable to validate subject token for token exchange");
+                Oauth2Error::InvalidRequest
+            })?;
 
-        Ok(OidcToken {
-            iss,
-            sub: OidcSubject::U(sub),
-            aud: client_id.to_string(),
-            iat,
-            nbf: Some(nbf),
-            exp,
-            auth_time: None,
-            nonce,
-            at_hash: None,
-            acr: None,
-            amr,
-            azp: Some(client_id.to_string()),
-            jti: Some(session_id.to_string()),
-            s_claims,
-            claims: extra_claims,
-        })
-    }
+        let (apit, entry) = match token {
+            Token::ApiToken(apit, entry) => (apit, entry),
+            Token::UserAuthToken(_) => {
+                security_info!("Token exchange subject_token must be a service account api token");
+                return Err(Oauth2Error::InvalidRequest);
+            }
+        };
 
-    #[instrument(level = "debug", skip_all)]
-    pub fn oauth2_rfc8414_metadata(
-        &self,
-        client_id: &str,
-    ) -> Result<Oauth2Rfc8414MetadataResponse, OperationError> {
-        let o2rs = self.oauth2rs.inner.rs_set_get(client_id).ok_or_else(|| {
-            warn!("Invalid OAuth2 client_id (have you configured the OAuth2 resource server?)");
-            OperationError::NoMatchingEntries
-        })?;
+        let ident = self
+            .process_apit_to_identity(&apit, Source::Internal, entry, ct)
+            .map_err(|err| match err {
+                OperationError::SessionExpired | OperationError::NotAuthenticated => {
+                    security_info!(
+                        ?err,
+                        "Service account api token rejected during token exchange"
+                    );
+                    Oauth2Error::InvalidRequest
+                }
+                err => Oauth2Error::ServerError(err),
+            })?;
 
-        let issuer = o2rs.iss.clone();
-        let authorization_endpoint = o2rs.authorization_endpoint.clone();
-        let token_endpoint = o2rs.token_endpoint.clone();
-        let revocation_endpoint = Some(o2rs.revocation_endpoint.clone());
-        let introspection_endpoint = Some(o2rs.introspection_endpoint.clone());
-        let jwks_uri = Some(o2rs.jwks_uri.clone());
-        let scopes_supported = Some(o2rs.scopes_supported.iter().cloned().collect());
-        let response_types_supported = vec![ResponseType::Code];
-        let response_modes_supported = vec![ResponseMode::Query, ResponseMode::Fragment];
-        let grant_types_supported = vec![GrantType::AuthorisationCode, GrantType::TokenExchange];
+        let (_req_scopes, granted_scopes) =
+            process_requested_scopes_for_identity(o2rs, &ident, req_scopes)?;
 
-        let token_endpoint_auth_methods_supported = vec![
-            TokenEndpointAuthMethod::ClientSecretBasic,
-            TokenEndpointAuthMethod::ClientSecretPost,
-        ];
+        let session_id = Uuid::new_v4();
+        let parent_session_id = apit.token_id;
+        let account_uuid = apit.account_id;
 
-        let revocation_endpoint_auth_methods_supported = vec![
-            TokenEndpointAuthMethod::ClientSecretBasic,
-            TokenEndpointAuthMethod::ClientSecretPost,
-        ];
+        self.generate_access_token_response(
+            o2rs,
+            ct,
+            granted_scopes,
+            account_uuid,
+            parent_session_id,
+            session_id,
+            None,
+            Vec::with_capacity(0),
+            binding,
+        )
+    }
 
-        let introspection_endpoint_auth_methods_supported = vec![
-            TokenEndpointAuthMethod::ClientSecretBasic,
-            TokenEndpointAuthMethod::ClientSecretPost,
-        ];
+    fn check_oauth2_token_client_credentials(
+        &mut self,
+        o2rs: &Oauth2RS,
+        req_scopes: Option<&BTreeSet<String>>,
+        binding: TokenBinding,
+        ct: Duration,
+    ) -> Result<AccessTokenResponse, Oauth2Error> {
+        let req_scopes = req_scopes.cloned().unwrap_or_default();
 
-        let service_documentation = Some(URL_SERVICE_DOCUMENTATION.clone());
+        // Validate all request scopes have valid syntax.
+        validate_scopes(&req_scopes)?;
 
-        let code_challenge_methods_supported = if o2rs.require_pkce() {
-            vec![PkceAlg::S256]
-        } else {
-            Vec::with_capacity(0)
-        };
+        // Of these scopes, which do we have available?
+        let avail_scopes: Vec<String> = req_scopes
+            .intersection(&o2rs.client_scopes)
+            .map(|s| s.to_string())
+            .collect();
 
-        Ok(Oauth2Rfc8414MetadataResponse {
-            issuer,
-            authorization_endpoint,
-            token_endpoint,
-            jwks_uri,
-            registration_endpoint: None,
-            scopes_supported,
-            response_types_supported,
-            response_modes_supported,
-            grant_types_supported,
-            token_endpoint_auth_methods_supported,
-            token_endpoint_auth_signing_alg_values_supported: None,
-            service_documentation,
-            ui_locales_supported: None,
-            op_policy_uri: None,
-            op_tos_uri: None,
-            revocation_endpoint,
-            revocation_endpoint_auth_methods_supported,
-            introspection_endpoint,
-            introspection_endpoint_auth_methods_supported,
-            introspection_endpoint_auth_signing_alg_values_supported: None,
-            code_challenge_methods_supported,
-        })
-    }
+        if avail_scopes.len() != req_scopes.len() {
+            admin_warn!(
+                ident = %o2rs.name,
+                requested_scopes = ?req_scopes,
+                available_scopes = ?o2rs.client_scopes,
+                "Client does not have access to the requested scopes"
+            );
+            return Err(Oauth2Error::AccessDenied);
+        }
 
-    #[instrument(level = "debug", skip_all)]
-    pub fn oauth2_openid_discovery(
-        &self,
-        client_id: &str,
-    ) -> Result<OidcDiscoveryResponse, OperationError> {
-        let o2rs = self.oauth2rs.inner.rs_set_get(client_id).ok_or_else(|| {
-            warn!("Invalid OAuth2 client_id (have you configured the OAuth2 resource server?)");
-            OperationError::NoMatchingEntries
-        })?;
+        // == ready to build the access token ==
 
-        let issuer = o2rs.iss.clone();
+        let granted_scopes = avail_scopes
+            .into_iter()
+            .chain(o2rs.client_sup_scopes.iter().cloned())
+            .collect::<BTreeSet<_>>();
 
-        let authorization_endpoint = o2rs.authorization_endpoint.clone();
-        let token_endpoint = o2rs.token_endpoint.clone();
-        let userinfo_endpoint = Some(o2rs.userinfo_endpoint.clone());
-        let jwks_uri = o2rs.jwks_uri.clone();
-        let scopes_supported = Some(o2rs.scopes_supported.iter().cloned().collect());
-        let response_types_supported = vec![ResponseType::Code];
-        let response_modes_supported = vec![ResponseMode::Query, ResponseMode::Fragment];
+        let odt_ct = OffsetDateTime::UNIX_EPOCH + ct;
+        let iat = ct.as_secs() as i64;
+        let exp = iat + OAUTH2_ACCESS_TOKEN_EXPIRY as i64;
+        let odt_exp = odt_ct + Duration::from_secs(OAUTH2_ACCESS_TOKEN_EXPIRY as u64);
+        let expires_in = OAUTH2_ACCESS_TOKEN_EXPIRY;
 
-        // TODO: add device code if the rs supports it per <https://www.rfc-editor.org/rfc/rfc8628#section-4>
-        // `urn:ietf:params:oauth:grant-type:device_code`
-        let grant_types_supported = vec![GrantType::AuthorisationCode, GrantType::TokenExchange];
+        let session_id = Uuid::new_v4();
 
-        let subject_types_supported = vec![SubjectType::Public];
+        let scope = granted_scopes.clone();
 
-        let id_token_signing_alg_values_supported = match &o2rs.sign_alg {
-            SignatureAlgo::Es256 => vec![IdTokenSignAlg::ES256],
-            SignatureAlgo::Rs256 => vec![IdTokenSignAlg::RS256],
-        };
+        let uuid = o2rs.uuid;
 
-        let userinfo_signing_alg_values_supported = None;
-        let token_endpoint_auth_methods_supported = vec![
-            TokenEndpointAuthMethod::ClientSecretBasic,
-            TokenEndpointAuthMethod::ClientSecretPost,
-        ];
-        let display_values_supported = Some(vec![DisplayValue::Page]);
-        let claim_types_supported = vec![ClaimType::Normal];
-        // What claims can we offer?
-        let claims_supported = None;
-        let service_documentation = Some(URL_SERVICE_DOCUMENTATION.clone());
+        let token_type = binding.token_type();
 
-        let code_challenge_methods_supported = if o2rs.require_pkce() {
-            vec![PkceAlg::S256]
-        } else {
-            Vec::with_capacity(0)
+        let access_token_raw = Oauth2TokenType::ClientAccess {
+            scopes: granted_scopes,
+            session_id,
+            uuid,
+            exp,
+            iat,
+            nbf: iat,
+            dpop_jkt: binding.dpop_jkt,
+            x5t_s256: binding.x5t_s256,
         };
 
-        // The following are extensions allowed by the oidc specification.
+        let access_token_data = Jwe::into_json(&access_token_raw).map_err(|err| {
+            error!(?err, "Unable to encode token data");
+            Oauth2Error::ServerError(OperationError::SerdeJsonError)
+        })?;
 
-        let revocation_endpoint = Some(o2rs.revocation_endpoint.clone());
-        let revocation_endpoint_auth_methods_supported = vec![
-            TokenEndpointAuthMethod::ClientSecretBasic,
-            TokenEndpointAuthMethod::ClientSecretPost,
-        ];
+        let access_token = o2rs
+            .key_object
+            .jwe_a128gcm_encrypt(&access_token_data, ct)
+            .map(|jwe| jwe.to_string())
+            .map_err(|err| {
+                error!(?err, "Unable to encode token data");
+                Oauth2Error::ServerError(OperationError::CryptographyError)
+            })?;
 
-        let introspection_endpoint = Some(o2rs.introspection_endpoint.clone());
-        let introspection_endpoint_auth_methods_supported = vec![
-            TokenEndpointAuthMethod::ClientSecretBasic,
-            TokenEndpointAuthMethod::ClientSecretPost,
-        ];
+        // Write the session to the db
+        let session = Value::Oauth2Session(
+            session_id,
+            Oauth2Session {
+                parent: None,
+                state: SessionState::ExpiresAt(odt_exp),
+                issued_at: odt_ct,
+                rs_uuid: o2rs.uuid,
+            },
+        );
 
-        Ok(OidcDiscoveryResponse {
-            issuer,
-            authorization_endpoint,
-            token_endpoint,
-            userinfo_endpoint,
-            jwks_uri,
-            registration_endpoint: None,
-            scopes_supported,
-            response_types_supported,
-            response_modes_supported,
-            grant_types_supported,
-            acr_values_supported: None,
-            subject_types_supported,
-            id_token_signing_alg_values_supported,
-            id_token_encryption_alg_values_supported: None,
-            id_token_encryption_enc_values_supported: None,
-            userinfo_signing_alg_values_supported,
-            userinfo_encryption_alg_values_supported: None,
-            userinfo_encryption_enc_values_supported: None,
-            request_object_signing_alg_values_supported: None,
-            request_object_encryption_alg_values_supported: None,
-            request_object_encryption_enc_values_supported: None,
-            token_endpoint_auth_methods_supported,
-            token_endpoint_auth_signing_alg_values_supported: None,
-            display_values_supported,
-            claim_types_supported,
-            claims_supported,
-            service_documentation,
-            claims_locales_supported: None,
-            ui_locales_supported: None,
-            claims_parameter_supported: false,
-            // TODO: once we support RFC9101 this can be true again
-            request_parameter_supported: false,
-            // TODO: if we support RFC9101 request_uri methods this can be true
-            request_uri_parameter_supported: false,
-            // TODO: if we support RFC9101 request_uri methods this should be true
-            require_request_uri_registration: false,
-            op_policy_uri: None,
-            op_tos_uri: None,
-            code_challenge_methods_supported,
-            // Extensions
-            revocation_endpoint,
-            revocation_endpoint_auth_methods_supported,
-            introspection_endpoint,
-            introspection_endpoint_auth_methods_supported,
-            introspection_endpoint_auth_signing_alg_values_supported: None,
-            device_authorization_endpoint: o2rs.device_authorization_endpoint.clone(),
+        // We need to create this session on the o2rs
+        let modlist =
+            ModifyList::new_list(vec![Modify::Present(Attribute::OAuth2Session, session)]);
+
+        self.qs_write
+            .internal_modify(
+                &filter!(f_eq(Attribute::Uuid, PartialValue::Uuid(uuid))),
+                &modlist,
+            )
+            .map_err(|e| {
+                admin_error!("Failed to persist OAuth2 session record {:?}", e);
+                Oauth2Error::ServerError(e)
+            })?;
+
+        Ok(AccessTokenResponse {
+            access_token,
+            token_type,
+            issued_token_type: Some(IssuedTokenType::AccessToken),
+            expires_in,
+            refresh_token: None,
+            scope,
+            id_token: None,
+            authorization_details: None,
         })
     }
 
-    #[instrument(level = "debug", skip_all)]
-    pub fn oauth2_openid_webfinger(
+    #[allow(clippy::too_many_arguments)]
+    fn generate_access_token_response(
         &mut self,
-        client_id: &str,
-        resource_id: &str,
-    ) -> Result<OidcWebfingerResponse, OperationError> {
-        let o2rs = self.oauth2rs.inner.rs_set_get(client_id).ok_or_else(|| {
-            warn!("Invalid OAuth2 client_id (have you configured the OAuth2 resource server?)");
-            OperationError::NoMatchingEntries
-        })?;
+        o2rs: &Oauth2RS,
+        ct: Duration,
+        //
+        scopes: BTreeSet<String>,
+        account_uuid: Uuid,
+        parent_session_id: Uuid,
+        session_id: Uuid,
+        nonce: Option<String>,
+        authorization_details: Vec<AuthorisationDetail>,
+        binding: TokenBinding,
+    ) -> Result<AccessTokenResponse, Oauth2Error> {
+        let odt_ct = OffsetDateTime::UNIX_EPOCH + ct;
+        let iat = ct.as_secs() as i64;
 
-        let Some(spn) = PartialValue::new_spn_s(resource_id) else {
-            return Err(OperationError::NoMatchingEntries);
-        };
+        // TODO: Configurable from the oauth2rs configuration?
 
-        // Ensure that the account exists.
-        if !self
-            .qs_read
-            .internal_exists(&Filter::new(f_eq(Attribute::Spn, spn)))?
-        {
-            return Err(OperationError::NoMatchingEntries);
-        }
+        // Disclaimer: It may seem odd here that we are ignoring our session when it comes to expiry
+        // times. However, this actually is valid because when we take the initial code exchange
+        // path we have already validate the expiry of the account/session in that process. For the
+        // refresh path, we validate the session expiry and validity before we call this. So these
+        // expiries are *purely* for the tokens we issue and are *not related* to the expiries of the
+        // the session - these are enforced as above!
 
-        let issuer = o2rs.iss.clone();
+        let expiry = odt_ct + Duration::from_secs(OAUTH2_ACCESS_TOKEN_EXPIRY as u64);
+        let expires_in = OAUTH2_ACCESS_TOKEN_EXPIRY;
+        let refresh_expiry = iat + OAUTH_REFRESH_TOKEN_EXPIRY as i64;
+        let odt_refresh_expiry = odt_ct + Duration::from_secs(OAUTH_REFRESH_TOKEN_EXPIRY);
 
-        Ok(OidcWebfingerResponse {
-            // we set the subject to the resource_id to ensure we always send something valid back
-            // but realistically this will be overwritten on at the API layer
-            subject: resource_id.to_string(),
-            links: vec![OidcWebfingerRel {
-                rel: "http://openid.net/specs/connect/1.0/issuer".into(),
-                href: issuer.into(),
-            }],
-        })
-    }
+        let scope = scopes.clone();
 
-    #[instrument(level = "debug", skip_all)]
-    pub fn oauth2_openid_publickey(&self, client_id: &str) -> Result<JwkKeySet, OperationError> {
-        let o2rs = self.oauth2rs.inner.rs_set_get(client_id).ok_or_else(|| {
-            warn!("Invalid OAuth2 client_id (have you configured the OAuth2 resource server?)");
-            OperationError::NoMatchingEntries
-        })?;
+        let granted_authorization_details =
+            (!authorization_details.is_empty()).then(|| authorization_details.clone());
 
-        trace!(sign_alg = ?o2rs.sign_alg);
+        let iss = o2rs.iss.clone();
 
-        match o2rs.sign_alg {
-            SignatureAlgo::Es256 => o2rs.key_object.jws_es256_jwks(),
-            SignatureAlgo::Rs256 => o2rs.key_object.jws_rs256_jwks(),
-        }
-        .ok_or_else(|| {
-            error!(o2_client = ?o2rs.name, "Unable to retrieve public keys");
-            OperationError::InvalidState
-        })
-    }
-}
+        // Just reflect the access token expiry.
+        let exp = expiry.unix_timestamp();
 
-fn parse_basic_authz(client_authz: &str) -> Result<ClientAuth, Oauth2Error> {
-    // Check the client_authz
-    let authz = general_purpose::STANDARD
-        .decode(client_authz)
-        .map_err(|_| {
-            admin_error!("Basic authz invalid base64");
-            Oauth2Error::AuthenticationRequired
-        })
-        .and_then(|data| {
-            String::from_utf8(data).map_err(|_| {
-                admin_error!("Basic authz invalid utf8");
-                Oauth2Error::AuthenticationRequired
-            })
-        })?;
+        let aud = o2rs.name.clone();
 
-    // Get the first :, it should be our delim.
-    //
-    let mut split_iter = authz.split(':');
+        let client_id = o2rs.name.clone();
 
-    let client_id = split_iter.next().ok_or_else(|| {
-        admin_error!("Basic authz invalid format (corrupt input?)");
-        Oauth2Error::AuthenticationRequired
-    })?;
-    let secret = split_iter.next().ok_or_else(|| {
-        admin_error!("Basic authz invalid format (missing ':' separator?)");
-        Oauth2Error::AuthenticationRequired
-    })?;
+        let id_token = if scopes.contains(OAUTH2_SCOPE_OPENID) {
+            // TODO: Scopes map to claims:
+            //
+            // * profile - (name, family\_name, given\_name, middle\_name, nickname, preferred\_username, profile, picture, website, gender, birthdate, zoneinfo, locale, and updated\_at)
+            // * email - (email, email\_verified)
+            // * address - (address)
+            // * phone - (phone\_number, phone\_number\_verified)
+            //
+            // https://openid.net/specs/openid-connect-basic-1_0.html#StandardClaims
 
-    // OAuth2 is bad and should feel bad:
-    // https://datatracker.ietf.org/doc/html/rfc6749#section-2.3.1
-    // The client_id MIGHT be url encoded. The secret won't be, because we
-    // always generate it in a "safe" manner.
-    //
-    // If we *fail* to decode, then we just drop the raw client_id back out,
-    // as this could be a client that didn't urlencoded their client_id.
-    //
-    // Both serde_urlencode and form_urlencoded can't handle this, as they assume
-    // you have a map, so you can't deseralise to a string.
-    //
-    // Thankfully in our case we only allow - and _ so we can just replace these
-    // directly.
-    let client_id = client_id.replace("%2D", "-").replace("%5F", "_");
+            // TODO: Can the user consent to which claims are released? Today as we don't support most
+            // of them anyway, no, but in the future, we can stash these to the consent req.
 
-    Ok((client_id.as_str(), Some(secret)).into())
-}
+            // TODO: If max_age was requested in the request, we MUST provide auth_time.
 
-fn s_claims_for_account(
-    o2rs: &Oauth2RS,
-    account: &Account,
-    scopes: &BTreeSet<String>,
-) -> OidcClaims {
-    let preferred_username = if o2rs.prefer_short_username {
-        Some(account.name().into())
-    } else {
-        Some(account.spn().into())
-    };
+            // amr == auth method
+            // We removed this from uat, and I think that it's okay here. AMR is a bit useless anyway
+            // since there is no standard for what it should look like wrt to cred strength.
+            let amr = None;
 
-    let (email, email_verified) = if scopes.contains(OAUTH2_SCOPE_EMAIL) {
-        if let Some(mp) = &account.mail_primary {
-            (Some(mp.clone()), Some(true))
-        } else {
-            (None, None)
-        }
-    } else {
-        (None, None)
-    };
+            let entry = match self.qs_write.internal_search_uuid(account_uuid) {
+                Ok(entry) => entry,
+                Err(err) => return Err(Oauth2Error::ServerError(err)),
+            };
 
-    let updated_at: Option<OffsetDateTime> = if scopes.contains(OAUTH2_SCOPE_PROFILE) {
-        account
-            .updated_at
-            .as_ref()
-            .map(OffsetDateTime::from)
-            .and_then(|odt| odt.replace_nanosecond(0).ok())
-    } else {
-        None
-    };
-    OidcClaims {
-        // Map from displayname
-        name: Some(account.displayname.clone()),
-        scopes: scopes.iter().cloned().collect(),
-        preferred_username,
-        email,
-        email_verified,
-        updated_at,
-        ..Default::default()
-    }
-}
+            let account = match Account::try_from_entry_rw(&entry, &mut self.qs_write) {
+                Ok(account) => account,
+                Err(err) => return Err(Oauth2Error::ServerError(err)),
+            };
 
-fn extra_claims_for_account(
-    account: &Account,
+            let s_claims = s_claims_for_account(o2rs, &account, &scopes);
+            let mut extra_claims = extra_claims_for_account(&account, &o2rs.claim_map, &scopes);
 
-    claim_map: &BTreeMap<Uuid, Vec<(String, ClaimValue)>>,
+            // Back-channel logout tokens identify the session by sid, so the client needs
+            // to be able to associate it with its own session.
+            if o2rs.backchannel_logout_uri.is_some() {
+                extra_claims.insert("sid".to_string(), session_id.to_string().into());
+            }
 
-    scopes: &BTreeSet<String>,
-) -> BTreeMap<String, serde_json::Value> {
-    let mut extra_claims = BTreeMap::new();
+            let oidc = OidcToken {
+                iss: iss.clone(),
+                sub: OidcSubject::U(o2rs.subject(account_uuid)),
+                aud: aud.clone(),
+                iat,
+                nbf: Some(iat),
+                exp,
+                auth_time: None,
+                nonce: nonce.clone(),
+                at_hash: None,
+                acr: None,
+                amr,
+                azp: Some(o2rs.name.clone()),
+                jti: Some(session_id.to_string()),
+                s_claims,
+                claims: extra_claims,
+            };
 
-    let mut account_claims: BTreeMap<&str, ClaimValue> = BTreeMap::new();
+            trace!(?oidc);
+            let oidc = JwsBuilder::into_json(&oidc)
+                .map(|builder| builder.build())
+                .map_err(|err| {
+                    admin_error!(?err, "Unable to encode access token data");
+                    Oauth2Error::ServerError(OperationError::InvalidState)
+                })?;
 
-    // for each group
-    for group_uuid in account.groups.iter().map(|g| g.uuid()) {
-        // Does this group have any custom claims?
-        if let Some(claim) = claim_map.get(group_uuid) {
-            // If so, iterate over the set of claims and values.
-            for (claim_name, claim_value) in claim.iter() {
-                // Does this claim name already exist in our in-progress map?
-                match account_claims.entry(claim_name.as_str()) {
-                    BTreeEntry::Vacant(e) => {
-                        e.insert(claim_value.clone());
-                    }
-                    BTreeEntry::Occupied(mut e) => {
-                        let mut_claim_value = e.get_mut();
-                        // Merge the extra details into this.
-                        mut_claim_value.merge(claim_value);
-                    }
-                }
+            let jwt_signed = match o2rs.sign_alg {
+                SignatureAlgo::Es256 => o2rs.key_object.jws_es256_sign(&oidc, ct),
+                SignatureAlgo::Rs256 => o2rs.key_object.jws_rs256_sign(&oidc, ct),
             }
-        }
-    }
+            .map_err(|err| {
+                error!(?err, "Unable to encode oidc token data");
+                Oauth2Error::ServerError(OperationError::InvalidState)
+            })?;
 
-    // Now, flatten all these into the final structure.
-    for (claim_name, claim_value) in account_claims {
-        extra_claims.insert(claim_name.to_string(), claim_value.to_json_value());
-    }
+            // An encrypted id_token is a nested JWT, signed and then encrypted.
+            let jwt_nested = JweBuilder::from(jwt_signed.to_string().into_bytes())
+                .set_cty(Some("JWT"))
+                .build();
 
-    // Now perform our custom claim's from scopes. We do these second so that
-    // a user can't stomp our claim names.
+            match o2rs.encrypt_response(&jwt_nested)? {
+                Some(jwt_encrypted) => Some(jwt_encrypted.to_string()),
+                None => Some(jwt_signed.to_string()),
+            }
+        } else {
+            // id_token is not required in non-openid flows.
+            None
+        };
 
-    if scopes.contains(OAUTH2_SCOPE_SSH_PUBLICKEYS) {
-        extra_claims.insert(
-            OAUTH2_SCOPE_SSH_PUBLICKEYS.to_string(),
-            account
-                .sshkeys()
-                .values()
-                .map(|pub_key| serde_json::Value::String(pub_key.to_string()))
-                .collect(),
-        );
-    }
+        // We need to record this into the record? Delayed action?
+        let access_token_data = OAuth2RFC9068Token {
+            iss: iss.to_string(),
+            sub: o2rs.subject(account_uuid),
+            aud,
+            exp,
+            nbf: iat,
+            iat,
+            jti: session_id,
+            client_id,
+            extensions: OAuth2RFC9068TokenExtensions {
+                auth_time: None,
+                acr: None,
+                amr: None,
+                scope: scopes.clone(),
+                nonce: nonce.clone(),
+                session_id,
+                parent_session_id: Some(parent_session_id),
+                cnf: binding.cnf(),
+                authorization_details: granted_authorization_details.clone(),
+            },
+        };
 
-    let wants_groups = scopes.contains(OAUTH2_SCOPE_GROUPS);
-    // groups implies uuid + spn to match current behaviour.
-    let wants_groups_uuid = wants_groups || scopes.contains(OAUTH2_SCOPE_GROUPS_UUID);
-    let wants_groups_spn = wants_groups || scopes.contains(OAUTH2_SCOPE_GROUPS_SPN);
-    let wants_groups_name = scopes.contains(OAUTH2_SCOPE_GROUPS_NAME);
+        let access_token_data = JwsBuilder::into_json(&access_token_data)
+            .map(|builder| builder.set_typ(Some("at+jwt")).build())
+            .map_err(|err| {
+                error!(?err, "Unable to encode access token data");
+                Oauth2Error::ServerError(OperationError::InvalidState)
+            })?;
 
-    if wants_groups_uuid || wants_groups_name || wants_groups_spn {
-        extra_claims.insert(
-            OAUTH2_SCOPE_GROUPS.to_string(),
-            account
-                .groups
-                .iter()
-                .flat_map(|group| {
-                    let mut attrs = Vec::with_capacity(3);
+        let access_token = match o2rs.sign_alg {
+            SignatureAlgo::Es256 => o2rs.key_object.jws_es256_sign(&access_token_data, ct),
+            SignatureAlgo::Rs256 => o2rs.key_object.jws_rs256_sign(&access_token_data, ct),
+        }
+        .map_err(|e| {
+            admin_error!(err = ?e, "Unable to sign access token data");
+            Oauth2Error::ServerError(OperationError::InvalidState)
+        })?;
 
-                    if wants_groups_uuid {
-                        attrs.push(group.uuid().as_hyphenated().to_string())
-                    }
+        let token_type = binding.token_type();
 
-                    if wants_groups_spn {
-                        attrs.push(group.spn().clone())
-                    }
+        let refresh_token_raw = Oauth2TokenType::Refresh {
+            scopes,
+            parent_session_id,
+            session_id,
+            exp: refresh_expiry,
+            uuid: account_uuid,
+            iat,
+            nbf: iat,
+            nonce,
+            dpop_jkt: binding.dpop_jkt,
+            x5t_s256: binding.x5t_s256,
+            authorization_details,
+        };
 
-                    if wants_groups_name {
-                        if let Some(name) = group.name() {
-                            attrs.push(name.into())
-                        }
-                    }
+        let refresh_token_data = Jwe::into_json(&refresh_token_raw).map_err(|err| {
+            error!(?err, "Unable to encode token data");
+            Oauth2Error::ServerError(OperationError::SerdeJsonError)
+        })?;
 
-                    attrs
-                })
-                .collect(),
-        );
-    }
+        let refresh_token = o2rs
+            .key_object
+            .jwe_a128gcm_encrypt(&refresh_token_data, ct)
+            .map(|jwe| jwe.to_string())
+            .map_err(|err| {
+                error!(?err, "Unable to encrypt token data");
+                Oauth2Error::ServerError(OperationError::CryptographyError)
+            })?;
 
-    trace!(?extra_claims);
+        // Write the session to the db even with the refresh path, we need to do
+        // this to update the "not issued before" time.
+        let session = Value::Oauth2Session(
+            session_id,
+            Oauth2Session {
+                parent: Some(parent_session_id),
+                state: SessionState::ExpiresAt(odt_refresh_expiry),
+                issued_at: odt_ct,
+                rs_uuid: o2rs.uuid,
+            },
+        );
 
-    extra_claims
-}
+        // We need to update (replace) this session id if present.
+        let modlist = ModifyList::new_list(vec![
+            // NOTE: Oauth2_session has special handling that allows update in place without
+            // the remove step needing to be carried out.
+            // Modify::Removed("oauth2_session".into(), PartialValue::Refer(session_id)),
+            Modify::Present(Attribute::OAuth2Session, session),
+        ]);
 
-fn process_requested_scopes_for_identity(
-    o2rs: &Oauth2RS,
-    ident: &Identity,
-    req_scopes: Option<&BTreeSet<String>>,
-) -> Result<(BTreeSet<String>, BTreeSet<String>), Oauth2Error> {
-    let req_scopes = req_scopes.cloned().unwrap_or_default();
+        self.qs_write
+            .internal_modify(
+                &filter!(f_eq(Attribute::Uuid, PartialValue::Uuid(account_uuid))),
+                &modlist,
+            )
+            .map_err(|e| {
+                admin_error!("Failed to persist OAuth2 session record {:?}", e);
+                Oauth2Error::ServerError(e)
+            })?;
 
-    if req_scopes.is_empty() {
-        admin_error!("Invalid OAuth2 request - must contain at least one requested scope");
-        return Err(Oauth2Error::InvalidRequest);
+        Ok(AccessTokenResponse {
+            access_token: access_token.to_string(),
+            token_type,
+            issued_token_type: Some(IssuedTokenType::AccessToken),
+            expires_in,
+            refresh_token: Some(refresh_token),
+            scope,
+            id_token,
+            authorization_details: granted_authorization_details,
+        })
     }
 
-    validate_scopes(&req_scopes)?;
-
-    let available_scopes: BTreeSet<String> = o2rs
-        .scope_maps
-        .iter()
-        .filter_map(|(u, m)| ident.is_memberof(*u).then_some(m.iter()))
-        .flatten()
-        .cloned()
-        .collect();
+    #[cfg(test)]
+    fn reflect_oauth2_token(
+        &mut self,
+        client_auth_info: &ClientAuthInfo,
+        token: &str,
+    ) -> Result<Oauth2TokenType, OperationError> {
+        let Some(client_authz) = client_auth_info.basic_authz.as_ref() else {
+            warn!("OAuth2 client_id not provided by basic authz");
+            return Err(OperationError::InvalidSessionState);
+        };
 
-    if !req_scopes.is_subset(&available_scopes) {
-        admin_warn!(
-            %ident,
-            requested_scopes = ?req_scopes,
-            available_scopes = ?available_scopes,
-            "Identity does not have access to the requested scopes"
-        );
-        return Err(Oauth2Error::AccessDenied);
-    }
+        let client_auth = parse_basic_authz(client_authz.as_str()).map_err(|_| {
+            warn!("Invalid client_authz base64");
+            OperationError::InvalidSessionState
+        })?;
 
-    let granted_scopes: BTreeSet<String> = o2rs
-        .sup_scope_maps
-        .iter()
-        .filter_map(|(u, m)| ident.is_memberof(*u).then_some(m.iter()))
-        .flatten()
-        .cloned()
-        .chain(req_scopes.iter().cloned())
-        .collect();
+        // Get the o2rs for the handle.
+        let o2rs = self
+            .oauth2rs
+            .inner
+            .rs_set_get(&client_auth.client_id)
+            .ok_or_else(|| {
+                warn!("Invalid OAuth2 client_id");
+                OperationError::InvalidSessionState
+            })?;
 
-    Ok((req_scopes, granted_scopes))
-}
+        // check the secret.
+        if let OauthRSType::Basic { authz_secret, .. } = &o2rs.type_ {
+            if o2rs.is_basic() && Some(authz_secret) != client_auth.client_secret.as_ref() {
+                info!(
+                    "Invalid OAuth2 secret for client_id={}",
+                    client_auth.client_id
+                );
+                return Err(OperationError::InvalidSessionState);
+            }
+        }
 
-fn validate_scopes(req_scopes: &BTreeSet<String>) -> Result<(), Oauth2Error> {
-    let failed_scopes = req_scopes
-        .iter()
-        .filter(|&s| !OAUTHSCOPE_RE.is_match(s))
-        .cloned()
-        .collect::<Vec<String>>();
+        let jwe_compact = JweCompact::from_str(token).map_err(|err| {
+            error!(?err, "Failed to deserialise a valid JWE");
+            OperationError::InvalidSessionState
+        })?;
 
-    if !failed_scopes.is_empty() {
-        let requested_scopes_string = req_scopes
-            .iter()
-            .cloned()
-            .collect::<Vec<String>>()
-            .join(",");
-        admin_error!(
-                "Invalid OAuth2 request - requested scopes ({}) but ({}) failed to pass validation rules - all must match the regex {}",
-                    requested_scopes_string,
-                    failed_scopes.join(","),
-                    OAUTHSCOPE_RE.as_str()
-            );
-        return Err(Oauth2Error::InvalidScope);
+        o2rs.key_object
+            .jwe_decrypt(&jwe_compact)
+            .map_err(|err| {
+                error!(?err, "Failed to decrypt token reflection request");
+                OperationError::CryptographyError
+            })
+            .and_then(|jwe| {
+                jwe.from_json().map_err(|err| {
+                    error!(?err, "Failed to deserialise token for reflection");
+                    OperationError::SerdeJsonError
+                })
+            })
     }
-    Ok(())
 }
 
-/// device code is a random bucket of bytes used in the device flow
-#[inline]
-#[cfg(any(feature = "dev-oauth2-device-flow", test))]
-#[allow(dead_code)]
-fn gen_device_code() -> Result<[u8; 16], Oauth2Error> {
-    use rand::TryRng;
-
-    let mut rng = rand::rng();
-    let mut result = [0u8; 16];
-    // doing it here because of feature-shenanigans.
-    if let Err(err) = rng.try_fill_bytes(&mut result) {
-        error!("Failed to generate device code! {:?}", err);
-        return Err(Oauth2Error::ServerError(OperationError::Backend));
+impl IdmServerProxyReadTransaction<'_> {
+    #[instrument(level = "debug", skip_all)]
+    pub fn check_oauth2_authorisation(
+        &self,
+        maybe_ident: Option<&Identity>,
+        auth_req: &AuthorisationRequest,
+        ct: Duration,
+    ) -> Result<AuthoriseResponse, Oauth2Error> {
+        self.check_oauth2_authorisation_inner(maybe_ident, auth_req, false, ct)
     }
-    Ok(result)
-}
 
-#[inline]
-#[cfg(any(feature = "dev-oauth2-device-flow", test))]
-#[allow(dead_code)]
-/// Returns (xxx-yyy-zzz, digits) where one's the human-facing code, the other is what we store in the DB.
-fn gen_user_code() -> (String, u32) {
-    use rand::RngExt;
-    let mut rng = rand::rng();
-    let num: u32 = rng.random_range(0..=999999999);
-    let result = format!("{num:09}");
-    (
-        format!("{}-{}-{}", &result[0..3], &result[3..6], &result[6..9]),
-        num,
-    )
-}
+    /// Check an authorisation request that may be given by value, or by reference to a
+    /// pushed authorisation request (RFC9126) or a signed request object (RFC9101).
+    #[instrument(level = "debug", skip_all)]
+    pub fn check_oauth2_authorisation_params(
+        &self,
+        maybe_ident: Option<&Identity>,
+        auth_req_params: &AuthorisationRequestParams,
+        ct: Duration,
+    ) -> Result<AuthoriseResponse, Oauth2Error> {
+        match auth_req_params {
+            AuthorisationRequestParams::ByValue(auth_req) => {
+                self.check_oauth2_authorisation_inner(maybe_ident, auth_req, false, ct)
+            }
+            AuthorisationRequestParams::ByReference(AuthorisationRequestRef {
+                client_id,
+                request_uri: Some(request_uri),
+                request: None,
+            }) => {
+                let auth_req = self.get_oauth2_pushed_authorisation(client_id, request_uri, ct)?;
+                self.check_oauth2_authorisation_inner(maybe_ident, &auth_req, true, ct)
+            }
+            AuthorisationRequestParams::ByReference(AuthorisationRequestRef {
+                client_id,
+                request_uri: None,
+                request: Some(request),
+            }) => {
+                let o2rs = self.oauth2rs.inner.rs_set_get(client_id).ok_or_else(|| {
+                    warn!(?client_id, "Invalid OAuth2 client_id in request object");
+                    Oauth2Error::InvalidClientId
+                })?;
 
-/// Take the supplied user code and check it's a valid u32
-#[allow(dead_code)]
-fn parse_user_code(val: &str) -> Result<u32, Oauth2Error> {
-    let mut val = val.to_string();
-    val.retain(|c| c.is_ascii_digit());
-    val.parse().map_err(|err| {
-        debug!("Failed to parse value={} as u32: {:?}", val, err);
-        Oauth2Error::InvalidRequest
-    })
-}
+                // The request is repeated once the user has authenticated, so the request
+                // object is only consumed when it can lead to an authorisation.
+                let used_request_objects = maybe_ident
+                    .is_some()
+                    .then_some(self.oauth2_request_objects.as_ref());
 
-/// Check if a host is local (loopback or localhost)
-fn host_is_local(host: &Host<&str>) -> bool {
-    match host {
-        Host::Ipv4(ip) => ip.is_loopback(),
-        Host::Ipv6(ip) => ip.is_loopback(),
-        Host::Domain(domain) => *domain == "localhost",
+                let auth_req =
+                    o2rs.verify_request_object(client_id, request, used_request_objects, ct)?;
+                self.check_oauth2_authorisation_inner(maybe_ident, &auth_req, false, ct)
+            }
+            AuthorisationRequestParams::ByReference(_) => {
+                warn!("Exactly one of request_uri or request must be provided");
+                Err(Oauth2Error::InvalidRequest)
+            }
+        }
     }
-}
-
-/// Ensure that the redirect URI is a loopback/localhost address
-fn check_is_loopback(redirect_uri: &Url) -> bool {
-    redirect_uri.host().is_some_and(|host| {
-        // Check if the host is a loopback/localhost address.
-        host_is_local(&host)
-    })
-}
-
-#[cfg(test)]
-mod tests {
-    use super::{Oauth2TokenType, PkceS256Secret, TOKEN_EXCHANGE_SUBJECT_TOKEN_TYPE_ACCESS};
-    use crate::credential::Credential;
-    use crate::idm::accountpolicy::ResolvedAccountPolicy;
-    use crate::idm::oauth2::{
-        host_is_local, parse_basic_authz, AuthoriseResponse, Oauth2Error, OauthRSType,
-    };
-    use crate::idm::server::{IdmServer, IdmServerTransaction};
-    use crate::idm::serviceaccount::GenerateApiTokenEvent;
-    use crate::prelude::*;
-    use crate::value::{AuthType, OauthClaimMapJoin, SessionState};
-    use crate::valueset::{ValueSetOauthScopeMap, ValueSetSshKey};
-    use base64::{engine::general_purpose, Engine as _};
-    use compact_jwt::{
-        compact::JwkUse, crypto::JwsRs256Verifier, dangernoverify::JwsDangerReleaseWithoutVerify,
-        JwaAlg, Jwk, JwsCompact, JwsEs256Verifier, JwsVerifier, OidcSubject, OidcToken,
-        OidcUnverified,
-    };
-    use kanidm_lib_crypto::CryptoPolicy;
-    use kanidm_proto::constants::*;
-    use kanidm_proto::internal::{SshPublicKey, UserAuthToken};
-    use kanidm_proto::oauth2::*;
-    use std::collections::{BTreeMap, BTreeSet};
-    use std::convert::TryFrom;
-    use std::str::FromStr;
-    use std::time::Duration;
-    use time::OffsetDateTime;
-    use uri::{OAUTH2_TOKEN_INTROSPECT_ENDPOINT, OAUTH2_TOKEN_REVOKE_ENDPOINT};
 
-    const TEST_CURRENT_TIME: u64 = 6000;
-    const UAT_EXPIRE: u64 = 5;
-    const TOKEN_EXPIRE: u64 = 900;
+    /// Find a pushed authorisation request by its request_uri, checking that it was
+    /// pushed by this client and has not expired.
+    fn get_oauth2_pushed_authorisation(
+        &self,
+        client_id: &str,
+        request_uri: &str,
+        ct: Duration,
+    ) -> Result<AuthorisationRequest, Oauth2Error> {
+        let pushed = request_uri
+            .strip_prefix(OAUTH2_PUSHED_AUTHORISATION_REQUEST_URI_PREFIX)
+            .and_then(|request_id| Uuid::parse_str(request_id).ok())
+            .and_then(|request_id| self.oauth2_pushed_authorisations.get(&request_id))
+            .ok_or_else(|| {
+                security_info!(?request_uri, "Unknown pushed authorisation request_uri");
+                Oauth2Error::InvalidRequestUri
+            })?;
 
-    const UUID_TESTGROUP: Uuid = uuid!("a3028223-bf20-47d5-8b65-967b5d2bb3eb");
+        if pushed.expiry <= ct {
+            security_info!(?request_uri, "Pushed authorisation request has expired");
+            return Err(Oauth2Error::InvalidRequestUri);
+        }
 
-    macro_rules! good_authorisation_request {
-        (
-            $idms_prox_read:expr,
-            $ident:expr,
-            $ct:expr,
-            $pkce_request:expr,
-            $scope:expr
-        ) => {{
-            #[allow(clippy::unnecessary_to_owned)]
-            let scope: BTreeSet<String> = $scope.split(" ").map(|s| s.to_string()).collect();
+        let o2rs = self.oauth2rs.inner.rs_set_get(client_id).ok_or_else(|| {
+            warn!(
+                ?client_id,
+                "Invalid OAuth2 client_id for pushed authorisation"
+            );
+            Oauth2Error::InvalidClientId
+        })?;
 
-            let auth_req = AuthorisationRequest {
-                response_type: ResponseType::Code,
-                response_mode: None,
-                client_id: "test_resource_server".to_string(),
-                state: Some("123".to_string()),
-                pkce_request: Some($pkce_request),
-                redirect_uri: Url::parse("https://demo.example.com/oauth2/result").unwrap(),
-                scope,
-                nonce: Some("abcdef".to_string()),
-                oidc_ext: Default::default(),
-                max_age: None,
-                unknown_keys: Default::default(),
-            };
+        if o2rs.uuid != pushed.rs_uuid {
+            security_info!(
+                ?client_id,
+                "Pushed authorisation request was pushed by a different client"
+            );
+            return Err(Oauth2Error::InvalidRequestUri);
+        }
 
-            $idms_prox_read
-                .check_oauth2_authorisation(Some($ident), &auth_req, $ct)
-                .expect("OAuth2 authorisation failed")
-        }};
+        Ok(pushed.auth_req.clone())
     }
 
-    // setup an OAuth2 instance.
-    async fn setup_oauth2_resource_server_basic(
-        idms: &IdmServer,
+    fn check_oauth2_authorisation_inner(
+        &self,
+        maybe_ident: Option<&Identity>,
+        auth_req: &AuthorisationRequest,
+        pushed: bool,
         ct: Duration,
-        enable_pkce: bool,
-        enable_legacy_crypto: bool,
-        prefer_short_username: bool,
-    ) -> (String, UserAuthToken, Identity, Uuid) {
-        let mut idms_prox_write = idms.proxy_write(ct).await.unwrap();
+    ) -> Result<AuthoriseResponse, Oauth2Error> {
+        // due to identity processing we already know that:
+        // * the session must be authenticated, and valid
+        // * is within it's valid time window.
+        trace!(?auth_req);
 
-        let rs_uuid = Uuid::new_v4();
+        /*
+         * 4.1.2.1.  Error Response
+         *
+         * If the request fails due to a missing, invalid, or mismatching
+         * redirection URI, or if the client identifier is missing or invalid,
+         * the authorization server SHOULD inform the resource owner of the
+         * error and MUST NOT automatically redirect the user-agent to the
+         * invalid redirection URI.
+         */
 
-        let entry_group: Entry<EntryInit, EntryNew> = entry_init!(
-            (Attribute::Class, EntryClass::Group.to_value()),
-            (Attribute::Name, Value::new_iname("testgroup")),
-            (Attribute::Description, Value::new_utf8s("testgroup")),
-            (Attribute::Uuid, Value::Uuid(UUID_TESTGROUP)),
-            (Attribute::Member, Value::Refer(UUID_TESTPERSON_1),)
-        );
+        //
+        let o2rs = self
+            .oauth2rs
+            .inner
+            .rs_set_get(&auth_req.client_id)
+            .ok_or_else(|| {
+                warn!(
+                    "Invalid OAuth2 client_id ({}) Have you configured the OAuth2 resource server?",
+                    &auth_req.client_id
+                );
+                Oauth2Error::InvalidClientId
+            })?;
 
-        let entry_rs: Entry<EntryInit, EntryNew> = entry_init!(
-            (Attribute::Class, EntryClass::Object.to_value()),
-            (Attribute::Class, EntryClass::Account.to_value()),
-            (
+        if o2rs.require_par && !pushed {
+            security_info!(?o2rs.name, "OAuth2 client requires pushed authorisation requests");
+            return Err(Oauth2Error::InvalidRequest);
+        }
+
+        let (response_mode, code_challenge) = validate_authorisation_request(o2rs, auth_req)?;
+
+        // https://datatracker.ietf.org/doc/html/rfc9396#section-5 - every requested
+        // authorisation detail must be of a type this client has been allowed.
+        let authorization_details = auth_req.authorization_details.clone().unwrap_or_default();
+
+        if let Some(detail) = authorization_details
+            .iter()
+            .find(|detail| !o2rs.authorisation_details_types.contains(&detail.type_))
+        {
+            warn!(
+                ?o2rs.name,
+                detail_type = %detail.type_,
+                "OAuth2 client requested an authorisation detail type it is not allowed"
+            );
+            return Err(Oauth2Error::InvalidAuthorizationDetails);
+        }
+
+        // =============================================================================
+        // By this point, we have validated the majority of the security related
+        // parameters of the request. We can now inspect the identity and decide
+        // if we should ask the user to re-authenticate and proceed.
+
+        // TODO: https://openid.net/specs/openid-connect-basic-1_0.html#RequestParameters
+        // Are we going to provide the functions for these? Most of these can be "later".
+        // IF CHANGED: Update OidcDiscoveryResponse!!!
+
+        // TODO: https://openid.net/specs/openid-connect-basic-1_0.html#RequestParameters
+        // prompt - if set to login, we need to force a re-auth. But we don't want to
+        // if the user "only just" logged in, that's annoying. So we need a time window for
+        // this, to detect when we should force it to the consent req.
+
+        // TODO: display = popup vs touch vs wap etc.
+
+        // TODO: max_age, pass through with consent req. If 0, force login.
+        // Otherwise force a login re the uat timeout.
+
+        // TODO: ui_locales / claims_locales for the ui. Only if we don't have a Uat that
+        // would provide this.
+
+        // TODO: id_token_hint - a past token which can be used as a hint.
+
+        let Some(ident) = maybe_ident else {
+            debug!("No identity available, assume authentication required");
+            return Ok(AuthoriseResponse::AuthenticationRequired {
+                client_name: o2rs.displayname.clone(),
+                login_hint: auth_req.oidc_ext.login_hint.clone(),
+            });
+        };
+
+        let Some(account_uuid) = ident.get_uuid() else {
+            error!("Consent request ident does not have a valid UUID, unable to proceed");
+            return Err(Oauth2Error::InvalidRequest);
+        };
+
+        // Deny anonymous access to oauth2
+        if account_uuid == UUID_ANONYMOUS {
+            admin_error!(
+                "Invalid OAuth2 request - refusing to allow user that authenticated with anonymous"
+            );
+            return Err(Oauth2Error::AccessDenied);
+        }
+
+        // scopes - you need to have every requested scope or this auth_req is denied.
+        let (req_scopes, granted_scopes) =
+            process_requested_scopes_for_identity(o2rs, ident, Some(&auth_req.scope))?;
+
+        // MICRO OPTIMISATION = flag if we have openid first, so we can into_iter here rather than
+        // cloning.
+        let openid_requested = req_scopes.contains(OAUTH2_SCOPE_OPENID);
+
+        let consent_previously_granted =
+            if let Some(consent_scopes) = ident.get_oauth2_consent_scopes(o2rs.uuid) {
+                trace!(?granted_scopes);
+                trace!(?consent_scopes);
+                granted_scopes.eq(consent_scopes)
+            } else {
+                false
+            };
+
+        let session_id = ident.get_session_id();
+
+        // Rich authorisation details describe a specific grant, so they are always
+        // presented to the user even if the scopes were consented to in the past.
+        if (consent_previously_granted && authorization_details.is_empty())
+            || !o2rs.enable_consent_prompt()
+        {
+            if event_enabled!(tracing::Level::DEBUG) {
+                let pretty_scopes: Vec<String> =
+                    granted_scopes.iter().map(|s| s.to_owned()).collect();
+                debug!(
+                    pretty_scopes = pretty_scopes.join(","),
+                    prompt_enabled = o2rs.enable_consent_prompt(),
+                    previously_granted = consent_previously_granted,
+                    "Consent flow passed"
+                );
+            }
+
+            // Xchg token expires in
+            let expiry = ct.as_secs() + 60;
+
+            // Setup for the permit success
+            let xchg_code = TokenExchangeCode {
+                account_uuid,
+                session_id,
+                expiry,
+                code_challenge,
+                redirect_uri: auth_req.redirect_uri.clone(),
+                scopes: granted_scopes.into_iter().collect(),
+                nonce: auth_req.nonce.clone(),
+                authorization_details,
+            };
+
+            // Encrypt the exchange token with the key of the client
+            let code_data_jwe = Jwe::into_json(&xchg_code).map_err(|err| {
+                error!(?err, "Unable to encode xchg_code data");
+                Oauth2Error::ServerError(OperationError::SerdeJsonError)
+            })?;
+
+            let code = o2rs
+                .key_object
+                .jwe_a128gcm_encrypt(&code_data_jwe, ct)
+                .map(|jwe| jwe.to_string())
+                .map_err(|err| {
+                    error!(?err, "Unable to encrypt xchg_code data");
+                    Oauth2Error::ServerError(OperationError::CryptographyError)
+                })?;
+
+            Ok(AuthoriseResponse::Permitted(AuthorisePermitSuccess {
+                redirect_uri: auth_req.redirect_uri.clone(),
+                state: auth_req.state.clone(),
+                code,
+                response_mode,
+            }))
+        } else {
+            //  Check that the scopes are the same as a previous consent (if any)
+            // If oidc, what PII is visible?
+            // TODO: Scopes map to claims:
+            //
+            // * profile - (name, family\_name, given\_name, middle\_name, nickname, preferred\_username, profile, picture, website, gender, birthdate, zoneinfo, locale, and updated\_at)
+            // * email - (email, email\_verified)
+            // * address - (address)
+            // * phone - (phone\_number, phone\_number\_verified)
+            //
+            // https://openid.net/specs/openid-connect-basic-1_0.html#StandardClaims
+
+            // IMPORTANT DISTINCTION - Here req scopes must contain openid, but the PII can be supplemented
+            // be the servers scopes!
+            let mut pii_scopes = BTreeSet::default();
+            if openid_requested {
+                // Only mutate if things were requested under openid
+                if granted_scopes.contains(OAUTH2_SCOPE_EMAIL) {
+                    pii_scopes.insert(OAUTH2_SCOPE_EMAIL.to_string());
+                    pii_scopes.insert("email_verified".to_string());
+                }
+            };
+
+            if granted_scopes.contains(OAUTH2_SCOPE_SSH_PUBLICKEYS) {
+                pii_scopes.insert(OAUTH2_SCOPE_SSH_PUBLICKEYS.to_string());
+            }
+
+            // Consent token expires in
+            let expiry = ct.as_secs() + 300;
+
+            // Subsequent we then return an encrypted session handle which allows
+            // the user to indicate their consent to this authorisation.
+            //
+            // This session handle is what we use in "permit" to generate the redirect.
+
+            let consent_req = ConsentToken {
+                client_id: auth_req.client_id.clone(),
+                ident_id: ident.get_event_origin_id(),
+                expiry,
+                session_id,
+                state: auth_req.state.clone(),
+                code_challenge,
+                redirect_uri: auth_req.redirect_uri.clone(),
+                scopes: granted_scopes.iter().cloned().collect(),
+                nonce: auth_req.nonce.clone(),
+                response_mode,
+                authorization_details: authorization_details.clone(),
+            };
+
+            let consent_jwe = Jwe::into_json(&consent_req).map_err(|err| {
+                error!(?err, "Unable to encode consent data");
+                Oauth2Error::ServerError(OperationError::SerdeJsonError)
+            })?;
+
+            let consent_token = self
+                .oauth2rs
+                .inner
+                .consent_key
+                .encipher::<JweA128GCMEncipher>(&consent_jwe)
+                .map(|jwe_compact| jwe_compact.to_string())
+                .map_err(|err| {
+                    error!(?err, "Unable to encrypt jwe");
+                    Oauth2Error::ServerError(OperationError::CryptographyError)
+                })?;
+
+            Ok(AuthoriseResponse::ConsentRequested {
+                client_name: o2rs.displayname.clone(),
+                scopes: granted_scopes.into_iter().collect(),
+                pii_scopes,
+                consent_token,
+                authorization_details,
+            })
+        }
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    pub fn check_oauth2_authorise_reject(
+        &self,
+        ident: &Identity,
+        consent_token: &str,
+        ct: Duration,
+    ) -> Result<AuthoriseReject, OperationError> {
+        let jwe_compact = JweCompact::from_str(consent_token).map_err(|_| {
+            error!("Failed to deserialise a valid JWE");
+            OperationError::CryptographyError
+        })?;
+
+        // Decode the consent req with our system fernet key. Use a ttl of 5 minutes.
+        let consent_req: ConsentToken = self
+            .oauth2rs
+            .inner
+            .consent_key
+            .decipher(&jwe_compact)
+            .map_err(|_| {
+                admin_error!("Failed to decrypt consent request");
+                OperationError::CryptographyError
+            })
+            .and_then(|jwe| {
+                jwe.from_json().map_err(|err| {
+                    error!(?err, "Failed to deserialise consent request");
+                    OperationError::SerdeJsonError
+                })
+            })?;
+
+        // Validate that the ident_id matches our current ident.
+        if consent_req.ident_id != ident.get_event_origin_id() {
+            security_info!("consent request ident id does not match the identity of our UAT.");
+            return Err(OperationError::InvalidSessionState);
+        }
+
+        // Validate that the session id matches our session
+        if consent_req.session_id != ident.get_session_id() {
+            security_info!("consent request sessien id does not match the session id of our UAT.");
+            return Err(OperationError::InvalidSessionState);
+        }
+
+        if consent_req.expiry <= ct.as_secs() {
+            // Token is expired
+            error!("Failed to decrypt consent request");
+            return Err(OperationError::CryptographyError);
+        }
+
+        // Get the resource server config based on this client_id.
+        let _o2rs = self
+            .oauth2rs
+            .inner
+            .rs_set_get(&consent_req.client_id)
+            .ok_or_else(|| {
+                admin_error!("Invalid consent request OAuth2 client_id");
+                OperationError::InvalidRequestState
+            })?;
+
+        // All good, now confirm the rejection to the client application.
+        Ok(AuthoriseReject {
+            redirect_uri: consent_req.redirect_uri,
+            response_mode: consent_req.response_mode,
+        })
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    pub fn check_oauth2_token_introspect(
+        &mut self,
+        client_auth_info: &ClientAuthInfo,
+        intr_req: &AccessTokenIntrospectRequest,
+        ct: Duration,
+    ) -> Result<AccessTokenIntrospectResponse, Oauth2Error> {
+        let client_auth = get_client_auth(client_auth_info, &intr_req.client_post_auth)?;
+
+        // Get the o2rs for the handle.
+        let o2rs = self
+            .oauth2rs
+            .inner
+            .rs_set_get(&client_auth.client_id)
+            .ok_or_else(|| {
+                warn!("Invalid OAuth2 client_id");
+                Oauth2Error::AuthenticationRequired
+            })?;
+
+        // We don't need to authenticate, since possession of the access token is already enough
+        // to prove identity, and we enforce it is cryptographically valid so it can't be bruteforced
+        // by a scanning attack.
+        let prefer_short_username = o2rs.prefer_short_username;
+
+        if let Ok(jwsc) = JwsCompact::from_str(&intr_req.token) {
+            let access_token = o2rs
+                .key_object
+                .jws_verify(&jwsc)
+                .map_err(|err| {
+                    error!(?err, "Unable to verify access token");
+                    Oauth2Error::InvalidRequest
+                })
+                .and_then(|jws| {
+                    jws.from_json().map_err(|err| {
+                        error!(?err, "Unable to deserialise access token");
+                        Oauth2Error::InvalidRequest
+                    })
+                })?;
+
+            let OAuth2RFC9068Token::<_> {
+                iss: _,
+                sub,
+                aud: _,
+                exp,
+                nbf,
+                iat,
+                jti,
+                client_id: _,
+                extensions:
+                    OAuth2RFC9068TokenExtensions {
+                        auth_time: _,
+                        acr: _,
+                        amr: _,
+                        scope: scopes,
+                        nonce: _,
+                        session_id,
+                        parent_session_id,
+                        cnf,
+                        authorization_details,
+                    },
+            } = access_token;
+
+            // Has this token expired?
+            if exp <= ct.as_secs() as i64 {
+                security_info!(?sub, "access token has expired, returning inactive");
+                return Ok(AccessTokenIntrospectResponse::inactive(jti));
+            }
+
+            let dpop_bound = cnf.as_ref().is_some_and(|cnf| cnf.jkt.is_some());
+
+            if o2rs.require_dpop && !dpop_bound {
+                security_info!(?sub, "access token is not DPoP bound, returning inactive");
+                return Ok(AccessTokenIntrospectResponse::inactive(jti));
+            }
+
+            let Some(account_uuid) =
+                oauth2_subject_account_uuid(&mut self.qs_read, o2rs, sub, session_id)
+                    .map_err(Oauth2Error::ServerError)?
+            else {
+                security_info!(
+                    ?sub,
+                    "access token subject has no matching session, returning inactive"
+                );
+                return Ok(AccessTokenIntrospectResponse::inactive(jti));
+            };
+
+            // Is the user expired, or the OAuth2 session invalid?
+            let valid = self
+                .check_oauth2_account_uuid_valid(
+                    account_uuid,
+                    session_id,
+                    parent_session_id,
+                    iat,
+                    ct,
+                )
+                .map_err(|_| admin_error!("Account is not valid"));
+
+            let Ok(Some(entry)) = valid else {
+                security_info!(
+                    ?sub,
+                    "access token account is not valid, returning inactive"
+                );
+                return Ok(AccessTokenIntrospectResponse::inactive(jti));
+            };
+
+            let account = match Account::try_from_entry_ro(&entry, &mut self.qs_read) {
+                Ok(account) => account,
+                Err(err) => return Err(Oauth2Error::ServerError(err)),
+            };
+
+            // ==== good to generate response ====
+
+            let scope = scopes.clone();
+
+            let preferred_username = if prefer_short_username {
+                Some(account.name().into())
+            } else {
+                Some(account.spn().into())
+            };
+
+            let token_type = if dpop_bound {
+                Some(AccessTokenType::DPoP)
+            } else {
+                Some(AccessTokenType::Bearer)
+            };
+            Ok(AccessTokenIntrospectResponse {
+                active: true,
+                scope,
+                client_id: Some(client_auth.client_id.clone()),
+                username: preferred_username,
+                token_type,
+                iat: Some(iat),
+                exp: Some(exp),
+                nbf: Some(nbf),
+                sub: Some(sub.to_string()),
+                aud: Some(client_auth.client_id),
+                iss: None,
+                jti,
+                cnf,
+                authorization_details,
+            })
+        } else {
+            let jwe_compact = JweCompact::from_str(&intr_req.token).map_err(|_| {
+                error!("Failed to deserialise a valid JWE");
+                Oauth2Error::InvalidRequest
+            })?;
+
+            let token: Oauth2TokenType = o2rs
+                .key_object
+                .jwe_decrypt(&jwe_compact)
+                .map_err(|_| {
+                    admin_error!("Failed to decrypt token introspection request");
+                    Oauth2Error::InvalidRequest
+                })
+                .and_then(|jwe| {
+                    jwe.from_json().map_err(|err| {
+                        error!(?err, "Failed to deserialise token");
+                        Oauth2Error::InvalidRequest
+                    })
+                })?;
+
+            match token {
+                Oauth2TokenType::ClientAccess {
+                    scopes,
+                    session_id,
+                    uuid,
+                    exp,
+                    iat,
+                    nbf,
+                    dpop_jkt,
+                    x5t_s256,
+                } => {
+                    // Has this token expired?
+                    if exp <= ct.as_secs() as i64 {
+                        security_info!(?uuid, "access token has expired, returning inactive");
+                        return Ok(AccessTokenIntrospectResponse::inactive(session_id));
+                    }
+
+                    if o2rs.require_dpop && dpop_jkt.is_none() {
+                        security_info!(?uuid, "access token is not DPoP bound, returning inactive");
+                        return Ok(AccessTokenIntrospectResponse::inactive(session_id));
+                    }
+
+                    // We can't do the same validity check for the client as we do with an account
+                    let valid = self
+                        .check_oauth2_account_uuid_valid(uuid, session_id, None, iat, ct)
+                        .map_err(|_| admin_error!("Account is not valid"));
+
+                    let Ok(Some(entry)) = valid else {
+                        security_info!(
+                            ?uuid,
+                            "access token account is not valid, returning inactive"
+                        );
+                        return Ok(AccessTokenIntrospectResponse::inactive(session_id));
+                    };
+
+                    let scope = scopes.clone();
+
+                    let binding = TokenBinding { dpop_jkt, x5t_s256 };
+                    let token_type = Some(binding.token_type());
+
+                    let username = if prefer_short_username {
+                        entry
+                            .get_ava_single_iname(Attribute::Name)
+                            .map(|s| s.to_string())
+                    } else {
+                        entry.get_ava_single_proto_string(Attribute::Spn)
+                    };
+
+                    Ok(AccessTokenIntrospectResponse {
+                        active: true,
+                        scope,
+                        client_id: Some(client_auth.client_id.clone()),
+                        username,
+                        token_type,
+                        iat: Some(iat),
+                        exp: Some(exp),
+                        nbf: Some(nbf),
+                        sub: Some(uuid.to_string()),
+                        aud: Some(client_auth.client_id),
+                        iss: None,
+                        jti: session_id,
+                        cnf: binding.cnf(),
+                        authorization_details: None,
+                    })
+                }
+                Oauth2TokenType::Refresh { session_id, .. } => {
+                    Ok(AccessTokenIntrospectResponse::inactive(session_id))
+                }
+            }
+        }
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    pub fn oauth2_openid_userinfo(
+        &mut self,
+        client_id: &str,
+        token: &JwsCompact,
+        dpop_proof: Option<&DpopProof>,
+        client_cert: Option<&ClientCertInfo>,
+        ct: Duration,
+    ) -> Result<OidcToken, Oauth2Error> {
+        // DANGER: Why do we have to do this? During the use of qs for internal search
+        // and other operations we need qs to be mut. But when we borrow oauth2rs here we
+        // cause multiple borrows to occur on struct members that freaks rust out. This *IS*
+        // safe however because no element of the search or write process calls the oauth2rs
+        // excepting for this idm layer within a single thread, meaning that stripping the
+        // lifetime here is safe since we are the sole accessor.
+        let o2rs: &Oauth2RS = unsafe {
+            let s = self.oauth2rs.inner.rs_set_get(client_id).ok_or_else(|| {
+                warn!("Invalid OAuth2 client_id (have you configured the OAuth2 resource server?)");
+                Oauth2Error::InvalidClientId
+            })?;
+            &*(s as *const _)
+        };
+
+        let access_token = o2rs
+            .key_object
+            .jws_verify(token)
+            .map_err(|err| {
+                error!(?err, "Unable to verify access token");
+                Oauth2Error::InvalidRequest
+            })
+            .and_then(|jws| {
+                jws.from_json().map_err(|err| {
+                    error!(?err, "Unable to deserialise access token");
+                    Oauth2Error::InvalidRequest
+                })
+            })?;
+
+        let OAuth2RFC9068Token::<_> {
+            iss: _,
+            sub,
+            aud: _,
+            exp,
+            nbf,
+            iat,
+            jti: _,
+            client_id: _,
+            extensions:
+                OAuth2RFC9068TokenExtensions {
+                    auth_time: _,
+                    acr: _,
+                    amr: _,
+                    scope: scopes,
+                    nonce,
+                    session_id,
+                    parent_session_id,
+                    cnf,
+                    authorization_details: _,
+                },
+        } = access_token;
+        // Has this token expired?
+        if exp <= ct.as_secs() as i64 {
+            security_info!(?sub, "access token has expired, returning inactive");
+            return Err(Oauth2Error::InvalidToken);
+        }
+
+        let ConfirmationClaim { jkt, x5t_s256 } = cnf.unwrap_or_default();
+
+        // A DPoP bound token must be accompanied by a proof from the key it is bound to.
+        match (jkt, dpop_proof) {
+            (Some(jkt), Some(dpop_proof)) => {
+                let verified =
+                    verify_dpop_proof(dpop_proof, &o2rs.userinfo_endpoint, Some(token), ct)?;
+                if verified.jkt != jkt {
+                    security_info!(
+                        ?sub,
+                        "DPoP proof was not made with the key the access token is bound to"
+                    );
+                    return Err(Oauth2Error::InvalidToken);
+                }
+                consume_dpop_proof(&self.oauth2_dpop_proofs, &verified, ct)?;
+            }
+            (Some(_), None) => {
+                security_info!(
+                    ?sub,
+                    "access token is DPoP bound, but no DPoP proof was provided"
+                );
+                return Err(Oauth2Error::InvalidToken);
+            }
+            (None, _) if o2rs.require_dpop => {
+                security_info!(?sub, "access token is not DPoP bound");
+                return Err(Oauth2Error::InvalidToken);
+            }
+            (None, _) => {}
+        }
+
+        // A certificate bound token must be presented over a connection with that certificate.
+        if let Some(x5t_s256) = x5t_s256 {
+            let presented_x5t_s256 = client_cert.map(certificate_thumbprint).transpose()?;
+            if presented_x5t_s256.as_ref() != Some(&x5t_s256) {
+                security_info!(
+                    ?sub,
+                    "access token is certificate bound, but was not presented with the bound certificate"
+                );
+                return Err(Oauth2Error::InvalidToken);
+            }
+        }
+
+        let Some(account_uuid) =
+            oauth2_subject_account_uuid(&mut self.qs_read, o2rs, sub, session_id)
+                .map_err(Oauth2Error::ServerError)?
+        else {
+            security_info!(?sub, "access token subject has no matching session");
+            return Err(Oauth2Error::InvalidToken);
+        };
+
+        // Is the user expired, or the OAuth2 session invalid?
+        let valid = self
+            .check_oauth2_account_uuid_valid(account_uuid, session_id, parent_session_id, iat, ct)
+            .map_err(|_| admin_error!("Account is not valid"));
+
+        let Ok(Some(entry)) = valid else {
+            security_info!(
+                ?sub,
+                "access token has account not valid, returning inactive"
+            );
+            return Err(Oauth2Error::InvalidToken);
+        };
+
+        let account = match Account::try_from_entry_ro(&entry, &mut self.qs_read) {
+            Ok(account) => account,
+            Err(err) => return Err(Oauth2Error::ServerError(err)),
+        };
+
+        let amr = None;
+
+        let iss = o2rs.iss.clone();
+
+        let s_claims = s_claims_for_account(o2rs, &account, &scopes);
+        let extra_claims = extra_claims_for_account(&account, &o2rs.claim_map, &scopes);
+
+        // ==== good to generate response ====
+
+        Ok(OidcToken {
+            iss,
+            sub: OidcSubject::U(sub),
+            aud: client_id.to_string(),
+            iat,
+            nbf: Some(nbf),
+            exp,
+            auth_time: None,
+            nonce,
+            at_hash: None,
+            acr: None,
+            amr,
+            azp: Some(client_id.to_string()),
+            jti: Some(session_id.to_string()),
+            s_claims,
+            claims: extra_claims,
+        })
+    }
+
+    /// Encrypt userinfo to the client if it requires encrypted userinfo responses,
+    /// otherwise the claims are returned as is.
+    #[instrument(level = "debug", skip_all)]
+    pub fn oauth2_openid_userinfo_response(
+        &self,
+        client_id: &str,
+        userinfo: OidcToken,
+    ) -> Result<UserinfoResponse, Oauth2Error> {
+        let o2rs = self.oauth2rs.inner.rs_set_get(client_id).ok_or_else(|| {
+            warn!("Invalid OAuth2 client_id (have you configured the OAuth2 resource server?)");
+            Oauth2Error::InvalidClientId
+        })?;
+
+        let userinfo_jwe = Jwe::into_json(&userinfo).map_err(|err| {
+            error!(?err, "Unable to encode userinfo");
+            Oauth2Error::ServerError(OperationError::SerdeJsonError)
+        })?;
+
+        Ok(match o2rs.encrypt_response(&userinfo_jwe)? {
+            Some(userinfo_encrypted) => UserinfoResponse::Encrypted(Box::new(userinfo_encrypted)),
+            None => UserinfoResponse::Claims(Box::new(userinfo)),
+        })
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    pub fn oauth2_rfc8414_metadata(
+        &self,
+        client_id: &str,
+    ) -> Result<Oauth2Rfc8414MetadataResponse, OperationError> {
+        let o2rs = self.oauth2rs.inner.rs_set_get(client_id).ok_or_else(|| {
+            warn!("Invalid OAuth2 client_id (have you configured the OAuth2 resource server?)");
+            OperationError::NoMatchingEntries
+        })?;
+
+        let issuer = o2rs.iss.clone();
+        let authorization_endpoint = o2rs.authorization_endpoint.clone();
+        let token_endpoint = o2rs.token_endpoint.clone();
+        let revocation_endpoint = Some(o2rs.revocation_endpoint.clone());
+        let introspection_endpoint = Some(o2rs.introspection_endpoint.clone());
+        let jwks_uri = Some(o2rs.jwks_uri.clone());
+        let scopes_supported = Some(o2rs.scopes_supported.iter().cloned().collect());
+        let response_types_supported = vec![ResponseType::Code];
+        let response_modes_supported = vec![ResponseMode::Query, ResponseMode::Fragment];
+        let mut grant_types_supported =
+            vec![GrantType::AuthorisationCode, GrantType::TokenExchange];
+        if o2rs.enable_ciba {
+            grant_types_supported.push(GrantType::Ciba);
+        }
+
+        let token_endpoint_auth_methods_supported = vec![
+            TokenEndpointAuthMethod::ClientSecretBasic,
+            TokenEndpointAuthMethod::ClientSecretPost,
+            TokenEndpointAuthMethod::ClientSecretJwt,
+            TokenEndpointAuthMethod::PrivateKeyJwt,
+            TokenEndpointAuthMethod::TlsClientAuth,
+            TokenEndpointAuthMethod::SelfSignedTlsClientAuth,
+        ];
+        let token_endpoint_auth_signing_alg_values_supported = Some(vec![
+            IdTokenSignAlg::ES256,
+            IdTokenSignAlg::RS256,
+            IdTokenSignAlg::HS256,
+        ]);
+
+        let revocation_endpoint_auth_methods_supported = vec![
+            TokenEndpointAuthMethod::ClientSecretBasic,
+            TokenEndpointAuthMethod::ClientSecretPost,
+        ];
+
+        let introspection_endpoint_auth_methods_supported = vec![
+            TokenEndpointAuthMethod::ClientSecretBasic,
+            TokenEndpointAuthMethod::ClientSecretPost,
+        ];
+
+        let service_documentation = Some(URL_SERVICE_DOCUMENTATION.clone());
+
+        let code_challenge_methods_supported = if o2rs.require_pkce() {
+            vec![PkceAlg::S256]
+        } else {
+            Vec::with_capacity(0)
+        };
+
+        let request_object_signing_alg_values_supported = Some(vec![
+            IdTokenSignAlg::ES256,
+            IdTokenSignAlg::RS256,
+            IdTokenSignAlg::HS256,
+        ]);
+
+        Ok(Oauth2Rfc8414MetadataResponse {
+            issuer,
+            authorization_endpoint,
+            token_endpoint,
+            jwks_uri,
+            registration_endpoint: Some(o2rs.registration_endpoint.clone()),
+            scopes_supported,
+            response_types_supported,
+            response_modes_supported,
+            grant_types_supported,
+            token_endpoint_auth_methods_supported,
+            token_endpoint_auth_signing_alg_values_supported,
+            service_documentation,
+            ui_locales_supported: None,
+            op_policy_uri: None,
+            op_tos_uri: None,
+            revocation_endpoint,
+            revocation_endpoint_auth_methods_supported,
+            introspection_endpoint,
+            introspection_endpoint_auth_methods_supported,
+            introspection_endpoint_auth_signing_alg_values_supported: None,
+            code_challenge_methods_supported,
+            request_parameter_supported: true,
+            request_uri_parameter_supported: true,
+            request_object_signing_alg_values_supported,
+            pushed_authorization_request_endpoint: Some(o2rs.par_endpoint.clone()),
+            require_pushed_authorization_requests: o2rs.require_par,
+            dpop_signing_alg_values_supported: Some(vec![
+                IdTokenSignAlg::ES256,
+                IdTokenSignAlg::RS256,
+            ]),
+            tls_client_certificate_bound_access_tokens: true,
+            authorization_details_types_supported: o2rs.authorization_details_types_supported(),
+            backchannel_authentication_endpoint: o2rs
+                .enable_ciba
+                .then(|| o2rs.backchannel_authentication_endpoint.clone()),
+            backchannel_token_delivery_modes_supported: o2rs
+                .enable_ciba
+                .then(|| vec![BackchannelTokenDeliveryMode::Poll]),
+            backchannel_user_code_parameter_supported: false,
+        })
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    pub fn oauth2_openid_discovery(
+        &self,
+        client_id: &str,
+    ) -> Result<OidcDiscoveryResponse, OperationError> {
+        let o2rs = self.oauth2rs.inner.rs_set_get(client_id).ok_or_else(|| {
+            warn!("Invalid OAuth2 client_id (have you configured the OAuth2 resource server?)");
+            OperationError::NoMatchingEntries
+        })?;
+
+        let issuer = o2rs.iss.clone();
+
+        let authorization_endpoint = o2rs.authorization_endpoint.clone();
+        let token_endpoint = o2rs.token_endpoint.clone();
+        let userinfo_endpoint = Some(o2rs.userinfo_endpoint.clone());
+        let jwks_uri = o2rs.jwks_uri.clone();
+        let scopes_supported = Some(o2rs.scopes_supported.iter().cloned().collect());
+        let response_types_supported = vec![ResponseType::Code];
+        let response_modes_supported = vec![ResponseMode::Query, ResponseMode::Fragment];
+
+        // TODO: add device code if the rs supports it per <https://www.rfc-editor.org/rfc/rfc8628#section-4>
+        // `urn:ietf:params:oauth:grant-type:device_code`
+        let mut grant_types_supported =
+            vec![GrantType::AuthorisationCode, GrantType::TokenExchange];
+        if o2rs.enable_ciba {
+            grant_types_supported.push(GrantType::Ciba);
+        }
+
+        let subject_types_supported = if o2rs.pairwise_subject_key.is_some() {
+            vec![SubjectType::Pairwise]
+        } else {
+            vec![SubjectType::Public]
+        };
+
+        let id_token_signing_alg_values_supported = match &o2rs.sign_alg {
+            SignatureAlgo::Es256 => vec![IdTokenSignAlg::ES256],
+            SignatureAlgo::Rs256 => vec![IdTokenSignAlg::RS256],
+        };
+
+        let userinfo_signing_alg_values_supported = None;
+
+        // ID tokens and userinfo can be encrypted to any client that registers a public key.
+        let encryption_alg_values_supported = Some(vec![
+            IdTokenEncryptionAlg::RsaOaep,
+            IdTokenEncryptionAlg::EcdhEsA256Kw,
+        ]);
+        let encryption_enc_values_supported = Some(vec![IdTokenEncryptionEnc::A256GCM]);
+
+        let token_endpoint_auth_methods_supported = vec![
+            TokenEndpointAuthMethod::ClientSecretBasic,
+            TokenEndpointAuthMethod::ClientSecretPost,
+            TokenEndpointAuthMethod::ClientSecretJwt,
+            TokenEndpointAuthMethod::PrivateKeyJwt,
+            TokenEndpointAuthMethod::TlsClientAuth,
+            TokenEndpointAuthMethod::SelfSignedTlsClientAuth,
+        ];
+        let token_endpoint_auth_signing_alg_values_supported = Some(vec![
+            "ES256".to_string(),
+            "RS256".to_string(),
+            "HS256".to_string(),
+        ]);
+        let request_object_signing_alg_values_supported = Some(vec![
+            "ES256".to_string(),
+            "RS256".to_string(),
+            "HS256".to_string(),
+        ]);
+        let display_values_supported = Some(vec![DisplayValue::Page]);
+        let claim_types_supported = vec![ClaimType::Normal];
+        // What claims can we offer?
+        let claims_supported = None;
+        let service_documentation = Some(URL_SERVICE_DOCUMENTATION.clone());
+
+        let code_challenge_methods_supported = if o2rs.require_pkce() {
+            vec![PkceAlg::S256]
+        } else {
+            Vec::with_capacity(0)
+        };
+
+        // The following are extensions allowed by the oidc specification.
+
+        let revocation_endpoint = Some(o2rs.revocation_endpoint.clone());
+        let revocation_endpoint_auth_methods_supported = vec![
+            TokenEndpointAuthMethod::ClientSecretBasic,
+            TokenEndpointAuthMethod::ClientSecretPost,
+        ];
+
+        let introspection_endpoint = Some(o2rs.introspection_endpoint.clone());
+        let introspection_endpoint_auth_methods_supported = vec![
+            TokenEndpointAuthMethod::ClientSecretBasic,
+            TokenEndpointAuthMethod::ClientSecretPost,
+        ];
+
+        Ok(OidcDiscoveryResponse {
+            issuer,
+            authorization_endpoint,
+            token_endpoint,
+            userinfo_endpoint,
+            jwks_uri,
+            registration_endpoint: Some(o2rs.registration_endpoint.clone()),
+            scopes_supported,
+            response_types_supported,
+            response_modes_supported,
+            grant_types_supported,
+            acr_values_supported: None,
+            subject_types_supported,
+            id_token_signing_alg_values_supported,
+            id_token_encryption_alg_values_supported: encryption_alg_values_supported.clone(),
+            id_token_encryption_enc_values_supported: encryption_enc_values_supported.clone(),
+            userinfo_signing_alg_values_supported,
+            userinfo_encryption_alg_values_supported: encryption_alg_values_supported,
+            userinfo_encryption_enc_values_supported: encryption_enc_values_supported,
+            request_object_signing_alg_values_supported,
+            request_object_encryption_alg_values_supported: None,
+            request_object_encryption_enc_values_supported: None,
+            token_endpoint_auth_methods_supported,
+            token_endpoint_auth_signing_alg_values_supported,
+            display_values_supported,
+            claim_types_supported,
+            claims_supported,
+            service_documentation,
+            claims_locales_supported: None,
+            ui_locales_supported: None,
+            claims_parameter_supported: false,
+            request_parameter_supported: true,
+            // Only request_uri values issued by our pushed authorisation endpoint are accepted.
+            request_uri_parameter_supported: true,
+            require_request_uri_registration: false,
+            op_policy_uri: None,
+            op_tos_uri: None,
+            code_challenge_methods_supported,
+            // Extensions
+            revocation_endpoint,
+            revocation_endpoint_auth_methods_supported,
+            introspection_endpoint,
+            introspection_endpoint_auth_methods_supported,
+            introspection_endpoint_auth_signing_alg_values_supported: None,
+            device_authorization_endpoint: o2rs.device_authorization_endpoint.clone(),
+            end_session_endpoint: Some(o2rs.end_session_endpoint.clone()),
+            backchannel_logout_supported: true,
+            backchannel_logout_session_supported: true,
+            pushed_authorization_request_endpoint: Some(o2rs.par_endpoint.clone()),
+            require_pushed_authorization_requests: o2rs.require_par,
+            dpop_signing_alg_values_supported: Some(vec![
+                IdTokenSignAlg::ES256,
+                IdTokenSignAlg::RS256,
+            ]),
+            tls_client_certificate_bound_access_tokens: true,
+            authorization_details_types_supported: o2rs.authorization_details_types_supported(),
+            backchannel_authentication_endpoint: o2rs
+                .enable_ciba
+                .then(|| o2rs.backchannel_authentication_endpoint.clone()),
+            backchannel_token_delivery_modes_supported: o2rs
+                .enable_ciba
+                .then(|| vec![BackchannelTokenDeliveryMode::Poll]),
+            backchannel_user_code_parameter_supported: false,
+        })
+    }
+
+    /// List the backchannel authentications that are waiting for this user to approve them.
+    #[instrument(level = "debug", skip_all)]
+    pub fn list_oauth2_backchannel_authorisations(
+        &self,
+        ident: &Identity,
+        ct: Duration,
+    ) -> Vec<PendingBackchannelAuthorisation> {
+        let Some(account_uuid) = ident.get_uuid() else {
+            return Vec::with_capacity(0);
+        };
+
+        self.oauth2_backchannel_authorisations
+            .iter()
+            .filter(|(_, bc_auth)| {
+                bc_auth.account_uuid == account_uuid
+                    && bc_auth.expiry > ct
+                    && matches!(bc_auth.state, BackchannelAuthorisationState::Pending)
+            })
+            .filter_map(|(auth_req_id, bc_auth)| {
+                let o2rs = self.oauth2rs.inner.rs_set_get(&bc_auth.client_id)?;
+                Some(PendingBackchannelAuthorisation {
+                    auth_req_id: *auth_req_id,
+                    client_name: o2rs.displayname.clone(),
+                    scopes: bc_auth.scopes.clone(),
+                    binding_message: bc_auth.binding_message.clone(),
+                })
+            })
+            .collect()
+    }
+
+    /// List the clients that this user has consented to, or that hold sessions for them.
+    #[instrument(level = "debug", skip_all)]
+    pub fn list_oauth2_consents(
+        &self,
+        ident: &Identity,
+        ct: Duration,
+    ) -> Result<Vec<Oauth2Consent>, OperationError> {
+        let Some(account_entry) = ident.get_user_entry() else {
+            error!("Ident is not a user and has no entry associated. Unable to proceed.");
+            return Err(OperationError::InvalidState);
+        };
+
+        let odt_ct = OffsetDateTime::UNIX_EPOCH + ct;
+
+        let consent_scopes = account_entry
+            .get_ava_as_oauthscopemaps(Attribute::OAuth2ConsentScopeMap)
+            .cloned()
+            .unwrap_or_default();
+
+        // Expired and revoked sessions are no longer able to be used by the client.
+        let sessions: Vec<&Oauth2Session> = account_entry
+            .get_ava_as_oauth2session_map(Attribute::OAuth2Session)
+            .into_iter()
+            .flat_map(|session_map| session_map.values())
+            .filter(|session| match session.state {
+                SessionState::RevokedAt(_) => false,
+                SessionState::ExpiresAt(exp) => exp > odt_ct,
+                SessionState::NeverExpires => true,
+            })
+            .collect();
+
+        let mut consents: Vec<_> = self
+            .oauth2rs
+            .inner
+            .private_rs_set
+            .values()
+            .filter_map(|o2rs| {
+                let client_sessions = sessions
+                    .iter()
+                    .filter(|session| session.rs_uuid == o2rs.uuid);
+                let session_count = client_sessions.clone().count();
+                let scopes = consent_scopes.get(&o2rs.uuid);
+
+                if scopes.is_none() && session_count == 0 {
+                    return None;
+                }
+
+                Some(Oauth2Consent {
+                    name: o2rs.name.clone(),
+                    display_name: o2rs.displayname.clone(),
+                    scopes: scopes.cloned().unwrap_or_default(),
+                    sessions: session_count,
+                    last_authorised: client_sessions.map(|session| session.issued_at).max(),
+                })
+            })
+            .collect();
+
+        consents.sort_unstable_by(|a, b| a.display_name.cmp(&b.display_name));
+
+        Ok(consents)
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    pub fn oauth2_openid_webfinger(
+        &mut self,
+        client_id: &str,
+        resource_id: &str,
+    ) -> Result<OidcWebfingerResponse, OperationError> {
+        let o2rs = self.oauth2rs.inner.rs_set_get(client_id).ok_or_else(|| {
+            warn!("Invalid OAuth2 client_id (have you configured the OAuth2 resource server?)");
+            OperationError::NoMatchingEntries
+        })?;
+
+        let Some(spn) = PartialValue::new_spn_s(resource_id) else {
+            return Err(OperationError::NoMatchingEntries);
+        };
+
+        // Ensure that the account exists.
+        if !self
+            .qs_read
+            .internal_exists(&Filter::new(f_eq(Attribute::Spn, spn)))?
+        {
+            return Err(OperationError::NoMatchingEntries);
+        }
+
+        let issuer = o2rs.iss.clone();
+
+        Ok(OidcWebfingerResponse {
+            // we set the subject to the resource_id to ensure we always send something valid back
+            // but realistically this will be overwritten on at the API layer
+            subject: resource_id.to_string(),
+            links: vec![OidcWebfingerRel {
+                rel: "http://openid.net/specs/connect/1.0/issuer".into(),
+                href: issuer.into(),
+            }],
+        })
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    pub fn oauth2_openid_publickey(&self, client_id: &str) -> Result<JwkKeySet, OperationError> {
+        let o2rs = self.oauth2rs.inner.rs_set_get(client_id).ok_or_else(|| {
+            warn!("Invalid OAuth2 client_id (have you configured the OAuth2 resource server?)");
+            OperationError::NoMatchingEntries
+        })?;
+
+        trace!(sign_alg = ?o2rs.sign_alg);
+
+        match o2rs.sign_alg {
+            SignatureAlgo::Es256 => o2rs.key_object.jws_es256_jwks(),
+            SignatureAlgo::Rs256 => o2rs.key_object.jws_rs256_jwks(),
+        }
+        .ok_or_else(|| {
+            error!(o2_client = ?o2rs.name, "Unable to retrieve public keys");
+            OperationError::InvalidState
+        })
+    }
+
+    /// Read the metadata of a registered client.
+    /// <https://datatracker.ietf.org/doc/html/rfc7592#section-2.1>
+    #[instrument(level = "debug", skip_all)]
+    pub fn oauth2_client_registration_read(
+        &mut self,
+        client_id: &str,
+        client_auth_info: &ClientAuthInfo,
+    ) -> Result<ClientRegistrationResponse, Oauth2Error> {
+        let o2rs = self.oauth2rs.inner.rs_set_get(client_id).ok_or_else(|| {
+            warn!("Invalid OAuth2 client_id {}", client_id);
+            Oauth2Error::AuthenticationRequired
+        })?;
+
+        o2rs.verify_registration_access_token(client_auth_info)?;
+
+        let ent = self
+            .qs_read
+            .internal_search_uuid(o2rs.uuid)
+            .map_err(Oauth2Error::ServerError)?;
+
+        client_registration_response(&self.oauth2rs.inner.origin, &ent)
+    }
+}
+
+/// Validate the parts of an authorisation request that do not depend on the user,
+/// returning the response mode and PKCE code challenge to use.
+fn validate_authorisation_request(
+    o2rs: &Oauth2RS,
+    auth_req: &AuthorisationRequest,
+) -> Result<(SupportedResponseMode, Option<Vec<u8>>), Oauth2Error> {
+    if auth_req.response_type != ResponseType::Code {
+        admin_warn!("Unsupported OAuth2 response_type (should be 'code')");
+        return Err(Oauth2Error::UnsupportedResponseType);
+    }
+
+    let Some(response_mode) = auth_req.get_response_mode() else {
+        warn!(
+            "Invalid response_mode {:?} for response_type {:?}",
+            auth_req.response_mode, auth_req.response_type
+        );
+        return Err(Oauth2Error::InvalidRequest);
+    };
+
+    let response_mode = match response_mode {
+        ResponseMode::Query => SupportedResponseMode::Query,
+        ResponseMode::Fragment => SupportedResponseMode::Fragment,
+        ResponseMode::FormPost => {
+            warn!(
+                    "Invalid response mode form_post requested - many clients request this incorrectly but proceed with response_mode=query. Remapping to query."
+                );
+            warn!("This behaviour WILL BE REMOVED in a future release.");
+            SupportedResponseMode::Query
+        }
+        ResponseMode::Invalid => {
+            warn!("Invalid response mode requested, unable to proceed");
+            return Err(Oauth2Error::InvalidRequest);
+        }
+    };
+
+    check_redirect_uri(o2rs, &auth_req.redirect_uri)?;
+
+    let code_challenge = if let Some(pkce_request) = &auth_req.pkce_request {
+        if !o2rs.require_pkce() {
+            security_info!(?o2rs.name, "Insecure OAuth2 client configuration - PKCE is not enforced, but client is requesting it!");
+        }
+        // CodeChallengeMethod must be S256
+        if pkce_request.code_challenge_method != CodeChallengeMethod::S256 {
+            admin_warn!("Invalid OAuth2 code_challenge_method (must be 'S256')");
+            return Err(Oauth2Error::InvalidRequest);
+        }
+        Some(pkce_request.code_challenge.clone())
+    } else if o2rs.require_pkce() {
+        security_error!(?o2rs.name, "No PKCE code challenge was provided with client in enforced PKCE mode");
+        return Err(Oauth2Error::InvalidRequest);
+    } else {
+        security_info!(?o2rs.name, "Insecure client configuration - PKCE is not enforced");
+        None
+    };
+
+    Ok((response_mode, code_challenge))
+}
+
+fn parse_basic_authz(client_authz: &str) -> Result<ClientAuth, Oauth2Error> {
+    // Check the client_authz
+    let authz = general_purpose::STANDARD
+        .decode(client_authz)
+        .map_err(|_| {
+            admin_error!("Basic authz invalid base64");
+            Oauth2Error::AuthenticationRequired
+        })
+        .and_then(|data| {
+            String::from_utf8(data).map_err(|_| {
+                admin_error!("Basic authz invalid utf8");
+                Oauth2Error::AuthenticationRequired
+            })
+        })?;
+
+    // Get the first :, it should be our delim.
+    //
+    let mut split_iter = authz.split(':');
+
+    let client_id = split_iter.next().ok_or_else(|| {
+        admin_error!("Basic authz invalid format (corrupt input?)");
+        Oauth2Error::AuthenticationRequired
+    })?;
+    let secret = split_iter.next().ok_or_else(|| {
+        admin_error!("Basic authz invalid format (missing ':' separator?)");
+        Oauth2Error::AuthenticationRequired
+    })?;
+
+    // OAuth2 is bad and should feel bad:
+    // https://datatracker.ietf.org/doc/html/rfc6749#section-2.3.1
+    // The client_id MIGHT be url encoded. The secret won't be, because we
+    // always generate it in a "safe" manner.
+    //
+    // If we *fail* to decode, then we just drop the raw client_id back out,
+    // as this could be a client that didn't urlencoded their client_id.
+    //
+    // Both serde_urlencode and form_urlencoded can't handle this, as they assume
+    // you have a map, so you can't deseralise to a string.
+    //
+    // Thankfully in our case we only allow - and _ so we can just replace these
+    // directly.
+    let client_id = client_id.replace("%2D", "-").replace("%5F", "_");
+
+    Ok((client_id.as_str(), Some(secret)).into())
+}
+
+fn s_claims_for_account(
+    o2rs: &Oauth2RS,
+    account: &Account,
+    scopes: &BTreeSet<String>,
+) -> OidcClaims {
+    let preferred_username = if o2rs.prefer_short_username {
+        Some(account.name().into())
+    } else {
+        Some(account.spn().into())
+    };
+
+    let (email, email_verified) = if scopes.contains(OAUTH2_SCOPE_EMAIL) {
+        if let Some(mp) = &account.mail_primary {
+            (Some(mp.clone()), Some(true))
+        } else {
+            (None, None)
+        }
+    } else {
+        (None, None)
+    };
+
+    let updated_at: Option<OffsetDateTime> = if scopes.contains(OAUTH2_SCOPE_PROFILE) {
+        account
+            .updated_at
+            .as_ref()
+            .map(OffsetDateTime::from)
+            .and_then(|odt| odt.replace_nanosecond(0).ok())
+    } else {
+        None
+    };
+    OidcClaims {
+        // Map from displayname
+        name: Some(account.displayname.clone()),
+        scopes: scopes.iter().cloned().collect(),
+        preferred_username,
+        email,
+        email_verified,
+        updated_at,
+        ..Default::default()
+    }
+}
+
+fn extra_claims_for_account(
+    account: &Account,
+
+    claim_map: &BTreeMap<Uuid, Vec<(String, ClaimValue)>>,
+
+    scopes: &BTreeSet<String>,
+) -> BTreeMap<String, serde_json::Value> {
+    let mut extra_claims = BTreeMap::new();
+
+    let mut account_claims: BTreeMap<&str, ClaimValue> = BTreeMap::new();
+
+    // for each group
+    for group_uuid in account.groups.iter().map(|g| g.uuid()) {
+        // Does this group have any custom claims?
+        if let Some(claim) = claim_map.get(group_uuid) {
+            // If so, iterate over the set of claims and values.
+            for (claim_name, claim_value) in claim.iter() {
+                // Does this claim name already exist in our in-progress map?
+                match account_claims.entry(claim_name.as_str()) {
+                    BTreeEntry::Vacant(e) => {
+                        e.insert(claim_value.clone());
+                    }
+                    BTreeEntry::Occupied(mut e) => {
+                        let mut_claim_value = e.get_mut();
+                        // Merge the extra details into this.
+                        mut_claim_value.merge(claim_value);
+                    }
+                }
+            }
+        }
+    }
+
+    // Now, flatten all these into the final structure.
+    for (claim_name, claim_value) in account_claims {
+        extra_claims.insert(claim_name.to_string(), claim_value.to_json_value());
+    }
+
+    // Now perform our custom claim's from scopes. We do these second so that
+    // a user can't stomp our claim names.
+
+    if scopes.contains(OAUTH2_SCOPE_SSH_PUBLICKEYS) {
+        extra_claims.insert(
+            OAUTH2_SCOPE_SSH_PUBLICKEYS.to_string(),
+            account
+                .sshkeys()
+                .values()
+                .map(|pub_key| serde_json::Value::String(pub_key.to_string()))
+                .collect(),
+        );
+    }
+
+    let wants_groups = scopes.contains(OAUTH2_SCOPE_GROUPS);
+    // groups implies uuid + spn to match current behaviour.
+    let wants_groups_uuid = wants_groups || scopes.contains(OAUTH2_SCOPE_GROUPS_UUID);
+    let wants_groups_spn = wants_groups || scopes.contains(OAUTH2_SCOPE_GROUPS_SPN);
+    let wants_groups_name = scopes.contains(OAUTH2_SCOPE_GROUPS_NAME);
+
+    if wants_groups_uuid || wants_groups_name || wants_groups_spn {
+        extra_claims.insert(
+            OAUTH2_SCOPE_GROUPS.to_string(),
+            account
+                .groups
+                .iter()
+                .flat_map(|group| {
+                    let mut attrs = Vec::with_capacity(3);
+
+                    if wants_groups_uuid {
+                        attrs.push(group.uuid().as_hyphenated().to_string())
+                    }
+
+                    if wants_groups_spn {
+                        attrs.push(group.spn().clone())
+                    }
+
+                    if wants_groups_name {
+                        if let Some(name) = group.name() {
+                            attrs.push(name.into())
+                        }
+                    }
+
+                    attrs
+                })
+                .collect(),
+        );
+    }
+
+    trace!(?extra_claims);
+
+    extra_claims
+}
+
+fn process_requested_scopes_for_identity(
+    o2rs: &Oauth2RS,
+    ident: &Identity,
+    req_scopes: Option<&BTreeSet<String>>,
+) -> Result<(BTreeSet<String>, BTreeSet<String>), Oauth2Error> {
+    let req_scopes = req_scopes.cloned().unwrap_or_default();
+
+    if req_scopes.is_empty() {
+        admin_error!("Invalid OAuth2 request - must contain at least one requested scope");
+        return Err(Oauth2Error::InvalidRequest);
+    }
+
+    validate_scopes(&req_scopes)?;
+
+    let available_scopes: BTreeSet<String> = o2rs
+        .scope_maps
+        .iter()
+        .filter_map(|(u, m)| ident.is_memberof(*u).then_some(m.iter()))
+        .flatten()
+        .cloned()
+        .collect();
+
+    if !req_scopes.is_subset(&available_scopes) {
+        admin_warn!(
+            %ident,
+            requested_scopes = ?req_scopes,
+            available_scopes = ?available_scopes,
+            "Identity does not have access to the requested scopes"
+        );
+        return Err(Oauth2Error::AccessDenied);
+    }
+
+    let granted_scopes: BTreeSet<String> = o2rs
+        .sup_scope_maps
+        .iter()
+        .filter_map(|(u, m)| ident.is_memberof(*u).then_some(m.iter()))
+        .flatten()
+        .cloned()
+        .chain(req_scopes.iter().cloned())
+        .collect();
+
+    Ok((req_scopes, granted_scopes))
+}
+
+fn validate_scopes(req_scopes: &BTreeSet<String>) -> Result<(), Oauth2Error> {
+    let failed_scopes = req_scopes
+        .iter()
+        .filter(|&s| !OAUTHSCOPE_RE.is_match(s))
+        .cloned()
+        .collect::<Vec<String>>();
+
+    if !failed_scopes.is_empty() {
+        let requested_scopes_string = req_scopes
+            .iter()
+            .cloned()
+            .collect::<Vec<String>>()
+            .join(",");
+        admin_error!(
+                "Invalid OAuth2 request - requested scopes ({}) but ({}) failed to pass validation rules - all must match the regex {}",
+                    requested_scopes_string,
+                    failed_scopes.join(","),
+                    OAUTHSCOPE_RE.as_str()
+            );
+        return Err(Oauth2Error::InvalidScope);
+    }
+    Ok(())
+}
+
+/// device code is a random bucket of bytes used in the device flow
+#[inline]
+#[cfg(any(feature = "dev-oauth2-device-flow", test))]
+#[allow(dead_code)]
+fn gen_device_code() -> Result<[u8; 16], Oauth2Error> {
+    use rand::TryRng;
+
+    let mut rng = rand::rng();
+    let mut result = [0u8; 16];
+    // doing it here because of feature-shenanigans.
+    if let Err(err) = rng.try_fill_bytes(&mut result) {
+        error!("Failed to generate device code! {:?}", err);
+        return Err(Oauth2Error::ServerError(OperationError::Backend));
+    }
+    Ok(result)
+}
+
+#[inline]
+#[cfg(any(feature = "dev-oauth2-device-flow", test))]
+#[allow(dead_code)]
+/// Returns (xxx-yyy-zzz, digits) where one's the human-facing code, the other is what we store in the DB.
+fn gen_user_code() -> (String, u32) {
+    use rand::RngExt;
+    let mut rng = rand::rng();
+    let num: u32 = rng.random_range(0..=999999999);
+    let result = format!("{num:09}");
+    (
+        format!("{}-{}-{}", &result[0..3], &result[3..6], &result[6..9]),
+        num,
+    )
+}
+
+/// Take the supplied user code and check it's a valid u32
+#[allow(dead_code)]
+fn parse_user_code(val: &str) -> Result<u32, Oauth2Error> {
+    let mut val = val.to_string();
+    val.retain(|c| c.is_ascii_digit());
+    val.parse().map_err(|err| {
+        debug!("Failed to parse value={} as u32: {:?}", val, err);
+        Oauth2Error::InvalidRequest
+    })
+}
+
+/// Check if a host is local (loopback or localhost)
+fn host_is_local(host: &Host<&str>) -> bool {
+    match host {
+        Host::Ipv4(ip) => ip.is_loopback(),
+        Host::Ipv6(ip) => ip.is_loopback(),
+        Host::Domain(domain) => *domain == "localhost",
+    }
+}
+
+/// Check that a redirect uri is one that the client may send the user agent to.
+fn check_redirect_uri(o2rs: &Oauth2RS, redirect_uri: &Url) -> Result<(), Oauth2Error> {
+    // redirect_uri must be part of the client_id origins, unless the client is public and then it MAY
+    // be a loopback address exempting it from this check and enforcement and we can carry on safely.
+
+    // == start validate oauth2 redirect conditions.
+
+    let redirect_uri_is_loopback = check_is_loopback(redirect_uri);
+    let type_allows_localhost_redirect = o2rs.type_.allow_localhost_redirect();
+
+    // This allows loopback uri's that are *not* part of the origin/redirect_uri configurations.
+    let loopback_uri_matched = redirect_uri_is_loopback && type_allows_localhost_redirect;
+
+    // The legacy origin match is in use.
+    let origin_uri_matched =
+        !o2rs.strict_redirect_uri && o2rs.origins.contains(&redirect_uri.origin());
+
+    // Strict uri validation is in use, must be an exact match.
+    let strict_redirect_uri_matched =
+        o2rs.strict_redirect_uri && o2rs.redirect_uris.contains(redirect_uri);
+
+    // Allow opaque origins such as app uris.
+    let opaque_origin_matched = o2rs.opaque_origins.contains(redirect_uri);
+
+    // Was the redirect origin secure?
+    let redirect_origin_is_secure =
+        opaque_origin_matched || redirect_uri_is_loopback || redirect_uri.scheme() == "https";
+
+    // We must assert that *AT LEAST* one of the above match conditions holds true to proceed.
+    let valid_match_condition_asserted = loopback_uri_matched
+        || origin_uri_matched
+        || strict_redirect_uri_matched
+        || opaque_origin_matched;
+
+    if valid_match_condition_asserted {
+        debug!(
+            ?loopback_uri_matched,
+            ?origin_uri_matched,
+            ?strict_redirect_uri_matched,
+            ?opaque_origin_matched,
+            "valid redirect uri match condition met."
+        );
+    } else {
+        // Display why it failed.
+
+        // This is to catch the specific case that a public client gets a localhost redirect, but
+        // the admin hasn't enabled the flag for it. This way we direct them to the correct cause
+        // of the issue, rather than telling them to configure localhost as a redirect location.
+        let could_allow_localhost_redirect =
+            o2rs.type_.allow_localhost_redirect_could_be_possible();
+
+        if redirect_uri_is_loopback
+            && could_allow_localhost_redirect
+            && !type_allows_localhost_redirect
+        {
+            warn!(redirect_uri = %redirect_uri, "OAuth2 redirect_uri returns to localhost, but localhost redirection is not allowed. See 'kanidm system oauth2 enable-localhost-redirects'");
+        } else {
+            // Not localhost - must be missing the redirect uri then, which is why strict/origin/opaque all failed to assert
+            if o2rs.strict_redirect_uri {
+                warn!(
+                    "Invalid OAuth2 redirect_uri (must be an exact match to a redirect-url) - got {} from client but configured uris do not match (check oauth2_rs_origin entries)",
+                    redirect_uri.as_str()
+                );
+            } else {
+                warn!(
+                    "Invalid OAuth2 redirect_uri (must be related to origin) - got {:?} from client but configured uris differ (compare oauth2_rs_origin_landing with oauth2_rs_origin entries)",
+                    redirect_uri.origin()
+                );
+            }
+        }
+
+        // All roads lead to error.
+        return Err(Oauth2Error::InvalidOrigin);
+    }
+
+    // Assert that if secure origins were required, that we are enforcing that.
+    if o2rs.origin_secure_required && !redirect_origin_is_secure {
+        warn!(
+            "Invalid OAuth2 redirect_uri scheme (must be a secure origin) - got {} instead. Secure origins are required when *at least* one redirect_uri is https, then all uri's must also be secure origins.",
+            redirect_uri
+        );
+        return Err(Oauth2Error::InvalidOrigin);
+    }
+
+    // == end validation of oauth2 redirect conditions.
+
+    Ok(())
+}
+
+/// Ensure that the redirect URI is a loopback/localhost address
+fn check_is_loopback(redirect_uri: &Url) -> bool {
+    redirect_uri.host().is_some_and(|host| {
+        // Check if the host is a loopback/localhost address.
+        host_is_local(&host)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Oauth2TokenType, PkceS256Secret, TOKEN_EXCHANGE_SUBJECT_TOKEN_TYPE_ACCESS};
+    use crate::credential::Credential;
+    use crate::idm::accountpolicy::ResolvedAccountPolicy;
+    use crate::idm::delayed::DelayedAction;
+    use crate::idm::oauth2::{
+        host_is_local, parse_basic_authz, AuthoriseResponse, Oauth2Error, OauthRSType,
+        UserinfoResponse,
+    };
+    use crate::idm::server::{IdmServer, IdmServerTransaction};
+    use crate::idm::serviceaccount::GenerateApiTokenEvent;
+    use crate::prelude::*;
+    use crate::value::{AuthType, OauthClaimMapJoin, SessionState};
+    use crate::valueset::{ValueSetOauthScopeMap, ValueSetSshKey};
+    use base64::{engine::general_purpose, Engine as _};
+    use compact_jwt::{
+        compact::JwkUse, crypto::JwsRs256Verifier, dangernoverify::JwsDangerReleaseWithoutVerify,
+        JwaAlg, Jwk, JwsCompact, JwsEs256Verifier, JwsVerifier, OidcSubject, OidcToken,
+        OidcUnverified,
+    };
+    use kanidm_lib_crypto::CryptoPolicy;
+    use kanidm_proto::constants::*;
+    use kanidm_proto::internal::{SshPublicKey, UserAuthToken};
+    use kanidm_proto::oauth2::*;
+    use std::collections::{BTreeMap, BTreeSet};
+    use std::convert::TryFrom;
+    use std::str::FromStr;
+    use std::time::Duration;
+    use time::OffsetDateTime;
+    use uri::{OAUTH2_TOKEN_INTROSPECT_ENDPOINT, OAUTH2_TOKEN_REVOKE_ENDPOINT};
+
+    const TEST_CURRENT_TIME: u64 = 6000;
+    const UAT_EXPIRE: u64 = 5;
+    const TOKEN_EXPIRE: u64 = 900;
+
+    const UUID_TESTGROUP: Uuid = uuid!("a3028223-bf20-47d5-8b65-967b5d2bb3eb");
+
+    macro_rules! good_authorisation_request {
+        (
+            $idms_prox_read:expr,
+            $ident:expr,
+            $ct:expr,
+            $pkce_request:expr,
+            $scope:expr
+        ) => {{
+            #[allow(clippy::unnecessary_to_owned)]
+            let scope: BTreeSet<String> = $scope.split(" ").map(|s| s.to_string()).collect();
+
+            let auth_req = AuthorisationRequest {
+                response_type: ResponseType::Code,
+                response_mode: None,
+                client_id: "test_resource_server".to_string(),
+                state: Some("123".to_string()),
+                pkce_request: Some($pkce_request),
+                redirect_uri: Url::parse("https://demo.example.com/oauth2/result").unwrap(),
+                scope,
+                nonce: Some("abcdef".to_string()),
+                oidc_ext: Default::default(),
+                max_age: None,
+                authorization_details: None,
+                unknown_keys: Default::default(),
+            };
+
+            $idms_prox_read
+                .check_oauth2_authorisation(Some($ident), &auth_req, $ct)
+                .expect("OAuth2 authorisation failed")
+        }};
+    }
+
+    // setup an OAuth2 instance.
+    async fn setup_oauth2_resource_server_basic(
+        idms: &IdmServer,
+        ct: Duration,
+        enable_pkce: bool,
+        enable_legacy_crypto: bool,
+        prefer_short_username: bool,
+    ) -> (String, UserAuthToken, Identity, Uuid) {
+        let mut idms_prox_write = idms.proxy_write(ct).await.unwrap();
+
+        let rs_uuid = Uuid::new_v4();
+
+        let entry_group: Entry<EntryInit, EntryNew> = entry_init!(
+            (Attribute::Class, EntryClass::Group.to_value()),
+            (Attribute::Name, Value::new_iname("testgroup")),
+            (Attribute::Description, Value::new_utf8s("testgroup")),
+            (Attribute::Uuid, Value::Uuid(UUID_TESTGROUP)),
+            (Attribute::Member, Value::Refer(UUID_TESTPERSON_1),)
+        );
+
+        let entry_rs: Entry<EntryInit, EntryNew> = entry_init!(
+            (Attribute::Class, EntryClass::Object.to_value()),
+            (Attribute::Class, EntryClass::Account.to_value()),
+            (
+                Attribute::Class,
+                EntryClass::OAuth2ResourceServer.to_value()
+            ),
+            (
+                Attribute::Class,
+                EntryClass::OAuth2ResourceServerBasic.to_value()
+            ),
+            (Attribute::Uuid, Value::Uuid(rs_uuid)),
+            (Attribute::Name, Value::new_iname("test_resource_server")),
+            (
+                Attribute::DisplayName,
+                Value::new_utf8s("test_resource_server")
+            ),
+            (
+                Attribute::OAuth2RsOriginLanding,
+                Value::new_url_s("https://demo.example.com").unwrap()
+            ),
+            // Supplemental origins
+            (
+                Attribute::OAuth2RsOrigin,
+                Value::new_url_s("https://demo.example.com/oauth2/result").unwrap()
+            ),
+            (
+                Attribute::OAuth2RsOrigin,
+                Value::new_url_s("https://portal.example.com/?custom=foo").unwrap()
+            ),
+            (
+                Attribute::OAuth2RsOrigin,
+                Value::new_url_s("app://cheese").unwrap()
+            ),
+            // System admins
+            (
+                Attribute::OAuth2RsScopeMap,
+                Value::new_oauthscopemap(
+                    UUID_TESTGROUP,
+                    btreeset![OAUTH2_SCOPE_GROUPS.to_string()]
+                )
+                .expect("invalid oauthscope")
+            ),
+            (
+                Attribute::OAuth2RsScopeMap,
+                Value::new_oauthscopemap(
+                    UUID_IDM_ALL_ACCOUNTS,
+                    btreeset![
+                        OAUTH2_SCOPE_OPENID.to_string(),
+                        OAUTH2_SCOPE_PROFILE.to_string()
+                    ]
+                )
+                .expect("invalid oauthscope")
+            ),
+            (
+                Attribute::OAuth2RsSupScopeMap,
+                Value::new_oauthscopemap(
+                    UUID_IDM_ALL_ACCOUNTS,
+                    btreeset!["supplement".to_string()]
+                )
+                .expect("invalid oauthscope")
+            ),
+            (
+                Attribute::OAuth2AllowInsecureClientDisablePkce,
+                Value::new_bool(!enable_pkce)
+            ),
+            (
+                Attribute::OAuth2JwtLegacyCryptoEnable,
+                Value::new_bool(enable_legacy_crypto)
+            ),
+            (
+                Attribute::OAuth2PreferShortUsername,
+                Value::new_bool(prefer_short_username)
+            )
+        );
+
+        let ce = CreateEvent::new_internal(vec![entry_rs, entry_group, E_TESTPERSON_1.clone()]);
+        assert!(idms_prox_write.qs_write.create(&ce).is_ok());
+
+        let entry = idms_prox_write
+            .qs_write
+            .internal_search_uuid(rs_uuid)
+            .expect("Failed to retrieve OAuth2 resource entry ");
+        let secret = entry
+            .get_ava_single_secret(Attribute::OAuth2RsBasicSecret)
+            .map(str::to_string)
+            .expect("No oauth2_rs_basic_secret found");
+
+        // Setup the uat we'll be using - note for these tests they *require*
+        // the parent session to be valid and present!
+        let session_id = uuid::Uuid::new_v4();
+
+        let account = idms_prox_write
+            .target_to_account(UUID_TESTPERSON_1)
+            .expect("account must exist");
+
+        let uat = account
+            .to_userauthtoken(
+                session_id,
+                SessionScope::ReadWrite,
+                ct,
+                &ResolvedAccountPolicy::test_policy(),
+            )
+            .expect("Unable to create uat");
+
+        // Need the uat first for expiry.
+        let state = uat
+            .expiry
+            .map(SessionState::ExpiresAt)
+            .unwrap_or(SessionState::NeverExpires);
+
+        let p = CryptoPolicy::minimum();
+        let cred =
+            Credential::new_password_only(&p, "test_password", OffsetDateTime::UNIX_EPOCH).unwrap();
+        let cred_id = cred.uuid;
+
+        let session = Value::Session(
+            session_id,
+            crate::value::Session {
+                label: "label".to_string(),
+                state,
+                issued_at: time::OffsetDateTime::UNIX_EPOCH + ct,
+                issued_by: IdentityId::Internal(UUID_SYSTEM),
+                cred_id,
+                scope: SessionScope::ReadWrite,
+                type_: AuthType::Passkey,
+                ext_metadata: Default::default(),
+            },
+        );
+
+        // Mod the user
+        let modlist = ModifyList::new_list(vec![
+            Modify::Present(Attribute::UserAuthTokenSession, session),
+            Modify::Present(
+                Attribute::PrimaryCredential,
+                Value::Cred("primary".to_string(), cred),
+            ),
+        ]);
+
+        idms_prox_write
+            .qs_write
+            .internal_modify(
+                &filter!(f_eq(Attribute::Uuid, PartialValue::Uuid(UUID_TESTPERSON_1))),
+                &modlist,
+            )
+            .expect("Failed to modify user");
+
+        let ident = idms_prox_write
+            .process_uat_to_identity(&uat, ct, Source::Internal)
+            .expect("Unable to process uat");
+
+        idms_prox_write.commit().expect("failed to commit");
+
+        (secret, uat, ident, rs_uuid)
+    }
+
+    async fn setup_oauth2_resource_server_public(
+        idms: &IdmServer,
+        ct: Duration,
+    ) -> (UserAuthToken, Identity, Uuid) {
+        let mut idms_prox_write = idms.proxy_write(ct).await.unwrap();
+
+        let rs_uuid = Uuid::new_v4();
+
+        let entry_group: Entry<EntryInit, EntryNew> = entry_init!(
+            (Attribute::Class, EntryClass::Group.to_value()),
+            (Attribute::Name, Value::new_iname("testgroup")),
+            (Attribute::Description, Value::new_utf8s("testgroup")),
+            (Attribute::Uuid, Value::Uuid(UUID_TESTGROUP)),
+            (Attribute::Member, Value::Refer(UUID_TESTPERSON_1),)
+        );
+
+        let entry_rs: Entry<EntryInit, EntryNew> = entry_init!(
+            (Attribute::Class, EntryClass::Object.to_value()),
+            (Attribute::Class, EntryClass::Account.to_value()),
+            (
                 Attribute::Class,
                 EntryClass::OAuth2ResourceServer.to_value()
             ),
             (
                 Attribute::Class,
-                EntryClass::OAuth2ResourceServerBasic.to_value()
+                EntryClass::OAuth2ResourceServerPublic.to_value()
             ),
             (Attribute::Uuid, Value::Uuid(rs_uuid)),
             (Attribute::Name, Value::new_iname("test_resource_server")),
@@ -3502,19 +6398,10 @@ mod tests {
                 Attribute::OAuth2RsOriginLanding,
                 Value::new_url_s("https://demo.example.com").unwrap()
             ),
-            // Supplemental origins
             (
                 Attribute::OAuth2RsOrigin,
                 Value::new_url_s("https://demo.example.com/oauth2/result").unwrap()
             ),
-            (
-                Attribute::OAuth2RsOrigin,
-                Value::new_url_s("https://portal.example.com/?custom=foo").unwrap()
-            ),
-            (
-                Attribute::OAuth2RsOrigin,
-                Value::new_url_s("app://cheese").unwrap()
-            ),
             // System admins
             (
                 Attribute::OAuth2RsScopeMap,
@@ -3528,367 +6415,1469 @@ mod tests {
                 Attribute::OAuth2RsScopeMap,
                 Value::new_oauthscopemap(
                     UUID_IDM_ALL_ACCOUNTS,
-                    btreeset![
-                        OAUTH2_SCOPE_OPENID.to_string(),
-                        OAUTH2_SCOPE_PROFILE.to_string()
-                    ]
+                    btreeset![OAUTH2_SCOPE_OPENID.to_string()]
+                )
+                .expect("invalid oauthscope")
+            ),
+            (
+                Attribute::OAuth2RsSupScopeMap,
+                Value::new_oauthscopemap(
+                    UUID_IDM_ALL_ACCOUNTS,
+                    btreeset!["supplement".to_string()]
+                )
+                .expect("invalid oauthscope")
+            )
+        );
+        let ce = CreateEvent::new_internal(vec![entry_rs, entry_group, E_TESTPERSON_1.clone()]);
+        assert!(idms_prox_write.qs_write.create(&ce).is_ok());
+
+        // Setup the uat we'll be using - note for these tests they *require*
+        // the parent session to be valid and present!
+
+        let session_id = uuid::Uuid::new_v4();
+
+        let account = idms_prox_write
+            .target_to_account(UUID_TESTPERSON_1)
+            .expect("account must exist");
+        let uat = account
+            .to_userauthtoken(
+                session_id,
+                SessionScope::ReadWrite,
+                ct,
+                &ResolvedAccountPolicy::test_policy(),
+            )
+            .expect("Unable to create uat");
+
+        // Need the uat first for expiry.
+        let state = uat
+            .expiry
+            .map(SessionState::ExpiresAt)
+            .unwrap_or(SessionState::NeverExpires);
+
+        let p = CryptoPolicy::minimum();
+        let cred =
+            Credential::new_password_only(&p, "test_password", OffsetDateTime::UNIX_EPOCH).unwrap();
+        let cred_id = cred.uuid;
+
+        let session = Value::Session(
+            session_id,
+            crate::value::Session {
+                label: "label".to_string(),
+                state,
+                issued_at: time::OffsetDateTime::UNIX_EPOCH + ct,
+                issued_by: IdentityId::Internal(UUID_SYSTEM),
+                cred_id,
+                scope: SessionScope::ReadWrite,
+                type_: AuthType::Passkey,
+                ext_metadata: Default::default(),
+            },
+        );
+
+        // Mod the user
+        let modlist = ModifyList::new_list(vec![
+            Modify::Present(Attribute::UserAuthTokenSession, session),
+            Modify::Present(
+                Attribute::PrimaryCredential,
+                Value::Cred("primary".to_string(), cred),
+            ),
+        ]);
+
+        idms_prox_write
+            .qs_write
+            .internal_modify(
+                &filter!(f_eq(Attribute::Uuid, PartialValue::Uuid(UUID_TESTPERSON_1))),
+                &modlist,
+            )
+            .expect("Failed to modify user");
+
+        let ident = idms_prox_write
+            .process_uat_to_identity(&uat, ct, Source::Internal)
+            .expect("Unable to process uat");
+
+        idms_prox_write.commit().expect("failed to commit");
+
+        (uat, ident, rs_uuid)
+    }
+
+    /// Perform an oauth2 exchange, assuming that it will succeed.
+    async fn perform_oauth2_exchange(
+        idms: &IdmServer,
+        ident: &Identity,
+        ct: Duration,
+        client_authz: ClientAuthInfo,
+        scopes: String,
+    ) -> AccessTokenResponse {
+        let idms_prox_read = idms.proxy_read().await.unwrap();
+
+        let pkce_secret = PkceS256Secret::default();
+
+        let consent_request = good_authorisation_request!(
+            idms_prox_read,
+            ident,
+            ct,
+            pkce_secret.to_request(),
+            scopes
+        );
+
+        let AuthoriseResponse::ConsentRequested { consent_token, .. } = consent_request else {
+            unreachable!();
+        };
+
+        // == Manually submit the consent token to the permit for the permit_success
+        drop(idms_prox_read);
+        let mut idms_prox_write = idms.proxy_write(ct).await.unwrap();
+
+        let permit_success = idms_prox_write
+            .check_oauth2_authorise_permit(ident, &consent_token, ct)
+            .expect("Failed to perform OAuth2 permit");
+
+        // == Submit the token exchange code.
+        let token_req: AccessTokenRequest = GrantTypeReq::AuthorizationCode {
+            code: permit_success.code,
+            redirect_uri: Url::parse("https://demo.example.com/oauth2/result").unwrap(),
+            code_verifier: Some(pkce_secret.to_verifier()),
+        }
+        .into();
+
+        let token_response = idms_prox_write
+            .check_oauth2_token_exchange(&client_authz, &token_req, ct)
+            .expect("Failed to perform OAuth2 token exchange");
+
+        assert!(idms_prox_write.commit().is_ok());
+
+        token_response
+    }
+
+    async fn validate_id_token(idms: &IdmServer, ct: Duration, id_token: &str) -> OidcToken {
+        let idms_prox_read = idms.proxy_read().await.unwrap();
+
+        let mut jwkset = idms_prox_read
+            .oauth2_openid_publickey("test_resource_server")
+            .expect("Failed to get public key");
+        let public_jwk = jwkset.keys.pop().expect("no such jwk");
+
+        let jws_validator =
+            JwsEs256Verifier::try_from(&public_jwk).expect("failed to build validator");
+
+        let oidc_unverified = OidcUnverified::from_str(id_token).expect("Failed to parse id_token");
+
+        let iat = ct.as_secs() as i64;
+
+        jws_validator
+            .verify(&oidc_unverified)
+            .unwrap()
+            .verify_exp(iat)
+            .expect("Failed to verify oidc")
+    }
+
+    async fn setup_idm_admin(idms: &IdmServer, ct: Duration) -> (UserAuthToken, Identity) {
+        let mut idms_prox_write = idms.proxy_write(ct).await.unwrap();
+        let account = idms_prox_write
+            .target_to_account(UUID_IDM_ADMIN)
+            .expect("account must exist");
+        let session_id = uuid::Uuid::new_v4();
+        let uat = account
+            .to_userauthtoken(
+                session_id,
+                SessionScope::ReadWrite,
+                ct,
+                &ResolvedAccountPolicy::test_policy(),
+            )
+            .expect("Unable to create uat");
+        let ident = idms_prox_write
+            .process_uat_to_identity(&uat, ct, Source::Internal)
+            .expect("Unable to process uat");
+
+        idms_prox_write.commit().expect("failed to commit");
+
+        (uat, ident)
+    }
+
+    #[test]
+    fn oauth2_parse_basic_authz() {
+        let r1 = parse_basic_authz("czZCaGRSa3F0Mzo3RmpmcDBaQnIxS3REUmJuZlZkbUl3").unwrap();
+        assert_eq!(r1.client_id, "s6BhdRkqt3");
+        assert_eq!(r1.client_secret.as_deref(), Some("7Fjfp0ZBr1KtDRbnfVdmIw"));
+
+        // This contains: "my%2Did:Dei7thai1ahne4a" which has a url-encoded client-id
+        let r2 = parse_basic_authz("bXklMkRpZDpEZWk3dGhhaTFhaG5lNGE=").unwrap();
+        assert_eq!(r2.client_id, "my-id");
+        assert_eq!(r2.client_secret.as_deref(), Some("Dei7thai1ahne4a"));
+    }
+
+    #[idm_test]
+    async fn test_idm_oauth2_basic_function(
+        idms: &IdmServer,
+        _idms_delayed: &mut IdmServerDelayed,
+    ) {
+        let ct = Duration::from_secs(TEST_CURRENT_TIME);
+        let (secret, _uat, ident, _) =
+            setup_oauth2_resource_server_basic(idms, ct, true, false, false).await;
+
+        let idms_prox_read = idms.proxy_read().await.unwrap();
+
+        // == Setup the authorisation request
+        let pkce_secret = PkceS256Secret::default();
+
+        let consent_request = good_authorisation_request!(
+            idms_prox_read,
+            &ident,
+            ct,
+            pkce_secret.to_request(),
+            OAUTH2_SCOPE_OPENID.to_string()
+        );
+
+        // Should be in the consent phase;
+        let AuthoriseResponse::ConsentRequested { consent_token, .. } = consent_request else {
+            unreachable!();
+        };
+
+        // == Manually submit the consent token to the permit for the permit_success
+        drop(idms_prox_read);
+        let mut idms_prox_write = idms.proxy_write(ct).await.unwrap();
+
+        let permit_success = idms_prox_write
+            .check_oauth2_authorise_permit(&ident, &consent_token, ct)
+            .expect("Failed to perform OAuth2 permit");
+
+        // Check we are reflecting the CSRF properly.
+        assert_eq!(permit_success.state.as_deref(), Some("123"));
+
+        // == Submit the token exchange code.
+
+        let token_req = AccessTokenRequest {
+            grant_type: GrantTypeReq::AuthorizationCode {
+                code: permit_success.code,
+                redirect_uri: Url::parse("https://demo.example.com/oauth2/result").unwrap(),
+                code_verifier: Some(pkce_secret.to_verifier()),
+            },
+            client_post_auth: ClientPostAuth {
+                client_id: Some("test_resource_server".to_string()),
+                client_secret: Some(secret),
+                ..Default::default()
+            },
+        };
+
+        let token_response = idms_prox_write
+            .check_oauth2_token_exchange(&ClientAuthInfo::none(), &token_req, ct)
+            .expect("Failed to perform OAuth2 token exchange");
+
+        // 🎉 We got a token! In the future we can then check introspection from this point.
+        assert_eq!(token_response.token_type, AccessTokenType::Bearer);
+
+        assert!(idms_prox_write.commit().is_ok());
+    }
+
+    #[idm_test]
+    async fn test_idm_oauth2_public_function(
+        idms: &IdmServer,
+        _idms_delayed: &mut IdmServerDelayed,
+    ) {
+        let ct = Duration::from_secs(TEST_CURRENT_TIME);
+        let (_uat, ident, _) = setup_oauth2_resource_server_public(idms, ct).await;
+
+        let idms_prox_read = idms.proxy_read().await.unwrap();
+
+        // Get an ident/uat for now.
+
+        // == Setup the authorisation request
+        let pkce_secret = PkceS256Secret::default();
+
+        let consent_request = good_authorisation_request!(
+            idms_prox_read,
+            &ident,
+            ct,
+            pkce_secret.to_request(),
+            OAUTH2_SCOPE_OPENID.to_string()
+        );
+
+        // Should be in the consent phase;
+        let AuthoriseResponse::ConsentRequested { consent_token, .. } = consent_request else {
+            unreachable!();
+        };
+
+        // == Manually submit the consent token to the permit for the permit_success
+        drop(idms_prox_read);
+        let mut idms_prox_write = idms.proxy_write(ct).await.unwrap();
+
+        let permit_success = idms_prox_write
+            .check_oauth2_authorise_permit(&ident, &consent_token, ct)
+            .expect("Failed to perform OAuth2 permit");
+
+        // Check we are reflecting the CSRF properly.
+        assert_eq!(permit_success.state.as_deref(), Some("123"));
+
+        // == Submit the token exchange code.
+
+        let token_req = AccessTokenRequest {
+            grant_type: GrantTypeReq::AuthorizationCode {
+                code: permit_success.code,
+                redirect_uri: Url::parse("https://demo.example.com/oauth2/result").unwrap(),
+                // From the first step.
+                code_verifier: Some(pkce_secret.to_verifier()),
+            },
+
+            client_post_auth: ClientPostAuth {
+                client_id: Some("Test_Resource_Server".to_string()),
+                client_secret: None,
+                ..Default::default()
+            },
+        };
+
+        let token_response = idms_prox_write
+            .check_oauth2_token_exchange(&ClientAuthInfo::none(), &token_req, ct)
+            .expect("Failed to perform OAuth2 token exchange");
+
+        // 🎉 We got a token! In the future we can then check introspection from this point.
+        assert_eq!(token_response.token_type, AccessTokenType::Bearer);
+
+        assert!(idms_prox_write.commit().is_ok());
+    }
+
+    #[idm_test]
+    async fn test_idm_oauth2_invalid_authorisation_requests(
+        idms: &IdmServer,
+        _idms_delayed: &mut IdmServerDelayed,
+    ) {
+        // Test invalid OAuth2 authorisation states/requests.
+        let ct = Duration::from_secs(TEST_CURRENT_TIME);
+        let (_secret, _uat, ident, _) =
+            setup_oauth2_resource_server_basic(idms, ct, true, false, false).await;
+
+        let (_anon_uat, anon_ident) = setup_idm_admin(idms, ct).await;
+        let (_idm_admin_uat, idm_admin_ident) = setup_idm_admin(idms, ct).await;
+
+        // Need a uat from a user not in the group. Probs anonymous.
+        let idms_prox_read = idms.proxy_read().await.unwrap();
+
+        let pkce_secret = PkceS256Secret::default();
+
+        let pkce_request = pkce_secret.to_request();
+
+        //  * response type != code.
+        let auth_req = AuthorisationRequest {
+            // We're unlikely to support Implicit Grant
+            response_type: ResponseType::Token,
+            response_mode: None,
+            client_id: "test_resource_server".to_string(),
+            state: Some("123".to_string()),
+            pkce_request: Some(pkce_request.clone()),
+            redirect_uri: Url::parse("https://demo.example.com/oauth2/result").unwrap(),
+            scope: btreeset![OAUTH2_SCOPE_OPENID.to_string()],
+            nonce: None,
+            oidc_ext: Default::default(),
+            max_age: None,
+            authorization_details: None,
+            unknown_keys: Default::default(),
+        };
+
+        assert!(
+            idms_prox_read
+                .check_oauth2_authorisation(Some(&ident), &auth_req, ct)
+                .unwrap_err()
+                == Oauth2Error::UnsupportedResponseType
+        );
+
+        // * No pkce in pkce enforced mode.
+        let auth_req = AuthorisationRequest {
+            response_type: ResponseType::Code,
+            response_mode: None,
+            client_id: "test_resource_server".to_string(),
+            state: Some("123".to_string()),
+            pkce_request: None,
+            redirect_uri: Url::parse("https://demo.example.com/oauth2/result").unwrap(),
+            scope: btreeset![OAUTH2_SCOPE_OPENID.to_string()],
+            nonce: None,
+            oidc_ext: Default::default(),
+            max_age: None,
+            authorization_details: None,
+            unknown_keys: Default::default(),
+        };
+
+        assert!(
+            idms_prox_read
+                .check_oauth2_authorisation(Some(&ident), &auth_req, ct)
+                .unwrap_err()
+                == Oauth2Error::InvalidRequest
+        );
+
+        //  * invalid rs name
+        let auth_req = AuthorisationRequest {
+            response_type: ResponseType::Code,
+            response_mode: None,
+            client_id: "NOT A REAL RESOURCE SERVER".to_string(),
+            state: Some("123".to_string()),
+            pkce_request: Some(pkce_request.clone()),
+            redirect_uri: Url::parse("https://demo.example.com/oauth2/result").unwrap(),
+            scope: btreeset![OAUTH2_SCOPE_OPENID.to_string()],
+            nonce: None,
+            oidc_ext: Default::default(),
+            max_age: None,
+            authorization_details: None,
+            unknown_keys: Default::default(),
+        };
+
+        assert!(
+            idms_prox_read
+                .check_oauth2_authorisation(Some(&ident), &auth_req, ct)
+                .unwrap_err()
+                == Oauth2Error::InvalidClientId
+        );
+
+        //  * mismatched origin in the redirect.
+        let auth_req = AuthorisationRequest {
+            response_type: ResponseType::Code,
+            response_mode: None,
+            client_id: "test_resource_server".to_string(),
+            state: Some("123".to_string()),
+            pkce_request: Some(pkce_request.clone()),
+            redirect_uri: Url::parse("https://totes.not.sus.org/oauth2/result").unwrap(),
+            scope: btreeset![OAUTH2_SCOPE_OPENID.to_string()],
+            nonce: None,
+            oidc_ext: Default::default(),
+            max_age: None,
+            authorization_details: None,
+            unknown_keys: Default::default(),
+        };
+
+        assert!(
+            idms_prox_read
+                .check_oauth2_authorisation(Some(&ident), &auth_req, ct)
+                .unwrap_err()
+                == Oauth2Error::InvalidOrigin
+        );
+
+        // * invalid uri in the redirect
+        let auth_req = AuthorisationRequest {
+            response_type: ResponseType::Code,
+            response_mode: None,
+            client_id: "test_resource_server".to_string(),
+            state: Some("123".to_string()),
+            pkce_request: Some(pkce_request.clone()),
+            redirect_uri: Url::parse("https://demo.example.com/oauth2/wrong_place").unwrap(),
+            scope: btreeset![OAUTH2_SCOPE_OPENID.to_string()],
+            nonce: None,
+            oidc_ext: Default::default(),
+            max_age: None,
+            authorization_details: None,
+            unknown_keys: Default::default(),
+        };
+
+        assert!(
+            idms_prox_read
+                .check_oauth2_authorisation(Some(&ident), &auth_req, ct)
+                .unwrap_err()
+                == Oauth2Error::InvalidOrigin
+        );
+
+        // * invalid uri (doesn't match query params)
+        let auth_req = AuthorisationRequest {
+            response_type: ResponseType::Code,
+            response_mode: None,
+            client_id: "test_resource_server".to_string(),
+            state: Some("123".to_string()),
+            pkce_request: Some(pkce_request.clone()),
+            redirect_uri: Url::parse("https://portal.example.com/?custom=foo&too=many").unwrap(),
+            scope: btreeset![OAUTH2_SCOPE_OPENID.to_string()],
+            nonce: None,
+            oidc_ext: Default::default(),
+            max_age: None,
+            authorization_details: None,
+            unknown_keys: Default::default(),
+        };
+
+        assert!(
+            idms_prox_read
+                .check_oauth2_authorisation(Some(&ident), &auth_req, ct)
+                .unwrap_err()
+                == Oauth2Error::InvalidOrigin
+        );
+
+        let auth_req = AuthorisationRequest {
+            response_type: ResponseType::Code,
+            response_mode: None,
+            client_id: "test_resource_server".to_string(),
+            state: Some("123".to_string()),
+            pkce_request: Some(pkce_request.clone()),
+            redirect_uri: Url::parse("https://portal.example.com").unwrap(),
+            scope: btreeset![OAUTH2_SCOPE_OPENID.to_string()],
+            nonce: None,
+            oidc_ext: Default::default(),
+            max_age: None,
+            authorization_details: None,
+            unknown_keys: Default::default(),
+        };
+
+        assert!(
+            idms_prox_read
+                .check_oauth2_authorisation(Some(&ident), &auth_req, ct)
+                .unwrap_err()
+                == Oauth2Error::InvalidOrigin
+        );
+
+        let auth_req = AuthorisationRequest {
+            response_type: ResponseType::Code,
+            response_mode: None,
+            client_id: "test_resource_server".to_string(),
+            state: Some("123".to_string()),
+            pkce_request: Some(pkce_request.clone()),
+            redirect_uri: Url::parse("https://portal.example.com/?wrong=queryparam").unwrap(),
+            scope: btreeset![OAUTH2_SCOPE_OPENID.to_string()],
+            nonce: None,
+            oidc_ext: Default::default(),
+            max_age: None,
+            authorization_details: None,
+            unknown_keys: Default::default(),
+        };
+
+        assert!(
+            idms_prox_read
+                .check_oauth2_authorisation(Some(&ident), &auth_req, ct)
+                .unwrap_err()
+                == Oauth2Error::InvalidOrigin
+        );
+
+        // Not Authenticated
+        let auth_req = AuthorisationRequest {
+            response_type: ResponseType::Code,
+            response_mode: None,
+            client_id: "test_resource_server".to_string(),
+            state: Some("123".to_string()),
+            pkce_request: Some(pkce_request.clone()),
+            redirect_uri: Url::parse("https://demo.example.com/oauth2/result").unwrap(),
+            scope: btreeset![OAUTH2_SCOPE_OPENID.to_string()],
+            nonce: None,
+            oidc_ext: Default::default(),
+            max_age: None,
+            authorization_details: None,
+            unknown_keys: Default::default(),
+        };
+
+        let req = idms_prox_read
+            .check_oauth2_authorisation(None, &auth_req, ct)
+            .unwrap();
+
+        assert!(matches!(
+            req,
+            AuthoriseResponse::AuthenticationRequired { .. }
+        ));
+
+        // Requested scope is not available
+        let auth_req = AuthorisationRequest {
+            response_type: ResponseType::Code,
+            response_mode: None,
+            client_id: "test_resource_server".to_string(),
+            state: Some("123".to_string()),
+            pkce_request: Some(pkce_request.clone()),
+            redirect_uri: Url::parse("https://demo.example.com/oauth2/result").unwrap(),
+            scope: btreeset!["invalid_scope".to_string(), "read".to_string()],
+            nonce: None,
+            oidc_ext: Default::default(),
+            max_age: None,
+            authorization_details: None,
+            unknown_keys: Default::default(),
+        };
+
+        assert!(
+            idms_prox_read
+                .check_oauth2_authorisation(Some(&ident), &auth_req, ct)
+                .unwrap_err()
+                == Oauth2Error::AccessDenied
+        );
+
+        // Not a member of the group.
+        let auth_req = AuthorisationRequest {
+            response_type: ResponseType::Code,
+            response_mode: None,
+            client_id: "test_resource_server".to_string(),
+            state: Some("123".to_string()),
+            pkce_request: Some(pkce_request.clone()),
+            redirect_uri: Url::parse("https://demo.example.com/oauth2/result").unwrap(),
+            scope: btreeset!["openid".to_string(), "read".to_string()],
+            nonce: None,
+            oidc_ext: Default::default(),
+            max_age: None,
+            authorization_details: None,
+            unknown_keys: Default::default(),
+        };
+
+        assert!(
+            idms_prox_read
+                .check_oauth2_authorisation(Some(&idm_admin_ident), &auth_req, ct)
+                .unwrap_err()
+                == Oauth2Error::AccessDenied
+        );
+
+        // Deny Anonymous auth methods
+        let auth_req = AuthorisationRequest {
+            response_type: ResponseType::Code,
+            response_mode: None,
+            client_id: "test_resource_server".to_string(),
+            state: Some("123".to_string()),
+            pkce_request: Some(pkce_request),
+            redirect_uri: Url::parse("https://demo.example.com/oauth2/result").unwrap(),
+            scope: btreeset!["openid".to_string(), "read".to_string()],
+            nonce: None,
+            oidc_ext: Default::default(),
+            max_age: None,
+            authorization_details: None,
+            unknown_keys: Default::default(),
+        };
+
+        assert!(
+            idms_prox_read
+                .check_oauth2_authorisation(Some(&anon_ident), &auth_req, ct)
+                .unwrap_err()
+                == Oauth2Error::AccessDenied
+        );
+    }
+
+    #[idm_test]
+    async fn test_idm_oauth2_invalid_authorisation_permit_requests(
+        idms: &IdmServer,
+        _idms_delayed: &mut IdmServerDelayed,
+    ) {
+        // Test invalid OAuth2 authorisation states/requests.
+        let ct = Duration::from_secs(TEST_CURRENT_TIME);
+        let (_secret, uat, ident, _) =
+            setup_oauth2_resource_server_basic(idms, ct, true, false, false).await;
+
+        let mut idms_prox_write = idms.proxy_write(ct).await.unwrap();
+
+        let mut uat_wrong_session_id = uat.clone();
+        uat_wrong_session_id.session_id = uuid::Uuid::new_v4();
+        let ident_wrong_session_id = idms_prox_write
+            .process_uat_to_identity(&uat_wrong_session_id, ct, Source::Internal)
+            .expect("Unable to process uat");
+
+        let account = idms_prox_write
+            .target_to_account(UUID_IDM_ADMIN)
+            .expect("account must exist");
+        let session_id = uuid::Uuid::new_v4();
+        let uat2 = account
+            .to_userauthtoken(
+                session_id,
+                SessionScope::ReadWrite,
+                ct,
+                &ResolvedAccountPolicy::test_policy(),
+            )
+            .expect("Unable to create uat");
+        let ident2 = idms_prox_write
+            .process_uat_to_identity(&uat2, ct, Source::Internal)
+            .expect("Unable to process uat");
+
+        assert!(idms_prox_write.commit().is_ok());
+
+        // Now start the test
+
+        let idms_prox_read = idms.proxy_read().await.unwrap();
+
+        let pkce_secret = PkceS256Secret::default();
+
+        let consent_request = good_authorisation_request!(
+            idms_prox_read,
+            &ident,
+            ct,
+            pkce_secret.to_request(),
+            OAUTH2_SCOPE_OPENID.to_string()
+        );
+
+        let AuthoriseResponse::ConsentRequested { consent_token, .. } = consent_request else {
+            unreachable!();
+        };
+
+        drop(idms_prox_read);
+        let mut idms_prox_write = idms.proxy_write(ct).await.unwrap();
+
+        // Invalid permits
+        //  * expired token, aka past ttl.
+        assert!(
+            idms_prox_write
+                .check_oauth2_authorise_permit(
+                    &ident,
+                    &consent_token,
+                    ct + Duration::from_secs(TOKEN_EXPIRE),
                 )
-                .expect("invalid oauthscope")
-            ),
-            (
-                Attribute::OAuth2RsSupScopeMap,
-                Value::new_oauthscopemap(
-                    UUID_IDM_ALL_ACCOUNTS,
-                    btreeset!["supplement".to_string()]
+                .unwrap_err()
+                == OperationError::CryptographyError
+        );
+
+        //  * incorrect ident
+        // We get another uat, but for a different user, and we'll introduce these
+        // inconsistently to cause confusion.
+
+        assert!(
+            idms_prox_write
+                .check_oauth2_authorise_permit(&ident2, &consent_token, ct,)
+                .unwrap_err()
+                == OperationError::InvalidSessionState
+        );
+
+        //  * incorrect session id
+        assert!(
+            idms_prox_write
+                .check_oauth2_authorise_permit(&ident_wrong_session_id, &consent_token, ct,)
+                .unwrap_err()
+                == OperationError::InvalidSessionState
+        );
+
+        assert!(idms_prox_write.commit().is_ok());
+    }
+
+    #[idm_test]
+    async fn test_idm_oauth2_invalid_token_exchange_requests(
+        idms: &IdmServer,
+        _idms_delayed: &mut IdmServerDelayed,
+    ) {
+        let ct = Duration::from_secs(TEST_CURRENT_TIME);
+        let (secret, mut uat, ident, _) =
+            setup_oauth2_resource_server_basic(idms, ct, true, false, false).await;
+
+        // ⚠️  We set the uat expiry time to 5 seconds from TEST_CURRENT_TIME. This
+        // allows all our other tests to pass, but it means when we specifically put the
+        // clock forward a fraction, the fernet tokens are still valid, but the uat
+        // is not.
+        // IE
+        //   |---------------------|------------------|
+        //   TEST_CURRENT_TIME     UAT_EXPIRE         TOKEN_EXPIRE
+        //
+        // This lets us check a variety of time based cases.
+        uat.expiry = Some(
+            time::OffsetDateTime::UNIX_EPOCH
+                + Duration::from_secs(TEST_CURRENT_TIME + UAT_EXPIRE - 1),
+        );
+
+        let idms_prox_read = idms.proxy_read().await.unwrap();
+
+        // == Setup the authorisation request
+        let pkce_secret = PkceS256Secret::default();
+
+        let consent_request = good_authorisation_request!(
+            idms_prox_read,
+            &ident,
+            ct,
+            pkce_secret.to_request(),
+            OAUTH2_SCOPE_OPENID.to_string()
+        );
+
+        let code_verifier = Some(pkce_secret.to_verifier());
+
+        let AuthoriseResponse::ConsentRequested { consent_token, .. } = consent_request else {
+            unreachable!();
+        };
+
+        drop(idms_prox_read);
+        let mut idms_prox_write = idms.proxy_write(ct).await.unwrap();
+
+        // == Manually submit the consent token to the permit for the permit_success
+        let permit_success = idms_prox_write
+            .check_oauth2_authorise_permit(&ident, &consent_token, ct)
+            .expect("Failed to perform OAuth2 permit");
+
+        // == Submit the token exchange code.
+
+        // Invalid token exchange
+        //  * invalid client_authz (not base64)
+        let token_req: AccessTokenRequest = GrantTypeReq::AuthorizationCode {
+            code: permit_success.code.clone(),
+            redirect_uri: Url::parse("https://demo.example.com/oauth2/result").unwrap(),
+            code_verifier: code_verifier.clone(),
+        }
+        .into();
+
+        let client_authz = ClientAuthInfo::from("not base64");
+
+        assert!(
+            idms_prox_write
+                .check_oauth2_token_exchange(&client_authz, &token_req, ct)
+                .unwrap_err()
+                == Oauth2Error::AuthenticationRequired
+        );
+
+        //  * doesn't have ':'
+        let client_authz =
+            general_purpose::STANDARD.encode(format!("test_resource_server {secret}"));
+        let client_authz = ClientAuthInfo::from(client_authz.as_str());
+
+        assert!(
+            idms_prox_write
+                .check_oauth2_token_exchange(&client_authz, &token_req, ct)
+                .unwrap_err()
+                == Oauth2Error::AuthenticationRequired
+        );
+
+        //  * invalid client_id
+        let client_authz = ClientAuthInfo::encode_basic("NOT A REAL SERVER", secret.as_str());
+
+        assert!(
+            idms_prox_write
+                .check_oauth2_token_exchange(&client_authz, &token_req, ct)
+                .unwrap_err()
+                == Oauth2Error::AuthenticationRequired
+        );
+
+        //  * valid client_id, but invalid secret
+        let client_authz = ClientAuthInfo::encode_basic("test_resource_server", "12345");
+
+        assert!(
+            idms_prox_write
+                .check_oauth2_token_exchange(&client_authz, &token_req, ct)
+                .unwrap_err()
+                == Oauth2Error::AuthenticationRequired
+        );
+
+        // ✅ Now the valid client_authz is in place.
+        let client_authz = ClientAuthInfo::encode_basic("test_resource_server", secret.as_str());
+
+        //  * expired exchange code (took too long)
+        assert!(
+            idms_prox_write
+                .check_oauth2_token_exchange(
+                    &client_authz,
+                    &token_req,
+                    ct + Duration::from_secs(TOKEN_EXPIRE)
                 )
-                .expect("invalid oauthscope")
-            ),
-            (
-                Attribute::OAuth2AllowInsecureClientDisablePkce,
-                Value::new_bool(!enable_pkce)
-            ),
-            (
-                Attribute::OAuth2JwtLegacyCryptoEnable,
-                Value::new_bool(enable_legacy_crypto)
-            ),
-            (
-                Attribute::OAuth2PreferShortUsername,
-                Value::new_bool(prefer_short_username)
-            )
+                .unwrap_err()
+                == Oauth2Error::InvalidRequest
+        );
+
+        /*
+        //  * incorrect grant_type
+        // No longer possible due to changes in json api
+        let token_req: AccessTokenRequest = GrantTypeReq::AuthorizationCode {
+            grant_type: "INCORRECT GRANT TYPE".to_string(),
+            code: permit_success.code.clone(),
+            redirect_uri: Url::parse("https://demo.example.com/oauth2/result").unwrap(),
+            client_id: None,
+            client_secret: None,
+            code_verifier: code_verifier.clone(),
+        };
+        assert!(
+            idms_prox_read
+                .check_oauth2_token_exchange(client_authz.as_deref(), &token_req, ct)
+                .unwrap_err()
+                == Oauth2Error::InvalidRequest
+        );
+        */
+
+        //  * Incorrect redirect uri
+        let token_req: AccessTokenRequest = GrantTypeReq::AuthorizationCode {
+            code: permit_success.code.clone(),
+            redirect_uri: Url::parse("https://totes.not.sus.org/oauth2/result").unwrap(),
+            code_verifier: code_verifier.clone(),
+        }
+        .into();
+        assert!(
+            idms_prox_write
+                .check_oauth2_token_exchange(&client_authz, &token_req, ct)
+                .unwrap_err()
+                == Oauth2Error::InvalidOrigin
+        );
+
+        //  * code verifier incorrect
+        let token_req: AccessTokenRequest = GrantTypeReq::AuthorizationCode {
+            code: permit_success.code,
+            redirect_uri: Url::parse("https://demo.example.com/oauth2/result").unwrap(),
+            code_verifier: Some("12345".to_string()),
+        }
+        .into();
+        assert!(
+            idms_prox_write
+                .check_oauth2_token_exchange(&client_authz, &token_req, ct)
+                .unwrap_err()
+                == Oauth2Error::InvalidRequest
         );
 
-        let ce = CreateEvent::new_internal(vec![entry_rs, entry_group, E_TESTPERSON_1.clone()]);
-        assert!(idms_prox_write.qs_write.create(&ce).is_ok());
+        assert!(idms_prox_write.commit().is_ok());
+    }
+
+    #[idm_test]
+    async fn test_idm_oauth2_supplemental_origin_redirect(
+        idms: &IdmServer,
+        _idms_delayed: &mut IdmServerDelayed,
+    ) {
+        let ct = Duration::from_secs(TEST_CURRENT_TIME);
+        let (secret, uat, ident, _) =
+            setup_oauth2_resource_server_basic(idms, ct, true, false, false).await;
+
+        let idms_prox_read = idms.proxy_read().await.unwrap();
+
+        // == Setup the authorisation request
+        let pkce_secret = PkceS256Secret::default();
+
+        let redirect_uri = Url::parse("https://portal.example.com/?custom=foo").unwrap();
+
+        let auth_req = AuthorisationRequest {
+            response_type: ResponseType::Code,
+            response_mode: None,
+            client_id: "test_resource_server".to_string(),
+            state: None,
+            pkce_request: Some(pkce_secret.to_request()),
+            redirect_uri: redirect_uri.clone(),
+            scope: btreeset![OAUTH2_SCOPE_GROUPS.to_string()],
+            nonce: Some("abcdef".to_string()),
+            oidc_ext: Default::default(),
+            max_age: None,
+            authorization_details: None,
+            unknown_keys: Default::default(),
+        };
+
+        let consent_request = idms_prox_read
+            .check_oauth2_authorisation(Some(&ident), &auth_req, ct)
+            .expect("OAuth2 authorisation failed");
 
-        let entry = idms_prox_write
-            .qs_write
-            .internal_search_uuid(rs_uuid)
-            .expect("Failed to retrieve OAuth2 resource entry ");
-        let secret = entry
-            .get_ava_single_secret(Attribute::OAuth2RsBasicSecret)
-            .map(str::to_string)
-            .expect("No oauth2_rs_basic_secret found");
+        trace!(?consent_request);
 
-        // Setup the uat we'll be using - note for these tests they *require*
-        // the parent session to be valid and present!
-        let session_id = uuid::Uuid::new_v4();
+        // Should be in the consent phase;
+        let AuthoriseResponse::ConsentRequested { consent_token, .. } = consent_request else {
+            unreachable!();
+        };
 
-        let account = idms_prox_write
-            .target_to_account(UUID_TESTPERSON_1)
-            .expect("account must exist");
+        // == Manually submit the consent token to the permit for the permit_success
+        drop(idms_prox_read);
+        let mut idms_prox_write = idms.proxy_write(ct).await.unwrap();
 
-        let uat = account
-            .to_userauthtoken(
-                session_id,
-                SessionScope::ReadWrite,
-                ct,
-                &ResolvedAccountPolicy::test_policy(),
-            )
-            .expect("Unable to create uat");
+        let permit_success = idms_prox_write
+            .check_oauth2_authorise_permit(&ident, &consent_token, ct)
+            .expect("Failed to perform OAuth2 permit");
 
-        // Need the uat first for expiry.
-        let state = uat
-            .expiry
-            .map(SessionState::ExpiresAt)
-            .unwrap_or(SessionState::NeverExpires);
+        // Check we are reflecting the CSRF properly.
+        assert_eq!(permit_success.state.as_deref(), None);
 
-        let p = CryptoPolicy::minimum();
-        let cred =
-            Credential::new_password_only(&p, "test_password", OffsetDateTime::UNIX_EPOCH).unwrap();
-        let cred_id = cred.uuid;
+        // Assert we followed the redirect uri including the query elements
+        // we have in the url.
+        let permit_redirect_uri = permit_success.build_redirect_uri();
 
-        let session = Value::Session(
-            session_id,
-            crate::value::Session {
-                label: "label".to_string(),
-                state,
-                issued_at: time::OffsetDateTime::UNIX_EPOCH + ct,
-                issued_by: IdentityId::Internal(UUID_SYSTEM),
-                cred_id,
-                scope: SessionScope::ReadWrite,
-                type_: AuthType::Passkey,
-                ext_metadata: Default::default(),
+        assert_eq!(permit_redirect_uri.origin(), redirect_uri.origin());
+        assert_eq!(permit_redirect_uri.path(), redirect_uri.path());
+        let query = BTreeMap::from_iter(permit_redirect_uri.query_pairs().into_owned());
+        // Assert the query pair wasn't changed
+        assert_eq!(query.get("custom").map(|s| s.as_str()), Some("foo"));
+
+        // == Submit the token exchange code.
+        // ⚠️  This is where we submit a different origin!
+        let token_req = AccessTokenRequest {
+            grant_type: GrantTypeReq::AuthorizationCode {
+                code: permit_success.code,
+                redirect_uri,
+                code_verifier: Some(pkce_secret.to_verifier()),
             },
-        );
 
-        // Mod the user
-        let modlist = ModifyList::new_list(vec![
-            Modify::Present(Attribute::UserAuthTokenSession, session),
-            Modify::Present(
-                Attribute::PrimaryCredential,
-                Value::Cred("primary".to_string(), cred),
-            ),
-        ]);
+            client_post_auth: ClientPostAuth {
+                client_id: Some("test_resource_server".to_string()),
+                client_secret: Some(secret.clone()),
+                ..Default::default()
+            },
+        };
 
-        idms_prox_write
-            .qs_write
-            .internal_modify(
-                &filter!(f_eq(Attribute::Uuid, PartialValue::Uuid(UUID_TESTPERSON_1))),
-                &modlist,
-            )
-            .expect("Failed to modify user");
+        let token_response = idms_prox_write
+            .check_oauth2_token_exchange(&ClientAuthInfo::none(), &token_req, ct)
+            .expect("Failed to perform OAuth2 token exchange");
 
-        let ident = idms_prox_write
+        // 🎉 We got a token! In the future we can then check introspection from this point.
+        assert_eq!(token_response.token_type, AccessTokenType::Bearer);
+
+        assert!(idms_prox_write.commit().is_ok());
+
+        // ============================================================================
+        // Now repeat the test with the app url.
+
+        let mut idms_prox_read = idms.proxy_read().await.unwrap();
+
+        // Reload the ident since it pins an entry in memory.
+        let ident = idms_prox_read
             .process_uat_to_identity(&uat, ct, Source::Internal)
             .expect("Unable to process uat");
 
-        idms_prox_write.commit().expect("failed to commit");
+        let pkce_secret = PkceS256Secret::default();
 
-        (secret, uat, ident, rs_uuid)
-    }
+        let auth_req = AuthorisationRequest {
+            response_type: ResponseType::Code,
+            response_mode: None,
+            client_id: "test_resource_server".to_string(),
+            state: Some("123".to_string()),
+            pkce_request: Some(pkce_secret.to_request()),
+            redirect_uri: Url::parse("app://cheese").unwrap(),
+            scope: btreeset![OAUTH2_SCOPE_GROUPS.to_string()],
+            nonce: Some("abcdef".to_string()),
+            oidc_ext: Default::default(),
+            max_age: None,
+            authorization_details: None,
+            unknown_keys: Default::default(),
+        };
 
-    async fn setup_oauth2_resource_server_public(
-        idms: &IdmServer,
-        ct: Duration,
-    ) -> (UserAuthToken, Identity, Uuid) {
+        let consent_request = idms_prox_read
+            .check_oauth2_authorisation(Some(&ident), &auth_req, ct)
+            .expect("OAuth2 authorisation failed");
+
+        trace!(?consent_request);
+
+        let AuthoriseResponse::Permitted(permit_success) = consent_request else {
+            unreachable!();
+        };
+
+        // == Manually submit the consent token to the permit for the permit_success
+        // Check we are reflecting the CSRF properly.
+        assert_eq!(permit_success.state.as_deref(), Some("123"));
+
+        // == Submit the token exchange code.
+        // ⚠️  This is where we submit a different origin!
+        let token_req = AccessTokenRequest {
+            grant_type: GrantTypeReq::AuthorizationCode {
+                code: permit_success.code,
+                redirect_uri: Url::parse("app://cheese").unwrap(),
+                code_verifier: Some(pkce_secret.to_verifier()),
+            },
+
+            client_post_auth: ClientPostAuth {
+                client_id: Some("test_resource_server".to_string()),
+                client_secret: Some(secret),
+                ..Default::default()
+            },
+        };
+
+        drop(idms_prox_read);
         let mut idms_prox_write = idms.proxy_write(ct).await.unwrap();
 
-        let rs_uuid = Uuid::new_v4();
+        let token_response = idms_prox_write
+            .check_oauth2_token_exchange(&ClientAuthInfo::none(), &token_req, ct)
+            .expect("Failed to perform OAuth2 token exchange");
 
-        let entry_group: Entry<EntryInit, EntryNew> = entry_init!(
-            (Attribute::Class, EntryClass::Group.to_value()),
-            (Attribute::Name, Value::new_iname("testgroup")),
-            (Attribute::Description, Value::new_utf8s("testgroup")),
-            (Attribute::Uuid, Value::Uuid(UUID_TESTGROUP)),
-            (Attribute::Member, Value::Refer(UUID_TESTPERSON_1),)
-        );
+        // 🎉 We got a token! In the future we can then check introspection from this point.
+        assert_eq!(token_response.token_type, AccessTokenType::Bearer);
+    }
 
-        let entry_rs: Entry<EntryInit, EntryNew> = entry_init!(
-            (Attribute::Class, EntryClass::Object.to_value()),
-            (Attribute::Class, EntryClass::Account.to_value()),
-            (
-                Attribute::Class,
-                EntryClass::OAuth2ResourceServer.to_value()
-            ),
-            (
-                Attribute::Class,
-                EntryClass::OAuth2ResourceServerPublic.to_value()
-            ),
-            (Attribute::Uuid, Value::Uuid(rs_uuid)),
-            (Attribute::Name, Value::new_iname("test_resource_server")),
-            (
-                Attribute::DisplayName,
-                Value::new_utf8s("test_resource_server")
-            ),
-            (
-                Attribute::OAuth2RsOriginLanding,
-                Value::new_url_s("https://demo.example.com").unwrap()
-            ),
-            (
-                Attribute::OAuth2RsOrigin,
-                Value::new_url_s("https://demo.example.com/oauth2/result").unwrap()
-            ),
-            // System admins
-            (
-                Attribute::OAuth2RsScopeMap,
-                Value::new_oauthscopemap(
-                    UUID_TESTGROUP,
-                    btreeset![OAUTH2_SCOPE_GROUPS.to_string()]
-                )
-                .expect("invalid oauthscope")
-            ),
-            (
-                Attribute::OAuth2RsScopeMap,
-                Value::new_oauthscopemap(
-                    UUID_IDM_ALL_ACCOUNTS,
-                    btreeset![OAUTH2_SCOPE_OPENID.to_string()]
-                )
-                .expect("invalid oauthscope")
-            ),
-            (
-                Attribute::OAuth2RsSupScopeMap,
-                Value::new_oauthscopemap(
-                    UUID_IDM_ALL_ACCOUNTS,
-                    btreeset!["supplement".to_string()]
-                )
-                .expect("invalid oauthscope")
-            )
+    #[idm_test]
+    async fn test_idm_oauth2_token_introspect(
+        idms: &IdmServer,
+        _idms_delayed: &mut IdmServerDelayed,
+    ) {
+        let ct = Duration::from_secs(TEST_CURRENT_TIME);
+        let (secret, _uat, ident, _) =
+            setup_oauth2_resource_server_basic(idms, ct, true, false, false).await;
+        let client_authz = ClientAuthInfo::encode_basic("test_resource_server", secret.as_str());
+
+        let idms_prox_read = idms.proxy_read().await.unwrap();
+
+        // == Setup the authorisation request
+        let pkce_secret = PkceS256Secret::default();
+        let consent_request = good_authorisation_request!(
+            idms_prox_read,
+            &ident,
+            ct,
+            pkce_secret.to_request(),
+            OAUTH2_SCOPE_OPENID.to_string()
         );
-        let ce = CreateEvent::new_internal(vec![entry_rs, entry_group, E_TESTPERSON_1.clone()]);
-        assert!(idms_prox_write.qs_write.create(&ce).is_ok());
 
-        // Setup the uat we'll be using - note for these tests they *require*
-        // the parent session to be valid and present!
+        let AuthoriseResponse::ConsentRequested { consent_token, .. } = consent_request else {
+            unreachable!();
+        };
 
-        let session_id = uuid::Uuid::new_v4();
+        // == Manually submit the consent token to the permit for the permit_success
+        drop(idms_prox_read);
+        let mut idms_prox_write = idms.proxy_write(ct).await.unwrap();
 
-        let account = idms_prox_write
-            .target_to_account(UUID_TESTPERSON_1)
-            .expect("account must exist");
-        let uat = account
-            .to_userauthtoken(
-                session_id,
-                SessionScope::ReadWrite,
-                ct,
-                &ResolvedAccountPolicy::test_policy(),
-            )
-            .expect("Unable to create uat");
+        let permit_success = idms_prox_write
+            .check_oauth2_authorise_permit(&ident, &consent_token, ct)
+            .expect("Failed to perform OAuth2 permit");
 
-        // Need the uat first for expiry.
-        let state = uat
-            .expiry
-            .map(SessionState::ExpiresAt)
-            .unwrap_or(SessionState::NeverExpires);
+        let token_req: AccessTokenRequest = GrantTypeReq::AuthorizationCode {
+            code: permit_success.code,
+            redirect_uri: Url::parse("https://demo.example.com/oauth2/result").unwrap(),
+            code_verifier: Some(pkce_secret.to_verifier()),
+        }
+        .into();
+        let oauth2_token = idms_prox_write
+            .check_oauth2_token_exchange(&client_authz, &token_req, ct)
+            .expect("Unable to exchange for OAuth2 token");
 
-        let p = CryptoPolicy::minimum();
-        let cred =
-            Credential::new_password_only(&p, "test_password", OffsetDateTime::UNIX_EPOCH).unwrap();
-        let cred_id = cred.uuid;
+        assert!(idms_prox_write.commit().is_ok());
 
-        let session = Value::Session(
-            session_id,
-            crate::value::Session {
-                label: "label".to_string(),
-                state,
-                issued_at: time::OffsetDateTime::UNIX_EPOCH + ct,
-                issued_by: IdentityId::Internal(UUID_SYSTEM),
-                cred_id,
-                scope: SessionScope::ReadWrite,
-                type_: AuthType::Passkey,
-                ext_metadata: Default::default(),
-            },
-        );
+        // Okay, now we have the token, we can check it works with introspect.
+        let mut idms_prox_read = idms.proxy_read().await.unwrap();
 
-        // Mod the user
-        let modlist = ModifyList::new_list(vec![
-            Modify::Present(Attribute::UserAuthTokenSession, session),
-            Modify::Present(
-                Attribute::PrimaryCredential,
-                Value::Cred("primary".to_string(), cred),
-            ),
-        ]);
+        let intr_request = AccessTokenIntrospectRequest {
+            token: oauth2_token.access_token,
+            token_type_hint: None,
+            client_post_auth: ClientPostAuth::default(),
+        };
+        let intr_response = idms_prox_read
+            .check_oauth2_token_introspect(&client_authz, &intr_request, ct)
+            .expect("Failed to inspect token");
 
-        idms_prox_write
-            .qs_write
-            .internal_modify(
-                &filter!(f_eq(Attribute::Uuid, PartialValue::Uuid(UUID_TESTPERSON_1))),
-                &modlist,
-            )
-            .expect("Failed to modify user");
+        eprintln!("👉  {intr_response:?}");
+        assert!(intr_response.active);
+        assert_eq!(
+            intr_response.scope,
+            btreeset!["openid".to_string(), "supplement".to_string()]
+        );
+        assert_eq!(
+            intr_response.client_id.as_deref(),
+            Some("test_resource_server")
+        );
+        assert_eq!(
+            intr_response.username.as_deref(),
+            Some("testperson1@example.com")
+        );
+        assert_eq!(intr_response.token_type, Some(AccessTokenType::Bearer));
+        assert_eq!(intr_response.iat, Some(ct.as_secs() as i64));
+        assert_eq!(intr_response.nbf, Some(ct.as_secs() as i64));
 
-        let ident = idms_prox_write
-            .process_uat_to_identity(&uat, ct, Source::Internal)
-            .expect("Unable to process uat");
+        drop(idms_prox_read);
+        // start a write,
 
-        idms_prox_write.commit().expect("failed to commit");
+        let mut idms_prox_write = idms.proxy_write(ct).await.unwrap();
+        // Expire the account, should cause introspect to return inactive.
+        let v_expire = Value::new_datetime_epoch(Duration::from_secs(TEST_CURRENT_TIME - 1));
+        let me_inv_m = ModifyEvent::new_internal_invalid(
+            filter!(f_eq(Attribute::Uuid, PartialValue::Uuid(UUID_TESTPERSON_1))),
+            ModifyList::new_list(vec![Modify::Present(Attribute::AccountExpire, v_expire)]),
+        );
+        // go!
+        assert!(idms_prox_write.qs_write.modify(&me_inv_m).is_ok());
+        assert!(idms_prox_write.commit().is_ok());
 
-        (uat, ident, rs_uuid)
+        // start a new read
+        // check again.
+        let mut idms_prox_read = idms.proxy_read().await.unwrap();
+        let intr_response = idms_prox_read
+            .check_oauth2_token_introspect(&client_authz, &intr_request, ct)
+            .expect("Failed to inspect token");
+
+        assert!(!intr_response.active);
     }
 
-    /// Perform an oauth2 exchange, assuming that it will succeed.
-    async fn perform_oauth2_exchange(
+    #[idm_test]
+    async fn test_idm_oauth2_openid_end_session(
         idms: &IdmServer,
-        ident: &Identity,
-        ct: Duration,
-        client_authz: ClientAuthInfo,
-        scopes: String,
-    ) -> AccessTokenResponse {
+        _idms_delayed: &mut IdmServerDelayed,
+    ) {
+        use compact_jwt::Jws;
+
+        let ct = Duration::from_secs(TEST_CURRENT_TIME);
+        let (secret, uat, ident, _) =
+            setup_oauth2_resource_server_basic(idms, ct, true, false, false).await;
+        let client_authz = ClientAuthInfo::encode_basic("test_resource_server", secret.as_str());
+
         let idms_prox_read = idms.proxy_read().await.unwrap();
 
         let pkce_secret = PkceS256Secret::default();
 
         let consent_request = good_authorisation_request!(
             idms_prox_read,
-            ident,
+            &ident,
             ct,
             pkce_secret.to_request(),
-            scopes
+            OAUTH2_SCOPE_OPENID.to_string()
         );
 
         let AuthoriseResponse::ConsentRequested { consent_token, .. } = consent_request else {
             unreachable!();
         };
 
-        // == Manually submit the consent token to the permit for the permit_success
         drop(idms_prox_read);
         let mut idms_prox_write = idms.proxy_write(ct).await.unwrap();
 
         let permit_success = idms_prox_write
-            .check_oauth2_authorise_permit(ident, &consent_token, ct)
+            .check_oauth2_authorise_permit(&ident, &consent_token, ct)
             .expect("Failed to perform OAuth2 permit");
 
-        // == Submit the token exchange code.
         let token_req: AccessTokenRequest = GrantTypeReq::AuthorizationCode {
             code: permit_success.code,
             redirect_uri: Url::parse("https://demo.example.com/oauth2/result").unwrap(),
             code_verifier: Some(pkce_secret.to_verifier()),
         }
         .into();
+        let oauth2_token = idms_prox_write
+            .check_oauth2_token_exchange(&client_authz, &token_req, ct)
+            .expect("Unable to exchange for OAuth2 token");
+
+        assert!(idms_prox_write.commit().is_ok());
+
+        let id_token = oauth2_token
+            .id_token
+            .clone()
+            .expect("No id_token in response!");
+
+        // Without an id_token_hint nothing happens.
+        let mut idms_prox_write = idms.proxy_write(ct).await.unwrap();
+        let end_session_req = EndSessionRequest {
+            id_token_hint: None,
+            client_id: None,
+            post_logout_redirect_uri: None,
+            state: None,
+        };
+        let err = idms_prox_write
+            .oauth2_openid_end_session(
+                "test_resource_server",
+                ClientAuthInfo::from(Source::Internal),
+                &end_session_req,
+                ct,
+            )
+            .unwrap_err();
+        assert!(matches!(err, Oauth2Error::InvalidRequest));
+
+        // The post logout redirect must be registered to the client.
+        let end_session_req = EndSessionRequest {
+            id_token_hint: Some(id_token.clone()),
+            client_id: None,
+            post_logout_redirect_uri: Some(Url::parse("https://evil.example.com/").unwrap()),
+            state: None,
+        };
+        let err = idms_prox_write
+            .oauth2_openid_end_session(
+                "test_resource_server",
+                ClientAuthInfo::from(Source::Internal),
+                &end_session_req,
+                ct,
+            )
+            .unwrap_err();
+        assert!(matches!(err, Oauth2Error::InvalidOrigin));
+        drop(idms_prox_write);
+
+        // Our token is still valid.
+        let mut idms_prox_read = idms.proxy_read().await.unwrap();
+        let intr_request = AccessTokenIntrospectRequest {
+            token: oauth2_token.access_token.clone(),
+            token_type_hint: None,
+            client_post_auth: ClientPostAuth::default(),
+        };
+        let intr_response = idms_prox_read
+            .check_oauth2_token_introspect(&client_authz, &intr_request, ct)
+            .expect("Failed to inspect token");
+        assert!(intr_response.active);
+        drop(idms_prox_read);
+
+        // End the session, returning to the client with the state. The user agent has no
+        // session, so only the client's session is ended.
+        let mut idms_prox_write = idms.proxy_write(ct).await.unwrap();
+        let end_session_req = EndSessionRequest {
+            id_token_hint: Some(id_token),
+            client_id: Some("test_resource_server".to_string()),
+            post_logout_redirect_uri: Some(
+                Url::parse("https://demo.example.com/oauth2/result").unwrap(),
+            ),
+            state: Some("abcdef".to_string()),
+        };
+        let redirect_uri = idms_prox_write
+            .oauth2_openid_end_session(
+                "test_resource_server",
+                ClientAuthInfo::from(Source::Internal),
+                &end_session_req,
+                ct,
+            )
+            .expect("Failed to end session");
+        assert_eq!(
+            redirect_uri,
+            Some(Url::parse("https://demo.example.com/oauth2/result?state=abcdef").unwrap())
+        );
+        assert!(idms_prox_write.commit().is_ok());
+
+        let mut idms_prox_read = idms.proxy_read().await.unwrap();
+        let intr_response = idms_prox_read
+            .check_oauth2_token_introspect(&client_authz, &intr_request, ct)
+            .expect("Failed to inspect token");
+        assert!(!intr_response.active);
+
+        let entry = idms_prox_read
+            .qs_read
+            .internal_search_uuid(UUID_TESTPERSON_1)
+            .expect("Failed to search for account");
+        let uat_session = entry
+            .get_ava_as_session_map(Attribute::UserAuthTokenSession)
+            .and_then(|sessions| sessions.get(&uat.session_id))
+            .expect("Session not found");
+        assert!(matches!(uat_session.state, SessionState::ExpiresAt(_)));
+        drop(idms_prox_read);
+
+        // Issue two more sessions from the same Kanidm session.
+        let mut oauth2_tokens = Vec::new();
+        for _ in 0..2 {
+            let mut idms_prox_read = idms.proxy_read().await.unwrap();
+            // Reload the ident since it pins an entry in memory.
+            let ident = idms_prox_read
+                .process_uat_to_identity(&uat, ct, Source::Internal)
+                .expect("Unable to process uat");
+            let pkce_secret = PkceS256Secret::default();
+            let consent_request = good_authorisation_request!(
+                idms_prox_read,
+                &ident,
+                ct,
+                pkce_secret.to_request(),
+                OAUTH2_SCOPE_OPENID.to_string()
+            );
+            let AuthoriseResponse::Permitted(permit_success) = consent_request else {
+                unreachable!();
+            };
+            drop(idms_prox_read);
+
+            let mut idms_prox_write = idms.proxy_write(ct).await.unwrap();
+            let token_req: AccessTokenRequest = GrantTypeReq::AuthorizationCode {
+                code: permit_success.code,
+                redirect_uri: Url::parse("https://demo.example.com/oauth2/result").unwrap(),
+                code_verifier: Some(pkce_secret.to_verifier()),
+            }
+            .into();
+            oauth2_tokens.push(
+                idms_prox_write
+                    .check_oauth2_token_exchange(&client_authz, &token_req, ct)
+                    .expect("Unable to exchange for OAuth2 token"),
+            );
+            assert!(idms_prox_write.commit().is_ok());
+        }
+
+        // Ending the session from the user agent that holds the Kanidm session ends it, and
+        // every session issued from it.
+        let mut idms_prox_write = idms.proxy_write(ct).await.unwrap();
+        let uat_jwsc = idms_prox_write
+            .qs_write
+            .get_domain_key_object_handle()
+            .expect("Unable to get domain keys")
+            .jws_es256_sign(&Jws::into_json(&uat).unwrap(), ct)
+            .expect("Unable to sign uat");
+        let end_session_req = EndSessionRequest {
+            id_token_hint: oauth2_tokens[0].id_token.clone(),
+            client_id: None,
+            post_logout_redirect_uri: None,
+            state: None,
+        };
+        let redirect_uri = idms_prox_write
+            .oauth2_openid_end_session(
+                "test_resource_server",
+                ClientAuthInfo::from(uat_jwsc),
+                &end_session_req,
+                ct,
+            )
+            .expect("Failed to end session");
+        assert_eq!(redirect_uri, None);
+        assert!(idms_prox_write.commit().is_ok());
+
+        let mut idms_prox_read = idms.proxy_read().await.unwrap();
+        for oauth2_token in oauth2_tokens {
+            let intr_request = AccessTokenIntrospectRequest {
+                token: oauth2_token.access_token,
+                token_type_hint: None,
+                client_post_auth: ClientPostAuth::default(),
+            };
+            let intr_response = idms_prox_read
+                .check_oauth2_token_introspect(&client_authz, &intr_request, ct)
+                .expect("Failed to inspect token");
+            assert!(!intr_response.active);
+        }
+
+        let entry = idms_prox_read
+            .qs_read
+            .internal_search_uuid(UUID_TESTPERSON_1)
+            .expect("Failed to search for account");
+        let uat_session = entry
+            .get_ava_as_session_map(Attribute::UserAuthTokenSession)
+            .and_then(|sessions| sessions.get(&uat.session_id))
+            .expect("Session not found");
+        assert!(matches!(uat_session.state, SessionState::RevokedAt(_)));
+    }
+
+    #[idm_test]
+    async fn test_idm_oauth2_openid_backchannel_logout(
+        idms: &IdmServer,
+        idms_delayed: &mut IdmServerDelayed,
+    ) {
+        let ct = Duration::from_secs(TEST_CURRENT_TIME);
+        let (secret, _uat, ident, rs_uuid) =
+            setup_oauth2_resource_server_basic(idms, ct, true, false, false).await;
+
+        let backchannel_logout_uri =
+            Url::parse("https://demo.example.com/oauth2/backchannel_logout").unwrap();
+
+        let mut idms_prox_write = idms.proxy_write(ct).await.unwrap();
+        idms_prox_write
+            .qs_write
+            .internal_modify_uuid(
+                rs_uuid,
+                &ModifyList::new_purge_and_set(
+                    Attribute::OAuth2RsBackchannelLogoutUri,
+                    Value::Url(backchannel_logout_uri.clone()),
+                ),
+            )
+            .expect("Failed to set backchannel logout uri");
+        assert!(idms_prox_write.commit().is_ok());
+
+        let client_authz = ClientAuthInfo::encode_basic("test_resource_server", secret.as_str());
+        let oauth2_token = perform_oauth2_exchange(
+            idms,
+            &ident,
+            ct,
+            client_authz.clone(),
+            OAUTH2_SCOPE_OPENID.to_string(),
+        )
+        .await;
 
-        let token_response = idms_prox_write
-            .check_oauth2_token_exchange(&client_authz, &token_req, ct)
-            .expect("Failed to perform OAuth2 token exchange");
+        // The id token carries the sid that logout tokens will refer to.
+        let id_token = oauth2_token.id_token.expect("No id_token in response!");
+        let oidc = validate_id_token(idms, ct, &id_token).await;
+        let sid = oidc.claims.get("sid").expect("No sid in id_token");
+        assert_eq!(oidc.jti.as_ref(), sid.as_str().map(str::to_string).as_ref());
 
+        // Nothing is sent while the session remains valid.
+        idms_delayed.check_is_empty_or_panic();
+
+        let mut idms_prox_write = idms.proxy_write(ct).await.unwrap();
+        let revoke_request = TokenRevokeRequest {
+            token: oauth2_token.access_token.clone(),
+            token_type_hint: None,
+            client_post_auth: ClientPostAuth::default(),
+        };
+        idms_prox_write
+            .oauth2_token_revoke(&client_authz, &revoke_request, ct)
+            .expect("Failed to revoke token");
         assert!(idms_prox_write.commit().is_ok());
 
-        token_response
-    }
+        let Ok(DelayedAction::Oauth2BackchannelLogout(backchannel_logout)) =
+            idms_delayed.try_recv()
+        else {
+            unreachable!();
+        };
+        idms_delayed.check_is_empty_or_panic();
 
-    async fn validate_id_token(idms: &IdmServer, ct: Duration, id_token: &str) -> OidcToken {
-        let idms_prox_read = idms.proxy_read().await.unwrap();
+        assert_eq!(backchannel_logout.rs_uuid, rs_uuid);
+        assert_eq!(
+            backchannel_logout.backchannel_logout_uri,
+            backchannel_logout_uri
+        );
 
+        let idms_prox_read = idms.proxy_read().await.unwrap();
         let mut jwkset = idms_prox_read
             .oauth2_openid_publickey("test_resource_server")
             .expect("Failed to get public key");
         let public_jwk = jwkset.keys.pop().expect("no such jwk");
-
         let jws_validator =
             JwsEs256Verifier::try_from(&public_jwk).expect("failed to build validator");
 
-        let oidc_unverified = OidcUnverified::from_str(id_token).expect("Failed to parse id_token");
-
-        let iat = ct.as_secs() as i64;
-
-        jws_validator
-            .verify(&oidc_unverified)
+        let logout_token =
+            JwsCompact::from_str(&backchannel_logout.logout_token).expect("Invalid logout token");
+        let logout_token = jws_validator
+            .verify(&logout_token)
             .unwrap()
-            .verify_exp(iat)
-            .expect("Failed to verify oidc")
-    }
-
-    async fn setup_idm_admin(idms: &IdmServer, ct: Duration) -> (UserAuthToken, Identity) {
-        let mut idms_prox_write = idms.proxy_write(ct).await.unwrap();
-        let account = idms_prox_write
-            .target_to_account(UUID_IDM_ADMIN)
-            .expect("account must exist");
-        let session_id = uuid::Uuid::new_v4();
-        let uat = account
-            .to_userauthtoken(
-                session_id,
-                SessionScope::ReadWrite,
-                ct,
-                &ResolvedAccountPolicy::test_policy(),
-            )
-            .expect("Unable to create uat");
-        let ident = idms_prox_write
-            .process_uat_to_identity(&uat, ct, Source::Internal)
-            .expect("Unable to process uat");
-
-        idms_prox_write.commit().expect("failed to commit");
-
-        (uat, ident)
-    }
-
-    #[test]
-    fn oauth2_parse_basic_authz() {
-        let r1 = parse_basic_authz("czZCaGRSa3F0Mzo3RmpmcDBaQnIxS3REUmJuZlZkbUl3").unwrap();
-        assert_eq!(r1.client_id, "s6BhdRkqt3");
-        assert_eq!(r1.client_secret.as_deref(), Some("7Fjfp0ZBr1KtDRbnfVdmIw"));
-
-        // This contains: "my%2Did:Dei7thai1ahne4a" which has a url-encoded client-id
-        let r2 = parse_basic_authz("bXklMkRpZDpEZWk3dGhhaTFhaG5lNGE=").unwrap();
-        assert_eq!(r2.client_id, "my-id");
-        assert_eq!(r2.client_secret.as_deref(), Some("Dei7thai1ahne4a"));
+            .from_json::<OidcLogoutToken>()
+            .expect("Failed to access internals of the logout token");
+
+        assert_eq!(logout_token.iss, oidc.iss);
+        assert_eq!(logout_token.aud, "test_resource_server");
+        assert_eq!(logout_token.sub, UUID_TESTPERSON_1);
+        assert_eq!(Some(logout_token.sid.to_string()), oidc.jti);
+        assert_eq!(logout_token.sid, backchannel_logout.session_id);
+        assert!(logout_token
+            .events
+            .contains_key(OIDC_BACKCHANNEL_LOGOUT_EVENT));
+        assert!(logout_token.exp > logout_token.iat);
     }
 
     #[idm_test]
-    async fn test_idm_oauth2_basic_function(
-        idms: &IdmServer,
-        _idms_delayed: &mut IdmServerDelayed,
-    ) {
+    async fn test_idm_oauth2_token_revoke(idms: &IdmServer, _idms_delayed: &mut IdmServerDelayed) {
+        // First, setup to get a token.
         let ct = Duration::from_secs(TEST_CURRENT_TIME);
         let (secret, _uat, ident, _) =
             setup_oauth2_resource_server_basic(idms, ct, true, false, false).await;
+        let client_authz = ClientAuthInfo::encode_basic("test_resource_server", secret.as_str());
 
         let idms_prox_read = idms.proxy_read().await.unwrap();
 
@@ -3903,7 +7892,6 @@ mod tests {
             OAUTH2_SCOPE_OPENID.to_string()
         );
 
-        // Should be in the consent phase;
         let AuthoriseResponse::ConsentRequested { consent_token, .. } = consent_request else {
             unreachable!();
         };
@@ -3916,680 +7904,745 @@ mod tests {
             .check_oauth2_authorise_permit(&ident, &consent_token, ct)
             .expect("Failed to perform OAuth2 permit");
 
-        // Check we are reflecting the CSRF properly.
-        assert_eq!(permit_success.state.as_deref(), Some("123"));
-
-        // == Submit the token exchange code.
-
-        let token_req = AccessTokenRequest {
-            grant_type: GrantTypeReq::AuthorizationCode {
-                code: permit_success.code,
-                redirect_uri: Url::parse("https://demo.example.com/oauth2/result").unwrap(),
-                code_verifier: Some(pkce_secret.to_verifier()),
-            },
-            client_post_auth: ClientPostAuth {
-                client_id: Some("test_resource_server".to_string()),
-                client_secret: Some(secret),
-            },
-        };
-
-        let token_response = idms_prox_write
-            .check_oauth2_token_exchange(&ClientAuthInfo::none(), &token_req, ct)
-            .expect("Failed to perform OAuth2 token exchange");
-
-        // 🎉 We got a token! In the future we can then check introspection from this point.
-        assert_eq!(token_response.token_type, AccessTokenType::Bearer);
+        // Assert that the consent was submitted
+        let token_req: AccessTokenRequest = GrantTypeReq::AuthorizationCode {
+            code: permit_success.code,
+            redirect_uri: Url::parse("https://demo.example.com/oauth2/result").unwrap(),
+            code_verifier: Some(pkce_secret.to_verifier()),
+        }
+        .into();
+        let oauth2_token = idms_prox_write
+            .check_oauth2_token_exchange(&client_authz, &token_req, ct)
+            .expect("Unable to exchange for OAuth2 token");
 
         assert!(idms_prox_write.commit().is_ok());
-    }
 
-    #[idm_test]
-    async fn test_idm_oauth2_public_function(
-        idms: &IdmServer,
-        _idms_delayed: &mut IdmServerDelayed,
-    ) {
-        let ct = Duration::from_secs(TEST_CURRENT_TIME);
-        let (_uat, ident, _) = setup_oauth2_resource_server_public(idms, ct).await;
+        // Okay, now we have the token, we can check behaviours with the revoke interface.
 
-        let idms_prox_read = idms.proxy_read().await.unwrap();
+        // First, assert it is valid, similar to the introspect api.
+        let mut idms_prox_read = idms.proxy_read().await.unwrap();
+        let intr_request = AccessTokenIntrospectRequest {
+            token: oauth2_token.access_token.clone(),
+            token_type_hint: None,
+            client_post_auth: ClientPostAuth::default(),
+        };
+        let intr_response = idms_prox_read
+            .check_oauth2_token_introspect(&client_authz, &intr_request, ct)
+            .expect("Failed to inspect token");
+        eprintln!("👉  {intr_response:?}");
+        assert!(intr_response.active);
+        drop(idms_prox_read);
 
-        // Get an ident/uat for now.
+        // First, the revoke needs basic auth. Provide incorrect auth, and we fail.
+        let mut idms_prox_write = idms.proxy_write(ct).await.unwrap();
 
-        // == Setup the authorisation request
-        let pkce_secret = PkceS256Secret::default();
+        let bad_client_authz = ClientAuthInfo::encode_basic("test_resource_server", "12345");
 
-        let consent_request = good_authorisation_request!(
-            idms_prox_read,
-            &ident,
-            ct,
-            pkce_secret.to_request(),
-            OAUTH2_SCOPE_OPENID.to_string()
-        );
+        let revoke_request = TokenRevokeRequest {
+            token: oauth2_token.access_token.clone(),
+            token_type_hint: None,
+            client_post_auth: ClientPostAuth::default(),
+        };
+        let e = idms_prox_write
+            .oauth2_token_revoke(&bad_client_authz, &revoke_request, ct)
+            .unwrap_err();
+        assert!(matches!(e, Oauth2Error::AuthenticationRequired));
+        assert!(idms_prox_write.commit().is_ok());
 
-        // Should be in the consent phase;
-        let AuthoriseResponse::ConsentRequested { consent_token, .. } = consent_request else {
-            unreachable!();
+        // Now submit a non-existent/invalid token. Does not affect our tokens validity.
+        let mut idms_prox_write = idms.proxy_write(ct).await.unwrap();
+        let revoke_request = TokenRevokeRequest {
+            token: "this is an invalid token, nothing will happen!".to_string(),
+            token_type_hint: None,
+            client_post_auth: ClientPostAuth::default(),
         };
+        let e = idms_prox_write
+            .oauth2_token_revoke(&client_authz, &revoke_request, ct)
+            .unwrap_err();
+        assert!(matches!(e, Oauth2Error::InvalidRequest));
+        assert!(idms_prox_write.commit().is_ok());
 
-        // == Manually submit the consent token to the permit for the permit_success
+        // Check our token is still valid.
+        let mut idms_prox_read = idms.proxy_read().await.unwrap();
+        let intr_response = idms_prox_read
+            .check_oauth2_token_introspect(&client_authz, &intr_request, ct)
+            .expect("Failed to inspect token");
+        assert!(intr_response.active);
         drop(idms_prox_read);
+
+        // Finally revoke it.
         let mut idms_prox_write = idms.proxy_write(ct).await.unwrap();
+        let revoke_request = TokenRevokeRequest {
+            token: oauth2_token.access_token.clone(),
+            token_type_hint: None,
+            client_post_auth: ClientPostAuth::default(),
+        };
+        assert!(idms_prox_write
+            .oauth2_token_revoke(&client_authz, &revoke_request, ct,)
+            .is_ok());
+        assert!(idms_prox_write.commit().is_ok());
 
-        let permit_success = idms_prox_write
-            .check_oauth2_authorise_permit(&ident, &consent_token, ct)
-            .expect("Failed to perform OAuth2 permit");
+        // Assert it is now invalid.
+        let mut idms_prox_read = idms.proxy_read().await.unwrap();
+        let intr_response = idms_prox_read
+            .check_oauth2_token_introspect(&client_authz, &intr_request, ct)
+            .expect("Failed to inspect token");
 
-        // Check we are reflecting the CSRF properly.
-        assert_eq!(permit_success.state.as_deref(), Some("123"));
+        assert!(!intr_response.active);
+        drop(idms_prox_read);
 
-        // == Submit the token exchange code.
+        // Force trim the session and wait for the grace window to pass. The token will be invalidated
+        let mut idms_prox_write = idms.proxy_write(ct).await.unwrap();
+        let filt = filter!(f_eq(
+            Attribute::Uuid,
+            PartialValue::Uuid(ident.get_uuid().unwrap())
+        ));
+        let mut work_set = idms_prox_write
+            .qs_write
+            .internal_search_writeable(&filt)
+            .expect("Failed to perform internal search writeable");
+        for (_, entry) in work_set.iter_mut() {
+            let _ = entry.force_trim_ava(Attribute::OAuth2Session);
+        }
+        assert!(idms_prox_write
+            .qs_write
+            .internal_apply_writable(work_set)
+            .is_ok());
 
-        let token_req = AccessTokenRequest {
-            grant_type: GrantTypeReq::AuthorizationCode {
-                code: permit_success.code,
-                redirect_uri: Url::parse("https://demo.example.com/oauth2/result").unwrap(),
-                // From the first step.
-                code_verifier: Some(pkce_secret.to_verifier()),
-            },
+        assert!(idms_prox_write.commit().is_ok());
 
-            client_post_auth: ClientPostAuth {
-                client_id: Some("Test_Resource_Server".to_string()),
-                client_secret: None,
-            },
-        };
+        let mut idms_prox_read = idms.proxy_read().await.unwrap();
+        // Grace window in effect.
+        let intr_response = idms_prox_read
+            .check_oauth2_token_introspect(&client_authz, &intr_request, ct)
+            .expect("Failed to inspect token");
+        assert!(intr_response.active);
 
-        let token_response = idms_prox_write
-            .check_oauth2_token_exchange(&ClientAuthInfo::none(), &token_req, ct)
-            .expect("Failed to perform OAuth2 token exchange");
+        // Grace window passed, it will now be invalid.
+        let ct = ct + AUTH_TOKEN_GRACE_WINDOW;
+        let intr_response = idms_prox_read
+            .check_oauth2_token_introspect(&client_authz, &intr_request, ct)
+            .expect("Failed to inspect token");
+        assert!(!intr_response.active);
 
-        // 🎉 We got a token! In the future we can then check introspection from this point.
-        assert_eq!(token_response.token_type, AccessTokenType::Bearer);
+        drop(idms_prox_read);
 
+        // A second invalidation of the token "does nothing".
+        let mut idms_prox_write = idms.proxy_write(ct).await.unwrap();
+        let revoke_request = TokenRevokeRequest {
+            token: oauth2_token.access_token,
+            token_type_hint: None,
+            client_post_auth: ClientPostAuth::default(),
+        };
+        assert!(idms_prox_write
+            .oauth2_token_revoke(&client_authz, &revoke_request, ct,)
+            .is_ok());
         assert!(idms_prox_write.commit().is_ok());
     }
 
     #[idm_test]
-    async fn test_idm_oauth2_invalid_authorisation_requests(
+    async fn test_idm_oauth2_session_cleanup_post_rs_delete(
         idms: &IdmServer,
         _idms_delayed: &mut IdmServerDelayed,
     ) {
-        // Test invalid OAuth2 authorisation states/requests.
+        // First, setup to get a token.
         let ct = Duration::from_secs(TEST_CURRENT_TIME);
-        let (_secret, _uat, ident, _) =
+        let (secret, _uat, ident, _) =
             setup_oauth2_resource_server_basic(idms, ct, true, false, false).await;
+        let client_authz = ClientAuthInfo::encode_basic("test_resource_server", secret.as_str());
 
-        let (_anon_uat, anon_ident) = setup_idm_admin(idms, ct).await;
-        let (_idm_admin_uat, idm_admin_ident) = setup_idm_admin(idms, ct).await;
-
-        // Need a uat from a user not in the group. Probs anonymous.
         let idms_prox_read = idms.proxy_read().await.unwrap();
 
+        // == Setup the authorisation request
         let pkce_secret = PkceS256Secret::default();
 
-        let pkce_request = pkce_secret.to_request();
+        let consent_request = good_authorisation_request!(
+            idms_prox_read,
+            &ident,
+            ct,
+            pkce_secret.to_request(),
+            OAUTH2_SCOPE_OPENID.to_string()
+        );
 
-        //  * response type != code.
-        let auth_req = AuthorisationRequest {
-            // We're unlikely to support Implicit Grant
-            response_type: ResponseType::Token,
-            response_mode: None,
-            client_id: "test_resource_server".to_string(),
-            state: Some("123".to_string()),
-            pkce_request: Some(pkce_request.clone()),
-            redirect_uri: Url::parse("https://demo.example.com/oauth2/result").unwrap(),
-            scope: btreeset![OAUTH2_SCOPE_OPENID.to_string()],
-            nonce: None,
-            oidc_ext: Default::default(),
-            max_age: None,
-            unknown_keys: Default::default(),
+        let AuthoriseResponse::ConsentRequested { consent_token, .. } = consent_request else {
+            unreachable!();
         };
 
-        assert!(
-            idms_prox_read
-                .check_oauth2_authorisation(Some(&ident), &auth_req, ct)
-                .unwrap_err()
-                == Oauth2Error::UnsupportedResponseType
-        );
+        // == Manually submit the consent token to the permit for the permit_success
+        drop(idms_prox_read);
+        let mut idms_prox_write = idms.proxy_write(ct).await.unwrap();
 
-        // * No pkce in pkce enforced mode.
-        let auth_req = AuthorisationRequest {
-            response_type: ResponseType::Code,
-            response_mode: None,
-            client_id: "test_resource_server".to_string(),
-            state: Some("123".to_string()),
-            pkce_request: None,
+        let permit_success = idms_prox_write
+            .check_oauth2_authorise_permit(&ident, &consent_token, ct)
+            .expect("Failed to perform OAuth2 permit");
+
+        let token_req: AccessTokenRequest = GrantTypeReq::AuthorizationCode {
+            code: permit_success.code,
             redirect_uri: Url::parse("https://demo.example.com/oauth2/result").unwrap(),
-            scope: btreeset![OAUTH2_SCOPE_OPENID.to_string()],
-            nonce: None,
-            oidc_ext: Default::default(),
-            max_age: None,
-            unknown_keys: Default::default(),
-        };
+            code_verifier: Some(pkce_secret.to_verifier()),
+        }
+        .into();
 
-        assert!(
-            idms_prox_read
-                .check_oauth2_authorisation(Some(&ident), &auth_req, ct)
-                .unwrap_err()
-                == Oauth2Error::InvalidRequest
-        );
+        let oauth2_token = idms_prox_write
+            .check_oauth2_token_exchange(&client_authz, &token_req, ct)
+            .expect("Unable to exchange for OAuth2 token");
 
-        //  * invalid rs name
-        let auth_req = AuthorisationRequest {
-            response_type: ResponseType::Code,
-            response_mode: None,
-            client_id: "NOT A REAL RESOURCE SERVER".to_string(),
-            state: Some("123".to_string()),
-            pkce_request: Some(pkce_request.clone()),
-            redirect_uri: Url::parse("https://demo.example.com/oauth2/result").unwrap(),
-            scope: btreeset![OAUTH2_SCOPE_OPENID.to_string()],
-            nonce: None,
-            oidc_ext: Default::default(),
-            max_age: None,
-            unknown_keys: Default::default(),
-        };
+        let access_token =
+            JwsCompact::from_str(&oauth2_token.access_token).expect("Invalid Access Token");
 
-        assert!(
-            idms_prox_read
-                .check_oauth2_authorisation(Some(&ident), &auth_req, ct)
-                .unwrap_err()
-                == Oauth2Error::InvalidClientId
-        );
+        let jws_verifier = JwsDangerReleaseWithoutVerify::default();
 
-        //  * mismatched origin in the redirect.
-        let auth_req = AuthorisationRequest {
-            response_type: ResponseType::Code,
-            response_mode: None,
-            client_id: "test_resource_server".to_string(),
-            state: Some("123".to_string()),
-            pkce_request: Some(pkce_request.clone()),
-            redirect_uri: Url::parse("https://totes.not.sus.org/oauth2/result").unwrap(),
-            scope: btreeset![OAUTH2_SCOPE_OPENID.to_string()],
-            nonce: None,
-            oidc_ext: Default::default(),
-            max_age: None,
-            unknown_keys: Default::default(),
-        };
+        let reflected_token = jws_verifier
+            .verify(&access_token)
+            .unwrap()
+            .from_json::<OAuth2RFC9068Token<OAuth2RFC9068TokenExtensions>>()
+            .expect("Failed to access internals of the refresh token");
 
-        assert!(
-            idms_prox_read
-                .check_oauth2_authorisation(Some(&ident), &auth_req, ct)
-                .unwrap_err()
-                == Oauth2Error::InvalidOrigin
-        );
+        let session_id = reflected_token.extensions.session_id;
 
-        // * invalid uri in the redirect
-        let auth_req = AuthorisationRequest {
-            response_type: ResponseType::Code,
-            response_mode: None,
-            client_id: "test_resource_server".to_string(),
-            state: Some("123".to_string()),
-            pkce_request: Some(pkce_request.clone()),
-            redirect_uri: Url::parse("https://demo.example.com/oauth2/wrong_place").unwrap(),
-            scope: btreeset![OAUTH2_SCOPE_OPENID.to_string()],
-            nonce: None,
-            oidc_ext: Default::default(),
-            max_age: None,
-            unknown_keys: Default::default(),
-        };
+        assert!(idms_prox_write.commit().is_ok());
 
-        assert!(
-            idms_prox_read
-                .check_oauth2_authorisation(Some(&ident), &auth_req, ct)
-                .unwrap_err()
-                == Oauth2Error::InvalidOrigin
-        );
+        // Process it to ensure the record exists.
+        let mut idms_prox_write = idms.proxy_write(ct).await.unwrap();
 
-        // * invalid uri (doesn't match query params)
-        let auth_req = AuthorisationRequest {
-            response_type: ResponseType::Code,
-            response_mode: None,
-            client_id: "test_resource_server".to_string(),
-            state: Some("123".to_string()),
-            pkce_request: Some(pkce_request.clone()),
-            redirect_uri: Url::parse("https://portal.example.com/?custom=foo&too=many").unwrap(),
-            scope: btreeset![OAUTH2_SCOPE_OPENID.to_string()],
-            nonce: None,
-            oidc_ext: Default::default(),
-            max_age: None,
-            unknown_keys: Default::default(),
-        };
+        // Check it is now there
+        let entry = idms_prox_write
+            .qs_write
+            .internal_search_uuid(UUID_TESTPERSON_1)
+            .expect("failed");
+        let valid = entry
+            .get_ava_as_oauth2session_map(Attribute::OAuth2Session)
+            .map(|map| map.get(&session_id).is_some())
+            .unwrap_or(false);
+        assert!(valid);
 
-        assert!(
-            idms_prox_read
-                .check_oauth2_authorisation(Some(&ident), &auth_req, ct)
-                .unwrap_err()
-                == Oauth2Error::InvalidOrigin
-        );
+        // Delete the resource server.
 
-        let auth_req = AuthorisationRequest {
-            response_type: ResponseType::Code,
-            response_mode: None,
-            client_id: "test_resource_server".to_string(),
-            state: Some("123".to_string()),
-            pkce_request: Some(pkce_request.clone()),
-            redirect_uri: Url::parse("https://portal.example.com").unwrap(),
-            scope: btreeset![OAUTH2_SCOPE_OPENID.to_string()],
-            nonce: None,
-            oidc_ext: Default::default(),
-            max_age: None,
-            unknown_keys: Default::default(),
-        };
+        let de = DeleteEvent::new_internal_invalid(filter!(f_eq(
+            Attribute::Name,
+            PartialValue::new_iname("test_resource_server")
+        )));
 
-        assert!(
-            idms_prox_read
-                .check_oauth2_authorisation(Some(&ident), &auth_req, ct)
-                .unwrap_err()
-                == Oauth2Error::InvalidOrigin
-        );
+        assert!(idms_prox_write.qs_write.delete(&de).is_ok());
 
-        let auth_req = AuthorisationRequest {
-            response_type: ResponseType::Code,
-            response_mode: None,
-            client_id: "test_resource_server".to_string(),
-            state: Some("123".to_string()),
-            pkce_request: Some(pkce_request.clone()),
-            redirect_uri: Url::parse("https://portal.example.com/?wrong=queryparam").unwrap(),
-            scope: btreeset![OAUTH2_SCOPE_OPENID.to_string()],
-            nonce: None,
-            oidc_ext: Default::default(),
-            max_age: None,
-            unknown_keys: Default::default(),
+        // Assert the session is revoked. This is cleaned up as an artifact of the referential
+        // integrity plugin. Remember, refint doesn't consider revoked sessions once they are
+        // revoked.
+        let entry = idms_prox_write
+            .qs_write
+            .internal_search_uuid(UUID_TESTPERSON_1)
+            .expect("failed");
+        let revoked = entry
+            .get_ava_as_oauth2session_map(Attribute::OAuth2Session)
+            .and_then(|sessions| sessions.get(&session_id))
+            .map(|session| matches!(session.state, SessionState::RevokedAt(_)))
+            .unwrap_or(false);
+        assert!(revoked);
+
+        assert!(idms_prox_write.commit().is_ok());
+    }
+
+    #[idm_test]
+    async fn test_idm_oauth2_authorisation_reject(
+        idms: &IdmServer,
+        _idms_delayed: &mut IdmServerDelayed,
+    ) {
+        let ct = Duration::from_secs(TEST_CURRENT_TIME);
+        let (_secret, _uat, ident, _) =
+            setup_oauth2_resource_server_basic(idms, ct, true, false, false).await;
+
+        let ident2 = {
+            let mut idms_prox_write = idms.proxy_write(ct).await.unwrap();
+            let account = idms_prox_write
+                .target_to_account(UUID_IDM_ADMIN)
+                .expect("account must exist");
+            let session_id = uuid::Uuid::new_v4();
+            let uat2 = account
+                .to_userauthtoken(
+                    session_id,
+                    SessionScope::ReadWrite,
+                    ct,
+                    &ResolvedAccountPolicy::test_policy(),
+                )
+                .expect("Unable to create uat");
+
+            idms_prox_write
+                .process_uat_to_identity(&uat2, ct, Source::Internal)
+                .expect("Unable to process uat")
         };
 
-        assert!(
-            idms_prox_read
-                .check_oauth2_authorisation(Some(&ident), &auth_req, ct)
-                .unwrap_err()
-                == Oauth2Error::InvalidOrigin
+        let idms_prox_read = idms.proxy_read().await.unwrap();
+        let redirect_uri = Url::parse("https://demo.example.com/oauth2/result").unwrap();
+
+        let pkce_secret = PkceS256Secret::default();
+
+        // Check reject behaviour
+        let consent_request = good_authorisation_request!(
+            idms_prox_read,
+            &ident,
+            ct,
+            pkce_secret.to_request(),
+            OAUTH2_SCOPE_OPENID.to_string()
         );
 
-        // Not Authenticated
-        let auth_req = AuthorisationRequest {
-            response_type: ResponseType::Code,
-            response_mode: None,
-            client_id: "test_resource_server".to_string(),
-            state: Some("123".to_string()),
-            pkce_request: Some(pkce_request.clone()),
-            redirect_uri: Url::parse("https://demo.example.com/oauth2/result").unwrap(),
-            scope: btreeset![OAUTH2_SCOPE_OPENID.to_string()],
-            nonce: None,
-            oidc_ext: Default::default(),
-            max_age: None,
-            unknown_keys: Default::default(),
+        let AuthoriseResponse::ConsentRequested { consent_token, .. } = consent_request else {
+            unreachable!();
         };
 
-        let req = idms_prox_read
-            .check_oauth2_authorisation(None, &auth_req, ct)
-            .unwrap();
-
-        assert!(matches!(
-            req,
-            AuthoriseResponse::AuthenticationRequired { .. }
-        ));
+        let reject_success = idms_prox_read
+            .check_oauth2_authorise_reject(&ident, &consent_token, ct)
+            .expect("Failed to perform OAuth2 reject");
 
-        // Requested scope is not available
-        let auth_req = AuthorisationRequest {
-            response_type: ResponseType::Code,
-            response_mode: None,
-            client_id: "test_resource_server".to_string(),
-            state: Some("123".to_string()),
-            pkce_request: Some(pkce_request.clone()),
-            redirect_uri: Url::parse("https://demo.example.com/oauth2/result").unwrap(),
-            scope: btreeset!["invalid_scope".to_string(), "read".to_string()],
-            nonce: None,
-            oidc_ext: Default::default(),
-            max_age: None,
-            unknown_keys: Default::default(),
-        };
+        assert_eq!(reject_success.redirect_uri, redirect_uri);
 
+        // Too much time past to reject
+        let past_ct = Duration::from_secs(TEST_CURRENT_TIME + 301);
         assert!(
             idms_prox_read
-                .check_oauth2_authorisation(Some(&ident), &auth_req, ct)
+                .check_oauth2_authorise_reject(&ident, &consent_token, past_ct)
                 .unwrap_err()
-                == Oauth2Error::AccessDenied
+                == OperationError::CryptographyError
         );
 
-        // Not a member of the group.
-        let auth_req = AuthorisationRequest {
-            response_type: ResponseType::Code,
-            response_mode: None,
-            client_id: "test_resource_server".to_string(),
-            state: Some("123".to_string()),
-            pkce_request: Some(pkce_request.clone()),
-            redirect_uri: Url::parse("https://demo.example.com/oauth2/result").unwrap(),
-            scope: btreeset!["openid".to_string(), "read".to_string()],
-            nonce: None,
-            oidc_ext: Default::default(),
-            max_age: None,
-            unknown_keys: Default::default(),
-        };
-
-        assert!(
+        // Invalid consent token
+        assert_eq!(
             idms_prox_read
-                .check_oauth2_authorisation(Some(&idm_admin_ident), &auth_req, ct)
-                .unwrap_err()
-                == Oauth2Error::AccessDenied
+                .check_oauth2_authorise_reject(&ident, "not a token", ct)
+                .unwrap_err(),
+            OperationError::CryptographyError
         );
 
-        // Deny Anonymous auth methods
-        let auth_req = AuthorisationRequest {
-            response_type: ResponseType::Code,
-            response_mode: None,
-            client_id: "test_resource_server".to_string(),
-            state: Some("123".to_string()),
-            pkce_request: Some(pkce_request),
-            redirect_uri: Url::parse("https://demo.example.com/oauth2/result").unwrap(),
-            scope: btreeset!["openid".to_string(), "read".to_string()],
-            nonce: None,
-            oidc_ext: Default::default(),
-            max_age: None,
-            unknown_keys: Default::default(),
-        };
-
+        // Wrong ident
         assert!(
             idms_prox_read
-                .check_oauth2_authorisation(Some(&anon_ident), &auth_req, ct)
+                .check_oauth2_authorise_reject(&ident2, &consent_token, ct)
                 .unwrap_err()
-                == Oauth2Error::AccessDenied
+                == OperationError::InvalidSessionState
         );
     }
 
     #[idm_test]
-    async fn test_idm_oauth2_invalid_authorisation_permit_requests(
+    async fn test_idm_oauth2_rfc8414_metadata(
         idms: &IdmServer,
         _idms_delayed: &mut IdmServerDelayed,
     ) {
-        // Test invalid OAuth2 authorisation states/requests.
         let ct = Duration::from_secs(TEST_CURRENT_TIME);
-        let (_secret, uat, ident, _) =
+        let (_secret, _uat, _ident, _) =
             setup_oauth2_resource_server_basic(idms, ct, true, false, false).await;
 
-        let mut idms_prox_write = idms.proxy_write(ct).await.unwrap();
+        let idms_prox_read = idms.proxy_read().await.unwrap();
 
-        let mut uat_wrong_session_id = uat.clone();
-        uat_wrong_session_id.session_id = uuid::Uuid::new_v4();
-        let ident_wrong_session_id = idms_prox_write
-            .process_uat_to_identity(&uat_wrong_session_id, ct, Source::Internal)
-            .expect("Unable to process uat");
+        // check the discovery end point works as we expect
+        assert!(
+            idms_prox_read
+                .oauth2_rfc8414_metadata("nosuchclient")
+                .unwrap_err()
+                == OperationError::NoMatchingEntries
+        );
 
-        let account = idms_prox_write
-            .target_to_account(UUID_IDM_ADMIN)
-            .expect("account must exist");
-        let session_id = uuid::Uuid::new_v4();
-        let uat2 = account
-            .to_userauthtoken(
-                session_id,
-                SessionScope::ReadWrite,
-                ct,
-                &ResolvedAccountPolicy::test_policy(),
-            )
-            .expect("Unable to create uat");
-        let ident2 = idms_prox_write
-            .process_uat_to_identity(&uat2, ct, Source::Internal)
-            .expect("Unable to process uat");
+        let discovery = idms_prox_read
+            .oauth2_rfc8414_metadata("test_resource_server")
+            .expect("Failed to get discovery");
 
-        assert!(idms_prox_write.commit().is_ok());
+        assert!(
+            discovery.issuer
+                == Url::parse("https://idm.example.com/oauth2/openid/test_resource_server")
+                    .unwrap()
+        );
 
-        // Now start the test
+        assert!(
+            discovery.authorization_endpoint
+                == Url::parse("https://idm.example.com/ui/oauth2").unwrap()
+        );
 
-        let idms_prox_read = idms.proxy_read().await.unwrap();
+        assert!(
+            discovery.token_endpoint
+                == Url::parse(&format!(
+                    "https://idm.example.com{}",
+                    uri::OAUTH2_TOKEN_ENDPOINT
+                ))
+                .unwrap()
+        );
 
-        let pkce_secret = PkceS256Secret::default();
+        assert!(
+            discovery.jwks_uri
+                == Some(
+                    Url::parse(
+                        "https://idm.example.com/oauth2/openid/test_resource_server/public_key.jwk"
+                    )
+                    .unwrap()
+                )
+        );
 
-        let consent_request = good_authorisation_request!(
-            idms_prox_read,
-            &ident,
-            ct,
-            pkce_secret.to_request(),
-            OAUTH2_SCOPE_OPENID.to_string()
+        assert_eq!(
+            discovery.registration_endpoint,
+            Some(Url::parse("https://idm.example.com/oauth2/register").unwrap())
         );
 
-        let AuthoriseResponse::ConsentRequested { consent_token, .. } = consent_request else {
-            unreachable!();
-        };
+        assert!(
+            discovery.scopes_supported
+                == Some(vec![
+                    OAUTH2_SCOPE_GROUPS.to_string(),
+                    OAUTH2_SCOPE_OPENID.to_string(),
+                    OAUTH2_SCOPE_PROFILE.to_string(),
+                    "supplement".to_string(),
+                ])
+        );
 
-        drop(idms_prox_read);
-        let mut idms_prox_write = idms.proxy_write(ct).await.unwrap();
+        assert_eq!(discovery.response_types_supported, vec![ResponseType::Code]);
+        assert_eq!(
+            discovery.response_modes_supported,
+            vec![ResponseMode::Query, ResponseMode::Fragment]
+        );
+        assert_eq!(
+            discovery.grant_types_supported,
+            vec![GrantType::AuthorisationCode, GrantType::TokenExchange]
+        );
+        assert!(
+            discovery.token_endpoint_auth_methods_supported
+                == vec![
+                    TokenEndpointAuthMethod::ClientSecretBasic,
+                    TokenEndpointAuthMethod::ClientSecretPost,
+                    TokenEndpointAuthMethod::ClientSecretJwt,
+                    TokenEndpointAuthMethod::PrivateKeyJwt,
+                    TokenEndpointAuthMethod::TlsClientAuth,
+                    TokenEndpointAuthMethod::SelfSignedTlsClientAuth
+                ]
+        );
+        assert!(discovery.service_documentation.is_some());
+
+        assert!(discovery.ui_locales_supported.is_none());
+        assert!(discovery.op_policy_uri.is_none());
+        assert!(discovery.op_tos_uri.is_none());
 
-        // Invalid permits
-        //  * expired token, aka past ttl.
         assert!(
-            idms_prox_write
-                .check_oauth2_authorise_permit(
-                    &ident,
-                    &consent_token,
-                    ct + Duration::from_secs(TOKEN_EXPIRE),
+            discovery.revocation_endpoint
+                == Some(
+                    Url::parse(&format!(
+                        "https://idm.example.com{OAUTH2_TOKEN_REVOKE_ENDPOINT}"
+                    ))
+                    .unwrap()
                 )
-                .unwrap_err()
-                == OperationError::CryptographyError
         );
-
-        //  * incorrect ident
-        // We get another uat, but for a different user, and we'll introduce these
-        // inconsistently to cause confusion.
+        assert!(
+            discovery.revocation_endpoint_auth_methods_supported
+                == vec![
+                    TokenEndpointAuthMethod::ClientSecretBasic,
+                    TokenEndpointAuthMethod::ClientSecretPost
+                ]
+        );
 
         assert!(
-            idms_prox_write
-                .check_oauth2_authorise_permit(&ident2, &consent_token, ct,)
-                .unwrap_err()
-                == OperationError::InvalidSessionState
+            discovery.introspection_endpoint
+                == Some(
+                    Url::parse(&format!(
+                        "https://idm.example.com{}",
+                        kanidm_proto::constants::uri::OAUTH2_TOKEN_INTROSPECT_ENDPOINT
+                    ))
+                    .unwrap()
+                )
         );
-
-        //  * incorrect session id
         assert!(
-            idms_prox_write
-                .check_oauth2_authorise_permit(&ident_wrong_session_id, &consent_token, ct,)
-                .unwrap_err()
-                == OperationError::InvalidSessionState
+            discovery.introspection_endpoint_auth_methods_supported
+                == vec![
+                    TokenEndpointAuthMethod::ClientSecretBasic,
+                    TokenEndpointAuthMethod::ClientSecretPost
+                ]
         );
+        assert!(discovery
+            .introspection_endpoint_auth_signing_alg_values_supported
+            .is_none());
 
-        assert!(idms_prox_write.commit().is_ok());
+        assert_eq!(
+            discovery.code_challenge_methods_supported,
+            vec![PkceAlg::S256]
+        );
+
+        assert!(discovery.request_parameter_supported);
+        assert!(discovery.request_uri_parameter_supported);
+        assert_eq!(
+            discovery.request_object_signing_alg_values_supported,
+            Some(vec![
+                IdTokenSignAlg::ES256,
+                IdTokenSignAlg::RS256,
+                IdTokenSignAlg::HS256
+            ])
+        );
+        assert_eq!(
+            discovery.pushed_authorization_request_endpoint,
+            Some(Url::parse("https://idm.example.com/oauth2/par").unwrap())
+        );
+        assert!(!discovery.require_pushed_authorization_requests);
     }
 
     #[idm_test]
-    async fn test_idm_oauth2_invalid_token_exchange_requests(
+    async fn test_idm_oauth2_openid_discovery(
         idms: &IdmServer,
         _idms_delayed: &mut IdmServerDelayed,
     ) {
         let ct = Duration::from_secs(TEST_CURRENT_TIME);
-        let (secret, mut uat, ident, _) =
+        let (_secret, _uat, _ident, _) =
             setup_oauth2_resource_server_basic(idms, ct, true, false, false).await;
 
-        // ⚠️  We set the uat expiry time to 5 seconds from TEST_CURRENT_TIME. This
-        // allows all our other tests to pass, but it means when we specifically put the
-        // clock forward a fraction, the fernet tokens are still valid, but the uat
-        // is not.
-        // IE
-        //   |---------------------|------------------|
-        //   TEST_CURRENT_TIME     UAT_EXPIRE         TOKEN_EXPIRE
-        //
-        // This lets us check a variety of time based cases.
-        uat.expiry = Some(
-            time::OffsetDateTime::UNIX_EPOCH
-                + Duration::from_secs(TEST_CURRENT_TIME + UAT_EXPIRE - 1),
-        );
-
         let idms_prox_read = idms.proxy_read().await.unwrap();
 
-        // == Setup the authorisation request
-        let pkce_secret = PkceS256Secret::default();
-
-        let consent_request = good_authorisation_request!(
-            idms_prox_read,
-            &ident,
-            ct,
-            pkce_secret.to_request(),
-            OAUTH2_SCOPE_OPENID.to_string()
+        // check the discovery end point works as we expect
+        assert!(
+            idms_prox_read
+                .oauth2_openid_discovery("nosuchclient")
+                .unwrap_err()
+                == OperationError::NoMatchingEntries
         );
 
-        let code_verifier = Some(pkce_secret.to_verifier());
-
-        let AuthoriseResponse::ConsentRequested { consent_token, .. } = consent_request else {
-            unreachable!();
-        };
-
-        drop(idms_prox_read);
-        let mut idms_prox_write = idms.proxy_write(ct).await.unwrap();
+        assert!(
+            idms_prox_read
+                .oauth2_openid_publickey("nosuchclient")
+                .unwrap_err()
+                == OperationError::NoMatchingEntries
+        );
 
-        // == Manually submit the consent token to the permit for the permit_success
-        let permit_success = idms_prox_write
-            .check_oauth2_authorise_permit(&ident, &consent_token, ct)
-            .expect("Failed to perform OAuth2 permit");
+        let discovery = idms_prox_read
+            .oauth2_openid_discovery("test_resource_server")
+            .expect("Failed to get discovery");
 
-        // == Submit the token exchange code.
+        let mut jwkset = idms_prox_read
+            .oauth2_openid_publickey("test_resource_server")
+            .expect("Failed to get public key");
 
-        // Invalid token exchange
-        //  * invalid client_authz (not base64)
-        let token_req: AccessTokenRequest = GrantTypeReq::AuthorizationCode {
-            code: permit_success.code.clone(),
-            redirect_uri: Url::parse("https://demo.example.com/oauth2/result").unwrap(),
-            code_verifier: code_verifier.clone(),
-        }
-        .into();
+        let jwk = jwkset.keys.pop().expect("no such jwk");
 
-        let client_authz = ClientAuthInfo::from("not base64");
+        match jwk {
+            Jwk::EC { alg, use_, kid, .. } => {
+                match (
+                    alg.unwrap(),
+                    &discovery.id_token_signing_alg_values_supported[0],
+                ) {
+                    (JwaAlg::ES256, IdTokenSignAlg::ES256) => {}
+                    _ => panic!(),
+                };
+                assert_eq!(use_.unwrap(), JwkUse::Sig);
+                assert!(kid.is_some())
+            }
+            _ => panic!(),
+        };
 
         assert!(
-            idms_prox_write
-                .check_oauth2_token_exchange(&client_authz, &token_req, ct)
-                .unwrap_err()
-                == Oauth2Error::AuthenticationRequired
+            discovery.issuer
+                == Url::parse("https://idm.example.com/oauth2/openid/test_resource_server")
+                    .unwrap()
         );
 
-        //  * doesn't have ':'
-        let client_authz =
-            general_purpose::STANDARD.encode(format!("test_resource_server {secret}"));
-        let client_authz = ClientAuthInfo::from(client_authz.as_str());
-
         assert!(
-            idms_prox_write
-                .check_oauth2_token_exchange(&client_authz, &token_req, ct)
-                .unwrap_err()
-                == Oauth2Error::AuthenticationRequired
+            discovery.authorization_endpoint
+                == Url::parse("https://idm.example.com/ui/oauth2").unwrap()
         );
 
-        //  * invalid client_id
-        let client_authz = ClientAuthInfo::encode_basic("NOT A REAL SERVER", secret.as_str());
+        assert!(
+            discovery.token_endpoint == Url::parse("https://idm.example.com/oauth2/token").unwrap()
+        );
 
         assert!(
-            idms_prox_write
-                .check_oauth2_token_exchange(&client_authz, &token_req, ct)
-                .unwrap_err()
-                == Oauth2Error::AuthenticationRequired
+            discovery.userinfo_endpoint
+                == Some(
+                    Url::parse(
+                        "https://idm.example.com/oauth2/openid/test_resource_server/userinfo"
+                    )
+                    .unwrap()
+                )
         );
 
-        //  * valid client_id, but invalid secret
-        let client_authz = ClientAuthInfo::encode_basic("test_resource_server", "12345");
+        assert!(
+            discovery.end_session_endpoint
+                == Some(
+                    Url::parse(
+                        "https://idm.example.com/oauth2/openid/test_resource_server/end_session"
+                    )
+                    .unwrap()
+                )
+        );
+        assert!(discovery.backchannel_logout_supported);
+        assert!(discovery.backchannel_logout_session_supported);
+        assert_eq!(
+            discovery.pushed_authorization_request_endpoint,
+            Some(Url::parse("https://idm.example.com/oauth2/par").unwrap())
+        );
+        assert!(!discovery.require_pushed_authorization_requests);
 
         assert!(
-            idms_prox_write
-                .check_oauth2_token_exchange(&client_authz, &token_req, ct)
-                .unwrap_err()
-                == Oauth2Error::AuthenticationRequired
+            discovery.jwks_uri
+                == Url::parse(
+                    "https://idm.example.com/oauth2/openid/test_resource_server/public_key.jwk"
+                )
+                .unwrap()
         );
 
-        // ✅ Now the valid client_authz is in place.
-        let client_authz = ClientAuthInfo::encode_basic("test_resource_server", secret.as_str());
+        assert!(
+            discovery.scopes_supported
+                == Some(vec![
+                    OAUTH2_SCOPE_GROUPS.to_string(),
+                    OAUTH2_SCOPE_OPENID.to_string(),
+                    OAUTH2_SCOPE_PROFILE.to_string(),
+                    "supplement".to_string(),
+                ])
+        );
 
-        //  * expired exchange code (took too long)
+        assert_eq!(discovery.response_types_supported, vec![ResponseType::Code]);
+        assert_eq!(
+            discovery.response_modes_supported,
+            vec![ResponseMode::Query, ResponseMode::Fragment]
+        );
+        assert_eq!(
+            discovery.grant_types_supported,
+            vec![GrantType::AuthorisationCode, GrantType::TokenExchange]
+        );
+        assert_eq!(discovery.subject_types_supported, vec![SubjectType::Public]);
+        assert_eq!(
+            discovery.id_token_signing_alg_values_supported,
+            vec![IdTokenSignAlg::ES256]
+        );
+        assert!(discovery.userinfo_signing_alg_values_supported.is_none());
         assert!(
-            idms_prox_write
-                .check_oauth2_token_exchange(
-                    &client_authz,
-                    &token_req,
-                    ct + Duration::from_secs(TOKEN_EXPIRE)
-                )
-                .unwrap_err()
-                == Oauth2Error::InvalidRequest
+            discovery.token_endpoint_auth_methods_supported
+                == vec![
+                    TokenEndpointAuthMethod::ClientSecretBasic,
+                    TokenEndpointAuthMethod::ClientSecretPost,
+                    TokenEndpointAuthMethod::ClientSecretJwt,
+                    TokenEndpointAuthMethod::PrivateKeyJwt,
+                    TokenEndpointAuthMethod::TlsClientAuth,
+                    TokenEndpointAuthMethod::SelfSignedTlsClientAuth
+                ]
         );
+        assert_eq!(
+            discovery.display_values_supported,
+            Some(vec![DisplayValue::Page])
+        );
+        assert_eq!(discovery.claim_types_supported, vec![ClaimType::Normal]);
+        assert!(discovery.claims_supported.is_none());
+        assert!(discovery.service_documentation.is_some());
 
-        /*
-        //  * incorrect grant_type
-        // No longer possible due to changes in json api
-        let token_req: AccessTokenRequest = GrantTypeReq::AuthorizationCode {
-            grant_type: "INCORRECT GRANT TYPE".to_string(),
-            code: permit_success.code.clone(),
-            redirect_uri: Url::parse("https://demo.example.com/oauth2/result").unwrap(),
-            client_id: None,
-            client_secret: None,
-            code_verifier: code_verifier.clone(),
-        };
+        assert_eq!(
+            discovery.registration_endpoint,
+            Some(Url::parse("https://idm.example.com/oauth2/register").unwrap())
+        );
+        assert!(discovery.acr_values_supported.is_none());
+        assert_eq!(
+            discovery.id_token_encryption_alg_values_supported,
+            Some(vec![
+                IdTokenEncryptionAlg::RsaOaep,
+                IdTokenEncryptionAlg::EcdhEsA256Kw
+            ])
+        );
+        assert_eq!(
+            discovery.id_token_encryption_enc_values_supported,
+            Some(vec![IdTokenEncryptionEnc::A256GCM])
+        );
+        assert_eq!(
+            discovery.userinfo_encryption_alg_values_supported,
+            Some(vec![
+                IdTokenEncryptionAlg::RsaOaep,
+                IdTokenEncryptionAlg::EcdhEsA256Kw
+            ])
+        );
+        assert_eq!(
+            discovery.userinfo_encryption_enc_values_supported,
+            Some(vec![IdTokenEncryptionEnc::A256GCM])
+        );
+        assert_eq!(
+            discovery.request_object_signing_alg_values_supported,
+            Some(vec![
+                "ES256".to_string(),
+                "RS256".to_string(),
+                "HS256".to_string()
+            ])
+        );
+        assert!(discovery
+            .request_object_encryption_alg_values_supported
+            .is_none());
+        assert!(discovery
+            .request_object_encryption_enc_values_supported
+            .is_none());
+        assert_eq!(
+            discovery.token_endpoint_auth_signing_alg_values_supported,
+            Some(vec![
+                "ES256".to_string(),
+                "RS256".to_string(),
+                "HS256".to_string()
+            ])
+        );
+        assert!(discovery.claims_locales_supported.is_none());
+        assert!(discovery.ui_locales_supported.is_none());
+        assert!(discovery.op_policy_uri.is_none());
+        assert!(discovery.op_tos_uri.is_none());
+        assert!(!discovery.claims_parameter_supported);
+        assert!(discovery.request_uri_parameter_supported);
+        assert!(!discovery.require_request_uri_registration);
+        assert!(discovery.request_parameter_supported);
+        assert_eq!(
+            discovery.code_challenge_methods_supported,
+            vec![PkceAlg::S256]
+        );
+
+        // Extensions
+        assert!(
+            discovery.revocation_endpoint
+                == Some(
+                    Url::parse(&format!(
+                        "https://idm.example.com{OAUTH2_TOKEN_REVOKE_ENDPOINT}"
+                    ))
+                    .unwrap()
+                )
+        );
         assert!(
-            idms_prox_read
-                .check_oauth2_token_exchange(client_authz.as_deref(), &token_req, ct)
-                .unwrap_err()
-                == Oauth2Error::InvalidRequest
+            discovery.revocation_endpoint_auth_methods_supported
+                == vec![
+                    TokenEndpointAuthMethod::ClientSecretBasic,
+                    TokenEndpointAuthMethod::ClientSecretPost
+                ]
         );
-        */
 
-        //  * Incorrect redirect uri
-        let token_req: AccessTokenRequest = GrantTypeReq::AuthorizationCode {
-            code: permit_success.code.clone(),
-            redirect_uri: Url::parse("https://totes.not.sus.org/oauth2/result").unwrap(),
-            code_verifier: code_verifier.clone(),
-        }
-        .into();
         assert!(
-            idms_prox_write
-                .check_oauth2_token_exchange(&client_authz, &token_req, ct)
-                .unwrap_err()
-                == Oauth2Error::InvalidOrigin
+            discovery.introspection_endpoint
+                == Some(
+                    Url::parse(&format!(
+                        "https://idm.example.com{OAUTH2_TOKEN_INTROSPECT_ENDPOINT}"
+                    ))
+                    .unwrap()
+                )
         );
-
-        //  * code verifier incorrect
-        let token_req: AccessTokenRequest = GrantTypeReq::AuthorizationCode {
-            code: permit_success.code,
-            redirect_uri: Url::parse("https://demo.example.com/oauth2/result").unwrap(),
-            code_verifier: Some("12345".to_string()),
-        }
-        .into();
         assert!(
-            idms_prox_write
-                .check_oauth2_token_exchange(&client_authz, &token_req, ct)
-                .unwrap_err()
-                == Oauth2Error::InvalidRequest
+            discovery.introspection_endpoint_auth_methods_supported
+                == vec![
+                    TokenEndpointAuthMethod::ClientSecretBasic,
+                    TokenEndpointAuthMethod::ClientSecretPost
+                ]
         );
-
-        assert!(idms_prox_write.commit().is_ok());
+        assert!(discovery
+            .introspection_endpoint_auth_signing_alg_values_supported
+            .is_none());
     }
 
     #[idm_test]
-    async fn test_idm_oauth2_supplemental_origin_redirect(
+    async fn test_idm_oauth2_openid_extensions(
         idms: &IdmServer,
         _idms_delayed: &mut IdmServerDelayed,
     ) {
         let ct = Duration::from_secs(TEST_CURRENT_TIME);
-        let (secret, uat, ident, _) =
+        let (secret, _uat, ident, _) =
             setup_oauth2_resource_server_basic(idms, ct, true, false, false).await;
+        let client_authz = ClientAuthInfo::encode_basic("test_resource_server", secret.as_str());
 
         let idms_prox_read = idms.proxy_read().await.unwrap();
 
-        // == Setup the authorisation request
         let pkce_secret = PkceS256Secret::default();
 
-        let redirect_uri = Url::parse("https://portal.example.com/?custom=foo").unwrap();
-
-        let auth_req = AuthorisationRequest {
-            response_type: ResponseType::Code,
-            response_mode: None,
-            client_id: "test_resource_server".to_string(),
-            state: None,
-            pkce_request: Some(pkce_secret.to_request()),
-            redirect_uri: redirect_uri.clone(),
-            scope: btreeset![OAUTH2_SCOPE_GROUPS.to_string()],
-            nonce: Some("abcdef".to_string()),
-            oidc_ext: Default::default(),
-            max_age: None,
-            unknown_keys: Default::default(),
-        };
-
-        let consent_request = idms_prox_read
-            .check_oauth2_authorisation(Some(&ident), &auth_req, ct)
-            .expect("OAuth2 authorisation failed");
-
-        trace!(?consent_request);
+        let consent_request = good_authorisation_request!(
+            idms_prox_read,
+            &ident,
+            ct,
+            pkce_secret.to_request(),
+            format!("{OAUTH2_SCOPE_OPENID} {OAUTH2_SCOPE_PROFILE}")
+        );
 
-        // Should be in the consent phase;
         let AuthoriseResponse::ConsentRequested { consent_token, .. } = consent_request else {
             unreachable!();
         };
@@ -4602,123 +8655,184 @@ mod tests {
             .check_oauth2_authorise_permit(&ident, &consent_token, ct)
             .expect("Failed to perform OAuth2 permit");
 
-        // Check we are reflecting the CSRF properly.
-        assert_eq!(permit_success.state.as_deref(), None);
-
-        // Assert we followed the redirect uri including the query elements
-        // we have in the url.
-        let permit_redirect_uri = permit_success.build_redirect_uri();
-
-        assert_eq!(permit_redirect_uri.origin(), redirect_uri.origin());
-        assert_eq!(permit_redirect_uri.path(), redirect_uri.path());
-        let query = BTreeMap::from_iter(permit_redirect_uri.query_pairs().into_owned());
-        // Assert the query pair wasn't changed
-        assert_eq!(query.get("custom").map(|s| s.as_str()), Some("foo"));
-
         // == Submit the token exchange code.
-        // ⚠️  This is where we submit a different origin!
-        let token_req = AccessTokenRequest {
-            grant_type: GrantTypeReq::AuthorizationCode {
-                code: permit_success.code,
-                redirect_uri,
-                code_verifier: Some(pkce_secret.to_verifier()),
-            },
-
-            client_post_auth: ClientPostAuth {
-                client_id: Some("test_resource_server".to_string()),
-                client_secret: Some(secret.clone()),
-            },
-        };
+        let token_req: AccessTokenRequest = GrantTypeReq::AuthorizationCode {
+            code: permit_success.code,
+            redirect_uri: Url::parse("https://demo.example.com/oauth2/result").unwrap(),
+            code_verifier: Some(pkce_secret.to_verifier()),
+        }
+        .into();
 
         let token_response = idms_prox_write
-            .check_oauth2_token_exchange(&ClientAuthInfo::none(), &token_req, ct)
+            .check_oauth2_token_exchange(&client_authz, &token_req, ct)
             .expect("Failed to perform OAuth2 token exchange");
 
-        // 🎉 We got a token! In the future we can then check introspection from this point.
+        // 🎉 We got a token!
         assert_eq!(token_response.token_type, AccessTokenType::Bearer);
 
-        assert!(idms_prox_write.commit().is_ok());
+        let id_token = token_response.id_token.expect("No id_token in response!");
 
-        // ============================================================================
-        // Now repeat the test with the app url.
+        let access_token =
+            JwsCompact::from_str(&token_response.access_token).expect("Invalid Access Token");
 
-        let mut idms_prox_read = idms.proxy_read().await.unwrap();
+        let refresh_token = token_response
+            .refresh_token
+            .as_ref()
+            .expect("no refresh token was issued")
+            .clone();
 
-        // Reload the ident since it pins an entry in memory.
-        let ident = idms_prox_read
-            .process_uat_to_identity(&uat, ct, Source::Internal)
-            .expect("Unable to process uat");
+        // Get the read txn for inspecting the tokens
+        assert!(idms_prox_write.commit().is_ok());
 
-        let pkce_secret = PkceS256Secret::default();
+        let mut idms_prox_read = idms.proxy_read().await.unwrap();
 
-        let auth_req = AuthorisationRequest {
-            response_type: ResponseType::Code,
-            response_mode: None,
-            client_id: "test_resource_server".to_string(),
-            state: Some("123".to_string()),
-            pkce_request: Some(pkce_secret.to_request()),
-            redirect_uri: Url::parse("app://cheese").unwrap(),
-            scope: btreeset![OAUTH2_SCOPE_GROUPS.to_string()],
-            nonce: Some("abcdef".to_string()),
-            oidc_ext: Default::default(),
-            max_age: None,
-            unknown_keys: Default::default(),
-        };
+        let mut jwkset = idms_prox_read
+            .oauth2_openid_publickey("test_resource_server")
+            .expect("Failed to get public key");
 
-        let consent_request = idms_prox_read
-            .check_oauth2_authorisation(Some(&ident), &auth_req, ct)
-            .expect("OAuth2 authorisation failed");
+        let public_jwk = jwkset.keys.pop().expect("no such jwk");
 
-        trace!(?consent_request);
+        let jws_validator =
+            JwsEs256Verifier::try_from(&public_jwk).expect("failed to build validator");
 
-        let AuthoriseResponse::Permitted(permit_success) = consent_request else {
-            unreachable!();
-        };
+        let oidc_unverified =
+            OidcUnverified::from_str(&id_token).expect("Failed to parse id_token");
 
-        // == Manually submit the consent token to the permit for the permit_success
-        // Check we are reflecting the CSRF properly.
-        assert_eq!(permit_success.state.as_deref(), Some("123"));
+        let iat = ct.as_secs() as i64;
 
-        // == Submit the token exchange code.
-        // ⚠️  This is where we submit a different origin!
-        let token_req = AccessTokenRequest {
-            grant_type: GrantTypeReq::AuthorizationCode {
-                code: permit_success.code,
-                redirect_uri: Url::parse("app://cheese").unwrap(),
-                code_verifier: Some(pkce_secret.to_verifier()),
-            },
+        let oidc = jws_validator
+            .verify(&oidc_unverified)
+            .unwrap()
+            .verify_exp(iat)
+            .expect("Failed to verify oidc");
 
-            client_post_auth: ClientPostAuth {
-                client_id: Some("test_resource_server".to_string()),
-                client_secret: Some(secret),
-            },
-        };
+        // Are the id_token values what we expect?
+        assert!(
+            oidc.iss
+                == Url::parse("https://idm.example.com/oauth2/openid/test_resource_server")
+                    .unwrap()
+        );
+        assert_eq!(oidc.sub, OidcSubject::U(UUID_TESTPERSON_1));
+        assert_eq!(oidc.aud, "test_resource_server");
+        assert_eq!(oidc.iat, iat);
+        assert_eq!(oidc.nbf, Some(iat));
+        // Previously this was the auth session but it's now inline with the access token expiry.
+        assert_eq!(oidc.exp, iat + (OAUTH2_ACCESS_TOKEN_EXPIRY as i64));
+        assert!(oidc.auth_time.is_none());
+        // Is nonce correctly passed through?
+        assert_eq!(oidc.nonce, Some("abcdef".to_string()));
+        assert!(oidc.at_hash.is_none());
+        assert!(oidc.acr.is_none());
+        assert!(oidc.amr.is_none());
+        assert_eq!(oidc.azp, Some("test_resource_server".to_string()));
+        assert!(oidc.jti.is_some());
+        if let Some(jti) = &oidc.jti {
+            assert!(Uuid::from_str(jti).is_ok());
+        }
+        assert_eq!(oidc.s_claims.name, Some("Test Person 1".to_string()));
+        assert_eq!(
+            oidc.s_claims.preferred_username,
+            Some("testperson1@example.com".to_string())
+        );
+        assert!(
+            oidc.s_claims.scopes
+                == vec![
+                    OAUTH2_SCOPE_OPENID.to_string(),
+                    OAUTH2_SCOPE_PROFILE.to_string(),
+                    "supplement".to_string()
+                ]
+        );
+        assert!(oidc.claims.is_empty());
+        // Does our access token work with the userinfo endpoint?
+        // Do the id_token details line up to the userinfo?
+        let userinfo = idms_prox_read
+            .oauth2_openid_userinfo("test_resource_server", &access_token, None, None, ct)
+            .expect("failed to get userinfo");
+
+        assert_eq!(oidc.iss, userinfo.iss);
+        assert_eq!(oidc.sub, userinfo.sub);
+        assert_eq!(oidc.aud, userinfo.aud);
+        assert_eq!(oidc.iat, userinfo.iat);
+        assert_eq!(oidc.nbf, userinfo.nbf);
+        assert_eq!(oidc.exp, userinfo.exp);
+        assert!(userinfo.auth_time.is_none());
+        assert_eq!(userinfo.nonce, Some("abcdef".to_string()));
+        assert!(userinfo.at_hash.is_none());
+        assert!(userinfo.acr.is_none());
+        assert_eq!(oidc.amr, userinfo.amr);
+        assert_eq!(oidc.azp, userinfo.azp);
+        assert!(userinfo.jti.is_some());
+        if let Some(jti) = &userinfo.jti {
+            assert!(Uuid::from_str(jti).is_ok());
+        }
+        assert_eq!(oidc.s_claims, userinfo.s_claims);
+        assert!(userinfo.claims.is_empty());
 
         drop(idms_prox_read);
+
+        // Importantly, we need to persist the nonce through access/refresh token operations
+        // because some clients like the rust openidconnect library require it always for claim
+        // verification.
         let mut idms_prox_write = idms.proxy_write(ct).await.unwrap();
 
+        let token_req: AccessTokenRequest = GrantTypeReq::RefreshToken {
+            refresh_token,
+            scope: None,
+        }
+        .into();
+
         let token_response = idms_prox_write
-            .check_oauth2_token_exchange(&ClientAuthInfo::none(), &token_req, ct)
-            .expect("Failed to perform OAuth2 token exchange");
+            .check_oauth2_token_exchange(&client_authz, &token_req, ct)
+            .expect("Unable to exchange for OAuth2 token");
 
-        // 🎉 We got a token! In the future we can then check introspection from this point.
-        assert_eq!(token_response.token_type, AccessTokenType::Bearer);
+        let access_token =
+            JwsCompact::from_str(&token_response.access_token).expect("Invalid Access Token");
+
+        assert!(idms_prox_write.commit().is_ok());
+
+        // Okay, refresh done, lets check it.
+        let mut idms_prox_read = idms.proxy_read().await.unwrap();
+
+        let userinfo = idms_prox_read
+            .oauth2_openid_userinfo("test_resource_server", &access_token, None, None, ct)
+            .expect("failed to get userinfo");
+
+        assert_eq!(oidc.iss, userinfo.iss);
+        assert_eq!(oidc.sub, userinfo.sub);
+        assert_eq!(oidc.aud, userinfo.aud);
+        assert_eq!(oidc.iat, userinfo.iat);
+        assert_eq!(oidc.nbf, userinfo.nbf);
+        assert_eq!(oidc.exp, userinfo.exp);
+        assert!(userinfo.auth_time.is_none());
+        assert_eq!(userinfo.nonce, Some("abcdef".to_string()));
+        assert!(userinfo.at_hash.is_none());
+        assert!(userinfo.acr.is_none());
+        assert_eq!(oidc.amr, userinfo.amr);
+        assert_eq!(oidc.azp, userinfo.azp);
+        assert!(userinfo.jti.is_some());
+        if let Some(jti) = &userinfo.jti {
+            assert!(Uuid::from_str(jti).is_ok());
+        }
+        assert_eq!(oidc.s_claims, userinfo.s_claims);
+        assert!(userinfo.claims.is_empty());
     }
 
     #[idm_test]
-    async fn test_idm_oauth2_token_introspect(
+    async fn test_idm_oauth2_openid_short_username(
         idms: &IdmServer,
         _idms_delayed: &mut IdmServerDelayed,
     ) {
+        // we run the same test as test_idm_oauth2_openid_extensions()
+        // but change the preferred_username setting on the RS
         let ct = Duration::from_secs(TEST_CURRENT_TIME);
         let (secret, _uat, ident, _) =
-            setup_oauth2_resource_server_basic(idms, ct, true, false, false).await;
+            setup_oauth2_resource_server_basic(idms, ct, true, false, true).await;
         let client_authz = ClientAuthInfo::encode_basic("test_resource_server", secret.as_str());
 
         let idms_prox_read = idms.proxy_read().await.unwrap();
 
-        // == Setup the authorisation request
         let pkce_secret = PkceS256Secret::default();
+
         let consent_request = good_authorisation_request!(
             idms_prox_read,
             &ident,
@@ -4739,85 +8853,101 @@ mod tests {
             .check_oauth2_authorise_permit(&ident, &consent_token, ct)
             .expect("Failed to perform OAuth2 permit");
 
+        // == Submit the token exchange code.
         let token_req: AccessTokenRequest = GrantTypeReq::AuthorizationCode {
             code: permit_success.code,
             redirect_uri: Url::parse("https://demo.example.com/oauth2/result").unwrap(),
             code_verifier: Some(pkce_secret.to_verifier()),
         }
         .into();
-        let oauth2_token = idms_prox_write
+
+        let token_response = idms_prox_write
             .check_oauth2_token_exchange(&client_authz, &token_req, ct)
-            .expect("Unable to exchange for OAuth2 token");
+            .expect("Failed to perform OAuth2 token exchange");
 
-        assert!(idms_prox_write.commit().is_ok());
+        let id_token = token_response.id_token.expect("No id_token in response!");
+        let access_token =
+            JwsCompact::from_str(&token_response.access_token).expect("Invalid Access Token");
 
-        // Okay, now we have the token, we can check it works with introspect.
+        assert!(idms_prox_write.commit().is_ok());
         let mut idms_prox_read = idms.proxy_read().await.unwrap();
 
-        let intr_request = AccessTokenIntrospectRequest {
-            token: oauth2_token.access_token,
-            token_type_hint: None,
-            client_post_auth: ClientPostAuth::default(),
-        };
-        let intr_response = idms_prox_read
-            .check_oauth2_token_introspect(&client_authz, &intr_request, ct)
-            .expect("Failed to inspect token");
+        let mut jwkset = idms_prox_read
+            .oauth2_openid_publickey("test_resource_server")
+            .expect("Failed to get public key");
+        let public_jwk = jwkset.keys.pop().expect("no such jwk");
 
-        eprintln!("👉  {intr_response:?}");
-        assert!(intr_response.active);
-        assert_eq!(
-            intr_response.scope,
-            btreeset!["openid".to_string(), "supplement".to_string()]
-        );
-        assert_eq!(
-            intr_response.client_id.as_deref(),
-            Some("test_resource_server")
-        );
-        assert_eq!(
-            intr_response.username.as_deref(),
-            Some("testperson1@example.com")
-        );
-        assert_eq!(intr_response.token_type, Some(AccessTokenType::Bearer));
-        assert_eq!(intr_response.iat, Some(ct.as_secs() as i64));
-        assert_eq!(intr_response.nbf, Some(ct.as_secs() as i64));
+        let jws_validator =
+            JwsEs256Verifier::try_from(&public_jwk).expect("failed to build validator");
 
-        drop(idms_prox_read);
-        // start a write,
+        let oidc_unverified =
+            OidcUnverified::from_str(&id_token).expect("Failed to parse id_token");
 
-        let mut idms_prox_write = idms.proxy_write(ct).await.unwrap();
-        // Expire the account, should cause introspect to return inactive.
-        let v_expire = Value::new_datetime_epoch(Duration::from_secs(TEST_CURRENT_TIME - 1));
-        let me_inv_m = ModifyEvent::new_internal_invalid(
-            filter!(f_eq(Attribute::Uuid, PartialValue::Uuid(UUID_TESTPERSON_1))),
-            ModifyList::new_list(vec![Modify::Present(Attribute::AccountExpire, v_expire)]),
-        );
-        // go!
-        assert!(idms_prox_write.qs_write.modify(&me_inv_m).is_ok());
-        assert!(idms_prox_write.commit().is_ok());
+        let iat = ct.as_secs() as i64;
 
-        // start a new read
-        // check again.
-        let mut idms_prox_read = idms.proxy_read().await.unwrap();
-        let intr_response = idms_prox_read
-            .check_oauth2_token_introspect(&client_authz, &intr_request, ct)
-            .expect("Failed to inspect token");
+        let oidc = jws_validator
+            .verify(&oidc_unverified)
+            .unwrap()
+            .verify_exp(iat)
+            .expect("Failed to verify oidc");
 
-        assert!(!intr_response.active);
+        // Do we have the short username in the token claims?
+        assert_eq!(
+            oidc.s_claims.preferred_username,
+            Some("testperson1".to_string())
+        );
+        // Do the id_token details line up to the userinfo?
+        let userinfo = idms_prox_read
+            .oauth2_openid_userinfo("test_resource_server", &access_token, None, None, ct)
+            .expect("failed to get userinfo");
+
+        assert_eq!(oidc.s_claims, userinfo.s_claims);
     }
 
     #[idm_test]
-    async fn test_idm_oauth2_token_revoke(idms: &IdmServer, _idms_delayed: &mut IdmServerDelayed) {
-        // First, setup to get a token.
+    async fn test_idm_oauth2_openid_encrypted_responses(
+        idms: &IdmServer,
+        _idms_delayed: &mut IdmServerDelayed,
+    ) {
+        use compact_jwt::{
+            compact::EcCurve, crypto::JweEcdhEsA256KWDecipher, JweCompact, JwkKeySet,
+        };
+        use crypto_glue::ecdh_p256::EcdhP256PublicEncodedPoint;
+
         let ct = Duration::from_secs(TEST_CURRENT_TIME);
-        let (secret, _uat, ident, _) =
+        let (secret, _uat, ident, rs_uuid) =
             setup_oauth2_resource_server_basic(idms, ct, true, false, false).await;
         let client_authz = ClientAuthInfo::encode_basic("test_resource_server", secret.as_str());
 
-        let idms_prox_read = idms.proxy_read().await.unwrap();
+        // An unsupported algorithm is rejected.
+        let mut idms_prox_write = idms.proxy_write(ct).await.unwrap();
+        assert_eq!(
+            idms_prox_write.qs_write.internal_modify_uuid(
+                rs_uuid,
+                &ModifyList::new_purge_and_set(
+                    Attribute::OAuth2RsEncryptedResponseAlg,
+                    Value::new_utf8s("A128KW")
+                ),
+            ),
+            Err(OperationError::PL0003Oauth2EncryptedResponseAlgInvalid)
+        );
+        drop(idms_prox_write);
 
-        // == Setup the authorisation request
-        let pkce_secret = PkceS256Secret::default();
+        let mut idms_prox_write = idms.proxy_write(ct).await.unwrap();
+        idms_prox_write
+            .qs_write
+            .internal_modify_uuid(
+                rs_uuid,
+                &ModifyList::new_purge_and_set(
+                    Attribute::OAuth2RsEncryptedResponseAlg,
+                    Value::new_utf8s(IdTokenEncryptionAlg::EcdhEsA256Kw.as_str()),
+                ),
+            )
+            .expect("Unable to set encrypted response alg");
+        assert!(idms_prox_write.commit().is_ok());
 
+        let idms_prox_read = idms.proxy_read().await.unwrap();
+        let pkce_secret = PkceS256Secret::default();
         let consent_request = good_authorisation_request!(
             idms_prox_read,
             &ident,
@@ -4829,665 +8959,829 @@ mod tests {
         let AuthoriseResponse::ConsentRequested { consent_token, .. } = consent_request else {
             unreachable!();
         };
-
-        // == Manually submit the consent token to the permit for the permit_success
         drop(idms_prox_read);
-        let mut idms_prox_write = idms.proxy_write(ct).await.unwrap();
 
+        let mut idms_prox_write = idms.proxy_write(ct).await.unwrap();
         let permit_success = idms_prox_write
             .check_oauth2_authorise_permit(&ident, &consent_token, ct)
             .expect("Failed to perform OAuth2 permit");
 
-        // Assert that the consent was submitted
         let token_req: AccessTokenRequest = GrantTypeReq::AuthorizationCode {
             code: permit_success.code,
             redirect_uri: Url::parse("https://demo.example.com/oauth2/result").unwrap(),
             code_verifier: Some(pkce_secret.to_verifier()),
         }
         .into();
-        let oauth2_token = idms_prox_write
-            .check_oauth2_token_exchange(&client_authz, &token_req, ct)
-            .expect("Unable to exchange for OAuth2 token");
 
+        // Without a public key to encrypt to, we must never issue the id_token in the clear.
+        assert_eq!(
+            idms_prox_write
+                .check_oauth2_token_exchange(&client_authz, &token_req, ct)
+                .unwrap_err(),
+            Oauth2Error::ServerError(OperationError::InvalidState)
+        );
+        drop(idms_prox_write);
+
+        let client_decipher =
+            JweEcdhEsA256KWDecipher::generate().expect("Unable to create decipher");
+        let client_public_key = EcdhP256PublicEncodedPoint::from(
+            client_decipher
+                .public_key()
+                .expect("Unable to get client public key"),
+        );
+        let client_jwks = JwkKeySet {
+            keys: vec![Jwk::EC {
+                crv: EcCurve::P256,
+                x: client_public_key
+                    .x()
+                    .expect("No x coordinate")
+                    .to_vec()
+                    .into(),
+                y: client_public_key
+                    .y()
+                    .expect("No y coordinate")
+                    .to_vec()
+                    .into(),
+                alg: None,
+                use_: Some(JwkUse::Enc),
+                kid: None,
+            }],
+        };
+
+        let mut idms_prox_write = idms.proxy_write(ct).await.unwrap();
+        idms_prox_write
+            .qs_write
+            .internal_modify_uuid(
+                rs_uuid,
+                &ModifyList::new_purge_and_set(
+                    Attribute::OAuth2RsJwks,
+                    Value::new_utf8s(&serde_json::to_string(&client_jwks).unwrap()),
+                ),
+            )
+            .expect("Unable to set client jwks");
         assert!(idms_prox_write.commit().is_ok());
 
-        // Okay, now we have the token, we can check behaviours with the revoke interface.
+        let mut idms_prox_write = idms.proxy_write(ct).await.unwrap();
+        let token_response = idms_prox_write
+            .check_oauth2_token_exchange(&client_authz, &token_req, ct)
+            .expect("Failed to perform OAuth2 token exchange");
+        assert!(idms_prox_write.commit().is_ok());
 
-        // First, assert it is valid, similar to the introspect api.
+        // The id_token is a nested JWT, that is signed and then encrypted to the client.
+        let id_token = token_response.id_token.expect("No id_token in response!");
+        let id_token = JweCompact::from_str(&id_token).expect("id_token is not encrypted");
+        let id_token = client_decipher
+            .decipher(&id_token)
+            .expect("Unable to decrypt id_token");
+        let id_token = std::str::from_utf8(id_token.payload()).expect("Invalid id_token payload");
+
+        let oidc = validate_id_token(idms, ct, id_token).await;
+        assert_eq!(oidc.sub, OidcSubject::U(UUID_TESTPERSON_1));
+
+        // Userinfo is encrypted to the client in the same way.
+        let access_token =
+            JwsCompact::from_str(&token_response.access_token).expect("Invalid Access Token");
         let mut idms_prox_read = idms.proxy_read().await.unwrap();
-        let intr_request = AccessTokenIntrospectRequest {
-            token: oauth2_token.access_token.clone(),
-            token_type_hint: None,
-            client_post_auth: ClientPostAuth::default(),
+        let userinfo = idms_prox_read
+            .oauth2_openid_userinfo("test_resource_server", &access_token, None, None, ct)
+            .expect("failed to get userinfo");
+        let UserinfoResponse::Encrypted(userinfo) = idms_prox_read
+            .oauth2_openid_userinfo_response("test_resource_server", userinfo)
+            .expect("failed to encrypt userinfo")
+        else {
+            unreachable!();
+        };
+
+        let userinfo: OidcToken = client_decipher
+            .decipher(&userinfo)
+            .expect("Unable to decrypt userinfo")
+            .from_json()
+            .expect("Invalid userinfo payload");
+        assert_eq!(oidc.s_claims, userinfo.s_claims);
+    }
+
+    #[idm_test]
+    async fn test_idm_oauth2_rich_authorisation_requests(
+        idms: &IdmServer,
+        _idms_delayed: &mut IdmServerDelayed,
+    ) {
+        let ct = Duration::from_secs(TEST_CURRENT_TIME);
+        let (secret, _uat, ident, rs_uuid) =
+            setup_oauth2_resource_server_basic(idms, ct, true, false, false).await;
+        let client_authz = ClientAuthInfo::encode_basic("test_resource_server", secret.as_str());
+
+        let mut idms_prox_write = idms.proxy_write(ct).await.unwrap();
+        idms_prox_write
+            .qs_write
+            .internal_modify_uuid(
+                rs_uuid,
+                &ModifyList::new_purge_and_set(
+                    Attribute::OAuth2RsAuthorisationDetailsType,
+                    Value::new_utf8s("account_information"),
+                ),
+            )
+            .expect("Unable to set authorisation details type");
+        assert!(idms_prox_write.commit().is_ok());
+
+        let account_information: AuthorisationDetail = serde_json::from_value(serde_json::json!({
+            "type": "account_information",
+            "actions": ["list_accounts", "read_balances"],
+            "locations": ["https://example.com/accounts"],
+            "identifier": "account-1234",
+        }))
+        .expect("Invalid authorisation detail");
+
+        let payment_initiation: AuthorisationDetail = serde_json::from_value(serde_json::json!({
+            "type": "payment_initiation",
+            "instructedAmount": {
+                "currency": "EUR",
+                "amount": "123.50"
+            },
+        }))
+        .expect("Invalid authorisation detail");
+
+        let pkce_secret = PkceS256Secret::default();
+        let mut auth_req = AuthorisationRequest {
+            response_type: ResponseType::Code,
+            response_mode: None,
+            client_id: "test_resource_server".to_string(),
+            state: Some("123".to_string()),
+            pkce_request: Some(pkce_secret.to_request()),
+            redirect_uri: Url::parse("https://demo.example.com/oauth2/result").unwrap(),
+            scope: btreeset![OAUTH2_SCOPE_OPENID.to_string()],
+            nonce: Some("abcdef".to_string()),
+            oidc_ext: Default::default(),
+            max_age: None,
+            authorization_details: Some(vec![
+                account_information.clone(),
+                payment_initiation.clone(),
+            ]),
+            unknown_keys: Default::default(),
+        };
+
+        let idms_prox_read = idms.proxy_read().await.unwrap();
+
+        // The client is not allowed to request payment initiation.
+        assert_eq!(
+            idms_prox_read
+                .check_oauth2_authorisation(Some(&ident), &auth_req, ct)
+                .unwrap_err(),
+            Oauth2Error::InvalidAuthorizationDetails
+        );
+
+        auth_req.authorization_details = Some(vec![account_information.clone()]);
+
+        let consent_request = idms_prox_read
+            .check_oauth2_authorisation(Some(&ident), &auth_req, ct)
+            .expect("OAuth2 authorisation failed");
+
+        // The details are shown to the user for consent.
+        let AuthoriseResponse::ConsentRequested {
+            consent_token,
+            authorization_details,
+            ..
+        } = consent_request
+        else {
+            unreachable!();
         };
-        let intr_response = idms_prox_read
-            .check_oauth2_token_introspect(&client_authz, &intr_request, ct)
-            .expect("Failed to inspect token");
-        eprintln!("👉  {intr_response:?}");
-        assert!(intr_response.active);
+        assert_eq!(authorization_details, vec![account_information.clone()]);
         drop(idms_prox_read);
 
-        // First, the revoke needs basic auth. Provide incorrect auth, and we fail.
         let mut idms_prox_write = idms.proxy_write(ct).await.unwrap();
+        let permit_success = idms_prox_write
+            .check_oauth2_authorise_permit(&ident, &consent_token, ct)
+            .expect("Failed to perform OAuth2 permit");
 
-        let bad_client_authz = ClientAuthInfo::encode_basic("test_resource_server", "12345");
+        let token_req: AccessTokenRequest = GrantTypeReq::AuthorizationCode {
+            code: permit_success.code,
+            redirect_uri: Url::parse("https://demo.example.com/oauth2/result").unwrap(),
+            code_verifier: Some(pkce_secret.to_verifier()),
+        }
+        .into();
 
-        let revoke_request = TokenRevokeRequest {
-            token: oauth2_token.access_token.clone(),
-            token_type_hint: None,
-            client_post_auth: ClientPostAuth::default(),
-        };
-        let e = idms_prox_write
-            .oauth2_token_revoke(&bad_client_authz, &revoke_request, ct)
-            .unwrap_err();
-        assert!(matches!(e, Oauth2Error::AuthenticationRequired));
+        let token_response = idms_prox_write
+            .check_oauth2_token_exchange(&client_authz, &token_req, ct)
+            .expect("Failed to perform OAuth2 token exchange");
         assert!(idms_prox_write.commit().is_ok());
 
-        // Now submit a non-existent/invalid token. Does not affect our tokens validity.
-        let mut idms_prox_write = idms.proxy_write(ct).await.unwrap();
-        let revoke_request = TokenRevokeRequest {
-            token: "this is an invalid token, nothing will happen!".to_string(),
-            token_type_hint: None,
-            client_post_auth: ClientPostAuth::default(),
-        };
-        let e = idms_prox_write
-            .oauth2_token_revoke(&client_authz, &revoke_request, ct)
-            .unwrap_err();
-        assert!(matches!(e, Oauth2Error::InvalidRequest));
-        assert!(idms_prox_write.commit().is_ok());
+        assert_eq!(
+            token_response.authorization_details,
+            Some(vec![account_information.clone()])
+        );
 
-        // Check our token is still valid.
+        // The resource server learns of the granted details from introspection.
         let mut idms_prox_read = idms.proxy_read().await.unwrap();
-        let intr_response = idms_prox_read
-            .check_oauth2_token_introspect(&client_authz, &intr_request, ct)
-            .expect("Failed to inspect token");
-        assert!(intr_response.active);
-        drop(idms_prox_read);
-
-        // Finally revoke it.
-        let mut idms_prox_write = idms.proxy_write(ct).await.unwrap();
-        let revoke_request = TokenRevokeRequest {
-            token: oauth2_token.access_token.clone(),
+        let intr_request = AccessTokenIntrospectRequest {
+            token: token_response.access_token,
             token_type_hint: None,
             client_post_auth: ClientPostAuth::default(),
         };
-        assert!(idms_prox_write
-            .oauth2_token_revoke(&client_authz, &revoke_request, ct,)
-            .is_ok());
-        assert!(idms_prox_write.commit().is_ok());
-
-        // Assert it is now invalid.
-        let mut idms_prox_read = idms.proxy_read().await.unwrap();
         let intr_response = idms_prox_read
             .check_oauth2_token_introspect(&client_authz, &intr_request, ct)
             .expect("Failed to inspect token");
 
-        assert!(!intr_response.active);
+        assert!(intr_response.active);
+        assert_eq!(
+            intr_response.authorization_details,
+            Some(vec![account_information.clone()])
+        );
         drop(idms_prox_read);
 
-        // Force trim the session and wait for the grace window to pass. The token will be invalidated
+        // The details are retained when the session is refreshed.
         let mut idms_prox_write = idms.proxy_write(ct).await.unwrap();
-        let filt = filter!(f_eq(
-            Attribute::Uuid,
-            PartialValue::Uuid(ident.get_uuid().unwrap())
-        ));
-        let mut work_set = idms_prox_write
-            .qs_write
-            .internal_search_writeable(&filt)
-            .expect("Failed to perform internal search writeable");
-        for (_, entry) in work_set.iter_mut() {
-            let _ = entry.force_trim_ava(Attribute::OAuth2Session);
+        let token_req: AccessTokenRequest = GrantTypeReq::RefreshToken {
+            refresh_token: token_response
+                .refresh_token
+                .expect("No refresh token in response!"),
+            scope: None,
         }
-        assert!(idms_prox_write
-            .qs_write
-            .internal_apply_writable(work_set)
-            .is_ok());
+        .into();
 
+        let token_response = idms_prox_write
+            .check_oauth2_token_exchange(&client_authz, &token_req, ct)
+            .expect("Unable to refresh OAuth2 token");
         assert!(idms_prox_write.commit().is_ok());
 
-        let mut idms_prox_read = idms.proxy_read().await.unwrap();
-        // Grace window in effect.
-        let intr_response = idms_prox_read
-            .check_oauth2_token_introspect(&client_authz, &intr_request, ct)
-            .expect("Failed to inspect token");
-        assert!(intr_response.active);
-
-        // Grace window passed, it will now be invalid.
-        let ct = ct + AUTH_TOKEN_GRACE_WINDOW;
-        let intr_response = idms_prox_read
-            .check_oauth2_token_introspect(&client_authz, &intr_request, ct)
-            .expect("Failed to inspect token");
-        assert!(!intr_response.active);
-
-        drop(idms_prox_read);
+        assert_eq!(
+            token_response.authorization_details,
+            Some(vec![account_information])
+        );
 
-        // A second invalidation of the token "does nothing".
-        let mut idms_prox_write = idms.proxy_write(ct).await.unwrap();
-        let revoke_request = TokenRevokeRequest {
-            token: oauth2_token.access_token,
-            token_type_hint: None,
-            client_post_auth: ClientPostAuth::default(),
-        };
-        assert!(idms_prox_write
-            .oauth2_token_revoke(&client_authz, &revoke_request, ct,)
-            .is_ok());
-        assert!(idms_prox_write.commit().is_ok());
+        // Discovery advertises the types the client may request.
+        let idms_prox_read = idms.proxy_read().await.unwrap();
+        let discovery = idms_prox_read
+            .oauth2_openid_discovery("test_resource_server")
+            .expect("Failed to get discovery");
+        assert_eq!(
+            discovery.authorization_details_types_supported,
+            Some(vec!["account_information".to_string()])
+        );
     }
 
     #[idm_test]
-    async fn test_idm_oauth2_session_cleanup_post_rs_delete(
+    async fn test_idm_oauth2_backchannel_authentication(
         idms: &IdmServer,
         _idms_delayed: &mut IdmServerDelayed,
     ) {
-        // First, setup to get a token.
         let ct = Duration::from_secs(TEST_CURRENT_TIME);
-        let (secret, _uat, ident, _) =
+        let (secret, _uat, ident, rs_uuid) =
             setup_oauth2_resource_server_basic(idms, ct, true, false, false).await;
         let client_authz = ClientAuthInfo::encode_basic("test_resource_server", secret.as_str());
+        let client_post_auth = ClientPostAuth::default();
 
-        let idms_prox_read = idms.proxy_read().await.unwrap();
-
-        // == Setup the authorisation request
-        let pkce_secret = PkceS256Secret::default();
-
-        let consent_request = good_authorisation_request!(
-            idms_prox_read,
-            &ident,
-            ct,
-            pkce_secret.to_request(),
-            OAUTH2_SCOPE_OPENID.to_string()
-        );
-
-        let AuthoriseResponse::ConsentRequested { consent_token, .. } = consent_request else {
-            unreachable!();
+        let mut bc_req = BackchannelAuthenticationRequest {
+            scope: btreeset![OAUTH2_SCOPE_OPENID.to_string()],
+            login_hint: Some("testperson1".to_string()),
+            binding_message: Some("W4SCT".to_string()),
+            ..Default::default()
         };
 
-        // == Manually submit the consent token to the permit for the permit_success
-        drop(idms_prox_read);
+        // The client has not been allowed to use backchannel authentication.
         let mut idms_prox_write = idms.proxy_write(ct).await.unwrap();
+        assert_eq!(
+            idms_prox_write
+                .oauth2_backchannel_authentication(&client_authz, &client_post_auth, &bc_req, ct)
+                .unwrap_err(),
+            Oauth2Error::UnauthorizedClient
+        );
 
-        let permit_success = idms_prox_write
-            .check_oauth2_authorise_permit(&ident, &consent_token, ct)
-            .expect("Failed to perform OAuth2 permit");
-
-        let token_req: AccessTokenRequest = GrantTypeReq::AuthorizationCode {
-            code: permit_success.code,
-            redirect_uri: Url::parse("https://demo.example.com/oauth2/result").unwrap(),
-            code_verifier: Some(pkce_secret.to_verifier()),
-        }
-        .into();
+        idms_prox_write
+            .qs_write
+            .internal_modify_uuid(
+                rs_uuid,
+                &ModifyList::new_purge_and_set(Attribute::OAuth2CibaEnable, Value::new_bool(true)),
+            )
+            .expect("Unable to enable backchannel authentication");
+        assert!(idms_prox_write.commit().is_ok());
 
-        let oauth2_token = idms_prox_write
-            .check_oauth2_token_exchange(&client_authz, &token_req, ct)
-            .expect("Unable to exchange for OAuth2 token");
+        let mut idms_prox_write = idms.proxy_write(ct).await.unwrap();
 
-        let access_token =
-            JwsCompact::from_str(&oauth2_token.access_token).expect("Invalid Access Token");
+        // The openid scope is required.
+        let mut bad_req = bc_req.clone();
+        bad_req.scope = btreeset![OAUTH2_SCOPE_GROUPS.to_string()];
+        assert_eq!(
+            idms_prox_write
+                .oauth2_backchannel_authentication(&client_authz, &client_post_auth, &bad_req, ct)
+                .unwrap_err(),
+            Oauth2Error::InvalidScope
+        );
 
-        let jws_verifier = JwsDangerReleaseWithoutVerify::default();
+        // The login hint must name a person.
+        let mut bad_req = bc_req.clone();
+        bad_req.login_hint = Some("test_resource_server".to_string());
+        assert_eq!(
+            idms_prox_write
+                .oauth2_backchannel_authentication(&client_authz, &client_post_auth, &bad_req, ct)
+                .unwrap_err(),
+            Oauth2Error::UnknownUserId
+        );
 
-        let reflected_token = jws_verifier
-            .verify(&access_token)
-            .unwrap()
-            .from_json::<OAuth2RFC9068Token<OAuth2RFC9068TokenExtensions>>()
-            .expect("Failed to access internals of the refresh token");
+        // Binding messages must be short enough to show on the device.
+        let mut bad_req = bc_req.clone();
+        bad_req.binding_message = Some("a".repeat(OAUTH2_BACKCHANNEL_BINDING_MESSAGE_MAX_LEN + 1));
+        assert_eq!(
+            idms_prox_write
+                .oauth2_backchannel_authentication(&client_authz, &client_post_auth, &bad_req, ct)
+                .unwrap_err(),
+            Oauth2Error::InvalidBindingMessage
+        );
 
-        let session_id = reflected_token.extensions.session_id;
+        let bc_res = idms_prox_write
+            .oauth2_backchannel_authentication(&client_authz, &client_post_auth, &bc_req, ct)
+            .expect("Failed to start backchannel authentication");
+        assert_eq!(bc_res.expires_in, OAUTH2_DEVICE_CODE_EXPIRY_SECONDS);
+        assert_eq!(bc_res.interval, OAUTH2_DEVICE_CODE_INTERVAL_SECONDS);
 
+        // The client polls until the user has decided.
+        let token_req: AccessTokenRequest = GrantTypeReq::Ciba {
+            auth_req_id: bc_res.auth_req_id.clone(),
+        }
+        .into();
+        assert_eq!(
+            idms_prox_write
+                .check_oauth2_token_exchange(&client_authz, &token_req, ct)
+                .unwrap_err(),
+            Oauth2Error::AuthorizationPending
+        );
         assert!(idms_prox_write.commit().is_ok());
 
-        // Process it to ensure the record exists.
+        // The request is shown to the user.
+        let idms_prox_read = idms.proxy_read().await.unwrap();
+        let pending = idms_prox_read.list_oauth2_backchannel_authorisations(&ident, ct);
+        assert_eq!(pending.len(), 1);
+        let auth_req_id = pending[0].auth_req_id;
+        assert_eq!(auth_req_id.to_string(), bc_res.auth_req_id);
+        assert_eq!(pending[0].binding_message.as_deref(), Some("W4SCT"));
+        drop(idms_prox_read);
+
         let mut idms_prox_write = idms.proxy_write(ct).await.unwrap();
+        idms_prox_write
+            .oauth2_backchannel_authorisation_decide(&ident, auth_req_id, true, ct)
+            .expect("Failed to approve backchannel authentication");
 
-        // Check it is now there
-        let entry = idms_prox_write
-            .qs_write
-            .internal_search_uuid(UUID_TESTPERSON_1)
-            .expect("failed");
-        let valid = entry
-            .get_ava_as_oauth2session_map(Attribute::OAuth2Session)
-            .map(|map| map.get(&session_id).is_some())
-            .unwrap_or(false);
-        assert!(valid);
+        // A request can only be decided once.
+        assert_eq!(
+            idms_prox_write.oauth2_backchannel_authorisation_decide(&ident, auth_req_id, false, ct),
+            Err(OperationError::InvalidRequestState)
+        );
 
-        // Delete the resource server.
+        let token_response = idms_prox_write
+            .check_oauth2_token_exchange(&client_authz, &token_req, ct)
+            .expect("Failed to exchange backchannel authentication");
+        assert!(token_response.id_token.is_some());
+        assert!(token_response.refresh_token.is_some());
 
-        let de = DeleteEvent::new_internal_invalid(filter!(f_eq(
-            Attribute::Name,
-            PartialValue::new_iname("test_resource_server")
-        )));
+        // The auth_req_id can only be exchanged once.
+        assert_eq!(
+            idms_prox_write
+                .check_oauth2_token_exchange(&client_authz, &token_req, ct)
+                .unwrap_err(),
+            Oauth2Error::InvalidGrant
+        );
+        assert!(idms_prox_write.commit().is_ok());
 
-        assert!(idms_prox_write.qs_write.delete(&de).is_ok());
+        // The issued token is valid for the user.
+        let mut idms_prox_read = idms.proxy_read().await.unwrap();
+        let intr_request = AccessTokenIntrospectRequest {
+            token: token_response.access_token,
+            token_type_hint: None,
+            client_post_auth: ClientPostAuth::default(),
+        };
+        let intr_response = idms_prox_read
+            .check_oauth2_token_introspect(&client_authz, &intr_request, ct)
+            .expect("Failed to inspect token");
+        assert!(intr_response.active);
+        assert_eq!(intr_response.sub, Some(UUID_TESTPERSON_1.to_string()));
+        drop(idms_prox_read);
 
-        // Assert the session is revoked. This is cleaned up as an artifact of the referential
-        // integrity plugin. Remember, refint doesn't consider revoked sessions once they are
-        // revoked.
-        let entry = idms_prox_write
-            .qs_write
-            .internal_search_uuid(UUID_TESTPERSON_1)
-            .expect("failed");
-        let revoked = entry
-            .get_ava_as_oauth2session_map(Attribute::OAuth2Session)
-            .and_then(|sessions| sessions.get(&session_id))
-            .map(|session| matches!(session.state, SessionState::RevokedAt(_)))
-            .unwrap_or(false);
-        assert!(revoked);
+        // A denied request is reported to the client.
+        bc_req.binding_message = None;
+        let mut idms_prox_write = idms.proxy_write(ct).await.unwrap();
+        let bc_res = idms_prox_write
+            .oauth2_backchannel_authentication(&client_authz, &client_post_auth, &bc_req, ct)
+            .expect("Failed to start backchannel authentication");
+        let auth_req_id = Uuid::parse_str(&bc_res.auth_req_id).expect("Invalid auth_req_id");
+        idms_prox_write
+            .oauth2_backchannel_authorisation_decide(&ident, auth_req_id, false, ct)
+            .expect("Failed to deny backchannel authentication");
 
+        let token_req: AccessTokenRequest = GrantTypeReq::Ciba {
+            auth_req_id: bc_res.auth_req_id,
+        }
+        .into();
+        assert_eq!(
+            idms_prox_write
+                .check_oauth2_token_exchange(&client_authz, &token_req, ct)
+                .unwrap_err(),
+            Oauth2Error::AccessDenied
+        );
         assert!(idms_prox_write.commit().is_ok());
+
+        // Discovery advertises the backchannel endpoint.
+        let idms_prox_read = idms.proxy_read().await.unwrap();
+        let discovery = idms_prox_read
+            .oauth2_openid_discovery("test_resource_server")
+            .expect("Failed to get discovery");
+        assert!(discovery.grant_types_supported.contains(&GrantType::Ciba));
+        assert_eq!(
+            discovery.backchannel_token_delivery_modes_supported,
+            Some(vec![BackchannelTokenDeliveryMode::Poll])
+        );
+        assert!(discovery.backchannel_authentication_endpoint.is_some());
     }
 
     #[idm_test]
-    async fn test_idm_oauth2_authorisation_reject(
+    async fn test_idm_oauth2_consent_list_and_revoke(
         idms: &IdmServer,
         _idms_delayed: &mut IdmServerDelayed,
     ) {
         let ct = Duration::from_secs(TEST_CURRENT_TIME);
-        let (_secret, _uat, ident, _) =
+        let (secret, uat, ident, _) =
             setup_oauth2_resource_server_basic(idms, ct, true, false, false).await;
+        let client_authz = ClientAuthInfo::encode_basic("test_resource_server", secret.as_str());
 
-        let ident2 = {
-            let mut idms_prox_write = idms.proxy_write(ct).await.unwrap();
-            let account = idms_prox_write
-                .target_to_account(UUID_IDM_ADMIN)
-                .expect("account must exist");
-            let session_id = uuid::Uuid::new_v4();
-            let uat2 = account
-                .to_userauthtoken(
-                    session_id,
-                    SessionScope::ReadWrite,
-                    ct,
-                    &ResolvedAccountPolicy::test_policy(),
-                )
-                .expect("Unable to create uat");
-
-            idms_prox_write
-                .process_uat_to_identity(&uat2, ct, Source::Internal)
-                .expect("Unable to process uat")
-        };
-
+        // No consent has been given yet.
         let idms_prox_read = idms.proxy_read().await.unwrap();
-        let redirect_uri = Url::parse("https://demo.example.com/oauth2/result").unwrap();
-
-        let pkce_secret = PkceS256Secret::default();
+        let consents = idms_prox_read
+            .list_oauth2_consents(&ident, ct)
+            .expect("Unable to list consents");
+        assert!(consents.is_empty());
+        drop(idms_prox_read);
 
-        // Check reject behaviour
-        let consent_request = good_authorisation_request!(
-            idms_prox_read,
+        let token_response = perform_oauth2_exchange(
+            idms,
             &ident,
             ct,
-            pkce_secret.to_request(),
-            OAUTH2_SCOPE_OPENID.to_string()
-        );
-
-        let AuthoriseResponse::ConsentRequested { consent_token, .. } = consent_request else {
-            unreachable!();
-        };
+            client_authz.clone(),
+            format!("{OAUTH2_SCOPE_OPENID} {OAUTH2_SCOPE_GROUPS}"),
+        )
+        .await;
 
-        let reject_success = idms_prox_read
-            .check_oauth2_authorise_reject(&ident, &consent_token, ct)
-            .expect("Failed to perform OAuth2 reject");
+        // We need to reload our identity to see the consent.
+        let mut idms_prox_read = idms.proxy_read().await.unwrap();
+        let ident = idms_prox_read
+            .process_uat_to_identity(&uat, ct, Source::Internal)
+            .expect("Unable to process uat");
 
-        assert_eq!(reject_success.redirect_uri, redirect_uri);
+        let consents = idms_prox_read
+            .list_oauth2_consents(&ident, ct)
+            .expect("Unable to list consents");
+        assert_eq!(consents.len(), 1);
+        assert_eq!(consents[0].name, "test_resource_server");
+        assert!(consents[0].scopes.contains(OAUTH2_SCOPE_OPENID));
+        assert!(consents[0].scopes.contains(OAUTH2_SCOPE_GROUPS));
+        assert_eq!(consents[0].sessions, 1);
+        assert_eq!(
+            consents[0].last_authorised,
+            Some(OffsetDateTime::UNIX_EPOCH + ct)
+        );
+        drop(idms_prox_read);
 
-        // Too much time past to reject
-        let past_ct = Duration::from_secs(TEST_CURRENT_TIME + 301);
-        assert!(
-            idms_prox_read
-                .check_oauth2_authorise_reject(&ident, &consent_token, past_ct)
-                .unwrap_err()
-                == OperationError::CryptographyError
+        // Revoking an unknown client fails.
+        let mut idms_prox_write = idms.proxy_write(ct).await.unwrap();
+        assert_eq!(
+            idms_prox_write.oauth2_consent_revoke(&ident, "not_a_client"),
+            Err(OperationError::NoMatchingEntries)
         );
 
-        // Invalid consent token
+        idms_prox_write
+            .oauth2_consent_revoke(&ident, "test_resource_server")
+            .expect("Unable to revoke consent");
+        assert!(idms_prox_write.commit().is_ok());
+
+        // The refresh token can no longer be used.
+        let mut idms_prox_write = idms.proxy_write(ct).await.unwrap();
+        let token_req: AccessTokenRequest = GrantTypeReq::RefreshToken {
+            refresh_token: token_response
+                .refresh_token
+                .expect("No refresh token in response!"),
+            scope: None,
+        }
+        .into();
         assert_eq!(
-            idms_prox_read
-                .check_oauth2_authorise_reject(&ident, "not a token", ct)
+            idms_prox_write
+                .check_oauth2_token_exchange(&client_authz, &token_req, ct)
                 .unwrap_err(),
-            OperationError::CryptographyError
+            Oauth2Error::InvalidGrant
         );
+        drop(idms_prox_write);
 
-        // Wrong ident
-        assert!(
-            idms_prox_read
-                .check_oauth2_authorise_reject(&ident2, &consent_token, ct)
-                .unwrap_err()
-                == OperationError::InvalidSessionState
+        // The client is no longer listed, and consent is requested again.
+        let mut idms_prox_read = idms.proxy_read().await.unwrap();
+        let ident = idms_prox_read
+            .process_uat_to_identity(&uat, ct, Source::Internal)
+            .expect("Unable to process uat");
+
+        let consents = idms_prox_read
+            .list_oauth2_consents(&ident, ct)
+            .expect("Unable to list consents");
+        assert!(consents.is_empty());
+
+        let pkce_secret = PkceS256Secret::default();
+        let consent_request = good_authorisation_request!(
+            idms_prox_read,
+            &ident,
+            ct,
+            pkce_secret.to_request(),
+            format!("{OAUTH2_SCOPE_OPENID} {OAUTH2_SCOPE_GROUPS}")
         );
+        assert!(matches!(
+            consent_request,
+            AuthoriseResponse::ConsentRequested { .. }
+        ));
     }
 
     #[idm_test]
-    async fn test_idm_oauth2_rfc8414_metadata(
+    async fn test_idm_oauth2_pairwise_subject(
         idms: &IdmServer,
         _idms_delayed: &mut IdmServerDelayed,
     ) {
         let ct = Duration::from_secs(TEST_CURRENT_TIME);
-        let (_secret, _uat, _ident, _) =
+        let (secret, _uat, ident, rs_uuid) =
             setup_oauth2_resource_server_basic(idms, ct, true, false, false).await;
+        let client_authz = ClientAuthInfo::encode_basic("test_resource_server", secret.as_str());
 
-        let idms_prox_read = idms.proxy_read().await.unwrap();
-
-        // check the discovery end point works as we expect
-        assert!(
-            idms_prox_read
-                .oauth2_rfc8414_metadata("nosuchclient")
-                .unwrap_err()
-                == OperationError::NoMatchingEntries
-        );
+        let mut idms_prox_write = idms.proxy_write(ct).await.unwrap();
+        idms_prox_write
+            .qs_write
+            .internal_modify_uuid(
+                rs_uuid,
+                &ModifyList::new_purge_and_set(
+                    Attribute::OAuth2PairwiseSubjectEnable,
+                    Value::new_bool(true),
+                ),
+            )
+            .expect("Unable to enable pairwise subjects");
+        assert!(idms_prox_write.commit().is_ok());
 
+        let idms_prox_read = idms.proxy_read().await.unwrap();
         let discovery = idms_prox_read
-            .oauth2_rfc8414_metadata("test_resource_server")
+            .oauth2_openid_discovery("test_resource_server")
             .expect("Failed to get discovery");
-
-        assert!(
-            discovery.issuer
-                == Url::parse("https://idm.example.com/oauth2/openid/test_resource_server")
-                    .unwrap()
-        );
-
-        assert!(
-            discovery.authorization_endpoint
-                == Url::parse("https://idm.example.com/ui/oauth2").unwrap()
-        );
-
-        assert!(
-            discovery.token_endpoint
-                == Url::parse(&format!(
-                    "https://idm.example.com{}",
-                    uri::OAUTH2_TOKEN_ENDPOINT
-                ))
-                .unwrap()
-        );
-
-        assert!(
-            discovery.jwks_uri
-                == Some(
-                    Url::parse(
-                        "https://idm.example.com/oauth2/openid/test_resource_server/public_key.jwk"
-                    )
-                    .unwrap()
-                )
+        assert_eq!(
+            discovery.subject_types_supported,
+            vec![SubjectType::Pairwise]
         );
+        drop(idms_prox_read);
 
-        assert!(discovery.registration_endpoint.is_none());
-
-        assert!(
-            discovery.scopes_supported
-                == Some(vec![
-                    OAUTH2_SCOPE_GROUPS.to_string(),
-                    OAUTH2_SCOPE_OPENID.to_string(),
-                    OAUTH2_SCOPE_PROFILE.to_string(),
-                    "supplement".to_string(),
-                ])
-        );
+        let token_response = perform_oauth2_exchange(
+            idms,
+            &ident,
+            ct,
+            client_authz.clone(),
+            OAUTH2_SCOPE_OPENID.to_string(),
+        )
+        .await;
 
-        assert_eq!(discovery.response_types_supported, vec![ResponseType::Code]);
-        assert_eq!(
-            discovery.response_modes_supported,
-            vec![ResponseMode::Query, ResponseMode::Fragment]
-        );
-        assert_eq!(
-            discovery.grant_types_supported,
-            vec![GrantType::AuthorisationCode, GrantType::TokenExchange]
-        );
-        assert!(
-            discovery.token_endpoint_auth_methods_supported
-                == vec![
-                    TokenEndpointAuthMethod::ClientSecretBasic,
-                    TokenEndpointAuthMethod::ClientSecretPost
-                ]
-        );
-        assert!(discovery.service_documentation.is_some());
+        // The client is not given the account uuid.
+        let oidc = validate_id_token(
+            idms,
+            ct,
+            token_response.id_token.as_deref().expect("No id_token"),
+        )
+        .await;
+        let OidcSubject::U(pairwise_sub) = oidc.sub else {
+            unreachable!();
+        };
+        assert_ne!(pairwise_sub, UUID_TESTPERSON_1);
 
-        assert!(discovery.ui_locales_supported.is_none());
-        assert!(discovery.op_policy_uri.is_none());
-        assert!(discovery.op_tos_uri.is_none());
+        // The subject is the same from userinfo and introspection.
+        let access_token =
+            JwsCompact::from_str(&token_response.access_token).expect("Invalid Access Token");
 
-        assert!(
-            discovery.revocation_endpoint
-                == Some(
-                    Url::parse(&format!(
-                        "https://idm.example.com{OAUTH2_TOKEN_REVOKE_ENDPOINT}"
-                    ))
-                    .unwrap()
-                )
-        );
-        assert!(
-            discovery.revocation_endpoint_auth_methods_supported
-                == vec![
-                    TokenEndpointAuthMethod::ClientSecretBasic,
-                    TokenEndpointAuthMethod::ClientSecretPost
-                ]
-        );
+        let mut idms_prox_read = idms.proxy_read().await.unwrap();
+        let userinfo = idms_prox_read
+            .oauth2_openid_userinfo("test_resource_server", &access_token, None, None, ct)
+            .expect("failed to get userinfo");
+        assert_eq!(userinfo.sub, OidcSubject::U(pairwise_sub));
 
-        assert!(
-            discovery.introspection_endpoint
-                == Some(
-                    Url::parse(&format!(
-                        "https://idm.example.com{}",
-                        kanidm_proto::constants::uri::OAUTH2_TOKEN_INTROSPECT_ENDPOINT
-                    ))
-                    .unwrap()
-                )
-        );
-        assert!(
-            discovery.introspection_endpoint_auth_methods_supported
-                == vec![
-                    TokenEndpointAuthMethod::ClientSecretBasic,
-                    TokenEndpointAuthMethod::ClientSecretPost
-                ]
-        );
-        assert!(discovery
-            .introspection_endpoint_auth_signing_alg_values_supported
-            .is_none());
+        let intr_request = AccessTokenIntrospectRequest {
+            token: token_response.access_token.clone(),
+            token_type_hint: None,
+            client_post_auth: ClientPostAuth::default(),
+        };
+        let intr_response = idms_prox_read
+            .check_oauth2_token_introspect(&client_authz, &intr_request, ct)
+            .expect("Failed to inspect token");
+        assert!(intr_response.active);
+        assert_eq!(intr_response.sub, Some(pairwise_sub.to_string()));
+        drop(idms_prox_read);
 
-        assert_eq!(
-            discovery.code_challenge_methods_supported,
-            vec![PkceAlg::S256]
+        // The subject is stable across a refresh.
+        let mut idms_prox_write = idms.proxy_write(ct).await.unwrap();
+        let token_req: AccessTokenRequest = GrantTypeReq::RefreshToken {
+            refresh_token: token_response
+                .refresh_token
+                .expect("No refresh token in response!"),
+            scope: None,
+        }
+        .into();
+        let refresh_response = idms_prox_write
+            .check_oauth2_token_exchange(&client_authz, &token_req, ct)
+            .expect("Failed to refresh token");
+        assert!(idms_prox_write.commit().is_ok());
+
+        let oidc = validate_id_token(
+            idms,
+            ct,
+            refresh_response.id_token.as_deref().expect("No id_token"),
         )
+        .await;
+        assert_eq!(oidc.sub, OidcSubject::U(pairwise_sub));
+
+        // A token with a pairwise subject can still be revoked.
+        let mut idms_prox_write = idms.proxy_write(ct).await.unwrap();
+        let revoke_request = TokenRevokeRequest {
+            token: refresh_response.access_token.clone(),
+            token_type_hint: None,
+            client_post_auth: ClientPostAuth::default(),
+        };
+        idms_prox_write
+            .oauth2_token_revoke(&client_authz, &revoke_request, ct)
+            .expect("Failed to revoke token");
+        assert!(idms_prox_write.commit().is_ok());
+
+        let mut idms_prox_read = idms.proxy_read().await.unwrap();
+        let intr_request = AccessTokenIntrospectRequest {
+            token: refresh_response.access_token,
+            token_type_hint: None,
+            client_post_auth: ClientPostAuth::default(),
+        };
+        let intr_response = idms_prox_read
+            .check_oauth2_token_introspect(&client_authz, &intr_request, ct)
+            .expect("Failed to inspect token");
+        assert!(!intr_response.active);
     }
 
     #[idm_test]
-    async fn test_idm_oauth2_openid_discovery(
+    async fn test_idm_oauth2_openid_group_claims(
         idms: &IdmServer,
         _idms_delayed: &mut IdmServerDelayed,
     ) {
+        // we run the same test as test_idm_oauth2_openid_extensions()
+        // but change the preferred_username setting on the RS
         let ct = Duration::from_secs(TEST_CURRENT_TIME);
-        let (_secret, _uat, _ident, _) =
-            setup_oauth2_resource_server_basic(idms, ct, true, false, false).await;
+        let (secret, _uat, ident, _) =
+            setup_oauth2_resource_server_basic(idms, ct, true, false, true).await;
 
-        let idms_prox_read = idms.proxy_read().await.unwrap();
+        let client_authz = ClientAuthInfo::encode_basic("test_resource_server", secret.as_str());
 
-        // check the discovery end point works as we expect
-        assert!(
-            idms_prox_read
-                .oauth2_openid_discovery("nosuchclient")
-                .unwrap_err()
-                == OperationError::NoMatchingEntries
-        );
+        let token_response = perform_oauth2_exchange(
+            idms,
+            &ident,
+            ct,
+            client_authz,
+            format!("{OAUTH2_SCOPE_OPENID} {OAUTH2_SCOPE_GROUPS}"),
+        )
+        .await;
 
-        assert!(
-            idms_prox_read
-                .oauth2_openid_publickey("nosuchclient")
-                .unwrap_err()
-                == OperationError::NoMatchingEntries
-        );
+        let id_token = token_response.id_token.expect("No id_token in response!");
+        let access_token =
+            JwsCompact::from_str(&token_response.access_token).expect("Invalid Access Token");
 
-        let discovery = idms_prox_read
-            .oauth2_openid_discovery("test_resource_server")
-            .expect("Failed to get discovery");
+        let oidc = validate_id_token(idms, ct, &id_token).await;
 
-        let mut jwkset = idms_prox_read
-            .oauth2_openid_publickey("test_resource_server")
-            .expect("Failed to get public key");
+        // does our id_token contain the expected groups?
+        assert!(oidc.claims.contains_key("groups"));
 
-        let jwk = jwkset.keys.pop().expect("no such jwk");
+        assert!(oidc
+            .claims
+            .get("groups")
+            .expect("unable to find key")
+            .as_array()
+            .unwrap()
+            .contains(&serde_json::json!(STR_UUID_IDM_ALL_ACCOUNTS)));
 
-        match jwk {
-            Jwk::EC { alg, use_, kid, .. } => {
-                match (
-                    alg.unwrap(),
-                    &discovery.id_token_signing_alg_values_supported[0],
-                ) {
-                    (JwaAlg::ES256, IdTokenSignAlg::ES256) => {}
-                    _ => panic!(),
-                };
-                assert_eq!(use_.unwrap(), JwkUse::Sig);
-                assert!(kid.is_some())
-            }
-            _ => panic!(),
-        };
+        let mut idms_prox_read = idms.proxy_read().await.unwrap();
 
-        assert!(
-            discovery.issuer
-                == Url::parse("https://idm.example.com/oauth2/openid/test_resource_server")
-                    .unwrap()
-        );
+        // Do the id_token details line up to the userinfo?
+        let userinfo = idms_prox_read
+            .oauth2_openid_userinfo("test_resource_server", &access_token, None, None, ct)
+            .expect("failed to get userinfo");
 
-        assert!(
-            discovery.authorization_endpoint
-                == Url::parse("https://idm.example.com/ui/oauth2").unwrap()
-        );
+        // does the userinfo endpoint provide the same groups?
+        assert_eq!(oidc.claims.get("groups"), userinfo.claims.get("groups"));
+    }
 
-        assert!(
-            discovery.token_endpoint == Url::parse("https://idm.example.com/oauth2/token").unwrap()
-        );
+    #[idm_test]
+    async fn test_idm_oauth2_openid_group_extended_claims(
+        idms: &IdmServer,
+        _idms_delayed: &mut IdmServerDelayed,
+    ) {
+        // we run the same test as test_idm_oauth2_openid_extensions()
+        // but change the preferred_username setting on the RS
+        let ct = Duration::from_secs(TEST_CURRENT_TIME);
+        let (secret, _uat, ident, oauth2_client_uuid) =
+            setup_oauth2_resource_server_basic(idms, ct, true, false, true).await;
 
-        assert!(
-            discovery.userinfo_endpoint
-                == Some(
-                    Url::parse(
-                        "https://idm.example.com/oauth2/openid/test_resource_server/userinfo"
-                    )
-                    .unwrap()
-                )
-        );
+        // Modify the oauth2 client to have different scope maps.
+        let mut idms_prox_write = idms.proxy_write(ct).await.unwrap();
 
-        assert!(
-            discovery.jwks_uri
-                == Url::parse(
-                    "https://idm.example.com/oauth2/openid/test_resource_server/public_key.jwk"
+        let modlist = ModifyList::new_list(vec![
+            Modify::Removed(
+                Attribute::OAuth2RsScopeMap,
+                PartialValue::Refer(UUID_TESTGROUP),
+            ),
+            Modify::Present(
+                Attribute::OAuth2RsScopeMap,
+                Value::new_oauthscopemap(
+                    UUID_TESTGROUP,
+                    btreeset![OAUTH2_SCOPE_GROUPS_NAME.to_string()],
                 )
-                .unwrap()
-        );
+                .expect("invalid oauthscope"),
+            ),
+        ]);
 
-        assert!(
-            discovery.scopes_supported
-                == Some(vec![
-                    OAUTH2_SCOPE_GROUPS.to_string(),
-                    OAUTH2_SCOPE_OPENID.to_string(),
-                    OAUTH2_SCOPE_PROFILE.to_string(),
-                    "supplement".to_string(),
-                ])
-        );
+        idms_prox_write
+            .qs_write
+            .internal_modify_uuid(oauth2_client_uuid, &modlist)
+            .expect("Failed to modify scopes");
 
-        assert_eq!(discovery.response_types_supported, vec![ResponseType::Code]);
-        assert_eq!(
-            discovery.response_modes_supported,
-            vec![ResponseMode::Query, ResponseMode::Fragment]
-        );
-        assert_eq!(
-            discovery.grant_types_supported,
-            vec![GrantType::AuthorisationCode, GrantType::TokenExchange]
-        );
-        assert_eq!(discovery.subject_types_supported, vec![SubjectType::Public]);
-        assert_eq!(
-            discovery.id_token_signing_alg_values_supported,
-            vec![IdTokenSignAlg::ES256]
-        );
-        assert!(discovery.userinfo_signing_alg_values_supported.is_none());
-        assert!(
-            discovery.token_endpoint_auth_methods_supported
-                == vec![
-                    TokenEndpointAuthMethod::ClientSecretBasic,
-                    TokenEndpointAuthMethod::ClientSecretPost
-                ]
-        );
-        assert_eq!(
-            discovery.display_values_supported,
-            Some(vec![DisplayValue::Page])
-        );
-        assert_eq!(discovery.claim_types_supported, vec![ClaimType::Normal]);
-        assert!(discovery.claims_supported.is_none());
-        assert!(discovery.service_documentation.is_some());
+        idms_prox_write.commit().expect("failed to commit");
 
-        assert!(discovery.registration_endpoint.is_none());
-        assert!(discovery.acr_values_supported.is_none());
-        assert!(discovery.id_token_encryption_alg_values_supported.is_none());
-        assert!(discovery.id_token_encryption_enc_values_supported.is_none());
-        assert!(discovery.userinfo_encryption_alg_values_supported.is_none());
-        assert!(discovery.userinfo_encryption_enc_values_supported.is_none());
-        assert!(discovery
-            .request_object_signing_alg_values_supported
-            .is_none());
-        assert!(discovery
-            .request_object_encryption_alg_values_supported
-            .is_none());
-        assert!(discovery
-            .request_object_encryption_enc_values_supported
-            .is_none());
-        assert!(discovery
-            .token_endpoint_auth_signing_alg_values_supported
-            .is_none());
-        assert!(discovery.claims_locales_supported.is_none());
-        assert!(discovery.ui_locales_supported.is_none());
-        assert!(discovery.op_policy_uri.is_none());
-        assert!(discovery.op_tos_uri.is_none());
-        assert!(!discovery.claims_parameter_supported);
-        assert!(!discovery.request_uri_parameter_supported);
-        assert!(!discovery.require_request_uri_registration);
-        assert!(!discovery.request_parameter_supported);
-        assert_eq!(
-            discovery.code_challenge_methods_supported,
-            vec![PkceAlg::S256]
-        );
+        // Now actually do the test.
+        let client_authz = ClientAuthInfo::encode_basic("test_resource_server", secret.as_str());
 
-        // Extensions
-        assert!(
-            discovery.revocation_endpoint
-                == Some(
-                    Url::parse(&format!(
-                        "https://idm.example.com{OAUTH2_TOKEN_REVOKE_ENDPOINT}"
-                    ))
-                    .unwrap()
-                )
-        );
-        assert!(
-            discovery.revocation_endpoint_auth_methods_supported
-                == vec![
-                    TokenEndpointAuthMethod::ClientSecretBasic,
-                    TokenEndpointAuthMethod::ClientSecretPost
-                ]
-        );
+        let token_response = perform_oauth2_exchange(
+            idms,
+            &ident,
+            ct,
+            client_authz,
+            format!("{OAUTH2_SCOPE_OPENID} {OAUTH2_SCOPE_GROUPS_NAME}"),
+        )
+        .await;
+
+        let id_token = token_response.id_token.expect("No id_token in response!");
+        let access_token =
+            JwsCompact::from_str(&token_response.access_token).expect("Invalid Access Token");
+
+        let oidc = validate_id_token(idms, ct, &id_token).await;
 
-        assert!(
-            discovery.introspection_endpoint
-                == Some(
-                    Url::parse(&format!(
-                        "https://idm.example.com{OAUTH2_TOKEN_INTROSPECT_ENDPOINT}"
-                    ))
-                    .unwrap()
-                )
-        );
-        assert!(
-            discovery.introspection_endpoint_auth_methods_supported
-                == vec![
-                    TokenEndpointAuthMethod::ClientSecretBasic,
-                    TokenEndpointAuthMethod::ClientSecretPost
-                ]
-        );
-        assert!(discovery
-            .introspection_endpoint_auth_signing_alg_values_supported
-            .is_none());
+        // does our id_token contain the expected groups?
+        assert!(oidc.claims.contains_key("groups"));
+
+        assert!(oidc
+            .claims
+            .get("groups")
+            .expect("unable to find key")
+            .as_array()
+            .unwrap()
+            .contains(&serde_json::json!("testgroup")));
+
+        let mut idms_prox_read = idms.proxy_read().await.unwrap();
+
+        // Do the id_token details line up to the userinfo?
+        let userinfo = idms_prox_read
+            .oauth2_openid_userinfo("test_resource_server", &access_token, None, None, ct)
+            .expect("failed to get userinfo");
+
+        // does the userinfo endpoint provide the same groups?
+        assert_eq!(oidc.claims.get("groups"), userinfo.claims.get("groups"));
     }
 
     #[idm_test]
-    async fn test_idm_oauth2_openid_extensions(
+    async fn test_idm_oauth2_openid_ssh_publickey_claim(
         idms: &IdmServer,
         _idms_delayed: &mut IdmServerDelayed,
     ) {
         let ct = Duration::from_secs(TEST_CURRENT_TIME);
-        let (secret, _uat, ident, _) =
-            setup_oauth2_resource_server_basic(idms, ct, true, false, false).await;
+        let (secret, _uat, ident, client_uuid) =
+            setup_oauth2_resource_server_basic(idms, ct, true, false, true).await;
+
+        // Extra setup for our test - add the correct claim and give an ssh publickey
+        // to our testperson
+        const ECDSA_SSH_PUBLIC_KEY: &str = "ecdsa-sha2-nistp521 AAAAE2VjZHNhLXNoYTItbmlzdHA1MjEAAAAIbmlzdHA1MjEAAACFBAGyIY7o3BtOzRiJ9vvjj96bRImwmyy5GvFSIUPlK00HitiAWGhiO1jGZKmK7220Oe4rqU3uAwA00a0758UODs+0OQHLMDRtl81lzPrVSdrYEDldxH9+a86dBZhdm0e15+ODDts2LHUknsJCRRldO4o9R9VrohlF7cbyBlnhJQrR4S+Oag== william@amethyst";
+        let ssh_pubkey = SshPublicKey::from_string(ECDSA_SSH_PUBLIC_KEY).unwrap();
+
+        let scope_set = BTreeSet::from([OAUTH2_SCOPE_SSH_PUBLICKEYS.to_string()]);
+
+        let mut idms_prox_write = idms.proxy_write(ct).await.unwrap();
+
+        idms_prox_write
+            .qs_write
+            .internal_batch_modify(
+                [
+                    (
+                        UUID_TESTPERSON_1,
+                        ModifyList::new_set(
+                            Attribute::SshPublicKey,
+                            ValueSetSshKey::new("label".to_string(), ssh_pubkey),
+                        ),
+                    ),
+                    (
+                        client_uuid,
+                        ModifyList::new_set(
+                            Attribute::OAuth2RsSupScopeMap,
+                            ValueSetOauthScopeMap::new(UUID_IDM_ALL_ACCOUNTS, scope_set),
+                        ),
+                    ),
+                ]
+                .into_iter(),
+            )
+            .expect("Failed to modify test entries");
+
+        assert!(idms_prox_write.commit().is_ok());
+
         let client_authz = ClientAuthInfo::encode_basic("test_resource_server", secret.as_str());
 
         let idms_prox_read = idms.proxy_read().await.unwrap();
@@ -5499,7 +9793,7 @@ mod tests {
             &ident,
             ct,
             pkce_secret.to_request(),
-            format!("{OAUTH2_SCOPE_OPENID} {OAUTH2_SCOPE_PROFILE}")
+            "openid groups".to_string()
         );
 
         let AuthoriseResponse::ConsentRequested { consent_token, .. } = consent_request else {
@@ -5526,29 +9820,16 @@ mod tests {
             .check_oauth2_token_exchange(&client_authz, &token_req, ct)
             .expect("Failed to perform OAuth2 token exchange");
 
-        // 🎉 We got a token!
-        assert_eq!(token_response.token_type, AccessTokenType::Bearer);
-
         let id_token = token_response.id_token.expect("No id_token in response!");
-
         let access_token =
             JwsCompact::from_str(&token_response.access_token).expect("Invalid Access Token");
 
-        let refresh_token = token_response
-            .refresh_token
-            .as_ref()
-            .expect("no refresh token was issued")
-            .clone();
-
-        // Get the read txn for inspecting the tokens
         assert!(idms_prox_write.commit().is_ok());
-
         let mut idms_prox_read = idms.proxy_read().await.unwrap();
 
         let mut jwkset = idms_prox_read
             .oauth2_openid_publickey("test_resource_server")
             .expect("Failed to get public key");
-
         let public_jwk = jwkset.keys.pop().expect("no such jwk");
 
         let jws_validator =
@@ -5565,131 +9846,137 @@ mod tests {
             .verify_exp(iat)
             .expect("Failed to verify oidc");
 
-        // Are the id_token values what we expect?
-        assert!(
-            oidc.iss
-                == Url::parse("https://idm.example.com/oauth2/openid/test_resource_server")
-                    .unwrap()
-        );
-        assert_eq!(oidc.sub, OidcSubject::U(UUID_TESTPERSON_1));
-        assert_eq!(oidc.aud, "test_resource_server");
-        assert_eq!(oidc.iat, iat);
-        assert_eq!(oidc.nbf, Some(iat));
-        // Previously this was the auth session but it's now inline with the access token expiry.
-        assert_eq!(oidc.exp, iat + (OAUTH2_ACCESS_TOKEN_EXPIRY as i64));
-        assert!(oidc.auth_time.is_none());
-        // Is nonce correctly passed through?
-        assert_eq!(oidc.nonce, Some("abcdef".to_string()));
-        assert!(oidc.at_hash.is_none());
-        assert!(oidc.acr.is_none());
-        assert!(oidc.amr.is_none());
-        assert_eq!(oidc.azp, Some("test_resource_server".to_string()));
-        assert!(oidc.jti.is_some());
-        if let Some(jti) = &oidc.jti {
-            assert!(Uuid::from_str(jti).is_ok());
-        }
-        assert_eq!(oidc.s_claims.name, Some("Test Person 1".to_string()));
-        assert_eq!(
-            oidc.s_claims.preferred_username,
-            Some("testperson1@example.com".to_string())
-        );
-        assert!(
-            oidc.s_claims.scopes
-                == vec![
-                    OAUTH2_SCOPE_OPENID.to_string(),
-                    OAUTH2_SCOPE_PROFILE.to_string(),
-                    "supplement".to_string()
-                ]
-        );
-        assert!(oidc.claims.is_empty());
-        // Does our access token work with the userinfo endpoint?
+        // does our id_token contain the expected groups?
+        assert!(oidc.claims.contains_key(OAUTH2_SCOPE_SSH_PUBLICKEYS));
+
+        assert!(oidc
+            .claims
+            .get(OAUTH2_SCOPE_SSH_PUBLICKEYS)
+            .expect("unable to find key")
+            .as_array()
+            .unwrap()
+            .contains(&serde_json::json!(ECDSA_SSH_PUBLIC_KEY)));
+
         // Do the id_token details line up to the userinfo?
         let userinfo = idms_prox_read
-            .oauth2_openid_userinfo("test_resource_server", &access_token, ct)
+            .oauth2_openid_userinfo("test_resource_server", &access_token, None, None, ct)
             .expect("failed to get userinfo");
 
-        assert_eq!(oidc.iss, userinfo.iss);
-        assert_eq!(oidc.sub, userinfo.sub);
-        assert_eq!(oidc.aud, userinfo.aud);
-        assert_eq!(oidc.iat, userinfo.iat);
-        assert_eq!(oidc.nbf, userinfo.nbf);
-        assert_eq!(oidc.exp, userinfo.exp);
-        assert!(userinfo.auth_time.is_none());
-        assert_eq!(userinfo.nonce, Some("abcdef".to_string()));
-        assert!(userinfo.at_hash.is_none());
-        assert!(userinfo.acr.is_none());
-        assert_eq!(oidc.amr, userinfo.amr);
-        assert_eq!(oidc.azp, userinfo.azp);
-        assert!(userinfo.jti.is_some());
-        if let Some(jti) = &userinfo.jti {
-            assert!(Uuid::from_str(jti).is_ok());
-        }
-        assert_eq!(oidc.s_claims, userinfo.s_claims);
-        assert!(userinfo.claims.is_empty());
+        // does the userinfo endpoint provide the same groups?
+        assert_eq!(
+            oidc.claims.get(OAUTH2_SCOPE_SSH_PUBLICKEYS),
+            userinfo.claims.get(OAUTH2_SCOPE_SSH_PUBLICKEYS)
+        );
+    }
 
-        drop(idms_prox_read);
+    //  Check insecure pkce behaviour.
+    #[idm_test]
+    async fn test_idm_oauth2_insecure_pkce(idms: &IdmServer, _idms_delayed: &mut IdmServerDelayed) {
+        let ct = Duration::from_secs(TEST_CURRENT_TIME);
+        let (_secret, _uat, ident, _) =
+            setup_oauth2_resource_server_basic(idms, ct, false, false, false).await;
 
-        // Importantly, we need to persist the nonce through access/refresh token operations
-        // because some clients like the rust openidconnect library require it always for claim
-        // verification.
-        let mut idms_prox_write = idms.proxy_write(ct).await.unwrap();
+        let idms_prox_read = idms.proxy_read().await.unwrap();
 
-        let token_req: AccessTokenRequest = GrantTypeReq::RefreshToken {
-            refresh_token,
-            scope: None,
-        }
-        .into();
+        // == Setup the authorisation request
+        let pkce_secret = PkceS256Secret::default();
 
-        let token_response = idms_prox_write
-            .check_oauth2_token_exchange(&client_authz, &token_req, ct)
-            .expect("Unable to exchange for OAuth2 token");
+        // Even in disable pkce mode, we will allow pkce
+        let _consent_request = good_authorisation_request!(
+            idms_prox_read,
+            &ident,
+            ct,
+            pkce_secret.to_request(),
+            OAUTH2_SCOPE_OPENID.to_string()
+        );
 
-        let access_token =
-            JwsCompact::from_str(&token_response.access_token).expect("Invalid Access Token");
+        // Check we allow none.
+        let auth_req = AuthorisationRequest {
+            response_type: ResponseType::Code,
+            response_mode: None,
+            client_id: "test_resource_server".to_string(),
+            state: Some("123".to_string()),
+            pkce_request: None,
+            redirect_uri: Url::parse("https://demo.example.com/oauth2/result").unwrap(),
+            scope: btreeset![OAUTH2_SCOPE_GROUPS.to_string()],
+            nonce: Some("abcdef".to_string()),
+            oidc_ext: Default::default(),
+            max_age: None,
+            authorization_details: None,
+            unknown_keys: Default::default(),
+        };
 
-        assert!(idms_prox_write.commit().is_ok());
+        idms_prox_read
+            .check_oauth2_authorisation(Some(&ident), &auth_req, ct)
+            .expect("Oauth2 authorisation failed");
+    }
 
-        // Okay, refresh done, lets check it.
+    #[idm_test]
+    async fn test_idm_oauth2_webfinger(idms: &IdmServer, _idms_delayed: &mut IdmServerDelayed) {
+        let ct = Duration::from_secs(TEST_CURRENT_TIME);
+        let (_secret, _uat, _ident, _) =
+            setup_oauth2_resource_server_basic(idms, ct, true, false, true).await;
         let mut idms_prox_read = idms.proxy_read().await.unwrap();
 
-        let userinfo = idms_prox_read
-            .oauth2_openid_userinfo("test_resource_server", &access_token, ct)
-            .expect("failed to get userinfo");
+        let user = "testperson1@example.com";
 
-        assert_eq!(oidc.iss, userinfo.iss);
-        assert_eq!(oidc.sub, userinfo.sub);
-        assert_eq!(oidc.aud, userinfo.aud);
-        assert_eq!(oidc.iat, userinfo.iat);
-        assert_eq!(oidc.nbf, userinfo.nbf);
-        assert_eq!(oidc.exp, userinfo.exp);
-        assert!(userinfo.auth_time.is_none());
-        assert_eq!(userinfo.nonce, Some("abcdef".to_string()));
-        assert!(userinfo.at_hash.is_none());
-        assert!(userinfo.acr.is_none());
-        assert_eq!(oidc.amr, userinfo.amr);
-        assert_eq!(oidc.azp, userinfo.azp);
-        assert!(userinfo.jti.is_some());
-        if let Some(jti) = &userinfo.jti {
-            assert!(Uuid::from_str(jti).is_ok());
-        }
-        assert_eq!(oidc.s_claims, userinfo.s_claims);
-        assert!(userinfo.claims.is_empty());
+        let webfinger = idms_prox_read
+            .oauth2_openid_webfinger("test_resource_server", user)
+            .expect("Failed to get webfinger");
+
+        assert_eq!(webfinger.subject, user);
+        assert_eq!(webfinger.links.len(), 1);
+
+        let link = &webfinger.links[0];
+        assert_eq!(link.rel, "http://openid.net/specs/connect/1.0/issuer");
+        assert_eq!(
+            link.href,
+            "https://idm.example.com/oauth2/openid/test_resource_server"
+        );
+
+        let failed_webfinger = idms_prox_read
+            .oauth2_openid_webfinger("test_resource_server", "someone@another.domain");
+        assert!(failed_webfinger.is_err());
     }
 
     #[idm_test]
-    async fn test_idm_oauth2_openid_short_username(
+    async fn test_idm_oauth2_openid_legacy_crypto(
         idms: &IdmServer,
         _idms_delayed: &mut IdmServerDelayed,
     ) {
-        // we run the same test as test_idm_oauth2_openid_extensions()
-        // but change the preferred_username setting on the RS
         let ct = Duration::from_secs(TEST_CURRENT_TIME);
         let (secret, _uat, ident, _) =
-            setup_oauth2_resource_server_basic(idms, ct, true, false, true).await;
-        let client_authz = ClientAuthInfo::encode_basic("test_resource_server", secret.as_str());
-
+            setup_oauth2_resource_server_basic(idms, ct, false, true, false).await;
         let idms_prox_read = idms.proxy_read().await.unwrap();
+        // The public key url should offer an rs key
+        // discovery should offer RS256
+        let discovery = idms_prox_read
+            .oauth2_openid_discovery("test_resource_server")
+            .expect("Failed to get discovery");
+
+        let mut jwkset = idms_prox_read
+            .oauth2_openid_publickey("test_resource_server")
+            .expect("Failed to get public key");
+
+        let jwk = jwkset.keys.pop().expect("no such jwk");
+        let public_jwk = jwk.clone();
+
+        match jwk {
+            Jwk::RSA { alg, use_, kid, .. } => {
+                match (
+                    alg.unwrap(),
+                    &discovery.id_token_signing_alg_values_supported[0],
+                ) {
+                    (JwaAlg::RS256, IdTokenSignAlg::RS256) => {}
+                    _ => panic!(),
+                };
+                assert_eq!(use_.unwrap(), JwkUse::Sig);
+                assert!(kid.is_some());
+            }
+            _ => panic!(),
+        };
 
+        // Check that the id_token is signed with the correct key.
         let pkce_secret = PkceS256Secret::default();
 
         let consent_request = good_authorisation_request!(
@@ -5713,31 +10000,30 @@ mod tests {
             .expect("Failed to perform OAuth2 permit");
 
         // == Submit the token exchange code.
-        let token_req: AccessTokenRequest = GrantTypeReq::AuthorizationCode {
-            code: permit_success.code,
-            redirect_uri: Url::parse("https://demo.example.com/oauth2/result").unwrap(),
-            code_verifier: Some(pkce_secret.to_verifier()),
-        }
-        .into();
+        let token_req = AccessTokenRequest {
+            grant_type: GrantTypeReq::AuthorizationCode {
+                code: permit_success.code,
+                redirect_uri: Url::parse("https://demo.example.com/oauth2/result").unwrap(),
+                code_verifier: Some(pkce_secret.to_verifier()),
+            },
+
+            client_post_auth: ClientPostAuth {
+                client_id: Some("test_resource_server".to_string()),
+                client_secret: Some(secret),
+                ..Default::default()
+            },
+        };
 
         let token_response = idms_prox_write
-            .check_oauth2_token_exchange(&client_authz, &token_req, ct)
+            .check_oauth2_token_exchange(&ClientAuthInfo::none(), &token_req, ct)
             .expect("Failed to perform OAuth2 token exchange");
 
+        // 🎉 We got a token!
+        assert_eq!(token_response.token_type, AccessTokenType::Bearer);
         let id_token = token_response.id_token.expect("No id_token in response!");
-        let access_token =
-            JwsCompact::from_str(&token_response.access_token).expect("Invalid Access Token");
-
-        assert!(idms_prox_write.commit().is_ok());
-        let mut idms_prox_read = idms.proxy_read().await.unwrap();
-
-        let mut jwkset = idms_prox_read
-            .oauth2_openid_publickey("test_resource_server")
-            .expect("Failed to get public key");
-        let public_jwk = jwkset.keys.pop().expect("no such jwk");
 
         let jws_validator =
-            JwsEs256Verifier::try_from(&public_jwk).expect("failed to build validator");
+            JwsRs256Verifier::try_from(&public_jwk).expect("failed to build validator");
 
         let oidc_unverified =
             OidcUnverified::from_str(&id_token).expect("Failed to parse id_token");
@@ -5750,202 +10036,425 @@ mod tests {
             .verify_exp(iat)
             .expect("Failed to verify oidc");
 
-        // Do we have the short username in the token claims?
-        assert_eq!(
-            oidc.s_claims.preferred_username,
-            Some("testperson1".to_string())
-        );
-        // Do the id_token details line up to the userinfo?
-        let userinfo = idms_prox_read
-            .oauth2_openid_userinfo("test_resource_server", &access_token, ct)
-            .expect("failed to get userinfo");
+        assert_eq!(oidc.sub, OidcSubject::U(UUID_TESTPERSON_1));
 
-        assert_eq!(oidc.s_claims, userinfo.s_claims);
+        assert!(idms_prox_write.commit().is_ok());
     }
 
     #[idm_test]
-    async fn test_idm_oauth2_openid_group_claims(
+    async fn test_idm_oauth2_consent_granted_and_changed_workflow(
         idms: &IdmServer,
         _idms_delayed: &mut IdmServerDelayed,
     ) {
-        // we run the same test as test_idm_oauth2_openid_extensions()
-        // but change the preferred_username setting on the RS
         let ct = Duration::from_secs(TEST_CURRENT_TIME);
-        let (secret, _uat, ident, _) =
-            setup_oauth2_resource_server_basic(idms, ct, true, false, true).await;
+        let (_secret, uat, ident, _) =
+            setup_oauth2_resource_server_basic(idms, ct, true, false, false).await;
 
-        let client_authz = ClientAuthInfo::encode_basic("test_resource_server", secret.as_str());
+        let idms_prox_read = idms.proxy_read().await.unwrap();
 
-        let token_response = perform_oauth2_exchange(
-            idms,
+        let pkce_secret = PkceS256Secret::default();
+
+        let consent_request = good_authorisation_request!(
+            idms_prox_read,
             &ident,
             ct,
-            client_authz,
-            format!("{OAUTH2_SCOPE_OPENID} {OAUTH2_SCOPE_GROUPS}"),
-        )
-        .await;
+            pkce_secret.to_request(),
+            OAUTH2_SCOPE_OPENID.to_string()
+        );
 
-        let id_token = token_response.id_token.expect("No id_token in response!");
-        let access_token =
-            JwsCompact::from_str(&token_response.access_token).expect("Invalid Access Token");
+        // Should be in the consent phase;
+        let AuthoriseResponse::ConsentRequested { consent_token, .. } = consent_request else {
+            unreachable!();
+        };
 
-        let oidc = validate_id_token(idms, ct, &id_token).await;
+        // == Manually submit the consent token to the permit for the permit_success
+        drop(idms_prox_read);
+        let mut idms_prox_write = idms.proxy_write(ct).await.unwrap();
 
-        // does our id_token contain the expected groups?
-        assert!(oidc.claims.contains_key("groups"));
+        let _permit_success = idms_prox_write
+            .check_oauth2_authorise_permit(&ident, &consent_token, ct)
+            .expect("Failed to perform OAuth2 permit");
 
-        assert!(oidc
-            .claims
-            .get("groups")
-            .expect("unable to find key")
-            .as_array()
-            .unwrap()
-            .contains(&serde_json::json!(STR_UUID_IDM_ALL_ACCOUNTS)));
+        assert!(idms_prox_write.commit().is_ok());
+
+        // == Now try the authorise again, should be in the permitted state.
+        let mut idms_prox_read = idms.proxy_read().await.unwrap();
+
+        // We need to reload our identity
+        let ident = idms_prox_read
+            .process_uat_to_identity(&uat, ct, Source::Internal)
+            .expect("Unable to process uat");
+
+        let pkce_secret = PkceS256Secret::default();
+
+        let consent_request = good_authorisation_request!(
+            idms_prox_read,
+            &ident,
+            ct,
+            pkce_secret.to_request(),
+            OAUTH2_SCOPE_OPENID.to_string()
+        );
+
+        // Should be in the consent phase;
+        let AuthoriseResponse::Permitted(_permit_success) = consent_request else {
+            unreachable!();
+        };
+
+        drop(idms_prox_read);
+
+        // Great! Now change the scopes on the OAuth2 instance, this revokes the permit.
+        let mut idms_prox_write = idms.proxy_write(ct).await.unwrap();
+
+        let me_extend_scopes = ModifyEvent::new_internal_invalid(
+            filter!(f_eq(
+                Attribute::Name,
+                PartialValue::new_iname("test_resource_server")
+            )),
+            ModifyList::new_list(vec![Modify::Present(
+                Attribute::OAuth2RsScopeMap,
+                Value::new_oauthscopemap(
+                    UUID_IDM_ALL_ACCOUNTS,
+                    btreeset![
+                        OAUTH2_SCOPE_EMAIL.to_string(),
+                        OAUTH2_SCOPE_PROFILE.to_string(),
+                        OAUTH2_SCOPE_OPENID.to_string()
+                    ],
+                )
+                .expect("invalid oauthscope"),
+            )]),
+        );
+
+        assert!(idms_prox_write.qs_write.modify(&me_extend_scopes).is_ok());
+        assert!(idms_prox_write.commit().is_ok());
+
+        // And do the workflow once more to see if we need to consent again.
+
+        let mut idms_prox_read = idms.proxy_read().await.unwrap();
+
+        // We need to reload our identity
+        let ident = idms_prox_read
+            .process_uat_to_identity(&uat, ct, Source::Internal)
+            .expect("Unable to process uat");
+
+        let pkce_secret = PkceS256Secret::default();
+
+        let auth_req = AuthorisationRequest {
+            response_type: ResponseType::Code,
+            response_mode: None,
+            client_id: "test_resource_server".to_string(),
+            state: Some("123".to_string()),
+            pkce_request: Some(pkce_secret.to_request()),
+            redirect_uri: Url::parse("https://demo.example.com/oauth2/result").unwrap(),
+            scope: btreeset![
+                "openid".to_string(),
+                "email".to_string(),
+                "profile".to_string()
+            ],
+            nonce: Some("abcdef".to_string()),
+            oidc_ext: Default::default(),
+            max_age: None,
+            authorization_details: None,
+            unknown_keys: Default::default(),
+        };
+
+        let consent_request = idms_prox_read
+            .check_oauth2_authorisation(Some(&ident), &auth_req, ct)
+            .expect("Oauth2 authorisation failed");
+
+        // Should be in the consent phase;
+        let AuthoriseResponse::ConsentRequested { .. } = consent_request else {
+            unreachable!();
+        };
+
+        drop(idms_prox_read);
+
+        // Success! We had to consent again due to the change :)
+
+        // Now change the supplemental scopes on the OAuth2 instance, this revokes the permit.
+        let mut idms_prox_write = idms.proxy_write(ct).await.unwrap();
+
+        let me_extend_scopes = ModifyEvent::new_internal_invalid(
+            filter!(f_eq(
+                Attribute::Name,
+                PartialValue::new_iname("test_resource_server")
+            )),
+            ModifyList::new_list(vec![Modify::Present(
+                Attribute::OAuth2RsSupScopeMap,
+                Value::new_oauthscopemap(UUID_IDM_ALL_ACCOUNTS, btreeset!["newscope".to_string()])
+                    .expect("invalid oauthscope"),
+            )]),
+        );
+
+        assert!(idms_prox_write.qs_write.modify(&me_extend_scopes).is_ok());
+        assert!(idms_prox_write.commit().is_ok());
+
+        // And do the workflow once more to see if we need to consent again.
 
         let mut idms_prox_read = idms.proxy_read().await.unwrap();
 
-        // Do the id_token details line up to the userinfo?
-        let userinfo = idms_prox_read
-            .oauth2_openid_userinfo("test_resource_server", &access_token, ct)
-            .expect("failed to get userinfo");
+        // We need to reload our identity
+        let ident = idms_prox_read
+            .process_uat_to_identity(&uat, ct, Source::Internal)
+            .expect("Unable to process uat");
+
+        let pkce_secret = PkceS256Secret::default();
+
+        let auth_req = AuthorisationRequest {
+            response_type: ResponseType::Code,
+            response_mode: None,
+            client_id: "test_resource_server".to_string(),
+            state: Some("123".to_string()),
+            pkce_request: Some(pkce_secret.to_request()),
+            redirect_uri: Url::parse("https://demo.example.com/oauth2/result").unwrap(),
+            // Note the scope isn't requested here!
+            scope: btreeset![
+                "openid".to_string(),
+                "email".to_string(),
+                "profile".to_string()
+            ],
+            nonce: Some("abcdef".to_string()),
+            oidc_ext: Default::default(),
+            max_age: None,
+            authorization_details: None,
+            unknown_keys: Default::default(),
+        };
+
+        let consent_request = idms_prox_read
+            .check_oauth2_authorisation(Some(&ident), &auth_req, ct)
+            .expect("Oauth2 authorisation failed");
 
-        // does the userinfo endpoint provide the same groups?
-        assert_eq!(oidc.claims.get("groups"), userinfo.claims.get("groups"));
+        // Should be present in the consent phase however!
+        let _consent_token = if let AuthoriseResponse::ConsentRequested {
+            consent_token,
+            scopes,
+            ..
+        } = consent_request
+        {
+            assert!(scopes.contains("newscope"));
+            consent_token
+        } else {
+            unreachable!();
+        };
     }
 
     #[idm_test]
-    async fn test_idm_oauth2_openid_group_extended_claims(
+    async fn test_idm_oauth2_consent_granted_refint_cleanup_on_delete(
         idms: &IdmServer,
         _idms_delayed: &mut IdmServerDelayed,
     ) {
-        // we run the same test as test_idm_oauth2_openid_extensions()
-        // but change the preferred_username setting on the RS
         let ct = Duration::from_secs(TEST_CURRENT_TIME);
-        let (secret, _uat, ident, oauth2_client_uuid) =
-            setup_oauth2_resource_server_basic(idms, ct, true, false, true).await;
-
-        // Modify the oauth2 client to have different scope maps.
-        let mut idms_prox_write = idms.proxy_write(ct).await.unwrap();
-
-        let modlist = ModifyList::new_list(vec![
-            Modify::Removed(
-                Attribute::OAuth2RsScopeMap,
-                PartialValue::Refer(UUID_TESTGROUP),
-            ),
-            Modify::Present(
-                Attribute::OAuth2RsScopeMap,
-                Value::new_oauthscopemap(
-                    UUID_TESTGROUP,
-                    btreeset![OAUTH2_SCOPE_GROUPS_NAME.to_string()],
-                )
-                .expect("invalid oauthscope"),
-            ),
-        ]);
-
-        idms_prox_write
-            .qs_write
-            .internal_modify_uuid(oauth2_client_uuid, &modlist)
-            .expect("Failed to modify scopes");
+        let (_secret, uat, ident, o2rs_uuid) =
+            setup_oauth2_resource_server_basic(idms, ct, true, false, false).await;
 
-        idms_prox_write.commit().expect("failed to commit");
+        // Assert there are no consent maps yet.
+        assert!(ident.get_oauth2_consent_scopes(o2rs_uuid).is_none());
 
-        // Now actually do the test.
-        let client_authz = ClientAuthInfo::encode_basic("test_resource_server", secret.as_str());
+        let idms_prox_read = idms.proxy_read().await.unwrap();
 
-        let token_response = perform_oauth2_exchange(
-            idms,
+        let pkce_secret = PkceS256Secret::default();
+        let consent_request = good_authorisation_request!(
+            idms_prox_read,
             &ident,
             ct,
-            client_authz,
-            format!("{OAUTH2_SCOPE_OPENID} {OAUTH2_SCOPE_GROUPS_NAME}"),
-        )
-        .await;
+            pkce_secret.to_request(),
+            OAUTH2_SCOPE_OPENID.to_string()
+        );
 
-        let id_token = token_response.id_token.expect("No id_token in response!");
-        let access_token =
-            JwsCompact::from_str(&token_response.access_token).expect("Invalid Access Token");
+        // Should be in the consent phase;
+        let AuthoriseResponse::ConsentRequested { consent_token, .. } = consent_request else {
+            unreachable!();
+        };
 
-        let oidc = validate_id_token(idms, ct, &id_token).await;
+        // == Manually submit the consent token to the permit for the permit_success
+        drop(idms_prox_read);
+        let mut idms_prox_write = idms.proxy_write(ct).await.unwrap();
 
-        // does our id_token contain the expected groups?
-        assert!(oidc.claims.contains_key("groups"));
+        let _permit_success = idms_prox_write
+            .check_oauth2_authorise_permit(&ident, &consent_token, ct)
+            .expect("Failed to perform OAuth2 permit");
 
-        assert!(oidc
-            .claims
-            .get("groups")
-            .expect("unable to find key")
-            .as_array()
-            .unwrap()
-            .contains(&serde_json::json!("testgroup")));
+        let ident = idms_prox_write
+            .process_uat_to_identity(&uat, ct, Source::Internal)
+            .expect("Unable to process uat");
 
-        let mut idms_prox_read = idms.proxy_read().await.unwrap();
+        // Assert that the ident now has the consents.
+        assert!(
+            ident.get_oauth2_consent_scopes(o2rs_uuid)
+                == Some(&btreeset![
+                    OAUTH2_SCOPE_OPENID.to_string(),
+                    "supplement".to_string()
+                ])
+        );
 
-        // Do the id_token details line up to the userinfo?
-        let userinfo = idms_prox_read
-            .oauth2_openid_userinfo("test_resource_server", &access_token, ct)
-            .expect("failed to get userinfo");
+        // Now trigger the delete of the RS
+        let de = DeleteEvent::new_internal_invalid(filter!(f_eq(
+            Attribute::Name,
+            PartialValue::new_iname("test_resource_server")
+        )));
 
-        // does the userinfo endpoint provide the same groups?
-        assert_eq!(oidc.claims.get("groups"), userinfo.claims.get("groups"));
+        assert!(idms_prox_write.qs_write.delete(&de).is_ok());
+        // Assert the consent maps are gone.
+        let ident = idms_prox_write
+            .process_uat_to_identity(&uat, ct, Source::Internal)
+            .expect("Unable to process uat");
+        dbg!(&o2rs_uuid);
+        dbg!(&ident);
+        let consent_scopes = ident.get_oauth2_consent_scopes(o2rs_uuid);
+        dbg!(consent_scopes);
+        assert!(consent_scopes.is_none());
+
+        assert!(idms_prox_write.commit().is_ok());
     }
 
+    // https://datatracker.ietf.org/doc/html/draft-ietf-oauth-security-topics#section-4.8
+    //
+    // It was reported we were vulnerable to this attack, but that isn't the case. First
+    // this attack relies on stripping the *code_challenge* from the internals of the returned
+    // code exchange token. This isn't possible due to our use of encryption of the code exchange
+    // token. If that code challenge *could* be removed, then the attacker could use the code exchange
+    // with no verifier or an incorrect verifier.
+    //
+    // Due to the logic in our server, if a code exchange contains a code challenge we always enforce
+    // it is correctly used!
+    //
+    // This left a single odd case where if a client did an authorisation request without a pkce
+    // verifier, but then a verifier was submitted during the code exchange, that the server would
+    // *ignore* the verifier parameter. In this case, no stripping of the code challenge was done,
+    // and the client could have simply also submitted *no* verifier anyway. It could be that
+    // an attacker could gain a code exchange with no code challenge and then force a victim to
+    // exchange that code exchange with out the verifier, but I'm not sure what damage that would
+    // lead to? Regardless, we test for and close off that possible hole in this test.
+    //
     #[idm_test]
-    async fn test_idm_oauth2_openid_ssh_publickey_claim(
+    async fn test_idm_oauth2_1076_pkce_downgrade(
         idms: &IdmServer,
         _idms_delayed: &mut IdmServerDelayed,
     ) {
         let ct = Duration::from_secs(TEST_CURRENT_TIME);
-        let (secret, _uat, ident, client_uuid) =
-            setup_oauth2_resource_server_basic(idms, ct, true, false, true).await;
+        // Enable pkce is set to FALSE
+        let (secret, _uat, ident, _) =
+            setup_oauth2_resource_server_basic(idms, ct, false, false, false).await;
 
-        // Extra setup for our test - add the correct claim and give an ssh publickey
-        // to our testperson
-        const ECDSA_SSH_PUBLIC_KEY: &str = "ecdsa-sha2-nistp521 AAAAE2VjZHNhLXNoYTItbmlzdHA1MjEAAAAIbmlzdHA1MjEAAACFBAGyIY7o3BtOzRiJ9vvjj96bRImwmyy5GvFSIUPlK00HitiAWGhiO1jGZKmK7220Oe4rqU3uAwA00a0758UODs+0OQHLMDRtl81lzPrVSdrYEDldxH9+a86dBZhdm0e15+ODDts2LHUknsJCRRldO4o9R9VrohlF7cbyBlnhJQrR4S+Oag== william@amethyst";
-        let ssh_pubkey = SshPublicKey::from_string(ECDSA_SSH_PUBLIC_KEY).unwrap();
+        let idms_prox_read = idms.proxy_read().await.unwrap();
 
-        let scope_set = BTreeSet::from([OAUTH2_SCOPE_SSH_PUBLICKEYS.to_string()]);
+        // Get an ident/uat for now.
+
+        // == Setup the authorisation request
+        // We attempt pkce even though the rs is set to not support pkce.
+        let pkce_secret = PkceS256Secret::default();
+
+        // First, the user does not request pkce in their exchange.
+        let auth_req = AuthorisationRequest {
+            response_type: ResponseType::Code,
+            response_mode: None,
+            client_id: "test_resource_server".to_string(),
+            state: Some("123".to_string()),
+            pkce_request: None,
+            redirect_uri: Url::parse("https://demo.example.com/oauth2/result").unwrap(),
+            scope: btreeset![OAUTH2_SCOPE_OPENID.to_string()],
+            nonce: None,
+            oidc_ext: Default::default(),
+            max_age: None,
+            authorization_details: None,
+            unknown_keys: Default::default(),
+        };
+
+        let consent_request = idms_prox_read
+            .check_oauth2_authorisation(Some(&ident), &auth_req, ct)
+            .expect("Failed to perform OAuth2 authorisation request.");
+
+        // Should be in the consent phase;
+        let AuthoriseResponse::ConsentRequested { consent_token, .. } = consent_request else {
+            unreachable!();
+        };
 
+        // == Manually submit the consent token to the permit for the permit_success
+        drop(idms_prox_read);
         let mut idms_prox_write = idms.proxy_write(ct).await.unwrap();
 
-        idms_prox_write
-            .qs_write
-            .internal_batch_modify(
-                [
-                    (
-                        UUID_TESTPERSON_1,
-                        ModifyList::new_set(
-                            Attribute::SshPublicKey,
-                            ValueSetSshKey::new("label".to_string(), ssh_pubkey),
-                        ),
-                    ),
-                    (
-                        client_uuid,
-                        ModifyList::new_set(
-                            Attribute::OAuth2RsSupScopeMap,
-                            ValueSetOauthScopeMap::new(UUID_IDM_ALL_ACCOUNTS, scope_set),
-                        ),
-                    ),
-                ]
-                .into_iter(),
-            )
-            .expect("Failed to modify test entries");
+        let permit_success = idms_prox_write
+            .check_oauth2_authorise_permit(&ident, &consent_token, ct)
+            .expect("Failed to perform OAuth2 permit");
+
+        // == Submit the token exchange code.
+        // This exchange failed because we submitted a verifier when the code exchange
+        // has NO code challenge present.
+        let token_req = AccessTokenRequest {
+            grant_type: GrantTypeReq::AuthorizationCode {
+                code: permit_success.code,
+                redirect_uri: Url::parse("https://demo.example.com/oauth2/result").unwrap(),
+                code_verifier: Some(pkce_secret.to_verifier()),
+            },
+            client_post_auth: ClientPostAuth {
+                client_id: Some("test_resource_server".to_string()),
+                client_secret: Some(secret),
+                ..Default::default()
+            },
+        };
+
+        // Assert the exchange fails.
+        assert!(matches!(
+            idms_prox_write.check_oauth2_token_exchange(&ClientAuthInfo::none(), &token_req, ct),
+            Err(Oauth2Error::InvalidRequest)
+        ));
 
         assert!(idms_prox_write.commit().is_ok());
+    }
 
-        let client_authz = ClientAuthInfo::encode_basic("test_resource_server", secret.as_str());
+    #[idm_test]
+    // https://datatracker.ietf.org/doc/html/draft-ietf-oauth-security-topics#section-2.1
+    //
+    // If the origin configured is https, do not allow downgrading to http on redirect
+    async fn test_idm_oauth2_redir_http_downgrade(
+        idms: &IdmServer,
+        _idms_delayed: &mut IdmServerDelayed,
+    ) {
+        let ct = Duration::from_secs(TEST_CURRENT_TIME);
+        // Enable pkce is set to FALSE
+        let (secret, _uat, ident, _) =
+            setup_oauth2_resource_server_basic(idms, ct, false, false, false).await;
 
         let idms_prox_read = idms.proxy_read().await.unwrap();
 
+        // Get an ident/uat for now.
+
+        // == Setup the authorisation request
+        // We attempt pkce even though the rs is set to not support pkce.
         let pkce_secret = PkceS256Secret::default();
 
+        // First, NOTE the lack of https on the redir uri.
+        let auth_req = AuthorisationRequest {
+            response_type: ResponseType::Code,
+            response_mode: None,
+            client_id: "test_resource_server".to_string(),
+            state: Some("123".to_string()),
+            pkce_request: Some(pkce_secret.to_request()),
+            redirect_uri: Url::parse("http://demo.example.com/oauth2/result").unwrap(),
+            scope: btreeset![OAUTH2_SCOPE_OPENID.to_string()],
+            nonce: None,
+            oidc_ext: Default::default(),
+            max_age: None,
+            authorization_details: None,
+            unknown_keys: Default::default(),
+        };
+
+        assert!(
+            idms_prox_read
+                .check_oauth2_authorisation(Some(&ident), &auth_req, ct)
+                .unwrap_err()
+                == Oauth2Error::InvalidOrigin
+        );
+
+        // This does have https
         let consent_request = good_authorisation_request!(
             idms_prox_read,
             &ident,
             ct,
             pkce_secret.to_request(),
-            "openid groups".to_string()
+            OAUTH2_SCOPE_OPENID.to_string()
         );
 
+        // Should be in the consent phase;
         let AuthoriseResponse::ConsentRequested { consent_token, .. } = consent_request else {
             unreachable!();
         };
@@ -5959,80 +10468,46 @@ mod tests {
             .expect("Failed to perform OAuth2 permit");
 
         // == Submit the token exchange code.
-        let token_req: AccessTokenRequest = GrantTypeReq::AuthorizationCode {
-            code: permit_success.code,
-            redirect_uri: Url::parse("https://demo.example.com/oauth2/result").unwrap(),
-            code_verifier: Some(pkce_secret.to_verifier()),
-        }
-        .into();
+        // NOTE the url is http again
+        let token_req = AccessTokenRequest {
+            grant_type: GrantTypeReq::AuthorizationCode {
+                code: permit_success.code,
+                redirect_uri: Url::parse("http://demo.example.com/oauth2/result").unwrap(),
+                code_verifier: Some(pkce_secret.to_verifier()),
+            },
 
-        let token_response = idms_prox_write
-            .check_oauth2_token_exchange(&client_authz, &token_req, ct)
-            .expect("Failed to perform OAuth2 token exchange");
+            client_post_auth: ClientPostAuth {
+                client_id: Some("test_resource_server".to_string()),
+                client_secret: Some(secret),
+                ..Default::default()
+            },
+        };
 
-        let id_token = token_response.id_token.expect("No id_token in response!");
-        let access_token =
-            JwsCompact::from_str(&token_response.access_token).expect("Invalid Access Token");
+        // Assert the exchange fails.
+        assert!(matches!(
+            idms_prox_write.check_oauth2_token_exchange(&ClientAuthInfo::none(), &token_req, ct),
+            Err(Oauth2Error::InvalidOrigin)
+        ));
 
         assert!(idms_prox_write.commit().is_ok());
-        let mut idms_prox_read = idms.proxy_read().await.unwrap();
-
-        let mut jwkset = idms_prox_read
-            .oauth2_openid_publickey("test_resource_server")
-            .expect("Failed to get public key");
-        let public_jwk = jwkset.keys.pop().expect("no such jwk");
-
-        let jws_validator =
-            JwsEs256Verifier::try_from(&public_jwk).expect("failed to build validator");
-
-        let oidc_unverified =
-            OidcUnverified::from_str(&id_token).expect("Failed to parse id_token");
-
-        let iat = ct.as_secs() as i64;
-
-        let oidc = jws_validator
-            .verify(&oidc_unverified)
-            .unwrap()
-            .verify_exp(iat)
-            .expect("Failed to verify oidc");
-
-        // does our id_token contain the expected groups?
-        assert!(oidc.claims.contains_key(OAUTH2_SCOPE_SSH_PUBLICKEYS));
-
-        assert!(oidc
-            .claims
-            .get(OAUTH2_SCOPE_SSH_PUBLICKEYS)
-            .expect("unable to find key")
-            .as_array()
-            .unwrap()
-            .contains(&serde_json::json!(ECDSA_SSH_PUBLIC_KEY)));
-
-        // Do the id_token details line up to the userinfo?
-        let userinfo = idms_prox_read
-            .oauth2_openid_userinfo("test_resource_server", &access_token, ct)
-            .expect("failed to get userinfo");
-
-        // does the userinfo endpoint provide the same groups?
-        assert_eq!(
-            oidc.claims.get(OAUTH2_SCOPE_SSH_PUBLICKEYS),
-            userinfo.claims.get(OAUTH2_SCOPE_SSH_PUBLICKEYS)
-        );
     }
 
-    //  Check insecure pkce behaviour.
-    #[idm_test]
-    async fn test_idm_oauth2_insecure_pkce(idms: &IdmServer, _idms_delayed: &mut IdmServerDelayed) {
-        let ct = Duration::from_secs(TEST_CURRENT_TIME);
-        let (_secret, _uat, ident, _) =
-            setup_oauth2_resource_server_basic(idms, ct, false, false, false).await;
+    async fn setup_refresh_token(
+        idms: &IdmServer,
+        _idms_delayed: &mut IdmServerDelayed,
+        ct: Duration,
+    ) -> (AccessTokenResponse, ClientAuthInfo) {
+        // First, setup to get a token.
+        let (secret, _uat, ident, _) =
+            setup_oauth2_resource_server_basic(idms, ct, true, false, false).await;
+        let client_authz = ClientAuthInfo::encode_basic("test_resource_server", secret.as_str());
 
         let idms_prox_read = idms.proxy_read().await.unwrap();
 
         // == Setup the authorisation request
         let pkce_secret = PkceS256Secret::default();
 
-        // Even in disable pkce mode, we will allow pkce
-        let _consent_request = good_authorisation_request!(
+        let consent_request = good_authorisation_request!(
             idms_prox_read,
             &ident,
             ct,
@@ -6040,613 +10515,706 @@ mod tests {
             OAUTH2_SCOPE_OPENID.to_string()
         );
 
-        // Check we allow none.
-        let auth_req = AuthorisationRequest {
-            response_type: ResponseType::Code,
-            response_mode: None,
-            client_id: "test_resource_server".to_string(),
-            state: Some("123".to_string()),
-            pkce_request: None,
-            redirect_uri: Url::parse("https://demo.example.com/oauth2/result").unwrap(),
-            scope: btreeset![OAUTH2_SCOPE_GROUPS.to_string()],
-            nonce: Some("abcdef".to_string()),
-            oidc_ext: Default::default(),
-            max_age: None,
-            unknown_keys: Default::default(),
+        let AuthoriseResponse::ConsentRequested { consent_token, .. } = consent_request else {
+            unreachable!();
         };
 
-        idms_prox_read
-            .check_oauth2_authorisation(Some(&ident), &auth_req, ct)
-            .expect("Oauth2 authorisation failed");
-    }
-
-    #[idm_test]
-    async fn test_idm_oauth2_webfinger(idms: &IdmServer, _idms_delayed: &mut IdmServerDelayed) {
-        let ct = Duration::from_secs(TEST_CURRENT_TIME);
-        let (_secret, _uat, _ident, _) =
-            setup_oauth2_resource_server_basic(idms, ct, true, false, true).await;
-        let mut idms_prox_read = idms.proxy_read().await.unwrap();
+        // == Manually submit the consent token to the permit for the permit_success
+        drop(idms_prox_read);
+        let mut idms_prox_write = idms.proxy_write(ct).await.unwrap();
 
-        let user = "testperson1@example.com";
+        let permit_success = idms_prox_write
+            .check_oauth2_authorise_permit(&ident, &consent_token, ct)
+            .expect("Failed to perform OAuth2 permit");
 
-        let webfinger = idms_prox_read
-            .oauth2_openid_webfinger("test_resource_server", user)
-            .expect("Failed to get webfinger");
+        let token_req: AccessTokenRequest = GrantTypeReq::AuthorizationCode {
+            code: permit_success.code,
+            redirect_uri: Url::parse("https://demo.example.com/oauth2/result").unwrap(),
+            code_verifier: Some(pkce_secret.to_verifier()),
+        }
+        .into();
+        let access_token_response_1 = idms_prox_write
+            .check_oauth2_token_exchange(&client_authz, &token_req, ct)
+            .expect("Unable to exchange for OAuth2 token");
 
-        assert_eq!(webfinger.subject, user);
-        assert_eq!(webfinger.links.len(), 1);
+        assert!(idms_prox_write.commit().is_ok());
 
-        let link = &webfinger.links[0];
-        assert_eq!(link.rel, "http://openid.net/specs/connect/1.0/issuer");
-        assert_eq!(
-            link.href,
-            "https://idm.example.com/oauth2/openid/test_resource_server"
-        );
+        trace!(?access_token_response_1);
 
-        let failed_webfinger = idms_prox_read
-            .oauth2_openid_webfinger("test_resource_server", "someone@another.domain");
-        assert!(failed_webfinger.is_err());
+        (access_token_response_1, client_authz)
     }
 
     #[idm_test]
-    async fn test_idm_oauth2_openid_legacy_crypto(
+    async fn test_idm_oauth2_refresh_token_basic(
         idms: &IdmServer,
-        _idms_delayed: &mut IdmServerDelayed,
+        idms_delayed: &mut IdmServerDelayed,
     ) {
+        // First, setup to get a token.
         let ct = Duration::from_secs(TEST_CURRENT_TIME);
-        let (secret, _uat, ident, _) =
-            setup_oauth2_resource_server_basic(idms, ct, false, true, false).await;
-        let idms_prox_read = idms.proxy_read().await.unwrap();
-        // The public key url should offer an rs key
-        // discovery should offer RS256
-        let discovery = idms_prox_read
-            .oauth2_openid_discovery("test_resource_server")
-            .expect("Failed to get discovery");
 
-        let mut jwkset = idms_prox_read
-            .oauth2_openid_publickey("test_resource_server")
-            .expect("Failed to get public key");
+        let (access_token_response_1, client_authz) =
+            setup_refresh_token(idms, idms_delayed, ct).await;
 
-        let jwk = jwkset.keys.pop().expect("no such jwk");
-        let public_jwk = jwk.clone();
+        // ============================================
+        // test basic refresh while access still valid.
 
-        match jwk {
-            Jwk::RSA { alg, use_, kid, .. } => {
-                match (
-                    alg.unwrap(),
-                    &discovery.id_token_signing_alg_values_supported[0],
-                ) {
-                    (JwaAlg::RS256, IdTokenSignAlg::RS256) => {}
-                    _ => panic!(),
-                };
-                assert_eq!(use_.unwrap(), JwkUse::Sig);
-                assert!(kid.is_some());
-            }
-            _ => panic!(),
-        };
+        let ct = Duration::from_secs(TEST_CURRENT_TIME + 10);
+        let mut idms_prox_write = idms.proxy_write(ct).await.unwrap();
+
+        let refresh_token = access_token_response_1
+            .refresh_token
+            .as_ref()
+            .expect("no refresh token was issued")
+            .clone();
+
+        let token_req: AccessTokenRequest = GrantTypeReq::RefreshToken {
+            refresh_token,
+            scope: None,
+        }
+        .into();
+
+        let access_token_response_2 = idms_prox_write
+            .check_oauth2_token_exchange(&client_authz, &token_req, ct)
+            .expect("Unable to exchange for OAuth2 token");
+
+        assert!(idms_prox_write.commit().is_ok());
+
+        trace!(?access_token_response_2);
+
+        assert!(access_token_response_1.access_token != access_token_response_2.access_token);
+        assert!(access_token_response_1.refresh_token != access_token_response_2.refresh_token);
+        assert!(access_token_response_1.id_token != access_token_response_2.id_token);
+
+        // ============================================
+        // test basic refresh after access exp
+        let ct =
+            Duration::from_secs(TEST_CURRENT_TIME + 20 + access_token_response_2.expires_in as u64);
+
+        let mut idms_prox_write = idms.proxy_write(ct).await.unwrap();
 
-        // Check that the id_token is signed with the correct key.
-        let pkce_secret = PkceS256Secret::default();
+        let refresh_token = access_token_response_2
+            .refresh_token
+            .as_ref()
+            .expect("no refresh token was issued")
+            .clone();
 
-        let consent_request = good_authorisation_request!(
-            idms_prox_read,
-            &ident,
-            ct,
-            pkce_secret.to_request(),
-            OAUTH2_SCOPE_OPENID.to_string()
-        );
+        // get the refresh token expiry now before we use it.
+        let reflected_token = idms_prox_write
+            .reflect_oauth2_token(&client_authz, &refresh_token)
+            .expect("Failed to access internals of the refresh token");
 
-        let AuthoriseResponse::ConsentRequested { consent_token, .. } = consent_request else {
-            unreachable!();
+        let refresh_exp = match reflected_token {
+            Oauth2TokenType::Refresh { exp, .. } => exp,
+            // Oauth2TokenType::Access { .. } |
+            Oauth2TokenType::ClientAccess { .. } => unreachable!(),
         };
 
-        // == Manually submit the consent token to the permit for the permit_success
-        drop(idms_prox_read);
-        let mut idms_prox_write = idms.proxy_write(ct).await.unwrap();
+        let token_req: AccessTokenRequest = GrantTypeReq::RefreshToken {
+            refresh_token,
+            scope: None,
+        }
+        .into();
 
-        let permit_success = idms_prox_write
-            .check_oauth2_authorise_permit(&ident, &consent_token, ct)
-            .expect("Failed to perform OAuth2 permit");
+        let access_token_response_3 = idms_prox_write
+            .check_oauth2_token_exchange(&client_authz, &token_req, ct)
+            .expect("Unable to exchange for OAuth2 token");
 
-        // == Submit the token exchange code.
-        let token_req = AccessTokenRequest {
-            grant_type: GrantTypeReq::AuthorizationCode {
-                code: permit_success.code,
-                redirect_uri: Url::parse("https://demo.example.com/oauth2/result").unwrap(),
-                code_verifier: Some(pkce_secret.to_verifier()),
-            },
+        // Get the user entry to check the session life was extended.
 
-            client_post_auth: ClientPostAuth {
-                client_id: Some("test_resource_server".to_string()),
-                client_secret: Some(secret),
-            },
-        };
+        let entry = idms_prox_write
+            .qs_write
+            .internal_search_uuid(UUID_TESTPERSON_1)
+            .expect("failed");
+        let session = entry
+            .get_ava_as_oauth2session_map(Attribute::OAuth2Session)
+            .and_then(|sessions| sessions.first_key_value())
+            // If there is no map, then something is wrong.
+            .unwrap();
 
-        let token_response = idms_prox_write
-            .check_oauth2_token_exchange(&ClientAuthInfo::none(), &token_req, ct)
-            .expect("Failed to perform OAuth2 token exchange");
+        trace!(?session);
+        // The Oauth2 Session must be updated with a newer session time.
+        assert_eq!(
+            SessionState::ExpiresAt(
+                time::OffsetDateTime::UNIX_EPOCH
+                    + ct
+                    + Duration::from_secs(OAUTH_REFRESH_TOKEN_EXPIRY)
+            ),
+            session.1.state
+        );
 
-        // 🎉 We got a token!
-        assert_eq!(token_response.token_type, AccessTokenType::Bearer);
-        let id_token = token_response.id_token.expect("No id_token in response!");
+        assert!(idms_prox_write.commit().is_ok());
 
-        let jws_validator =
-            JwsRs256Verifier::try_from(&public_jwk).expect("failed to build validator");
+        trace!(?access_token_response_3);
 
-        let oidc_unverified =
-            OidcUnverified::from_str(&id_token).expect("Failed to parse id_token");
+        assert!(access_token_response_3.access_token != access_token_response_2.access_token);
+        assert!(access_token_response_3.refresh_token != access_token_response_2.refresh_token);
+        assert!(access_token_response_3.id_token != access_token_response_2.id_token);
 
-        let iat = ct.as_secs() as i64;
+        // refresh after refresh has expired.
+        // Refresh tokens have a max time limit - the session time limit still bounds it though, but
+        // so does the refresh token limit. We check both, but the refresh time is checked first so
+        // we can guarantee this in this test.
 
-        let oidc = jws_validator
-            .verify(&oidc_unverified)
-            .unwrap()
-            .verify_exp(iat)
-            .expect("Failed to verify oidc");
+        let ct = Duration::from_secs(
+            TEST_CURRENT_TIME + refresh_exp as u64 + access_token_response_3.expires_in as u64,
+        );
 
-        assert_eq!(oidc.sub, OidcSubject::U(UUID_TESTPERSON_1));
+        let mut idms_prox_write = idms.proxy_write(ct).await.unwrap();
+
+        let refresh_token = access_token_response_3
+            .refresh_token
+            .as_ref()
+            .expect("no refresh token was issued")
+            .clone();
+
+        let token_req: AccessTokenRequest = GrantTypeReq::RefreshToken {
+            refresh_token,
+            scope: None,
+        }
+        .into();
+        let access_token_response_4 = idms_prox_write
+            .check_oauth2_token_exchange(&client_authz, &token_req, ct)
+            .unwrap_err();
+
+        assert_eq!(access_token_response_4, Oauth2Error::InvalidGrant);
 
         assert!(idms_prox_write.commit().is_ok());
     }
 
+    // refresh when OAuth2 parent session exp / missing.
     #[idm_test]
-    async fn test_idm_oauth2_consent_granted_and_changed_workflow(
+    async fn test_idm_oauth2_refresh_token_oauth2_session_expired(
         idms: &IdmServer,
-        _idms_delayed: &mut IdmServerDelayed,
+        idms_delayed: &mut IdmServerDelayed,
     ) {
+        // First, setup to get a token.
         let ct = Duration::from_secs(TEST_CURRENT_TIME);
-        let (_secret, uat, ident, _) =
-            setup_oauth2_resource_server_basic(idms, ct, true, false, false).await;
-
-        let idms_prox_read = idms.proxy_read().await.unwrap();
 
-        let pkce_secret = PkceS256Secret::default();
+        let (access_token_response_1, client_authz) =
+            setup_refresh_token(idms, idms_delayed, ct).await;
 
-        let consent_request = good_authorisation_request!(
-            idms_prox_read,
-            &ident,
-            ct,
-            pkce_secret.to_request(),
-            OAUTH2_SCOPE_OPENID.to_string()
-        );
+        // ============================================
+        // Revoke the OAuth2 session
 
-        // Should be in the consent phase;
-        let AuthoriseResponse::ConsentRequested { consent_token, .. } = consent_request else {
-            unreachable!();
+        let mut idms_prox_write = idms.proxy_write(ct).await.unwrap();
+        let revoke_request = TokenRevokeRequest {
+            token: access_token_response_1.access_token.clone(),
+            token_type_hint: None,
+            client_post_auth: ClientPostAuth::default(),
         };
+        assert!(idms_prox_write
+            .oauth2_token_revoke(&client_authz, &revoke_request, ct,)
+            .is_ok());
+        assert!(idms_prox_write.commit().is_ok());
 
-        // == Manually submit the consent token to the permit for the permit_success
-        drop(idms_prox_read);
+        // ============================================
+        // then attempt a refresh.
         let mut idms_prox_write = idms.proxy_write(ct).await.unwrap();
 
-        let _permit_success = idms_prox_write
-            .check_oauth2_authorise_permit(&ident, &consent_token, ct)
-            .expect("Failed to perform OAuth2 permit");
+        let refresh_token = access_token_response_1
+            .refresh_token
+            .as_ref()
+            .expect("no refresh token was issued")
+            .clone();
 
-        assert!(idms_prox_write.commit().is_ok());
+        let token_req: AccessTokenRequest = GrantTypeReq::RefreshToken {
+            refresh_token,
+            scope: None,
+        }
+        .into();
+        let access_token_response_2 = idms_prox_write
+            .check_oauth2_token_exchange(&client_authz, &token_req, ct)
+            // Should be unable to exchange.
+            .unwrap_err();
 
-        // == Now try the authorise again, should be in the permitted state.
-        let mut idms_prox_read = idms.proxy_read().await.unwrap();
+        assert_eq!(access_token_response_2, Oauth2Error::InvalidGrant);
 
-        // We need to reload our identity
-        let ident = idms_prox_read
-            .process_uat_to_identity(&uat, ct, Source::Internal)
-            .expect("Unable to process uat");
+        assert!(idms_prox_write.commit().is_ok());
+    }
 
-        let pkce_secret = PkceS256Secret::default();
+    // refresh with wrong client id/authz
+    #[idm_test]
+    async fn test_idm_oauth2_refresh_token_invalid_client_authz(
+        idms: &IdmServer,
+        idms_delayed: &mut IdmServerDelayed,
+    ) {
+        // First, setup to get a token.
+        let ct = Duration::from_secs(TEST_CURRENT_TIME);
 
-        let consent_request = good_authorisation_request!(
-            idms_prox_read,
-            &ident,
-            ct,
-            pkce_secret.to_request(),
-            OAUTH2_SCOPE_OPENID.to_string()
-        );
+        let (access_token_response_1, _client_authz) =
+            setup_refresh_token(idms, idms_delayed, ct).await;
 
-        // Should be in the consent phase;
-        let AuthoriseResponse::Permitted(_permit_success) = consent_request else {
-            unreachable!();
-        };
+        let bad_client_authz = ClientAuthInfo::encode_basic("test_resource_server", "12345");
 
-        drop(idms_prox_read);
+        // ============================================
+        // Refresh with invalid client authz
 
-        // Great! Now change the scopes on the OAuth2 instance, this revokes the permit.
         let mut idms_prox_write = idms.proxy_write(ct).await.unwrap();
 
-        let me_extend_scopes = ModifyEvent::new_internal_invalid(
-            filter!(f_eq(
-                Attribute::Name,
-                PartialValue::new_iname("test_resource_server")
-            )),
-            ModifyList::new_list(vec![Modify::Present(
-                Attribute::OAuth2RsScopeMap,
-                Value::new_oauthscopemap(
-                    UUID_IDM_ALL_ACCOUNTS,
-                    btreeset![
-                        OAUTH2_SCOPE_EMAIL.to_string(),
-                        OAUTH2_SCOPE_PROFILE.to_string(),
-                        OAUTH2_SCOPE_OPENID.to_string()
-                    ],
-                )
-                .expect("invalid oauthscope"),
-            )]),
-        );
+        let refresh_token = access_token_response_1
+            .refresh_token
+            .as_ref()
+            .expect("no refresh token was issued")
+            .clone();
+
+        let token_req: AccessTokenRequest = GrantTypeReq::RefreshToken {
+            refresh_token,
+            scope: None,
+        }
+        .into();
+        let access_token_response_2 = idms_prox_write
+            .check_oauth2_token_exchange(&bad_client_authz, &token_req, ct)
+            .unwrap_err();
+
+        assert_eq!(access_token_response_2, Oauth2Error::AuthenticationRequired);
 
-        assert!(idms_prox_write.qs_write.modify(&me_extend_scopes).is_ok());
         assert!(idms_prox_write.commit().is_ok());
+    }
 
-        // And do the workflow once more to see if we need to consent again.
+    // Incorrect scopes re-requested
+    #[idm_test]
+    async fn test_idm_oauth2_refresh_token_inconsistent_scopes(
+        idms: &IdmServer,
+        idms_delayed: &mut IdmServerDelayed,
+    ) {
+        // First, setup to get a token.
+        let ct = Duration::from_secs(TEST_CURRENT_TIME);
+
+        let (access_token_response_1, client_authz) =
+            setup_refresh_token(idms, idms_delayed, ct).await;
 
-        let mut idms_prox_read = idms.proxy_read().await.unwrap();
+        // ============================================
+        // Refresh with different scopes
 
-        // We need to reload our identity
-        let ident = idms_prox_read
-            .process_uat_to_identity(&uat, ct, Source::Internal)
-            .expect("Unable to process uat");
+        let mut idms_prox_write = idms.proxy_write(ct).await.unwrap();
 
-        let pkce_secret = PkceS256Secret::default();
+        let refresh_token = access_token_response_1
+            .refresh_token
+            .as_ref()
+            .expect("no refresh token was issued")
+            .clone();
 
-        let auth_req = AuthorisationRequest {
-            response_type: ResponseType::Code,
-            response_mode: None,
-            client_id: "test_resource_server".to_string(),
-            state: Some("123".to_string()),
-            pkce_request: Some(pkce_secret.to_request()),
-            redirect_uri: Url::parse("https://demo.example.com/oauth2/result").unwrap(),
-            scope: btreeset![
-                "openid".to_string(),
-                "email".to_string(),
-                "profile".to_string()
-            ],
-            nonce: Some("abcdef".to_string()),
-            oidc_ext: Default::default(),
-            max_age: None,
-            unknown_keys: Default::default(),
-        };
+        let token_req: AccessTokenRequest = GrantTypeReq::RefreshToken {
+            refresh_token,
+            scope: Some(btreeset!["invalid_scope".to_string()]),
+        }
+        .into();
+        let access_token_response_2 = idms_prox_write
+            .check_oauth2_token_exchange(&client_authz, &token_req, ct)
+            .unwrap_err();
 
-        let consent_request = idms_prox_read
-            .check_oauth2_authorisation(Some(&ident), &auth_req, ct)
-            .expect("Oauth2 authorisation failed");
+        assert_eq!(access_token_response_2, Oauth2Error::InvalidScope);
 
-        // Should be in the consent phase;
-        let AuthoriseResponse::ConsentRequested { .. } = consent_request else {
-            unreachable!();
-        };
+        assert!(idms_prox_write.commit().is_ok());
+    }
 
-        drop(idms_prox_read);
+    // Test that reuse of a refresh token is denied + terminates the session.
+    //
+    // https://www.ietf.org/archive/id/draft-ietf-oauth-security-topics-18.html#refresh_token_protection
+    #[idm_test]
+    async fn test_idm_oauth2_refresh_token_reuse_invalidates_session(
+        idms: &IdmServer,
+        idms_delayed: &mut IdmServerDelayed,
+    ) {
+        // First, setup to get a token.
+        let ct = Duration::from_secs(TEST_CURRENT_TIME);
 
-        // Success! We had to consent again due to the change :)
+        let (access_token_response_1, client_authz) =
+            setup_refresh_token(idms, idms_delayed, ct).await;
 
-        // Now change the supplemental scopes on the OAuth2 instance, this revokes the permit.
+        // ============================================
+        // Use the refresh token once
+        let ct = Duration::from_secs(TEST_CURRENT_TIME + 1);
         let mut idms_prox_write = idms.proxy_write(ct).await.unwrap();
 
-        let me_extend_scopes = ModifyEvent::new_internal_invalid(
-            filter!(f_eq(
-                Attribute::Name,
-                PartialValue::new_iname("test_resource_server")
-            )),
-            ModifyList::new_list(vec![Modify::Present(
-                Attribute::OAuth2RsSupScopeMap,
-                Value::new_oauthscopemap(UUID_IDM_ALL_ACCOUNTS, btreeset!["newscope".to_string()])
-                    .expect("invalid oauthscope"),
-            )]),
-        );
+        let refresh_token = access_token_response_1
+            .refresh_token
+            .as_ref()
+            .expect("no refresh token was issued")
+            .clone();
+
+        let token_req: AccessTokenRequest = GrantTypeReq::RefreshToken {
+            refresh_token,
+            scope: None,
+        }
+        .into();
+
+        let _access_token_response_2 = idms_prox_write
+            .check_oauth2_token_exchange(&client_authz, &token_req, ct)
+            .expect("Unable to exchange for OAuth2 token");
 
-        assert!(idms_prox_write.qs_write.modify(&me_extend_scopes).is_ok());
         assert!(idms_prox_write.commit().is_ok());
 
-        // And do the workflow once more to see if we need to consent again.
+        // Now use it again. - this will cause an error and the session to be terminated.
+        let ct = Duration::from_secs(TEST_CURRENT_TIME + 2);
+        let mut idms_prox_write = idms.proxy_write(ct).await.unwrap();
 
-        let mut idms_prox_read = idms.proxy_read().await.unwrap();
+        let refresh_token = access_token_response_1
+            .refresh_token
+            .as_ref()
+            .expect("no refresh token was issued")
+            .clone();
 
-        // We need to reload our identity
-        let ident = idms_prox_read
-            .process_uat_to_identity(&uat, ct, Source::Internal)
-            .expect("Unable to process uat");
+        let token_req: AccessTokenRequest = GrantTypeReq::RefreshToken {
+            refresh_token,
+            scope: None,
+        }
+        .into();
 
-        let pkce_secret = PkceS256Secret::default();
+        let access_token_response_3 = idms_prox_write
+            .check_oauth2_token_exchange(&client_authz, &token_req, ct)
+            .unwrap_err();
 
-        let auth_req = AuthorisationRequest {
-            response_type: ResponseType::Code,
-            response_mode: None,
-            client_id: "test_resource_server".to_string(),
-            state: Some("123".to_string()),
-            pkce_request: Some(pkce_secret.to_request()),
-            redirect_uri: Url::parse("https://demo.example.com/oauth2/result").unwrap(),
-            // Note the scope isn't requested here!
-            scope: btreeset![
-                "openid".to_string(),
-                "email".to_string(),
-                "profile".to_string()
-            ],
-            nonce: Some("abcdef".to_string()),
-            oidc_ext: Default::default(),
-            max_age: None,
-            unknown_keys: Default::default(),
-        };
+        assert_eq!(access_token_response_3, Oauth2Error::InvalidGrant);
 
-        let consent_request = idms_prox_read
-            .check_oauth2_authorisation(Some(&ident), &auth_req, ct)
-            .expect("Oauth2 authorisation failed");
+        let entry = idms_prox_write
+            .qs_write
+            .internal_search_uuid(UUID_TESTPERSON_1)
+            .expect("failed");
+        let valid = entry
+            .get_ava_as_oauth2session_map(Attribute::OAuth2Session)
+            .and_then(|sessions| sessions.first_key_value())
+            .map(|(_, session)| !matches!(session.state, SessionState::RevokedAt(_)))
+            // If there is no map, then something is wrong.
+            .unwrap();
+        // The session should be invalid at this point.
+        assert!(!valid);
 
-        // Should be present in the consent phase however!
-        let _consent_token = if let AuthoriseResponse::ConsentRequested {
-            consent_token,
-            scopes,
-            ..
-        } = consent_request
-        {
-            assert!(scopes.contains("newscope"));
-            consent_token
-        } else {
-            unreachable!();
-        };
+        assert!(idms_prox_write.commit().is_ok());
     }
 
+    // Test session divergence. This means that we have to:
+    // access + refresh 1
+    // use refresh 1 -> access + refresh 2 // don't commit this txn.
+    // use refresh 2 -> access + refresh 3
+    //    check the session state.
+
     #[idm_test]
-    async fn test_idm_oauth2_consent_granted_refint_cleanup_on_delete(
+    async fn test_idm_oauth2_refresh_token_divergence(
         idms: &IdmServer,
-        _idms_delayed: &mut IdmServerDelayed,
+        idms_delayed: &mut IdmServerDelayed,
     ) {
+        // First, setup to get a token.
         let ct = Duration::from_secs(TEST_CURRENT_TIME);
-        let (_secret, uat, ident, o2rs_uuid) =
-            setup_oauth2_resource_server_basic(idms, ct, true, false, false).await;
-
-        // Assert there are no consent maps yet.
-        assert!(ident.get_oauth2_consent_scopes(o2rs_uuid).is_none());
-
-        let idms_prox_read = idms.proxy_read().await.unwrap();
-
-        let pkce_secret = PkceS256Secret::default();
-        let consent_request = good_authorisation_request!(
-            idms_prox_read,
-            &ident,
-            ct,
-            pkce_secret.to_request(),
-            OAUTH2_SCOPE_OPENID.to_string()
-        );
 
-        // Should be in the consent phase;
-        let AuthoriseResponse::ConsentRequested { consent_token, .. } = consent_request else {
-            unreachable!();
-        };
+        let (access_token_response_1, client_authz) =
+            setup_refresh_token(idms, idms_delayed, ct).await;
 
-        // == Manually submit the consent token to the permit for the permit_success
-        drop(idms_prox_read);
+        // ============================================
+        // Use the refresh token once
+        let ct = Duration::from_secs(TEST_CURRENT_TIME + 1);
         let mut idms_prox_write = idms.proxy_write(ct).await.unwrap();
 
-        let _permit_success = idms_prox_write
-            .check_oauth2_authorise_permit(&ident, &consent_token, ct)
-            .expect("Failed to perform OAuth2 permit");
+        let refresh_token = access_token_response_1
+            .refresh_token
+            .as_ref()
+            .expect("no refresh token was issued")
+            .clone();
 
-        let ident = idms_prox_write
-            .process_uat_to_identity(&uat, ct, Source::Internal)
-            .expect("Unable to process uat");
+        let token_req: AccessTokenRequest = GrantTypeReq::RefreshToken {
+            refresh_token,
+            scope: None,
+        }
+        .into();
 
-        // Assert that the ident now has the consents.
-        assert!(
-            ident.get_oauth2_consent_scopes(o2rs_uuid)
-                == Some(&btreeset![
-                    OAUTH2_SCOPE_OPENID.to_string(),
-                    "supplement".to_string()
-                ])
-        );
+        let access_token_response_2 = idms_prox_write
+            .check_oauth2_token_exchange(&client_authz, &token_req, ct)
+            .expect("Unable to exchange for OAuth2 token");
 
-        // Now trigger the delete of the RS
-        let de = DeleteEvent::new_internal_invalid(filter!(f_eq(
-            Attribute::Name,
-            PartialValue::new_iname("test_resource_server")
-        )));
+        // DO NOT COMMIT HERE - this is what forces the session issued_at
+        // time to stay at the original time!
+        drop(idms_prox_write);
 
-        assert!(idms_prox_write.qs_write.delete(&de).is_ok());
-        // Assert the consent maps are gone.
-        let ident = idms_prox_write
-            .process_uat_to_identity(&uat, ct, Source::Internal)
-            .expect("Unable to process uat");
-        dbg!(&o2rs_uuid);
-        dbg!(&ident);
-        let consent_scopes = ident.get_oauth2_consent_scopes(o2rs_uuid);
-        dbg!(consent_scopes);
-        assert!(consent_scopes.is_none());
+        // ============================================
+        let ct = Duration::from_secs(TEST_CURRENT_TIME + 2);
+        let mut idms_prox_write = idms.proxy_write(ct).await.unwrap();
+
+        let refresh_token = access_token_response_2
+            .refresh_token
+            .as_ref()
+            .expect("no refresh token was issued")
+            .clone();
+
+        let token_req: AccessTokenRequest = GrantTypeReq::RefreshToken {
+            refresh_token,
+            scope: None,
+        }
+        .into();
+
+        let _access_token_response_3 = idms_prox_write
+            .check_oauth2_token_exchange(&client_authz, &token_req, ct)
+            .expect("Unable to exchange for OAuth2 token");
 
         assert!(idms_prox_write.commit().is_ok());
+
+        // Success!
     }
 
-    // https://datatracker.ietf.org/doc/html/draft-ietf-oauth-security-topics#section-4.8
-    //
-    // It was reported we were vulnerable to this attack, but that isn't the case. First
-    // this attack relies on stripping the *code_challenge* from the internals of the returned
-    // code exchange token. This isn't possible due to our use of encryption of the code exchange
-    // token. If that code challenge *could* be removed, then the attacker could use the code exchange
-    // with no verifier or an incorrect verifier.
-    //
-    // Due to the logic in our server, if a code exchange contains a code challenge we always enforce
-    // it is correctly used!
-    //
-    // This left a single odd case where if a client did an authorisation request without a pkce
-    // verifier, but then a verifier was submitted during the code exchange, that the server would
-    // *ignore* the verifier parameter. In this case, no stripping of the code challenge was done,
-    // and the client could have simply also submitted *no* verifier anyway. It could be that
-    // an attacker could gain a code exchange with no code challenge and then force a victim to
-    // exchange that code exchange with out the verifier, but I'm not sure what damage that would
-    // lead to? Regardless, we test for and close off that possible hole in this test.
-    //
     #[idm_test]
-    async fn test_idm_oauth2_1076_pkce_downgrade(
+    async fn test_idm_oauth2_refresh_token_scope_constraints(
         idms: &IdmServer,
-        _idms_delayed: &mut IdmServerDelayed,
+        idms_delayed: &mut IdmServerDelayed,
     ) {
+        // First, setup to get a token.
         let ct = Duration::from_secs(TEST_CURRENT_TIME);
-        // Enable pkce is set to FALSE
-        let (secret, _uat, ident, _) =
-            setup_oauth2_resource_server_basic(idms, ct, false, false, false).await;
 
-        let idms_prox_read = idms.proxy_read().await.unwrap();
+        let (access_token_response_1, client_authz) =
+            setup_refresh_token(idms, idms_delayed, ct).await;
 
-        // Get an ident/uat for now.
+        // https://www.rfc-editor.org/rfc/rfc6749#section-1.5
+        // Refresh tokens are issued to the client by the authorization
+        // server and are used to obtain a new access token when the
+        // current access token becomes invalid or expires, or to obtain
+        // additional access tokens with identical or narrower scope
+        // (access tokens may have a shorter lifetime and fewer
+        // permissions than authorized by the resource owner).
 
-        // == Setup the authorisation request
-        // We attempt pkce even though the rs is set to not support pkce.
-        let pkce_secret = PkceS256Secret::default();
+        let ct = Duration::from_secs(TEST_CURRENT_TIME + 10);
+        let mut idms_prox_write = idms.proxy_write(ct).await.unwrap();
 
-        // First, the user does not request pkce in their exchange.
-        let auth_req = AuthorisationRequest {
-            response_type: ResponseType::Code,
-            response_mode: None,
-            client_id: "test_resource_server".to_string(),
-            state: Some("123".to_string()),
-            pkce_request: None,
-            redirect_uri: Url::parse("https://demo.example.com/oauth2/result").unwrap(),
-            scope: btreeset![OAUTH2_SCOPE_OPENID.to_string()],
-            nonce: None,
-            oidc_ext: Default::default(),
-            max_age: None,
-            unknown_keys: Default::default(),
-        };
+        let refresh_token = access_token_response_1
+            .refresh_token
+            .as_ref()
+            .expect("no refresh token was issued")
+            .clone();
 
-        let consent_request = idms_prox_read
-            .check_oauth2_authorisation(Some(&ident), &auth_req, ct)
-            .expect("Failed to perform OAuth2 authorisation request.");
+        // Get the initial scopes.
+        let jws_verifier = JwsDangerReleaseWithoutVerify::default();
 
-        // Should be in the consent phase;
-        let AuthoriseResponse::ConsentRequested { consent_token, .. } = consent_request else {
-            unreachable!();
-        };
+        let access_token_unverified = JwsCompact::from_str(&access_token_response_1.access_token)
+            .expect("Invalid Access Token");
 
-        // == Manually submit the consent token to the permit for the permit_success
-        drop(idms_prox_read);
-        let mut idms_prox_write = idms.proxy_write(ct).await.unwrap();
+        let reflected_token = jws_verifier
+            .verify(&access_token_unverified)
+            .unwrap()
+            .from_json::<OAuth2RFC9068Token<OAuth2RFC9068TokenExtensions>>()
+            .expect("Failed to access internals of the refresh token");
 
-        let permit_success = idms_prox_write
-            .check_oauth2_authorise_permit(&ident, &consent_token, ct)
-            .expect("Failed to perform OAuth2 permit");
+        trace!(?reflected_token);
+        let initial_scopes = reflected_token.extensions.scope;
+        trace!(?initial_scopes);
 
-        // == Submit the token exchange code.
-        // This exchange failed because we submitted a verifier when the code exchange
-        // has NO code challenge present.
-        let token_req = AccessTokenRequest {
-            grant_type: GrantTypeReq::AuthorizationCode {
-                code: permit_success.code,
-                redirect_uri: Url::parse("https://demo.example.com/oauth2/result").unwrap(),
-                code_verifier: Some(pkce_secret.to_verifier()),
-            },
-            client_post_auth: ClientPostAuth {
-                client_id: Some("test_resource_server".to_string()),
-                client_secret: Some(secret),
-            },
-        };
+        // Should be the same scopes as initial.
+        let token_req: AccessTokenRequest = GrantTypeReq::RefreshToken {
+            refresh_token,
+            scope: None,
+        }
+        .into();
 
-        // Assert the exchange fails.
-        assert!(matches!(
-            idms_prox_write.check_oauth2_token_exchange(&ClientAuthInfo::none(), &token_req, ct),
-            Err(Oauth2Error::InvalidRequest)
-        ));
+        let access_token_response_2 = idms_prox_write
+            .check_oauth2_token_exchange(&client_authz, &token_req, ct)
+            .expect("Unable to exchange for OAuth2 token");
 
-        assert!(idms_prox_write.commit().is_ok());
-    }
+        let access_token_unverified = JwsCompact::from_str(&access_token_response_2.access_token)
+            .expect("Invalid Access Token");
 
-    #[idm_test]
-    // https://datatracker.ietf.org/doc/html/draft-ietf-oauth-security-topics#section-2.1
-    //
-    // If the origin configured is https, do not allow downgrading to http on redirect
-    async fn test_idm_oauth2_redir_http_downgrade(
-        idms: &IdmServer,
-        _idms_delayed: &mut IdmServerDelayed,
-    ) {
-        let ct = Duration::from_secs(TEST_CURRENT_TIME);
-        // Enable pkce is set to FALSE
-        let (secret, _uat, ident, _) =
-            setup_oauth2_resource_server_basic(idms, ct, false, false, false).await;
+        let reflected_token = jws_verifier
+            .verify(&access_token_unverified)
+            .unwrap()
+            .from_json::<OAuth2RFC9068Token<OAuth2RFC9068TokenExtensions>>()
+            .expect("Failed to access internals of the refresh token");
 
-        let idms_prox_read = idms.proxy_read().await.unwrap();
+        assert_eq!(initial_scopes, reflected_token.extensions.scope);
 
-        // Get an ident/uat for now.
+        let refresh_token = access_token_response_2
+            .refresh_token
+            .as_ref()
+            .expect("no refresh token was issued")
+            .clone();
 
-        // == Setup the authorisation request
-        // We attempt pkce even though the rs is set to not support pkce.
-        let pkce_secret = PkceS256Secret::default();
+        // Now the scopes can be constrained.
+        let token_req: AccessTokenRequest = GrantTypeReq::RefreshToken {
+            refresh_token,
+            scope: Some(["openid".to_string()].into()),
+        }
+        .into();
 
-        // First, NOTE the lack of https on the redir uri.
-        let auth_req = AuthorisationRequest {
-            response_type: ResponseType::Code,
-            response_mode: None,
-            client_id: "test_resource_server".to_string(),
-            state: Some("123".to_string()),
-            pkce_request: Some(pkce_secret.to_request()),
-            redirect_uri: Url::parse("http://demo.example.com/oauth2/result").unwrap(),
-            scope: btreeset![OAUTH2_SCOPE_OPENID.to_string()],
-            nonce: None,
-            oidc_ext: Default::default(),
-            max_age: None,
-            unknown_keys: Default::default(),
-        };
+        let access_token_response_3 = idms_prox_write
+            .check_oauth2_token_exchange(&client_authz, &token_req, ct)
+            .expect("Unable to exchange for OAuth2 token");
 
-        assert!(
-            idms_prox_read
-                .check_oauth2_authorisation(Some(&ident), &auth_req, ct)
-                .unwrap_err()
-                == Oauth2Error::InvalidOrigin
-        );
+        let access_token_unverified = JwsCompact::from_str(&access_token_response_3.access_token)
+            .expect("Invalid Access Token");
 
-        // This does have https
-        let consent_request = good_authorisation_request!(
-            idms_prox_read,
-            &ident,
-            ct,
-            pkce_secret.to_request(),
-            OAUTH2_SCOPE_OPENID.to_string()
-        );
+        let reflected_token = jws_verifier
+            .verify(&access_token_unverified)
+            .unwrap()
+            .from_json::<OAuth2RFC9068Token<OAuth2RFC9068TokenExtensions>>()
+            .expect("Failed to access internals of the refresh token");
+
+        assert_ne!(initial_scopes, reflected_token.extensions.scope);
+
+        // Keep the constrained scopes.
+        let constrained_scopes = reflected_token.extensions.scope;
+
+        let refresh_token = access_token_response_3
+            .refresh_token
+            .as_ref()
+            .expect("no refresh token was issued")
+            .clone();
+
+        // No scope request still issues the constrained values.
+        let token_req: AccessTokenRequest = GrantTypeReq::RefreshToken {
+            refresh_token,
+            scope: None,
+        }
+        .into();
+
+        let access_token_response_4 = idms_prox_write
+            .check_oauth2_token_exchange(&client_authz, &token_req, ct)
+            .expect("Unable to exchange for OAuth2 token");
+
+        let access_token_unverified = JwsCompact::from_str(&access_token_response_4.access_token)
+            .expect("Invalid Access Token");
+
+        let reflected_token = jws_verifier
+            .verify(&access_token_unverified)
+            .unwrap()
+            .from_json::<OAuth2RFC9068Token<OAuth2RFC9068TokenExtensions>>()
+            .expect("Failed to access internals of the refresh token");
+
+        assert_ne!(initial_scopes, reflected_token.extensions.scope);
+        assert_eq!(constrained_scopes, reflected_token.extensions.scope);
+
+        let refresh_token = access_token_response_4
+            .refresh_token
+            .as_ref()
+            .expect("no refresh token was issued")
+            .clone();
+
+        // We can't now extend back to the initial scopes.
+        let token_req: AccessTokenRequest = GrantTypeReq::RefreshToken {
+            refresh_token,
+            scope: Some(initial_scopes),
+        }
+        .into();
+
+        let access_token_response_5_err = idms_prox_write
+            .check_oauth2_token_exchange(&client_authz, &token_req, ct)
+            .unwrap_err();
+
+        assert_eq!(access_token_response_5_err, Oauth2Error::InvalidScope);
 
-        // Should be in the consent phase;
-        let AuthoriseResponse::ConsentRequested { consent_token, .. } = consent_request else {
-            unreachable!();
-        };
+        assert!(idms_prox_write.commit().is_ok());
+    }
 
-        // == Manually submit the consent token to the permit for the permit_success
-        drop(idms_prox_read);
-        let mut idms_prox_write = idms.proxy_write(ct).await.unwrap();
+    #[test]
+    // I know this looks kinda dumb but at some point someone pointed out that our scope syntax wasn't compliant with rfc6749
+    //(https://datatracker.ietf.org/doc/html/rfc6749#section-3.3), so I'm just making sure that we don't break it again.
+    fn compliant_serialization_test() {
+        let token_req: Result<AccessTokenRequest, serde_json::Error> = serde_json::from_str(
+            r#"
+            {
+                "grant_type": "refresh_token",
+                "refresh_token": "some_dumb_refresh_token",
+                "scope": "invalid_scope vasd asd"
+            }
+        "#,
+        );
+        assert!(token_req.is_ok());
+    }
 
-        let permit_success = idms_prox_write
-            .check_oauth2_authorise_permit(&ident, &consent_token, ct)
-            .expect("Failed to perform OAuth2 permit");
+    #[idm_test]
+    async fn test_idm_oauth2_custom_claims(idms: &IdmServer, _idms_delayed: &mut IdmServerDelayed) {
+        let ct = Duration::from_secs(TEST_CURRENT_TIME);
+        let (secret, _uat, ident, oauth2_rs_uuid) =
+            setup_oauth2_resource_server_basic(idms, ct, true, false, false).await;
 
-        // == Submit the token exchange code.
-        // NOTE the url is http again
-        let token_req = AccessTokenRequest {
-            grant_type: GrantTypeReq::AuthorizationCode {
-                code: permit_success.code,
-                redirect_uri: Url::parse("http://demo.example.com/oauth2/result").unwrap(),
-                code_verifier: Some(pkce_secret.to_verifier()),
-            },
+        // Setup custom claim maps here.
+        let mut idms_prox_write = idms.proxy_write(ct).await.unwrap();
 
-            client_post_auth: ClientPostAuth {
-                client_id: Some("test_resource_server".to_string()),
-                client_secret: Some(secret),
-            },
-        };
+        let modlist = ModifyList::new_list(vec![
+            // Member of a claim map.
+            Modify::Present(
+                Attribute::OAuth2RsClaimMap,
+                Value::OauthClaimMap(
+                    "custom_a".to_string(),
+                    OauthClaimMapJoin::CommaSeparatedValue,
+                ),
+            ),
+            Modify::Present(
+                Attribute::OAuth2RsClaimMap,
+                Value::OauthClaimValue(
+                    "custom_a".to_string(),
+                    UUID_TESTGROUP,
+                    btreeset!["value_a".to_string()],
+                ),
+            ),
+            // If you are a member of two groups, the claim maps merge.
+            Modify::Present(
+                Attribute::OAuth2RsClaimMap,
+                Value::OauthClaimValue(
+                    "custom_a".to_string(),
+                    UUID_IDM_ALL_ACCOUNTS,
+                    btreeset!["value_b".to_string()],
+                ),
+            ),
+            // Map with a different separator
+            Modify::Present(
+                Attribute::OAuth2RsClaimMap,
+                Value::OauthClaimMap(
+                    "custom_b".to_string(),
+                    OauthClaimMapJoin::SpaceSeparatedValue,
+                ),
+            ),
+            Modify::Present(
+                Attribute::OAuth2RsClaimMap,
+                Value::OauthClaimValue(
+                    "custom_b".to_string(),
+                    UUID_TESTGROUP,
+                    btreeset!["value_a".to_string()],
+                ),
+            ),
+            Modify::Present(
+                Attribute::OAuth2RsClaimMap,
+                Value::OauthClaimValue(
+                    "custom_b".to_string(),
+                    UUID_IDM_ALL_ACCOUNTS,
+                    btreeset!["value_b".to_string()],
+                ),
+            ),
+            // Not a member of the claim map.
+            Modify::Present(
+                Attribute::OAuth2RsClaimMap,
+                Value::OauthClaimValue(
+                    "custom_b".to_string(),
+                    UUID_IDM_ADMINS,
+                    btreeset!["value_c".to_string()],
+                ),
+            ),
+            // Extended claim name syntax, allows characters beyond scope names.
+            Modify::Present(
+                Attribute::OAuth2RsClaimMap,
+                Value::OauthClaimMap(
+                    "custom:claim-name".to_string(),
+                    OauthClaimMapJoin::CommaSeparatedValue,
+                ),
+            ),
+            Modify::Present(
+                Attribute::OAuth2RsClaimMap,
+                Value::OauthClaimValue(
+                    "custom:claim-name".to_string(),
+                    UUID_TESTGROUP,
+                    btreeset!["value:a-a".to_string()],
+                ),
+            ),
+        ]);
 
-        // Assert the exchange fails.
-        assert!(matches!(
-            idms_prox_write.check_oauth2_token_exchange(&ClientAuthInfo::none(), &token_req, ct),
-            Err(Oauth2Error::InvalidOrigin)
-        ));
+        assert!(idms_prox_write
+            .qs_write
+            .internal_modify(
+                &filter!(f_eq(Attribute::Uuid, PartialValue::Uuid(oauth2_rs_uuid))),
+                &modlist,
+            )
+            .is_ok());
 
         assert!(idms_prox_write.commit().is_ok());
-    }
 
-    async fn setup_refresh_token(
-        idms: &IdmServer,
-        _idms_delayed: &mut IdmServerDelayed,
-        ct: Duration,
-    ) -> (AccessTokenResponse, ClientAuthInfo) {
-        // First, setup to get a token.
-        let (secret, _uat, ident, _) =
-            setup_oauth2_resource_server_basic(idms, ct, true, false, false).await;
+        // Claim maps setup, lets go.
         let client_authz = ClientAuthInfo::encode_basic("test_resource_server", secret.as_str());
 
         let idms_prox_read = idms.proxy_read().await.unwrap();
 
-        // == Setup the authorisation request
         let pkce_secret = PkceS256Secret::default();
 
         let consent_request = good_authorisation_request!(
@@ -6669,696 +11237,1142 @@ mod tests {
             .check_oauth2_authorise_permit(&ident, &consent_token, ct)
             .expect("Failed to perform OAuth2 permit");
 
+        // == Submit the token exchange code.
         let token_req: AccessTokenRequest = GrantTypeReq::AuthorizationCode {
             code: permit_success.code,
             redirect_uri: Url::parse("https://demo.example.com/oauth2/result").unwrap(),
             code_verifier: Some(pkce_secret.to_verifier()),
         }
         .into();
-        let access_token_response_1 = idms_prox_write
-            .check_oauth2_token_exchange(&client_authz, &token_req, ct)
-            .expect("Unable to exchange for OAuth2 token");
-
-        assert!(idms_prox_write.commit().is_ok());
-
-        trace!(?access_token_response_1);
-
-        (access_token_response_1, client_authz)
-    }
-
-    #[idm_test]
-    async fn test_idm_oauth2_refresh_token_basic(
-        idms: &IdmServer,
-        idms_delayed: &mut IdmServerDelayed,
-    ) {
-        // First, setup to get a token.
-        let ct = Duration::from_secs(TEST_CURRENT_TIME);
-
-        let (access_token_response_1, client_authz) =
-            setup_refresh_token(idms, idms_delayed, ct).await;
-
-        // ============================================
-        // test basic refresh while access still valid.
-
-        let ct = Duration::from_secs(TEST_CURRENT_TIME + 10);
-        let mut idms_prox_write = idms.proxy_write(ct).await.unwrap();
-
-        let refresh_token = access_token_response_1
-            .refresh_token
-            .as_ref()
-            .expect("no refresh token was issued")
-            .clone();
-
-        let token_req: AccessTokenRequest = GrantTypeReq::RefreshToken {
-            refresh_token,
-            scope: None,
-        }
-        .into();
-
-        let access_token_response_2 = idms_prox_write
-            .check_oauth2_token_exchange(&client_authz, &token_req, ct)
-            .expect("Unable to exchange for OAuth2 token");
-
-        assert!(idms_prox_write.commit().is_ok());
-
-        trace!(?access_token_response_2);
-
-        assert!(access_token_response_1.access_token != access_token_response_2.access_token);
-        assert!(access_token_response_1.refresh_token != access_token_response_2.refresh_token);
-        assert!(access_token_response_1.id_token != access_token_response_2.id_token);
-
-        // ============================================
-        // test basic refresh after access exp
-        let ct =
-            Duration::from_secs(TEST_CURRENT_TIME + 20 + access_token_response_2.expires_in as u64);
-
-        let mut idms_prox_write = idms.proxy_write(ct).await.unwrap();
-
-        let refresh_token = access_token_response_2
-            .refresh_token
-            .as_ref()
-            .expect("no refresh token was issued")
-            .clone();
-
-        // get the refresh token expiry now before we use it.
-        let reflected_token = idms_prox_write
-            .reflect_oauth2_token(&client_authz, &refresh_token)
-            .expect("Failed to access internals of the refresh token");
-
-        let refresh_exp = match reflected_token {
-            Oauth2TokenType::Refresh { exp, .. } => exp,
-            // Oauth2TokenType::Access { .. } |
-            Oauth2TokenType::ClientAccess { .. } => unreachable!(),
-        };
-
-        let token_req: AccessTokenRequest = GrantTypeReq::RefreshToken {
-            refresh_token,
-            scope: None,
-        }
-        .into();
 
-        let access_token_response_3 = idms_prox_write
+        let token_response = idms_prox_write
             .check_oauth2_token_exchange(&client_authz, &token_req, ct)
-            .expect("Unable to exchange for OAuth2 token");
-
-        // Get the user entry to check the session life was extended.
-
-        let entry = idms_prox_write
-            .qs_write
-            .internal_search_uuid(UUID_TESTPERSON_1)
-            .expect("failed");
-        let session = entry
-            .get_ava_as_oauth2session_map(Attribute::OAuth2Session)
-            .and_then(|sessions| sessions.first_key_value())
-            // If there is no map, then something is wrong.
-            .unwrap();
+            .expect("Failed to perform OAuth2 token exchange");
 
-        trace!(?session);
-        // The Oauth2 Session must be updated with a newer session time.
-        assert_eq!(
-            SessionState::ExpiresAt(
-                time::OffsetDateTime::UNIX_EPOCH
-                    + ct
-                    + Duration::from_secs(OAUTH_REFRESH_TOKEN_EXPIRY)
-            ),
-            session.1.state
-        );
+        // 🎉 We got a token!
+        assert_eq!(token_response.token_type, AccessTokenType::Bearer);
+
+        let id_token = token_response.id_token.expect("No id_token in response!");
+        let access_token =
+            JwsCompact::from_str(&token_response.access_token).expect("Invalid Access Token");
 
+        // Get the read txn for inspecting the tokens
         assert!(idms_prox_write.commit().is_ok());
 
-        trace!(?access_token_response_3);
+        let mut idms_prox_read = idms.proxy_read().await.unwrap();
 
-        assert!(access_token_response_3.access_token != access_token_response_2.access_token);
-        assert!(access_token_response_3.refresh_token != access_token_response_2.refresh_token);
-        assert!(access_token_response_3.id_token != access_token_response_2.id_token);
+        let mut jwkset = idms_prox_read
+            .oauth2_openid_publickey("test_resource_server")
+            .expect("Failed to get public key");
 
-        // refresh after refresh has expired.
-        // Refresh tokens have a max time limit - the session time limit still bounds it though, but
-        // so does the refresh token limit. We check both, but the refresh time is checked first so
-        // we can guarantee this in this test.
+        let public_jwk = jwkset.keys.pop().expect("no such jwk");
 
-        let ct = Duration::from_secs(
-            TEST_CURRENT_TIME + refresh_exp as u64 + access_token_response_3.expires_in as u64,
+        let jws_validator =
+            JwsEs256Verifier::try_from(&public_jwk).expect("failed to build validator");
+
+        let oidc_unverified =
+            OidcUnverified::from_str(&id_token).expect("Failed to parse id_token");
+
+        let iat = ct.as_secs() as i64;
+
+        let oidc = jws_validator
+            .verify(&oidc_unverified)
+            .unwrap()
+            .verify_exp(iat)
+            .expect("Failed to verify oidc");
+
+        // Are the id_token values what we expect?
+        assert!(
+            oidc.iss
+                == Url::parse("https://idm.example.com/oauth2/openid/test_resource_server")
+                    .unwrap()
+        );
+        assert_eq!(oidc.sub, OidcSubject::U(UUID_TESTPERSON_1));
+        assert_eq!(oidc.aud, "test_resource_server");
+        assert_eq!(oidc.iat, iat);
+        assert_eq!(oidc.nbf, Some(iat));
+        // Previously this was the auth session but it's now inline with the access token expiry.
+        assert_eq!(oidc.exp, iat + (OAUTH2_ACCESS_TOKEN_EXPIRY as i64));
+        assert!(oidc.auth_time.is_none());
+        // Is nonce correctly passed through?
+        assert_eq!(oidc.nonce, Some("abcdef".to_string()));
+        assert!(oidc.at_hash.is_none());
+        assert!(oidc.acr.is_none());
+        assert!(oidc.amr.is_none());
+        assert_eq!(oidc.azp, Some("test_resource_server".to_string()));
+        assert!(oidc.jti.is_some());
+        if let Some(jti) = &oidc.jti {
+            assert!(Uuid::from_str(jti).is_ok());
+        }
+        assert_eq!(oidc.s_claims.name, Some("Test Person 1".to_string()));
+        assert_eq!(
+            oidc.s_claims.preferred_username,
+            Some("testperson1@example.com".to_string())
+        );
+        assert!(
+            oidc.s_claims.scopes == vec![OAUTH2_SCOPE_OPENID.to_string(), "supplement".to_string()]
         );
 
-        let mut idms_prox_write = idms.proxy_write(ct).await.unwrap();
+        assert_eq!(
+            oidc.claims.get("custom_a").and_then(|v| v.as_str()),
+            Some("value_a,value_b")
+        );
+        assert_eq!(
+            oidc.claims.get("custom_b").and_then(|v| v.as_str()),
+            Some("value_a value_b")
+        );
 
-        let refresh_token = access_token_response_3
-            .refresh_token
-            .as_ref()
-            .expect("no refresh token was issued")
-            .clone();
+        assert_eq!(
+            oidc.claims
+                .get("custom:claim-name")
+                .and_then(|v| v.as_str()),
+            Some("value:a-a")
+        );
 
-        let token_req: AccessTokenRequest = GrantTypeReq::RefreshToken {
-            refresh_token,
-            scope: None,
+        // Does our access token work with the userinfo endpoint?
+        // Do the id_token details line up to the userinfo?
+        let userinfo = idms_prox_read
+            .oauth2_openid_userinfo("test_resource_server", &access_token, None, None, ct)
+            .expect("failed to get userinfo");
+
+        assert_eq!(oidc.iss, userinfo.iss);
+        assert_eq!(oidc.sub, userinfo.sub);
+        assert_eq!(oidc.aud, userinfo.aud);
+        assert_eq!(oidc.iat, userinfo.iat);
+        assert_eq!(oidc.nbf, userinfo.nbf);
+        assert_eq!(oidc.exp, userinfo.exp);
+        assert!(userinfo.auth_time.is_none());
+        assert_eq!(userinfo.nonce, Some("abcdef".to_string()));
+        assert!(userinfo.at_hash.is_none());
+        assert!(userinfo.jti.is_some());
+        if let Some(jti) = &userinfo.jti {
+            assert!(Uuid::from_str(jti).is_ok());
         }
-        .into();
-        let access_token_response_4 = idms_prox_write
-            .check_oauth2_token_exchange(&client_authz, &token_req, ct)
-            .unwrap_err();
+        assert_eq!(oidc.amr, userinfo.amr);
+        assert_eq!(oidc.azp, userinfo.azp);
+        assert!(userinfo.jti.is_some());
+        if let Some(jti) = &userinfo.jti {
+            assert!(Uuid::from_str(jti).is_ok());
+        }
+        assert_eq!(oidc.s_claims, userinfo.s_claims);
+        assert_eq!(oidc.claims, userinfo.claims);
 
-        assert_eq!(access_token_response_4, Oauth2Error::InvalidGrant);
+        // Check the oauth2 introspect bits.
+        let intr_request = AccessTokenIntrospectRequest {
+            token: token_response.access_token.clone(),
+            token_type_hint: None,
+            client_post_auth: ClientPostAuth::default(),
+        };
+        let intr_response = idms_prox_read
+            .check_oauth2_token_introspect(&client_authz, &intr_request, ct)
+            .expect("Failed to inspect token");
 
-        assert!(idms_prox_write.commit().is_ok());
+        eprintln!("👉  {intr_response:?}");
+        assert!(intr_response.active);
+        assert_eq!(
+            intr_response.scope,
+            btreeset!["openid".to_string(), "supplement".to_string()]
+        );
+        assert_eq!(
+            intr_response.client_id.as_deref(),
+            Some("test_resource_server")
+        );
+        assert_eq!(
+            intr_response.username.as_deref(),
+            Some("testperson1@example.com")
+        );
+        assert_eq!(intr_response.token_type, Some(AccessTokenType::Bearer));
+        assert_eq!(intr_response.iat, Some(ct.as_secs() as i64));
+        assert_eq!(intr_response.nbf, Some(ct.as_secs() as i64));
+        // Introspect doesn't have custom claims.
+
+        drop(idms_prox_read);
     }
 
-    // refresh when OAuth2 parent session exp / missing.
     #[idm_test]
-    async fn test_idm_oauth2_refresh_token_oauth2_session_expired(
+    async fn test_idm_oauth2_public_allow_localhost_redirect(
         idms: &IdmServer,
-        idms_delayed: &mut IdmServerDelayed,
+        _idms_delayed: &mut IdmServerDelayed,
     ) {
-        // First, setup to get a token.
         let ct = Duration::from_secs(TEST_CURRENT_TIME);
+        let (_uat, ident, oauth2_rs_uuid) = setup_oauth2_resource_server_public(idms, ct).await;
 
-        let (access_token_response_1, client_authz) =
-            setup_refresh_token(idms, idms_delayed, ct).await;
+        let mut idms_prox_write: crate::idm::server::IdmServerProxyWriteTransaction<'_> =
+            idms.proxy_write(ct).await.unwrap();
 
-        // ============================================
-        // Revoke the OAuth2 session
+        let redirect_uri = Url::parse("http://localhost:8765/oauth2/result")
+            .expect("Failed to parse redirect URL");
+
+        let modlist = ModifyList::new_list(vec![
+            Modify::Present(Attribute::OAuth2AllowLocalhostRedirect, Value::Bool(true)),
+            Modify::Present(Attribute::OAuth2RsOrigin, Value::Url(redirect_uri.clone())),
+        ]);
 
-        let mut idms_prox_write = idms.proxy_write(ct).await.unwrap();
-        let revoke_request = TokenRevokeRequest {
-            token: access_token_response_1.access_token.clone(),
-            token_type_hint: None,
-            client_post_auth: ClientPostAuth::default(),
-        };
         assert!(idms_prox_write
-            .oauth2_token_revoke(&client_authz, &revoke_request, ct,)
+            .qs_write
+            .internal_modify(
+                &filter!(f_eq(Attribute::Uuid, PartialValue::Uuid(oauth2_rs_uuid))),
+                &modlist,
+            )
             .is_ok());
+
         assert!(idms_prox_write.commit().is_ok());
 
-        // ============================================
-        // then attempt a refresh.
+        let idms_prox_read = idms.proxy_read().await.unwrap();
+
+        // == Setup the authorisation request
+        let pkce_secret = PkceS256Secret::default();
+
+        let auth_req = AuthorisationRequest {
+            response_type: ResponseType::Code,
+            response_mode: None,
+            client_id: "test_resource_server".to_string(),
+            state: Some("123".to_string()),
+            pkce_request: Some(pkce_secret.to_request()),
+            redirect_uri: Url::parse("http://localhost:8765/oauth2/result").unwrap(),
+            scope: btreeset![OAUTH2_SCOPE_OPENID.to_string()],
+            nonce: Some("abcdef".to_string()),
+            oidc_ext: Default::default(),
+            max_age: None,
+            authorization_details: None,
+            unknown_keys: Default::default(),
+        };
+
+        let consent_request = idms_prox_read
+            .check_oauth2_authorisation(Some(&ident), &auth_req, ct)
+            .expect("OAuth2 authorisation failed");
+
+        // Should be in the consent phase;
+        let AuthoriseResponse::ConsentRequested { consent_token, .. } = consent_request else {
+            unreachable!();
+        };
+
+        // == Manually submit the consent token to the permit for the permit_success
+        drop(idms_prox_read);
         let mut idms_prox_write = idms.proxy_write(ct).await.unwrap();
 
-        let refresh_token = access_token_response_1
-            .refresh_token
-            .as_ref()
-            .expect("no refresh token was issued")
-            .clone();
+        let permit_success = idms_prox_write
+            .check_oauth2_authorise_permit(&ident, &consent_token, ct)
+            .expect("Failed to perform OAuth2 permit");
 
-        let token_req: AccessTokenRequest = GrantTypeReq::RefreshToken {
-            refresh_token,
-            scope: None,
-        }
-        .into();
-        let access_token_response_2 = idms_prox_write
-            .check_oauth2_token_exchange(&client_authz, &token_req, ct)
-            // Should be unable to exchange.
-            .unwrap_err();
+        // Check we are reflecting the CSRF properly.
+        assert_eq!(permit_success.state.as_deref(), Some("123"));
 
-        assert_eq!(access_token_response_2, Oauth2Error::InvalidGrant);
+        // == Submit the token exchange code.
+        let token_req = AccessTokenRequest {
+            grant_type: GrantTypeReq::AuthorizationCode {
+                code: permit_success.code,
+                redirect_uri,
+                code_verifier: Some(pkce_secret.to_verifier()),
+            },
+            client_post_auth: ClientPostAuth {
+                client_id: Some("test_resource_server".to_string()),
+                client_secret: None,
+                ..Default::default()
+            },
+        };
+
+        let token_response = idms_prox_write
+            .check_oauth2_token_exchange(&ClientAuthInfo::none(), &token_req, ct)
+            .expect("Failed to perform OAuth2 token exchange");
+
+        // 🎉 We got a token! In the future we can then check introspection from this point.
+        assert_eq!(token_response.token_type, AccessTokenType::Bearer);
 
         assert!(idms_prox_write.commit().is_ok());
     }
 
-    // refresh with wrong client id/authz
     #[idm_test]
-    async fn test_idm_oauth2_refresh_token_invalid_client_authz(
+    async fn test_idm_oauth2_service_account_token_exchange(
         idms: &IdmServer,
-        idms_delayed: &mut IdmServerDelayed,
+        _idms_delayed: &mut IdmServerDelayed,
     ) {
-        // First, setup to get a token.
         let ct = Duration::from_secs(TEST_CURRENT_TIME);
+        let (secret, _uat, _ident, _) =
+            setup_oauth2_resource_server_basic(idms, ct, true, false, false).await;
 
-        let (access_token_response_1, _client_authz) =
-            setup_refresh_token(idms, idms_delayed, ct).await;
-
-        let bad_client_authz = ClientAuthInfo::encode_basic("test_resource_server", "12345");
+        let mut idms_prox_write = idms.proxy_write(ct).await.unwrap();
 
-        // ============================================
-        // Refresh with invalid client authz
+        let service_account_uuid = Uuid::new_v4();
+        let sa_entry: Entry<EntryInit, EntryNew> = entry_init!(
+            (Attribute::Class, EntryClass::Object.to_value()),
+            (Attribute::Class, EntryClass::Account.to_value()),
+            (Attribute::Class, EntryClass::ServiceAccount.to_value()),
+            (Attribute::Name, Value::new_iname("test_sa_oauth2")),
+            (Attribute::Uuid, Value::Uuid(service_account_uuid)),
+            (Attribute::DisplayName, Value::new_utf8s("test_sa_oauth2")),
+            (Attribute::Description, Value::new_utf8s("test_sa_oauth2"))
+        );
 
-        let mut idms_prox_write = idms.proxy_write(ct).await.unwrap();
+        idms_prox_write
+            .qs_write
+            .internal_create(vec![sa_entry])
+            .expect("Failed to create service account");
 
-        let refresh_token = access_token_response_1
-            .refresh_token
-            .as_ref()
-            .expect("no refresh token was issued")
-            .clone();
+        idms_prox_write
+            .qs_write
+            .internal_modify(
+                &filter!(f_eq(Attribute::Uuid, PartialValue::Uuid(UUID_TESTGROUP))),
+                &ModifyList::new_list(vec![Modify::Present(
+                    Attribute::Member,
+                    Value::Refer(service_account_uuid),
+                )]),
+            )
+            .expect("Failed to add service account to scope group");
 
-        let token_req: AccessTokenRequest = GrantTypeReq::RefreshToken {
-            refresh_token,
-            scope: None,
-        }
-        .into();
-        let access_token_response_2 = idms_prox_write
-            .check_oauth2_token_exchange(&bad_client_authz, &token_req, ct)
-            .unwrap_err();
+        let gte = GenerateApiTokenEvent::new_internal(service_account_uuid, "sa-token", None);
 
-        assert_eq!(access_token_response_2, Oauth2Error::AuthenticationRequired);
+        let api_token = idms_prox_write
+            .service_account_generate_api_token(&gte, ct)
+            .expect("failed to generate api token");
 
         assert!(idms_prox_write.commit().is_ok());
-    }
 
-    // Incorrect scopes re-requested
-    #[idm_test]
-    async fn test_idm_oauth2_refresh_token_inconsistent_scopes(
-        idms: &IdmServer,
-        idms_delayed: &mut IdmServerDelayed,
-    ) {
-        // First, setup to get a token.
-        let ct = Duration::from_secs(TEST_CURRENT_TIME);
+        let client_authz = ClientAuthInfo::encode_basic("test_resource_server", secret.as_str());
 
-        let (access_token_response_1, client_authz) =
-            setup_refresh_token(idms, idms_delayed, ct).await;
+        let scopes: BTreeSet<String> =
+            btreeset![OAUTH2_SCOPE_OPENID.into(), OAUTH2_SCOPE_GROUPS.into()];
 
-        // ============================================
-        // Refresh with different scopes
+        let build_exchange_request =
+            |requested_scopes: BTreeSet<String>, client_secret: Option<String>| {
+                AccessTokenRequest {
+                    grant_type: GrantTypeReq::TokenExchange {
+                        subject_token: api_token.to_string(),
+                        subject_token_type: TOKEN_EXCHANGE_SUBJECT_TOKEN_TYPE_ACCESS.into(),
+                        requested_token_type: None,
+                        audience: Some("test_resource_server".into()),
+                        resource: None,
+                        actor_token: None,
+                        actor_token_type: None,
+                        scope: Some(requested_scopes),
+                    },
+                    client_post_auth: ClientPostAuth {
+                        client_id: Some("test_resource_server".into()),
+                        client_secret,
+                        ..Default::default()
+                    },
+                }
+            };
+
+        let token_req = build_exchange_request(scopes.clone(), None);
+        let forbidden_secret_req = build_exchange_request(scopes.clone(), Some(secret.clone()));
+        let empty_scope_req = build_exchange_request(BTreeSet::new(), None);
 
         let mut idms_prox_write = idms.proxy_write(ct).await.unwrap();
 
-        let refresh_token = access_token_response_1
-            .refresh_token
-            .as_ref()
-            .expect("no refresh token was issued")
-            .clone();
+        assert_eq!(
+            idms_prox_write
+                .check_oauth2_token_exchange(&client_authz, &forbidden_secret_req, ct)
+                .unwrap_err(),
+            Oauth2Error::InvalidRequest
+        );
 
-        let token_req: AccessTokenRequest = GrantTypeReq::RefreshToken {
-            refresh_token,
-            scope: Some(btreeset!["invalid_scope".to_string()]),
-        }
-        .into();
-        let access_token_response_2 = idms_prox_write
-            .check_oauth2_token_exchange(&client_authz, &token_req, ct)
-            .unwrap_err();
+        assert_eq!(
+            idms_prox_write
+                .check_oauth2_token_exchange(&ClientAuthInfo::none(), &empty_scope_req, ct)
+                .unwrap_err(),
+            Oauth2Error::InvalidRequest
+        );
 
-        assert_eq!(access_token_response_2, Oauth2Error::InvalidScope);
+        let token_response = idms_prox_write
+            .check_oauth2_token_exchange(&ClientAuthInfo::none(), &token_req, ct)
+            .expect("Failed to perform OAuth2 token exchange for service account");
+
+        assert_eq!(token_response.token_type, AccessTokenType::Bearer);
+        assert_eq!(
+            token_response.issued_token_type,
+            Some(IssuedTokenType::AccessToken)
+        );
+        assert!(token_response.refresh_token.is_some());
+        assert!(token_response.id_token.is_some());
+        let response_scopes = token_response.scope.clone();
+        assert!(response_scopes.contains(OAUTH2_SCOPE_OPENID));
+        assert!(response_scopes.contains(OAUTH2_SCOPE_GROUPS));
 
         assert!(idms_prox_write.commit().is_ok());
+
+        let mut idms_prox_read = idms.proxy_read().await.unwrap();
+        let intr_request = AccessTokenIntrospectRequest {
+            token: token_response.access_token.clone(),
+            token_type_hint: None,
+            client_post_auth: ClientPostAuth::default(),
+        };
+        let intr_response = idms_prox_read
+            .check_oauth2_token_introspect(&client_authz, &intr_request, ct)
+            .expect("Failed to introspect service account token");
+
+        assert!(intr_response.active);
+        assert_eq!(
+            intr_response.client_id.as_deref(),
+            Some("test_resource_server")
+        );
+        assert_eq!(
+            intr_response.sub.as_deref(),
+            Some(service_account_uuid.to_string().as_str())
+        );
     }
 
-    // Test that reuse of a refresh token is denied + terminates the session.
-    //
-    // https://www.ietf.org/archive/id/draft-ietf-oauth-security-topics-18.html#refresh_token_protection
     #[idm_test]
-    async fn test_idm_oauth2_refresh_token_reuse_invalidates_session(
+    async fn test_idm_oauth2_basic_client_credentials_grant_valid(
         idms: &IdmServer,
-        idms_delayed: &mut IdmServerDelayed,
+        _idms_delayed: &mut IdmServerDelayed,
     ) {
-        // First, setup to get a token.
         let ct = Duration::from_secs(TEST_CURRENT_TIME);
+        let (secret, _uat, _ident, _) =
+            setup_oauth2_resource_server_basic(idms, ct, true, false, false).await;
+        let client_authz = ClientAuthInfo::encode_basic("test_resource_server", secret.as_str());
 
-        let (access_token_response_1, client_authz) =
-            setup_refresh_token(idms, idms_delayed, ct).await;
-
-        // ============================================
-        // Use the refresh token once
-        let ct = Duration::from_secs(TEST_CURRENT_TIME + 1);
+        // scope: Some(btreeset!["invalid_scope".to_string()]),
         let mut idms_prox_write = idms.proxy_write(ct).await.unwrap();
 
-        let refresh_token = access_token_response_1
-            .refresh_token
-            .as_ref()
-            .expect("no refresh token was issued")
-            .clone();
+        let token_req = AccessTokenRequest {
+            grant_type: GrantTypeReq::ClientCredentials { scope: None },
 
-        let token_req: AccessTokenRequest = GrantTypeReq::RefreshToken {
-            refresh_token,
-            scope: None,
-        }
-        .into();
+            client_post_auth: ClientPostAuth {
+                client_id: Some("test_resource_server".to_string()),
+                client_secret: Some(secret),
+                ..Default::default()
+            },
+        };
 
-        let _access_token_response_2 = idms_prox_write
-            .check_oauth2_token_exchange(&client_authz, &token_req, ct)
-            .expect("Unable to exchange for OAuth2 token");
+        let oauth2_token = idms_prox_write
+            .check_oauth2_token_exchange(&ClientAuthInfo::none(), &token_req, ct)
+            .expect("Failed to perform OAuth2 token exchange");
 
         assert!(idms_prox_write.commit().is_ok());
 
-        // Now use it again. - this will cause an error and the session to be terminated.
-        let ct = Duration::from_secs(TEST_CURRENT_TIME + 2);
-        let mut idms_prox_write = idms.proxy_write(ct).await.unwrap();
+        // 🎉 We got a token! In the future we can then check introspection from this point.
+        assert_eq!(oauth2_token.token_type, AccessTokenType::Bearer);
 
-        let refresh_token = access_token_response_1
-            .refresh_token
-            .as_ref()
-            .expect("no refresh token was issued")
-            .clone();
+        // Check Oauth2 Token Introspection
+        let mut idms_prox_read = idms.proxy_read().await.unwrap();
 
-        let token_req: AccessTokenRequest = GrantTypeReq::RefreshToken {
-            refresh_token,
-            scope: None,
-        }
-        .into();
+        let intr_request = AccessTokenIntrospectRequest {
+            token: oauth2_token.access_token.clone(),
+            token_type_hint: None,
+            client_post_auth: ClientPostAuth::default(),
+        };
+        let intr_response = idms_prox_read
+            .check_oauth2_token_introspect(&client_authz, &intr_request, ct)
+            .expect("Failed to inspect token");
 
-        let access_token_response_3 = idms_prox_write
-            .check_oauth2_token_exchange(&client_authz, &token_req, ct)
-            .unwrap_err();
+        eprintln!("👉  {intr_response:?}");
+        assert!(intr_response.active);
+        assert_eq!(intr_response.scope, btreeset!["supplement".to_string()]);
+        assert_eq!(
+            intr_response.client_id.as_deref(),
+            Some("test_resource_server")
+        );
+        assert_eq!(
+            intr_response.username.as_deref(),
+            Some("test_resource_server@example.com")
+        );
+        assert_eq!(intr_response.token_type, Some(AccessTokenType::Bearer));
+        assert_eq!(intr_response.iat, Some(ct.as_secs() as i64));
+        assert_eq!(intr_response.nbf, Some(ct.as_secs() as i64));
 
-        assert_eq!(access_token_response_3, Oauth2Error::InvalidGrant);
+        drop(idms_prox_read);
+
+        // Assert we can revoke.
+        let mut idms_prox_write = idms.proxy_write(ct).await.unwrap();
+        let revoke_request = TokenRevokeRequest {
+            token: oauth2_token.access_token.clone(),
+            token_type_hint: None,
+            client_post_auth: ClientPostAuth::default(),
+        };
+        assert!(idms_prox_write
+            .oauth2_token_revoke(&client_authz, &revoke_request, ct,)
+            .is_ok());
+        assert!(idms_prox_write.commit().is_ok());
+
+        // Now must be invalid.
+        let ct = ct + AUTH_TOKEN_GRACE_WINDOW;
+        let mut idms_prox_read = idms.proxy_read().await.unwrap();
+
+        let intr_request = AccessTokenIntrospectRequest {
+            token: oauth2_token.access_token.clone(),
+            token_type_hint: None,
+            client_post_auth: ClientPostAuth::default(),
+        };
 
-        let entry = idms_prox_write
-            .qs_write
-            .internal_search_uuid(UUID_TESTPERSON_1)
-            .expect("failed");
-        let valid = entry
-            .get_ava_as_oauth2session_map(Attribute::OAuth2Session)
-            .and_then(|sessions| sessions.first_key_value())
-            .map(|(_, session)| !matches!(session.state, SessionState::RevokedAt(_)))
-            // If there is no map, then something is wrong.
-            .unwrap();
-        // The session should be invalid at this point.
-        assert!(!valid);
+        let intr_response = idms_prox_read
+            .check_oauth2_token_introspect(&client_authz, &intr_request, ct)
+            .expect("Failed to inspect token");
+        assert!(!intr_response.active);
 
-        assert!(idms_prox_write.commit().is_ok());
+        drop(idms_prox_read);
     }
 
-    // Test session divergence. This means that we have to:
-    // access + refresh 1
-    // use refresh 1 -> access + refresh 2 // don't commit this txn.
-    // use refresh 2 -> access + refresh 3
-    //    check the session state.
-
     #[idm_test]
-    async fn test_idm_oauth2_refresh_token_divergence(
+    async fn test_idm_oauth2_basic_client_credentials_grant_invalid(
         idms: &IdmServer,
-        idms_delayed: &mut IdmServerDelayed,
+        _idms_delayed: &mut IdmServerDelayed,
     ) {
-        // First, setup to get a token.
         let ct = Duration::from_secs(TEST_CURRENT_TIME);
+        let (secret, _uat, _ident, _) =
+            setup_oauth2_resource_server_basic(idms, ct, true, false, false).await;
 
-        let (access_token_response_1, client_authz) =
-            setup_refresh_token(idms, idms_delayed, ct).await;
-
-        // ============================================
-        // Use the refresh token once
-        let ct = Duration::from_secs(TEST_CURRENT_TIME + 1);
         let mut idms_prox_write = idms.proxy_write(ct).await.unwrap();
 
-        let refresh_token = access_token_response_1
-            .refresh_token
-            .as_ref()
-            .expect("no refresh token was issued")
-            .clone();
+        // Public Client
+        let token_req = AccessTokenRequest {
+            grant_type: GrantTypeReq::ClientCredentials { scope: None },
+            client_post_auth: ClientPostAuth {
+                client_id: Some("test_resource_server".to_string()),
+                client_secret: None,
+                ..Default::default()
+            },
+        };
 
-        let token_req: AccessTokenRequest = GrantTypeReq::RefreshToken {
-            refresh_token,
-            scope: None,
-        }
-        .into();
+        assert_eq!(
+            idms_prox_write
+                .check_oauth2_token_exchange(&ClientAuthInfo::none(), &token_req, ct)
+                .unwrap_err(),
+            Oauth2Error::AuthenticationRequired
+        );
 
-        let access_token_response_2 = idms_prox_write
-            .check_oauth2_token_exchange(&client_authz, &token_req, ct)
-            .expect("Unable to exchange for OAuth2 token");
+        // Incorrect Password
+        let token_req = AccessTokenRequest {
+            grant_type: GrantTypeReq::ClientCredentials { scope: None },
 
-        // DO NOT COMMIT HERE - this is what forces the session issued_at
-        // time to stay at the original time!
-        drop(idms_prox_write);
+            client_post_auth: ClientPostAuth {
+                client_id: Some("test_resource_server".to_string()),
+                client_secret: Some("wrong password".to_string()),
+                ..Default::default()
+            },
+        };
 
-        // ============================================
-        let ct = Duration::from_secs(TEST_CURRENT_TIME + 2);
-        let mut idms_prox_write = idms.proxy_write(ct).await.unwrap();
+        assert_eq!(
+            idms_prox_write
+                .check_oauth2_token_exchange(&ClientAuthInfo::none(), &token_req, ct)
+                .unwrap_err(),
+            Oauth2Error::AuthenticationRequired
+        );
 
-        let refresh_token = access_token_response_2
-            .refresh_token
-            .as_ref()
-            .expect("no refresh token was issued")
-            .clone();
+        // Invalid scope
+        let scope = Some(btreeset!["💅".to_string()]);
+        let token_req = AccessTokenRequest {
+            grant_type: GrantTypeReq::ClientCredentials { scope },
 
-        let token_req: AccessTokenRequest = GrantTypeReq::RefreshToken {
-            refresh_token,
-            scope: None,
-        }
-        .into();
+            client_post_auth: ClientPostAuth {
+                client_id: Some("test_resource_server".to_string()),
+                client_secret: Some(secret.clone()),
+                ..Default::default()
+            },
+        };
 
-        let _access_token_response_3 = idms_prox_write
-            .check_oauth2_token_exchange(&client_authz, &token_req, ct)
-            .expect("Unable to exchange for OAuth2 token");
+        assert_eq!(
+            idms_prox_write
+                .check_oauth2_token_exchange(&ClientAuthInfo::none(), &token_req, ct)
+                .unwrap_err(),
+            Oauth2Error::InvalidScope
+        );
 
-        assert!(idms_prox_write.commit().is_ok());
+        // Scopes we aren't a member-of
+        let scope = Some(btreeset!["invalid_scope".to_string()]);
+        let token_req = AccessTokenRequest {
+            grant_type: GrantTypeReq::ClientCredentials { scope },
 
-        // Success!
+            client_post_auth: ClientPostAuth {
+                client_id: Some("test_resource_server".to_string()),
+                client_secret: Some(secret.clone()),
+                ..Default::default()
+            },
+        };
+
+        assert_eq!(
+            idms_prox_write
+                .check_oauth2_token_exchange(&ClientAuthInfo::none(), &token_req, ct)
+                .unwrap_err(),
+            Oauth2Error::AccessDenied
+        );
+
+        assert!(idms_prox_write.commit().is_ok());
     }
 
     #[idm_test]
-    async fn test_idm_oauth2_refresh_token_scope_constraints(
+    async fn test_idm_oauth2_client_assertion_authentication(
         idms: &IdmServer,
-        idms_delayed: &mut IdmServerDelayed,
+        _idms_delayed: &mut IdmServerDelayed,
     ) {
-        // First, setup to get a token.
+        use compact_jwt::{
+            JwkKeySet, Jws, JwsEs256Signer, JwsHs256Signer, JwsSigner, JwsSignerToVerifier,
+        };
+
         let ct = Duration::from_secs(TEST_CURRENT_TIME);
+        let (secret, _uat, _ident, rs_uuid) =
+            setup_oauth2_resource_server_basic(idms, ct, true, false, false).await;
 
-        let (access_token_response_1, client_authz) =
-            setup_refresh_token(idms, idms_delayed, ct).await;
+        let client_signer = JwsEs256Signer::generate_es256().expect("Unable to create signer");
+        let client_jwks = JwkKeySet {
+            keys: vec![client_signer
+                .get_verifier()
+                .and_then(|verifier| verifier.public_key_as_jwk())
+                .expect("Unable to get client public key")],
+        };
 
-        // https://www.rfc-editor.org/rfc/rfc6749#section-1.5
-        // Refresh tokens are issued to the client by the authorization
-        // server and are used to obtain a new access token when the
-        // current access token becomes invalid or expires, or to obtain
-        // additional access tokens with identical or narrower scope
-        // (access tokens may have a shorter lifetime and fewer
-        // permissions than authorized by the resource owner).
+        // An invalid key set is rejected.
+        let mut idms_prox_write = idms.proxy_write(ct).await.unwrap();
+        assert_eq!(
+            idms_prox_write.qs_write.internal_modify_uuid(
+                rs_uuid,
+                &ModifyList::new_purge_and_set(Attribute::OAuth2RsJwks, Value::new_utf8s("{}")),
+            ),
+            Err(OperationError::PL0002Oauth2JwksInvalid)
+        );
+        drop(idms_prox_write);
 
-        let ct = Duration::from_secs(TEST_CURRENT_TIME + 10);
         let mut idms_prox_write = idms.proxy_write(ct).await.unwrap();
+        idms_prox_write
+            .qs_write
+            .internal_modify_uuid(
+                rs_uuid,
+                &ModifyList::new_purge_and_set(
+                    Attribute::OAuth2RsJwks,
+                    Value::new_utf8s(&serde_json::to_string(&client_jwks).unwrap()),
+                ),
+            )
+            .expect("Failed to set client jwks");
+        assert!(idms_prox_write.commit().is_ok());
 
-        let refresh_token = access_token_response_1
-            .refresh_token
-            .as_ref()
-            .expect("no refresh token was issued")
-            .clone();
+        let token_endpoint = format!("https://idm.example.com{}", uri::OAUTH2_TOKEN_ENDPOINT);
+        let claims = |jti: &str, aud: &str, exp: Duration| {
+            Jws::into_json(&serde_json::json!({
+                "iss": "test_resource_server",
+                "sub": "test_resource_server",
+                "aud": aud,
+                "exp": exp.as_secs(),
+                "iat": ct.as_secs(),
+                "jti": jti,
+            }))
+            .expect("Unable to serialise client assertion")
+        };
+        let token_req = |client_id: Option<&str>, client_assertion: String| AccessTokenRequest {
+            grant_type: GrantTypeReq::ClientCredentials { scope: None },
+            client_post_auth: ClientPostAuth {
+                client_id: client_id.map(str::to_string),
+                client_assertion: Some(client_assertion),
+                client_assertion_type: Some(OAUTH2_CLIENT_ASSERTION_TYPE_JWT_BEARER.to_string()),
+                ..Default::default()
+            },
+        };
 
-        // Get the initial scopes.
-        let jws_verifier = JwsDangerReleaseWithoutVerify::default();
+        let mut idms_prox_write = idms.proxy_write(ct).await.unwrap();
 
-        let access_token_unverified = JwsCompact::from_str(&access_token_response_1.access_token)
-            .expect("Invalid Access Token");
+        // private_key_jwt, signed with the registered key.
+        let private_key_jwt = client_signer
+            .sign(&claims(
+                "one",
+                &token_endpoint,
+                ct + Duration::from_secs(60),
+            ))
+            .expect("Unable to sign client assertion")
+            .to_string();
 
-        let reflected_token = jws_verifier
-            .verify(&access_token_unverified)
-            .unwrap()
-            .from_json::<OAuth2RFC9068Token<OAuth2RFC9068TokenExtensions>>()
-            .expect("Failed to access internals of the refresh token");
+        let oauth2_token = idms_prox_write
+            .check_oauth2_token_exchange(
+                &ClientAuthInfo::none(),
+                &token_req(Some("test_resource_server"), private_key_jwt.clone()),
+                ct,
+            )
+            .expect("Failed to perform OAuth2 token exchange");
+        assert_eq!(oauth2_token.token_type, AccessTokenType::Bearer);
 
-        trace!(?reflected_token);
-        let initial_scopes = reflected_token.extensions.scope;
-        trace!(?initial_scopes);
+        // The same assertion can not be used twice.
+        assert_eq!(
+            idms_prox_write
+                .check_oauth2_token_exchange(
+                    &ClientAuthInfo::none(),
+                    &token_req(Some("test_resource_server"), private_key_jwt),
+                    ct,
+                )
+                .unwrap_err(),
+            Oauth2Error::AuthenticationRequired
+        );
 
-        // Should be the same scopes as initial.
-        let token_req: AccessTokenRequest = GrantTypeReq::RefreshToken {
-            refresh_token,
-            scope: None,
+        // client_secret_jwt, where the client is named only by the assertion.
+        let secret_signer =
+            JwsHs256Signer::try_from(secret.as_bytes()).expect("Unable to create signer");
+        let client_secret_jwt = secret_signer
+            .sign(&claims(
+                "two",
+                &token_endpoint,
+                ct + Duration::from_secs(60),
+            ))
+            .expect("Unable to sign client assertion")
+            .to_string();
+
+        assert!(idms_prox_write
+            .check_oauth2_token_exchange(
+                &ClientAuthInfo::none(),
+                &token_req(None, client_secret_jwt),
+                ct,
+            )
+            .is_ok());
+
+        // A key that isn't registered to the client.
+        let other_signer = JwsEs256Signer::generate_es256().expect("Unable to create signer");
+        let other_jwt = other_signer
+            .sign(&claims(
+                "three",
+                &token_endpoint,
+                ct + Duration::from_secs(60),
+            ))
+            .expect("Unable to sign client assertion")
+            .to_string();
+
+        // An assertion for some other audience.
+        let wrong_aud_jwt = client_signer
+            .sign(&claims(
+                "four",
+                "https://other.example.com/token",
+                ct + Duration::from_secs(60),
+            ))
+            .expect("Unable to sign client assertion")
+            .to_string();
+
+        // An assertion that has expired, or is valid for too long.
+        let expired_jwt = client_signer
+            .sign(&claims(
+                "five",
+                &token_endpoint,
+                ct - Duration::from_secs(1),
+            ))
+            .expect("Unable to sign client assertion")
+            .to_string();
+
+        let long_lived_jwt = client_signer
+            .sign(&claims(
+                "six",
+                &token_endpoint,
+                ct + Duration::from_secs(OAUTH2_CLIENT_ASSERTION_MAX_EXPIRY + 60),
+            ))
+            .expect("Unable to sign client assertion")
+            .to_string();
+
+        for client_assertion in [other_jwt, wrong_aud_jwt, expired_jwt, long_lived_jwt] {
+            assert_eq!(
+                idms_prox_write
+                    .check_oauth2_token_exchange(
+                        &ClientAuthInfo::none(),
+                        &token_req(Some("test_resource_server"), client_assertion),
+                        ct,
+                    )
+                    .unwrap_err(),
+                Oauth2Error::AuthenticationRequired
+            );
         }
-        .into();
 
-        let access_token_response_2 = idms_prox_write
-            .check_oauth2_token_exchange(&client_authz, &token_req, ct)
-            .expect("Unable to exchange for OAuth2 token");
+        // Unknown assertion types are rejected.
+        let mut req = token_req(Some("test_resource_server"), "invalid".to_string());
+        req.client_post_auth.client_assertion_type = Some("urn:example:unknown".to_string());
+        assert_eq!(
+            idms_prox_write
+                .check_oauth2_token_exchange(&ClientAuthInfo::none(), &req, ct)
+                .unwrap_err(),
+            Oauth2Error::InvalidRequest
+        );
+
+        assert!(idms_prox_write.commit().is_ok());
+
+        // An assertion presented to a request that fails is still consumed, even though
+        // the transaction that saw it is never committed.
+        let failed_jwt = client_signer
+            .sign(&claims(
+                "seven",
+                &token_endpoint,
+                ct + Duration::from_secs(60),
+            ))
+            .expect("Unable to sign client assertion")
+            .to_string();
+
+        let mut idms_prox_write = idms.proxy_write(ct).await.unwrap();
+        let mut req = token_req(Some("test_resource_server"), failed_jwt.clone());
+        req.grant_type = GrantTypeReq::ClientCredentials {
+            scope: Some(btreeset!["invalid_scope".to_string()]),
+        };
+        assert_eq!(
+            idms_prox_write
+                .check_oauth2_token_exchange(&ClientAuthInfo::none(), &req, ct)
+                .unwrap_err(),
+            Oauth2Error::AccessDenied
+        );
+        drop(idms_prox_write);
+
+        let mut idms_prox_write = idms.proxy_write(ct).await.unwrap();
+        assert_eq!(
+            idms_prox_write
+                .check_oauth2_token_exchange(
+                    &ClientAuthInfo::none(),
+                    &token_req(Some("test_resource_server"), failed_jwt),
+                    ct,
+                )
+                .unwrap_err(),
+            Oauth2Error::AuthenticationRequired
+        );
+        drop(idms_prox_write);
+    }
+
+    #[idm_test]
+    async fn test_idm_oauth2_pushed_authorisation_request(
+        idms: &IdmServer,
+        _idms_delayed: &mut IdmServerDelayed,
+    ) {
+        use compact_jwt::{Jws, JwsHs256Signer, JwsSigner};
 
-        let access_token_unverified = JwsCompact::from_str(&access_token_response_2.access_token)
-            .expect("Invalid Access Token");
+        let ct = Duration::from_secs(TEST_CURRENT_TIME);
+        let (secret, _uat, ident, rs_uuid) =
+            setup_oauth2_resource_server_basic(idms, ct, true, false, false).await;
 
-        let reflected_token = jws_verifier
-            .verify(&access_token_unverified)
-            .unwrap()
-            .from_json::<OAuth2RFC9068Token<OAuth2RFC9068TokenExtensions>>()
-            .expect("Failed to access internals of the refresh token");
+        let pkce_secret = PkceS256Secret::default();
+        let auth_req = AuthorisationRequest {
+            response_type: ResponseType::Code,
+            response_mode: None,
+            client_id: "test_resource_server".to_string(),
+            state: Some("123".to_string()),
+            pkce_request: Some(pkce_secret.to_request()),
+            redirect_uri: Url::parse("https://demo.example.com/oauth2/result").unwrap(),
+            scope: btreeset![OAUTH2_SCOPE_OPENID.to_string()],
+            nonce: None,
+            oidc_ext: Default::default(),
+            max_age: None,
+            authorization_details: None,
+            unknown_keys: Default::default(),
+        };
+        let auth_req_params = AuthorisationRequestParams::from(auth_req.clone());
 
-        assert_eq!(initial_scopes, reflected_token.extensions.scope);
+        let mut idms_prox_write = idms.proxy_write(ct).await.unwrap();
 
-        let refresh_token = access_token_response_2
-            .refresh_token
-            .as_ref()
-            .expect("no refresh token was issued")
-            .clone();
+        // The client must authenticate to push a request.
+        assert_eq!(
+            idms_prox_write
+                .oauth2_pushed_authorisation(
+                    &ClientAuthInfo::none(),
+                    &ClientPostAuth::from(("test_resource_server", Some("12345"))),
+                    &auth_req_params,
+                    ct,
+                )
+                .unwrap_err(),
+            Oauth2Error::AuthenticationRequired
+        );
 
-        // Now the scopes can be constrained.
-        let token_req: AccessTokenRequest = GrantTypeReq::RefreshToken {
-            refresh_token,
-            scope: Some(["openid".to_string()].into()),
-        }
-        .into();
+        let client_post_auth =
+            ClientPostAuth::from(("test_resource_server", Some(secret.as_str())));
 
-        let access_token_response_3 = idms_prox_write
-            .check_oauth2_token_exchange(&client_authz, &token_req, ct)
-            .expect("Unable to exchange for OAuth2 token");
+        let par_response = idms_prox_write
+            .oauth2_pushed_authorisation(
+                &ClientAuthInfo::none(),
+                &client_post_auth,
+                &auth_req_params,
+                ct,
+            )
+            .expect("Failed to push authorisation request");
+        assert!(par_response
+            .request_uri
+            .starts_with(OAUTH2_PUSHED_AUTHORISATION_REQUEST_URI_PREFIX));
+        assert_eq!(
+            par_response.expires_in,
+            OAUTH2_PUSHED_AUTHORISATION_EXPIRY_SECONDS
+        );
 
-        let access_token_unverified = JwsCompact::from_str(&access_token_response_3.access_token)
-            .expect("Invalid Access Token");
+        // A request_uri can not itself be pushed.
+        assert_eq!(
+            idms_prox_write
+                .oauth2_pushed_authorisation(
+                    &ClientAuthInfo::none(),
+                    &client_post_auth,
+                    &AuthorisationRequestParams::ByReference(AuthorisationRequestRef {
+                        client_id: "test_resource_server".to_string(),
+                        request_uri: Some(par_response.request_uri.clone()),
+                        request: None,
+                    }),
+                    ct,
+                )
+                .unwrap_err(),
+            Oauth2Error::InvalidRequest
+        );
 
-        let reflected_token = jws_verifier
-            .verify(&access_token_unverified)
-            .unwrap()
-            .from_json::<OAuth2RFC9068Token<OAuth2RFC9068TokenExtensions>>()
-            .expect("Failed to access internals of the refresh token");
+        assert!(idms_prox_write.commit().is_ok());
 
-        assert_ne!(initial_scopes, reflected_token.extensions.scope);
+        let by_request_uri = |request_uri: &str| {
+            AuthorisationRequestParams::ByReference(AuthorisationRequestRef {
+                client_id: "test_resource_server".to_string(),
+                request_uri: Some(request_uri.to_string()),
+                request: None,
+            })
+        };
 
-        // Keep the constrained scopes.
-        let constrained_scopes = reflected_token.extensions.scope;
+        let idms_prox_read = idms.proxy_read().await.unwrap();
 
-        let refresh_token = access_token_response_3
-            .refresh_token
-            .as_ref()
-            .expect("no refresh token was issued")
-            .clone();
+        assert!(matches!(
+            idms_prox_read.check_oauth2_authorisation_params(
+                Some(&ident),
+                &by_request_uri(&par_response.request_uri),
+                ct
+            ),
+            Ok(AuthoriseResponse::ConsentRequested { .. })
+        ));
 
-        // No scope request still issues the constrained values.
-        let token_req: AccessTokenRequest = GrantTypeReq::RefreshToken {
-            refresh_token,
-            scope: None,
-        }
-        .into();
+        // Unknown and expired request_uris are rejected.
+        assert_eq!(
+            idms_prox_read
+                .check_oauth2_authorisation_params(
+                    Some(&ident),
+                    &by_request_uri(&format!(
+                        "{OAUTH2_PUSHED_AUTHORISATION_REQUEST_URI_PREFIX}{}",
+                        Uuid::new_v4()
+                    )),
+                    ct
+                )
+                .unwrap_err(),
+            Oauth2Error::InvalidRequestUri
+        );
 
-        let access_token_response_4 = idms_prox_write
-            .check_oauth2_token_exchange(&client_authz, &token_req, ct)
-            .expect("Unable to exchange for OAuth2 token");
+        assert_eq!(
+            idms_prox_read
+                .check_oauth2_authorisation_params(
+                    Some(&ident),
+                    &by_request_uri(&par_response.request_uri),
+                    ct + Duration::from_secs(OAUTH2_PUSHED_AUTHORISATION_EXPIRY_SECONDS)
+                )
+                .unwrap_err(),
+            Oauth2Error::InvalidRequestUri
+        );
 
-        let access_token_unverified = JwsCompact::from_str(&access_token_response_4.access_token)
-            .expect("Invalid Access Token");
+        // A signed request object, keyed by the client secret.
+        let mut request_claims = serde_json::to_value(&auth_req).unwrap();
+        request_claims["iss"] = serde_json::json!("test_resource_server");
+        request_claims["aud"] =
+            serde_json::json!("https://idm.example.com/oauth2/openid/test_resource_server");
+        request_claims["exp"] = serde_json::json!(ct.as_secs() + 60);
+        request_claims["iat"] = serde_json::json!(ct.as_secs());
+        request_claims["jti"] = serde_json::json!("request-object-1");
+        let request_jws = Jws::into_json(&request_claims).unwrap();
 
-        let reflected_token = jws_verifier
-            .verify(&access_token_unverified)
-            .unwrap()
-            .from_json::<OAuth2RFC9068Token<OAuth2RFC9068TokenExtensions>>()
-            .expect("Failed to access internals of the refresh token");
+        let by_request = |request: String| {
+            AuthorisationRequestParams::ByReference(AuthorisationRequestRef {
+                client_id: "test_resource_server".to_string(),
+                request_uri: None,
+                request: Some(request),
+            })
+        };
 
-        assert_ne!(initial_scopes, reflected_token.extensions.scope);
-        assert_eq!(constrained_scopes, reflected_token.extensions.scope);
+        let sign_request = |request_jws: &Jws| {
+            JwsHs256Signer::try_from(secret.as_bytes())
+                .expect("Unable to create signer")
+                .sign(request_jws)
+                .expect("Unable to sign request object")
+                .to_string()
+        };
 
-        let refresh_token = access_token_response_4
-            .refresh_token
-            .as_ref()
-            .expect("no refresh token was issued")
-            .clone();
+        let request = sign_request(&request_jws);
 
-        // We can't now extend back to the initial scopes.
-        let token_req: AccessTokenRequest = GrantTypeReq::RefreshToken {
-            refresh_token,
-            scope: Some(initial_scopes),
-        }
-        .into();
+        // Before the user has authenticated the request object is not consumed, as it is
+        // presented again once they have.
+        assert!(matches!(
+            idms_prox_read.check_oauth2_authorisation_params(
+                None,
+                &by_request(request.clone()),
+                ct
+            ),
+            Ok(AuthoriseResponse::AuthenticationRequired { .. })
+        ));
 
-        let access_token_response_5_err = idms_prox_write
-            .check_oauth2_token_exchange(&client_authz, &token_req, ct)
-            .unwrap_err();
+        assert!(matches!(
+            idms_prox_read.check_oauth2_authorisation_params(
+                Some(&ident),
+                &by_request(request.clone()),
+                ct
+            ),
+            Ok(AuthoriseResponse::ConsentRequested { .. })
+        ));
 
-        assert_eq!(access_token_response_5_err, Oauth2Error::InvalidScope);
+        // A request object can only be used once.
+        assert_eq!(
+            idms_prox_read
+                .check_oauth2_authorisation_params(Some(&ident), &by_request(request), ct)
+                .unwrap_err(),
+            Oauth2Error::InvalidRequestObject
+        );
 
-        assert!(idms_prox_write.commit().is_ok());
-    }
+        // Request objects must expire soon, be for this issuer and have a jti.
+        for (claim, value) in [
+            ("exp", serde_json::Value::Null),
+            (
+                "exp",
+                serde_json::json!(ct.as_secs() + OAUTH2_REQUEST_OBJECT_MAX_EXPIRY + 60),
+            ),
+            ("iat", serde_json::json!(ct.as_secs() - 3600)),
+            ("aud", serde_json::Value::Null),
+            ("aud", serde_json::json!("https://idm.example.com/")),
+            ("jti", serde_json::Value::Null),
+        ] {
+            let mut invalid_claims = request_claims.clone();
+            invalid_claims["jti"] = serde_json::json!(format!("request-object-{claim}"));
+            invalid_claims[claim] = value;
+            let invalid_request = sign_request(&Jws::into_json(&invalid_claims).unwrap());
+
+            assert_eq!(
+                idms_prox_read
+                    .check_oauth2_authorisation_params(
+                        Some(&ident),
+                        &by_request(invalid_request),
+                        ct
+                    )
+                    .unwrap_err(),
+                Oauth2Error::InvalidRequestObject
+            );
+        }
 
-    #[test]
-    // I know this looks kinda dumb but at some point someone pointed out that our scope syntax wasn't compliant with rfc6749
-    //(https://datatracker.ietf.org/doc/html/rfc6749#section-3.3), so I'm just making sure that we don't break it again.
-    fn compliant_serialization_test() {
-        let token_req: Result<AccessTokenRequest, serde_json::Error> = serde_json::from_str(
-            r#"
-            {
-                "grant_type": "refresh_token",
-                "refresh_token": "some_dumb_refresh_token",
-                "scope": "invalid_scope vasd asd"
-            }
-        "#,
+        let bad_request =
+            JwsHs256Signer::try_from("this is not the secret of the client".as_bytes())
+                .expect("Unable to create signer")
+                .sign(&request_jws)
+                .expect("Unable to sign request object")
+                .to_string();
+
+        assert_eq!(
+            idms_prox_read
+                .check_oauth2_authorisation_params(Some(&ident), &by_request(bad_request), ct)
+                .unwrap_err(),
+            Oauth2Error::InvalidRequestObject
         );
-        assert!(token_req.is_ok());
-    }
 
-    #[idm_test]
-    async fn test_idm_oauth2_custom_claims(idms: &IdmServer, _idms_delayed: &mut IdmServerDelayed) {
-        let ct = Duration::from_secs(TEST_CURRENT_TIME);
-        let (secret, _uat, ident, oauth2_rs_uuid) =
-            setup_oauth2_resource_server_basic(idms, ct, true, false, false).await;
+        drop(idms_prox_read);
 
-        // Setup custom claim maps here.
+        // Once pushed requests are required, they are the only accepted form.
         let mut idms_prox_write = idms.proxy_write(ct).await.unwrap();
+        idms_prox_write
+            .qs_write
+            .internal_modify_uuid(
+                rs_uuid,
+                &ModifyList::new_purge_and_set(Attribute::OAuth2RequirePar, Value::new_bool(true)),
+            )
+            .expect("Failed to require pushed authorisation requests");
+        assert!(idms_prox_write.commit().is_ok());
 
-        let modlist = ModifyList::new_list(vec![
-            // Member of a claim map.
-            Modify::Present(
-                Attribute::OAuth2RsClaimMap,
-                Value::OauthClaimMap(
-                    "custom_a".to_string(),
-                    OauthClaimMapJoin::CommaSeparatedValue,
-                ),
-            ),
-            Modify::Present(
-                Attribute::OAuth2RsClaimMap,
-                Value::OauthClaimValue(
-                    "custom_a".to_string(),
-                    UUID_TESTGROUP,
-                    btreeset!["value_a".to_string()],
-                ),
-            ),
-            // If you are a member of two groups, the claim maps merge.
-            Modify::Present(
-                Attribute::OAuth2RsClaimMap,
-                Value::OauthClaimValue(
-                    "custom_a".to_string(),
-                    UUID_IDM_ALL_ACCOUNTS,
-                    btreeset!["value_b".to_string()],
-                ),
-            ),
-            // Map with a different separator
-            Modify::Present(
-                Attribute::OAuth2RsClaimMap,
-                Value::OauthClaimMap(
-                    "custom_b".to_string(),
-                    OauthClaimMapJoin::SpaceSeparatedValue,
-                ),
-            ),
-            Modify::Present(
-                Attribute::OAuth2RsClaimMap,
-                Value::OauthClaimValue(
-                    "custom_b".to_string(),
-                    UUID_TESTGROUP,
-                    btreeset!["value_a".to_string()],
-                ),
-            ),
-            Modify::Present(
-                Attribute::OAuth2RsClaimMap,
-                Value::OauthClaimValue(
-                    "custom_b".to_string(),
-                    UUID_IDM_ALL_ACCOUNTS,
-                    btreeset!["value_b".to_string()],
-                ),
-            ),
-            // Not a member of the claim map.
-            Modify::Present(
-                Attribute::OAuth2RsClaimMap,
-                Value::OauthClaimValue(
-                    "custom_b".to_string(),
-                    UUID_IDM_ADMINS,
-                    btreeset!["value_c".to_string()],
-                ),
-            ),
-            // Extended claim name syntax, allows characters beyond scope names.
-            Modify::Present(
-                Attribute::OAuth2RsClaimMap,
-                Value::OauthClaimMap(
-                    "custom:claim-name".to_string(),
-                    OauthClaimMapJoin::CommaSeparatedValue,
-                ),
-            ),
-            Modify::Present(
-                Attribute::OAuth2RsClaimMap,
-                Value::OauthClaimValue(
-                    "custom:claim-name".to_string(),
-                    UUID_TESTGROUP,
-                    btreeset!["value:a-a".to_string()],
-                ),
+        let idms_prox_read = idms.proxy_read().await.unwrap();
+
+        assert!(
+            idms_prox_read
+                .oauth2_openid_discovery("test_resource_server")
+                .expect("Failed to get discovery")
+                .require_pushed_authorization_requests
+        );
+
+        assert_eq!(
+            idms_prox_read
+                .check_oauth2_authorisation_params(Some(&ident), &auth_req_params, ct)
+                .unwrap_err(),
+            Oauth2Error::InvalidRequest
+        );
+
+        assert!(matches!(
+            idms_prox_read.check_oauth2_authorisation_params(
+                Some(&ident),
+                &by_request_uri(&par_response.request_uri),
+                ct
             ),
-        ]);
+            Ok(AuthoriseResponse::ConsentRequested { .. })
+        ));
+    }
 
-        assert!(idms_prox_write
-            .qs_write
-            .internal_modify(
-                &filter!(f_eq(Attribute::Uuid, PartialValue::Uuid(oauth2_rs_uuid))),
-                &modlist,
-            )
-            .is_ok());
+    #[test]
+    fn test_oauth2_jwk_thumbprint() {
+        // https://datatracker.ietf.org/doc/html/rfc7638#section-3.1
+        let jwk = serde_json::json!({
+            "kty": "RSA",
+            "n": "0vx7agoebGcQSuuPiLJXZptN9nndrQmbXEps2aiAFbWhM78LhWx4cbbfAAtVT86zwu1RK7aPFFxuhDR1L6tSoc_BJECPebWKRXjBZCiFV4n3oknjhMstn64tZ_2W-5JsGY4Hc5n9yBXArwl93lqt7_RN5w6Cf0h4QyQ5v-65YGjQR0_FDW2QvzqY368QQMicAtaSqzs8KJZgnYb9c7d0zgdAZHzu6qMQvRL5hajrn1n91CbOpbISD08qNLyrdkt-bFTWhAI4vMQFh6WeZu0fM4lFd2NcRwr3XPksINHaQ-G_xBniIqbw0Ls1jF44-csFCur-kEgU8awapJzKnqDKgw",
+            "e": "AQAB",
+            "alg": "RS256",
+            "kid": "2011-04-29"
+        });
 
-        assert!(idms_prox_write.commit().is_ok());
+        assert_eq!(
+            super::jwk_thumbprint(&jwk).as_deref(),
+            Some("NzbLsXh8uDCcd-6MNwXF4W_7noWXFZAfHkxZsRGC9Xs")
+        );
 
-        // Claim maps setup, lets go.
-        let client_authz = ClientAuthInfo::encode_basic("test_resource_server", secret.as_str());
+        assert!(super::jwk_thumbprint(&serde_json::json!({ "kty": "oct", "k": "AAAA" })).is_none());
+    }
+
+    #[idm_test]
+    async fn test_idm_oauth2_dpop_bound_tokens(
+        idms: &IdmServer,
+        _idms_delayed: &mut IdmServerDelayed,
+    ) {
+        use compact_jwt::{jws::JwsBuilder, JwsEs256Signer, JwsSigner, JwsSignerToVerifier};
+        use crypto_glue::{s256::Sha256, traits::Digest};
+
+        let ct = Duration::from_secs(TEST_CURRENT_TIME);
+        let (secret, _uat, ident, rs_uuid) =
+            setup_oauth2_resource_server_basic(idms, ct, true, false, false).await;
+
+        let dpop_signer = JwsEs256Signer::generate_es256()
+            .expect("Unable to create signer")
+            .set_sign_option_embed_jwk(true);
+        let other_signer = JwsEs256Signer::generate_es256()
+            .expect("Unable to create signer")
+            .set_sign_option_embed_jwk(true);
+
+        let dpop_jwk = dpop_signer
+            .get_verifier()
+            .and_then(|verifier| verifier.public_key_as_jwk())
+            .map(|jwk| serde_json::to_value(jwk).unwrap())
+            .expect("Unable to get DPoP public key");
+        let dpop_jkt = super::jwk_thumbprint(&dpop_jwk).expect("Unable to get DPoP key thumbprint");
+
+        let token_endpoint = format!("https://idm.example.com{}", uri::OAUTH2_TOKEN_ENDPOINT);
+        let userinfo_endpoint =
+            "https://idm.example.com/oauth2/openid/test_resource_server/userinfo";
+
+        let dpop_proof = |signer: &JwsEs256Signer,
+                          htm: &str,
+                          htu: &str,
+                          iat: Duration,
+                          access_token: Option<&str>| {
+            let ath = access_token.map(|access_token| {
+                let mut hasher = Sha256::new();
+                hasher.update(access_token.as_bytes());
+                general_purpose::URL_SAFE_NO_PAD.encode(hasher.finalize())
+            });
 
+            let claims = serde_json::json!({
+                "jti": Uuid::new_v4(),
+                "htm": htm,
+                "htu": htu,
+                "iat": iat.as_secs(),
+                "ath": ath,
+            });
+
+            let jws = JwsBuilder::into_json(&claims)
+                .map(|builder| builder.set_typ(Some(OAUTH2_DPOP_PROOF_TYP)).build())
+                .expect("Unable to serialise DPoP proof");
+
+            let proof = signer
+                .sign(&jws)
+                .expect("Unable to sign DPoP proof")
+                .to_string();
+
+            DpopProof {
+                proof,
+                htm: htm.to_string(),
+            }
+        };
+
+        let client_authz_with = |dpop_proof: Option<DpopProof>| {
+            let mut client_authz =
+                ClientAuthInfo::encode_basic("test_resource_server", secret.as_str());
+            if let Some(dpop_proof) = dpop_proof {
+                client_authz.set_dpop_proof(dpop_proof);
+            }
+            client_authz
+        };
+
+        // == Exchange an authorisation code with a DPoP proof.
         let idms_prox_read = idms.proxy_read().await.unwrap();
 
         let pkce_secret = PkceS256Secret::default();
-
         let consent_request = good_authorisation_request!(
             idms_prox_read,
             &ident,
@@ -7371,7 +12385,6 @@ mod tests {
             unreachable!();
         };
 
-        // == Manually submit the consent token to the permit for the permit_success
         drop(idms_prox_read);
         let mut idms_prox_write = idms.proxy_write(ct).await.unwrap();
 
@@ -7379,7 +12392,6 @@ mod tests {
             .check_oauth2_authorise_permit(&ident, &consent_token, ct)
             .expect("Failed to perform OAuth2 permit");
 
-        // == Submit the token exchange code.
         let token_req: AccessTokenRequest = GrantTypeReq::AuthorizationCode {
             code: permit_success.code,
             redirect_uri: Url::parse("https://demo.example.com/oauth2/result").unwrap(),
@@ -7387,540 +12399,1010 @@ mod tests {
         }
         .into();
 
-        let token_response = idms_prox_write
-            .check_oauth2_token_exchange(&client_authz, &token_req, ct)
-            .expect("Failed to perform OAuth2 token exchange");
-
-        // 🎉 We got a token!
-        assert_eq!(token_response.token_type, AccessTokenType::Bearer);
+        // Proofs for another request, or that are stale, are rejected.
+        for invalid_proof in [
+            DpopProof {
+                htm: "POST".to_string(),
+                ..dpop_proof(&dpop_signer, "GET", &token_endpoint, ct, None)
+            },
+            dpop_proof(&dpop_signer, "POST", userinfo_endpoint, ct, None),
+            dpop_proof(
+                &dpop_signer,
+                "POST",
+                &token_endpoint,
+                ct - Duration::from_secs(OAUTH2_DPOP_PROOF_LEEWAY_SECONDS + 1),
+                None,
+            ),
+        ] {
+            assert_eq!(
+                idms_prox_write
+                    .check_oauth2_token_exchange(
+                        &client_authz_with(Some(invalid_proof)),
+                        &token_req,
+                        ct
+                    )
+                    .unwrap_err(),
+                Oauth2Error::InvalidDpopProof
+            );
+        }
 
-        let id_token = token_response.id_token.expect("No id_token in response!");
-        let access_token =
-            JwsCompact::from_str(&token_response.access_token).expect("Invalid Access Token");
+        let access_token_response = idms_prox_write
+            .check_oauth2_token_exchange(
+                &client_authz_with(Some(dpop_proof(
+                    &dpop_signer,
+                    "POST",
+                    &token_endpoint,
+                    ct,
+                    None,
+                ))),
+                &token_req,
+                ct,
+            )
+            .expect("Unable to exchange for OAuth2 token");
 
-        // Get the read txn for inspecting the tokens
         assert!(idms_prox_write.commit().is_ok());
 
-        let mut idms_prox_read = idms.proxy_read().await.unwrap();
+        assert_eq!(access_token_response.token_type, AccessTokenType::DPoP);
 
-        let mut jwkset = idms_prox_read
-            .oauth2_openid_publickey("test_resource_server")
-            .expect("Failed to get public key");
+        let access_token = JwsCompact::from_str(&access_token_response.access_token)
+            .expect("Invalid Access Token");
 
-        let public_jwk = jwkset.keys.pop().expect("no such jwk");
+        let reflected_token = JwsDangerReleaseWithoutVerify::default()
+            .verify(&access_token)
+            .unwrap()
+            .from_json::<OAuth2RFC9068Token<OAuth2RFC9068TokenExtensions>>()
+            .expect("Failed to access internals of the access token");
 
-        let jws_validator =
-            JwsEs256Verifier::try_from(&public_jwk).expect("failed to build validator");
+        assert_eq!(
+            reflected_token.extensions.cnf,
+            Some(ConfirmationClaim {
+                jkt: Some(dpop_jkt.clone()),
+                x5t_s256: None,
+            })
+        );
 
-        let oidc_unverified =
-            OidcUnverified::from_str(&id_token).expect("Failed to parse id_token");
+        // == Introspection reports the binding.
+        let mut idms_prox_read = idms.proxy_read().await.unwrap();
 
-        let iat = ct.as_secs() as i64;
+        let intr_request = AccessTokenIntrospectRequest {
+            token: access_token_response.access_token.clone(),
+            token_type_hint: None,
+            client_post_auth: ClientPostAuth::default(),
+        };
+        let intr_response = idms_prox_read
+            .check_oauth2_token_introspect(&client_authz_with(None), &intr_request, ct)
+            .expect("Failed to inspect token");
 
-        let oidc = jws_validator
-            .verify(&oidc_unverified)
-            .unwrap()
-            .verify_exp(iat)
-            .expect("Failed to verify oidc");
+        assert!(intr_response.active);
+        assert_eq!(intr_response.token_type, Some(AccessTokenType::DPoP));
+        assert_eq!(
+            intr_response.cnf,
+            Some(ConfirmationClaim {
+                jkt: Some(dpop_jkt.clone()),
+                x5t_s256: None,
+            })
+        );
 
-        // Are the id_token values what we expect?
-        assert!(
-            oidc.iss
-                == Url::parse("https://idm.example.com/oauth2/openid/test_resource_server")
-                    .unwrap()
+        // == Userinfo requires a proof from the bound key, made for this access token.
+        for (invalid_proof, err) in [
+            (None, Oauth2Error::InvalidToken),
+            (
+                Some(dpop_proof(
+                    &other_signer,
+                    "GET",
+                    userinfo_endpoint,
+                    ct,
+                    Some(&access_token_response.access_token),
+                )),
+                Oauth2Error::InvalidToken,
+            ),
+            (
+                Some(dpop_proof(&dpop_signer, "GET", userinfo_endpoint, ct, None)),
+                Oauth2Error::InvalidDpopProof,
+            ),
+        ] {
+            assert_eq!(
+                idms_prox_read
+                    .oauth2_openid_userinfo(
+                        "test_resource_server",
+                        &access_token,
+                        invalid_proof.as_ref(),
+                        None,
+                        ct
+                    )
+                    .unwrap_err(),
+                err
+            );
+        }
+
+        let userinfo_proof = dpop_proof(
+            &dpop_signer,
+            "GET",
+            userinfo_endpoint,
+            ct,
+            Some(&access_token_response.access_token),
+        );
+        assert!(idms_prox_read
+            .oauth2_openid_userinfo(
+                "test_resource_server",
+                &access_token,
+                Some(&userinfo_proof),
+                None,
+                ct
+            )
+            .is_ok());
+
+        // The proof can't be replayed, even though userinfo is only a read.
+        assert_eq!(
+            idms_prox_read
+                .oauth2_openid_userinfo(
+                    "test_resource_server",
+                    &access_token,
+                    Some(&userinfo_proof),
+                    None,
+                    ct
+                )
+                .unwrap_err(),
+            Oauth2Error::InvalidDpopProof
         );
-        assert_eq!(oidc.sub, OidcSubject::U(UUID_TESTPERSON_1));
-        assert_eq!(oidc.aud, "test_resource_server");
-        assert_eq!(oidc.iat, iat);
-        assert_eq!(oidc.nbf, Some(iat));
-        // Previously this was the auth session but it's now inline with the access token expiry.
-        assert_eq!(oidc.exp, iat + (OAUTH2_ACCESS_TOKEN_EXPIRY as i64));
-        assert!(oidc.auth_time.is_none());
-        // Is nonce correctly passed through?
-        assert_eq!(oidc.nonce, Some("abcdef".to_string()));
-        assert!(oidc.at_hash.is_none());
-        assert!(oidc.acr.is_none());
-        assert!(oidc.amr.is_none());
-        assert_eq!(oidc.azp, Some("test_resource_server".to_string()));
-        assert!(oidc.jti.is_some());
-        if let Some(jti) = &oidc.jti {
-            assert!(Uuid::from_str(jti).is_ok());
+
+        drop(idms_prox_read);
+
+        // == The refresh token may only be used with the bound key.
+        let ct = Duration::from_secs(TEST_CURRENT_TIME + 10);
+        let mut idms_prox_write = idms.proxy_write(ct).await.unwrap();
+
+        let token_req: AccessTokenRequest = GrantTypeReq::RefreshToken {
+            refresh_token: access_token_response
+                .refresh_token
+                .clone()
+                .expect("no refresh token was issued"),
+            scope: None,
         }
-        assert_eq!(oidc.s_claims.name, Some("Test Person 1".to_string()));
+        .into();
+
+        for invalid_proof in [
+            None,
+            Some(dpop_proof(&other_signer, "POST", &token_endpoint, ct, None)),
+        ] {
+            assert_eq!(
+                idms_prox_write
+                    .check_oauth2_token_exchange(&client_authz_with(invalid_proof), &token_req, ct)
+                    .unwrap_err(),
+                Oauth2Error::InvalidGrant
+            );
+        }
+
+        let refresh_proof = dpop_proof(&dpop_signer, "POST", &token_endpoint, ct, None);
+
+        let refreshed_response = idms_prox_write
+            .check_oauth2_token_exchange(
+                &client_authz_with(Some(refresh_proof.clone())),
+                &token_req,
+                ct,
+            )
+            .expect("Unable to refresh OAuth2 token");
+        assert_eq!(refreshed_response.token_type, AccessTokenType::DPoP);
+
+        // A proof can only be used once.
+        let token_req: AccessTokenRequest = GrantTypeReq::RefreshToken {
+            refresh_token: refreshed_response
+                .refresh_token
+                .clone()
+                .expect("no refresh token was issued"),
+            scope: None,
+        }
+        .into();
+
         assert_eq!(
-            oidc.s_claims.preferred_username,
-            Some("testperson1@example.com".to_string())
+            idms_prox_write
+                .check_oauth2_token_exchange(
+                    &client_authz_with(Some(refresh_proof)),
+                    &token_req,
+                    ct
+                )
+                .unwrap_err(),
+            Oauth2Error::InvalidDpopProof
         );
-        assert!(
-            oidc.s_claims.scopes == vec![OAUTH2_SCOPE_OPENID.to_string(), "supplement".to_string()]
+
+        // A client credentials token issued without a proof is a bearer token.
+        let client_credentials_req: AccessTokenRequest =
+            GrantTypeReq::ClientCredentials { scope: None }.into();
+
+        let bearer_response = idms_prox_write
+            .check_oauth2_token_exchange(&client_authz_with(None), &client_credentials_req, ct)
+            .expect("Unable to exchange for OAuth2 token");
+        assert_eq!(bearer_response.token_type, AccessTokenType::Bearer);
+
+        assert!(idms_prox_write.commit().is_ok());
+
+        // == Once DPoP is required, unbound tokens are neither issued nor accepted.
+        let mut idms_prox_write = idms.proxy_write(ct).await.unwrap();
+        idms_prox_write
+            .qs_write
+            .internal_modify_uuid(
+                rs_uuid,
+                &ModifyList::new_purge_and_set(Attribute::OAuth2RequireDpop, Value::new_bool(true)),
+            )
+            .expect("Failed to require DPoP");
+        assert!(idms_prox_write.commit().is_ok());
+
+        let mut idms_prox_write = idms.proxy_write(ct).await.unwrap();
+
+        assert_eq!(
+            idms_prox_write
+                .check_oauth2_token_exchange(&client_authz_with(None), &client_credentials_req, ct)
+                .unwrap_err(),
+            Oauth2Error::InvalidDpopProof
+        );
+
+        let bound_response = idms_prox_write
+            .check_oauth2_token_exchange(
+                &client_authz_with(Some(dpop_proof(
+                    &dpop_signer,
+                    "POST",
+                    &token_endpoint,
+                    ct,
+                    None,
+                ))),
+                &client_credentials_req,
+                ct,
+            )
+            .expect("Unable to exchange for OAuth2 token");
+        assert_eq!(bound_response.token_type, AccessTokenType::DPoP);
+
+        assert!(idms_prox_write.commit().is_ok());
+
+        let mut idms_prox_read = idms.proxy_read().await.unwrap();
+
+        assert!(idms_prox_read
+            .oauth2_openid_discovery("test_resource_server")
+            .expect("Failed to get discovery")
+            .dpop_signing_alg_values_supported
+            .is_some_and(|algs| algs.contains(&IdTokenSignAlg::ES256)));
+
+        let mut introspect = |token: &str| {
+            idms_prox_read
+                .check_oauth2_token_introspect(
+                    &client_authz_with(None),
+                    &AccessTokenIntrospectRequest {
+                        token: token.to_string(),
+                        token_type_hint: None,
+                        client_post_auth: ClientPostAuth::default(),
+                    },
+                    ct,
+                )
+                .expect("Failed to inspect token")
+        };
+
+        assert!(!introspect(&bearer_response.access_token).active);
+
+        let intr_response = introspect(&bound_response.access_token);
+        assert!(intr_response.active);
+        assert_eq!(
+            intr_response.cnf,
+            Some(ConfirmationClaim {
+                jkt: Some(dpop_jkt),
+                x5t_s256: None,
+            })
+        );
+    }
+
+    #[idm_test]
+    async fn test_idm_oauth2_mtls_client_auth_and_bound_tokens(
+        idms: &IdmServer,
+        _idms_delayed: &mut IdmServerDelayed,
+    ) {
+        use crypto_glue::{
+            ecdsa_p256::{self, EcdsaP256DerSignature, EcdsaP256SigningKey, EcdsaP256VerifyingKey},
+            rand,
+            s256::Sha256,
+            traits::{Digest, EncodeDer},
+            x509::{
+                uuid_to_serial, x509_digest_public_key_sha256, Builder, Certificate,
+                CertificateBuilder, GeneralizedTime, Name, Profile, SubjectPublicKeyInfoOwned,
+                Time, Validity, X509Store,
+            },
+        };
+
+        let ct = Duration::from_secs(TEST_CURRENT_TIME);
+        let (secret, _uat, _ident, rs_uuid) =
+            setup_oauth2_resource_server_basic(idms, ct, true, false, false).await;
+        let client_authz = ClientAuthInfo::encode_basic("test_resource_server", secret.as_str());
+
+        let build_certificate =
+            |profile: Profile, subject: &str, signing_key: &EcdsaP256SigningKey, spki| {
+                let validity = Validity {
+                    not_before: Time::from(
+                        GeneralizedTime::from_unix_duration(ct).expect("Invalid time"),
+                    ),
+                    not_after: Time::from(
+                        GeneralizedTime::from_unix_duration(ct + Duration::from_secs(86400))
+                            .expect("Invalid time"),
+                    ),
+                };
+
+                CertificateBuilder::new(
+                    profile,
+                    uuid_to_serial(Uuid::new_v4()),
+                    validity,
+                    Name::from_str(subject).expect("Invalid subject"),
+                    spki,
+                    signing_key,
+                )
+                .expect("Unable to construct certificate builder")
+                .build_with_rng::<EcdsaP256DerSignature>(&mut rand::thread_rng())
+                .expect("Unable to build certificate")
+            };
+
+        let new_key = || {
+            let signing_key = EcdsaP256SigningKey::from(&ecdsa_p256::new_key());
+            let spki =
+                SubjectPublicKeyInfoOwned::from_key(EcdsaP256VerifyingKey::from(&signing_key))
+                    .expect("Unable to encode public key");
+            (signing_key, spki)
+        };
+
+        // The CA dedicated to OAuth2 clients, and a certificate it issued to this client.
+        let (ca_key, ca_spki) = new_key();
+        let ca_certificate =
+            build_certificate(Profile::Root, "CN=OAuth2 Client CA", &ca_key, ca_spki);
+        let oauth2_client_ca = X509Store::new(std::slice::from_ref(&ca_certificate));
+
+        let subject_dn = "CN=test_resource_server,O=Example";
+        let (_client_key, client_spki) = new_key();
+        let certificate = build_certificate(
+            Profile::Leaf {
+                issuer: ca_certificate.tbs_certificate.subject.clone(),
+                enable_key_agreement: false,
+                enable_key_encipherment: false,
+                include_subject_key_identifier: true,
+            },
+            subject_dn,
+            &ca_key,
+            client_spki,
         );
 
+        let to_client_cert_auth = |certificate: &Certificate| {
+            ClientAuthInfo::from(ClientCertInfo {
+                public_key_s256: x509_digest_public_key_sha256(certificate)
+                    .expect("Unable to digest public key"),
+                certificate: certificate.clone(),
+            })
+        };
+        let client_cert_auth = to_client_cert_auth(&certificate);
+
+        let x5t_s256 = {
+            let mut hasher = Sha256::new();
+            hasher.update(certificate.to_der().expect("Unable to encode certificate"));
+            general_purpose::URL_SAFE_NO_PAD.encode(hasher.finalize())
+        };
+
+        // The client authenticates with only its client_id and the connection's certificate.
+        let token_req = AccessTokenRequest {
+            grant_type: GrantTypeReq::ClientCredentials { scope: None },
+            client_post_auth: ClientPostAuth {
+                client_id: Some("test_resource_server".to_string()),
+                ..Default::default()
+            },
+        };
+
+        let set_subject_dn = |value: Value| async move {
+            let mut idms_prox_write = idms.proxy_write(ct).await.unwrap();
+            idms_prox_write
+                .qs_write
+                .internal_modify_uuid(
+                    rs_uuid,
+                    &ModifyList::new_purge_and_set(
+                        Attribute::OAuth2RsTlsClientAuthSubjectDn,
+                        value,
+                    ),
+                )
+                .expect("Unable to update client");
+            assert!(idms_prox_write.commit().is_ok());
+        };
+
+        let exchange = |client_auth_info: &ClientAuthInfo, with_client_ca: bool| {
+            let client_auth_info = client_auth_info.clone();
+            let token_req = &token_req;
+            let oauth2_client_ca = &oauth2_client_ca;
+            async move {
+                let mut idms_prox_write = idms.proxy_write(ct).await.unwrap();
+                idms_prox_write.oauth2_client_ca = with_client_ca.then_some(oauth2_client_ca);
+                let res =
+                    idms_prox_write.check_oauth2_token_exchange(&client_auth_info, token_req, ct);
+                assert!(idms_prox_write.commit().is_ok());
+                res
+            }
+        };
+
+        // The client has no subject DN configured, so no certificate authenticates it.
         assert_eq!(
-            oidc.claims.get("custom_a").and_then(|v| v.as_str()),
-            Some("value_a,value_b")
+            exchange(&client_cert_auth, true).await.unwrap_err(),
+            Oauth2Error::AuthenticationRequired
         );
+
+        set_subject_dn(Value::new_utf8s("CN=not the test certificate")).await;
+
         assert_eq!(
-            oidc.claims.get("custom_b").and_then(|v| v.as_str()),
-            Some("value_a value_b")
+            exchange(&client_cert_auth, true).await.unwrap_err(),
+            Oauth2Error::AuthenticationRequired
         );
 
+        set_subject_dn(Value::new_utf8s(subject_dn)).await;
+
+        // Without an OAuth2 client CA, a matching subject DN is not enough.
         assert_eq!(
-            oidc.claims
-                .get("custom:claim-name")
-                .and_then(|v| v.as_str()),
-            Some("value:a-a")
+            exchange(&client_cert_auth, false).await.unwrap_err(),
+            Oauth2Error::AuthenticationRequired
         );
 
-        // Does our access token work with the userinfo endpoint?
-        // Do the id_token details line up to the userinfo?
-        let userinfo = idms_prox_read
-            .oauth2_openid_userinfo("test_resource_server", &access_token, ct)
-            .expect("failed to get userinfo");
+        let oauth2_token = exchange(&client_cert_auth, true)
+            .await
+            .expect("Failed to perform OAuth2 token exchange");
 
-        assert_eq!(oidc.iss, userinfo.iss);
-        assert_eq!(oidc.sub, userinfo.sub);
-        assert_eq!(oidc.aud, userinfo.aud);
-        assert_eq!(oidc.iat, userinfo.iat);
-        assert_eq!(oidc.nbf, userinfo.nbf);
-        assert_eq!(oidc.exp, userinfo.exp);
-        assert!(userinfo.auth_time.is_none());
-        assert_eq!(userinfo.nonce, Some("abcdef".to_string()));
-        assert!(userinfo.at_hash.is_none());
-        assert!(userinfo.jti.is_some());
-        if let Some(jti) = &userinfo.jti {
-            assert!(Uuid::from_str(jti).is_ok());
-        }
-        assert_eq!(oidc.amr, userinfo.amr);
-        assert_eq!(oidc.azp, userinfo.azp);
-        assert!(userinfo.jti.is_some());
-        if let Some(jti) = &userinfo.jti {
-            assert!(Uuid::from_str(jti).is_ok());
-        }
-        assert_eq!(oidc.s_claims, userinfo.s_claims);
-        assert_eq!(oidc.claims, userinfo.claims);
+        // Certificate bound tokens remain bearer tokens to the client.
+        assert_eq!(oauth2_token.token_type, AccessTokenType::Bearer);
 
-        // Check the oauth2 introspect bits.
+        // Introspection reports the binding so the resource server can enforce it.
+        let mut idms_prox_read = idms.proxy_read().await.unwrap();
         let intr_request = AccessTokenIntrospectRequest {
-            token: token_response.access_token.clone(),
+            token: oauth2_token.access_token.clone(),
             token_type_hint: None,
             client_post_auth: ClientPostAuth::default(),
         };
         let intr_response = idms_prox_read
             .check_oauth2_token_introspect(&client_authz, &intr_request, ct)
             .expect("Failed to inspect token");
-
-        eprintln!("👉  {intr_response:?}");
         assert!(intr_response.active);
         assert_eq!(
-            intr_response.scope,
-            btreeset!["openid".to_string(), "supplement".to_string()]
-        );
-        assert_eq!(
-            intr_response.client_id.as_deref(),
-            Some("test_resource_server")
-        );
-        assert_eq!(
-            intr_response.username.as_deref(),
-            Some("testperson1@example.com")
+            intr_response.cnf,
+            Some(ConfirmationClaim {
+                jkt: None,
+                x5t_s256: Some(x5t_s256.clone()),
+            })
         );
-        assert_eq!(intr_response.token_type, Some(AccessTokenType::Bearer));
-        assert_eq!(intr_response.iat, Some(ct.as_secs() as i64));
-        assert_eq!(intr_response.nbf, Some(ct.as_secs() as i64));
-        // Introspect doesn't have custom claims.
-
         drop(idms_prox_read);
-    }
-
-    #[idm_test]
-    async fn test_idm_oauth2_public_allow_localhost_redirect(
-        idms: &IdmServer,
-        _idms_delayed: &mut IdmServerDelayed,
-    ) {
-        let ct = Duration::from_secs(TEST_CURRENT_TIME);
-        let (_uat, ident, oauth2_rs_uuid) = setup_oauth2_resource_server_public(idms, ct).await;
-
-        let mut idms_prox_write: crate::idm::server::IdmServerProxyWriteTransaction<'_> =
-            idms.proxy_write(ct).await.unwrap();
-
-        let redirect_uri = Url::parse("http://localhost:8765/oauth2/result")
-            .expect("Failed to parse redirect URL");
-
-        let modlist = ModifyList::new_list(vec![
-            Modify::Present(Attribute::OAuth2AllowLocalhostRedirect, Value::Bool(true)),
-            Modify::Present(Attribute::OAuth2RsOrigin, Value::Url(redirect_uri.clone())),
-        ]);
 
-        assert!(idms_prox_write
-            .qs_write
-            .internal_modify(
-                &filter!(f_eq(Attribute::Uuid, PartialValue::Uuid(oauth2_rs_uuid))),
-                &modlist,
-            )
-            .is_ok());
+        // Tokens issued without a certificate are not bound.
+        let oauth2_token = exchange(
+            &ClientAuthInfo::encode_basic("test_resource_server", secret.as_str()),
+            true,
+        )
+        .await
+        .expect("Failed to perform OAuth2 token exchange");
 
-        assert!(idms_prox_write.commit().is_ok());
+        let mut idms_prox_read = idms.proxy_read().await.unwrap();
+        let intr_request = AccessTokenIntrospectRequest {
+            token: oauth2_token.access_token.clone(),
+            token_type_hint: None,
+            client_post_auth: ClientPostAuth::default(),
+        };
+        let intr_response = idms_prox_read
+            .check_oauth2_token_introspect(&client_authz, &intr_request, ct)
+            .expect("Failed to inspect token");
+        assert!(intr_response.active);
+        assert_eq!(intr_response.cnf, None);
+        drop(idms_prox_read);
 
-        let idms_prox_read = idms.proxy_read().await.unwrap();
+        // A self signed certificate with the same subject DN is not issued by the OAuth2
+        // client CA, even if it was trusted by the client CA during the TLS handshake.
+        let (self_signed_key, self_signed_spki) = new_key();
+        let self_signed_certificate = build_certificate(
+            Profile::Root,
+            subject_dn,
+            &self_signed_key,
+            self_signed_spki,
+        );
 
-        // == Setup the authorisation request
-        let pkce_secret = PkceS256Secret::default();
+        assert_eq!(
+            exchange(&to_client_cert_auth(&self_signed_certificate), true)
+                .await
+                .unwrap_err(),
+            Oauth2Error::AuthenticationRequired
+        );
 
-        let auth_req = AuthorisationRequest {
-            response_type: ResponseType::Code,
-            response_mode: None,
-            client_id: "test_resource_server".to_string(),
-            state: Some("123".to_string()),
-            pkce_request: Some(pkce_secret.to_request()),
-            redirect_uri: Url::parse("http://localhost:8765/oauth2/result").unwrap(),
-            scope: btreeset![OAUTH2_SCOPE_OPENID.to_string()],
-            nonce: Some("abcdef".to_string()),
-            oidc_ext: Default::default(),
-            max_age: None,
-            unknown_keys: Default::default(),
+        let introspect_cnf = |access_token: String| {
+            let client_authz = &client_authz;
+            async move {
+                let mut idms_prox_read = idms.proxy_read().await.unwrap();
+                let intr_request = AccessTokenIntrospectRequest {
+                    token: access_token,
+                    token_type_hint: None,
+                    client_post_auth: ClientPostAuth::default(),
+                };
+                let intr_response = idms_prox_read
+                    .check_oauth2_token_introspect(client_authz, &intr_request, ct)
+                    .expect("Failed to inspect token");
+                assert!(intr_response.active);
+                intr_response.cnf
+            }
         };
 
-        let consent_request = idms_prox_read
-            .check_oauth2_authorisation(Some(&ident), &auth_req, ct)
-            .expect("OAuth2 authorisation failed");
-
-        // Should be in the consent phase;
-        let AuthoriseResponse::ConsentRequested { consent_token, .. } = consent_request else {
-            unreachable!();
+        // A certificate that the client did not authenticate with does not bind the tokens,
+        // even when the client authenticated by other means.
+        let self_signed_client_cert = ClientCertInfo {
+            public_key_s256: x509_digest_public_key_sha256(&self_signed_certificate)
+                .expect("Unable to digest public key"),
+            certificate: self_signed_certificate.clone(),
         };
+        let mut secret_with_cert_auth =
+            ClientAuthInfo::encode_basic("test_resource_server", secret.as_str());
+        secret_with_cert_auth.set_oauth2_client_cert(self_signed_client_cert.clone());
 
-        // == Manually submit the consent token to the permit for the permit_success
-        drop(idms_prox_read);
-        let mut idms_prox_write = idms.proxy_write(ct).await.unwrap();
-
-        let permit_success = idms_prox_write
-            .check_oauth2_authorise_permit(&ident, &consent_token, ct)
-            .expect("Failed to perform OAuth2 permit");
+        let oauth2_token = exchange(&secret_with_cert_auth, true)
+            .await
+            .expect("Failed to perform OAuth2 token exchange");
+        assert_eq!(introspect_cnf(oauth2_token.access_token).await, None);
 
-        // Check we are reflecting the CSRF properly.
-        assert_eq!(permit_success.state.as_deref(), Some("123"));
+        // == self_signed_tls_client_auth - once registered to the client, the self signed
+        // certificate authenticates it without any CA, and the tokens are bound to it.
+        let mut idms_prox_write = idms.proxy_write(ct).await.unwrap();
+        idms_prox_write
+            .qs_write
+            .internal_modify_uuid(
+                rs_uuid,
+                &ModifyList::new_purge_and_set(
+                    Attribute::OAuth2RsTlsClientCertificate,
+                    Value::Certificate(Box::new(self_signed_certificate.clone())),
+                ),
+            )
+            .expect("Unable to update client");
+        assert!(idms_prox_write.commit().is_ok());
 
-        // == Submit the token exchange code.
-        let token_req = AccessTokenRequest {
-            grant_type: GrantTypeReq::AuthorizationCode {
-                code: permit_success.code,
-                redirect_uri,
-                code_verifier: Some(pkce_secret.to_verifier()),
-            },
-            client_post_auth: ClientPostAuth {
-                client_id: Some("test_resource_server".to_string()),
-                client_secret: None,
-            },
-        };
+        let mut self_signed_cert_auth = ClientAuthInfo::from(Source::Internal);
+        self_signed_cert_auth.set_oauth2_client_cert(self_signed_client_cert);
 
-        let token_response = idms_prox_write
-            .check_oauth2_token_exchange(&ClientAuthInfo::none(), &token_req, ct)
+        let oauth2_token = exchange(&self_signed_cert_auth, false)
+            .await
             .expect("Failed to perform OAuth2 token exchange");
 
-        // 🎉 We got a token! In the future we can then check introspection from this point.
-        assert_eq!(token_response.token_type, AccessTokenType::Bearer);
-
-        assert!(idms_prox_write.commit().is_ok());
+        let self_signed_x5t_s256 = {
+            let mut hasher = Sha256::new();
+            hasher.update(
+                self_signed_certificate
+                    .to_der()
+                    .expect("Unable to encode certificate"),
+            );
+            general_purpose::URL_SAFE_NO_PAD.encode(hasher.finalize())
+        };
+        assert_eq!(
+            introspect_cnf(oauth2_token.access_token).await,
+            Some(ConfirmationClaim {
+                jkt: None,
+                x5t_s256: Some(self_signed_x5t_s256),
+            })
+        );
     }
 
     #[idm_test]
-    async fn test_idm_oauth2_service_account_token_exchange(
+    async fn test_idm_oauth2_dynamic_client_registration(
         idms: &IdmServer,
         _idms_delayed: &mut IdmServerDelayed,
     ) {
         let ct = Duration::from_secs(TEST_CURRENT_TIME);
-        let (secret, _uat, _ident, _) =
-            setup_oauth2_resource_server_basic(idms, ct, true, false, false).await;
+        // This creates the test group that registered clients are granted scopes through.
+        let _ = setup_oauth2_resource_server_basic(idms, ct, true, false, false).await;
 
         let mut idms_prox_write = idms.proxy_write(ct).await.unwrap();
 
-        let service_account_uuid = Uuid::new_v4();
-        let sa_entry: Entry<EntryInit, EntryNew> = entry_init!(
+        let registrant_uuid = Uuid::new_v4();
+        let unpermitted_uuid = Uuid::new_v4();
+
+        let registrant: EntryInitNew = entry_init!(
             (Attribute::Class, EntryClass::Object.to_value()),
             (Attribute::Class, EntryClass::Account.to_value()),
             (Attribute::Class, EntryClass::ServiceAccount.to_value()),
-            (Attribute::Name, Value::new_iname("test_sa_oauth2")),
-            (Attribute::Uuid, Value::Uuid(service_account_uuid)),
-            (Attribute::DisplayName, Value::new_utf8s("test_sa_oauth2")),
-            (Attribute::Description, Value::new_utf8s("test_sa_oauth2"))
+            (Attribute::Name, Value::new_iname("test_registrant")),
+            (Attribute::Uuid, Value::Uuid(registrant_uuid)),
+            (Attribute::DisplayName, Value::new_utf8s("test_registrant")),
+            (
+                Attribute::OAuth2RegistrationScopeMap,
+                Value::new_oauthscopemap(
+                    UUID_TESTGROUP,
+                    btreeset![
+                        OAUTH2_SCOPE_OPENID.to_string(),
+                        OAUTH2_SCOPE_GROUPS.to_string()
+                    ]
+                )
+                .expect("invalid oauthscope")
+            ),
+            (
+                Attribute::OAuth2RegistrationScopeMap,
+                Value::new_oauthscopemap(
+                    UUID_IDM_ALL_ACCOUNTS,
+                    btreeset![
+                        OAUTH2_SCOPE_OPENID.to_string(),
+                        OAUTH2_SCOPE_PROFILE.to_string()
+                    ]
+                )
+                .expect("invalid oauthscope")
+            )
         );
 
-        idms_prox_write
-            .qs_write
-            .internal_create(vec![sa_entry])
-            .expect("Failed to create service account");
+        let unpermitted: EntryInitNew = entry_init!(
+            (Attribute::Class, EntryClass::Object.to_value()),
+            (Attribute::Class, EntryClass::Account.to_value()),
+            (Attribute::Class, EntryClass::ServiceAccount.to_value()),
+            (Attribute::Name, Value::new_iname("test_unpermitted")),
+            (Attribute::Uuid, Value::Uuid(unpermitted_uuid)),
+            (Attribute::DisplayName, Value::new_utf8s("test_unpermitted"))
+        );
 
         idms_prox_write
             .qs_write
-            .internal_modify(
-                &filter!(f_eq(Attribute::Uuid, PartialValue::Uuid(UUID_TESTGROUP))),
-                &ModifyList::new_list(vec![Modify::Present(
-                    Attribute::Member,
-                    Value::Refer(service_account_uuid),
-                )]),
-            )
-            .expect("Failed to add service account to scope group");
+            .internal_create(vec![registrant, unpermitted])
+            .expect("Failed to create service accounts");
 
-        let gte = GenerateApiTokenEvent::new_internal(service_account_uuid, "sa-token", None);
+        let mut generate_token = |target: Uuid, read_write: bool| {
+            let mut gte = GenerateApiTokenEvent::new_internal(target, "iat", None);
+            gte.read_write = read_write;
+            idms_prox_write
+                .service_account_generate_api_token(&gte, ct)
+                .expect("failed to generate api token")
+        };
 
-        let api_token = idms_prox_write
-            .service_account_generate_api_token(&gte, ct)
-            .expect("failed to generate api token");
+        let initial_access_token = generate_token(registrant_uuid, true);
+        let read_only_token = generate_token(registrant_uuid, false);
+        let unpermitted_token = generate_token(unpermitted_uuid, true);
 
         assert!(idms_prox_write.commit().is_ok());
 
-        let client_authz = ClientAuthInfo::encode_basic("test_resource_server", secret.as_str());
-
-        let scopes: BTreeSet<String> =
-            btreeset![OAUTH2_SCOPE_OPENID.into(), OAUTH2_SCOPE_GROUPS.into()];
-
-        let build_exchange_request =
-            |requested_scopes: BTreeSet<String>, client_secret: Option<String>| {
-                AccessTokenRequest {
-                    grant_type: GrantTypeReq::TokenExchange {
-                        subject_token: api_token.to_string(),
-                        subject_token_type: TOKEN_EXCHANGE_SUBJECT_TOKEN_TYPE_ACCESS.into(),
-                        requested_token_type: None,
-                        audience: Some("test_resource_server".into()),
-                        resource: None,
-                        actor_token: None,
-                        actor_token_type: None,
-                        scope: Some(requested_scopes),
-                    },
-                    client_post_auth: ClientPostAuth {
-                        client_id: Some("test_resource_server".into()),
-                        client_secret,
-                    },
-                }
-            };
-
-        let token_req = build_exchange_request(scopes.clone(), None);
-        let forbidden_secret_req = build_exchange_request(scopes.clone(), Some(secret.clone()));
-        let empty_scope_req = build_exchange_request(BTreeSet::new(), None);
+        let metadata = ClientMetadata {
+            redirect_uris: vec![Url::parse("https://app.example.com/oauth2/callback").unwrap()],
+            client_name: Some("Registered Application".to_string()),
+            scope: Some("openid groups".to_string()),
+            ..Default::default()
+        };
 
         let mut idms_prox_write = idms.proxy_write(ct).await.unwrap();
 
+        // Only read write tokens of accounts with registration scope maps may register.
+        for token in [read_only_token, unpermitted_token] {
+            let ident = idms_prox_write
+                .validate_client_auth_info_to_ident(token.into(), ct)
+                .expect("Failed to validate api token");
+            assert_eq!(
+                idms_prox_write
+                    .oauth2_client_register(&ident, &metadata, ct)
+                    .unwrap_err(),
+                Oauth2Error::AccessDenied
+            );
+        }
+
+        let ident = idms_prox_write
+            .validate_client_auth_info_to_ident(initial_access_token.clone().into(), ct)
+            .expect("Failed to validate api token");
+
+        let excessive_scope = ClientMetadata {
+            scope: Some("openid admin".to_string()),
+            ..metadata.clone()
+        };
         assert_eq!(
             idms_prox_write
-                .check_oauth2_token_exchange(&client_authz, &forbidden_secret_req, ct)
+                .oauth2_client_register(&ident, &excessive_scope, ct)
                 .unwrap_err(),
-            Oauth2Error::InvalidRequest
+            Oauth2Error::InvalidClientMetadata
         );
 
+        let insecure_redirect = ClientMetadata {
+            redirect_uris: vec![Url::parse("http://app.example.com/oauth2/callback").unwrap()],
+            ..metadata.clone()
+        };
         assert_eq!(
             idms_prox_write
-                .check_oauth2_token_exchange(&ClientAuthInfo::none(), &empty_scope_req, ct)
+                .oauth2_client_register(&ident, &insecure_redirect, ct)
                 .unwrap_err(),
-            Oauth2Error::InvalidRequest
+            Oauth2Error::InvalidRedirectUri
         );
 
-        let token_response = idms_prox_write
-            .check_oauth2_token_exchange(&ClientAuthInfo::none(), &token_req, ct)
-            .expect("Failed to perform OAuth2 token exchange for service account");
+        let registration = idms_prox_write
+            .oauth2_client_register(&ident, &metadata, ct)
+            .expect("Failed to register client");
 
-        assert_eq!(token_response.token_type, AccessTokenType::Bearer);
+        assert!(idms_prox_write.commit().is_ok());
+
+        let client_id = registration.client_id.clone();
+        assert!(registration.client_secret.is_some());
+        assert_eq!(registration.client_secret_expires_at, Some(0));
         assert_eq!(
-            token_response.issued_token_type,
-            Some(IssuedTokenType::AccessToken)
+            registration.client_id_issued_at,
+            Some(TEST_CURRENT_TIME as i64)
+        );
+        assert_eq!(
+            registration.metadata.token_endpoint_auth_method,
+            Some(TokenEndpointAuthMethod::ClientSecretBasic)
+        );
+        assert_eq!(
+            registration.metadata.scope.as_deref(),
+            Some("groups openid")
+        );
+        assert_eq!(
+            registration.registration_client_uri,
+            Some(
+                Url::parse(&format!(
+                    "https://idm.example.com/oauth2/register/{client_id}"
+                ))
+                .unwrap()
+            )
         );
-        assert!(token_response.refresh_token.is_some());
-        assert!(token_response.id_token.is_some());
-        let response_scopes = token_response.scope.clone();
-        assert!(response_scopes.contains(OAUTH2_SCOPE_OPENID));
-        assert!(response_scopes.contains(OAUTH2_SCOPE_GROUPS));
 
-        assert!(idms_prox_write.commit().is_ok());
+        let registration_access_token = registration
+            .registration_access_token
+            .as_deref()
+            .map(JwsCompact::from_str)
+            .expect("Missing registration access token")
+            .expect("Invalid registration access token");
+        let registration_auth: ClientAuthInfo = registration_access_token.into();
 
         let mut idms_prox_read = idms.proxy_read().await.unwrap();
-        let intr_request = AccessTokenIntrospectRequest {
-            token: token_response.access_token.clone(),
-            token_type_hint: None,
-            client_post_auth: ClientPostAuth::default(),
-        };
-        let intr_response = idms_prox_read
-            .check_oauth2_token_introspect(&client_authz, &intr_request, ct)
-            .expect("Failed to introspect service account token");
 
-        assert!(intr_response.active);
+        // The registrant's scope maps are narrowed to the requested scopes.
+        let o2rs = idms_prox_read
+            .oauth2rs
+            .inner
+            .rs_set_get(&client_id)
+            .expect("Registered client is missing");
         assert_eq!(
-            intr_response.client_id.as_deref(),
-            Some("test_resource_server")
-        );
+            idms_prox_read
+                .qs_read
+                .internal_search_uuid(o2rs.uuid)
+                .expect("Failed to search registered client")
+                .get_ava_single_refer(Attribute::EntryManagedBy),
+            Some(registrant_uuid)
+        );
+        let rs_uuid = o2rs.uuid;
+        let registration_token_id = o2rs
+            .registration_token_id
+            .expect("Registered client is missing its registration token id");
         assert_eq!(
-            intr_response.sub.as_deref(),
-            Some(service_account_uuid.to_string().as_str())
+            o2rs.scope_maps,
+            btreemap![
+                (
+                    UUID_TESTGROUP,
+                    btreeset![
+                        OAUTH2_SCOPE_OPENID.to_string(),
+                        OAUTH2_SCOPE_GROUPS.to_string()
+                    ]
+                ),
+                (
+                    UUID_IDM_ALL_ACCOUNTS,
+                    btreeset![OAUTH2_SCOPE_OPENID.to_string()]
+                )
+            ]
         );
-    }
 
-    #[idm_test]
-    async fn test_idm_oauth2_basic_client_credentials_grant_valid(
-        idms: &IdmServer,
-        _idms_delayed: &mut IdmServerDelayed,
-    ) {
-        let ct = Duration::from_secs(TEST_CURRENT_TIME);
-        let (secret, _uat, _ident, _) =
-            setup_oauth2_resource_server_basic(idms, ct, true, false, false).await;
-        let client_authz = ClientAuthInfo::encode_basic("test_resource_server", secret.as_str());
+        // Only the registration access token can manage the client.
+        for client_auth_info in [ClientAuthInfo::none(), initial_access_token.into()] {
+            assert_eq!(
+                idms_prox_read
+                    .oauth2_client_registration_read(&client_id, &client_auth_info)
+                    .unwrap_err(),
+                Oauth2Error::AuthenticationRequired
+            );
+        }
 
-        // scope: Some(btreeset!["invalid_scope".to_string()]),
-        let mut idms_prox_write = idms.proxy_write(ct).await.unwrap();
+        let read = idms_prox_read
+            .oauth2_client_registration_read(&client_id, &registration_auth)
+            .expect("Failed to read client registration");
+        assert_eq!(read.client_secret, registration.client_secret);
+        assert_eq!(read.metadata.redirect_uris, metadata.redirect_uris);
+        assert_eq!(
+            read.metadata.client_name.as_deref(),
+            Some("Registered Application")
+        );
+        drop(idms_prox_read);
 
-        let token_req = AccessTokenRequest {
-            grant_type: GrantTypeReq::ClientCredentials { scope: None },
+        let mut idms_prox_write = idms.proxy_write(ct).await.unwrap();
 
-            client_post_auth: ClientPostAuth {
-                client_id: Some("test_resource_server".to_string()),
-                client_secret: Some(secret),
-            },
+        // The scopes of a registered client can be narrowed but not widened, and it can't
+        // become a public client.
+        let widened_scope = ClientMetadata {
+            scope: Some("openid groups profile".to_string()),
+            ..metadata.clone()
         };
+        let public_client = ClientMetadata {
+            token_endpoint_auth_method: Some(TokenEndpointAuthMethod::None),
+            ..metadata.clone()
+        };
+        for invalid_update in [widened_scope, public_client] {
+            assert_eq!(
+                idms_prox_write
+                    .oauth2_client_registration_update(
+                        &client_id,
+                        &registration_auth,
+                        &invalid_update
+                    )
+                    .unwrap_err(),
+                Oauth2Error::InvalidClientMetadata
+            );
+        }
 
-        let oauth2_token = idms_prox_write
-            .check_oauth2_token_exchange(&ClientAuthInfo::none(), &token_req, ct)
-            .expect("Failed to perform OAuth2 token exchange");
+        let narrowed = ClientMetadata {
+            redirect_uris: vec![Url::parse("https://app.example.com/oauth2/other").unwrap()],
+            scope: Some("openid".to_string()),
+            ..metadata.clone()
+        };
+        let updated = idms_prox_write
+            .oauth2_client_registration_update(&client_id, &registration_auth, &narrowed)
+            .expect("Failed to update client registration");
+        assert_eq!(updated.metadata.scope.as_deref(), Some("openid"));
+        assert_eq!(updated.metadata.redirect_uris, narrowed.redirect_uris);
 
         assert!(idms_prox_write.commit().is_ok());
 
-        // 🎉 We got a token! In the future we can then check introspection from this point.
-        assert_eq!(oauth2_token.token_type, AccessTokenType::Bearer);
+        // An administrator can supersede or revoke the registration access token.
+        for modlist in [
+            ModifyList::new_purge_and_set(
+                Attribute::OAuth2RsRegistrationTokenId,
+                Value::Uuid(Uuid::new_v4()),
+            ),
+            ModifyList::new_purge(Attribute::OAuth2RsRegistrationTokenId),
+        ] {
+            let mut idms_prox_write = idms.proxy_write(ct).await.unwrap();
+            idms_prox_write
+                .qs_write
+                .internal_modify_uuid(rs_uuid, &modlist)
+                .expect("Failed to modify registration token id");
+            assert!(idms_prox_write.commit().is_ok());
+
+            let mut idms_prox_read = idms.proxy_read().await.unwrap();
+            assert_eq!(
+                idms_prox_read
+                    .oauth2_client_registration_read(&client_id, &registration_auth)
+                    .unwrap_err(),
+                Oauth2Error::AuthenticationRequired
+            );
+        }
 
-        // Check Oauth2 Token Introspection
-        let mut idms_prox_read = idms.proxy_read().await.unwrap();
+        let mut idms_prox_write = idms.proxy_write(ct).await.unwrap();
+        idms_prox_write
+            .qs_write
+            .internal_modify_uuid(
+                rs_uuid,
+                &ModifyList::new_purge_and_set(
+                    Attribute::OAuth2RsRegistrationTokenId,
+                    Value::Uuid(registration_token_id),
+                ),
+            )
+            .expect("Failed to restore registration token id");
+        assert!(idms_prox_write.commit().is_ok());
 
-        let intr_request = AccessTokenIntrospectRequest {
-            token: oauth2_token.access_token.clone(),
-            token_type_hint: None,
-            client_post_auth: ClientPostAuth::default(),
-        };
-        let intr_response = idms_prox_read
-            .check_oauth2_token_introspect(&client_authz, &intr_request, ct)
-            .expect("Failed to inspect token");
+        let mut idms_prox_write = idms.proxy_write(ct).await.unwrap();
 
-        eprintln!("👉  {intr_response:?}");
-        assert!(intr_response.active);
-        assert_eq!(intr_response.scope, btreeset!["supplement".to_string()]);
-        assert_eq!(
-            intr_response.client_id.as_deref(),
-            Some("test_resource_server")
-        );
         assert_eq!(
-            intr_response.username.as_deref(),
-            Some("test_resource_server@example.com")
+            idms_prox_write
+                .oauth2_client_registration_delete(&client_id, &ClientAuthInfo::none())
+                .unwrap_err(),
+            Oauth2Error::AuthenticationRequired
         );
-        assert_eq!(intr_response.token_type, Some(AccessTokenType::Bearer));
-        assert_eq!(intr_response.iat, Some(ct.as_secs() as i64));
-        assert_eq!(intr_response.nbf, Some(ct.as_secs() as i64));
 
-        drop(idms_prox_read);
+        idms_prox_write
+            .oauth2_client_registration_delete(&client_id, &registration_auth)
+            .expect("Failed to delete client registration");
 
-        // Assert we can revoke.
-        let mut idms_prox_write = idms.proxy_write(ct).await.unwrap();
-        let revoke_request = TokenRevokeRequest {
-            token: oauth2_token.access_token.clone(),
-            token_type_hint: None,
-            client_post_auth: ClientPostAuth::default(),
-        };
-        assert!(idms_prox_write
-            .oauth2_token_revoke(&client_authz, &revoke_request, ct,)
-            .is_ok());
         assert!(idms_prox_write.commit().is_ok());
 
-        // Now must be invalid.
-        let ct = ct + AUTH_TOKEN_GRACE_WINDOW;
-        let mut idms_prox_read = idms.proxy_read().await.unwrap();
-
-        let intr_request = AccessTokenIntrospectRequest {
-            token: oauth2_token.access_token.clone(),
-            token_type_hint: None,
-            client_post_auth: ClientPostAuth::default(),
-        };
-
-        let intr_response = idms_prox_read
-            .check_oauth2_token_introspect(&client_authz, &intr_request, ct)
-            .expect("Failed to inspect token");
-        assert!(!intr_response.active);
-
-        drop(idms_prox_read);
+        let idms_prox_read = idms.proxy_read().await.unwrap();
+        assert!(idms_prox_read
+            .oauth2rs
+            .inner
+            .rs_set_get(&client_id)
+            .is_none());
     }
 
     #[idm_test]
-    async fn test_idm_oauth2_basic_client_credentials_grant_invalid(
+    async fn test_idm_oauth2_dynamic_client_registration_access(
         idms: &IdmServer,
         _idms_delayed: &mut IdmServerDelayed,
     ) {
         let ct = Duration::from_secs(TEST_CURRENT_TIME);
-        let (secret, _uat, _ident, _) =
-            setup_oauth2_resource_server_basic(idms, ct, true, false, false).await;
+        let _ = setup_oauth2_resource_server_basic(idms, ct, true, false, false).await;
 
         let mut idms_prox_write = idms.proxy_write(ct).await.unwrap();
 
-        // Public Client
-        let token_req = AccessTokenRequest {
-            grant_type: GrantTypeReq::ClientCredentials { scope: None },
-            client_post_auth: ClientPostAuth {
-                client_id: Some("test_resource_server".to_string()),
-                client_secret: None,
-            },
-        };
+        let registrant_uuid = Uuid::new_v4();
 
-        assert_eq!(
-            idms_prox_write
-                .check_oauth2_token_exchange(&ClientAuthInfo::none(), &token_req, ct)
-                .unwrap_err(),
-            Oauth2Error::AuthenticationRequired
+        let registrant: EntryInitNew = entry_init!(
+            (Attribute::Class, EntryClass::Object.to_value()),
+            (Attribute::Class, EntryClass::Account.to_value()),
+            (Attribute::Class, EntryClass::ServiceAccount.to_value()),
+            (Attribute::Name, Value::new_iname("test_registrant")),
+            (Attribute::Uuid, Value::Uuid(registrant_uuid)),
+            (Attribute::DisplayName, Value::new_utf8s("test_registrant"))
         );
 
-        // Incorrect Password
-        let token_req = AccessTokenRequest {
-            grant_type: GrantTypeReq::ClientCredentials { scope: None },
+        idms_prox_write
+            .qs_write
+            .internal_create(vec![registrant])
+            .expect("Failed to create service account");
 
-            client_post_auth: ClientPostAuth {
-                client_id: Some("test_resource_server".to_string()),
-                client_secret: Some("wrong password".to_string()),
-            },
-        };
+        let admin_ident = idms_prox_write
+            .qs_write
+            .internal_search_uuid(UUID_IDM_ADMIN)
+            .map(Identity::from_impersonate_entry_readwrite)
+            .expect("Failed to get idm_admin");
 
-        assert_eq!(
-            idms_prox_write
-                .check_oauth2_token_exchange(&ClientAuthInfo::none(), &token_req, ct)
-                .unwrap_err(),
-            Oauth2Error::AuthenticationRequired
+        let person_ident = idms_prox_write
+            .qs_write
+            .internal_search_uuid(UUID_TESTPERSON_1)
+            .map(Identity::from_impersonate_entry_readwrite)
+            .expect("Failed to get testperson1");
+
+        // Only oauth2 administrators may grant an account the right to register clients.
+        let filter = filter!(f_eq(Attribute::Uuid, PartialValue::Uuid(registrant_uuid)));
+        let modlist = ModifyList::new_append(
+            Attribute::OAuth2RegistrationScopeMap,
+            Value::new_oauthscopemap(UUID_TESTGROUP, btreeset![OAUTH2_SCOPE_OPENID.to_string()])
+                .expect("invalid oauthscope"),
         );
 
-        // Invalid scope
-        let scope = Some(btreeset!["💅".to_string()]);
-        let token_req = AccessTokenRequest {
-            grant_type: GrantTypeReq::ClientCredentials { scope },
+        assert!(idms_prox_write
+            .qs_write
+            .impersonate_modify(&filter, &filter, &modlist, &person_ident)
+            .is_err());
 
-            client_post_auth: ClientPostAuth {
-                client_id: Some("test_resource_server".to_string()),
-                client_secret: Some(secret.clone()),
-            },
-        };
+        idms_prox_write
+            .qs_write
+            .impersonate_modify(&filter, &filter, &modlist, &admin_ident)
+            .expect("Failed to grant registration scope map");
 
-        assert_eq!(
-            idms_prox_write
-                .check_oauth2_token_exchange(&ClientAuthInfo::none(), &token_req, ct)
-                .unwrap_err(),
-            Oauth2Error::InvalidScope
-        );
+        let mut gte = GenerateApiTokenEvent::new_internal(registrant_uuid, "iat", None);
+        gte.read_write = true;
+        let initial_access_token = idms_prox_write
+            .service_account_generate_api_token(&gte, ct)
+            .expect("failed to generate api token");
 
-        // Scopes we aren't a member-of
-        let scope = Some(btreeset!["invalid_scope".to_string()]);
-        let token_req = AccessTokenRequest {
-            grant_type: GrantTypeReq::ClientCredentials { scope },
+        assert!(idms_prox_write.commit().is_ok());
 
-            client_post_auth: ClientPostAuth {
-                client_id: Some("test_resource_server".to_string()),
-                client_secret: Some(secret.clone()),
-            },
+        let mut idms_prox_write = idms.proxy_write(ct).await.unwrap();
+
+        let ident = idms_prox_write
+            .validate_client_auth_info_to_ident(initial_access_token.into(), ct)
+            .expect("Failed to validate api token");
+
+        let metadata = ClientMetadata {
+            redirect_uris: vec![Url::parse("https://app.example.com/oauth2/callback").unwrap()],
+            scope: Some("openid".to_string()),
+            ..Default::default()
         };
 
+        let registration = idms_prox_write
+            .oauth2_client_register(&ident, &metadata, ct)
+            .expect("Failed to register client");
+
+        // Oauth2 administrators can see who registered a client, and can supersede its
+        // registration access token.
+        let filter = filter!(f_eq(
+            Attribute::Name,
+            PartialValue::new_iname(&registration.client_id)
+        ));
+
+        let entries = idms_prox_write
+            .qs_write
+            .impersonate_search_ext(filter.clone(), filter.clone(), &admin_ident)
+            .expect("Failed to search registered client");
+        assert_eq!(entries.len(), 1);
         assert_eq!(
-            idms_prox_write
-                .check_oauth2_token_exchange(&ClientAuthInfo::none(), &token_req, ct)
-                .unwrap_err(),
-            Oauth2Error::AccessDenied
+            entries[0].get_ava_single_refer(Attribute::EntryManagedBy),
+            Some(registrant_uuid)
         );
 
+        let modlist = ModifyList::new_purge_and_set(
+            Attribute::OAuth2RsRegistrationTokenId,
+            Value::Uuid(Uuid::new_v4()),
+        );
+
+        assert!(idms_prox_write
+            .qs_write
+            .impersonate_modify(&filter, &filter, &modlist, &person_ident)
+            .is_err());
+
+        idms_prox_write
+            .qs_write
+            .impersonate_modify(&filter, &filter, &modlist, &admin_ident)
+            .expect("Failed to supersede registration access token");
+
         assert!(idms_prox_write.commit().is_ok());
     }
 
@@ -8063,6 +13545,7 @@ mod tests {
             nonce: Some("abcdef".to_string()),
             oidc_ext: Default::default(),
             max_age: None,
+            authorization_details: None,
             unknown_keys: Default::default(),
         };
         println!("{auth_req:?}");
@@ -111,6 +111,9 @@ impl IdmServerAuthTransaction<'_> {
 
         let is_valid = if let Some(slock_ref) = maybe_slock {
             let mut slock = slock_ref.lock().await;
+            if let Some(record) = account.softlock_record(&session_cred_id) {
+                slock.apply_record(record);
+            }
             slock.apply_time_step(ct, None);
             slock.is_valid()
         } else {
@@ -137,6 +140,7 @@ impl IdmServerAuthTransaction<'_> {
             ct,
             client_auth_info,
             oauth2_client_provider: None,
+            credential_update_intents: None,
         };
 
         let domain_keys = self.qs_read.get_domain_key_object_handle()?;
@@ -712,6 +716,10 @@ mod tests {
             _ => panic!("Oh no"),
         }
 
+        // And the failure is queued to be persisted.
+        let da = idms_delayed.try_recv().expect("invalid");
+        assert!(matches!(da, DelayedAction::CredentialSoftLockRecord(_)));
+
         // Start the re-auth - MUST FAIL!
         assert!(
             reauth_password_totp(idms, ct, &ident, &pw, &totp, idms_delayed)
@@ -7,41 +7,46 @@ use crate::idm::application::{
 use crate::idm::audit::AuditEvent;
 use crate::idm::authentication::{AuthState, PreValidatedTokenStatus};
 use crate::idm::authsession::{AuthSession, AuthSessionData};
+use crate::idm::breachedpassword::BreachedPasswordIndex;
 use crate::idm::credupdatesession::CredentialUpdateSessionMutex;
 use crate::idm::delayed::{
-    AuthSessionRecord, BackupCodeRemoval, DelayedAction, PasswordUpgrade, UnixPasswordUpgrade,
-    WebauthnCounterIncrement,
+    AuthSessionRecord, BackupCodeRemoval, CredentialSoftLockRecord, CredentialUpdateIntentRecord,
+    DelayedAction, PasswordUpgrade, UnixPasswordUpgrade, WebauthnCounterIncrement,
 };
 use crate::idm::event::{
-    AuthEvent, AuthEventStep, AuthResult, CredentialStatusEvent, LdapAuthEvent, LdapTokenAuthEvent,
-    RadiusAuthTokenEvent, RegenerateRadiusSecretEvent, UnixGroupTokenEvent,
-    UnixPasswordChangeEvent, UnixUserAuthEvent, UnixUserTokenEvent,
+    AuthEvent, AuthEventStep, AuthResult, CredentialSoftLockEvent, CredentialStatusEvent,
+    LdapAuthEvent, LdapTokenAuthEvent, RadiusAuthTokenEvent, RegenerateRadiusSecretEvent,
+    UnixGroupTokenEvent, UnixPasswordChangeEvent, UnixUserAuthEvent, UnixUserTokenEvent,
 };
 use crate::idm::group::{Group, Unix};
 use crate::idm::oauth2::{
-    Oauth2ResourceServers, Oauth2ResourceServersReadTransaction,
-    Oauth2ResourceServersWriteTransaction,
+    BackchannelAuthorisation, ClientAssertionJti, DpopProofJti, Oauth2ResourceServers,
+    Oauth2ResourceServersReadTransaction, Oauth2ResourceServersWriteTransaction,
+    PushedAuthorisation, RequestObjectJti,
 };
 use crate::idm::oauth2_client::OAuth2ClientProvider;
 use crate::idm::radius::RadiusAccount;
 use crate::idm::scim::SyncAccount;
 use crate::idm::serviceaccount::ServiceAccount;
 use crate::prelude::*;
+use crate::server::access::Access;
 use crate::server::keys::KeyProvidersTransaction;
 use crate::server::DomainInfo;
 use crate::utils::{password_from_random, readable_password_from_random, uuid_from_duration, Sid};
-use crate::value::{Session, SessionState};
+use crate::value::{IntentTokenState, Session, SessionState};
 use compact_jwt::{Jwk, JwsCompact};
 use concread::bptree::{BptreeMap, BptreeMapReadTxn, BptreeMapWriteTxn};
 use concread::cowcell::CowCellReadTxn;
 use concread::hashmap::{HashMap, HashMapReadTxn, HashMapWriteTxn};
+use crypto_glue::x509::X509Store;
 use kanidm_lib_crypto::CryptoPolicy;
 use kanidm_proto::internal::{
-    ApiToken, CredentialStatus, PasswordFeedback, RadiusAuthToken, ScimSyncToken, UatPurpose,
-    UserAuthToken,
+    ApiToken, CredentialSoftLockAccount, CredentialSoftLockStatus, CredentialStatus,
+    PasswordFeedback, RadiusAuthToken, ScimSyncToken, UatPurpose, UserAuthToken,
 };
 use kanidm_proto::v1::{UnixGroupToken, UnixUserToken};
 use rand::prelude::*;
+use std::collections::BTreeSet;
 use std::convert::TryFrom;
 use std::sync::Arc;
 use std::time::Duration;
@@ -61,6 +66,32 @@ use crate::idm::event::PasswordChangeEvent;
 pub(crate) type AuthSessionMutex = Arc<Mutex<AuthSession>>;
 pub(crate) type CredSoftLockMutex = Arc<Mutex<CredSoftLock>>;
 
+/// Queue the persistence of a soft lock after a failure changed its state, so that the
+/// lock is retained if this server restarts and is replicated to other servers.
+pub(crate) fn queue_softlock_record(
+    async_tx: &Sender<DelayedAction>,
+    target_uuid: Uuid,
+    cred_uuid: Uuid,
+    slock: &CredSoftLock,
+) {
+    let Some(record) = slock.to_record() else {
+        return;
+    };
+
+    if async_tx
+        .send(DelayedAction::CredentialSoftLockRecord(
+            CredentialSoftLockRecord {
+                target_uuid,
+                cred_uuid,
+                record,
+            },
+        ))
+        .is_err()
+    {
+        admin_warn!("unable to queue delayed credential soft lock record, continuing ... ");
+    }
+}
+
 pub type DomainInfoRead = CowCellReadTxn<DomainInfo>;
 
 pub struct IdmServer {
@@ -73,10 +104,28 @@ pub struct IdmServer {
     softlocks: HashMap<Uuid, CredSoftLockMutex>,
     /// A set of in progress credential registrations
     cred_update_sessions: BptreeMap<Uuid, CredentialUpdateSessionMutex>,
+    /// OAuth2 client assertions that have been used, retained until they expire. These are
+    /// shared outside of the transaction so that an assertion is consumed even when the
+    /// request that presented it fails.
+    oauth2_client_assertions: Arc<std::sync::Mutex<BTreeSet<ClientAssertionJti>>>,
+    /// DPoP proofs that have been used, retained while they could be replayed. Like client
+    /// assertions these are shared outside of the transaction, as userinfo is only a read.
+    oauth2_dpop_proofs: Arc<std::sync::Mutex<BTreeSet<DpopProofJti>>>,
+    /// Request objects that have been used, retained until they expire. These are shared
+    /// outside of the transaction, as the authorisation endpoint is only a read.
+    oauth2_request_objects: Arc<std::sync::Mutex<BTreeSet<RequestObjectJti>>>,
+    /// OAuth2 authorisation requests pushed by clients, indexed by their request_uri.
+    oauth2_pushed_authorisations: BptreeMap<Uuid, PushedAuthorisation>,
+    /// OAuth2 backchannel authentications waiting on the user, indexed by their auth_req_id.
+    oauth2_backchannel_authorisations: BptreeMap<Uuid, BackchannelAuthorisation>,
     /// Reference to the query server.
     qs: QueryServer,
     /// The configured crypto policy for the IDM server. Later this could be transactional and loaded from the db similar to access. But today it's just to allow dynamic pbkdf2rounds
     crypto_policy: CryptoPolicy,
+    /// An optional offline index of breached passwords that new passwords are checked against.
+    breached_passwords: Option<BreachedPasswordIndex>,
+    /// The CA that issues certificates to OAuth2 clients which authenticate with tls_client_auth.
+    oauth2_client_ca: Option<X509Store>,
     async_tx: Sender<DelayedAction>,
     audit_tx: Sender<AuditEvent>,
     /// [Webauthn] verifier/config
@@ -112,12 +161,18 @@ pub struct IdmServerCredUpdateTransaction<'a> {
     pub(crate) webauthn: &'a Webauthn,
     pub(crate) cred_update_sessions: BptreeMapReadTxn<'a, Uuid, CredentialUpdateSessionMutex>,
     pub(crate) crypto_policy: &'a CryptoPolicy,
+    pub(crate) breached_passwords: Option<&'a BreachedPasswordIndex>,
 }
 
 /// This contains read-only methods, like getting users, groups and other structured content.
 pub struct IdmServerProxyReadTransaction<'a> {
     pub qs_read: QueryServerReadTransaction<'a>,
     pub(crate) oauth2rs: Oauth2ResourceServersReadTransaction,
+    pub(crate) oauth2_pushed_authorisations: BptreeMapReadTxn<'a, Uuid, PushedAuthorisation>,
+    pub(crate) oauth2_backchannel_authorisations:
+        BptreeMapReadTxn<'a, Uuid, BackchannelAuthorisation>,
+    pub(crate) oauth2_dpop_proofs: Arc<std::sync::Mutex<BTreeSet<DpopProofJti>>>,
+    pub(crate) oauth2_request_objects: Arc<std::sync::Mutex<BTreeSet<RequestObjectJti>>>,
 }
 
 pub struct IdmServerProxyWriteTransaction<'a> {
@@ -126,14 +181,25 @@ pub struct IdmServerProxyWriteTransaction<'a> {
     pub qs_write: QueryServerWriteTransaction<'a>,
     /// Associate to an event origin ID, which has a TS and a UUID instead
     pub(crate) cred_update_sessions: BptreeMapWriteTxn<'a, Uuid, CredentialUpdateSessionMutex>,
+    pub(crate) oauth2_client_assertions: Arc<std::sync::Mutex<BTreeSet<ClientAssertionJti>>>,
+    pub(crate) oauth2_dpop_proofs: Arc<std::sync::Mutex<BTreeSet<DpopProofJti>>>,
+    pub(crate) oauth2_request_objects: Arc<std::sync::Mutex<BTreeSet<RequestObjectJti>>>,
+    pub(crate) oauth2_pushed_authorisations: BptreeMapWriteTxn<'a, Uuid, PushedAuthorisation>,
+    pub(crate) oauth2_backchannel_authorisations:
+        BptreeMapWriteTxn<'a, Uuid, BackchannelAuthorisation>,
     pub(crate) sid: Sid,
     crypto_policy: &'a CryptoPolicy,
     webauthn: &'a Webauthn,
+    pub(crate) oauth2_client_ca: Option<&'a X509Store>,
     pub(crate) oauth2rs: Oauth2ResourceServersWriteTransaction<'a>,
     pub(crate) applications: LdapApplicationsWriteTransaction<'a>,
 
     pub(crate) origin: &'a Url,
     pub(crate) oauth2_client_providers: HashMapWriteTxn<'a, Uuid, OAuth2ClientProvider>,
+    async_tx: Sender<DelayedAction>,
+    audit_tx: Sender<AuditEvent>,
+    // Audit events for changes made in this transaction, sent once it commits.
+    audit_events: Vec<AuditEvent>,
 }
 
 pub struct IdmServerDelayed {
@@ -149,6 +215,8 @@ impl IdmServer {
         qs: QueryServer,
         origin: &Url,
         is_integration_test: bool,
+        breached_passwords: Option<BreachedPasswordIndex>,
+        oauth2_client_ca: Option<X509Store>,
         current_time: Duration,
     ) -> Result<(IdmServer, IdmServerDelayed, IdmServerAudit), OperationError> {
         let crypto_policy = if cfg!(test) || is_integration_test {
@@ -218,8 +286,15 @@ impl IdmServer {
             sessions: BptreeMap::new(),
             softlocks: HashMap::new(),
             cred_update_sessions: BptreeMap::new(),
+            oauth2_client_assertions: Arc::new(std::sync::Mutex::new(BTreeSet::new())),
+            oauth2_dpop_proofs: Arc::new(std::sync::Mutex::new(BTreeSet::new())),
+            oauth2_request_objects: Arc::new(std::sync::Mutex::new(BTreeSet::new())),
+            oauth2_pushed_authorisations: BptreeMap::new(),
+            oauth2_backchannel_authorisations: BptreeMap::new(),
             qs,
             crypto_policy,
+            breached_passwords,
+            oauth2_client_ca,
             async_tx,
             audit_tx,
             webauthn,
@@ -279,6 +354,10 @@ impl IdmServer {
         Ok(IdmServerProxyReadTransaction {
             qs_read,
             oauth2rs: self.oauth2rs.read(),
+            oauth2_pushed_authorisations: self.oauth2_pushed_authorisations.read(),
+            oauth2_backchannel_authorisations: self.oauth2_backchannel_authorisations.read(),
+            oauth2_dpop_proofs: self.oauth2_dpop_proofs.clone(),
+            oauth2_request_objects: self.oauth2_request_objects.clone(),
             // async_tx: self.async_tx.clone(),
         })
     }
@@ -296,14 +375,23 @@ impl IdmServer {
 
         Ok(IdmServerProxyWriteTransaction {
             cred_update_sessions: self.cred_update_sessions.write(),
+            oauth2_client_assertions: self.oauth2_client_assertions.clone(),
+            oauth2_dpop_proofs: self.oauth2_dpop_proofs.clone(),
+            oauth2_request_objects: self.oauth2_request_objects.clone(),
+            oauth2_pushed_authorisations: self.oauth2_pushed_authorisations.write(),
+            oauth2_backchannel_authorisations: self.oauth2_backchannel_authorisations.write(),
             qs_write,
             sid,
             crypto_policy: &self.crypto_policy,
             webauthn: &self.webauthn,
+            oauth2_client_ca: self.oauth2_client_ca.as_ref(),
             oauth2rs: self.oauth2rs.write(),
             applications: self.applications.write(),
             origin: &self.origin,
             oauth2_client_providers: self.oauth2_client_providers.write(),
+            async_tx: self.async_tx.clone(),
+            audit_tx: self.audit_tx.clone(),
+            audit_events: Vec::new(),
         })
     }
 
@@ -317,6 +405,7 @@ impl IdmServer {
             webauthn: &self.webauthn,
             cred_update_sessions: self.cred_update_sessions.read(),
             crypto_policy: &self.crypto_policy,
+            breached_passwords: self.breached_passwords.as_ref(),
         })
     }
 
@@ -421,8 +510,10 @@ pub trait IdmServerTransaction<'a> {
         let ClientAuthInfo {
             source,
             client_cert,
+            oauth2_client_cert: _,
             bearer_token,
             basic_authz: _,
+            dpop_proof: _,
             pre_validated_token: _,
         } = client_auth_info;
 
@@ -880,7 +971,18 @@ pub trait IdmServerTransaction<'a> {
         source: Source,
     ) -> Result<Identity, OperationError> {
         let cert_entry = self.client_cert_info_entry(client_cert_info)?;
+        self.client_cert_entry_to_identity(&cert_entry, ct, source)
+    }
 
+    /// Given an enrolled client certificate entry, discover and validate the account that
+    /// owns the certificate and build its identity.
+    #[instrument(level = "debug", skip_all)]
+    fn client_cert_entry_to_identity(
+        &mut self,
+        cert_entry: &EntrySealedCommitted,
+        ct: Duration,
+        source: Source,
+    ) -> Result<Identity, OperationError> {
         // This is who the certificate belongs to.
         let refers_uuid = cert_entry
             .get_ava_single_refer(Attribute::Refers)
@@ -967,12 +1069,13 @@ pub trait IdmServerTransaction<'a> {
             .ok_or(OperationError::InvalidState)
     }
 
-    fn process_ldap_uuid_to_identity(
+    /// Resolve an account that has authenticated over LDAP, returning its entry and the
+    /// limits granted by its account policy.
+    fn process_ldap_uuid_to_limits(
         &mut self,
         uuid: &Uuid,
         ct: Duration,
-        source: Source,
-    ) -> Result<Identity, OperationError> {
+    ) -> Result<(Arc<EntrySealedCommitted>, Limits), OperationError> {
         let entry = self
             .get_qs_txn()
             .internal_search_uuid(*uuid)
@@ -989,6 +1092,27 @@ pub trait IdmServerTransaction<'a> {
             return Err(OperationError::SessionExpired);
         }
 
+        let mut limits = Limits::default();
+
+        // Update limits from account policy
+        if let Some(max_results) = account_policy.limit_search_max_results() {
+            limits.search_max_results = max_results as usize;
+        }
+        if let Some(max_filter) = account_policy.limit_search_max_filter_test() {
+            limits.search_max_filter_test = max_filter as usize;
+        }
+
+        Ok((entry, limits))
+    }
+
+    fn process_ldap_uuid_to_identity(
+        &mut self,
+        uuid: &Uuid,
+        ct: Duration,
+        source: Source,
+    ) -> Result<Identity, OperationError> {
+        let (entry, limits) = self.process_ldap_uuid_to_limits(uuid, ct)?;
+
         // Good to go
         let anon_entry = if *uuid == UUID_ANONYMOUS {
             // We already have it.
@@ -1006,17 +1130,8 @@ pub trait IdmServerTransaction<'a> {
                 })?
         };
 
-        let mut limits = Limits::default();
         let session_id = Uuid::new_v4();
 
-        // Update limits from account policy
-        if let Some(max_results) = account_policy.limit_search_max_results() {
-            limits.search_max_results = max_results as usize;
-        }
-        if let Some(max_filter) = account_policy.limit_search_max_filter_test() {
-            limits.search_max_filter_test = max_filter as usize;
-        }
-
         // Users via LDAP are always only granted anonymous rights unless
         // they auth with an api-token
         Ok(Identity::new(
@@ -1040,6 +1155,18 @@ pub trait IdmServerTransaction<'a> {
                 self.process_ldap_uuid_to_identity(uuid, ct, source)
             }
             LdapSession::UserAuthToken(uat) => self.process_uat_to_identity(uat, ct, source),
+            LdapSession::ClientCertificate(cert_uuid, _) => {
+                // If the certificate has been removed since the bind, the session is invalid.
+                let cert_entry =
+                    self.get_qs_txn()
+                        .internal_search_uuid(*cert_uuid)
+                        .map_err(|e| {
+                            admin_error!("Failed to validate ldap session -> {:?}", e);
+                            e
+                        })?;
+
+                self.client_cert_entry_to_identity(&cert_entry, ct, source)
+            }
             LdapSession::ApiToken(apit) => {
                 let entry = self
                     .get_qs_txn()
@@ -1054,6 +1181,32 @@ pub trait IdmServerTransaction<'a> {
         }
     }
 
+    /// Resolve the identity of an account that has re-verified its password during an
+    /// LDAP operation, such as a password modify. Unlike a unix bind, which is only
+    /// granted anonymous rights, this acts as the account itself.
+    #[instrument(level = "debug", skip_all)]
+    fn validate_ldap_reauth(
+        &mut self,
+        uuid: &Uuid,
+        source: Source,
+        ct: Duration,
+    ) -> Result<Identity, OperationError> {
+        if *uuid == UUID_ANONYMOUS {
+            security_info!("Anonymous can not re-authenticate");
+            return Err(OperationError::NotAuthenticated);
+        }
+
+        let (entry, limits) = self.process_ldap_uuid_to_limits(uuid, ct)?;
+
+        Ok(Identity::new(
+            IdentType::User(IdentUser { entry }),
+            source,
+            Uuid::new_v4(),
+            AccessScope::ReadWrite,
+            limits,
+        ))
+    }
+
     #[instrument(level = "info", skip_all)]
     fn validate_sync_client_auth_info_to_ident(
         &mut self,
@@ -1250,6 +1403,8 @@ impl IdmServerAuthTransaction<'_> {
                     ct,
                     client_auth_info,
                     oauth2_client_provider,
+                    credential_update_intents: entry
+                        .get_ava_as_intenttokens(Attribute::CredentialUpdateIntentToken),
                 };
 
                 let domain_keys = self.qs_read.get_domain_key_object_handle()?;
@@ -1303,6 +1458,13 @@ impl IdmServerAuthTransaction<'_> {
                         if let Some(slock_ref) = softlock_read.get(&cred_uuid) {
                             let mut slock = slock_ref.lock().await;
 
+                            // Bring in any failures recorded by other servers, or before
+                            // this server was restarted.
+                            if let Some(record) = auth_session.account().softlock_record(&cred_uuid)
+                            {
+                                slock.apply_record(record);
+                            }
+
                             let softlock_expire_odt = auth_session.account().softlock_expire();
 
                             let softlock_expire = softlock_expire_odt
@@ -1350,7 +1512,9 @@ impl IdmServerAuthTransaction<'_> {
 
                 let mut auth_session = auth_session_ref.lock().await;
 
-                let maybe_slock_ref = match auth_session.get_credential_uuid()? {
+                let maybe_cred_uuid = auth_session.get_credential_uuid()?;
+
+                let maybe_slock_ref = match maybe_cred_uuid {
                     Some(cred_uuid) => {
                         let softlock_read = self.softlocks.read();
                         softlock_read.get(&cred_uuid).cloned()
@@ -1358,6 +1522,8 @@ impl IdmServerAuthTransaction<'_> {
                     None => None,
                 };
 
+                let target_uuid = auth_session.account().uuid;
+
                 // From the auth_session, determine if the current account
                 // credential that we are using has become softlocked or not.
                 let mut maybe_slock = if let Some(s) = maybe_slock_ref.as_ref() {
@@ -1394,8 +1560,17 @@ impl IdmServerAuthTransaction<'_> {
                             // if it was a failure, we need to inc the softlock.
                             if let AuthState::Denied(_) = aus {
                                 // Update it.
-                                if let Some(ref mut slock) = maybe_slock {
-                                    slock.record_failure(ct);
+                                if let (Some(slock), Some(cred_uuid)) =
+                                    (maybe_slock.as_mut(), maybe_cred_uuid)
+                                {
+                                    if slock.record_failure(ct) {
+                                        queue_softlock_record(
+                                            &self.async_tx,
+                                            target_uuid,
+                                            cred_uuid,
+                                            slock,
+                                        );
+                                    }
                                 }
                             };
                         })
@@ -1417,7 +1592,7 @@ impl IdmServerAuthTransaction<'_> {
         id: Uuid,
         cleartext: &str,
         ct: Duration,
-    ) -> Result<Option<Account>, OperationError> {
+    ) -> Result<Option<(Account, Option<OffsetDateTime>)>, OperationError> {
         let entry = match self.qs_read.internal_search_uuid(id) {
             Ok(entry) => entry,
             Err(e) => {
@@ -1483,6 +1658,10 @@ impl IdmServerAuthTransaction<'_> {
 
         let mut slock = slock_ref.lock().await;
 
+        if let Some(record) = account.softlock_record(&cred_id) {
+            slock.apply_record(record);
+        }
+
         slock.apply_time_step(ct, softlock_expire);
 
         if !slock.is_valid() {
@@ -1498,11 +1677,23 @@ impl IdmServerAuthTransaction<'_> {
 
         if !valid {
             // Update it.
-            slock.record_failure(ct);
+            if slock.record_failure(ct) {
+                queue_softlock_record(&self.async_tx, id, cred_id, &slock);
+            }
 
             return Ok(None);
         }
 
+        // Passwords that have exceeded their maximum age can't be used until they are changed.
+        let password_expiry = acp
+            .pw_maximum_age()
+            .and_then(|maximum_age| cred.password_expiry(maximum_age));
+
+        if password_expiry.is_some_and(|expiry| expiry <= OffsetDateTime::UNIX_EPOCH + ct) {
+            security_info!("Account unix (or primary) password has expired and must be changed.");
+            return Err(OperationError::PasswordExpired);
+        }
+
         security_info!("Successfully authenticated with unix (or primary) password");
         if password.requires_upgrade() {
             self.async_tx
@@ -1516,7 +1707,7 @@ impl IdmServerAuthTransaction<'_> {
                 })?;
         }
 
-        Ok(Some(account))
+        Ok(Some((account, password_expiry)))
     }
 
     pub async fn auth_unix(
@@ -1527,7 +1718,12 @@ impl IdmServerAuthTransaction<'_> {
         Ok(self
             .auth_with_unix_pass(uae.target, &uae.cleartext, ct)
             .await?
-            .and_then(|acc| acc.to_unixusertoken(ct).ok()))
+            .and_then(|(acc, password_expiry)| {
+                acc.to_unixusertoken(ct).ok().map(|mut token| {
+                    token.password_expiry = Account::password_expiry_warning(password_expiry, ct);
+                    token
+                })
+            }))
     }
 
     pub async fn auth_ldap(
@@ -1569,12 +1765,18 @@ impl IdmServerAuthTransaction<'_> {
                 return Ok(None);
             }
 
-            let auth = self
+            // LDAP has no way to prompt for a password change, so an expired password
+            // is treated the same as an invalid one.
+            let auth = match self
                 .auth_with_unix_pass(lae.target, &lae.cleartext, ct)
-                .await?;
+                .await
+            {
+                Err(OperationError::PasswordExpired) => None,
+                res => res?,
+            };
 
             match auth {
-                Some(account) => {
+                Some((account, _)) => {
                     let session_id = Uuid::new_v4();
                     security_info!(
                         "Starting session {} for {} {}",
@@ -1594,6 +1796,37 @@ impl IdmServerAuthTransaction<'_> {
         }
     }
 
+    pub fn certificate_auth_ldap(
+        &mut self,
+        client_cert_info: &ClientCertInfo,
+        ct: Duration,
+    ) -> Result<LdapBoundToken, OperationError> {
+        let cert_entry = self.client_cert_info_entry(client_cert_info)?;
+        let ident = self.client_cert_entry_to_identity(&cert_entry, ct, Source::Internal)?;
+
+        let entry = ident.get_user_entry().ok_or(OperationError::InvalidState)?;
+
+        let spn = entry
+            .get_ava_single_proto_string(Attribute::Spn)
+            .ok_or_else(|| OperationError::MissingAttribute(Attribute::Spn))?;
+
+        let cert_uuid = cert_entry.get_uuid();
+
+        security_info!(
+            "Certificate {:?} bound to {} {:?}",
+            cert_uuid,
+            spn,
+            entry.get_uuid()
+        );
+
+        Ok(LdapBoundToken {
+            spn,
+            // As with the https interface, the certificate uuid is the session id.
+            session_id: cert_uuid,
+            effective_session: LdapSession::ClientCertificate(cert_uuid, entry.get_uuid()),
+        })
+    }
+
     pub async fn token_auth_ldap(
         &mut self,
         lae: &LdapTokenAuthEvent,
@@ -1639,8 +1872,11 @@ fn gen_password_mod(
     cleartext: &str,
     crypto_policy: &CryptoPolicy,
     timestamp: OffsetDateTime,
+    replaced: Option<&Credential>,
+    pw_history_length: u32,
 ) -> Result<ModifyList<ModifyInvalid>, OperationError> {
-    let new_cred = Credential::new_password_only(crypto_policy, cleartext, timestamp)?;
+    let mut new_cred = Credential::new_password_only(crypto_policy, cleartext, timestamp)?;
+    new_cred.rotate_password_history(replaced, pw_history_length);
     let cred_value = Value::new_credential("unix", new_cred);
     Ok(ModifyList::new_purge_and_set(
         Attribute::UnixPassword,
@@ -1744,6 +1980,88 @@ impl IdmServerProxyReadTransaction<'_> {
 
         account.to_credentialstatus()
     }
+
+    pub fn get_credential_softlock_status(
+        &mut self,
+        csle: &CredentialSoftLockEvent,
+        ct: Duration,
+    ) -> Result<Vec<CredentialSoftLockStatus>, OperationError> {
+        let account = self
+            .qs_read
+            .impersonate_search_ext_uuid(csle.target, &csle.ident)
+            .and_then(|account_entry| {
+                Account::try_from_entry_reduced(&account_entry, &mut self.qs_read)
+            })
+            .map_err(|e| {
+                admin_error!("Failed to search account {:?}", e);
+                e
+            })?;
+
+        Ok(account.to_credential_softlock_status(ct))
+    }
+
+    /// List the persons visible to this identity that have at least one credential that
+    /// is currently soft locked.
+    pub fn list_credential_softlocked(
+        &mut self,
+        ident: &Identity,
+        ct: Duration,
+    ) -> Result<Vec<CredentialSoftLockAccount>, OperationError> {
+        let filter = filter!(f_and!([
+            f_eq(Attribute::Class, EntryClass::Person.into()),
+            f_pres(Attribute::CredentialSoftLock)
+        ]));
+
+        let entries = self
+            .qs_read
+            .impersonate_search_ext(filter.clone(), filter, ident)
+            .inspect_err(|err| {
+                error!(?err, "Failed to search soft locked accounts");
+            })?;
+
+        entries
+            .iter()
+            .map(|entry| Account::try_from_entry_reduced(entry, &mut self.qs_read))
+            .filter_map(|account| match account {
+                Ok(account) => {
+                    let credentials: Vec<_> = account
+                        .to_credential_softlock_status(ct)
+                        .into_iter()
+                        .filter(|status| status.locked)
+                        .collect();
+
+                    (!credentials.is_empty()).then(|| {
+                        Ok(CredentialSoftLockAccount {
+                            uuid: account.uuid,
+                            spn: account.spn().into(),
+                            credentials,
+                        })
+                    })
+                }
+                Err(err) => Some(Err(err)),
+            })
+            .collect()
+    }
+
+    pub fn account_credential_softlock_status(
+        &mut self,
+        name: &str,
+        ct: Duration,
+    ) -> Result<Vec<CredentialSoftLockStatus>, OperationError> {
+        let target = self.qs_read.name_to_uuid(name).inspect_err(|err| {
+            error!(?err, "name to uuid failed");
+        })?;
+
+        let account = self
+            .qs_read
+            .internal_search_uuid(target)
+            .and_then(|account_entry| Account::try_from_entry_ro(&account_entry, &mut self.qs_read))
+            .inspect_err(|err| {
+                error!(?err, "Failed to search account");
+            })?;
+
+        Ok(account.to_credential_softlock_status(ct))
+    }
 }
 
 impl<'a> IdmServerTransaction<'a> for IdmServerProxyWriteTransaction<'a> {
@@ -1905,12 +2223,12 @@ impl IdmServerProxyWriteTransaction<'_> {
         pce: &UnixPasswordChangeEvent,
     ) -> Result<(), OperationError> {
         // Get the account
-        let account = self
+        let (account, account_policy) = self
             .qs_write
             .internal_search_uuid(pce.target)
             .and_then(|account_entry| {
                 // Assert the account is unix and valid.
-                Account::try_from_entry_rw(&account_entry, &mut self.qs_write)
+                Account::try_from_entry_with_policy(&account_entry, &mut self.qs_write)
             })
             .map_err(|e| {
                 admin_error!("Failed to start set unix account password {:?}", e);
@@ -1931,12 +2249,20 @@ impl IdmServerProxyWriteTransaction<'_> {
         }
 
         let timestamp = self.qs_write.get_curtime_odt();
-
-        let modlist = gen_password_mod(pce.cleartext.as_str(), self.crypto_policy, timestamp)
-            .map_err(|e| {
-                admin_error!(?e, "Unable to generate password change modlist");
-                e
-            })?;
+        let pw_history_length = account_policy.pw_history_length();
+        let unix_cred = account.unix_extn().and_then(|extn| extn.ucred());
+
+        let modlist = gen_password_mod(
+            pce.cleartext.as_str(),
+            self.crypto_policy,
+            timestamp,
+            unix_cred,
+            pw_history_length,
+        )
+        .map_err(|e| {
+            admin_error!(?e, "Unable to generate password change modlist");
+            e
+        })?;
         trace!(?modlist, "processing change");
 
         // Check with the QS if we would be ALLOWED to do this change.
@@ -1974,6 +2300,19 @@ impl IdmServerProxyWriteTransaction<'_> {
                 e
             })?;
 
+        // This is last since verifying each historic password is expensive.
+        if let Some(unix_cred) = unix_cred {
+            if unix_cred
+                .password_recently_used(pce.cleartext.as_str(), pw_history_length)
+                .unwrap_or_default()
+            {
+                security_info!("Password was recently used, rejecting");
+                return Err(OperationError::PasswordQuality(vec![
+                    PasswordFeedback::RecentlyUsed(pw_history_length),
+                ]));
+            }
+        }
+
         // And actually really apply it now.
         self.qs_write.modify_apply(mp).map_err(|e| {
             request_error!(error = ?e);
@@ -2064,6 +2403,86 @@ impl IdmServerProxyWriteTransaction<'_> {
         Ok(())
     }
 
+    pub fn credential_softlock_unlock(
+        &mut self,
+        csle: &CredentialSoftLockEvent,
+    ) -> Result<(), OperationError> {
+        // Soft locks protect the credentials of the account, so releasing them requires the
+        // same access as resetting the primary credential. The lock records themselves are
+        // only ever written by the server.
+        if !csle.ident.is_internal() {
+            let entry = self.qs_write.internal_search_uuid(csle.target)?;
+
+            let effective_perms = self
+                .qs_write
+                .get_accesscontrols()
+                .effective_permission_check(
+                    &csle.ident,
+                    Some(btreeset![Attribute::PrimaryCredential]),
+                    &[entry],
+                )?;
+
+            let can_reset_primary_cred = effective_perms.first().is_some_and(|eperm| {
+                eperm.target == csle.target
+                    && match &eperm.modify_pres {
+                        Access::Deny => false,
+                        Access::Grant => true,
+                        Access::Allow(attrs) => attrs.contains(&Attribute::PrimaryCredential),
+                    }
+            });
+
+            if !can_reset_primary_cred {
+                security_info!(
+                    "{} is not permitted to release the soft locks of {}",
+                    csle.ident,
+                    csle.target
+                );
+                return Err(OperationError::AccessDenied);
+            }
+        }
+
+        let account = self.target_to_account(csle.target)?;
+
+        let modlist = account.gen_softlock_unlock_mod(self.qs_write.get_curtime());
+
+        if modlist.is_empty() {
+            info!("Account has no credentials to unlock");
+            return Ok(());
+        }
+
+        trace!(?modlist, "processing change");
+
+        self.qs_write
+            .internal_modify(
+                &filter!(f_eq(Attribute::Uuid, PartialValue::Uuid(csle.target))),
+                &modlist,
+            )
+            .inspect_err(|err| {
+                error!(?err, "Failed to unlock credentials");
+            })?;
+
+        // Manual unlocks bypass the protection soft locks provide, so they are always audited.
+        self.audit_events
+            .push(AuditEvent::CredentialSoftLockUnlock {
+                source: csle.ident.source().clone().into(),
+                actor: csle.ident.get_uuid(),
+                uuid: account.uuid,
+                spn: account.spn().into(),
+                time: self.qs_write.get_curtime_odt(),
+            });
+
+        Ok(())
+    }
+
+    pub fn unlock_account_credentials(&mut self, name: &str) -> Result<(), OperationError> {
+        // name to uuid
+        let target = self.qs_write.name_to_uuid(name).inspect_err(|err| {
+            error!(?err, "name to uuid failed");
+        })?;
+
+        self.credential_softlock_unlock(&CredentialSoftLockEvent::new_internal(target))
+    }
+
     #[instrument(level = "debug", skip_all)]
     pub fn regenerate_radius_secret(
         &mut self,
@@ -2268,6 +2687,58 @@ impl IdmServerProxyWriteTransaction<'_> {
         // Done!
     }
 
+    #[instrument(level = "debug", skip_all)]
+    pub(crate) fn process_credentialupdateintentrecord(
+        &mut self,
+        cuir: &CredentialUpdateIntentRecord,
+    ) -> Result<(), OperationError> {
+        info!(target_uuid = %cuir.target_uuid, "Persisting credential update intent");
+
+        let modlist = ModifyList::new_append(
+            Attribute::CredentialUpdateIntentToken,
+            Value::IntentToken(
+                cuir.intent_id.clone(),
+                IntentTokenState::Valid {
+                    max_ttl: cuir.max_ttl,
+                    perms: cuir.perms,
+                },
+            ),
+        );
+
+        self.qs_write
+            .internal_modify(
+                &filter!(f_eq(Attribute::Uuid, PartialValue::Uuid(cuir.target_uuid))),
+                &modlist,
+            )
+            .map_err(|e| {
+                admin_error!("Failed to persist credential update intent {:?}", e);
+                e
+            })
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    pub(crate) fn process_credentialsoftlockrecord(
+        &mut self,
+        cslr: &CredentialSoftLockRecord,
+    ) -> Result<(), OperationError> {
+        info!(target_uuid = %cslr.target_uuid, cred_uuid = %cslr.cred_uuid, "Persisting credential soft lock");
+
+        let modlist = ModifyList::new_append(
+            Attribute::CredentialSoftLock,
+            Value::CredentialSoftLock(cslr.cred_uuid, cslr.record),
+        );
+
+        self.qs_write
+            .internal_modify(
+                &filter!(f_eq(Attribute::Uuid, PartialValue::Uuid(cslr.target_uuid))),
+                &modlist,
+            )
+            .map_err(|e| {
+                admin_error!("Failed to persist credential soft lock {:?}", e);
+                e
+            })
+    }
+
     #[instrument(level = "debug", skip_all)]
     pub fn process_delayedaction(
         &mut self,
@@ -2280,6 +2751,17 @@ impl IdmServerProxyWriteTransaction<'_> {
             DelayedAction::WebauthnCounterIncrement(wci) => self.process_webauthncounterinc(wci),
             DelayedAction::BackupCodeRemoval(bcr) => self.process_backupcoderemoval(bcr),
             DelayedAction::AuthSessionRecord(asr) => self.process_authsessionrecord(asr),
+            DelayedAction::CredentialUpdateIntentRecord(cuir) => {
+                self.process_credentialupdateintentrecord(cuir)
+            }
+            DelayedAction::CredentialSoftLockRecord(cslr) => {
+                self.process_credentialsoftlockrecord(cslr)
+            }
+            DelayedAction::Oauth2BackchannelLogout(_) => {
+                // These are delivered to the client by the server core, and have
+                // nothing to write.
+                Ok(())
+            }
         }
     }
 
@@ -2320,14 +2802,42 @@ impl IdmServerProxyWriteTransaction<'_> {
             self.reload_oauth2_client_providers()?;
         }
 
+        // Any oauth2 sessions that were terminated need their clients to be told. We
+        // sign these now, but they are only sent once the commit succeeds.
+        let revoked_oauth2_sessions = self.qs_write.take_revoked_oauth2_sessions();
+        let backchannel_logouts = self
+            .oauth2rs
+            .backchannel_logout_tokens(&revoked_oauth2_sessions, self.qs_write.get_curtime());
+
         // Commit everything.
         self.applications.commit();
         self.oauth2rs.commit();
         self.cred_update_sessions.commit();
+        self.oauth2_pushed_authorisations.commit();
+        self.oauth2_backchannel_authorisations.commit();
         self.oauth2_client_providers.commit();
 
         trace!("cred_update_session.commit");
-        self.qs_write.commit()
+        self.qs_write.commit()?;
+
+        for backchannel_logout in backchannel_logouts {
+            if self
+                .async_tx
+                .send(DelayedAction::Oauth2BackchannelLogout(backchannel_logout))
+                .is_err()
+            {
+                admin_error!("failed to queue delayed action - oauth2 backchannel logout");
+            }
+        }
+
+        // Only audit changes that were actually committed.
+        for audit_event in self.audit_events {
+            if self.audit_tx.send(audit_event).is_err() {
+                error!("Unable to submit audit event to queue");
+            }
+        }
+
+        Ok(())
     }
 }
 
@@ -2344,7 +2854,7 @@ mod tests {
     use crate::idm::audit::AuditEvent;
     use crate::idm::authentication::AuthState;
     use crate::idm::delayed::{AuthSessionRecord, DelayedAction};
-    use crate::idm::event::{AuthEvent, AuthResult};
+    use crate::idm::event::{AuthEvent, AuthResult, CredentialSoftLockEvent};
     use crate::idm::event::{
         LdapAuthEvent, PasswordChangeEvent, RadiusAuthTokenEvent, RegenerateRadiusSecretEvent,
         UnixGroupTokenEvent, UnixPasswordChangeEvent, UnixUserAuthEvent, UnixUserTokenEvent,
@@ -2353,9 +2863,11 @@ mod tests {
     use crate::modify::{Modify, ModifyList};
     use crate::prelude::*;
     use crate::server::keys::KeyProvidersTransaction;
+    use crate::utils::password_from_random;
     use crate::value::{AuthType, SessionState};
     use compact_jwt::{traits::JwsVerifiable, JwsCompact, JwsEs256Verifier, JwsVerifier};
     use kanidm_lib_crypto::CryptoPolicy;
+    use kanidm_proto::internal::PasswordFeedback;
     use kanidm_proto::v1::{AuthAllowed, AuthIssueSession, AuthMech};
     use time::OffsetDateTime;
     use uuid::Uuid;
@@ -2704,7 +3216,7 @@ mod tests {
     #[idm_test(audit = 1)]
     async fn test_idm_simple_password_invalid(
         idms: &IdmServer,
-        _idms_delayed: &IdmServerDelayed,
+        idms_delayed: &mut IdmServerDelayed,
         idms_audit: &mut IdmServerAudit,
     ) {
         init_testperson_w_password(idms, TEST_PASSWORD)
@@ -2755,6 +3267,10 @@ mod tests {
         }
 
         idms_auth.commit().expect("Must not fail");
+
+        // And the failure is queued to be persisted.
+        let da = idms_delayed.try_recv().expect("invalid");
+        assert!(matches!(da, DelayedAction::CredentialSoftLockRecord(_)));
     }
 
     #[idm_test]
@@ -2923,7 +3439,7 @@ mod tests {
     #[idm_test]
     async fn test_idm_simple_unix_password_reset(
         idms: &IdmServer,
-        _idms_delayed: &IdmServerDelayed,
+        idms_delayed: &mut IdmServerDelayed,
     ) {
         let mut idms_prox_write = idms.proxy_write(duration_from_epoch_now()).await.unwrap();
         // make the admin a valid posix account
@@ -2963,6 +3479,10 @@ mod tests {
         };
         assert!(idms_auth.commit().is_ok());
 
+        // The failure is queued to be persisted.
+        let da = idms_delayed.try_recv().expect("invalid");
+        assert!(matches!(da, DelayedAction::CredentialSoftLockRecord(_)));
+
         // Check deleting the password
         let mut idms_prox_write = idms.proxy_write(duration_from_epoch_now()).await.unwrap();
         let me_purge_up = ModifyEvent::new_internal_invalid(
@@ -2986,22 +3506,77 @@ mod tests {
     }
 
     #[idm_test]
-    async fn test_idm_simple_password_upgrade(
+    async fn test_idm_unix_password_history(
         idms: &IdmServer,
-        idms_delayed: &mut IdmServerDelayed,
+        _idms_delayed: &mut IdmServerDelayed,
     ) {
-        let ct = duration_from_epoch_now();
-        // Assert the delayed action queue is empty
-        idms_delayed.check_is_empty_or_panic();
-        // Setup the admin w_ an imported password.
-        {
-            let mut idms_prox_write = idms.proxy_write(ct).await.unwrap();
-            // now modify and provide a primary credential.
-
-            idms_prox_write
-                .qs_write
-                .internal_create(vec![E_TESTPERSON_1.clone()])
-                .expect("Failed to create test person");
+        let mut idms_prox_write = idms.proxy_write(duration_from_epoch_now()).await.unwrap();
+        // make the admin a valid posix account
+        let me_posix = ModifyEvent::new_internal_invalid(
+            filter!(f_eq(Attribute::Name, PartialValue::new_iname("admin"))),
+            ModifyList::new_list(vec![
+                Modify::Present(Attribute::Class, EntryClass::PosixAccount.into()),
+                Modify::Present(Attribute::GidNumber, Value::new_uint32(2001)),
+            ]),
+        );
+        assert!(idms_prox_write.qs_write.modify(&me_posix).is_ok());
+
+        // Excessive history lengths are capped.
+        let modlist = ModifyList::new_purge_and_set(
+            Attribute::AuthPasswordHistoryLength,
+            Value::Uint32(PW_HISTORY_LENGTH_MAX + 1),
+        );
+        idms_prox_write
+            .qs_write
+            .internal_modify_uuid(UUID_IDM_ALL_ACCOUNTS, &modlist)
+            .expect("Unable to change password history length");
+        assert!(idms_prox_write.commit().is_ok());
+
+        let pw_a = password_from_random();
+        let pw_b = password_from_random();
+
+        let mut idms_prox_write = idms.proxy_write(duration_from_epoch_now()).await.unwrap();
+        for pw in [&pw_a, &pw_b] {
+            let pce = UnixPasswordChangeEvent::new_internal(UUID_ADMIN, pw);
+            assert!(idms_prox_write.set_unix_account_password(&pce).is_ok());
+        }
+        assert!(idms_prox_write.commit().is_ok());
+
+        // Neither the current or prior password can be used again.
+        let mut idms_prox_write = idms.proxy_write(duration_from_epoch_now()).await.unwrap();
+        for pw in [&pw_a, &pw_b] {
+            let pce = UnixPasswordChangeEvent::new_internal(UUID_ADMIN, pw);
+            let err = idms_prox_write.set_unix_account_password(&pce).unwrap_err();
+            assert_eq!(
+                err,
+                OperationError::PasswordQuality(vec![PasswordFeedback::RecentlyUsed(
+                    PW_HISTORY_LENGTH_MAX
+                )])
+            );
+        }
+
+        let pce = UnixPasswordChangeEvent::new_internal(UUID_ADMIN, TEST_PASSWORD);
+        assert!(idms_prox_write.set_unix_account_password(&pce).is_ok());
+        assert!(idms_prox_write.commit().is_ok());
+    }
+
+    #[idm_test]
+    async fn test_idm_simple_password_upgrade(
+        idms: &IdmServer,
+        idms_delayed: &mut IdmServerDelayed,
+    ) {
+        let ct = duration_from_epoch_now();
+        // Assert the delayed action queue is empty
+        idms_delayed.check_is_empty_or_panic();
+        // Setup the admin w_ an imported password.
+        {
+            let mut idms_prox_write = idms.proxy_write(ct).await.unwrap();
+            // now modify and provide a primary credential.
+
+            idms_prox_write
+                .qs_write
+                .internal_create(vec![E_TESTPERSON_1.clone()])
+                .expect("Failed to create test person");
 
             let me_inv_m =
                 ModifyEvent::new_internal_invalid(
@@ -3281,6 +3856,195 @@ mod tests {
         assert!(!tok_r.valid);
     }
 
+    #[idm_test]
+    async fn test_idm_password_maximum_age(idms: &IdmServer, idms_delayed: &mut IdmServerDelayed) {
+        let ct = duration_from_epoch_now();
+
+        init_testperson_w_password(idms, TEST_PASSWORD)
+            .await
+            .expect("Failed to setup admin account");
+
+        // The test credential was set at the epoch, so this expires it in 30 days.
+        let maximum_age = ct + Duration::from_secs(86400 * 30);
+        let password_expiry =
+            OffsetDateTime::UNIX_EPOCH + Duration::from_secs(maximum_age.as_secs());
+
+        let mut idms_prox_write = idms.proxy_write(ct).await.unwrap();
+        idms_prox_write
+            .qs_write
+            .internal_modify_uuid(
+                UUID_IDM_ALL_ACCOUNTS,
+                &ModifyList::new_purge_and_set(
+                    Attribute::AuthPasswordMaximumAge,
+                    Value::Uint32(maximum_age.as_secs() as u32),
+                ),
+            )
+            .expect("Unable to change password maximum age");
+        assert!(idms_prox_write.commit().is_ok());
+
+        // Outside of the warning period, no expiry is hinted.
+        let token = check_testperson_password(idms, TEST_PASSWORD, ct).await;
+        let Token::UserAuthToken(uat) = idms
+            .proxy_read()
+            .await
+            .unwrap()
+            .validate_and_parse_token_to_identity_token(&token, ct)
+            .expect("Must not fail")
+        else {
+            panic!("Unexpected auth token type");
+        };
+        assert!(uat.password_expiry.is_none());
+
+        // Within the warning period the expiry is hinted.
+        let ct_warn = ct + Duration::from_secs(86400 * 20);
+        let token = check_testperson_password(idms, TEST_PASSWORD, ct_warn).await;
+        let Token::UserAuthToken(uat) = idms
+            .proxy_read()
+            .await
+            .unwrap()
+            .validate_and_parse_token_to_identity_token(&token, ct_warn)
+            .expect("Must not fail")
+        else {
+            panic!("Unexpected auth token type");
+        };
+        assert_eq!(uat.password_expiry, Some(password_expiry));
+
+        let da = idms_delayed.try_recv().expect("invalid");
+        assert!(matches!(da, DelayedAction::AuthSessionRecord(_)));
+        let da = idms_delayed.try_recv().expect("invalid");
+        assert!(matches!(da, DelayedAction::AuthSessionRecord(_)));
+        idms_delayed.check_is_empty_or_panic();
+
+        // Once expired, only a credential update intent is issued.
+        let ct_expired = ct + Duration::from_secs(86400 * 31);
+        let sid = init_authsession_sid(idms, ct_expired, "testperson1").await;
+
+        let mut idms_auth = idms.auth().await.unwrap();
+        let pw_step = AuthEvent::cred_step_password(sid, TEST_PASSWORD);
+        let AuthResult { state, .. } = idms_auth
+            .auth(&pw_step, ct_expired, Source::Internal.into())
+            .await
+            .expect("Failed to step auth session");
+        idms_auth.commit().expect("Must not fail");
+
+        let AuthState::CredentialUpdateRequired(intent) = state else {
+            panic!("Expected a credential update intent");
+        };
+
+        let da = idms_delayed.try_recv().expect("invalid");
+        assert!(matches!(
+            da,
+            DelayedAction::CredentialUpdateIntentRecord(ref cuir) if cuir.target_uuid == UUID_TESTPERSON_1
+        ));
+        idms_delayed.check_is_empty_or_panic();
+
+        let mut idms_prox_write = idms.proxy_write(ct_expired).await.unwrap();
+        assert!(idms_prox_write
+            .process_delayedaction(&da, ct_expired)
+            .is_ok());
+        assert!(idms_prox_write.commit().is_ok());
+
+        // Authenticating again returns the existing intent rather than issuing another.
+        let sid = init_authsession_sid(idms, ct_expired, "testperson1").await;
+
+        let mut idms_auth = idms.auth().await.unwrap();
+        let pw_step = AuthEvent::cred_step_password(sid, TEST_PASSWORD);
+        let AuthResult { state, .. } = idms_auth
+            .auth(&pw_step, ct_expired, Source::Internal.into())
+            .await
+            .expect("Failed to step auth session");
+        idms_auth.commit().expect("Must not fail");
+
+        let AuthState::CredentialUpdateRequired(reissued_intent) = state else {
+            panic!("Expected a credential update intent");
+        };
+        assert_eq!(reissued_intent.intent_id, intent.intent_id);
+        idms_delayed.check_is_empty_or_panic();
+
+        // The intent can be used to update the password.
+        let mut idms_prox_write = idms.proxy_write(ct_expired).await.unwrap();
+        assert!(idms_prox_write
+            .exchange_intent_credential_update(intent.into(), ct_expired)
+            .is_ok());
+        assert!(idms_prox_write.commit().is_ok());
+    }
+
+    #[idm_test]
+    async fn test_idm_unix_password_maximum_age(
+        idms: &IdmServer,
+        idms_delayed: &mut IdmServerDelayed,
+    ) {
+        let ct = duration_from_epoch_now();
+
+        init_testperson_w_password(idms, TEST_PASSWORD)
+            .await
+            .expect("Failed to setup admin account");
+
+        let mut idms_prox_write = idms.proxy_write(ct).await.unwrap();
+        let me_posix = ModifyEvent::new_internal_invalid(
+            filter!(f_eq(Attribute::Uuid, PartialValue::Uuid(UUID_TESTPERSON_1))),
+            ModifyList::new_list(vec![
+                Modify::Present(Attribute::Class, EntryClass::PosixAccount.into()),
+                Modify::Present(Attribute::GidNumber, Value::new_uint32(2001)),
+            ]),
+        );
+        assert!(idms_prox_write.qs_write.modify(&me_posix).is_ok());
+
+        let pce = UnixPasswordChangeEvent::new_internal(UUID_TESTPERSON_1, TEST_PASSWORD);
+        assert!(idms_prox_write.set_unix_account_password(&pce).is_ok());
+
+        idms_prox_write
+            .qs_write
+            .internal_modify_uuid(
+                UUID_IDM_ALL_ACCOUNTS,
+                &ModifyList::new_purge_and_set(
+                    Attribute::AuthPasswordMaximumAge,
+                    Value::Uint32(86400 * 30),
+                ),
+            )
+            .expect("Unable to change password maximum age");
+        assert!(idms_prox_write.commit().is_ok());
+
+        let uuae = UnixUserAuthEvent::new_internal(UUID_TESTPERSON_1, TEST_PASSWORD);
+
+        // Outside of the warning period, no expiry is hinted.
+        let mut idms_auth = idms.auth().await.unwrap();
+        let token = idms_auth
+            .auth_unix(&uuae, ct)
+            .await
+            .expect("Failed to auth unix")
+            .expect("Unix auth was denied");
+        assert!(token.password_expiry.is_none());
+
+        // Within the warning period the expiry is hinted.
+        let ct_warn = ct + Duration::from_secs(86400 * 20);
+        let token = idms_auth
+            .auth_unix(&uuae, ct_warn)
+            .await
+            .expect("Failed to auth unix")
+            .expect("Unix auth was denied");
+        assert!(token.password_expiry.is_some());
+
+        // Once expired, the password is denied with an error that can be shown to the user.
+        let ct_expired = ct + Duration::from_secs(86400 * 31);
+        let result = idms_auth.auth_unix(&uuae, ct_expired).await;
+        assert!(matches!(result, Err(OperationError::PasswordExpired)));
+
+        // An incorrect password must not reveal that the password has expired.
+        let uuae_bad = UnixUserAuthEvent::new_internal(UUID_TESTPERSON_1, "bad password");
+        let result = idms_auth
+            .auth_unix(&uuae_bad, ct_expired)
+            .await
+            .expect("Failed to auth unix");
+        assert!(result.is_none());
+
+        let da = idms_delayed.try_recv().expect("invalid");
+        assert!(matches!(da, DelayedAction::CredentialSoftLockRecord(_)));
+        idms_delayed.check_is_empty_or_panic();
+
+        idms_auth.commit().expect("Must not fail");
+    }
+
     #[idm_test]
     async fn test_idm_radius_valid_from_expire(
         idms: &IdmServer,
@@ -3382,6 +4146,10 @@ mod tests {
 
         idms_auth.commit().expect("Must not fail");
 
+        // The failure is queued to be persisted.
+        let da = idms_delayed.try_recv().expect("invalid");
+        assert!(matches!(da, DelayedAction::CredentialSoftLockRecord(_)));
+
         // Auth init, softlock present, count == 1, same time (so before unlock_at)
         // aka Auth valid immediate, (ct < exp), autofail
         // aka Auth invalid immediate, (ct < exp), autofail
@@ -3492,7 +4260,7 @@ mod tests {
     #[idm_test(audit = 1)]
     async fn test_idm_account_softlocking_interleaved(
         idms: &IdmServer,
-        _idms_delayed: &mut IdmServerDelayed,
+        idms_delayed: &mut IdmServerDelayed,
         idms_audit: &mut IdmServerAudit,
     ) {
         init_testperson_w_password(idms, TEST_PASSWORD)
@@ -3548,6 +4316,9 @@ mod tests {
 
         idms_auth.commit().expect("Must not fail");
 
+        let da = idms_delayed.try_recv().expect("invalid");
+        assert!(matches!(da, DelayedAction::CredentialSoftLockRecord(_)));
+
         // Now check that sid_early is denied due to softlock.
         let mut idms_auth = idms.auth().await.unwrap();
         let anon_step = AuthEvent::cred_step_password(sid_early, TEST_PASSWORD);
@@ -3588,7 +4359,7 @@ mod tests {
     #[idm_test]
     async fn test_idm_account_unix_softlocking(
         idms: &IdmServer,
-        _idms_delayed: &mut IdmServerDelayed,
+        idms_delayed: &mut IdmServerDelayed,
     ) {
         init_testperson_w_password(idms, TEST_PASSWORD)
             .await
@@ -3639,6 +4410,256 @@ mod tests {
         };
 
         assert!(idms_auth.commit().is_ok());
+
+        // Only the initial failure was queued to be persisted.
+        let da = idms_delayed.try_recv().expect("invalid");
+        assert!(matches!(da, DelayedAction::CredentialSoftLockRecord(_)));
+        idms_delayed.check_is_empty_or_panic();
+    }
+
+    async fn check_testperson_softlocked(idms: &IdmServer, ct: Duration) -> bool {
+        let mut idms_auth = idms.auth().await.unwrap();
+        let admin_init = AuthEvent::named_init("testperson1");
+
+        let r1 = idms_auth
+            .auth(&admin_init, ct, Source::Internal.into())
+            .await;
+        let AuthResult { sessionid, state } = r1.unwrap();
+        assert!(matches!(state, AuthState::Choose(_)));
+
+        let admin_begin = AuthEvent::begin_mech(sessionid, AuthMech::Password);
+
+        let r2 = idms_auth
+            .auth(&admin_begin, ct, Source::Internal.into())
+            .await;
+        let AuthResult {
+            sessionid: _,
+            state,
+        } = r2.unwrap();
+
+        idms_auth.commit().expect("Must not fail");
+
+        match state {
+            AuthState::Denied(reason) => {
+                assert_eq!(reason, "Account is temporarily locked");
+                true
+            }
+            AuthState::Continue(_) => false,
+            _ => panic!("Unexpected auth state"),
+        }
+    }
+
+    #[idm_test(audit = 1)]
+    async fn test_idm_account_softlock_persisted(
+        idms: &IdmServer,
+        idms_delayed: &mut IdmServerDelayed,
+        idms_audit: &mut IdmServerAudit,
+    ) {
+        let ct = Duration::from_secs(TEST_CURRENT_TIME);
+
+        init_testperson_w_password(idms, TEST_PASSWORD)
+            .await
+            .expect("Failed to setup admin account");
+
+        let sid = init_authsession_sid(idms, ct, "testperson1").await;
+        let mut idms_auth = idms.auth().await.unwrap();
+        let anon_step = AuthEvent::cred_step_password(sid, TEST_PASSWORD_INC);
+
+        let r2 = idms_auth
+            .auth(&anon_step, ct, Source::Internal.into())
+            .await;
+        assert!(matches!(
+            r2,
+            Ok(AuthResult {
+                state: AuthState::Denied(_),
+                ..
+            })
+        ));
+        idms_auth.commit().expect("Must not fail");
+
+        match idms_audit.audit_rx().try_recv() {
+            Ok(AuditEvent::AuthenticationDenied { .. }) => {}
+            _ => panic!("Oh no"),
+        }
+
+        // Persist the failure to the account.
+        let da = idms_delayed.try_recv().expect("invalid");
+        assert!(matches!(da, DelayedAction::CredentialSoftLockRecord(_)));
+        let r = idms.delayed_action(ct, da).await;
+        assert_eq!(Ok(true), r);
+        idms_delayed.check_is_empty_or_panic();
+
+        let mut idms_prox_read = idms.proxy_read().await.unwrap();
+        let status = idms_prox_read
+            .account_credential_softlock_status("testperson1", ct)
+            .expect("Failed to get soft lock status");
+        drop(idms_prox_read);
+
+        assert_eq!(status.len(), 1);
+        assert_eq!(status[0].failures, 1);
+        assert!(status[0].locked);
+
+        // The account is listed as locked to people administrators.
+        let mut idms_prox_read = idms.proxy_read().await.unwrap();
+        let admin_ident = idms_prox_read
+            .qs_read
+            .internal_search_uuid(UUID_IDM_ADMIN)
+            .map(Identity::from_impersonate_entry_readwrite)
+            .expect("Failed to get idm_admin");
+        let locked = idms_prox_read
+            .list_credential_softlocked(&admin_ident, ct)
+            .expect("Failed to list soft locked accounts");
+        drop(idms_prox_read);
+
+        assert_eq!(locked.len(), 1);
+        assert_eq!(locked[0].uuid, UUID_TESTPERSON_1);
+        assert_eq!(locked[0].credentials, status);
+
+        // Forget the in memory lock, as though this server had restarted or
+        // the authentication was made to another replica.
+        {
+            let mut softlock_write = idms.softlocks.write();
+            softlock_write.remove(&status[0].cred_id);
+            softlock_write.commit();
+        }
+
+        // The persisted record is adopted, so the account remains locked.
+        assert!(check_testperson_softlocked(idms, ct).await);
+
+        // Unlocking the account releases the lock before it would expire.
+        let ct = ct + Duration::from_millis(100);
+        let mut idms_prox_write = idms.proxy_write(ct).await.unwrap();
+        idms_prox_write
+            .unlock_account_credentials("testperson1")
+            .expect("Failed to unlock account");
+
+        // Nothing is audited until the unlock is committed.
+        assert!(idms_audit.audit_rx().try_recv().is_err());
+        idms_prox_write.commit().expect("Must not fail");
+
+        // The unlock is audited.
+        match idms_audit.audit_rx().try_recv() {
+            Ok(AuditEvent::CredentialSoftLockUnlock { uuid, .. }) => {
+                assert_eq!(uuid, UUID_TESTPERSON_1)
+            }
+            _ => panic!("Oh no"),
+        }
+
+        let mut idms_prox_read = idms.proxy_read().await.unwrap();
+        let status = idms_prox_read
+            .account_credential_softlock_status("testperson1", ct)
+            .expect("Failed to get soft lock status");
+        let locked = idms_prox_read
+            .list_credential_softlocked(&admin_ident, ct)
+            .expect("Failed to list soft locked accounts");
+        drop(idms_prox_read);
+        assert!(status.is_empty());
+        assert!(locked.is_empty());
+
+        let ct = ct + Duration::from_millis(100);
+        assert!(!check_testperson_softlocked(idms, ct).await);
+    }
+
+    #[idm_test(audit = 1)]
+    async fn test_idm_account_softlock_unlock_access(
+        idms: &IdmServer,
+        _idms_delayed: &mut IdmServerDelayed,
+        idms_audit: &mut IdmServerAudit,
+    ) {
+        let ct = Duration::from_secs(TEST_CURRENT_TIME);
+
+        init_testperson_w_password(idms, TEST_PASSWORD)
+            .await
+            .expect("Failed to setup admin account");
+
+        let mut idms_prox_write = idms.proxy_write(ct).await.unwrap();
+
+        idms_prox_write
+            .qs_write
+            .internal_create(vec![E_TESTPERSON_2.clone()])
+            .expect("Failed to create test person");
+
+        // Another person may not release the locks of this account.
+        let other_ident = idms_prox_write
+            .qs_write
+            .internal_search_uuid(UUID_TESTPERSON_2)
+            .map(Identity::from_impersonate_entry_readwrite)
+            .expect("Failed to get testperson2");
+
+        let csle = CredentialSoftLockEvent {
+            ident: other_ident,
+            target: UUID_TESTPERSON_1,
+        };
+        assert_eq!(
+            idms_prox_write.credential_softlock_unlock(&csle),
+            Err(OperationError::AccessDenied)
+        );
+        // A denied unlock is not audited.
+        assert!(idms_audit.audit_rx().try_recv().is_err());
+
+        // Nor may they modify the lock records directly.
+        let me_inv_m = ModifyEvent::new_impersonate_entry(
+            idms_prox_write
+                .qs_write
+                .internal_search_uuid(UUID_TESTPERSON_1)
+                .expect("Failed to get testperson1"),
+            filter!(f_eq(Attribute::Uuid, PartialValue::Uuid(UUID_TESTPERSON_1))),
+            ModifyList::new_purge(Attribute::CredentialSoftLock),
+        );
+        assert_eq!(
+            idms_prox_write.qs_write.modify(&me_inv_m),
+            Err(OperationError::AccessDenied)
+        );
+
+        // A person may release the locks of their own account.
+        let self_ident = idms_prox_write
+            .qs_write
+            .internal_search_uuid(UUID_TESTPERSON_1)
+            .map(Identity::from_impersonate_entry_readwrite)
+            .expect("Failed to get testperson1");
+
+        let csle = CredentialSoftLockEvent {
+            ident: self_ident,
+            target: UUID_TESTPERSON_1,
+        };
+        idms_prox_write
+            .credential_softlock_unlock(&csle)
+            .expect("Failed to unlock account");
+
+        // As may people administrators.
+        let admin_ident = idms_prox_write
+            .qs_write
+            .internal_search_uuid(UUID_IDM_ADMIN)
+            .map(Identity::from_impersonate_entry_readwrite)
+            .expect("Failed to get idm_admin");
+
+        let csle = CredentialSoftLockEvent {
+            ident: admin_ident,
+            target: UUID_TESTPERSON_1,
+        };
+        idms_prox_write
+            .credential_softlock_unlock(&csle)
+            .expect("Failed to unlock account");
+
+        // The unlocks are only audited once they are committed.
+        assert!(idms_audit.audit_rx().try_recv().is_err());
+        idms_prox_write.commit().expect("Must not fail");
+
+        match idms_audit.audit_rx().try_recv() {
+            Ok(AuditEvent::CredentialSoftLockUnlock { uuid, actor, .. }) => {
+                assert_eq!(uuid, UUID_TESTPERSON_1);
+                assert_eq!(actor, Some(UUID_TESTPERSON_1));
+            }
+            _ => panic!("Oh no"),
+        }
+
+        match idms_audit.audit_rx().try_recv() {
+            Ok(AuditEvent::CredentialSoftLockUnlock { uuid, actor, .. }) => {
+                assert_eq!(uuid, UUID_TESTPERSON_1);
+                assert_eq!(actor, Some(UUID_IDM_ADMIN));
+            }
+            _ => panic!("Oh no"),
+        }
     }
 
     #[idm_test]
@@ -88,7 +88,7 @@ pub mod prelude {
         f_and, f_andnot, f_eq, f_gt, f_id, f_inc, f_invalid, f_lt, f_or, f_pres, f_self,
         f_spn_name, f_sub, Filter, FilterInvalid, FilterValid, FC,
     };
-    pub use crate::idm::authentication::{ClientAuthInfo, ClientCertInfo};
+    pub use crate::idm::authentication::{ClientAuthInfo, ClientCertInfo, DpopProof};
     pub use crate::idm::server::{IdmServer, IdmServerAudit, IdmServerDelayed};
     pub use crate::modify::{
         m_assert, m_pres, m_purge, m_remove, Modify, ModifyInvalid, ModifyList, ModifyValid,
@@ -511,6 +511,8 @@ pub static IDM_ACP_GROUP_ACCOUNT_POLICY_MANAGE_DL8: LazyLock<BuiltinAcp> =
             Attribute::Uuid,
             Attribute::AuthSessionExpiry,
             Attribute::AuthPasswordMinimumLength,
+            Attribute::AuthPasswordHistoryLength,
+            Attribute::AuthPasswordMaximumAge,
             Attribute::CredentialTypeMinimum,
             Attribute::PrivilegeExpiry,
             Attribute::WebauthnAttestationCaList,
@@ -522,6 +524,8 @@ pub static IDM_ACP_GROUP_ACCOUNT_POLICY_MANAGE_DL8: LazyLock<BuiltinAcp> =
             Attribute::Class,
             Attribute::AuthSessionExpiry,
             Attribute::AuthPasswordMinimumLength,
+            Attribute::AuthPasswordHistoryLength,
+            Attribute::AuthPasswordMaximumAge,
             Attribute::CredentialTypeMinimum,
             Attribute::PrivilegeExpiry,
             Attribute::WebauthnAttestationCaList,
@@ -533,6 +537,8 @@ pub static IDM_ACP_GROUP_ACCOUNT_POLICY_MANAGE_DL8: LazyLock<BuiltinAcp> =
             Attribute::Class,
             Attribute::AuthSessionExpiry,
             Attribute::AuthPasswordMinimumLength,
+            Attribute::AuthPasswordHistoryLength,
+            Attribute::AuthPasswordMaximumAge,
             Attribute::CredentialTypeMinimum,
             Attribute::PrivilegeExpiry,
             Attribute::WebauthnAttestationCaList,
@@ -582,7 +588,19 @@ pub static IDM_ACP_OAUTH2_MANAGE: LazyLock<BuiltinAcp> = LazyLock::new(|| Builti
         Attribute::Image,
         Attribute::OAuth2StrictRedirectUri,
         Attribute::OAuth2DeviceFlowEnable,
+        Attribute::OAuth2RsBackchannelLogoutUri,
+        Attribute::OAuth2RsJwks,
+        Attribute::OAuth2RequirePar,
+        Attribute::OAuth2RequireDpop,
+        Attribute::OAuth2RsTlsClientAuthSubjectDn,
+        Attribute::OAuth2RsTlsClientCertificate,
+        Attribute::OAuth2RsRegistrationTokenId,
+        Attribute::OAuth2RsEncryptedResponseAlg,
+        Attribute::OAuth2RsAuthorisationDetailsType,
+        Attribute::OAuth2CibaEnable,
+        Attribute::OAuth2PairwiseSubjectEnable,
         Attribute::KeyInternalData,
+        Attribute::EntryManagedBy,
     ],
     modify_removed_attrs: vec![
         Attribute::Description,
@@ -602,6 +620,17 @@ pub static IDM_ACP_OAUTH2_MANAGE: LazyLock<BuiltinAcp> = LazyLock::new(|| Builti
         Attribute::Image,
         Attribute::OAuth2StrictRedirectUri,
         Attribute::OAuth2DeviceFlowEnable,
+        Attribute::OAuth2RsBackchannelLogoutUri,
+        Attribute::OAuth2RsJwks,
+        Attribute::OAuth2RequirePar,
+        Attribute::OAuth2RequireDpop,
+        Attribute::OAuth2RsTlsClientAuthSubjectDn,
+        Attribute::OAuth2RsTlsClientCertificate,
+        Attribute::OAuth2RsRegistrationTokenId,
+        Attribute::OAuth2RsEncryptedResponseAlg,
+        Attribute::OAuth2RsAuthorisationDetailsType,
+        Attribute::OAuth2CibaEnable,
+        Attribute::OAuth2PairwiseSubjectEnable,
         Attribute::KeyActionRevoke,
         Attribute::KeyActionRotate,
     ],
@@ -621,6 +650,17 @@ pub static IDM_ACP_OAUTH2_MANAGE: LazyLock<BuiltinAcp> = LazyLock::new(|| Builti
         Attribute::Image,
         Attribute::OAuth2StrictRedirectUri,
         Attribute::OAuth2DeviceFlowEnable,
+        Attribute::OAuth2RsBackchannelLogoutUri,
+        Attribute::OAuth2RsJwks,
+        Attribute::OAuth2RequirePar,
+        Attribute::OAuth2RequireDpop,
+        Attribute::OAuth2RsTlsClientAuthSubjectDn,
+        Attribute::OAuth2RsTlsClientCertificate,
+        Attribute::OAuth2RsRegistrationTokenId,
+        Attribute::OAuth2RsEncryptedResponseAlg,
+        Attribute::OAuth2RsAuthorisationDetailsType,
+        Attribute::OAuth2CibaEnable,
+        Attribute::OAuth2PairwiseSubjectEnable,
         Attribute::KeyActionRevoke,
         Attribute::KeyActionRotate,
     ],
@@ -642,6 +682,17 @@ pub static IDM_ACP_OAUTH2_MANAGE: LazyLock<BuiltinAcp> = LazyLock::new(|| Builti
         Attribute::Image,
         Attribute::OAuth2StrictRedirectUri,
         Attribute::OAuth2DeviceFlowEnable,
+        Attribute::OAuth2RsBackchannelLogoutUri,
+        Attribute::OAuth2RsJwks,
+        Attribute::OAuth2RequirePar,
+        Attribute::OAuth2RequireDpop,
+        Attribute::OAuth2RsTlsClientAuthSubjectDn,
+        Attribute::OAuth2RsTlsClientCertificate,
+        Attribute::OAuth2RsRegistrationTokenId,
+        Attribute::OAuth2RsEncryptedResponseAlg,
+        Attribute::OAuth2RsAuthorisationDetailsType,
+        Attribute::OAuth2CibaEnable,
+        Attribute::OAuth2PairwiseSubjectEnable,
     ],
     create_classes: vec![
         EntryClass::Object,
@@ -954,6 +1005,7 @@ pub static IDM_ACP_SELF_READ_DL8: LazyLock<BuiltinAcp> = LazyLock::new(|| Builti
         Attribute::UnixPassword,
         Attribute::LastModifiedCid,
         Attribute::PasswordChangedTime,
+        Attribute::CredentialSoftLock,
     ],
     ..Default::default()
 });
@@ -1508,6 +1560,7 @@ pub static IDM_ACP_PEOPLE_CREDENTIAL_RESET_V1: LazyLock<BuiltinAcp> =
             Attribute::PassKeys,
             Attribute::AttestedPasskeys,
             Attribute::AccountSoftlockExpire,
+            Attribute::CredentialSoftLock,
         ],
         modify_removed_attrs: vec![
             Attribute::PrimaryCredential,
@@ -1558,6 +1611,7 @@ pub static IDM_ACP_HP_PEOPLE_CREDENTIAL_RESET_V1: LazyLock<BuiltinAcp> =
             Attribute::AccountValidFrom,
             Attribute::PassKeys,
             Attribute::AttestedPasskeys,
+            Attribute::CredentialSoftLock,
         ],
         modify_removed_attrs: vec![
             Attribute::PrimaryCredential,
@@ -2112,3 +2166,33 @@ pub static IDM_ACP_OAUTH2_ACCOUNT_ENROL: LazyLock<BuiltinAcp> = LazyLock::new(||
     modify_classes: vec![EntryClass::OAuth2Account],
     ..Default::default()
 });
+
+pub static IDM_ACP_OAUTH2_CLIENT_REGISTRATION_MANAGE: LazyLock<BuiltinAcp> = LazyLock::new(|| {
+    BuiltinAcp {
+        classes: vec![
+            EntryClass::Object,
+            EntryClass::AccessControlProfile,
+            EntryClass::AccessControlModify,
+            EntryClass::AccessControlSearch,
+        ],
+        name: "idm_acp_oauth2_client_registration_manage",
+        uuid: UUID_IDM_ACP_OAUTH2_CLIENT_REGISTRATION_MANAGE,
+        description:
+            "Builtin IDM Control for granting service accounts the right to register oauth2 clients.",
+        receiver: BuiltinAcpReceiver::Group(vec![UUID_IDM_OAUTH2_ADMINS]),
+        target: BuiltinAcpTarget::Filter(ProtoFilter::And(vec![
+            match_class_filter!(EntryClass::ServiceAccount),
+            FILTER_ANDNOT_HP_OR_RECYCLED_OR_TOMBSTONE.clone(),
+        ])),
+        search_attrs: vec![
+            Attribute::Class,
+            Attribute::Name,
+            Attribute::Spn,
+            Attribute::Uuid,
+            Attribute::OAuth2RegistrationScopeMap,
+        ],
+        modify_removed_attrs: vec![Attribute::OAuth2RegistrationScopeMap],
+        modify_present_attrs: vec![Attribute::OAuth2RegistrationScopeMap],
+        ..Default::default()
+    }
+});
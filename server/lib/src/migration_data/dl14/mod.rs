@@ -131,6 +131,25 @@ pub fn phase_1_schema_attrs() -> Vec<EntryInitNew> {
         SCHEMA_ATTR_ACCOUNT_SOFTLOCK_EXPIRE.clone().into(),
         // DL14
         SCHEMA_ATTR_PASSWORD_CHANGED_TIME.clone().into(),
+        SCHEMA_ATTR_OAUTH2_RS_BACKCHANNEL_LOGOUT_URI.clone().into(),
+        SCHEMA_ATTR_OAUTH2_RS_JWKS.clone().into(),
+        SCHEMA_ATTR_OAUTH2_REQUIRE_PAR.clone().into(),
+        SCHEMA_ATTR_OAUTH2_REQUIRE_DPOP.clone().into(),
+        SCHEMA_ATTR_OAUTH2_RS_TLS_CLIENT_AUTH_SUBJECT_DN
+            .clone()
+            .into(),
+        SCHEMA_ATTR_OAUTH2_RS_TLS_CLIENT_CERTIFICATE.clone().into(),
+        SCHEMA_ATTR_OAUTH2_RS_REGISTRATION_TOKEN_ID.clone().into(),
+        SCHEMA_ATTR_OAUTH2_REGISTRATION_SCOPE_MAP.clone().into(),
+        SCHEMA_ATTR_OAUTH2_RS_ENCRYPTED_RESPONSE_ALG.clone().into(),
+        SCHEMA_ATTR_OAUTH2_RS_AUTHORISATION_DETAILS_TYPE
+            .clone()
+            .into(),
+        SCHEMA_ATTR_OAUTH2_CIBA_ENABLE.clone().into(),
+        SCHEMA_ATTR_OAUTH2_PAIRWISE_SUBJECT_ENABLE.clone().into(),
+        SCHEMA_ATTR_AUTH_PASSWORD_HISTORY_LENGTH.clone().into(),
+        SCHEMA_ATTR_AUTH_PASSWORD_MAXIMUM_AGE.clone().into(),
+        SCHEMA_ATTR_CREDENTIAL_SOFTLOCK.clone().into(),
     ]
 }
 
@@ -155,14 +174,14 @@ pub fn phase_2_schema_classes() -> Vec<EntryInitNew> {
         SCHEMA_CLASS_KEY_OBJECT_JWE_A128GCM_DL6.clone().into(),
         SCHEMA_CLASS_KEY_OBJECT_INTERNAL_DL6.clone().into(),
         // DL7
-        SCHEMA_CLASS_SERVICE_ACCOUNT_DL7.clone().into(),
+        SCHEMA_CLASS_SERVICE_ACCOUNT_DL14.clone().into(),
         SCHEMA_CLASS_SYNC_ACCOUNT_DL7.clone().into(),
         SCHEMA_CLASS_CLIENT_CERTIFICATE_DL7.clone().into(),
         // DL8
         SCHEMA_CLASS_ACCOUNT_POLICY_DL8.clone().into(),
         SCHEMA_CLASS_PERSON_DL14.clone().into(),
         // DL9
-        SCHEMA_CLASS_OAUTH2_RS_DL9.clone().into(),
+        SCHEMA_CLASS_OAUTH2_RS_DL14.clone().into(),
         // DL10
         SCHEMA_CLASS_DOMAIN_INFO_DL10.clone().into(),
         SCHEMA_CLASS_KEY_OBJECT_JWT_RS256.clone().into(),
@@ -193,6 +212,7 @@ pub fn phase_4_system_entries() -> Vec<EntryInitNew> {
         e_domain_info_dl6(),
         e_system_config_v1(),
         e_uuid_domain_id_verification_key_v1(),
+        e_uuid_domain_oauth2_pairwise_key_v1(),
         e_hmac_name_history_feature(),
     ]
 }
@@ -320,6 +340,8 @@ pub fn phase_7_builtin_access_control_profiles() -> Vec<EntryInitNew> {
         IDM_ACP_OAUTH2_ACCOUNT_ENROL.clone().into(),
         // DL13
         IDM_ACP_OAUTH2_MANAGE_BASIC.clone().into(),
+        // DL14
+        IDM_ACP_OAUTH2_CLIENT_REGISTRATION_MANAGE.clone().into(),
     ]
 }
 
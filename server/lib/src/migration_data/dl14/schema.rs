@@ -238,6 +238,24 @@ pub static SCHEMA_ATTR_AUTH_PASSWORD_MINIMUM_LENGTH: LazyLock<SchemaAttribute> =
         ..Default::default()
     });
 
+pub static SCHEMA_ATTR_AUTH_PASSWORD_HISTORY_LENGTH: LazyLock<SchemaAttribute> =
+    LazyLock::new(|| SchemaAttribute {
+        uuid: UUID_SCHEMA_ATTR_AUTH_PASSWORD_HISTORY_LENGTH,
+        name: Attribute::AuthPasswordHistoryLength,
+        description: "Number of recent passwords that may not be reused".to_string(),
+        syntax: SyntaxType::Uint32,
+        ..Default::default()
+    });
+
+pub static SCHEMA_ATTR_AUTH_PASSWORD_MAXIMUM_AGE: LazyLock<SchemaAttribute> =
+    LazyLock::new(|| SchemaAttribute {
+        uuid: UUID_SCHEMA_ATTR_AUTH_PASSWORD_MAXIMUM_AGE,
+        name: Attribute::AuthPasswordMaximumAge,
+        description: "Maximum age in seconds of a password before it must be changed".to_string(),
+        syntax: SyntaxType::Uint32,
+        ..Default::default()
+    });
+
 pub static SCHEMA_ATTR_LOGINSHELL: LazyLock<SchemaAttribute> = LazyLock::new(|| SchemaAttribute {
     uuid: UUID_SCHEMA_ATTR_LOGINSHELL,
     name: Attribute::LoginShell,
@@ -519,6 +537,16 @@ pub static SCHEMA_ATTR_CREDENTIAL_UPDATE_INTENT_TOKEN: LazyLock<SchemaAttribute>
         ..Default::default()
     });
 
+pub static SCHEMA_ATTR_CREDENTIAL_SOFTLOCK: LazyLock<SchemaAttribute> =
+    LazyLock::new(|| SchemaAttribute {
+        uuid: UUID_SCHEMA_ATTR_CREDENTIAL_SOFTLOCK,
+        name: Attribute::CredentialSoftLock,
+        description: "The soft lock state of the credentials of an account".to_string(),
+        multivalue: true,
+        syntax: SyntaxType::CredentialSoftLock,
+        ..Default::default()
+    });
+
 pub static SCHEMA_ATTR_PASSKEYS: LazyLock<SchemaAttribute> = LazyLock::new(|| SchemaAttribute {
     uuid: UUID_SCHEMA_ATTR_PASSKEYS,
     name: Attribute::PassKeys,
@@ -1029,6 +1057,127 @@ pub static SCHEMA_ATTR_OAUTH2_CONSENT_PROMPT_ENABLE: LazyLock<SchemaAttribute> =
         ..Default::default()
     });
 
+pub static SCHEMA_ATTR_OAUTH2_RS_BACKCHANNEL_LOGOUT_URI: LazyLock<SchemaAttribute> =
+    LazyLock::new(|| SchemaAttribute {
+        uuid: UUID_SCHEMA_ATTR_OAUTH2_RS_BACKCHANNEL_LOGOUT_URI,
+        name: Attribute::OAuth2RsBackchannelLogoutUri,
+        description:
+            "The URL that logout tokens are sent to when a session of this client is terminated."
+                .to_string(),
+        syntax: SyntaxType::Url,
+        ..Default::default()
+    });
+
+pub static SCHEMA_ATTR_OAUTH2_RS_JWKS: LazyLock<SchemaAttribute> =
+    LazyLock::new(|| SchemaAttribute {
+        uuid: UUID_SCHEMA_ATTR_OAUTH2_RS_JWKS,
+        name: Attribute::OAuth2RsJwks,
+        description: "A JSON web key set of the public keys this client signs assertions with."
+            .to_string(),
+        syntax: SyntaxType::Utf8String,
+        ..Default::default()
+    });
+
+pub static SCHEMA_ATTR_OAUTH2_REQUIRE_PAR: LazyLock<SchemaAttribute> =
+    LazyLock::new(|| SchemaAttribute {
+        uuid: UUID_SCHEMA_ATTR_OAUTH2_REQUIRE_PAR,
+        name: Attribute::OAuth2RequirePar,
+        description: "Require that this client pushes authorisation requests before redirecting."
+            .to_string(),
+        syntax: SyntaxType::Boolean,
+        ..Default::default()
+    });
+
+pub static SCHEMA_ATTR_OAUTH2_REQUIRE_DPOP: LazyLock<SchemaAttribute> =
+    LazyLock::new(|| SchemaAttribute {
+        uuid: UUID_SCHEMA_ATTR_OAUTH2_REQUIRE_DPOP,
+        name: Attribute::OAuth2RequireDpop,
+        description: "Require that tokens issued to this client are bound to a DPoP key."
+            .to_string(),
+        syntax: SyntaxType::Boolean,
+        ..Default::default()
+    });
+
+pub static SCHEMA_ATTR_OAUTH2_RS_TLS_CLIENT_AUTH_SUBJECT_DN: LazyLock<SchemaAttribute> =
+    LazyLock::new(|| SchemaAttribute {
+        uuid: UUID_SCHEMA_ATTR_OAUTH2_RS_TLS_CLIENT_AUTH_SUBJECT_DN,
+        name: Attribute::OAuth2RsTlsClientAuthSubjectDn,
+        description: "The subject DN of the certificate this client may authenticate with."
+            .to_string(),
+        syntax: SyntaxType::Utf8String,
+        ..Default::default()
+    });
+
+pub static SCHEMA_ATTR_OAUTH2_RS_TLS_CLIENT_CERTIFICATE: LazyLock<SchemaAttribute> =
+    LazyLock::new(|| SchemaAttribute {
+        uuid: UUID_SCHEMA_ATTR_OAUTH2_RS_TLS_CLIENT_CERTIFICATE,
+        name: Attribute::OAuth2RsTlsClientCertificate,
+        description: "Self signed certificates this client may authenticate with.".to_string(),
+        multivalue: true,
+        syntax: SyntaxType::Certificate,
+        ..Default::default()
+    });
+
+pub static SCHEMA_ATTR_OAUTH2_RS_REGISTRATION_TOKEN_ID: LazyLock<SchemaAttribute> =
+    LazyLock::new(|| SchemaAttribute {
+        uuid: UUID_SCHEMA_ATTR_OAUTH2_RS_REGISTRATION_TOKEN_ID,
+        name: Attribute::OAuth2RsRegistrationTokenId,
+        description: "The id of the registration access token of a dynamically registered client."
+            .to_string(),
+        syntax: SyntaxType::Uuid,
+        ..Default::default()
+    });
+
+pub static SCHEMA_ATTR_OAUTH2_REGISTRATION_SCOPE_MAP: LazyLock<SchemaAttribute> =
+    LazyLock::new(|| SchemaAttribute {
+        uuid: UUID_SCHEMA_ATTR_OAUTH2_REGISTRATION_SCOPE_MAP,
+        name: Attribute::OAuth2RegistrationScopeMap,
+        description: "The scope maps that clients registered by this account may be granted."
+            .to_string(),
+        multivalue: true,
+        syntax: SyntaxType::OauthScopeMap,
+        ..Default::default()
+    });
+
+pub static SCHEMA_ATTR_OAUTH2_RS_ENCRYPTED_RESPONSE_ALG: LazyLock<SchemaAttribute> =
+    LazyLock::new(|| SchemaAttribute {
+        uuid: UUID_SCHEMA_ATTR_OAUTH2_RS_ENCRYPTED_RESPONSE_ALG,
+        name: Attribute::OAuth2RsEncryptedResponseAlg,
+        description: "The algorithm that ID tokens and userinfo are encrypted to this client with."
+            .to_string(),
+        syntax: SyntaxType::Utf8String,
+        ..Default::default()
+    });
+
+pub static SCHEMA_ATTR_OAUTH2_RS_AUTHORISATION_DETAILS_TYPE: LazyLock<SchemaAttribute> =
+    LazyLock::new(|| SchemaAttribute {
+        uuid: UUID_SCHEMA_ATTR_OAUTH2_RS_AUTHORISATION_DETAILS_TYPE,
+        name: Attribute::OAuth2RsAuthorisationDetailsType,
+        description: "The types of authorisation details that this client may request.".to_string(),
+        multivalue: true,
+        syntax: SyntaxType::Utf8String,
+        ..Default::default()
+    });
+
+pub static SCHEMA_ATTR_OAUTH2_CIBA_ENABLE: LazyLock<SchemaAttribute> =
+    LazyLock::new(|| SchemaAttribute {
+        uuid: UUID_SCHEMA_ATTR_OAUTH2_CIBA_ENABLE,
+        name: Attribute::OAuth2CibaEnable,
+        description: "Allow this client to request that users approve it from another device."
+            .to_string(),
+        syntax: SyntaxType::Boolean,
+        ..Default::default()
+    });
+
+pub static SCHEMA_ATTR_OAUTH2_PAIRWISE_SUBJECT_ENABLE: LazyLock<SchemaAttribute> =
+    LazyLock::new(|| SchemaAttribute {
+        uuid: UUID_SCHEMA_ATTR_OAUTH2_PAIRWISE_SUBJECT_ENABLE,
+        name: Attribute::OAuth2PairwiseSubjectEnable,
+        description: "Issue subject identifiers to this client that are unique to it.".to_string(),
+        syntax: SyntaxType::Boolean,
+        ..Default::default()
+    });
+
 pub static SCHEMA_ATTR_S256: LazyLock<SchemaAttribute> = LazyLock::new(|| SchemaAttribute {
     uuid: UUID_SCHEMA_ATTR_S256,
     name: Attribute::S256,
@@ -1132,6 +1281,8 @@ pub static SCHEMA_CLASS_ACCOUNT_POLICY_DL8: LazyLock<SchemaClass> = LazyLock::ne
         Attribute::AuthSessionExpiry,
         Attribute::PrivilegeExpiry,
         Attribute::AuthPasswordMinimumLength,
+        Attribute::AuthPasswordHistoryLength,
+        Attribute::AuthPasswordMaximumAge,
         Attribute::CredentialTypeMinimum,
         Attribute::WebauthnAttestationCaList,
         Attribute::LimitSearchMaxResults,
@@ -1154,6 +1305,7 @@ pub static SCHEMA_CLASS_ACCOUNT_DL5: LazyLock<SchemaClass> = LazyLock::new(|| Sc
         Attribute::NameHistory,
         Attribute::HmacNameHistory,
         Attribute::AccountSoftlockExpire,
+        Attribute::CredentialSoftLock,
     ],
     systemmust: vec![Attribute::DisplayName, Attribute::Spn],
     systemsupplements: vec![
@@ -1164,7 +1316,7 @@ pub static SCHEMA_CLASS_ACCOUNT_DL5: LazyLock<SchemaClass> = LazyLock::new(|| Sc
     ..Default::default()
 });
 
-pub static SCHEMA_CLASS_SERVICE_ACCOUNT_DL7: LazyLock<SchemaClass> =
+pub static SCHEMA_CLASS_SERVICE_ACCOUNT_DL14: LazyLock<SchemaClass> =
     LazyLock::new(|| SchemaClass {
         uuid: UUID_SCHEMA_CLASS_SERVICE_ACCOUNT,
         name: EntryClass::ServiceAccount.into(),
@@ -1180,6 +1332,7 @@ pub static SCHEMA_CLASS_SERVICE_ACCOUNT_DL7: LazyLock<SchemaClass> =
             Attribute::Mail,
             Attribute::PrimaryCredential,
             Attribute::ApiTokenSession,
+            Attribute::OAuth2RegistrationScopeMap,
         ],
         systemmust: vec![Attribute::Name],
         systemexcludes: vec![EntryClass::Person.into()],
@@ -1265,7 +1418,7 @@ pub static SCHEMA_CLASS_SYSTEM_CONFIG: LazyLock<SchemaClass> = LazyLock::new(||
     ..Default::default()
 });
 
-pub static SCHEMA_CLASS_OAUTH2_RS_DL9: LazyLock<SchemaClass> = LazyLock::new(|| SchemaClass {
+pub static SCHEMA_CLASS_OAUTH2_RS_DL14: LazyLock<SchemaClass> = LazyLock::new(|| SchemaClass {
     uuid: UUID_SCHEMA_CLASS_OAUTH2_RS,
     name: EntryClass::OAuth2ResourceServer.into(),
     description: "The class epresenting a configured OAuth2 Client".to_string(),
@@ -1283,6 +1436,17 @@ pub static SCHEMA_CLASS_OAUTH2_RS_DL9: LazyLock<SchemaClass> = LazyLock::new(||
         Attribute::OAuth2StrictRedirectUri,
         Attribute::OAuth2DeviceFlowEnable,
         Attribute::OAuth2ConsentPromptEnable,
+        Attribute::OAuth2RsBackchannelLogoutUri,
+        Attribute::OAuth2RsJwks,
+        Attribute::OAuth2RequirePar,
+        Attribute::OAuth2RequireDpop,
+        Attribute::OAuth2RsTlsClientAuthSubjectDn,
+        Attribute::OAuth2RsTlsClientCertificate,
+        Attribute::OAuth2RsRegistrationTokenId,
+        Attribute::OAuth2RsEncryptedResponseAlg,
+        Attribute::OAuth2RsAuthorisationDetailsType,
+        Attribute::OAuth2CibaEnable,
+        Attribute::OAuth2PairwiseSubjectEnable,
         // Deprecated
         Attribute::Rs256PrivateKeyDer,
         Attribute::OAuth2RsTokenKey,
@@ -50,6 +50,22 @@ pub fn e_uuid_domain_id_verification_key_v1() -> EntryInitNew {
     ])
 }
 
+pub fn e_uuid_domain_oauth2_pairwise_key_v1() -> EntryInitNew {
+    entry_init_fn([
+        (Attribute::Class, EntryClass::Object.to_value()),
+        (Attribute::Class, EntryClass::KeyObject.to_value()),
+        (Attribute::Class, EntryClass::KeyObjectHkdfS256.to_value()),
+        (
+            Attribute::Uuid,
+            Value::Uuid(UUID_DOMAIN_OAUTH2_PAIRWISE_KEY),
+        ),
+        (
+            Attribute::Description,
+            Value::new_utf8s("The domain-local HMAC key used for OAuth2 pairwise subjects."),
+        ),
+    ])
+}
+
 pub fn e_hmac_name_history_feature() -> EntryInitNew {
     entry_init_fn([
         (Attribute::Class, EntryClass::Object.to_value()),
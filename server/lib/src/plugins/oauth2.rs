@@ -3,7 +3,9 @@ use crate::plugins::Plugin;
 use crate::prelude::*;
 use crate::utils::password_from_random;
 use crate::valueset::ValueSetUuid;
-use compact_jwt::{crypto::JwsRs256Signer, JwsEs256Signer};
+use compact_jwt::{crypto::JwsRs256Signer, JwkKeySet, JwsEs256Signer};
+use kanidm_proto::oauth2::IdTokenEncryptionAlg;
+use std::str::FromStr;
 use std::sync::Arc;
 
 pub struct OAuth2 {}
@@ -80,6 +82,22 @@ impl OAuth2 {
                         entry.add_ava(Attribute::OAuth2RsBasicSecret, v);
                 }
 
+            // Any registered client assertion keys must be a valid key set.
+            if let Some(jwks) = entry.get_ava_single_utf8(Attribute::OAuth2RsJwks) {
+                serde_json::from_str::<JwkKeySet>(jwks).map_err(|e| {
+                    admin_error!(err = ?e, "Invalid oauth2 client JSON web key set");
+                    OperationError::PL0002Oauth2JwksInvalid
+                })?;
+            }
+
+            // Responses can only be encrypted with algorithms that we support.
+            if let Some(alg) = entry.get_ava_single_utf8(Attribute::OAuth2RsEncryptedResponseAlg) {
+                IdTokenEncryptionAlg::from_str(alg).map_err(|e| {
+                    admin_error!(err = ?e, "Invalid oauth2 client encrypted response algorithm");
+                    OperationError::PL0003Oauth2EncryptedResponseAlgInvalid
+                })?;
+            }
+
             let has_rs256 = entry.get_ava_single_bool(Attribute::OAuth2JwtLegacyCryptoEnable).unwrap_or(false);
 
             if domain_level >= DOMAIN_LEVEL_10 {
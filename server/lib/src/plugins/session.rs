@@ -6,10 +6,14 @@
 //!
 //! This plugin is also responsible for invaliding old sessions that are past
 //! their expiry.
+//!
+//! Finally, any oauth2 session that is terminated is recorded so that the client
+//! can be sent a back-channel logout once the transaction commits.
 
 use crate::event::ModifyEvent;
 use crate::plugins::Plugin;
 use crate::prelude::*;
+use crate::server::RevokedOauth2Session;
 use crate::value::SessionState;
 use std::collections::BTreeSet;
 use std::sync::Arc;
@@ -25,21 +29,25 @@ impl Plugin for SessionConsistency {
     #[instrument(level = "debug", name = "session_consistency", skip_all)]
     fn pre_modify(
         qs: &mut QueryServerWriteTransaction,
-        _pre_cand: &[Arc<EntrySealedCommitted>],
+        pre_cand: &[Arc<EntrySealedCommitted>],
         cand: &mut Vec<Entry<EntryInvalid, EntryCommitted>>,
         _me: &ModifyEvent,
     ) -> Result<(), OperationError> {
-        Self::modify_inner(qs, cand)
+        Self::modify_inner(qs, cand)?;
+        Self::record_revoked_oauth2_sessions(qs, pre_cand, cand);
+        Ok(())
     }
 
     #[instrument(level = "debug", name = "session_consistency", skip_all)]
     fn pre_batch_modify(
         qs: &mut QueryServerWriteTransaction,
-        _pre_cand: &[Arc<EntrySealedCommitted>],
+        pre_cand: &[Arc<EntrySealedCommitted>],
         cand: &mut Vec<Entry<EntryInvalid, EntryCommitted>>,
         _me: &BatchModifyEvent,
     ) -> Result<(), OperationError> {
-        Self::modify_inner(qs, cand)
+        Self::modify_inner(qs, cand)?;
+        Self::record_revoked_oauth2_sessions(qs, pre_cand, cand);
+        Ok(())
     }
 }
 
@@ -186,6 +194,43 @@ impl SessionConsistency {
             Ok(())
         })
     }
+
+    fn record_revoked_oauth2_sessions(
+        qs: &mut QueryServerWriteTransaction,
+        pre_cand: &[Arc<EntrySealedCommitted>],
+        cand: &[Entry<EntryInvalid, EntryCommitted>],
+    ) {
+        // * If an oauth2 session was valid, and is now revoked or removed, record it.
+        for (pre, post) in pre_cand.iter().zip(cand) {
+            let Some(pre_sessions) = pre.get_ava_as_oauth2session_map(Attribute::OAuth2Session)
+            else {
+                continue;
+            };
+
+            let post_sessions = post.get_ava_as_oauth2session_map(Attribute::OAuth2Session);
+
+            for (session_id, session) in pre_sessions.iter() {
+                if matches!(session.state, SessionState::RevokedAt(_)) {
+                    continue;
+                }
+
+                let terminated = post_sessions
+                    .and_then(|sessions| sessions.get(session_id))
+                    .map(|session| matches!(session.state, SessionState::RevokedAt(_)))
+                    // The session was removed.
+                    .unwrap_or(true);
+
+                if terminated {
+                    debug!(%session_id, "Recording terminated oauth2 session");
+                    qs.record_revoked_oauth2_session(RevokedOauth2Session {
+                        account_uuid: pre.get_uuid(),
+                        rs_uuid: session.rs_uuid,
+                        session_id: *session_id,
+                    });
+                }
+            }
+        }
+    }
 }
 
 #[cfg(test)]
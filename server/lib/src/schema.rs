@@ -281,6 +281,7 @@ impl SchemaAttribute {
                 matches!(v, PartialValue::Uuid(_)) || matches!(v, PartialValue::Refer(_))
             }
             SyntaxType::Sha256 => matches!(v, PartialValue::Sha256(_)),
+            SyntaxType::CredentialSoftLock => matches!(v, PartialValue::Uuid(_)),
             // SyntaxType::Json => matches!(v, PartialValue::Json),
             // Should not be queried
             SyntaxType::Json | SyntaxType::Message => false,
@@ -351,6 +352,7 @@ impl SchemaAttribute {
                 SyntaxType::ApplicationPassword => matches!(v, Value::ApplicationPassword(..)),
                 SyntaxType::Json => matches!(v, Value::Json(_)),
                 SyntaxType::Sha256 => matches!(v, Value::Sha256(_)),
+                SyntaxType::CredentialSoftLock => matches!(v, Value::CredentialSoftLock(..)),
                 SyntaxType::EcKeyPrivate => matches!(v, Value::SecretValue(_)),
                 SyntaxType::Message => false,
             };
@@ -101,6 +101,8 @@ pub fn migration_entry_attrs(
         allow_attrs.extend([
             Attribute::AuthSessionExpiry,
             Attribute::AuthPasswordMinimumLength,
+            Attribute::AuthPasswordHistoryLength,
+            Attribute::AuthPasswordMaximumAge,
             Attribute::CredentialTypeMinimum,
             Attribute::PrivilegeExpiry,
             Attribute::WebauthnAttestationCaList,
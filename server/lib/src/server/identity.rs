@@ -177,7 +177,6 @@ impl Identity {
         }
     }
 
-    #[allow(dead_code)]
     pub(crate) fn source(&self) -> &Source {
         &self.source
     }
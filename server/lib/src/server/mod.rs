@@ -204,6 +204,14 @@ bitflags::bitflags! {
     }
 }
 
+/// An oauth2 session that was revoked or removed during a write transaction.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) struct RevokedOauth2Session {
+    pub account_uuid: Uuid,
+    pub rs_uuid: Uuid,
+    pub session_id: Uuid,
+}
+
 pub struct QueryServerWriteTransaction<'a> {
     committed: bool,
     phase: CowCellWriteTxn<'a, ServerPhase>,
@@ -241,12 +249,23 @@ pub struct QueryServerWriteTransaction<'a> {
     >,
     dyngroup_cache: CowCellWriteTxn<'a, DynGroupCache>,
     txn_name_to_uuid: BTreeMap<String, Uuid>,
+    // The oauth2 sessions that were terminated in this transaction, so that
+    // the idm layer can notify the affected clients once we commit.
+    revoked_oauth2_sessions: BTreeSet<RevokedOauth2Session>,
 }
 
 impl QueryServerWriteTransaction<'_> {
     pub(crate) fn trim_cid(&self) -> &Cid {
         &self.trim_cid
     }
+
+    pub(crate) fn record_revoked_oauth2_session(&mut self, revoked: RevokedOauth2Session) {
+        self.revoked_oauth2_sessions.insert(revoked);
+    }
+
+    pub(crate) fn take_revoked_oauth2_sessions(&mut self) -> BTreeSet<RevokedOauth2Session> {
+        std::mem::take(&mut self.revoked_oauth2_sessions)
+    }
 }
 
 /// The `QueryServerTransaction` trait provides a set of common read only operations to be
@@ -793,6 +812,7 @@ pub trait QueryServerTransaction<'a> {
                     SyntaxType::Json => Err(OperationError::InvalidAttribute("Json values can not be supplied through modification".to_string())),
                     SyntaxType::Sha256 => Err(OperationError::InvalidAttribute("SHA256 values can not be supplied through modification".to_string())),
                     SyntaxType::Message => Err(OperationError::InvalidAttribute("Message values can not be supplied through modification".to_string())),
+                    SyntaxType::CredentialSoftLock => Err(OperationError::InvalidAttribute("Credential soft locks are generated and not able to be set.".to_string())),
                 }
             }
             None => {
@@ -940,6 +960,13 @@ pub trait QueryServerTransaction<'a> {
                             ))
                         }
                     }
+                    SyntaxType::CredentialSoftLock => {
+                        PartialValue::new_uuid_s(value).ok_or_else(|| {
+                            OperationError::InvalidAttribute(
+                                "Invalid syntax, expected credential uuid".to_string(),
+                            )
+                        })
+                    }
                     SyntaxType::Json => Err(OperationError::InvalidAttribute(
                         "Json values can not be validated by this interface".to_string(),
                     )),
@@ -2111,6 +2138,7 @@ impl QueryServer {
             dyngroup_cache: self.dyngroup_cache.write(),
             key_providers: self.key_providers.write(),
             txn_name_to_uuid: Default::default(),
+            revoked_oauth2_sessions: Default::default(),
         })
     }
 
@@ -2950,6 +2978,7 @@ impl<'a> QueryServerWriteTransaction<'a> {
             resolve_filter_cache_clear,
             mut resolve_filter_cache_write,
             txn_name_to_uuid: _,
+            revoked_oauth2_sessions: _,
         } = self;
         debug_assert!(!committed);
 
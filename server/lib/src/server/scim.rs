@@ -5,10 +5,15 @@ use crate::server::batch_modify::{BatchModifyEvent, ModSetValid};
 use crate::server::ValueSetResolveStatus;
 use crate::valueset::*;
 use crypto_glue::s256::Sha256Output;
+use kanidm_proto::attribute::SubAttribute;
 use kanidm_proto::scim_v1::client::{
-    ScimEntryAssertion, ScimEntryPostGeneric, ScimEntryPutGeneric,
+    ScimBulkMethod, ScimBulkOperation, ScimBulkRequest, ScimEntryAssertion, ScimEntryPatchGeneric,
+    ScimEntryPostGeneric, ScimEntryPutGeneric, ScimPatchOpType, ScimPatchOperation,
+};
+use kanidm_proto::scim_v1::{
+    JsonValue, ScimComplexFilter, ScimEntryGetQuery, ScimPatchPath, SCIM_BULK_MAX_OPERATIONS,
+    SCIM_SCHEMA_BULK_REQUEST, SCIM_SCHEMA_PATCH_OP,
 };
-use kanidm_proto::scim_v1::JsonValue;
 use std::collections::{
     // BTreeSet,
     BTreeMap,
@@ -59,6 +64,51 @@ impl ScimEntryPutEvent {
     }
 }
 
+#[derive(Debug)]
+pub struct ScimEntryPatchEvent {
+    /// The identity performing the change.
+    pub(crate) ident: Identity,
+
+    /// The target entry that will be changed
+    pub(crate) target: Uuid,
+    /// The changes to apply, in the order of the patch operations.
+    pub(crate) mods: ModifyList<ModifyInvalid>,
+
+    /// If an effective access check should be carried out post modification
+    /// of the entries
+    pub(crate) effective_access_check: bool,
+}
+
+impl ScimEntryPatchEvent {
+    pub fn try_from(
+        ident: Identity,
+        target: Uuid,
+        patch: ScimEntryPatchGeneric,
+        query: &ScimEntryGetQuery,
+        qs: &mut QueryServerWriteTransaction,
+    ) -> Result<Self, OperationError> {
+        if !patch
+            .schemas
+            .iter()
+            .any(|schema| schema == SCIM_SCHEMA_PATCH_OP)
+        {
+            return Err(OperationError::SC0034PatchSchemaInvalid);
+        }
+
+        let mut mods = Vec::with_capacity(patch.operations.len());
+        for operation in patch.operations {
+            qs.resolve_scim_patch_operation(operation, &mut mods)?;
+        }
+
+        Ok(ScimEntryPatchEvent {
+            ident,
+            target,
+            mods: ModifyList::new_list(mods),
+            effective_access_check: query.ext_access_check,
+        })
+    }
+}
+
 #[derive(Debug)]
 pub struct ScimCreateEvent {
     pub(crate) ident: Identity,
@@ -145,6 +195,123 @@ impl ScimAssertEvent {
     }
 }
 
+/// A SCIM bulk request that is being applied.
+///
+/// The operations are applied in order in a single transaction. Once failOnErrors
+/// operations have failed, the remaining operations are not applied.
+#[derive(Debug)]
+pub struct ScimBulkEvent {
+    operations: Vec<ScimBulkOperation>,
+    /// The uuid of the entry that each POST operation creates. These are assigned up
+    /// front so that operations may refer to an entry by bulkId before it is created.
+    created: BTreeMap<usize, Uuid>,
+    bulk_ids: BTreeMap<String, Uuid>,
+    fail_on_errors: usize,
+}
+
+impl ScimBulkEvent {
+    pub fn try_from(bulk: ScimBulkRequest) -> Result<Self, OperationError> {
+        if !bulk
+            .schemas
+            .iter()
+            .any(|schema| schema == SCIM_SCHEMA_BULK_REQUEST)
+        {
+            return Err(OperationError::SC0038BulkRequestInvalid);
+        }
+
+        if bulk.operations.is_empty() {
+            return Err(OperationError::EmptyRequest);
+        }
+
+        if bulk.operations.len() > SCIM_BULK_MAX_OPERATIONS {
+            request_error!(
+                operations = bulk.operations.len(),
+                "too many operations in bulk request"
+            );
+            return Err(OperationError::SC0038BulkRequestInvalid);
+        }
+
+        let mut created = BTreeMap::new();
+        let mut bulk_ids = BTreeMap::new();
+
+        for (index, operation) in bulk.operations.iter().enumerate() {
+            if operation.method != ScimBulkMethod::Post {
+                continue;
+            }
+
+            // Honour a uuid that the client chose for the entry.
+            let uuid = operation
+                .data
+                .as_ref()
+                .and_then(|data| data.get(Attribute::Uuid.as_str()))
+                .and_then(|uuid| uuid.as_str())
+                .and_then(|uuid| Uuid::parse_str(uuid).ok())
+                .unwrap_or_else(Uuid::new_v4);
+
+            created.insert(index, uuid);
+
+            if let Some(bulk_id) = operation.bulk_id.as_ref() {
+                if bulk_ids.insert(bulk_id.clone(), uuid).is_some() {
+                    request_error!(?bulk_id, "bulkId is used by more than one operation");
+                    return Err(OperationError::SC0038BulkRequestInvalid);
+                }
+            }
+        }
+
+        let fail_on_errors = bulk
+            .fail_on_errors
+            .and_then(|fail_on_errors| usize::try_from(fail_on_errors.get()).ok())
+            .unwrap_or(usize::MAX);
+
+        Ok(ScimBulkEvent {
+            operations: bulk.operations,
+            created,
+            bulk_ids,
+            fail_on_errors,
+        })
+    }
+
+    /// Replace references of the form `bulkId:<bulk_id>` with the uuid of the entry
+    /// that the operation with that bulkId creates.
+    fn resolve_bulk_ids(&self, value: &mut JsonValue) -> Result<(), OperationError> {
+        match value {
+            JsonValue::String(s) => {
+                if let Some(bulk_id) = s.strip_prefix("bulkId:") {
+                    let uuid = self.bulk_ids.get(bulk_id).ok_or_else(|| {
+                        request_error!(?bulk_id, "unknown bulkId");
+                        OperationError::SC0039BulkOperationInvalid
+                    })?;
+                    *s = uuid.to_string();
+                }
+                Ok(())
+            }
+            JsonValue::Array(values) => values
+                .iter_mut()
+                .try_for_each(|value| self.resolve_bulk_ids(value)),
+            JsonValue::Object(attrs) => attrs
+                .values_mut()
+                .try_for_each(|value| self.resolve_bulk_ids(value)),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// The result of an operation of a SCIM bulk request.
+#[derive(Debug)]
+pub enum ScimBulkOperationResult {
+    Created(Uuid),
+    Modified(Uuid),
+    Deleted,
+    Failed(OperationError),
+}
+
+#[derive(Debug)]
+pub struct ScimBulkOperationOutcome {
+    pub method: ScimBulkMethod,
+    pub bulk_id: Option<String>,
+    pub result: ScimBulkOperationResult,
+}
+
 impl QueryServerWriteTransaction<'_> {
     /// SCIM PUT is the handler where a single entry is updated. In a SCIM PUT request
     /// the request defines the state of an attribute in entirety for the update. This
@@ -164,6 +331,32 @@ impl QueryServerWriteTransaction<'_> {
         // This function transforms the put event into a modify event.
         let mods_invalid: ModifyList<ModifyInvalid> = attrs.into();
 
+        self.scim_modify(ident, target, &mods_invalid, effective_access_check)
+    }
+
+    /// SCIM PATCH is the handler where a single entry is partially updated. Unlike PUT, each
+    /// operation in a PATCH request can add or remove individual values of an attribute.
+    pub fn scim_patch(
+        &mut self,
+        scim_entry_patch: ScimEntryPatchEvent,
+    ) -> Result<ScimEntryKanidm, OperationError> {
+        let ScimEntryPatchEvent {
+            ident,
+            target,
+            mods,
+            effective_access_check,
+        } = scim_entry_patch;
+
+        self.scim_modify(ident, target, &mods, effective_access_check)
+    }
+
+    fn scim_modify(
+        &mut self,
+        ident: Identity,
+        target: Uuid,
+        mods_invalid: &ModifyList<ModifyInvalid>,
+        effective_access_check: bool,
+    ) -> Result<ScimEntryKanidm, OperationError> {
         let mods_valid = mods_invalid
             .validate(self.get_schema())
             .map_err(OperationError::SchemaViolation)?;
@@ -362,6 +555,249 @@ impl QueryServerWriteTransaction<'_> {
         self.assert(assert_event)
     }
 
+    /// Apply a SCIM bulk request, returning the outcome of each operation that was
+    /// attempted. An operation that fails before it has written any changes is recorded
+    /// as failed, and the remaining operations are still applied. If an operation fails
+    /// after it has written changes, the transaction can't be committed and the error is
+    /// returned instead.
+    pub fn scim_bulk(
+        &mut self,
+        ident: &Identity,
+        bulk: &ScimBulkEvent,
+    ) -> Result<Vec<ScimBulkOperationOutcome>, OperationError> {
+        let mut outcomes = Vec::with_capacity(bulk.operations.len());
+        let mut failures = 0;
+
+        for (index, operation) in bulk.operations.iter().enumerate() {
+            if failures >= bulk.fail_on_errors {
+                debug!(failures, "bulk failOnErrors reached");
+                break;
+            }
+
+            let writes = self.be_txn.writes();
+
+            let result = match self.scim_bulk_operation(ident, bulk, index) {
+                Ok(result) => result,
+                Err(err) if self.be_txn.writes() == writes => {
+                    debug!(?err, index, "bulk operation failed");
+                    failures += 1;
+                    ScimBulkOperationResult::Failed(err)
+                }
+                Err(err) => {
+                    error!(
+                        ?err,
+                        index, "bulk operation failed after writing changes, aborting bulk"
+                    );
+                    return Err(err);
+                }
+            };
+
+            outcomes.push(ScimBulkOperationOutcome {
+                method: operation.method,
+                bulk_id: operation.bulk_id.clone(),
+                result,
+            });
+        }
+
+        Ok(outcomes)
+    }
+
+    fn scim_bulk_operation(
+        &mut self,
+        ident: &Identity,
+        bulk: &ScimBulkEvent,
+        index: usize,
+    ) -> Result<ScimBulkOperationResult, OperationError> {
+        let ScimBulkOperation {
+            method, path, data, ..
+        } = bulk
+            .operations
+            .get(index)
+            .cloned()
+            .ok_or(OperationError::InvalidState)?;
+
+        let data = data
+            .map(|mut data| bulk.resolve_bulk_ids(&mut data).map(|_| data))
+            .transpose()?;
+
+        // Operations may only refer to generic entries.
+        let path = path.trim_end_matches('/');
+        let target = if path == "/Entry" {
+            None
+        } else if let Some(id) = path.strip_prefix("/Entry/") {
+            let target = match id.strip_prefix("bulkId:") {
+                Some(bulk_id) => bulk.bulk_ids.get(bulk_id).copied().ok_or_else(|| {
+                    request_error!(?bulk_id, "unknown bulkId");
+                    OperationError::SC0039BulkOperationInvalid
+                })?,
+                None => self.name_to_uuid(id)?,
+            };
+            Some(target)
+        } else {
+            request_error!(?path, "unsupported bulk operation path");
+            return Err(OperationError::SC0039BulkOperationInvalid);
+        };
+
+        fn from_data<T: serde::de::DeserializeOwned>(data: JsonValue) -> Result<T, OperationError> {
+            serde_json::from_value(data).map_err(|err| {
+                request_error!(?err, "invalid bulk operation data");
+                OperationError::SC0039BulkOperationInvalid
+            })
+        }
+
+        match (method, target, data) {
+            (ScimBulkMethod::Post, None, Some(data)) => {
+                let mut entry: ScimEntryPostGeneric = from_data(data)?;
+                let uuid = bulk
+                    .created
+                    .get(&index)
+                    .copied()
+                    .ok_or(OperationError::InvalidState)?;
+                entry
+                    .attrs
+                    .insert(Attribute::Uuid, JsonValue::String(uuid.to_string()));
+
+                let scim_create_event = ScimCreateEvent::try_from(ident.clone(), &[], entry, self)?;
+                self.scim_create(scim_create_event)
+                    .map(|entry| ScimBulkOperationResult::Created(entry.header.id))
+            }
+            (ScimBulkMethod::Put, Some(target), Some(mut data)) => {
+                // The target of a PUT is given by the path.
+                if let JsonValue::Object(attrs) = &mut data {
+                    attrs.insert("id".to_string(), JsonValue::String(target.to_string()));
+                }
+                let entry: ScimEntryPutGeneric = from_data(data)?;
+
+                let scim_entry_put_event = ScimEntryPutEvent::try_from(ident.clone(), entry, self)?;
+                self.scim_put(scim_entry_put_event)
+                    .map(|_| ScimBulkOperationResult::Modified(target))
+            }
+            (ScimBulkMethod::Patch, Some(target), Some(data)) => {
+                let patch: ScimEntryPatchGeneric = from_data(data)?;
+
+                let scim_entry_patch_event = ScimEntryPatchEvent::try_from(
+                    ident.clone(),
+                    target,
+                    patch,
+                    &ScimEntryGetQuery::default(),
+                    self,
+                )?;
+                self.scim_patch(scim_entry_patch_event)
+                    .map(|_| ScimBulkOperationResult::Modified(target))
+            }
+            (ScimBulkMethod::Delete, Some(target), None) => {
+                let scim_delete_event =
+                    ScimDeleteEvent::new(ident.clone(), target, EntryClass::Object);
+                self.scim_delete(scim_delete_event)
+                    .map(|_| ScimBulkOperationResult::Deleted)
+            }
+            _ => {
+                request_error!(?method, ?path, "bulk operation has an invalid path or data");
+                Err(OperationError::SC0039BulkOperationInvalid)
+            }
+        }
+    }
+
+    fn resolve_scim_patch_operation(
+        &mut self,
+        operation: ScimPatchOperation,
+        mods: &mut Vec<Modify>,
+    ) -> Result<(), OperationError> {
+        let ScimPatchOperation { op, path, value } = operation;
+
+        let Some(path) = path else {
+            // Without a path, the value is an object of attributes and the values to
+            // add or replace on each of them.
+            return match (op, value) {
+                (
+                    ScimPatchOpType::Add | ScimPatchOpType::Replace,
+                    Some(JsonValue::Object(attrs)),
+                ) => attrs.into_iter().try_for_each(|(attr, value)| {
+                    let path = ScimPatchPath {
+                        a: Attribute::from(attr.as_str()),
+                        s: None,
+                        filter: None,
+                    };
+                    self.resolve_scim_patch_operation(
+                        ScimPatchOperation {
+                            op,
+                            path: Some(path),
+                            value: Some(value),
+                        },
+                        mods,
+                    )
+                }),
+                _ => Err(OperationError::SC0035PatchOperationInvalid),
+            };
+        };
+
+        let ScimPatchPath { a: attr, s, filter } = path;
+
+        if s.is_some() {
+            // We can't alter parts of a single value.
+            return Err(OperationError::SC0036PatchPathUnsupported);
+        }
+
+        match (op, filter, value) {
+            (ScimPatchOpType::Add, None, Some(value)) => {
+                let multivalue = self
+                    .get_schema()
+                    .get_attributes()
+                    .get(&attr)
+                    .map(|schema_a| schema_a.multivalue)
+                    .unwrap_or_default();
+
+                let valueset = self.resolve_scim_json_post(&attr, value)?;
+
+                if multivalue {
+                    mods.extend(
+                        valueset
+                            .to_value_iter()
+                            .map(|value| Modify::Present(attr.clone(), value)),
+                    );
+                } else {
+                    // Adding to a single value attribute replaces the existing value.
+                    mods.push(Modify::Set(attr, valueset));
+                }
+            }
+            (ScimPatchOpType::Replace, None, Some(value)) => {
+                let valueset = self.resolve_scim_json_post(&attr, value)?;
+                mods.push(Modify::Set(attr, valueset));
+            }
+            (ScimPatchOpType::Remove, None, None) => {
+                mods.push(Modify::Purged(attr));
+            }
+            (ScimPatchOpType::Remove, None, Some(value)) => {
+                let valueset = self.resolve_scim_json_post(&attr, value)?;
+                mods.extend(
+                    valueset
+                        .to_partialvalue_iter()
+                        .map(|pv| Modify::Removed(attr.clone(), pv)),
+                );
+            }
+            (ScimPatchOpType::Remove, Some(filter), None) => {
+                let mut values = Vec::new();
+                scim_patch_filter_values(&filter, &mut values)?;
+
+                for value in values {
+                    let value = match value {
+                        JsonValue::String(value) => value,
+                        value => value.to_string(),
+                    };
+                    let pv = self.clone_partialvalue(&attr, &value)?;
+                    mods.push(Modify::Removed(attr.clone(), pv));
+                }
+            }
+            (ScimPatchOpType::Add | ScimPatchOpType::Replace, Some(_), _) => {
+                // Altering selected values in place is not supported.
+                return Err(OperationError::SC0036PatchPathUnsupported);
+            }
+            _ => return Err(OperationError::SC0035PatchOperationInvalid),
+        };
+
+        Ok(())
+    }
+
     pub(crate) fn resolve_scim_json_put(
         &mut self,
         attr: &Attribute,
@@ -517,6 +953,9 @@ impl QueryServerWriteTransaction<'_> {
             SyntaxType::ApplicationPassword => Err(OperationError::InvalidAttribute(
                 "Application Passwords are not able to be set.".to_string(),
             )),
+            SyntaxType::CredentialSoftLock => Err(OperationError::InvalidAttribute(
+                "Credential Soft Locks are not able to be set.".to_string(),
+            )),
         }?;
 
         match resolve_status {
@@ -528,15 +967,37 @@ impl QueryServerWriteTransaction<'_> {
     }
 }
 
+/// Extract the values selected by a value filter in a patch path. Only equality on the
+/// value of an attribute is supported, such as `member[value eq "uuid" or value eq "name"]`.
+fn scim_patch_filter_values(
+    filter: &ScimComplexFilter,
+    values: &mut Vec<JsonValue>,
+) -> Result<(), OperationError> {
+    match filter {
+        ScimComplexFilter::Equal(SubAttribute::Value, value) => {
+            values.push(value.clone());
+            Ok(())
+        }
+        ScimComplexFilter::Or(left, right) => {
+            scim_patch_filter_values(left, values)?;
+            scim_patch_filter_values(right, values)
+        }
+        _ => Err(OperationError::SC0036PatchPathUnsupported),
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use super::{ScimAssertEvent, ScimEntryPutEvent};
+    use super::{
+        ScimAssertEvent, ScimBulkEvent, ScimBulkOperationOutcome, ScimBulkOperationResult,
+        ScimEntryPatchEvent, ScimEntryPutEvent,
+    };
     use crate::prelude::*;
     use kanidm_proto::scim_v1::client::{
         ScimEntryAssertion, ScimEntryPutKanidm, ScimReference as ScimClientReference,
     };
     use kanidm_proto::scim_v1::server::ScimReference;
-    use kanidm_proto::scim_v1::ScimMail;
+    use kanidm_proto::scim_v1::{ScimMail, SCIM_SCHEMA_BULK_REQUEST, SCIM_SCHEMA_PATCH_OP};
     use std::collections::BTreeMap;
 
     #[qs_test]
@@ -791,6 +1252,129 @@ mod tests {
         assert!(!updated_entry.attrs.contains_key(&Attribute::Member));
     }
 
+    #[qs_test]
+    async fn scim_patch_basic(server: &QueryServer) {
+        let mut server_txn = server.write(duration_from_epoch_now()).await.unwrap();
+
+        let idm_admin_entry = server_txn.internal_search_uuid(UUID_IDM_ADMIN).unwrap();
+
+        let idm_admin_ident = Identity::from_impersonate_entry_readwrite(idm_admin_entry);
+
+        let group_uuid = Uuid::new_v4();
+        let extra1_uuid = Uuid::new_v4();
+        let extra2_uuid = Uuid::new_v4();
+
+        let e1 = entry_init!(
+            (Attribute::Class, EntryClass::Object.to_value()),
+            (Attribute::Class, EntryClass::Group.to_value()),
+            (Attribute::Name, Value::new_iname("testgroup")),
+            (Attribute::Uuid, Value::Uuid(group_uuid))
+        );
+
+        let e2 = entry_init!(
+            (Attribute::Class, EntryClass::Object.to_value()),
+            (Attribute::Class, EntryClass::Group.to_value()),
+            (Attribute::Name, Value::new_iname("extra_1")),
+            (Attribute::Uuid, Value::Uuid(extra1_uuid))
+        );
+
+        let e3 = entry_init!(
+            (Attribute::Class, EntryClass::Object.to_value()),
+            (Attribute::Class, EntryClass::Group.to_value()),
+            (Attribute::Name, Value::new_iname("extra_2")),
+            (Attribute::Uuid, Value::Uuid(extra2_uuid))
+        );
+
+        assert!(server_txn.internal_create(vec![e1, e2, e3]).is_ok());
+
+        let mut patch = |patch: serde_json::Value| {
+            let patch = serde_json::from_value(patch).expect("Invalid patch");
+            ScimEntryPatchEvent::try_from(
+                idm_admin_ident.clone(),
+                group_uuid,
+                patch,
+                &Default::default(),
+                &mut server_txn,
+            )
+            .and_then(|patch_event| server_txn.scim_patch(patch_event))
+        };
+
+        // Add members one at a time, and replace the description.
+        let updated_entry = patch(serde_json::json!({
+            "schemas": [SCIM_SCHEMA_PATCH_OP],
+            "Operations": [
+                { "op": "add", "path": "member", "value": ["extra_1"] },
+                { "op": "Add", "path": "member", "value": [{ "value": extra2_uuid }] },
+                { "op": "replace", "value": { "description": "Group Description" } }
+            ]
+        }))
+        .expect("Failed to patch");
+
+        match updated_entry.attrs.get(&Attribute::Member) {
+            Some(ScimValueKanidm::EntryReferences(member_set)) => {
+                assert_eq!(member_set.len(), 2);
+            }
+            _ => unreachable!("Expected 2 members"),
+        };
+
+        match updated_entry.attrs.get(&Attribute::Description) {
+            Some(ScimValueKanidm::String(gdesc)) if gdesc == "Group Description" => {}
+            _ => unreachable!("Expected a string"),
+        };
+
+        // Remove a single member with a value filter, and remove the description.
+        let updated_entry = patch(serde_json::json!({
+            "schemas": [SCIM_SCHEMA_PATCH_OP],
+            "Operations": [
+                { "op": "remove", "path": format!("member[value eq \"{extra1_uuid}\"]") },
+                { "op": "remove", "path": "description" }
+            ]
+        }))
+        .expect("Failed to patch");
+
+        match updated_entry.attrs.get(&Attribute::Member) {
+            Some(ScimValueKanidm::EntryReferences(member_set)) => {
+                assert_eq!(member_set.len(), 1);
+                assert!(member_set.contains(&ScimReference {
+                    uuid: extra2_uuid,
+                    value: "extra_2@example.com".to_string(),
+                }));
+            }
+            _ => unreachable!("Expected 1 member"),
+        };
+        assert!(!updated_entry.attrs.contains_key(&Attribute::Description));
+
+        // The PatchOp schema is required
+        let err = patch(serde_json::json!({
+            "schemas": [],
+            "Operations": [
+                { "op": "remove", "path": "member" }
+            ]
+        }))
+        .unwrap_err();
+        assert_eq!(err, OperationError::SC0034PatchSchemaInvalid);
+
+        // Replace requires a value
+        let err = patch(serde_json::json!({
+            "schemas": [SCIM_SCHEMA_PATCH_OP],
+            "Operations": [
+                { "op": "replace", "path": "member" }
+            ]
+        }))
+        .unwrap_err();
+        assert_eq!(err, OperationError::SC0035PatchOperationInvalid);
+
+        // Sub-attributes of values can't be patched
+        let err = patch(serde_json::json!({
+            "schemas": [SCIM_SCHEMA_PATCH_OP],
+            "Operations": [
+                { "op": "replace", "path": "mail[value eq \"test@example.com\"].primary", "value": true }
+            ]
+        }))
+        .unwrap_err();
+        assert_eq!(err, OperationError::SC0036PatchPathUnsupported);
+    }
+
     #[qs_test]
     async fn scim_assert_basic(server: &QueryServer) {
         let mut server_txn = server.write(duration_from_epoch_now()).await.unwrap();
@@ -840,4 +1424,121 @@ mod tests {
 
         server_txn.scim_assert(scim_assert).expect("Must not fail!");
     }
+
+    async fn scim_bulk_apply(
+        server: &QueryServer,
+        ident: &Identity,
+        bulk: serde_json::Value,
+    ) -> Vec<ScimBulkOperationOutcome> {
+        let bulk = serde_json::from_value(bulk).expect("Invalid bulk request");
+        let bulk = ScimBulkEvent::try_from(bulk).expect("Failed to create bulk event");
+
+        let mut server_txn = server.write(duration_from_epoch_now()).await.unwrap();
+        let outcomes = server_txn
+            .scim_bulk(ident, &bulk)
+            .expect("Failed to apply bulk");
+        server_txn.commit().expect("Failed to commit");
+        outcomes
+    }
+
+    #[qs_test]
+    async fn scim_bulk_basic(server: &QueryServer) {
+        let mut server_txn = server.write(duration_from_epoch_now()).await.unwrap();
+        let idm_admin_entry = server_txn.internal_search_uuid(UUID_IDM_ADMIN).unwrap();
+        let idm_admin_ident = Identity::from_impersonate_entry_readwrite(idm_admin_entry);
+        drop(server_txn);
+
+        // Create two groups, and make one a member of the other by bulkId. The
+        // failing operations must not prevent the others from applying.
+        let outcomes = scim_bulk_apply(
+            server,
+            &idm_admin_ident,
+            serde_json::json!({
+                "schemas": [SCIM_SCHEMA_BULK_REQUEST],
+                "Operations": [
+                    {
+                        "method": "POST",
+                        "path": "/Entry",
+                        "bulkId": "group",
+                        "data": { "class": ["object", "group"], "name": "bulk_group" }
+                    },
+                    {
+                        "method": "POST",
+                        "path": "/Entry",
+                        "bulkId": "member",
+                        "data": { "class": ["object", "group"], "name": "bulk_member" }
+                    },
+                    {
+                        "method": "PATCH",
+                        "path": "/Entry/bulkId:group",
+                        "data": {
+                            "schemas": [SCIM_SCHEMA_PATCH_OP],
+                            "Operations": [
+                                { "op": "add", "path": "member", "value": ["bulkId:member"] }
+                            ]
+                        }
+                    },
+                    { "method": "DELETE", "path": "/Entry/bulk_missing" },
+                    {
+                        "method": "POST",
+                        "path": "/Entry",
+                        "data": { "class": ["object", "group"], "name": "bulk_group" }
+                    }
+                ]
+            }),
+        )
+        .await;
+
+        assert_eq!(outcomes.len(), 5);
+        let ScimBulkOperationResult::Created(group_uuid) = outcomes[0].result else {
+            unreachable!("Expected the group to be created");
+        };
+        let ScimBulkOperationResult::Created(member_uuid) = outcomes[1].result else {
+            unreachable!("Expected the member to be created");
+        };
+        assert_eq!(outcomes[0].bulk_id.as_deref(), Some("group"));
+        assert!(matches!(
+            outcomes[2].result,
+            ScimBulkOperationResult::Modified(uuid) if uuid == group_uuid
+        ));
+        assert!(matches!(
+            outcomes[3].result,
+            ScimBulkOperationResult::Failed(OperationError::NoMatchingEntries)
+        ));
+        assert!(matches!(
+            outcomes[4].result,
+            ScimBulkOperationResult::Failed(_)
+        ));
+
+        let mut server_txn = server.write(duration_from_epoch_now()).await.unwrap();
+        let group = server_txn
+            .internal_search_uuid(group_uuid)
+            .expect("Group was not created");
+        assert!(group.attribute_equality(Attribute::Member, &PartialValue::Refer(member_uuid)));
+        drop(server_txn);
+
+        // Once failOnErrors is reached, no further operations are applied.
+        let outcomes = scim_bulk_apply(
+            server,
+            &idm_admin_ident,
+            serde_json::json!({
+                "schemas": [SCIM_SCHEMA_BULK_REQUEST],
+                "failOnErrors": 1,
+                "Operations": [
+                    { "method": "DELETE", "path": "/Entry/bulkId:missing" },
+                    { "method": "DELETE", "path": "/Entry/bulk_member" }
+                ]
+            }),
+        )
+        .await;
+
+        assert_eq!(outcomes.len(), 1);
+        assert!(matches!(
+            outcomes[0].result,
+            ScimBulkOperationResult::Failed(OperationError::SC0039BulkOperationInvalid)
+        ));
+
+        let mut server_txn = server.write(duration_from_epoch_now()).await.unwrap();
+        assert!(server_txn.internal_search_uuid(member_uuid).is_ok());
+    }
 }
@@ -5,7 +5,9 @@
 
 use crate::be::dbentry::DbIdentSpn;
 use crate::be::dbvalue::DbValueOauthClaimMapJoinV1;
-use crate::credential::{apppwd::ApplicationPassword, totp::Totp, Credential};
+use crate::credential::{
+    apppwd::ApplicationPassword, softlock::CredSoftLockRecord, totp::Totp, Credential,
+};
 use crate::prelude::*;
 use crate::repl::cid::Cid;
 use crate::server::identity::IdentityId;
@@ -287,6 +289,7 @@ pub enum SyntaxType {
     Sha256 = 44,
     Int64 = 45,
     Uint64 = 46,
+    CredentialSoftLock = 47,
 }
 
 impl TryFrom<&str> for SyntaxType {
@@ -342,6 +345,7 @@ impl TryFrom<&str> for SyntaxType {
             "SHA256" => Ok(SyntaxType::Sha256),
             "INT64" => Ok(SyntaxType::Int64),
             "UINT64" => Ok(SyntaxType::Uint64),
+            "CREDENTIAL_SOFTLOCK" => Ok(SyntaxType::CredentialSoftLock),
             _ => Err(()),
         }
     }
@@ -397,6 +401,7 @@ impl fmt::Display for SyntaxType {
             SyntaxType::Sha256 => "SHA256",
             SyntaxType::Int64 => "INT64",
             SyntaxType::Uint64 => "UINT64",
+            SyntaxType::CredentialSoftLock => "CREDENTIAL_SOFTLOCK",
         })
     }
 }
@@ -470,6 +475,7 @@ impl SyntaxType {
             SyntaxType::JsonFilter => &[],
             SyntaxType::Json => &[],
             SyntaxType::Message => &[],
+            SyntaxType::CredentialSoftLock => &[],
             SyntaxType::Sha256 => &[IndexType::Equality],
         }
     }
@@ -1446,6 +1452,7 @@ pub enum Value {
     ApplicationPassword(ApplicationPassword),
     Json(JsonValue),
     Sha256(Sha256Output),
+    CredentialSoftLock(Uuid, CredSoftLockRecord),
 }
 
 impl PartialEq for Value {
@@ -1480,6 +1487,10 @@ impl PartialEq for Value {
             (Value::Int64(a), Value::Int64(b)) => a.eq(b),
             // Uint64
             (Value::Uint64(a), Value::Uint64(b)) => a.eq(b),
+            // CredentialSoftLock
+            (Value::CredentialSoftLock(a, c), Value::CredentialSoftLock(b, d)) => {
+                a.eq(b) && c.eq(d)
+            }
             // Cid
             (Value::Cid(a), Value::Cid(b)) => a.eq(b),
             // DateTime
@@ -2323,6 +2334,7 @@ impl Value {
             | Value::CredentialType(_)
             | Value::Json(_)
             | Value::Sha256(_)
+            | Value::CredentialSoftLock(_, _)
             | Value::WebauthnAttestationCaList(_) => true,
         }
     }
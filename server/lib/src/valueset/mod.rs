@@ -1,5 +1,7 @@
 use crate::be::dbvalue::DbValueSetV2;
-use crate::credential::{apppwd::ApplicationPassword, totp::Totp, Credential};
+use crate::credential::{
+    apppwd::ApplicationPassword, softlock::CredSoftLockRecord, totp::Totp, Credential,
+};
 use crate::prelude::*;
 use crate::repl::cid::Cid;
 use crate::schema::SchemaAttribute;
@@ -55,6 +57,7 @@ pub use self::restricted::ValueSetRestricted;
 pub use self::s256::ValueSetSha256;
 pub use self::secret::ValueSetSecret;
 pub use self::session::{ValueSetApiToken, ValueSetOauth2Session, ValueSetSession};
+pub use self::softlock::ValueSetCredentialSoftLock;
 pub use self::spn::ValueSetSpn;
 pub use self::ssh::ValueSetSshKey;
 pub use self::syntax::ValueSetSyntax;
@@ -91,6 +94,7 @@ mod restricted;
 mod s256;
 mod secret;
 mod session;
+mod softlock;
 mod spn;
 mod ssh;
 mod syntax;
@@ -438,6 +442,10 @@ pub trait ValueSetT: std::fmt::Debug + DynClone {
         None
     }
 
+    fn as_credential_softlock_map(&self) -> Option<&BTreeMap<Uuid, CredSoftLockRecord>> {
+        None
+    }
+
     fn to_value_single(&self) -> Option<Value> {
         if self.len() != 1 {
             None
@@ -910,6 +918,7 @@ pub fn from_result_value_iter(
         | Value::HexString(_)
         | Value::Json(_)
         | Value::Sha256(_)
+        | Value::CredentialSoftLock(_, _)
         | Value::KeyInternal { .. } => {
             debug_assert!(false);
             return Err(OperationError::InvalidValueState);
@@ -994,6 +1003,9 @@ pub fn from_value_iter(mut iter: impl Iterator<Item = Value>) -> Result<ValueSet
             return Err(OperationError::InvalidValueState);
         }
         Value::ApplicationPassword(ap) => ValueSetApplicationPassword::new(ap),
+        Value::CredentialSoftLock(cred_id, record) => {
+            ValueSetCredentialSoftLock::new(cred_id, record)
+        }
         Value::Sha256(_) => {
             debug_assert!(false);
             return Err(OperationError::InvalidValueState);
@@ -1066,6 +1078,7 @@ pub fn from_db_valueset_v2(dbvs: DbValueSetV2) -> Result<ValueSet, OperationErro
         DbValueSetV2::ApplicationPassword(set) => ValueSetApplicationPassword::from_dbvs2(set),
         DbValueSetV2::Json(object) => Ok(ValueSetJson::new(object)),
         DbValueSetV2::Sha256(set) => ValueSetSha256::from_dbvs2(set),
+        DbValueSetV2::CredentialSoftLock(set) => ValueSetCredentialSoftLock::from_dbvs2(set),
         DbValueSetV2::Message(object) => Ok(ValueSetMessage::new(object)),
         DbValueSetV2::EcKeyPrivate(_key) => Err(OperationError::InvalidState),
     }
@@ -0,0 +1,300 @@
+use crate::be::dbvalue::{DbValueCredentialSoftLock, DbValueSetV2};
+use crate::credential::softlock::CredSoftLockRecord;
+use crate::prelude::*;
+use crate::schema::SchemaAttribute;
+use crate::valueset::{uuid_to_proto_string, ScimResolveStatus, ValueSet};
+use std::collections::BTreeMap;
+
+#[derive(Debug, Clone)]
+pub struct ValueSetCredentialSoftLock {
+    // The map key is the credential's UUID.
+    map: BTreeMap<Uuid, CredSoftLockRecord>,
+}
+
+impl ValueSetCredentialSoftLock {
+    pub fn new(cred_id: Uuid, record: CredSoftLockRecord) -> Box<Self> {
+        let map = BTreeMap::from([(cred_id, record)]);
+        Box::new(ValueSetCredentialSoftLock { map })
+    }
+
+    fn from_dbv_iter(
+        data: impl Iterator<Item = DbValueCredentialSoftLock>,
+    ) -> Result<ValueSet, OperationError> {
+        let map = data
+            .map(|dbv| match dbv {
+                DbValueCredentialSoftLock::V1 {
+                    cred_id,
+                    count,
+                    reset_at,
+                    unlock_at,
+                    updated_at,
+                } => (
+                    cred_id,
+                    CredSoftLockRecord {
+                        count,
+                        reset_at,
+                        unlock_at,
+                        updated_at,
+                    },
+                ),
+            })
+            .collect();
+        Ok(Box::new(ValueSetCredentialSoftLock { map }))
+    }
+
+    pub fn from_dbvs2(data: Vec<DbValueCredentialSoftLock>) -> Result<ValueSet, OperationError> {
+        Self::from_dbv_iter(data.into_iter())
+    }
+
+    fn to_vec_dbvs(&self) -> Vec<DbValueCredentialSoftLock> {
+        self.map
+            .iter()
+            .map(|(cred_id, record)| DbValueCredentialSoftLock::V1 {
+                cred_id: *cred_id,
+                count: record.count,
+                reset_at: record.reset_at,
+                unlock_at: record.unlock_at,
+                updated_at: record.updated_at,
+            })
+            .collect()
+    }
+
+    fn merge_map(&mut self, other: &BTreeMap<Uuid, CredSoftLockRecord>) {
+        for (k_other, v_other) in other.iter() {
+            self.map
+                .entry(*k_other)
+                .and_modify(|v_self| *v_self = v_self.merge(v_other))
+                .or_insert(*v_other);
+        }
+    }
+}
+
+impl ValueSetT for ValueSetCredentialSoftLock {
+    fn insert_checked(&mut self, value: Value) -> Result<bool, OperationError> {
+        match value {
+            Value::CredentialSoftLock(cred_id, record) => {
+                // Records are combined rather than replaced so that failures written
+                // concurrently by other sessions are not lost.
+                let next = match self.map.get(&cred_id) {
+                    Some(current) => current.merge(&record),
+                    None => record,
+                };
+                Ok(self.map.insert(cred_id, next) != Some(next))
+            }
+            _ => Err(OperationError::InvalidValueState),
+        }
+    }
+
+    fn clear(&mut self) {
+        self.map.clear();
+    }
+
+    fn remove(&mut self, pv: &PartialValue, _cid: &Cid) -> bool {
+        match pv {
+            PartialValue::Uuid(u) => self.map.remove(u).is_some(),
+            _ => false,
+        }
+    }
+
+    fn trim(&mut self, trim_cid: &Cid) {
+        // Once a record has reset and is outside of the replication window it can
+        // no longer affect the lock state on any replica.
+        self.map
+            .retain(|_, record| record.reset_at >= trim_cid.ts || record.updated_at >= trim_cid.ts);
+    }
+
+    fn contains(&self, pv: &PartialValue) -> bool {
+        match pv {
+            PartialValue::Uuid(u) => self.map.contains_key(u),
+            _ => false,
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    fn syntax(&self) -> SyntaxType {
+        SyntaxType::CredentialSoftLock
+    }
+
+    fn validate(&self, _schema_attr: &SchemaAttribute) -> bool {
+        self.map
+            .values()
+            .all(|record| record.is_cleared() || record.unlock_at <= record.reset_at)
+    }
+
+    fn to_proto_string_clone_iter(&self) -> Box<dyn Iterator<Item = String> + '_> {
+        Box::new(self.map.iter().map(|(cred_id, record)| {
+            format!(
+                "{}: {} {} {}",
+                uuid_to_proto_string(*cred_id),
+                record.count,
+                record.unlock_at.as_secs(),
+                record.reset_at.as_secs()
+            )
+        }))
+    }
+
+    fn to_scim_value(&self) -> Option<ScimResolveStatus> {
+        None
+    }
+
+    fn to_db_valueset_v2(&self) -> DbValueSetV2 {
+        DbValueSetV2::CredentialSoftLock(self.to_vec_dbvs())
+    }
+
+    fn to_partialvalue_iter(&self) -> Box<dyn Iterator<Item = PartialValue> + '_> {
+        Box::new(self.map.keys().copied().map(PartialValue::Uuid))
+    }
+
+    fn to_value_iter(&self) -> Box<dyn Iterator<Item = Value> + '_> {
+        Box::new(
+            self.map
+                .iter()
+                .map(|(cred_id, record)| Value::CredentialSoftLock(*cred_id, *record)),
+        )
+    }
+
+    fn equal(&self, other: &ValueSet) -> bool {
+        if let Some(other) = other.as_credential_softlock_map() {
+            &self.map == other
+        } else {
+            debug_assert!(false);
+            false
+        }
+    }
+
+    fn merge(&mut self, other: &ValueSet) -> Result<(), OperationError> {
+        if let Some(b) = other.as_credential_softlock_map() {
+            self.merge_map(b);
+            Ok(())
+        } else {
+            debug_assert!(false);
+            Err(OperationError::InvalidValueState)
+        }
+    }
+
+    fn as_credential_softlock_map(&self) -> Option<&BTreeMap<Uuid, CredSoftLockRecord>> {
+        Some(&self.map)
+    }
+
+    fn repl_merge_valueset(&self, older: &ValueSet, trim_cid: &Cid) -> Option<ValueSet> {
+        // Failures may be recorded on any replica concurrently, so rather than taking
+        // the newer set we combine both, keeping the most restrictive state of each
+        // credential.
+        let b = older.as_credential_softlock_map()?;
+
+        let mut vs = Box::new(self.clone());
+        vs.merge_map(b);
+        vs.trim(trim_cid);
+
+        Some(vs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ValueSetCredentialSoftLock;
+    use crate::credential::softlock::CredSoftLockRecord;
+    use crate::prelude::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_valueset_credential_softlock_repl_merge() {
+        let cred_a = Uuid::new_v4();
+        let cred_b = Uuid::new_v4();
+
+        let record_a1 = CredSoftLockRecord {
+            count: 3,
+            reset_at: Duration::from_secs(100),
+            unlock_at: Duration::from_secs(20),
+            updated_at: Duration::from_secs(17),
+        };
+        let record_a2 = CredSoftLockRecord {
+            count: 1,
+            reset_at: Duration::from_secs(100),
+            unlock_at: Duration::from_secs(26),
+            updated_at: Duration::from_secs(25),
+        };
+        let record_b = CredSoftLockRecord {
+            count: 1,
+            reset_at: Duration::from_secs(100),
+            unlock_at: Duration::from_secs(11),
+            updated_at: Duration::from_secs(10),
+        };
+
+        let mut vs_a: ValueSet = ValueSetCredentialSoftLock::new(cred_a, record_a1);
+        vs_a.insert_checked(Value::CredentialSoftLock(cred_b, record_b))
+            .expect("Failed to insert");
+
+        let vs_b: ValueSet = ValueSetCredentialSoftLock::new(cred_a, record_a2);
+
+        let zero_cid = Cid::new_zero();
+
+        let r_vs = vs_b
+            .repl_merge_valueset(&vs_a, &zero_cid)
+            .expect("Failed to merge");
+
+        let map = r_vs
+            .as_credential_softlock_map()
+            .expect("Invalid valueset type");
+
+        // Both credentials are retained, and the concurrent failures are combined.
+        assert_eq!(map.len(), 2);
+        assert_eq!(
+            map.get(&cred_a),
+            Some(&CredSoftLockRecord {
+                count: 3,
+                reset_at: Duration::from_secs(100),
+                unlock_at: Duration::from_secs(26),
+                updated_at: Duration::from_secs(25),
+            })
+        );
+        assert_eq!(map.get(&cred_b), Some(&record_b));
+
+        // An unlock supersedes the failures it was issued after.
+        let cleared = CredSoftLockRecord::cleared(Duration::from_secs(30));
+        let vs_c: ValueSet = ValueSetCredentialSoftLock::new(cred_a, cleared);
+
+        let r_vs = vs_c
+            .repl_merge_valueset(&r_vs, &zero_cid)
+            .expect("Failed to merge");
+
+        let map = r_vs
+            .as_credential_softlock_map()
+            .expect("Invalid valueset type");
+        assert_eq!(map.get(&cred_a), Some(&cleared));
+    }
+
+    #[test]
+    fn test_valueset_credential_softlock_trim() {
+        let cred_a = Uuid::new_v4();
+        let cred_b = Uuid::new_v4();
+
+        let mut vs: ValueSet = ValueSetCredentialSoftLock::new(
+            cred_a,
+            CredSoftLockRecord::cleared(Duration::from_secs(10)),
+        );
+        vs.insert_checked(Value::CredentialSoftLock(
+            cred_b,
+            CredSoftLockRecord {
+                count: 1,
+                reset_at: Duration::from_secs(100),
+                unlock_at: Duration::from_secs(11),
+                updated_at: Duration::from_secs(10),
+            },
+        ))
+        .expect("Failed to insert");
+
+        // The cleared record is outside the window, but the lock has not yet reset.
+        let trim_cid = Cid::new_count(50);
+        vs.trim(&trim_cid);
+        assert!(!vs.contains(&PartialValue::Uuid(cred_a)));
+        assert!(vs.contains(&PartialValue::Uuid(cred_b)));
+
+        let trim_cid = Cid::new_count(101);
+        vs.trim(&trim_cid);
+        assert!(vs.is_empty());
+    }
+}
@@ -7,16 +7,16 @@ use kanidm_proto::constants::*;
 use kanidm_proto::internal::Oauth2ClaimMapJoin;
 use kanidm_proto::oauth2::{
     AccessTokenIntrospectRequest, AccessTokenIntrospectResponse, AccessTokenRequest,
-    AccessTokenResponse, AccessTokenType, AuthorisationResponse, ClientPostAuth, GrantTypeReq,
-    OidcDiscoveryResponse, TokenRevokeRequest,
+    AccessTokenResponse, AccessTokenType, AuthorisationResponse, ClientMetadata, ClientPostAuth,
+    ClientRegistrationResponse, GrantTypeReq, OidcDiscoveryResponse, TokenRevokeRequest,
 };
-use kanidmd_lib::constants::NAME_IDM_ALL_ACCOUNTS;
+use kanidmd_lib::constants::{NAME_IDM_ADMINS, NAME_IDM_ALL_ACCOUNTS};
 use kanidmd_lib::prelude::Attribute;
 use kanidmd_testkit::{
-    assert_no_cache, ADMIN_TEST_PASSWORD, ADMIN_TEST_USER, NOT_ADMIN_TEST_EMAIL,
-    NOT_ADMIN_TEST_PASSWORD, NOT_ADMIN_TEST_USERNAME, TEST_INTEGRATION_RS_DISPLAY,
-    TEST_INTEGRATION_RS_GROUP_ALL, TEST_INTEGRATION_RS_ID, TEST_INTEGRATION_RS_REDIRECT_URL,
-    TEST_INTEGRATION_RS_URL, TEST_INTEGRATION_STATE_VALUE,
+    assert_no_cache, ADMIN_TEST_PASSWORD, ADMIN_TEST_USER, IDM_ADMIN_TEST_PASSWORD,
+    IDM_ADMIN_TEST_USER, NOT_ADMIN_TEST_EMAIL, NOT_ADMIN_TEST_PASSWORD, NOT_ADMIN_TEST_USERNAME,
+    TEST_INTEGRATION_RS_DISPLAY, TEST_INTEGRATION_RS_GROUP_ALL, TEST_INTEGRATION_RS_ID,
+    TEST_INTEGRATION_RS_REDIRECT_URL, TEST_INTEGRATION_RS_URL, TEST_INTEGRATION_STATE_VALUE,
 };
 use oauth2_ext::PkceCodeChallenge;
 use reqwest::header::{HeaderValue, CONTENT_TYPE};
@@ -24,7 +24,10 @@ use std::collections::{BTreeMap, BTreeSet};
 use std::convert::TryFrom;
 use std::str::FromStr;
 use time::OffsetDateTime;
-use uri::{OAUTH2_TOKEN_ENDPOINT, OAUTH2_TOKEN_INTROSPECT_ENDPOINT, OAUTH2_TOKEN_REVOKE_ENDPOINT};
+use uri::{
+    OAUTH2_REGISTRATION_ENDPOINT, OAUTH2_TOKEN_ENDPOINT, OAUTH2_TOKEN_INTROSPECT_ENDPOINT,
+    OAUTH2_TOKEN_REVOKE_ENDPOINT,
+};
 use url::{form_urlencoded::parse as query_parse, Url};
 
 enum AuthMethod {
@@ -375,6 +378,7 @@ async fn test_oauth2_openid_basic_flow_impl(
             form_req.client_post_auth = ClientPostAuth {
                 client_id: Some(TEST_INTEGRATION_RS_ID.to_string()),
                 client_secret: Some(client_secret.clone()),
+                ..Default::default()
             }
         }
         AuthMethod::Basic => {
@@ -427,6 +431,7 @@ async fn test_oauth2_openid_basic_flow_impl(
             intr_request.client_post_auth = ClientPostAuth {
                 client_id: Some(TEST_INTEGRATION_RS_ID.to_string()),
                 client_secret: Some(client_secret.clone()),
+                ..Default::default()
             };
         }
     }
@@ -1232,6 +1237,7 @@ async fn test_oauth2_token_revoke_post_postauth(rsclient: &KanidmClient) {
         client_post_auth: ClientPostAuth {
             client_id: Some("invalid".to_string()),
             client_secret: Some("lolol".to_string()),
+            ..Default::default()
         },
     };
 
@@ -1298,3 +1304,206 @@ async fn test_oauth2_openid_public_consent_cant_be_disabled(rsclient: &KanidmCli
         ClientError::Http(StatusCode::FORBIDDEN, _, _)
     ));
 }
+
+#[kanidmd_testkit::test]
+async fn test_oauth2_dynamic_client_registration(rsclient: &KanidmClient) {
+    let res = rsclient
+        .auth_simple_password(IDM_ADMIN_TEST_USER, IDM_ADMIN_TEST_PASSWORD)
+        .await;
+    assert!(res.is_ok());
+
+    let registrant = "test_registrant";
+
+    rsclient
+        .idm_service_account_create(registrant, "Test Registrant", NAME_IDM_ADMINS)
+        .await
+        .expect("Failed to create service account");
+
+    rsclient
+        .idm_service_account_update_oauth2_registration_scope_map(
+            registrant,
+            NAME_IDM_ALL_ACCOUNTS,
+            vec![OAUTH2_SCOPE_OPENID, OAUTH2_SCOPE_EMAIL],
+        )
+        .await
+        .expect("Failed to set registration scope map");
+
+    let scope_maps = rsclient
+        .idm_service_account_get_attr(registrant, Attribute::OAuth2RegistrationScopeMap.as_str())
+        .await
+        .expect("Failed to get registration scope maps");
+    assert_eq!(scope_maps.map(|v| v.len()), Some(1));
+
+    let read_only_token = rsclient
+        .idm_service_account_generate_api_token(registrant, "read only", None, false, false)
+        .await
+        .expect("Failed to create service account api token");
+
+    let initial_access_token = rsclient
+        .idm_service_account_generate_api_token(registrant, "iat", None, true, false)
+        .await
+        .expect("Failed to create service account api token");
+
+    let client = get_reqwest_client();
+
+    let metadata = ClientMetadata {
+        redirect_uris: vec![Url::parse(TEST_INTEGRATION_RS_REDIRECT_URL).expect("Invalid URL")],
+        client_name: Some(TEST_INTEGRATION_RS_DISPLAY.to_string()),
+        scope: Some(format!("{OAUTH2_SCOPE_OPENID} {OAUTH2_SCOPE_EMAIL}")),
+        ..Default::default()
+    };
+
+    let register = |token: &str| {
+        client
+            .post(rsclient.make_url(OAUTH2_REGISTRATION_ENDPOINT))
+            .bearer_auth(token)
+            .json(&metadata)
+            .send()
+    };
+
+    // A read only api token is not an initial access token.
+    let response = register(&read_only_token)
+        .await
+        .expect("Failed to send registration request.");
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let response = register(&initial_access_token)
+        .await
+        .expect("Failed to send registration request.");
+    assert_eq!(response.status(), StatusCode::CREATED);
+
+    let registration: ClientRegistrationResponse = response
+        .json()
+        .await
+        .expect("Failed to access response body");
+    let client_id = registration.client_id.clone();
+    let registration_access_token = registration
+        .registration_access_token
+        .clone()
+        .expect("Missing registration access token");
+    let registration_url =
+        rsclient.make_url(&format!("{OAUTH2_REGISTRATION_ENDPOINT}/{client_id}"));
+
+    // The registrant is recorded as the manager of the client.
+    let entry = rsclient
+        .idm_oauth2_rs_get(&client_id)
+        .await
+        .expect("Failed to get registered client")
+        .expect("Registered client is missing");
+    assert!(entry
+        .attrs
+        .get(Attribute::EntryManagedBy.as_str())
+        .is_some_and(|managers| managers.iter().any(|m| m.starts_with(registrant))));
+
+    // Only the registration access token can read the configuration of the client.
+    for token in [None, Some(&initial_access_token)] {
+        let mut request = client.get(registration_url.clone());
+        if let Some(token) = token {
+            request = request.bearer_auth(token);
+        }
+        let response = request
+            .send()
+            .await
+            .expect("Failed to send registration read request.");
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    let response = client
+        .get(registration_url.clone())
+        .bearer_auth(&registration_access_token)
+        .send()
+        .await
+        .expect("Failed to send registration read request.");
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let read: ClientRegistrationResponse = response
+        .json()
+        .await
+        .expect("Failed to access response body");
+    assert_eq!(read.client_id, client_id);
+    assert_eq!(read.metadata.redirect_uris, metadata.redirect_uris);
+
+    let narrowed = ClientMetadata {
+        scope: Some(OAUTH2_SCOPE_OPENID.to_string()),
+        ..metadata.clone()
+    };
+
+    let response = client
+        .put(registration_url.clone())
+        .bearer_auth(&registration_access_token)
+        .json(&narrowed)
+        .send()
+        .await
+        .expect("Failed to send registration update request.");
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let updated: ClientRegistrationResponse = response
+        .json()
+        .await
+        .expect("Failed to access response body");
+    assert_eq!(updated.metadata.scope.as_deref(), Some(OAUTH2_SCOPE_OPENID));
+
+    // Once an administrator revokes the registration access token, it can no longer manage
+    // the client.
+    rsclient
+        .idm_oauth2_rs_revoke_registration_access_token(&client_id)
+        .await
+        .expect("Failed to revoke registration access token");
+
+    let response = client
+        .delete(registration_url)
+        .bearer_auth(&registration_access_token)
+        .send()
+        .await
+        .expect("Failed to send registration delete request.");
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    assert!(rsclient
+        .idm_oauth2_rs_get(&client_id)
+        .await
+        .expect("Failed to get registered client")
+        .is_some());
+
+    // A client can delete its own registration.
+    let response = register(&initial_access_token)
+        .await
+        .expect("Failed to send registration request.");
+    assert_eq!(response.status(), StatusCode::CREATED);
+
+    let registration: ClientRegistrationResponse = response
+        .json()
+        .await
+        .expect("Failed to access response body");
+
+    let response = client
+        .delete(rsclient.make_url(&format!(
+            "{OAUTH2_REGISTRATION_ENDPOINT}/{}",
+            registration.client_id
+        )))
+        .bearer_auth(
+            registration
+                .registration_access_token
+                .expect("Missing registration access token"),
+        )
+        .send()
+        .await
+        .expect("Failed to send registration delete request.");
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+    assert!(rsclient
+        .idm_oauth2_rs_get(&registration.client_id)
+        .await
+        .expect("Failed to get registered client")
+        .is_none());
+
+    // Without registration scope maps the service account can no longer register clients.
+    rsclient
+        .idm_service_account_delete_oauth2_registration_scope_map(registrant, NAME_IDM_ALL_ACCOUNTS)
+        .await
+        .expect("Failed to delete registration scope map");
+
+    let response = register(&initial_access_token)
+        .await
+        .expect("Failed to send registration request.");
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
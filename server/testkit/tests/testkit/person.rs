@@ -1,6 +1,8 @@
 use kanidm_client::{ClientError, KanidmClient, StatusCode};
 use kanidm_proto::constants::ATTR_MAIL;
-use kanidmd_testkit::{create_user, ADMIN_TEST_PASSWORD, ADMIN_TEST_USER};
+use kanidmd_testkit::{
+    create_user, ADMIN_TEST_PASSWORD, ADMIN_TEST_USER, IDM_ADMIN_TEST_PASSWORD, IDM_ADMIN_TEST_USER,
+};
 use serde_json::Value;
 
 #[kanidmd_testkit::test]
@@ -51,3 +53,34 @@ async fn test_v1_person_id_ssh_pubkeys_post(rsclient: &KanidmClient) {
         ClientError::Http(StatusCode::BAD_REQUEST, _, _)
     ));
 }
+
+#[kanidmd_testkit::test]
+async fn test_v1_person_credential_lock(rsclient: &KanidmClient) {
+    let res = rsclient
+        .auth_simple_password(IDM_ADMIN_TEST_USER, IDM_ADMIN_TEST_PASSWORD)
+        .await;
+    assert!(res.is_ok());
+
+    rsclient
+        .idm_person_account_create("foo", "Foo")
+        .await
+        .expect("Failed to create person");
+
+    // Nothing has failed to authenticate, so nothing is locked.
+    let locked = rsclient
+        .idm_person_account_list_credential_locked()
+        .await
+        .expect("Failed to list locked persons");
+    assert!(locked.is_empty());
+
+    let status = rsclient
+        .idm_person_account_get_credential_lock_status("foo")
+        .await
+        .expect("Failed to get lock status");
+    assert!(status.is_empty());
+
+    rsclient
+        .idm_person_account_credential_unlock("foo")
+        .await
+        .expect("Failed to unlock person");
+}
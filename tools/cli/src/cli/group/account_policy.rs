@@ -77,6 +77,54 @@ impl GroupAccountPolicyOpt {
                         .print_message("Successfully reset password minimum length.");
                 }
             }
+            GroupAccountPolicyOpt::PasswordHistoryLength { name, length } => {
+                let client = opt.to_client(OpType::Write).await;
+                if let Err(e) = client
+                    .group_account_policy_password_history_length_set(name, *length)
+                    .await
+                {
+                    handle_group_account_policy_error(e, opt.output_mode);
+                } else {
+                    opt.output_mode
+                        .print_message("Updated password history length.");
+                }
+            }
+            GroupAccountPolicyOpt::ResetPasswordHistoryLength { name } => {
+                let client = opt.to_client(OpType::Write).await;
+                if let Err(e) = client
+                    .group_account_policy_password_history_length_reset(name)
+                    .await
+                {
+                    handle_group_account_policy_error(e, opt.output_mode);
+                } else {
+                    opt.output_mode
+                        .print_message("Successfully reset password history length.");
+                }
+            }
+            GroupAccountPolicyOpt::PasswordMaximumAge { name, age } => {
+                let client = opt.to_client(OpType::Write).await;
+                if let Err(e) = client
+                    .group_account_policy_password_maximum_age_set(name, *age)
+                    .await
+                {
+                    handle_group_account_policy_error(e, opt.output_mode);
+                } else {
+                    opt.output_mode
+                        .print_message("Updated password maximum age.");
+                }
+            }
+            GroupAccountPolicyOpt::ResetPasswordMaximumAge { name } => {
+                let client = opt.to_client(OpType::Write).await;
+                if let Err(e) = client
+                    .group_account_policy_password_maximum_age_reset(name)
+                    .await
+                {
+                    handle_group_account_policy_error(e, opt.output_mode);
+                } else {
+                    opt.output_mode
+                        .print_message("Successfully reset password maximum age.");
+                }
+            }
             GroupAccountPolicyOpt::PrivilegedSessionExpiry { name, expiry } => {
                 let client = opt.to_client(OpType::Write).await;
                 if let Err(e) = client
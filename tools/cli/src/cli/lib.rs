@@ -109,6 +109,27 @@ impl SelfOpt {
                     Err(e) => handle_client_error(e, opt.output_mode),
                 }
             }
+            SelfOpt::ListConsent => {
+                let client = opt.to_client(OpType::Read).await;
+                match client.idm_self_oauth2_consent_list().await {
+                    Ok(consents) => {
+                        for consent in consents {
+                            opt.output_mode.print_message(consent);
+                        }
+                    }
+                    Err(e) => handle_client_error(e, opt.output_mode),
+                }
+            }
+            SelfOpt::RevokeConsent(nopt) => {
+                let client = opt.to_client(OpType::Write).await;
+                match client
+                    .idm_self_oauth2_consent_revoke(nopt.name.as_str())
+                    .await
+                {
+                    Ok(_) => opt.output_mode.print_message("Success"),
+                    Err(e) => handle_client_error(e, opt.output_mode),
+                }
+            }
             SelfOpt::IdentifyUser => {
                 let client = opt.to_client(OpType::Write).await;
                 let whoami_response = match client.whoami().await {
@@ -1,8 +1,9 @@
 use crate::OpType;
 use crate::{handle_client_error, Oauth2Opt, OutputMode};
-use crate::{KanidmClientParser, Oauth2ClaimMapJoin};
+use crate::{KanidmClientParser, Oauth2ClaimMapJoin, Oauth2EncryptedResponseAlg};
 use anyhow::{Context, Error};
 use kanidm_proto::internal::{ImageValue, Oauth2ClaimMapJoin as ProtoOauth2ClaimMapJoin};
+use kanidm_proto::oauth2::IdTokenEncryptionAlg;
 use std::fs::read;
 use std::process::exit;
 
@@ -498,6 +499,212 @@ impl Oauth2Opt {
                     Err(e) => handle_client_error(e, opt.output_mode),
                 }
             }
+            Oauth2Opt::SetBackchannelLogoutUrl { nopt, url } => {
+                let client = opt.to_client(OpType::Write).await;
+                match client
+                    .idm_oauth2_rs_set_backchannel_logout_uri(nopt.name.as_str(), &url)
+                    .await
+                {
+                    Ok(_) => opt.output_mode.print_message("Success"),
+                    Err(e) => handle_client_error(e, opt.output_mode),
+                }
+            }
+            Oauth2Opt::RemoveBackchannelLogoutUrl(nopt) => {
+                let client = opt.to_client(OpType::Write).await;
+                match client
+                    .idm_oauth2_rs_remove_backchannel_logout_uri(nopt.name.as_str())
+                    .await
+                {
+                    Ok(_) => opt.output_mode.print_message("Success"),
+                    Err(e) => handle_client_error(e, opt.output_mode),
+                }
+            }
+            Oauth2Opt::SetJwks { nopt, path } => {
+                let jwks = std::fs::read_to_string(path).unwrap_or_else(|e| {
+                    error!("Could not read JSON web key set file {path:?}: {e:?}");
+                    exit(1);
+                });
+
+                let client = opt.to_client(OpType::Write).await;
+                match client
+                    .idm_oauth2_rs_set_jwks(nopt.name.as_str(), &jwks)
+                    .await
+                {
+                    Ok(_) => opt.output_mode.print_message("Success"),
+                    Err(e) => handle_client_error(e, opt.output_mode),
+                }
+            }
+            Oauth2Opt::RemoveJwks(nopt) => {
+                let client = opt.to_client(OpType::Write).await;
+                match client.idm_oauth2_rs_remove_jwks(nopt.name.as_str()).await {
+                    Ok(_) => opt.output_mode.print_message("Success"),
+                    Err(e) => handle_client_error(e, opt.output_mode),
+                }
+            }
+            Oauth2Opt::EnableRequirePar(nopt) => {
+                let client = opt.to_client(OpType::Write).await;
+                match client
+                    .idm_oauth2_rs_enable_require_par(nopt.name.as_str())
+                    .await
+                {
+                    Ok(_) => opt.output_mode.print_message("Success"),
+                    Err(e) => handle_client_error(e, opt.output_mode),
+                }
+            }
+            Oauth2Opt::DisableRequirePar(nopt) => {
+                let client = opt.to_client(OpType::Write).await;
+                match client
+                    .idm_oauth2_rs_disable_require_par(nopt.name.as_str())
+                    .await
+                {
+                    Ok(_) => opt.output_mode.print_message("Success"),
+                    Err(e) => handle_client_error(e, opt.output_mode),
+                }
+            }
+            Oauth2Opt::EnableRequireDpop(nopt) => {
+                let client = opt.to_client(OpType::Write).await;
+                match client
+                    .idm_oauth2_rs_enable_require_dpop(nopt.name.as_str())
+                    .await
+                {
+                    Ok(_) => opt.output_mode.print_message("Success"),
+                    Err(e) => handle_client_error(e, opt.output_mode),
+                }
+            }
+            Oauth2Opt::DisableRequireDpop(nopt) => {
+                let client = opt.to_client(OpType::Write).await;
+                match client
+                    .idm_oauth2_rs_disable_require_dpop(nopt.name.as_str())
+                    .await
+                {
+                    Ok(_) => opt.output_mode.print_message("Success"),
+                    Err(e) => handle_client_error(e, opt.output_mode),
+                }
+            }
+            Oauth2Opt::SetTlsClientAuthSubjectDn { nopt, subject_dn } => {
+                let client = opt.to_client(OpType::Write).await;
+                match client
+                    .idm_oauth2_rs_set_tls_client_auth_subject_dn(nopt.name.as_str(), &subject_dn)
+                    .await
+                {
+                    Ok(_) => opt.output_mode.print_message("Success"),
+                    Err(e) => handle_client_error(e, opt.output_mode),
+                }
+            }
+            Oauth2Opt::RemoveTlsClientAuthSubjectDn(nopt) => {
+                let client = opt.to_client(OpType::Write).await;
+                match client
+                    .idm_oauth2_rs_remove_tls_client_auth_subject_dn(nopt.name.as_str())
+                    .await
+                {
+                    Ok(_) => opt.output_mode.print_message("Success"),
+                    Err(e) => handle_client_error(e, opt.output_mode),
+                }
+            }
+            Oauth2Opt::AddTlsClientCertificate { nopt, path } => {
+                let pem_data = std::fs::read_to_string(path).unwrap_or_else(|e| {
+                    error!("Could not read certificate file {path:?}: {e:?}");
+                    exit(1);
+                });
+
+                let client = opt.to_client(OpType::Write).await;
+                match client
+                    .idm_oauth2_rs_add_tls_client_certificate(nopt.name.as_str(), &pem_data)
+                    .await
+                {
+                    Ok(_) => opt.output_mode.print_message("Success"),
+                    Err(e) => handle_client_error(e, opt.output_mode),
+                }
+            }
+            Oauth2Opt::RemoveTlsClientCertificates(nopt) => {
+                let client = opt.to_client(OpType::Write).await;
+                match client
+                    .idm_oauth2_rs_remove_tls_client_certificates(nopt.name.as_str())
+                    .await
+                {
+                    Ok(_) => opt.output_mode.print_message("Success"),
+                    Err(e) => handle_client_error(e, opt.output_mode),
+                }
+            }
+            Oauth2Opt::SetEncryptedResponseAlg { nopt, alg } => {
+                let alg = match alg {
+                    Oauth2EncryptedResponseAlg::RsaOaep => IdTokenEncryptionAlg::RsaOaep,
+                    Oauth2EncryptedResponseAlg::EcdhEsA256Kw => IdTokenEncryptionAlg::EcdhEsA256Kw,
+                };
+
+                let client = opt.to_client(OpType::Write).await;
+                match client
+                    .idm_oauth2_rs_set_encrypted_response_alg(nopt.name.as_str(), alg)
+                    .await
+                {
+                    Ok(_) => opt.output_mode.print_message("Success"),
+                    Err(e) => handle_client_error(e, opt.output_mode),
+                }
+            }
+            Oauth2Opt::RemoveEncryptedResponseAlg(nopt) => {
+                let client = opt.to_client(OpType::Write).await;
+                match client
+                    .idm_oauth2_rs_remove_encrypted_response_alg(nopt.name.as_str())
+                    .await
+                {
+                    Ok(_) => opt.output_mode.print_message("Success"),
+                    Err(e) => handle_client_error(e, opt.output_mode),
+                }
+            }
+            Oauth2Opt::AddAuthorisationDetailsType { name, details_type } => {
+                let client = opt.to_client(OpType::Write).await;
+                match client
+                    .idm_oauth2_rs_add_authorisation_details_type(name, details_type)
+                    .await
+                {
+                    Ok(_) => opt.output_mode.print_message("Success"),
+                    Err(e) => handle_client_error(e, opt.output_mode),
+                }
+            }
+            Oauth2Opt::RemoveAuthorisationDetailsType { name, details_type } => {
+                let client = opt.to_client(OpType::Write).await;
+                match client
+                    .idm_oauth2_rs_remove_authorisation_details_type(name, details_type)
+                    .await
+                {
+                    Ok(_) => opt.output_mode.print_message("Success"),
+                    Err(e) => handle_client_error(e, opt.output_mode),
+                }
+            }
+            Oauth2Opt::EnableCiba(nopt) => {
+                let client = opt.to_client(OpType::Write).await;
+                match client.idm_oauth2_rs_enable_ciba(nopt.name.as_str()).await {
+                    Ok(_) => opt.output_mode.print_message("Success"),
+                    Err(e) => handle_client_error(e, opt.output_mode),
+                }
+            }
+            Oauth2Opt::DisableCiba(nopt) => {
+                let client = opt.to_client(OpType::Write).await;
+                match client.idm_oauth2_rs_disable_ciba(nopt.name.as_str()).await {
+                    Ok(_) => opt.output_mode.print_message("Success"),
+                    Err(e) => handle_client_error(e, opt.output_mode),
+                }
+            }
+            Oauth2Opt::EnablePairwiseSubject(nopt) => {
+                let client = opt.to_client(OpType::Write).await;
+                match client
+                    .idm_oauth2_rs_enable_pairwise_subject(nopt.name.as_str())
+                    .await
+                {
+                    Ok(_) => opt.output_mode.print_message("Success"),
+                    Err(e) => handle_client_error(e, opt.output_mode),
+                }
+            }
+            Oauth2Opt::DisablePairwiseSubject(nopt) => {
+                let client = opt.to_client(OpType::Write).await;
+                match client
+                    .idm_oauth2_rs_disable_pairwise_subject(nopt.name.as_str())
+                    .await
+                {
+                    Ok(_) => opt.output_mode.print_message("Success"),
+                    Err(e) => handle_client_error(e, opt.output_mode),
+                }
+            }
         }
     }
 }
@@ -568,6 +568,37 @@ impl AccountCredential {
                     }
                 }
             }
+            AccountCredential::LockStatus(aopt) => {
+                let client = opt.to_client(OpType::Read).await;
+                match client
+                    .idm_person_account_get_credential_lock_status(aopt.aopts.account_id.as_str())
+                    .await
+                {
+                    Ok(statuses) => {
+                        if statuses.is_empty() {
+                            println!("No credentials have recorded authentication failures.");
+                        }
+                        for status in statuses {
+                            println!("{status}");
+                        }
+                    }
+                    Err(e) => handle_client_error(e, opt.output_mode),
+                }
+            }
+            AccountCredential::LockList => {
+                let client = opt.to_client(OpType::Read).await;
+                match client.idm_person_account_list_credential_locked().await {
+                    Ok(accounts) => {
+                        if accounts.is_empty() {
+                            println!("No persons have locked credentials.");
+                        }
+                        for account in accounts {
+                            println!("{account}");
+                        }
+                    }
+                    Err(e) => handle_client_error(e, opt.output_mode),
+                }
+            }
             AccountCredential::Update(aopt) => {
                 let client = opt.to_client(OpType::Write).await;
                 match client
@@ -692,6 +723,16 @@ impl AccountCredential {
             } => {
                 let client = opt.to_client(OpType::Write).await;
 
+                if datetime == "now" {
+                    if let Err(e) = client
+                        .idm_person_account_credential_unlock(account_id.as_str())
+                        .await
+                    {
+                        handle_client_error(e, opt.output_mode);
+                        return;
+                    }
+                }
+
                 let validity = match try_expire_at_from_string(datetime.as_str()) {
                     Ok(val) => val,
                     Err(()) => return,
@@ -8,8 +8,8 @@ use time::OffsetDateTime;
 
 use crate::{
     handle_client_error, AccountSsh, AccountUserAuthToken, AccountValidity, KanidmClientParser,
-    OutputMode, ServiceAccountApiToken, ServiceAccountCredential, ServiceAccountOpt,
-    ServiceAccountPosix,
+    OutputMode, ServiceAccountApiToken, ServiceAccountCredential, ServiceAccountOauth2Registration,
+    ServiceAccountOpt, ServiceAccountPosix,
 };
 use time::format_description::well_known::Rfc3339;
 
@@ -147,6 +147,39 @@ impl ServiceAccountOpt {
                     }
                 }
             }, // End ServiceAccountOpt::ApiToken
+            ServiceAccountOpt::Oauth2Registration { commands } => match commands {
+                ServiceAccountOauth2Registration::UpdateScopeMap {
+                    aopts,
+                    group,
+                    scopes,
+                } => {
+                    let client = opt.to_client(OpType::Write).await;
+                    match client
+                        .idm_service_account_update_oauth2_registration_scope_map(
+                            aopts.account_id.as_str(),
+                            group.as_str(),
+                            scopes.iter().map(String::as_str).collect(),
+                        )
+                        .await
+                    {
+                        Ok(()) => println!("Success"),
+                        Err(e) => handle_client_error(e, opt.output_mode),
+                    }
+                }
+                ServiceAccountOauth2Registration::DeleteScopeMap { aopts, group } => {
+                    let client = opt.to_client(OpType::Write).await;
+                    match client
+                        .idm_service_account_delete_oauth2_registration_scope_map(
+                            aopts.account_id.as_str(),
+                            group.as_str(),
+                        )
+                        .await
+                    {
+                        Ok(()) => println!("Success"),
+                        Err(e) => handle_client_error(e, opt.output_mode),
+                    }
+                }
+            }, // End ServiceAccountOpt::Oauth2Registration
             ServiceAccountOpt::Posix { commands } => match commands {
                 ServiceAccountPosix::Show(aopt) => {
                     let client = opt.to_client(OpType::Read).await;
@@ -415,6 +415,14 @@ pub(crate) async fn process_auth_state(
                 error!("Authentication Denied: {:?}", reason);
                 std::process::exit(1);
             }
+            AuthState::CredentialUpdateRequired(intent) => {
+                error!("Your password has expired and must be changed before you can login.");
+                println!(
+                    "Run this command: kanidm person credential use-reset-token {}",
+                    intent.token
+                );
+                std::process::exit(1);
+            }
             _ => {
                 error!("Error in authentication phase: invalid authstate");
                 std::process::exit(1);
@@ -485,6 +493,12 @@ pub(crate) async fn process_auth_state(
                 }
             };
 
+            if let Some(password_expiry) = tonk.password_expiry {
+                println!(
+                    "Your password expires at {password_expiry}, please change it before then."
+                );
+            }
+
             let spn = tonk.spn;
             // Return the original jws
             (spn, jwsc)
@@ -150,6 +150,12 @@ pub enum GroupAccountPolicyOpt {
     /// Set the minimum character length of passwords for accounts.
     #[clap(name = "password-minimum-length")]
     PasswordMinimumLength { name: String, length: u32 },
+    /// Set the number of recent passwords that accounts may not reuse.
+    #[clap(name = "password-history-length")]
+    PasswordHistoryLength { name: String, length: u32 },
+    /// Set the maximum age in seconds of a password before it must be changed.
+    #[clap(name = "password-maximum-age")]
+    PasswordMaximumAge { name: String, age: u32 },
 
     /// Set the maximum time for privilege session expiry in seconds.
     #[clap(name = "privilege-expiry")]
@@ -189,6 +195,12 @@ pub enum GroupAccountPolicyOpt {
     /// Reset the minimum character length of passwords to its default value.
     #[clap(name = "reset-password-minimum-length")]
     ResetPasswordMinimumLength { name: String },
+    /// Reset the password history length to its default value, allowing passwords to be reused.
+    #[clap(name = "reset-password-history-length")]
+    ResetPasswordHistoryLength { name: String },
+    /// Reset the password maximum age to its default value, allowing passwords to never expire.
+    #[clap(name = "reset-password-maximum-age")]
+    ResetPasswordMaximumAge { name: String },
     /// Reset the maximum time for privilege session expiry to its default value.
     #[clap(name = "reset-privilege-expiry")]
     ResetPrivilegedSessionExpiry { name: String },
@@ -414,7 +426,16 @@ pub enum AccountCredential {
         /// must exist on the account for the reset to be sent.
         alternate_email: Option<String>,
     },
+    /// Show the failed authentication counters and lock state of each of this
+    /// account's credentials.
+    #[clap(name = "lock-status")]
+    LockStatus(AccountNamedOpt),
+    /// List the persons that have at least one credential that is currently locked.
+    #[clap(name = "lock-list")]
+    LockList,
     /// Reset the softlocks on this account. This applies to all credentials of the account.
+    /// When reset "now", the failed authentication counters and lock state of each
+    /// credential are also cleared.
     #[clap(name = "softlock-reset")]
     SoftlockReset {
         account_id: String,
@@ -667,6 +688,29 @@ pub enum ServiceAccountApiToken {
     },
 }
 
+#[derive(Debug, Subcommand, Clone)]
+pub enum ServiceAccountOauth2Registration {
+    /// Update or add a mapping from a group to the scopes it is granted by OAuth2 clients
+    /// that are registered with this service account's api tokens.
+    #[clap(name = "update-scope-map", visible_aliases=&["create-scope-map"])]
+    UpdateScopeMap {
+        #[clap(flatten)]
+        aopts: AccountCommonOpt,
+        #[clap(name = "group")]
+        group: String,
+        #[clap(name = "scopes", required = true, num_args=1.. )]
+        scopes: Vec<String>,
+    },
+    /// Remove a mapping from a group to the scopes it is granted by registered OAuth2 clients.
+    #[clap(name = "delete-scope-map")]
+    DeleteScopeMap {
+        #[clap(flatten)]
+        aopts: AccountCommonOpt,
+        #[clap(name = "group")]
+        group: String,
+    },
+}
+
 #[derive(Debug, Args, Clone)]
 pub struct ServiceAccountUpdateOpt {
     #[clap(flatten)]
@@ -710,6 +754,13 @@ pub enum ServiceAccountOpt {
         #[clap(subcommand)]
         commands: ServiceAccountApiToken,
     },
+    /// Manage the OAuth2 clients that can be registered with this service account's api
+    /// tokens.
+    #[clap(name = "oauth2-registration")]
+    Oauth2Registration {
+        #[clap(subcommand)]
+        commands: ServiceAccountOauth2Registration,
+    },
     /// Manage posix extensions for this service account allowing access to unix/linux systems
     #[clap(name = "posix")]
     Posix {
@@ -823,6 +874,12 @@ pub enum SelfOpt {
     IdentifyUser,
     /// Show the current authenticated user's identity
     Whoami,
+    /// List the OAuth2 applications you have shared your information with
+    #[clap(name = "list-consent")]
+    ListConsent,
+    /// Revoke your consent to an OAuth2 application, and sign out of all of its sessions
+    #[clap(name = "revoke-consent")]
+    RevokeConsent(Named),
 }
 
 #[derive(Debug, Args, Clone)]
@@ -886,6 +943,31 @@ impl ValueEnum for Oauth2ClaimMapJoin {
     }
 }
 
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Oauth2EncryptedResponseAlg {
+    RsaOaep,
+    EcdhEsA256Kw,
+}
+
+impl Oauth2EncryptedResponseAlg {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::RsaOaep => "RSA-OAEP",
+            Self::EcdhEsA256Kw => "ECDH-ES+A256KW",
+        }
+    }
+}
+
+impl ValueEnum for Oauth2EncryptedResponseAlg {
+    fn value_variants<'a>() -> &'a [Self] {
+        &[Self::RsaOaep, Self::EcdhEsA256Kw]
+    }
+
+    fn to_possible_value(&self) -> Option<PossibleValue> {
+        Some(self.as_str().into())
+    }
+}
+
 #[derive(Debug, Subcommand, Clone)]
 pub enum Oauth2Opt {
     #[clap(name = "list")]
@@ -1089,6 +1171,112 @@ pub enum Oauth2Opt {
     /// Enable the regular user consent prompt.
     #[clap(name = "enable-consent-prompt")]
     EnableConsentPrompt(Named),
+    /// Set the URL that signed logout tokens are sent to when a session of this
+    /// client is ended, as described by OpenID Connect Back-Channel Logout.
+    #[clap(name = "set-backchannel-logout-url")]
+    SetBackchannelLogoutUrl {
+        #[clap(flatten)]
+        nopt: Named,
+        #[clap(name = "url")]
+        url: Url,
+    },
+    /// Stop sending logout tokens to this client.
+    #[clap(name = "remove-backchannel-logout-url")]
+    RemoveBackchannelLogoutUrl(Named),
+    /// Set the JSON web key set of public keys this client signs `private_key_jwt`
+    /// client assertions with.
+    #[clap(name = "set-jwks")]
+    SetJwks {
+        #[clap(flatten)]
+        nopt: Named,
+        #[clap(name = "jwks-json-file")]
+        /// A local file path to the JSON web key set.
+        path: PathBuf,
+    },
+    /// Remove the JSON web key set of this client.
+    #[clap(name = "remove-jwks")]
+    RemoveJwks(Named),
+    /// Require this client to push its authorisation requests to the server before
+    /// redirecting the user, as described by RFC9126.
+    #[clap(name = "enable-require-par")]
+    EnableRequirePar(Named),
+    /// Allow this client to send authorisation requests directly through the user's browser.
+    #[clap(name = "disable-require-par")]
+    DisableRequirePar(Named),
+    /// Require that tokens issued to this client are bound to a key held by the client
+    /// with DPoP proofs, as described by RFC9449.
+    #[clap(name = "enable-require-dpop")]
+    EnableRequireDpop(Named),
+    /// Allow this client to be issued bearer tokens that are not bound to a DPoP key.
+    #[clap(name = "disable-require-dpop")]
+    DisableRequireDpop(Named),
+    /// Allow this client to authenticate with a certificate issued by the OAuth2 client CA
+    /// that has this subject DN, as described by RFC8705 `tls_client_auth`.
+    #[clap(name = "set-tls-client-auth-subject-dn")]
+    SetTlsClientAuthSubjectDn {
+        #[clap(flatten)]
+        nopt: Named,
+        #[clap(name = "subject-dn")]
+        subject_dn: String,
+    },
+    /// Stop this client authenticating with a CA issued certificate.
+    #[clap(name = "remove-tls-client-auth-subject-dn")]
+    RemoveTlsClientAuthSubjectDn(Named),
+    /// Register a self signed certificate that this client may authenticate with, as
+    /// described by RFC8705 `self_signed_tls_client_auth`.
+    #[clap(name = "add-tls-client-certificate")]
+    AddTlsClientCertificate {
+        #[clap(flatten)]
+        nopt: Named,
+        #[clap(name = "pem-file")]
+        /// A local file path to the PEM encoded certificate.
+        path: PathBuf,
+    },
+    /// Remove all self signed certificates registered to this client.
+    #[clap(name = "remove-tls-client-certificates")]
+    RemoveTlsClientCertificates(Named),
+    /// Encrypt ID tokens and userinfo responses to a public key in the JSON web key set
+    /// of this client. Content is encrypted with A256GCM.
+    #[clap(name = "set-encrypted-response-alg")]
+    SetEncryptedResponseAlg {
+        #[clap(flatten)]
+        nopt: Named,
+        #[clap(name = "alg", value_enum)]
+        alg: Oauth2EncryptedResponseAlg,
+    },
+    /// Stop encrypting ID tokens and userinfo responses to this client.
+    #[clap(name = "remove-encrypted-response-alg")]
+    RemoveEncryptedResponseAlg(Named),
+    /// Allow this client to request rich authorisation details of this type. The
+    /// type is defined by the resource server that consumes the details.
+    #[clap(name = "add-authorisation-details-type")]
+    AddAuthorisationDetailsType {
+        name: String,
+        #[clap(name = "type")]
+        details_type: String,
+    },
+    /// Stop this client from requesting rich authorisation details of this type.
+    #[clap(name = "remove-authorisation-details-type")]
+    RemoveAuthorisationDetailsType {
+        name: String,
+        #[clap(name = "type")]
+        details_type: String,
+    },
+    /// Allow this client to start sign ins with client initiated backchannel authentication.
+    /// The user approves these requests from their applications page.
+    #[clap(name = "enable-ciba")]
+    EnableCiba(Named),
+    /// Stop this client from using client initiated backchannel authentication.
+    #[clap(name = "disable-ciba")]
+    DisableCiba(Named),
+    /// Issue this client subject identifiers that are unique to it, so that it can not
+    /// correlate its users with other clients. Changing this changes the subject of every
+    /// user of the client.
+    #[clap(name = "enable-pairwise-subject")]
+    EnablePairwiseSubject(Named),
+    /// Issue this client the uuid of the user as their subject identifier.
+    #[clap(name = "disable-pairwise-subject")]
+    DisablePairwiseSubject(Named),
 }
 
 #[derive(Args, Debug, Clone)]
@@ -68,7 +68,17 @@ pub struct DeviceAuthorizationResponse {
 pub enum PamAuthResponse {
     Unknown,
     Success,
+    /// Authentication succeeded, and PAM should show this message to the user. For
+    /// example, a warning that their password will soon expire.
+    SuccessWithMessage {
+        msg: String,
+    },
     Denied,
+    /// Authentication was denied, and PAM should show this message to the user. For
+    /// example, that their password has expired.
+    DeniedWithMessage {
+        msg: String,
+    },
     Password,
     DeviceAuthorizationGrant {
         data: DeviceAuthorizationResponse,
@@ -169,12 +169,32 @@ pub fn sm_authenticate_connected<P: PamHandler>(
             } => {
                 return PamResultCode::PAM_SUCCESS;
             }
+            ClientResponse::PamAuthenticateStepResponse {
+                response: PamAuthResponse::SuccessWithMessage { msg },
+                session_id: _,
+            } => {
+                // The authentication has already succeeded, so failing to show
+                // the message must not fail it.
+                if let Err(err) = pamh.message(msg.as_str()) {
+                    debug!(?err, "Message prompt failed");
+                }
+                return PamResultCode::PAM_SUCCESS;
+            }
             ClientResponse::PamAuthenticateStepResponse {
                 response: PamAuthResponse::Denied,
                 session_id: _,
             } => {
                 return PamResultCode::PAM_AUTH_ERR;
             }
+            ClientResponse::PamAuthenticateStepResponse {
+                response: PamAuthResponse::DeniedWithMessage { msg },
+                session_id: _,
+            } => {
+                if let Err(err) = pamh.message(msg.as_str()) {
+                    debug!(?err, "Message prompt failed");
+                }
+                return PamResultCode::PAM_AUTH_ERR;
+            }
             ClientResponse::PamAuthenticateStepResponse {
                 response: PamAuthResponse::Unknown,
                 session_id: _,
@@ -114,6 +114,14 @@ async fn main() -> ExitCode {
                             println!("auth success!");
                             break;
                         }
+                        ClientResponse::PamAuthenticateStepResponse {
+                            response: PamAuthResponse::SuccessWithMessage { msg },
+                            session_id: _,
+                        } => {
+                            println!("{msg}");
+                            println!("auth success!");
+                            break;
+                        }
                         ClientResponse::PamAuthenticateStepResponse {
                             response: PamAuthResponse::Denied,
                             session_id: _,
@@ -121,6 +129,14 @@ async fn main() -> ExitCode {
                             println!("auth failed!");
                             break;
                         }
+                        ClientResponse::PamAuthenticateStepResponse {
+                            response: PamAuthResponse::DeniedWithMessage { msg },
+                            session_id: _,
+                        } => {
+                            println!("{msg}");
+                            println!("auth failed!");
+                            break;
+                        }
                         ClientResponse::PamAuthenticateStepResponse {
                             response: PamAuthResponse::Unknown,
                             session_id: _,
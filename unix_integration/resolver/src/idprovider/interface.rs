@@ -198,8 +198,16 @@ impl Into<PamAuthResponse> for AuthRequest {
 
 pub enum AuthResult {
     Success,
-    SuccessUpdate { new_token: UserToken },
+    SuccessUpdate {
+        new_token: UserToken,
+        /// A message to display to the user, such as a password expiry warning.
+        message: Option<String>,
+    },
     Denied,
+    DeniedWithMessage {
+        /// A message to display to the user, such as a password expiry notice.
+        message: String,
+    },
     Next(AuthRequest),
 }
 
@@ -142,6 +142,7 @@ impl From<UnixUserToken> for UserToken {
             groups,
             sshkeys,
             valid,
+            password_expiry: _,
         } = value;
 
         let sshkeys = sshkeys.iter().map(|s| s.to_string()).collect();
@@ -504,6 +505,12 @@ impl IdProvider for KanidmProvider {
 
                 match auth_result {
                     Ok(Some(n_tok)) => {
+                        let message = n_tok.password_expiry.map(|password_expiry| {
+                            format!(
+                                "Your password expires at {password_expiry}, please change it before then."
+                            )
+                        });
+
                         let mut new_token = UserToken::from(n_tok);
 
                         // Update any keys that may have been in the db in the current
@@ -520,7 +527,7 @@ impl IdProvider for KanidmProvider {
                             &inner.hmac_key,
                         );
 
-                        Ok(AuthResult::SuccessUpdate { new_token })
+                        Ok(AuthResult::SuccessUpdate { new_token, message })
                     }
                     Ok(None) => {
                         // TODO: i'm not a huge fan of this rn, but currently the way we handle
@@ -533,6 +540,14 @@ impl IdProvider for KanidmProvider {
                         // at the start of the auth and checking for account validity instead.
                         Ok(AuthResult::Denied)
                     }
+                    Err(ClientError::Http(_, Some(OperationError::PasswordExpired), opid)) => {
+                        info!("password has expired - eventid {}", opid);
+                        Ok(AuthResult::DeniedWithMessage {
+                            message:
+                                "Your password has expired, please change it before logging in."
+                                    .to_string(),
+                        })
+                    }
                     Err(ClientError::Transport(err)) => {
                         error!(?err, "A client transport error occurred.");
                         Err(IdpError::Transport)
@@ -639,7 +654,10 @@ impl IdProvider for KanidmProvider {
 
                     // TODO: We can update the token here and then do lockouts.
 
-                    Ok(AuthResult::SuccessUpdate { new_token })
+                    Ok(AuthResult::SuccessUpdate {
+                        new_token,
+                        message: None,
+                    })
                 } else {
                     Ok(AuthResult::Denied)
                 }
@@ -1233,7 +1233,7 @@ impl Resolver {
                     Ok(AuthResult::SuccessUpdate { .. } | AuthResult::Success) => {
                         info!(?account_id, "Authentication Success");
                     }
-                    Ok(AuthResult::Denied) => {
+                    Ok(AuthResult::Denied | AuthResult::DeniedWithMessage { .. }) => {
                         info!(?account_id, "Authentication Denied");
                     }
                     Ok(AuthResult::Next(_)) => {
@@ -1279,7 +1279,7 @@ impl Resolver {
                     Ok(AuthResult::SuccessUpdate { .. } | AuthResult::Success) => {
                         info!(?account_id, "Authentication Success");
                     }
-                    Ok(AuthResult::Denied) => {
+                    Ok(AuthResult::Denied | AuthResult::DeniedWithMessage { .. }) => {
                         info!(?account_id, "Authentication Denied");
                     }
                     Ok(AuthResult::Next(_)) => {
@@ -1331,18 +1331,29 @@ impl Resolver {
                 *auth_session = AuthSession::Success;
                 Ok(PamAuthResponse::Success)
             }
-            Ok(AuthResult::SuccessUpdate { mut new_token }) => {
+            Ok(AuthResult::SuccessUpdate {
+                mut new_token,
+                message,
+            }) => {
                 self.set_cache_usertoken(&mut new_token, hsm_lock.deref_mut())
                     .await?;
                 *auth_session = AuthSession::Success;
 
-                Ok(PamAuthResponse::Success)
+                match message {
+                    Some(msg) => Ok(PamAuthResponse::SuccessWithMessage { msg }),
+                    None => Ok(PamAuthResponse::Success),
+                }
             }
             Ok(AuthResult::Denied) => {
                 *auth_session = AuthSession::Denied;
 
                 Ok(PamAuthResponse::Denied)
             }
+            Ok(AuthResult::DeniedWithMessage { message }) => {
+                *auth_session = AuthSession::Denied;
+
+                Ok(PamAuthResponse::DeniedWithMessage { msg: message })
+            }
             Ok(AuthResult::Next(req)) => Ok(req.into()),
             Err(IdpError::NotFound) => {
                 *auth_session = AuthSession::Denied;
@@ -1407,8 +1418,10 @@ impl Resolver {
                 auth_session
             }
             (_, PamAuthResponse::Unknown) => return Ok(None),
-            (_, PamAuthResponse::Denied) => return Ok(Some(false)),
-            (_, PamAuthResponse::Success) => {
+            (_, PamAuthResponse::Denied | PamAuthResponse::DeniedWithMessage { .. }) => {
+                return Ok(Some(false))
+            }
+            (_, PamAuthResponse::Success | PamAuthResponse::SuccessWithMessage { .. }) => {
                 // Should never get here "off the rip".
                 debug_assert!(false);
                 return Ok(Some(true));
@@ -1423,8 +1436,8 @@ impl Resolver {
             .pam_account_authenticate_step(&mut auth_session, pam_next_req)
             .await?
         {
-            PamAuthResponse::Success => Ok(Some(true)),
-            PamAuthResponse::Denied => Ok(Some(false)),
+            PamAuthResponse::Success | PamAuthResponse::SuccessWithMessage { .. } => Ok(Some(true)),
+            PamAuthResponse::Denied | PamAuthResponse::DeniedWithMessage { .. } => Ok(Some(false)),
             _ => {
                 // Should not be able to get here, if the user was unknown they should
                 // be out. If it wants more mechanisms, we can't proceed here.